@@ -0,0 +1,155 @@
+//!
+//! Provides the [`ComfortNoise`] generator, which fills the gaps of a stream with low-level noise matched to the stream's background noise.
+//!
+//! With discontinuous transmission the senders stop sending during silence, and extended loss leaves the stream empty too.
+//! Dead silence makes the listeners believe the call has dropped, so the gaps are filled with noise at the level of the sender's background instead (like the comfort noise of RFC 3389).
+//!
+
+use std::time::Duration;
+
+use crate::packet::{AUDIO_LEVEL_LOUDEST, AUDIO_LEVEL_SILENCE};
+
+///
+/// Comfort noise configuration type definition.
+///
+/// Describes how loud the comfort noise may get, and how long it is generated for.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComfortNoiseConfig {
+    /// The loudest comfort noise generated in -dBov, so that the noise estimate can't be picked up from speech.
+    pub max_level: u8,
+
+    /// The longest gap the comfort noise is generated for, as the sender has most likely left after it.
+    pub max_duration: Duration,
+
+    /// The speed the noise estimate follows a louder background at, in dB per second.
+    /// The estimate follows a quieter background right away, so it settles on the quietest parts of the stream between the words.
+    pub rise_rate_db: f32,
+}
+
+impl Default for ComfortNoiseConfig {
+    fn default() -> Self {
+        Self {
+            max_level: 50,
+            max_duration: Duration::from_secs(30),
+            rise_rate_db: 3.,
+        }
+    }
+}
+
+///
+/// Comfort noise generator type definition.
+///
+/// Estimates the background noise of a single stream from its samples, and generates white noise at the estimated level.
+///
+#[derive(Debug, Clone)]
+pub struct ComfortNoise {
+    /// The configuration of the generator.
+    config: ComfortNoiseConfig,
+
+    /// The amount of interleaved samples a second of audio contains.
+    samples_per_second: usize,
+
+    /// The estimated RMS of the background noise, [`None`] until the first samples are observed.
+    noise_rms: Option<f32>,
+
+    /// The amount of samples which have been missing from the stream since the last observed samples.
+    silent_samples: usize,
+
+    /// The state of the xorshift generator of the noise.
+    random_state: u32,
+}
+
+impl ComfortNoise {
+    /// Creates a new [`ComfortNoise`] instance, for a stream with the `sample_rate` and the amount of interleaved `channels`.
+    pub fn new(config: ComfortNoiseConfig, sample_rate: u32, channels: usize) -> Self {
+        Self {
+            config,
+            samples_per_second: sample_rate as usize * channels,
+            noise_rms: None,
+            silent_samples: 0,
+            random_state: 0x9E37_79B9,
+        }
+    }
+
+    /// Returns the estimated level of the background noise in -dBov, [`None`] until the first samples are observed.
+    pub fn noise_level(&self) -> Option<u8> {
+        self.noise_rms.map(|noise_rms| {
+            (-20. * noise_rms.log10()).clamp(AUDIO_LEVEL_LOUDEST as f32, AUDIO_LEVEL_SILENCE as f32)
+                as u8
+        })
+    }
+
+    /// Returns whether the stream is still considered alive, which is until it has been missing for [`ComfortNoiseConfig::max_duration`].
+    pub fn is_active(&self) -> bool {
+        (self.silent_samples as f64)
+            < self.config.max_duration.as_secs_f64() * self.samples_per_second as f64
+    }
+
+    ///
+    /// Updates the noise estimate with the played out `samples` of the stream.
+    ///
+    /// # Behavior
+    /// The estimate falls to the RMS of the samples right away if they are quieter, and rises towards it by [`ComfortNoiseConfig::rise_rate_db`] otherwise.
+    /// Empty `samples` are ignored.
+    ///
+    pub fn observe(&mut self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+
+        self.silent_samples = 0;
+
+        let rms = (samples.iter().map(|sample| sample * sample).sum::<f32>()
+            / samples.len() as f32)
+            .sqrt();
+
+        let duration = samples.len() as f32 / self.samples_per_second as f32;
+        let max_rise = 10_f32.powf(self.config.rise_rate_db * duration / 20.);
+
+        self.noise_rms = Some(match self.noise_rms {
+            Some(noise_rms) if rms > noise_rms => rms.min(noise_rms * max_rise),
+            _ => rms,
+        });
+    }
+
+    ///
+    /// Fills the `output` with comfort noise, as the stream has nothing to play out.
+    ///
+    /// # Behavior
+    /// Returns the amount of samples generated, the `output` is filled with silence if nothing was generated.
+    /// Nothing is generated before the first samples are observed, or once the stream has been missing for [`ComfortNoiseConfig::max_duration`].
+    /// The noise is never louder than [`ComfortNoiseConfig::max_level`].
+    ///
+    pub fn generate(&mut self, output: &mut [f32]) -> usize {
+        output.fill(0.);
+
+        let is_active = self.is_active();
+
+        self.silent_samples = self.silent_samples.saturating_add(output.len());
+
+        let Some(noise_rms) = self.noise_rms.filter(|_| is_active) else {
+            return 0;
+        };
+
+        let max_rms = 10_f32.powf(-(self.config.max_level as f32) / 20.);
+
+        //Uniform noise between -1 and 1 has an RMS of 1/sqrt(3)
+        let amplitude = noise_rms.min(max_rms) * 3_f32.sqrt();
+
+        for sample in output.iter_mut() {
+            *sample = self.next_random() * amplitude;
+        }
+
+        output.len()
+    }
+
+    /// Returns the next uniformly distributed random number between -1 and 1.
+    fn next_random(&mut self) -> f32 {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 17;
+        self.random_state ^= self.random_state << 5;
+
+        self.random_state as f32 / u32::MAX as f32 * 2. - 1.
+    }
+}
@@ -0,0 +1,357 @@
+//!
+//! Provides the [`JitterBuffer`], which turns the decoded frames of a stream (arriving with jitter, out of order, or not at all) into a steady stream of samples.
+//!
+//! The jitter buffer holds back the playout until enough audio is buffered to absorb the jitter of the network, then hands out the samples on demand, in the order of the sequence numbers of the frames.
+//! This lets audio engines with their own clock pull the samples whenever their callback runs.
+//!
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::Duration,
+};
+
+/// The extended sequence number of the first frame, which leaves room for the frames arriving earlier than it.
+const INITIAL_EXTENDED_SEQUENCE: u64 = 1 << 32;
+
+///
+/// Jitter buffer configuration type definition.
+///
+/// Describes how much audio the [`JitterBuffer`] holds back.
+/// Lower delays lower the latency (for example for music or gaming), higher delays absorb more jitter without underruns.
+///
+#[derive(Debug, Clone)]
+pub struct JitterConfig {
+    /// The lowest target delay, the target delay is never set below it.
+    pub min_delay: Duration,
+
+    /// The amount of audio buffered before the playout starts (or restarts after an underrun).
+    /// It is clamped between the minimum and the maximum delay.
+    pub target_delay: Duration,
+
+    /// The highest amount of audio buffered, the oldest samples are discarded beyond it so the latency doesn't grow indefinitely.
+    pub max_delay: Duration,
+}
+
+impl Default for JitterConfig {
+    fn default() -> Self {
+        Self {
+            min_delay: Duration::from_millis(20),
+            target_delay: Duration::from_millis(60),
+            max_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl JitterConfig {
+    /// Returns the `target_delay` clamped between the minimum and the maximum delay.
+    pub fn clamp_delay(&self, target_delay: Duration) -> Duration {
+        target_delay.max(self.min_delay).min(self.max_delay)
+    }
+}
+
+///
+/// Jitter buffer statistics type definition.
+///
+/// Describes how well the [`JitterBuffer`] has absorbed the jitter of the network, so that the applications can warn their users about an unstable network.
+/// The counters are cumulative since the jitter buffer was created.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JitterStats {
+    /// The duration of the audio waiting to be played out.
+    pub buffered: Duration,
+
+    /// The amount of frames which were concealed, either by the decoder (see [`JitterBuffer::push_concealed`]) or by skipping them as they were lost.
+    pub concealed_frames: u64,
+
+    /// The amount of frames which were discarded, as they arrived after a later frame was already played out.
+    pub late_frames: u64,
+
+    /// The amount of frames whose samples were (entirely or partly) discarded to catch up with the maximum delay, which speeds up the playout.
+    pub accelerations: u64,
+
+    /// The amount of pulls which were filled with silence while the target delay was being buffered, which holds back the playout.
+    pub decelerations: u64,
+
+    /// The amount of times the playout has run dry, and started buffering again.
+    pub underruns: u64,
+
+    /// The amount of times more than the maximum delay was buffered, and the oldest samples were discarded.
+    pub overruns: u64,
+}
+
+impl JitterStats {
+    /// Adds the counters of the `other` statistics to these, keeping the longer of the buffered durations.
+    pub fn accumulate(&mut self, other: &JitterStats) {
+        self.buffered = self.buffered.max(other.buffered);
+        self.concealed_frames += other.concealed_frames;
+        self.late_frames += other.late_frames;
+        self.accelerations += other.accelerations;
+        self.decelerations += other.decelerations;
+        self.underruns += other.underruns;
+        self.overruns += other.overruns;
+    }
+}
+
+///
+/// Jitter buffer type definition.
+///
+/// Reorders the decoded frames of a single stream by their sequence numbers, and plays them out once the target delay is buffered.
+///
+#[derive(Debug, Clone)]
+pub struct JitterBuffer {
+    /// The configuration of the jitter buffer.
+    config: JitterConfig,
+
+    /// The amount of interleaved samples a second of audio contains.
+    samples_per_second: usize,
+
+    /// The frames waiting to be played out, by their extended (non-wrapping) sequence numbers.
+    frames: BTreeMap<u64, Vec<f32>>,
+
+    /// The extended sequence number of the next frame to be played out, and the sequence number it was extended from.
+    next_sequence: Option<(u64, u32)>,
+
+    /// The samples of the frames already taken from `frames`, which haven't been played out yet.
+    playout: VecDeque<f32>,
+
+    /// Whether the playout waits for the target delay to be buffered.
+    is_buffering: bool,
+
+    /// Whether any frame was played out, earlier frames are only accepted until then.
+    has_played: bool,
+
+    /// Whether the stream was ended, the rest of the buffered audio is played out without waiting for the target delay.
+    is_ended: bool,
+
+    /// The counters of the statistics of the jitter buffer.
+    stats: JitterStats,
+}
+
+impl JitterBuffer {
+    /// Creates a new [`JitterBuffer`] instance, for a stream with the `sample_rate` and the amount of interleaved `channels`.
+    pub fn new(mut config: JitterConfig, sample_rate: u32, channels: usize) -> Self {
+        config.target_delay = config.clamp_delay(config.target_delay);
+
+        Self {
+            config,
+            samples_per_second: sample_rate as usize * channels,
+            frames: BTreeMap::new(),
+            next_sequence: None,
+            playout: VecDeque::new(),
+            is_buffering: true,
+            has_played: false,
+            is_ended: false,
+            stats: JitterStats::default(),
+        }
+    }
+
+    /// Returns the [`JitterStats`] of the jitter buffer, including the duration of the audio currently buffered.
+    pub fn stats(&self) -> JitterStats {
+        JitterStats {
+            buffered: self.buffered_duration(),
+            ..self.stats
+        }
+    }
+
+    /// Returns the amount of buffered interleaved samples.
+    pub fn buffered_samples(&self) -> usize {
+        self.playout.len() + self.frames.values().map(Vec::len).sum::<usize>()
+    }
+
+    /// Returns the duration of the buffered audio.
+    pub fn buffered_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.buffered_samples() as f64 / self.samples_per_second as f64)
+    }
+
+    /// Returns the delay the playout is currently applying, which is the duration of the audio waiting to be played out.
+    pub fn playout_delay(&self) -> Duration {
+        self.buffered_duration()
+    }
+
+    /// Returns the amount of audio buffered before the playout starts (or restarts after an underrun).
+    pub fn target_delay(&self) -> Duration {
+        self.config.target_delay
+    }
+
+    /// Sets the amount of audio buffered before the playout starts (or restarts after an underrun), returning the target delay after it was clamped between the minimum and the maximum delay.
+    /// A lower target only lowers the latency after the next underrun, as the buffered audio isn't discarded.
+    pub fn set_target_delay(&mut self, target_delay: Duration) -> Duration {
+        self.config.target_delay = self.config.clamp_delay(target_delay);
+
+        self.config.target_delay
+    }
+
+    /// Returns whether the jitter buffer has nothing left to play out.
+    pub fn is_empty(&self) -> bool {
+        self.buffered_samples() == 0
+    }
+
+    /// Returns whether the stream was [ended](JitterBuffer::end), and no frames were pushed since.
+    pub fn is_ended(&self) -> bool {
+        self.is_ended
+    }
+
+    /// Marks the stream ended, the rest of the buffered audio is played out right away, and running dry isn't counted as an underrun.
+    pub fn end(&mut self) {
+        self.is_ended = true;
+        self.is_buffering = false;
+    }
+
+    ///
+    /// Pushes the decoded `samples` of the frame with the `sequence` number into the jitter buffer.
+    ///
+    /// # Behavior
+    /// Frames without a sequence number are played out in the order they were pushed in.
+    /// Frames arriving after a later frame was already played out are discarded, as they are too late to be played.
+    /// If more than the maximum delay is buffered, the oldest samples are discarded.
+    /// Pushing a frame into an [ended](JitterBuffer::end) jitter buffer resumes the stream.
+    ///
+    pub fn push(&mut self, sequence: Option<u32>, samples: Vec<f32>) {
+        let extended_sequence = match (sequence, self.next_sequence) {
+            (Some(sequence), Some((next_extended, next_sequence))) => {
+                //The sequence numbers wrap around, so compare them by their distance
+                let distance = sequence.wrapping_sub(next_sequence) as i32;
+
+                if distance >= 0 {
+                    next_extended + distance as u64
+                } else if !self.has_played {
+                    //The frame was overtaken by the first frame, but the playout hasn't started yet
+                    let extended_sequence =
+                        next_extended.saturating_sub(distance.unsigned_abs() as u64);
+
+                    self.next_sequence = Some((extended_sequence, sequence));
+
+                    extended_sequence
+                } else {
+                    self.stats.late_frames += 1;
+
+                    return;
+                }
+            }
+            (Some(sequence), None) => {
+                self.next_sequence = Some((INITIAL_EXTENDED_SEQUENCE, sequence));
+
+                INITIAL_EXTENDED_SEQUENCE
+            }
+            (None, _) => {
+                let extended_sequence = self
+                    .frames
+                    .keys()
+                    .next_back()
+                    .map(|last| last + 1)
+                    .or(self.next_sequence.map(|(next_extended, _)| next_extended))
+                    .unwrap_or(INITIAL_EXTENDED_SEQUENCE);
+
+                if self.next_sequence.is_none() {
+                    self.next_sequence = Some((extended_sequence, 0));
+                }
+
+                extended_sequence
+            }
+        };
+
+        self.frames.insert(extended_sequence, samples);
+        self.is_ended = false;
+
+        //Catch up if the buffer has grown beyond the maximum delay
+        let max_samples =
+            (self.config.max_delay.as_secs_f64() * self.samples_per_second as f64) as usize;
+        let excess = self.buffered_samples().saturating_sub(max_samples);
+
+        if excess > 0 {
+            self.stats.overruns += 1;
+
+            self.discard(excess);
+        }
+    }
+
+    /// Pushes the `samples` the decoder has generated in place of the lost frame with the `sequence` number, counting it as a concealed frame (see [`JitterBuffer::push`]).
+    pub fn push_concealed(&mut self, sequence: Option<u32>, samples: Vec<f32>) {
+        self.stats.concealed_frames += 1;
+
+        self.push(sequence, samples);
+    }
+
+    ///
+    /// Fills the `output` with the next samples of the stream.
+    ///
+    /// # Behavior
+    /// Returns the amount of samples played out, the rest of the `output` is filled with silence.
+    /// Nothing is played out until the target delay is buffered, and the buffer starts buffering again after it has run dry (underrun).
+    /// Lost frames are skipped.
+    ///
+    pub fn pull(&mut self, output: &mut [f32]) -> usize {
+        output.fill(0.);
+
+        if self.is_buffering {
+            if self.buffered_duration() < self.config.target_delay {
+                if !output.is_empty() {
+                    self.stats.decelerations += 1;
+                }
+
+                return 0;
+            }
+
+            self.is_buffering = false;
+        }
+
+        while self.playout.len() < output.len() && self.take_next_frame() {}
+
+        let sample_count = output.len().min(self.playout.len());
+
+        for (output_sample, sample) in output.iter_mut().zip(self.playout.drain(..sample_count)) {
+            *output_sample = sample;
+        }
+
+        //An ended stream running dry isn't an underrun, as nothing is missing
+        if sample_count < output.len() {
+            self.is_buffering = true;
+
+            if !self.is_ended {
+                self.stats.underruns += 1;
+            }
+        }
+
+        sample_count
+    }
+
+    /// Moves the next frame (in sequence order) into the playout queue, skipping the lost frames.
+    /// Returns `false` if there are no frames left.
+    fn take_next_frame(&mut self) -> bool {
+        let Some((extended_sequence, samples)) = self.frames.pop_first() else {
+            return false;
+        };
+
+        let next_sequence = self
+            .next_sequence
+            .map(|(next_extended, next_sequence)| {
+                //The frames between the next expected one and this one were lost
+                self.stats.concealed_frames += extended_sequence.saturating_sub(next_extended);
+
+                next_sequence.wrapping_add((extended_sequence + 1 - next_extended) as u32)
+            })
+            .unwrap_or_default();
+
+        self.next_sequence = Some((extended_sequence + 1, next_sequence));
+        self.playout.extend(samples);
+        self.has_played = true;
+
+        true
+    }
+
+    /// Discards the oldest `sample_count` buffered samples.
+    fn discard(&mut self, mut sample_count: usize) {
+        while sample_count > 0 {
+            if self.playout.is_empty() && !self.take_next_frame() {
+                return;
+            }
+
+            let discarded = sample_count.min(self.playout.len());
+
+            self.playout.drain(..discarded);
+            self.stats.accelerations += 1;
+
+            sample_count -= discarded;
+        }
+    }
+}
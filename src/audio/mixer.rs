@@ -0,0 +1,383 @@
+//!
+//! Provides the [`Mixer`], which mixes the decoded streams of every participant into a single stream of samples.
+//!
+//! The mixer can optionally duck (attenuate) the other streams while a prioritized stream is active, which is useful for dispatch and intercom deployments, where a dispatcher or an announcement has to be heard over everyone else.
+//!
+//! The mixer can also spatialize the streams which have a [`Position`] (see [`VoipHeader::position`](crate::packet::VoipHeader::position)), so game and VR integrations get positional voice out of the box.
+//! Spatialization attenuates the streams by their distance from the listener, and pans stereo output by their direction.
+//!
+
+use std::{
+    collections::{HashMap, VecDeque},
+    f32::consts::FRAC_PI_4,
+    time::Duration,
+};
+
+use uuid::Uuid;
+
+use crate::packet::Position;
+
+///
+/// Ducking configuration type definition.
+///
+/// Describes how much, and for how long, the [`Mixer`] attenuates the other streams while a prioritized stream is active.
+///
+#[derive(Debug, Clone)]
+pub struct DuckingConfig {
+    /// The attenuation (in dB) applied to the other streams.
+    pub attenuation_db: f32,
+
+    /// The RMS level (from `0.0` to `1.0`) from which a prioritized stream counts as active.
+    pub activity_threshold: f32,
+
+    /// The duration the other streams stay attenuated for after the prioritized streams have fallen silent.
+    /// This prevents the other streams from swelling up in the short pauses of the prioritized speaker.
+    pub release: Duration,
+}
+
+impl Default for DuckingConfig {
+    fn default() -> Self {
+        Self {
+            attenuation_db: 12.,
+            activity_threshold: 0.01,
+            release: Duration::from_millis(500),
+        }
+    }
+}
+
+///
+/// Spatialization configuration type definition.
+///
+/// Describes how the [`Mixer`] attenuates the streams by their distance from the listener.
+/// The attenuation follows the inverse distance model: streams within the reference distance are not attenuated, and streams beyond the maximum distance are not attenuated any further.
+///
+#[derive(Debug, Clone)]
+pub struct SpatialConfig {
+    /// The distance (in metres) within which the streams are not attenuated.
+    pub reference_distance: f32,
+
+    /// The rate the streams are attenuated with as they move away from the listener.
+    pub rolloff: f32,
+
+    /// The distance (in metres) beyond which the streams are not attenuated any further.
+    pub max_distance: f32,
+}
+
+impl Default for SpatialConfig {
+    fn default() -> Self {
+        Self {
+            reference_distance: 1.,
+            rolloff: 1.,
+            max_distance: 50.,
+        }
+    }
+}
+
+/// A single stream of the [`Mixer`].
+#[derive(Debug, Clone)]
+struct MixerStream {
+    /// The samples waiting to be mixed.
+    samples: VecDeque<f32>,
+
+    /// The linear gain the stream is mixed with.
+    gain: f32,
+
+    /// Whether the stream ducks the other streams while it is active.
+    is_prioritized: bool,
+
+    /// The [`Position`] the stream is spatialized at.
+    position: Option<Position>,
+}
+
+impl Default for MixerStream {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::new(),
+            gain: 1.,
+            is_prioritized: false,
+            position: None,
+        }
+    }
+}
+
+/// The samples a [`MixerStream`] contributes to a single [`Mixer::mix`] call.
+#[derive(Debug, Clone)]
+struct MixerChunk {
+    /// The samples of the stream.
+    samples: Vec<f32>,
+
+    /// The linear gain the stream is mixed with.
+    gain: f32,
+
+    /// Whether the stream ducks the other streams while it is active.
+    is_prioritized: bool,
+
+    /// The [`Position`] the stream is spatialized at.
+    position: Option<Position>,
+}
+
+///
+/// Mixer type definition.
+///
+/// Buffers the decoded samples of every author, and mixes them when the playback requests the next samples.
+///
+#[derive(Debug, Clone)]
+pub struct Mixer {
+    /// The sample rate of every stream.
+    sample_rate: u32,
+
+    /// The amount of interleaved channels of every stream.
+    channels: usize,
+
+    /// The stream of every author.
+    streams: HashMap<Uuid, MixerStream>,
+
+    /// The ducking configuration, ducking is disabled if this is [`None`].
+    ducking: Option<DuckingConfig>,
+
+    /// The gain currently applied to the ducked streams.
+    ducking_gain: f32,
+
+    /// The amount of samples the ducked streams stay attenuated for.
+    ducking_hold: usize,
+
+    /// The spatialization configuration, spatialization is disabled if this is [`None`].
+    spatialization: Option<SpatialConfig>,
+
+    /// The [`Position`] of the listener.
+    listener_position: Position,
+
+    /// The direction the listener is facing, in radians clockwise from the `z` (forward) axis when looking down.
+    listener_yaw: f32,
+}
+
+impl Mixer {
+    /// Creates a new [`Mixer`] instance, mixing streams with the `sample_rate` and the amount of interleaved `channels`.
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            streams: HashMap::new(),
+            ducking: None,
+            ducking_gain: 1.,
+            ducking_hold: 0,
+            spatialization: None,
+            listener_position: Position::default(),
+            listener_yaw: 0.,
+        }
+    }
+
+    /// Returns the sample rate of every stream.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the amount of interleaved channels of every stream.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Sets the [`DuckingConfig`], or disables ducking if it is [`None`].
+    pub fn set_ducking(&mut self, ducking: Option<DuckingConfig>) {
+        self.ducking = ducking;
+    }
+
+    /// Sets the [`SpatialConfig`], or disables spatialization if it is [`None`].
+    pub fn set_spatialization(&mut self, spatialization: Option<SpatialConfig>) {
+        self.spatialization = spatialization;
+    }
+
+    /// Sets the [`Position`] of the listener, and the direction (`yaw`) it is facing in radians clockwise from the `z` (forward) axis.
+    pub fn set_listener(&mut self, position: Position, yaw: f32) {
+        self.listener_position = position;
+        self.listener_yaw = yaw;
+    }
+
+    /// Sets the [`Position`] the stream of the `author` is spatialized at, or [`None`] if it shouldn't be spatialized.
+    /// This is usually the [position](crate::packet::VoipHeader::position) of the author's latest voice message.
+    pub fn set_position(&mut self, author: Uuid, position: Option<Position>) {
+        self.streams.entry(author).or_default().position = position;
+    }
+
+    /// Sets whether the stream of the `author` ducks the other streams while it is active (for example a dispatcher or an announcement).
+    pub fn set_prioritized(&mut self, author: Uuid, is_prioritized: bool) {
+        self.streams.entry(author).or_default().is_prioritized = is_prioritized;
+    }
+
+    /// Sets the linear gain the stream of the `author` is mixed with.
+    pub fn set_gain(&mut self, author: Uuid, gain: f32) {
+        self.streams.entry(author).or_default().gain = gain;
+    }
+
+    /// Queues the decoded interleaved `samples` of the `author`.
+    pub fn push_samples(&mut self, author: Uuid, samples: &[f32]) {
+        self.streams
+            .entry(author)
+            .or_default()
+            .samples
+            .extend(samples);
+    }
+
+    /// Removes the stream of the `author` (for example when the author has left the session).
+    pub fn remove_stream(&mut self, author: Uuid) {
+        self.streams.remove(&author);
+    }
+
+    ///
+    /// Mixes the next samples of every stream into the `output`.
+    ///
+    /// # Behavior
+    /// Every stream contributes the same amount of samples as the length of the `output`, streams which dont have enough samples queued are padded with silence.
+    /// If ducking is enabled, the streams which aren't prioritized are attenuated while any of the prioritized streams is active.
+    /// The attenuation is ramped over the `output`, so that ducking doesn't produce clicks.
+    /// The mixed samples are clamped to the `-1.0..=1.0` range.
+    ///
+    pub fn mix(&mut self, output: &mut [f32]) {
+        output.fill(0.);
+
+        let chunks: Vec<MixerChunk> = self
+            .streams
+            .values_mut()
+            .map(|stream| {
+                let sample_count = output.len().min(stream.samples.len());
+
+                MixerChunk {
+                    samples: stream.samples.drain(..sample_count).collect(),
+                    gain: stream.gain,
+                    is_prioritized: stream.is_prioritized,
+                    position: stream.position,
+                }
+            })
+            .collect();
+
+        let sample_count = output.len();
+        let target_gain = self.update_ducking(&chunks, sample_count);
+        let start_gain = self.ducking_gain;
+
+        self.ducking_gain = target_gain;
+
+        for chunk in chunks {
+            let samples = match chunk.position {
+                Some(position) => self.spatialize(chunk.samples, position),
+                None => chunk.samples,
+            };
+
+            for (index, (output_sample, sample)) in output.iter_mut().zip(samples).enumerate() {
+                let ducking_gain = if chunk.is_prioritized {
+                    1.
+                } else {
+                    //Ramp the attenuation linearly over the buffer
+                    start_gain
+                        + (target_gain - start_gain) * (index + 1) as f32 / sample_count as f32
+                };
+
+                *output_sample += sample * chunk.gain * ducking_gain;
+            }
+        }
+
+        for output_sample in output.iter_mut() {
+            *output_sample = output_sample.clamp(-1., 1.);
+        }
+    }
+
+    /// Updates the ducking state with the next `chunks` of the streams, and returns the gain the ducked streams should reach by the end of the `sample_count` samples.
+    fn update_ducking(&mut self, chunks: &[MixerChunk], sample_count: usize) -> f32 {
+        let Some(ducking) = &self.ducking else {
+            return 1.;
+        };
+
+        let is_priority_active = chunks
+            .iter()
+            .filter(|chunk| chunk.is_prioritized)
+            .any(|chunk| rms(&chunk.samples) >= ducking.activity_threshold);
+
+        if is_priority_active {
+            self.ducking_hold = (ducking.release.as_secs_f32()
+                * self.sample_rate as f32
+                * self.channels as f32) as usize;
+        } else if self.ducking_hold > 0 {
+            self.ducking_hold = self.ducking_hold.saturating_sub(sample_count);
+        } else {
+            return 1.;
+        }
+
+        10_f32.powf(-ducking.attenuation_db / 20.)
+    }
+
+    ///
+    /// Spatializes the `samples` of a stream at the `position`.
+    ///
+    /// # Behavior
+    /// The samples are attenuated by the distance of the `position` from the listener.
+    /// Stereo output is also downmixed and panned (with constant power) by the direction of the `position`, other channel layouts are only attenuated.
+    /// The samples are returned unchanged if spatialization is disabled.
+    ///
+    fn spatialize(&self, mut samples: Vec<f32>, position: Position) -> Vec<f32> {
+        let Some(spatialization) = &self.spatialization else {
+            return samples;
+        };
+
+        let [source_x, source_y, source_z] = position.to_meters();
+        let [listener_x, listener_y, listener_z] = self.listener_position.to_meters();
+        let (dx, dy, dz) = (
+            source_x - listener_x,
+            source_y - listener_y,
+            source_z - listener_z,
+        );
+
+        let distance = (dx * dx + dy * dy + dz * dz).sqrt();
+
+        //Inverse distance attenuation, clamped to the reference and the maximum distance
+        let clamped_distance = distance.clamp(
+            spatialization.reference_distance,
+            spatialization
+                .max_distance
+                .max(spatialization.reference_distance),
+        );
+        let distance_gain = spatialization.reference_distance
+            / (spatialization.reference_distance
+                + spatialization.rolloff * (clamped_distance - spatialization.reference_distance));
+
+        if self.channels != 2 {
+            samples
+                .iter_mut()
+                .for_each(|sample| *sample *= distance_gain);
+
+            return samples;
+        }
+
+        //Project the direction of the source onto the axes of the listener
+        let (sin_yaw, cos_yaw) = self.listener_yaw.sin_cos();
+        let right = dx * cos_yaw - dz * sin_yaw;
+        let forward = dx * sin_yaw + dz * cos_yaw;
+
+        let pan = if right == 0. && forward == 0. {
+            0.
+        } else {
+            right.atan2(forward).sin()
+        };
+
+        let (right_gain, left_gain) = ((pan + 1.) * FRAC_PI_4).sin_cos();
+
+        for frame in samples.chunks_mut(2) {
+            let mono = frame.iter().sum::<f32>() / 2.;
+
+            frame[0] = mono * left_gain * distance_gain;
+
+            if let Some(right_sample) = frame.get_mut(1) {
+                *right_sample = mono * right_gain * distance_gain;
+            }
+        }
+
+        samples
+    }
+}
+
+/// Calculates the RMS level of the `samples`.
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.;
+    }
+
+    (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+}
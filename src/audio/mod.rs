@@ -0,0 +1,11 @@
+//!
+//! Provides the building blocks of the playback side of a voip application, which work on the decoded samples of the received streams.
+//!
+//! The samples are interleaved `f32`s, like the ones produced by the [opus](https://opus-codec.org/) decoder.
+//!
+
+pub mod comfort_noise;
+pub mod jitter;
+pub mod mixer;
+pub mod pipeline;
+pub mod soundboard;
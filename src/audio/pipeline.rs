@@ -0,0 +1,84 @@
+//!
+//! Provides the [`AudioStage`] trait and the [`AudioPipeline`], which chain custom signal processing into the capture and the playback side of the [`Client`](crate::udp::client::Client).
+//!
+//! The capture pipeline processes the samples before they are encoded (for example denoise → AGC → VAD), and the playback pipeline processes the mixed samples before they are handed to the audio output (for example EQ → limiter).
+//! Any closure taking the samples is an [`AudioStage`], and pipelines are stages themselves, so chains can be nested.
+//!
+
+use std::fmt;
+
+///
+/// Audio stage trait definition.
+///
+/// Processes the interleaved samples flowing through an [`AudioPipeline`] in place.
+///
+pub trait AudioStage: Send + 'static {
+    /// Processes the interleaved `samples` in place.
+    /// The stage is called with every chunk of the stream in order, the length of the chunks depends on the caller (for example the size of the buffers of the audio engine).
+    fn process(&mut self, samples: &mut [f32]);
+}
+
+impl<F> AudioStage for F
+where
+    F: FnMut(&mut [f32]) + Send + 'static,
+{
+    fn process(&mut self, samples: &mut [f32]) {
+        self(samples)
+    }
+}
+
+///
+/// Audio pipeline type definition.
+///
+/// Runs the samples through its [`AudioStage`]s, in the order they were added.
+///
+#[derive(Default)]
+pub struct AudioPipeline {
+    /// The stages of the pipeline, in processing order.
+    stages: Vec<Box<dyn AudioStage>>,
+}
+
+impl fmt::Debug for AudioPipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AudioPipeline")
+            .field("stages", &self.stages.len())
+            .finish()
+    }
+}
+
+impl AudioPipeline {
+    /// Creates a new [`AudioPipeline`] instance without any stages, which leaves the samples untouched.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the pipeline with the `stage` appended to its end, so pipelines can be built in a single expression.
+    pub fn with_stage(mut self, stage: impl AudioStage) -> Self {
+        self.push_stage(stage);
+
+        self
+    }
+
+    /// Appends the `stage` to the end of the pipeline.
+    pub fn push_stage(&mut self, stage: impl AudioStage) {
+        self.stages.push(Box::new(stage));
+    }
+
+    /// Returns the amount of stages in the pipeline.
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Returns whether the pipeline has no stages.
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl AudioStage for AudioPipeline {
+    fn process(&mut self, samples: &mut [f32]) {
+        for stage in &mut self.stages {
+            stage.process(samples);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//!
+//! Provides the [`SoundBoard`], an auxiliary mixer input for local sounds like the notifications of the user interface, or the clips of a soundboard.
+//!
+//! The [`Client`](crate::udp::client::Client) has a sound board mixed into the playback, and another one mixed into the sent voice (see [`SoundTarget`]), each with its own gain.
+//! The clips which overlap are summed, so a notification doesn't wait for a longer clip to finish.
+//!
+
+use std::{collections::VecDeque, time::Duration};
+
+///
+/// Sound target type definition.
+///
+/// A bitset of the sound boards a clip is played on by [`Client::play_sound`](crate::udp::client::Client::play_sound).
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoundTarget(u8);
+
+impl SoundTarget {
+    /// The sound is played on none of the sound boards.
+    pub const NONE: Self = Self(0);
+
+    /// The sound is played out locally, mixed into the received voice.
+    pub const PLAYBACK: Self = Self(1);
+
+    /// The sound is sent to the other participants, mixed into the captured voice.
+    pub const OUTGOING: Self = Self(1 << 1);
+
+    /// Returns whether every target of `other` is set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for SoundTarget {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+///
+/// Sound board type definition.
+///
+/// Queues the clips played on it, and mixes them into a stream of samples with its gain.
+///
+#[derive(Debug, Clone)]
+pub struct SoundBoard {
+    /// The sample rate of the clips.
+    sample_rate: u32,
+
+    /// The amount of interleaved channels of the clips.
+    channels: usize,
+
+    /// The linear gain the clips are mixed in with.
+    gain: f32,
+
+    /// The samples of the clips waiting to be mixed in, the overlapping clips are already summed.
+    samples: VecDeque<f32>,
+}
+
+impl SoundBoard {
+    /// Creates a new [`SoundBoard`] instance, whose clips have the `sample_rate` and the `channels`.
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            sample_rate,
+            channels: channels.max(1),
+            gain: 1.,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Returns the linear gain the clips are mixed in with.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Sets the linear gain the clips are mixed in with, including the clips already playing.
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain.max(0.);
+    }
+
+    /// Plays the interleaved `samples` of a clip, on top of the clips already playing.
+    /// The samples have to match the sample rate and the channels of the sound board.
+    pub fn play(&mut self, samples: &[f32]) {
+        let overlap = samples.len().min(self.samples.len());
+
+        for (queued_sample, sample) in self.samples.iter_mut().zip(&samples[..overlap]) {
+            *queued_sample += sample;
+        }
+
+        self.samples.extend(&samples[overlap..]);
+    }
+
+    /// Stops every clip playing.
+    pub fn stop(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Returns whether a clip is playing.
+    pub fn is_playing(&self) -> bool {
+        !self.samples.is_empty()
+    }
+
+    /// Returns the duration left from the longest clip playing.
+    pub fn remaining(&self) -> Duration {
+        Duration::from_secs_f64(
+            (self.samples.len() / self.channels) as f64 / self.sample_rate.max(1) as f64,
+        )
+    }
+
+    /// Mixes the next samples of the clips into the `frames` with the gain, clamping the mixed samples.
+    /// The `frames` are left untouched after the clips have ended.
+    pub fn mix_into(&mut self, frames: &mut [f32]) {
+        let sample_count = frames.len().min(self.samples.len());
+
+        for (frame, sample) in frames.iter_mut().zip(self.samples.drain(..sample_count)) {
+            *frame = (*frame + sample * self.gain).clamp(-1., 1.);
+        }
+    }
+}
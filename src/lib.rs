@@ -1,3 +1,4 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![warn(
     missing_debug_implementations,
     missing_docs,
@@ -19,6 +20,11 @@
 //!
 //! ***The crate uses [UDP](https://en.wikipedia.org/wiki/User_Datagram_Protocol) for it's real time communication, which does not mitigate against packet loss.***
 //!
+//! The [`packet`] module only depends on `core` and `alloc`, so the protocol can be used on embedded targets by disabling the default `std` feature (and enabling the `postcard` codec).
+//! The [tokio](https://crates.io/crates/tokio) based client and server are only available with the `std` feature.
+//!
+
+extern crate alloc;
 
 /// Maximum Transmission Unit size.
 /// This is a limit of the packet length the client can send, so that messages wont get fragmented.
@@ -27,12 +33,18 @@ pub const MTU_MAX_PACKET_SIZE: usize = 1300;
 #[cfg(feature = "udp")]
 pub mod udp;
 
-#[cfg(any(feature = "voice", feature = "video"))]
+#[cfg(feature = "std")]
+pub mod audio;
+
 pub mod packet;
 
+#[cfg(feature = "testing")]
+pub mod testing;
+
 #[doc(hidden)]
 #[cfg(test)]
 pub mod tests;
 
 /// Re-export all of the functionalities depending on the features this crate has enabled.
-pub use silence_core;
\ No newline at end of file
+#[cfg(any(feature = "voice", feature = "video"))]
+pub use silence_core;
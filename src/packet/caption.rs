@@ -0,0 +1,109 @@
+//!
+//! Provides the [`Caption`] type, which carries a live caption (the transcript of the voice of a speaker) to the participants of a session.
+//!
+//! Captions are sent as [`VoipMessageType::CaptionMessage`], the UTF-8 encoded text is the body of the message and the [`CaptionInfo`] is stored in the header.
+//! The start and the end of a caption are media times of the captioned author's stream (like [`VoipHeader::timestamp`]), so the receivers can show the caption in sync with the voice it transcribes.
+//! Transcription engines usually send interim captions while the author is speaking, which are replaced by the next caption with the same start, until the final one.
+//!
+
+use alloc::string::String;
+use core::time::Duration;
+
+use uuid::Uuid;
+
+use super::{frame::duration_to_micros, VoipHeader, VoipMessageType};
+
+///
+/// Caption info type definition.
+///
+/// The metadata of a caption, which is stored in the header of a [`VoipMessageType::CaptionMessage`].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CaptionInfo {
+    /// The author of the captioned voice, which isn't necessarily the sender of the caption (for example a transcription service).
+    pub author: Uuid,
+
+    /// The media time the captioned voice starts at, in microseconds since the start of the author's stream.
+    pub start: u64,
+
+    /// The media time the captioned voice ends at, in microseconds since the start of the author's stream.
+    pub end: u64,
+
+    /// Whether the caption is final, the interim captions are replaced by the next caption with the same start.
+    pub is_final: bool,
+}
+
+///
+/// Caption type definition.
+///
+/// A live caption, and the span of the voice it transcribes.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caption {
+    /// The author of the captioned voice.
+    pub author: Uuid,
+
+    /// The channel (or room) the caption belongs to.
+    pub channel: u32,
+
+    /// The transcribed text.
+    pub text: String,
+
+    /// The media time the captioned voice starts at, since the start of the author's stream.
+    pub start: Duration,
+
+    /// The media time the captioned voice ends at, since the start of the author's stream.
+    pub end: Duration,
+
+    /// Whether the caption is final, the interim captions are replaced by the next caption with the same start.
+    pub is_final: bool,
+}
+
+impl Caption {
+    /// Creates a new [`Caption`] instance of the voice of the `author` between the `start` and the `end`, on the channel `0`.
+    pub fn new(author: Uuid, text: String, start: Duration, end: Duration, is_final: bool) -> Self {
+        Self {
+            author,
+            channel: 0,
+            text,
+            start,
+            end,
+            is_final,
+        }
+    }
+
+    /// Creates a [`Caption`] from the [`VoipHeader`] and the [`CaptionInfo`] of a received caption message, and its decoded `text`.
+    pub fn from_message(
+        voip_header: &VoipHeader,
+        caption_info: &CaptionInfo,
+        text: String,
+    ) -> Self {
+        Self {
+            author: caption_info.author,
+            channel: voip_header.channel(),
+            text,
+            start: Duration::from_micros(caption_info.start),
+            end: Duration::from_micros(caption_info.end),
+            is_final: caption_info.is_final,
+        }
+    }
+
+    /// Returns the [`CaptionInfo`] stored in the header of the caption message.
+    pub fn info(&self) -> CaptionInfo {
+        CaptionInfo {
+            author: self.author,
+            start: duration_to_micros(self.start),
+            end: duration_to_micros(self.end),
+            is_final: self.is_final,
+        }
+    }
+
+    /// Creates the [`VoipHeader`] of the caption message sent by the `sender`, the body of the message is the UTF-8 encoded [`Caption::text`].
+    pub fn to_header(&self, sender: Uuid) -> VoipHeader {
+        VoipHeader::new(
+            VoipMessageType::CaptionMessage(self.text.len() as u64, self.info()),
+            sender,
+        )
+        .with_channel(self.channel)
+    }
+}
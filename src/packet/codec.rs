@@ -0,0 +1,151 @@
+//!
+//! Provides the [`HeaderCodec`] abstraction, which defines the serialization format of the [`VoipHeader`].
+//!
+//! The crate ships with three implementations, each behind its own feature:
+//! * [`MessagePack`] (`msgpack` feature, enabled by default)
+//! * [`Bincode`] (`bincode` feature)
+//! * [`Postcard`] (`postcard` feature), which produces the smallest headers.
+//!
+//! The codec used by the [`Client`](crate::udp::client::Client) and the [`Server`](crate::udp::server::Server) is [`DefaultCodec`], which is selected by the enabled features.
+//! If more than one of them is enabled, [`Postcard`] takes precedence over [`Bincode`], which takes precedence over [`MessagePack`].
+//!
+//! ***Every peer of a deployment must be built with the same codec, as the formats are not compatible with each other.***
+//!
+//! The decoders only ever see the first [`MAX_HEADER_SIZE`] bytes of a message, and the built in ones refuse to nest deeper than [`MAX_HEADER_DEPTH`], so a malformed header can't make them allocate or recurse unboundedly.
+//!
+
+use alloc::{boxed::Box, vec::Vec};
+
+use super::{VoipHeader, MAX_HEADER_SIZE};
+
+#[cfg(not(any(feature = "msgpack", feature = "bincode", feature = "postcard")))]
+compile_error!("At least one header codec feature (`msgpack`, `bincode` or `postcard`) must be enabled.");
+
+/// The [`HeaderCodec`] selected by the enabled features.
+#[cfg(feature = "postcard")]
+pub type DefaultCodec = Postcard;
+
+/// The [`HeaderCodec`] selected by the enabled features.
+#[cfg(all(feature = "bincode", not(feature = "postcard")))]
+pub type DefaultCodec = Bincode;
+
+/// The [`HeaderCodec`] selected by the enabled features.
+#[cfg(all(
+    feature = "msgpack",
+    not(any(feature = "bincode", feature = "postcard"))
+))]
+pub type DefaultCodec = MessagePack;
+
+/// The deepest nesting the built in [`HeaderCodec`]s accept in a serialized [`VoipHeader`].
+/// The header itself nests a few levels deep, the limit only stops self-describing formats from recursing on crafted input.
+pub const MAX_HEADER_DEPTH: usize = 16;
+
+/// Custom header codec errors.
+#[derive(thiserror::Error, Debug)]
+pub enum CodecError {
+    /// This error is thrown when [`MessagePack`] failed to serialize a [`VoipHeader`].
+    #[cfg(feature = "msgpack")]
+    #[error("Failed to serialize a VoipHeader: {0}")]
+    MessagePackEncode(#[from] rmp_serde::encode::Error),
+
+    /// This error is thrown when [`MessagePack`] failed to deserialize a [`VoipHeader`].
+    #[cfg(feature = "msgpack")]
+    #[error("Failed to deserialize a VoipHeader: {0}")]
+    MessagePackDecode(#[from] rmp_serde::decode::Error),
+
+    /// This error is thrown when [`Bincode`] failed to serialize or deserialize a [`VoipHeader`].
+    #[cfg(feature = "bincode")]
+    #[error("Failed to (de)serialize a VoipHeader: {0}")]
+    Bincode(#[from] bincode::Error),
+
+    /// This error is thrown when [`Postcard`] failed to serialize or deserialize a [`VoipHeader`].
+    #[cfg(feature = "postcard")]
+    #[error("Failed to (de)serialize a VoipHeader: {0}")]
+    Postcard(#[from] postcard::Error),
+
+    /// This error is thrown when a serialized [`VoipHeader`] is longer than [`MAX_HEADER_SIZE`].
+    #[error("The serialized VoipHeader is {0} bytes long, which exceeds the limit of {MAX_HEADER_SIZE} bytes.")]
+    HeaderTooLarge(usize),
+
+    /// This error is thrown by user provided [`HeaderCodec`] implementations.
+    #[error("Failed to (de)serialize a VoipHeader: {0}")]
+    Custom(Box<dyn core::error::Error + Send + Sync>),
+}
+
+///
+/// Header serialization format definition.
+///
+/// Implement this trait to use a custom serialization format for the [`VoipHeader`].
+///
+pub trait HeaderCodec {
+    /// Serializes the [`VoipHeader`] into bytes.
+    fn encode(voip_header: &VoipHeader) -> Result<Vec<u8>, CodecError>;
+
+    /// Deserializes a [`VoipHeader`] from the start of the bytes.
+    /// The bytes contain the data of the message after the header, which must be ignored.
+    /// At most [`MAX_HEADER_SIZE`] bytes are passed in, implementations should not allocate more than that based on the lengths declared in the bytes.
+    fn decode(bytes: &[u8]) -> Result<VoipHeader, CodecError>;
+}
+
+/// [MessagePack](https://msgpack.org/) header codec, implemented with [`rmp_serde`].
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl HeaderCodec for MessagePack {
+    fn encode(voip_header: &VoipHeader) -> Result<Vec<u8>, CodecError> {
+        Ok(rmp_serde::to_vec(voip_header)?)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<VoipHeader, CodecError> {
+        let mut deserializer = rmp_serde::Deserializer::from_read_ref(bytes);
+
+        //MessagePack is self-describing, so limit how deep the skipped values may nest
+        deserializer.set_max_depth(MAX_HEADER_DEPTH);
+
+        Ok(serde::Deserialize::deserialize(&mut deserializer)?)
+    }
+}
+
+/// [Bincode](https://github.com/bincode-org/bincode) header codec.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl HeaderCodec for Bincode {
+    fn encode(voip_header: &VoipHeader) -> Result<Vec<u8>, CodecError> {
+        Ok(bincode::serialize(voip_header)?)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<VoipHeader, CodecError> {
+        use bincode::Options;
+
+        //Same options as `bincode::deserialize`, but with a limit on the bytes the declared lengths may allocate
+        Ok(bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(MAX_HEADER_SIZE as u64)
+            .deserialize(bytes)?)
+    }
+}
+
+/// [Postcard](https://github.com/jamesmunns/postcard) header codec.
+#[cfg(feature = "postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl HeaderCodec for Postcard {
+    fn encode(voip_header: &VoipHeader) -> Result<Vec<u8>, CodecError> {
+        Ok(postcard::to_allocvec(voip_header)?)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<VoipHeader, CodecError> {
+        //The data of the message follows the header, which we dont need here
+        let (voip_header, _data) = postcard::take_from_bytes(bytes)?;
+
+        Ok(voip_header)
+    }
+}
@@ -0,0 +1,669 @@
+//!
+//! Provides the wire-format conformance test vectors, which let the alternative implementations of the protocol (for example the mobile or the web clients) verify that they encode and decode the messages byte-for-byte like this crate.
+//!
+//! [`test_vectors`] returns the canonical matrix of messages: every [`VoipMessageType`], every [`ControlMessage`], every field of the [`VoipHeader`] alone and all of them together, and the integer boundaries of the variable length encodings.
+//! [`emit_vectors`] encodes them with a [`HeaderCodec`] into a plain text file, with one `name hex` pair on every line (the lines starting with `#` are comments).
+//! An implementation under test decodes every vector of the file and re-encodes it, then the vectors it has encoded can be checked with [`verify_vectors`], which compares them against the reference encoding of the same name.
+//!
+//! The vectors are only comparable between the peers using the same [`HeaderCodec`], see the [`codec`](super::codec) module.
+//!
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use uuid::Uuid;
+
+use super::{
+    caption::CaptionInfo,
+    codec::{CodecError, HeaderCodec},
+    control::{
+        CallSignal, CallSignalKind, CloseCode, CloseReason, ConnectionId, ControlMessage,
+        DeviceNotice, FloorState, LayerSelection, MediaState, PolicyViolation, PresenceState,
+        QualityReport, RecordingState, ResumptionTicket, RetryToken, RoomPolicy, ToneEvent,
+    },
+    decode_message_with, HeaderFlags, MediaCodec, PacketError, Position, SliceInfo, VoipHeader,
+    VoipMessageType, AUDIO_LEVEL_LOUDEST, AUDIO_LEVEL_SILENCE,
+};
+
+/// The author of every test vector.
+pub const TEST_VECTOR_AUTHOR: Uuid = Uuid::from_u128(0x0011_2233_4455_6677_8899_aabb_ccdd_eeff);
+
+/// The [`Uuid`] every test vector refers to besides its author (for example the participant of a [`ControlMessage::ParticipantJoined`]).
+pub const TEST_VECTOR_PEER: Uuid = Uuid::from_u128(0xffee_ddcc_bbaa_9988_7766_5544_3322_1100);
+
+/// The boundaries of the variable length integer encodings, every sequence number in the list is a test vector.
+const INTEGER_BOUNDARIES: [u32; 10] = [
+    0,
+    127,
+    128,
+    255,
+    256,
+    16_383,
+    16_384,
+    65_535,
+    65_536,
+    u32::MAX,
+];
+
+/// Wire-format conformance errors.
+#[derive(thiserror::Error, Debug)]
+pub enum ConformanceError {
+    /// This error is thrown when a line of the vectors isn't a `name hex` pair.
+    #[error("Line {0} isn't a `name hex` pair.")]
+    InvalidLine(usize),
+
+    /// This error is thrown when the encoded message of a line isn't valid hex.
+    #[error("Line {0} contains invalid hex.")]
+    InvalidHex(usize),
+
+    /// This error is thrown when a vector isn't a valid message.
+    #[error("The vector could not be decoded: {0}")]
+    Decode(#[from] PacketError),
+
+    /// This error is thrown when the reference encoding of a vector could not be created.
+    #[error("The vector could not be encoded: {0}")]
+    Encode(#[from] CodecError),
+
+    /// This error is thrown when a vector decodes to a different message than the reference vector of the same name.
+    #[error("The vector decodes to a different message than the reference.")]
+    MessageMismatch,
+
+    /// This error is thrown when a vector decodes to the right message, but it isn't encoded byte-for-byte like the reference.
+    #[error("The vector differs from the reference from byte {offset} on.")]
+    BytesMismatch {
+        /// The offset of the first byte which differs.
+        offset: usize,
+        /// The reference encoding of the vector.
+        expected: Vec<u8>,
+        /// The encoding of the vector under test.
+        actual: Vec<u8>,
+    },
+}
+
+///
+/// Test vector type definition.
+///
+/// A named message of the conformance matrix, before it is encoded with a [`HeaderCodec`].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestVector {
+    /// The name of the vector, which is the same for every codec.
+    pub name: String,
+
+    /// The header of the message.
+    pub voip_header: VoipHeader,
+
+    /// The data following the header.
+    pub body: Vec<u8>,
+}
+
+impl TestVector {
+    /// Creates a new [`TestVector`] instance, the body length of the [`VoipMessageType`] is set to the length of the `body`.
+    pub fn new(name: &str, voip_header: VoipHeader, body: Vec<u8>) -> Self {
+        let voip_message_type = voip_header
+            .voip_message_type()
+            .with_body_length(body.len() as u64);
+
+        Self {
+            name: name.to_string(),
+            voip_header: voip_header.with_voip_message_type(voip_message_type),
+            body,
+        }
+    }
+
+    /// Encodes the message of the vector with the [`HeaderCodec`] `C`.
+    pub fn encode<C: HeaderCodec>(&self) -> Result<Vec<u8>, CodecError> {
+        Ok(self
+            .voip_header
+            .create_message_buffer_with::<C>(&self.body)?
+            .inner()
+            .to_vec())
+    }
+}
+
+///
+/// Encoded vector type definition.
+///
+/// A named encoded message, as read from the vectors of an implementation with [`parse_vectors`].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedVector {
+    /// The name of the vector.
+    pub name: String,
+
+    /// The encoded message.
+    pub message: Vec<u8>,
+}
+
+///
+/// Vector report type definition.
+///
+/// The outcome of verifying a single [`EncodedVector`] with [`verify_vectors`].
+///
+#[derive(Debug)]
+pub struct VectorReport {
+    /// The name of the vector.
+    pub name: String,
+
+    /// Whether the vector conforms to the reference encoding.
+    pub result: Result<(), ConformanceError>,
+}
+
+/// Returns the canonical matrix of [`TestVector`]s, in a stable order.
+pub fn test_vectors() -> Vec<TestVector> {
+    let voice = || {
+        VoipHeader::new(VoipMessageType::VoiceMessage(0), TEST_VECTOR_AUTHOR)
+            .with_codec(MediaCodec::Opus)
+    };
+    let body = vec![0xde, 0xad, 0xbe, 0xef];
+
+    let mut test_vectors = vec![
+        //Every message type
+        TestVector::new("voice", voice(), body.clone()),
+        TestVector::new("voice.empty", voice(), vec![]),
+        TestVector::new(
+            "voice.end_of_stream",
+            voice()
+                .with_flags(HeaderFlags::default() | HeaderFlags::END_OF_STREAM)
+                .with_sequence(1),
+            vec![],
+        ),
+        TestVector::new(
+            "video",
+            VoipHeader::new(VoipMessageType::VideoMessage(0), TEST_VECTOR_AUTHOR)
+                .with_codec(MediaCodec::Avif),
+            body.clone(),
+        ),
+        TestVector::new(
+            "text",
+            VoipHeader::new(VoipMessageType::TextMessage(0), TEST_VECTOR_AUTHOR),
+            b"Hello, world!".to_vec(),
+        ),
+        TestVector::new(
+            "caption",
+            VoipHeader::new(
+                VoipMessageType::CaptionMessage(
+                    0,
+                    CaptionInfo {
+                        author: TEST_VECTOR_PEER,
+                        start: 1_000_000,
+                        end: 2_500_000,
+                        is_final: true,
+                    },
+                ),
+                TEST_VECTOR_AUTHOR,
+            ),
+            b"Hello".to_vec(),
+        ),
+        //Every field of the header alone
+        TestVector::new(
+            "header.codec.raw",
+            voice().with_codec(MediaCodec::Raw),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.codec.custom",
+            voice().with_codec(MediaCodec::Custom(u16::MAX)),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.channel",
+            voice().with_channel(u32::MAX),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.flags.empty",
+            voice().with_flags(HeaderFlags::EMPTY),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.flags.all",
+            voice().with_flags(
+                HeaderFlags::MARKER
+                    | HeaderFlags::LAST_FRAGMENT
+                    | HeaderFlags::FIRST_FRAGMENT
+                    | HeaderFlags::PADDING
+                    | HeaderFlags::END_OF_STREAM,
+            ),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.audio_level.loudest",
+            voice().with_audio_level(AUDIO_LEVEL_LOUDEST),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.audio_level.silence",
+            voice().with_audio_level(AUDIO_LEVEL_SILENCE),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.position",
+            voice().with_position(Position::new(i32::MIN, -1, i32::MAX)),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.timestamp",
+            voice().with_timestamp(u64::MAX),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.redundancy",
+            voice().with_redundancy(Some(2)),
+            body.clone(),
+        ),
+        TestVector::new("header.layer", voice().with_layer(Some(2)), body.clone()),
+        TestVector::new("header.stream", voice().with_stream(Some(1)), body.clone()),
+        TestVector::new(
+            "header.frame_lengths",
+            voice().with_frame_lengths(Some(vec![1, 2])),
+            body.clone(),
+        ),
+        TestVector::new(
+            "header.slice",
+            VoipHeader::new(VoipMessageType::VideoMessage(0), TEST_VECTOR_AUTHOR)
+                .with_codec(MediaCodec::Avif)
+                .with_slice(Some(SliceInfo { index: 1, count: 4 })),
+            body.clone(),
+        ),
+        TestVector::new("header.device", voice().with_device(Some(1)), body.clone()),
+        //Every field of the header together
+        TestVector::new(
+            "header.all",
+            voice()
+                .with_channel(7)
+                .with_flags(HeaderFlags::MARKER | HeaderFlags::LAST_FRAGMENT)
+                .with_audio_level(42)
+                .with_position(Position::new(1_500, -200, 30_000))
+                .with_sequence(1_000)
+                .with_timestamp(20_000_000)
+                .with_redundancy(Some(1))
+                .with_layer(Some(0))
+                .with_stream(Some(3))
+                .with_frame_lengths(Some(vec![1]))
+                .with_slice(Some(SliceInfo { index: 0, count: 1 }))
+                .with_device(Some(2)),
+            body.clone(),
+        ),
+    ];
+
+    //The boundaries of the variable length integers
+    for sequence in INTEGER_BOUNDARIES {
+        test_vectors.push(TestVector::new(
+            &format!("header.sequence.{sequence}"),
+            voice().with_sequence(sequence),
+            body.clone(),
+        ));
+    }
+
+    //Every control message
+    for (name, control_message) in control_messages() {
+        test_vectors.push(TestVector::new(
+            &format!("control.{name}"),
+            VoipHeader::new(
+                VoipMessageType::Control(control_message),
+                TEST_VECTOR_AUTHOR,
+            ),
+            vec![],
+        ));
+    }
+
+    test_vectors
+}
+
+/// Returns every [`ControlMessage`] with the name of its vector, the variants with a payload are listed with every variant of their payload.
+fn control_messages() -> Vec<(&'static str, ControlMessage)> {
+    let call_signal = |kind| CallSignal {
+        call_id: TEST_VECTOR_PEER,
+        recipient: TEST_VECTOR_PEER,
+        kind,
+    };
+
+    vec![
+        (
+            "participant_joined",
+            ControlMessage::ParticipantJoined(TEST_VECTOR_PEER),
+        ),
+        (
+            "participant_left",
+            ControlMessage::ParticipantLeft(TEST_VECTOR_PEER),
+        ),
+        ("heartbeat", ControlMessage::Heartbeat),
+        (
+            "quality_report",
+            ControlMessage::QualityReport(QualityReport {
+                packets_received: 1_000,
+                packets_lost: 12,
+                jitter_ms: 30,
+                round_trip_time_ms: 150,
+                packets_ce: 3,
+            }),
+        ),
+        ("max_bitrate", ControlMessage::MaxBitrate(Some(64_000))),
+        ("max_bitrate.none", ControlMessage::MaxBitrate(None)),
+        (
+            "room_policy",
+            ControlMessage::RoomPolicy(RoomPolicy {
+                target_loudness: Some(23),
+                max_bitrate: Some(32_000),
+                mandatory_dtx: true,
+                allowed_codecs: Some(vec![MediaCodec::Opus, MediaCodec::Avif]),
+                max_video_bitrate: Some(500_000),
+                video_disabled: false,
+                screen_sharing_disabled: true,
+            }),
+        ),
+        ("goodbye", ControlMessage::Goodbye),
+        (
+            "close",
+            ControlMessage::Close(CloseReason::new(
+                CloseCode::Kicked,
+                Some(String::from("Bye")),
+            )),
+        ),
+        (
+            "close.custom",
+            ControlMessage::Close(CloseReason::new(CloseCode::Custom(4_000), None)),
+        ),
+        ("ping", ControlMessage::Ping(7)),
+        ("pong", ControlMessage::Pong(7)),
+        (
+            "retry",
+            ControlMessage::Retry(RetryToken::new(1_700_000_000, [0xab; 16])),
+        ),
+        (
+            "retry_heartbeat",
+            ControlMessage::RetryHeartbeat(RetryToken::new(1_700_000_000, [0xab; 16])),
+        ),
+        (
+            "select_layer",
+            ControlMessage::SelectLayer(TEST_VECTOR_PEER, LayerSelection::Layer(1)),
+        ),
+        (
+            "select_layer.auto",
+            ControlMessage::SelectLayer(TEST_VECTOR_PEER, LayerSelection::Auto),
+        ),
+        ("tone", ControlMessage::Tone(ToneEvent::new(11, 250))),
+        ("floor_request", ControlMessage::FloorRequest),
+        ("floor_release", ControlMessage::FloorRelease),
+        ("floor.idle", ControlMessage::Floor(FloorState::Idle)),
+        (
+            "floor.granted",
+            ControlMessage::Floor(FloorState::Granted(TEST_VECTOR_PEER)),
+        ),
+        ("floor.queued", ControlMessage::Floor(FloorState::Queued(2))),
+        ("floor.denied", ControlMessage::Floor(FloorState::Denied)),
+        (
+            "call.invite",
+            ControlMessage::Call(call_signal(CallSignalKind::Invite)),
+        ),
+        (
+            "call.ringing",
+            ControlMessage::Call(call_signal(CallSignalKind::Ringing)),
+        ),
+        (
+            "call.accept",
+            ControlMessage::Call(call_signal(CallSignalKind::Accept)),
+        ),
+        (
+            "call.reject",
+            ControlMessage::Call(call_signal(CallSignalKind::Reject)),
+        ),
+        (
+            "call.busy",
+            ControlMessage::Call(call_signal(CallSignalKind::Busy)),
+        ),
+        (
+            "call.hangup",
+            ControlMessage::Call(call_signal(CallSignalKind::Hangup)),
+        ),
+        (
+            "call.do_not_disturb",
+            ControlMessage::Call(call_signal(CallSignalKind::DoNotDisturb)),
+        ),
+        (
+            "presence.available",
+            ControlMessage::Presence(PresenceState::Available),
+        ),
+        (
+            "presence.busy",
+            ControlMessage::Presence(PresenceState::Busy),
+        ),
+        (
+            "presence.do_not_disturb",
+            ControlMessage::Presence(PresenceState::DoNotDisturb),
+        ),
+        (
+            "presence.away",
+            ControlMessage::Presence(PresenceState::Away),
+        ),
+        (
+            "codecs",
+            ControlMessage::Codecs(vec![MediaCodec::Opus, MediaCodec::Custom(1)]),
+        ),
+        (
+            "media_state",
+            ControlMessage::MediaState(MediaState {
+                audio_muted: true,
+                video_muted: false,
+                screen_sharing: true,
+            }),
+        ),
+        (
+            "recording_state.stopped",
+            ControlMessage::RecordingState(RecordingState::Stopped),
+        ),
+        (
+            "recording_state.recording",
+            ControlMessage::RecordingState(RecordingState::Recording {
+                consent_required: true,
+            }),
+        ),
+        ("recording_consent", ControlMessage::RecordingConsent(true)),
+        ("keepalive_probe", ControlMessage::KeepaliveProbe(3, 45_000)),
+        ("pause_video", ControlMessage::PauseVideo(true)),
+        (
+            "resumption_ticket",
+            ControlMessage::ResumptionTicket(ResumptionTicket::new(1_700_000_000, [0xcd; 16])),
+        ),
+        (
+            "resume",
+            ControlMessage::Resume(ResumptionTicket::new(1_700_000_000, [0xcd; 16])),
+        ),
+        (
+            "close.duplicate_session",
+            ControlMessage::Close(CloseReason::new(CloseCode::DuplicateSession, None)),
+        ),
+        (
+            "close.replaced",
+            ControlMessage::Close(CloseReason::new(CloseCode::Replaced, None)),
+        ),
+        (
+            "device.assigned",
+            ControlMessage::Device(DeviceNotice::Assigned(1)),
+        ),
+        (
+            "device.joined",
+            ControlMessage::Device(DeviceNotice::Joined(1)),
+        ),
+        ("device.left", ControlMessage::Device(DeviceNotice::Left(1))),
+        (
+            "policy_violation.codec",
+            ControlMessage::PolicyViolation(PolicyViolation::Codec(MediaCodec::Custom(7))),
+        ),
+        (
+            "policy_violation.audio_bitrate",
+            ControlMessage::PolicyViolation(PolicyViolation::AudioBitrate(32_000)),
+        ),
+        (
+            "policy_violation.video_bitrate",
+            ControlMessage::PolicyViolation(PolicyViolation::VideoBitrate(500_000)),
+        ),
+        (
+            "policy_violation.video",
+            ControlMessage::PolicyViolation(PolicyViolation::Video),
+        ),
+        (
+            "policy_violation.screen_sharing",
+            ControlMessage::PolicyViolation(PolicyViolation::ScreenSharing),
+        ),
+        (
+            "connection_id",
+            ControlMessage::ConnectionId(ConnectionId::new([0xef; 16])),
+        ),
+        (
+            "connection_heartbeat",
+            ControlMessage::ConnectionHeartbeat(ConnectionId::new([0xef; 16])),
+        ),
+    ]
+}
+
+///
+/// Encodes the [`test_vectors`] with the [`HeaderCodec`] `C`, into the text format of the vectors.
+///
+/// # Error
+/// Returns an error if a vector could not be encoded, which means that the codec can't encode every message.
+///
+pub fn emit_vectors<C: HeaderCodec>() -> Result<String, CodecError> {
+    let mut vectors = String::from(
+        "# Wire-format conformance test vectors, one `name hex` pair on every line.\n",
+    );
+
+    let _ = writeln!(vectors, "# Header codec: {}", core::any::type_name::<C>());
+
+    for test_vector in test_vectors() {
+        let _ = writeln!(
+            vectors,
+            "{} {}",
+            test_vector.name,
+            encode_hex(&test_vector.encode::<C>()?)
+        );
+    }
+
+    Ok(vectors)
+}
+
+///
+/// Parses the text format of the vectors, as emitted by [`emit_vectors`] or by an implementation under test.
+///
+/// # Behavior
+/// The empty lines and the lines starting with `#` are skipped, the hex is case insensitive.
+///
+/// # Error
+/// Returns an error if a line isn't a `name hex` pair, with the number of the line (starting from `1`).
+///
+pub fn parse_vectors(vectors: &str) -> Result<Vec<EncodedVector>, ConformanceError> {
+    let mut encoded_vectors = Vec::new();
+
+    for (index, line) in vectors.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+
+        let (Some(name), Some(hex), None) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(ConformanceError::InvalidLine(index + 1));
+        };
+
+        encoded_vectors.push(EncodedVector {
+            name: name.to_string(),
+            message: decode_hex(hex).ok_or(ConformanceError::InvalidHex(index + 1))?,
+        });
+    }
+
+    Ok(encoded_vectors)
+}
+
+///
+/// Verifies the `encoded_vectors` of an implementation under test against the reference encoding of the [`HeaderCodec`] `C`.
+///
+/// # Behavior
+/// Every vector has to decode to the same message as the reference vector of the same name, and has to be encoded byte-for-byte like it.
+/// The vectors whose name isn't in the [`test_vectors`] (for example the extra cases of the implementation) have to decode, and re-encoding them has to result in the same bytes.
+///
+pub fn verify_vectors<C: HeaderCodec>(encoded_vectors: &[EncodedVector]) -> Vec<VectorReport> {
+    let test_vectors = test_vectors();
+
+    encoded_vectors
+        .iter()
+        .map(|encoded_vector| {
+            let test_vector = test_vectors
+                .iter()
+                .find(|test_vector| test_vector.name == encoded_vector.name);
+
+            VectorReport {
+                name: encoded_vector.name.clone(),
+                result: verify_vector::<C>(&encoded_vector.message, test_vector),
+            }
+        })
+        .collect()
+}
+
+/// Verifies an encoded `message` against the reference encoding of its `test_vector`, or against its own re-encoding if it has none.
+fn verify_vector<C: HeaderCodec>(
+    message: &[u8],
+    test_vector: Option<&TestVector>,
+) -> Result<(), ConformanceError> {
+    let (voip_header, body) = decode_message_with::<C>(message)?;
+
+    let expected = match test_vector {
+        Some(test_vector) => {
+            if test_vector.voip_header != voip_header || test_vector.body != body {
+                return Err(ConformanceError::MessageMismatch);
+            }
+
+            test_vector.encode::<C>()?
+        }
+        None => voip_header
+            .create_message_buffer_with::<C>(&body)?
+            .inner()
+            .to_vec(),
+    };
+
+    if expected != message {
+        let offset = expected
+            .iter()
+            .zip(message)
+            .position(|(expected, actual)| expected != actual)
+            .unwrap_or(expected.len().min(message.len()));
+
+        return Err(ConformanceError::BytesMismatch {
+            offset,
+            expected,
+            actual: message.to_vec(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Encodes the `bytes` as lowercase hex.
+fn encode_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+
+    for byte in bytes {
+        let _ = write!(hex, "{byte:02x}");
+    }
+
+    hex
+}
+
+/// Decodes the `hex`, returns [`None`] if it isn't valid hex.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
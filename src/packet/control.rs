@@ -0,0 +1,571 @@
+//!
+//! Provides the control messages, which are exchanged between the peers to manage the session itself instead of carrying media.
+//!
+//! Control messages are sent as [`VoipMessageType::Control`](super::VoipMessageType::Control) and never have a body, every information they carry is stored in the header.
+//!
+
+use alloc::{string::String, vec::Vec};
+use uuid::Uuid;
+
+use super::MediaCodec;
+
+/// The longest delay (in milliseconds) a [`ControlMessage::KeepaliveProbe`] is answered after, the longer delays are capped at it.
+pub const MAX_KEEPALIVE_PROBE_DELAY_MS: u32 = 600_000;
+
+///
+/// Control message type definition.
+///
+/// This enum contains the control messages the [`VoipHeader`](super::VoipHeader) can contain.
+///
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ControlMessage {
+    /// This message is sent to the clients when a participant has joined the session.
+    /// Contains the [`Uuid`] of the participant.
+    /// The server sends it to the joining clients for every participant already in the session, so that they start with the whole roster.
+    ParticipantJoined(Uuid),
+
+    /// This message is sent to the clients when a participant has left the session.
+    /// Contains the [`Uuid`] of the participant.
+    ParticipantLeft(Uuid),
+
+    /// This message is sent periodically by the clients, to signal that they are still alive.
+    /// The server refreshes the sender's entry in its peer registry, and echoes the heartbeat back so the client can tell the server is alive too.
+    Heartbeat,
+
+    /// This message is sent by the clients to report the quality of the stream they are receiving.
+    /// The server stores the latest report of the sender, and forwards it to the application (for example to adapt the bitrate).
+    QualityReport(QualityReport),
+
+    /// This message is sent by the clients to signal the highest bitrate (in bits per second) they can receive, or [`None`] to remove the limit.
+    /// The server stores the limit of the sender, and (if transcoding is enabled) re-encodes the voice messages exceeding it before sending them to the sender.
+    /// The server sends it to the clients exceeding its bandwidth caps, with the highest bitrate their media is forwarded at, and the clients cap their voice to it.
+    MaxBitrate(Option<u32>),
+
+    /// This message is sent by the server to advertise the [`RoomPolicy`] of the channel (or room) set in the header.
+    /// The server sends the policy of every room when a client joins, and the clients apply it to the voice messages they send.
+    RoomPolicy(RoomPolicy),
+
+    /// This message is sent by a peer which is leaving the session.
+    /// The server removes the sender from its peer registry and reply list, and notifies the remaining clients with [`ControlMessage::ParticipantLeft`].
+    Goodbye,
+
+    /// This message is sent by a peer which is terminating the session, with the [`CloseReason`] of the termination.
+    /// The receiving client reports it as its final event and shuts down, the receiving server handles it like a [`ControlMessage::Goodbye`].
+    Close(CloseReason),
+
+    /// This message is sent to measure the round trip time to the server, without joining the session (for example to pick the closest relay).
+    /// The server answers it with a [`ControlMessage::Pong`] carrying the same sequence number, without registering the sender.
+    Ping(u32),
+
+    /// This message is sent by the server as the answer to a [`ControlMessage::Ping`], with the sequence number of the ping.
+    Pong(u32),
+
+    /// This message is sent by a server requiring address validation, as the answer to the heartbeats of the unregistered addresses.
+    /// The receiving client echoes the [`RetryToken`] in a [`ControlMessage::RetryHeartbeat`], which proves that it can receive messages at its address.
+    Retry(RetryToken),
+
+    /// This message is a [`ControlMessage::Heartbeat`] echoing the [`RetryToken`] of a [`ControlMessage::Retry`].
+    /// The server only registers the sender if the token is valid for its address.
+    RetryHeartbeat(RetryToken),
+
+    /// This message is sent by the clients to select the simulcast layer of the author's video they receive, with the [`Uuid`] of the author.
+    /// The server switches to the selected layer on its next keyframe, so the receivers never get a frame they can't decode.
+    SelectLayer(Uuid, LayerSelection),
+
+    /// This message is sent by the clients to signal a [`ToneEvent`] (for example a DTMF digit for a telephony gateway, or a doorbell trigger in an intercom).
+    /// The server forwards it to the application like the other messages of the session.
+    Tone(ToneEvent),
+
+    /// This message is sent by the clients to request the floor of the channel (or room) set in the header, if the server has enabled floor control.
+    /// The server answers it with a [`ControlMessage::Floor`], granting, queueing or denying the request.
+    FloorRequest,
+
+    /// This message is sent by the clients to release the floor of the channel (or room) set in the header, or to leave its queue.
+    FloorRelease,
+
+    /// This message is sent by the server to signal the [`FloorState`] of the channel (or room) set in the header.
+    /// The changes of the holder are sent to every client, the queue positions and the denials only to the requester.
+    Floor(FloorState),
+
+    /// This message is sent by the clients to set up or tear down a one-to-one call with the recipient of the [`CallSignal`].
+    /// The server routes it to the recipient, or answers it with a [`CallSignalKind::Hangup`] if the recipient isn't in the session.
+    Call(CallSignal),
+
+    /// This message is sent by the clients to set their [`PresenceState`].
+    /// The server stores the state of the sender and sends it to every other client (with the sender as the author), the joining clients are sent the state of every peer which isn't [`PresenceState::Available`].
+    Presence(PresenceState),
+
+    /// This message is sent by the clients to advertise the audio codecs they can decode, every client decodes [`MediaCodec::Opus`] even if it isn't listed.
+    /// The server answers every advertisement (and every peer joining or leaving a session with advertisements) by sending the codecs every peer can decode to every client, with the server as the author.
+    Codecs(Vec<MediaCodec>),
+
+    /// This message is sent by the clients to signal the [`MediaState`] of their media in the channel (or room) set in the header.
+    /// The server retains the latest state of the sender in every room and sends it to every other client (with the sender as the author), the joining clients are sent every retained state which isn't the default.
+    MediaState(MediaState),
+
+    /// This message is sent by the server to signal the [`RecordingState`] of the channel (or room) set in the header, to every client when the recording starts or stops, and to the joining clients while the room is recorded.
+    /// While the recording requires consent, the server only forwards the media the clients send in the room after they have consented with a [`ControlMessage::RecordingConsent`].
+    RecordingState(RecordingState),
+
+    /// This message is sent by the clients to give (`true`) or withdraw (`false`) their consent to the recording of the channel (or room) set in the header.
+    /// The server stores the consent of the sender until the recording stops or the sender leaves.
+    RecordingConsent(bool),
+
+    /// This message is sent by the clients to learn whether their NAT binding survives a silence of the delay (in milliseconds), with a sequence number and the delay.
+    /// The server answers it with a [`ControlMessage::Pong`] carrying the sequence number once the delay has passed (at most [`MAX_KEEPALIVE_PROBE_DELAY_MS`]), the sender doesn't send anything in between, so the answer only arrives if the binding has survived.
+    /// Only the probes of the registered peers are answered, and only the latest probe of every peer.
+    KeepaliveProbe(u32, u32),
+
+    /// This message is sent by the clients to stop (`true`) or resume (`false`) receiving the video of the other participants, for example while the application is in the background.
+    /// The server stores the choice of the sender, and skips it while fanning out the video messages until it is resumed.
+    PauseVideo(bool),
+
+    /// This message is sent by a server issuing resumption tickets to the clients which have joined (or resumed) their session, and again before their ticket expires.
+    /// The receiving client keeps the latest [`ResumptionTicket`], and presents it in a [`ControlMessage::Resume`] after a crash or a network blip.
+    ResumptionTicket(ResumptionTicket),
+
+    /// This message is a [`ControlMessage::Heartbeat`] presenting the [`ResumptionTicket`] the server has issued to the author of the header.
+    /// If the ticket is valid for its author, the server answers it with a [`ControlMessage::Retry`], and restores the session at the address of the sender once it has echoed the token.
+    Resume(ResumptionTicket),
+
+    /// This message is sent by a server allowing the authors to join from several devices, to signal the [`DeviceNotice`]s of the author of the header to its devices.
+    Device(DeviceNotice),
+
+    /// This message is sent by the server to a client whose media message was rejected, as it violates the [`RoomPolicy`] of the channel (or room) set in the header.
+    PolicyViolation(PolicyViolation),
+
+    /// This message is sent by the server to the clients which have joined (or resumed) their session, with the [`ConnectionId`] of the session.
+    /// The receiving client keeps it, and presents it in a [`ControlMessage::ConnectionHeartbeat`] instead of the plain heartbeats.
+    ConnectionId(ConnectionId),
+
+    /// This message is a [`ControlMessage::Heartbeat`] presenting the [`ConnectionId`] the server has issued to the session of the sender.
+    /// If it arrives from a new address (for example after a NAT rebinding or a failover), the server answers it with a [`ControlMessage::Retry`], and moves the session to the new address once it has echoed the token.
+    ConnectionHeartbeat(ConnectionId),
+}
+
+/// The rule of a [`RoomPolicy`] a rejected media message has violated (see [`ControlMessage::PolicyViolation`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PolicyViolation {
+    /// The message is encoded with the contained codec, which isn't in the [`RoomPolicy::allowed_codecs`].
+    Codec(MediaCodec),
+
+    /// The voice of the sender exceeds the contained [`RoomPolicy::max_bitrate`] (in bits per second).
+    AudioBitrate(u32),
+
+    /// The video of the sender exceeds the contained [`RoomPolicy::max_video_bitrate`] (in bits per second).
+    VideoBitrate(u32),
+
+    /// The room doesn't permit video, see [`RoomPolicy::video_disabled`].
+    Video,
+
+    /// The room doesn't permit screen sharing, see [`RoomPolicy::screen_sharing_disabled`].
+    ScreenSharing,
+}
+
+/// A change of the devices of an author, signaled by a server allowing the authors to join from several devices (see [`ControlMessage::Device`]).
+/// The devices are identified by their ids among the devices of their author, the first device of an author is `0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DeviceNotice {
+    /// The receiving device has joined the session, with the contained id.
+    Assigned(u32),
+
+    /// Another device of the author has joined the session, with the contained id.
+    Joined(u32),
+
+    /// Another device of the author has left the session, with the contained id.
+    Left(u32),
+}
+
+/// The presence state a user has set, which is shown to the other users of the session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum PresenceState {
+    /// The user is available.
+    #[default]
+    Available,
+
+    /// The user is busy, but can still be called.
+    Busy,
+
+    /// The user doesn't want to be disturbed, the server rejects the calls to the user with [`CallSignalKind::DoNotDisturb`].
+    DoNotDisturb,
+
+    /// The user is away.
+    Away,
+}
+
+///
+/// Media state type definition.
+///
+/// Describes the media a participant sends in a channel (or room), as signaled with [`ControlMessage::MediaState`].
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct MediaState {
+    /// Whether the participant has muted its voice.
+    pub audio_muted: bool,
+
+    /// Whether the participant has turned off its video.
+    pub video_muted: bool,
+
+    /// Whether the participant is sharing its screen.
+    pub screen_sharing: bool,
+}
+
+/// The recording state of a room, as signaled with [`ControlMessage::RecordingState`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum RecordingState {
+    /// The room isn't recorded.
+    #[default]
+    Stopped,
+
+    /// The room is recorded.
+    Recording {
+        /// Whether the media of the clients is only forwarded (and recorded) after they have consented with a [`ControlMessage::RecordingConsent`].
+        consent_required: bool,
+    },
+}
+
+impl RecordingState {
+    /// Returns whether the room is recorded.
+    pub fn is_recording(&self) -> bool {
+        matches!(self, Self::Recording { .. })
+    }
+
+    /// Returns whether the room is recorded, and the clients have to consent before their media is forwarded.
+    pub fn requires_consent(&self) -> bool {
+        matches!(
+            self,
+            Self::Recording {
+                consent_required: true
+            }
+        )
+    }
+}
+
+/// The state of the floor of a room, as signaled with [`ControlMessage::Floor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum FloorState {
+    /// Nobody holds the floor.
+    Idle,
+
+    /// The floor is held by the contained author, only their voice is forwarded.
+    Granted(Uuid),
+
+    /// The request of the receiver is queued, at the contained position (`1` being the next to be granted).
+    Queued(u32),
+
+    /// The request of the receiver was denied, as the queue is full.
+    Denied,
+}
+
+/// The simulcast layer a receiver selects with [`ControlMessage::SelectLayer`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum LayerSelection {
+    /// The highest layer the author is sending.
+    #[default]
+    Auto,
+
+    /// The contained layer, or the closest lower layer the author is sending (`0` being the lowest quality).
+    Layer(u8),
+}
+
+/// The code of a [`CloseReason`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum CloseCode {
+    /// The session was closed normally.
+    Normal,
+
+    /// The client was kicked from the session.
+    Kicked,
+
+    /// The client was banned from the session.
+    Banned,
+
+    /// The server is shutting down.
+    Shutdown,
+
+    /// The peer has failed to authenticate.
+    AuthenticationFailed,
+
+    /// The peer has violated the protocol.
+    ProtocolError,
+
+    /// An application defined reason, identified by the contained code.
+    Custom(u16),
+
+    /// The author of the peer is already in the session from another address, which is kept.
+    DuplicateSession,
+
+    /// The author of the peer has joined the session from another address, which has replaced this one.
+    Replaced,
+}
+
+///
+/// Close reason type definition.
+///
+/// Describes why a peer has terminated the session.
+/// The whole message must fit in [`MTU_MAX_PACKET_SIZE`](crate::MTU_MAX_PACKET_SIZE), so the optional message should be kept short.
+///
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CloseReason {
+    /// The [`CloseCode`] of the termination.
+    code: CloseCode,
+
+    /// A human readable message describing the termination.
+    message: Option<String>,
+}
+
+impl CloseReason {
+    /// Creates a new [`CloseReason`] instance.
+    pub fn new(code: CloseCode, message: Option<String>) -> Self {
+        Self { code, message }
+    }
+
+    /// Returns the [`CloseCode`] of the termination.
+    pub fn code(&self) -> CloseCode {
+        self.code
+    }
+
+    /// Returns the human readable message describing the termination, if there is one.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+///
+/// Retry token type definition.
+///
+/// A stateless cookie the server issues to an address, which is only valid for that address and for a limited time.
+/// The token is opaque to the clients, they only echo it back.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RetryToken {
+    /// The time the token was issued at, in seconds since the unix epoch.
+    issued_at: u64,
+
+    /// The truncated message authentication code of the address and the issue time.
+    mac: [u8; 16],
+}
+
+impl RetryToken {
+    /// Creates a new [`RetryToken`] instance.
+    pub fn new(issued_at: u64, mac: [u8; 16]) -> Self {
+        Self { issued_at, mac }
+    }
+
+    /// Returns the time the token was issued at, in seconds since the unix epoch.
+    pub fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+
+    /// Returns the message authentication code of the token.
+    pub fn mac(&self) -> [u8; 16] {
+        self.mac
+    }
+}
+
+///
+/// Resumption ticket type definition.
+///
+/// A stateless ticket the server issues to the author of a session, which is only valid for that author and for a limited time.
+/// Unlike the [`RetryToken`] it isn't bound to an address, so the session can be resumed from any address. The ticket is opaque to the clients, they only present it back.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct ResumptionTicket {
+    /// The time the ticket was issued at, in seconds since the unix epoch.
+    issued_at: u64,
+
+    /// The truncated message authentication code of the author and the issue time.
+    mac: [u8; 16],
+}
+
+impl ResumptionTicket {
+    /// Creates a new [`ResumptionTicket`] instance.
+    pub fn new(issued_at: u64, mac: [u8; 16]) -> Self {
+        Self { issued_at, mac }
+    }
+
+    /// Returns the time the ticket was issued at, in seconds since the unix epoch.
+    pub fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+
+    /// Returns the message authentication code of the ticket.
+    pub fn mac(&self) -> [u8; 16] {
+        self.mac
+    }
+}
+
+///
+/// Connection id type definition.
+///
+/// An unguessable identifier the server issues to every session when it joins, which proves that a heartbeat sent from a new address belongs to the session.
+/// Unlike the author of the messages, it is only known to the server and the client of the session. The id is opaque to the clients, they only present it back.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct ConnectionId {
+    /// The random bytes of the id.
+    bytes: [u8; 16],
+}
+
+impl ConnectionId {
+    /// Creates a new [`ConnectionId`] instance.
+    pub fn new(bytes: [u8; 16]) -> Self {
+        Self { bytes }
+    }
+
+    /// Returns the bytes of the id.
+    pub fn bytes(&self) -> [u8; 16] {
+        self.bytes
+    }
+}
+
+///
+/// Room policy type definition.
+///
+/// Describes the audio settings every client of a room has to apply, which keeps large public rooms consistent without tuning every client.
+/// The server rejects the media messages violating the codec, bitrate and video rules with a [`ControlMessage::PolicyViolation`].
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RoomPolicy {
+    /// The level (in -dBov) the voice of the clients is normalized to.
+    pub target_loudness: Option<u8>,
+
+    /// The highest bitrate (in bits per second) the clients may send voice messages at.
+    pub max_bitrate: Option<u32>,
+
+    /// Whether the clients must use discontinuous transmission, which means that silent voice frames are not sent at all.
+    pub mandatory_dtx: bool,
+
+    /// The codecs the voice and the video of the room may be encoded with, [`None`] allows every codec.
+    #[serde(default)]
+    pub allowed_codecs: Option<Vec<MediaCodec>>,
+
+    /// The highest bitrate (in bits per second) the clients may send video messages at.
+    #[serde(default)]
+    pub max_video_bitrate: Option<u32>,
+
+    /// Whether the clients may not send video at all, including the shared screens.
+    #[serde(default)]
+    pub video_disabled: bool,
+
+    /// Whether the clients may not send video while they are sharing their screen (see [`MediaState::screen_sharing`]).
+    #[serde(default)]
+    pub screen_sharing_disabled: bool,
+}
+
+impl RoomPolicy {
+    /// Returns whether the media encoded with the `codec` is allowed in the room.
+    pub fn allows_codec(&self, codec: MediaCodec) -> bool {
+        self.allowed_codecs
+            .as_ref()
+            .is_none_or(|allowed_codecs| allowed_codecs.contains(&codec))
+    }
+}
+
+///
+/// Call signal type definition.
+///
+/// Describes a step of the setup or the teardown of a one-to-one call, the sender is the author of the message carrying it.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CallSignal {
+    /// The id of the call, which is chosen by the caller and is the same in every signal of the call.
+    pub call_id: Uuid,
+
+    /// The [`Uuid`] of the peer the signal is sent to.
+    pub recipient: Uuid,
+
+    /// The step of the call.
+    pub kind: CallSignalKind,
+}
+
+/// The step of a call signaled with a [`CallSignal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum CallSignalKind {
+    /// The caller invites the recipient to the call.
+    Invite,
+
+    /// The invited recipient is alerting its user.
+    Ringing,
+
+    /// The invited recipient has accepted the call.
+    Accept,
+
+    /// The invited recipient has rejected the call.
+    Reject,
+
+    /// The invited recipient is already in another call.
+    Busy,
+
+    /// Either side has ended the call (or the caller has cancelled the invitation).
+    Hangup,
+
+    /// The invited recipient doesn't accept calls, as its [`PresenceState`] is [`PresenceState::DoNotDisturb`].
+    /// This is sent by the server on behalf of the recipient.
+    DoNotDisturb,
+}
+
+///
+/// Tone event type definition.
+///
+/// Describes a tone signaled out of band instead of being mixed into the voice.
+/// The ids of the DTMF tones match the event codes of [RFC 4733](https://www.rfc-editor.org/rfc/rfc4733), so that the events can be passed to telephony gateways unchanged.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ToneEvent {
+    /// The id of the tone, `0`-`15` are the DTMF tones (see [`ToneEvent::dtmf`]), the other ids are application defined.
+    pub tone: u8,
+
+    /// The duration of the tone, in milliseconds.
+    pub duration_ms: u32,
+}
+
+impl ToneEvent {
+    /// The DTMF digits, indexed by their tone id.
+    const DTMF_DIGITS: [char; 16] = [
+        '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '*', '#', 'A', 'B', 'C', 'D',
+    ];
+
+    /// Creates a new [`ToneEvent`] instance.
+    pub fn new(tone: u8, duration_ms: u32) -> Self {
+        Self { tone, duration_ms }
+    }
+
+    /// Creates the [`ToneEvent`] of a DTMF `digit` (`0`-`9`, `*`, `#` or `A`-`D`).
+    /// Returns [`None`] if the `digit` isn't a DTMF digit.
+    pub fn dtmf(digit: char, duration_ms: u32) -> Option<Self> {
+        Self::DTMF_DIGITS
+            .iter()
+            .position(|dtmf_digit| *dtmf_digit == digit.to_ascii_uppercase())
+            .map(|tone| Self::new(tone as u8, duration_ms))
+    }
+
+    /// Returns the DTMF digit of the tone, or [`None`] if the tone is application defined.
+    pub fn dtmf_digit(&self) -> Option<char> {
+        Self::DTMF_DIGITS.get(self.tone as usize).copied()
+    }
+}
+
+///
+/// Connection quality report type definition.
+///
+/// Describes the quality of the stream a peer has received since its last report.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct QualityReport {
+    /// The amount of messages received since the last report.
+    pub packets_received: u64,
+
+    /// The amount of messages lost since the last report.
+    pub packets_lost: u64,
+
+    /// The interarrival jitter of the received messages, in milliseconds.
+    pub jitter_ms: u32,
+
+    /// The round trip time measured with the remote address, in milliseconds.
+    pub round_trip_time_ms: u32,
+
+    /// The amount of messages received with the ECN-CE (congestion experienced) codepoint since the last report.
+    /// These messages weren't lost, but a router on the path was about to drop them, so their sender should lower its bitrate.
+    pub packets_ce: u64,
+}
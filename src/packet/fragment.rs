@@ -0,0 +1,253 @@
+//!
+//! Provides the fragmentation of frames which dont fit in a single message (for example video frames), and their reassembly.
+//!
+//! Every fragment is a complete message, with the [`HeaderFlags::FIRST_FRAGMENT`] flag set on the first, and the [`HeaderFlags::LAST_FRAGMENT`] flag set on the last fragment of the frame.
+//! Messages which aren't fragmented only have the [`HeaderFlags::LAST_FRAGMENT`] flag set, so the [`Reassembler`] passes them through unchanged.
+//!
+//! The video frames encoded in slices are fragmented slice by slice (see [`VideoFrame::to_messages`]), so a lost fragment only discards its slice.
+//! The reassembled slices are collected into their frames by the [`SliceReassembler`].
+//!
+
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use uuid::Uuid;
+
+use super::{
+    codec::{CodecError, DefaultCodec, HeaderCodec},
+    frame::VideoFrame,
+    HeaderFlags, VoipHeader, VoipPacket,
+};
+use crate::MTU_MAX_PACKET_SIZE;
+
+/// The maximum size of a frame the [`Reassembler`] buffers, larger frames are discarded.
+/// This prevents a sender from exhausting the memory of the receiver with a frame which never ends.
+pub const MAX_REASSEMBLED_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+///
+/// Splits the `data` described by the [`VoipHeader`] into messages which fit in [`MTU_MAX_PACKET_SIZE`].
+///
+/// # Behavior
+/// Every fragment has the same header as the `voip_header` (except for its body length), with the fragment flags set.
+/// The first fragment has the [`HeaderFlags::FIRST_FRAGMENT`] flag, the last fragment has the [`HeaderFlags::LAST_FRAGMENT`] flag set, a frame which fits in a single message has both of them.
+///
+/// # Error
+/// Returns an error if the header could not be serialized.
+///
+pub fn fragment_message(
+    voip_header: &VoipHeader,
+    data: &[u8],
+) -> Result<Vec<VoipPacket>, CodecError> {
+    fragment_message_with::<DefaultCodec>(voip_header, data)
+}
+
+/// Splits the `data` into messages like [`fragment_message`], but serializes the headers with the given [`HeaderCodec`].
+pub fn fragment_message_with<C: HeaderCodec>(
+    voip_header: &VoipHeader,
+    data: &[u8],
+) -> Result<Vec<VoipPacket>, CodecError> {
+    let mut base_flags = voip_header.flags();
+
+    base_flags.set(
+        HeaderFlags::FIRST_FRAGMENT | HeaderFlags::LAST_FRAGMENT,
+        false,
+    );
+
+    //Measure the largest header a fragment can have, so that every fragment fits in the MTU
+    let largest_header = voip_header
+        .clone()
+        .with_voip_message_type(
+            voip_header
+                .voip_message_type()
+                .with_body_length(MTU_MAX_PACKET_SIZE as u64),
+        )
+        .with_flags(base_flags | HeaderFlags::FIRST_FRAGMENT | HeaderFlags::LAST_FRAGMENT);
+    let fragment_size = MTU_MAX_PACKET_SIZE
+        .saturating_sub(C::encode(&largest_header)?.len())
+        .max(1);
+
+    let fragments: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(fragment_size).collect()
+    };
+
+    let fragment_count = fragments.len();
+
+    fragments
+        .into_iter()
+        .enumerate()
+        .map(|(index, fragment)| {
+            let mut flags = base_flags;
+
+            flags.set(HeaderFlags::FIRST_FRAGMENT, index == 0);
+            flags.set(HeaderFlags::LAST_FRAGMENT, index + 1 == fragment_count);
+
+            voip_header
+                .clone()
+                .with_voip_message_type(
+                    voip_header
+                        .voip_message_type()
+                        .with_body_length(fragment.len() as u64),
+                )
+                .with_flags(flags)
+                .create_message_buffer_with::<C>(fragment)
+        })
+        .collect()
+}
+
+///
+/// Reassembler type definition.
+///
+/// Buffers the fragments of every author and channel, until the last fragment of the frame arrives.
+/// Fragments are expected in order, as they are sent by [`fragment_message`].
+///
+#[derive(Debug, Clone, Default)]
+pub struct Reassembler {
+    /// The fragments received so far, by the author and the channel of the frame.
+    partial_frames: BTreeMap<(Uuid, u32), Vec<u8>>,
+}
+
+impl Reassembler {
+    /// Creates a new [`Reassembler`] instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Pushes a fragment into the reassembler.
+    ///
+    /// # Behavior
+    /// Returns the whole frame if the fragment completed it, or [`None`] if more fragments are needed.
+    /// A frame is discarded if its first fragment was lost, if a new frame starts before it was completed, or if it grows larger than [`MAX_REASSEMBLED_FRAME_SIZE`].
+    ///
+    pub fn push(&mut self, voip_header: &VoipHeader, data: Vec<u8>) -> Option<Vec<u8>> {
+        let key = (voip_header.author(), voip_header.channel());
+        let is_first = voip_header.flags().contains(HeaderFlags::FIRST_FRAGMENT);
+        let is_last = voip_header.is_last_fragment();
+
+        let frame = if is_first {
+            //A new frame discards the incomplete one
+            self.partial_frames.remove(&key);
+
+            data
+        } else {
+            match self.partial_frames.remove(&key) {
+                Some(mut partial_frame) => {
+                    partial_frame.extend(data);
+
+                    partial_frame
+                }
+                //Messages which aren't fragmented are complete on their own
+                None if is_last => return Some(data),
+                //The beginning of the frame was lost
+                None => return None,
+            }
+        };
+
+        if is_last {
+            return Some(frame);
+        }
+
+        if frame.len() <= MAX_REASSEMBLED_FRAME_SIZE {
+            self.partial_frames.insert(key, frame);
+        }
+
+        None
+    }
+
+    /// Discards the incomplete frames of the `author` (for example when the author has left the session).
+    pub fn remove_author(&mut self, author: Uuid) {
+        self.partial_frames
+            .retain(|(frame_author, _), _| *frame_author != author);
+    }
+}
+
+///
+/// Slice reassembler type definition.
+///
+/// Collects the slices of the video frames encoded in slices, after their fragments were reassembled by a [`Reassembler`].
+/// Slices are expected in order, as they are sent by [`VideoFrame::to_messages`].
+///
+#[derive(Debug, Clone, Default)]
+pub struct SliceReassembler {
+    /// The frames whose slices are still arriving, by their author and channel.
+    partial_frames: BTreeMap<(Uuid, u32), VideoFrame>,
+}
+
+impl SliceReassembler {
+    /// Creates a new [`SliceReassembler`] instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Pushes a reassembled video message into the reassembler.
+    ///
+    /// # Behavior
+    /// Returns the frames finished by the message, which are at most two.
+    /// A frame is finished once every slice of it has arrived, or once a slice of the next frame arrives, in which case it is returned without its lost slices (see [`VideoSlices::is_complete`](super::frame::VideoSlices::is_complete)).
+    /// Messages which don't carry a slice are returned as whole frames, a frame is discarded if it grows larger than [`MAX_REASSEMBLED_FRAME_SIZE`].
+    ///
+    pub fn push(&mut self, voip_header: &VoipHeader, data: Vec<u8>) -> Vec<VideoFrame> {
+        let slice_frame = VideoFrame::from_message(voip_header, data);
+
+        let Some(slice) = voip_header.slice() else {
+            return vec![slice_frame];
+        };
+
+        let key = (voip_header.author(), voip_header.channel());
+        let mut finished_frames = vec![];
+
+        let partial_frame = self.partial_frames.remove(&key).and_then(|partial_frame| {
+            let is_same_frame = partial_frame.sequence == slice_frame.sequence
+                && partial_frame.slices.as_ref().is_some_and(|slices| {
+                    slices.count == slice.count
+                        && slices
+                            .lengths
+                            .last()
+                            .is_some_and(|(index, _)| *index < slice.index)
+                });
+
+            if is_same_frame {
+                return Some(partial_frame);
+            }
+
+            //The slice belongs to the next frame, so the slices still missing were lost
+            finished_frames.push(partial_frame);
+
+            None
+        });
+
+        let frame = match partial_frame {
+            Some(mut partial_frame) => {
+                if let (Some(slices), Some(slice_slices)) =
+                    (partial_frame.slices.as_mut(), slice_frame.slices)
+                {
+                    slices.lengths.extend(slice_slices.lengths);
+                }
+
+                partial_frame.payload.extend(slice_frame.payload);
+
+                partial_frame
+            }
+            None => slice_frame,
+        };
+
+        if frame
+            .slices
+            .as_ref()
+            .is_some_and(|slices| slices.is_complete())
+        {
+            finished_frames.push(frame);
+        } else if frame.payload.len() <= MAX_REASSEMBLED_FRAME_SIZE {
+            self.partial_frames.insert(key, frame);
+        }
+
+        finished_frames
+    }
+
+    /// Discards the unfinished frames of the `author` (for example when the author has left the session).
+    pub fn remove_author(&mut self, author: Uuid) {
+        self.partial_frames
+            .retain(|(frame_author, _), _| *frame_author != author);
+    }
+}
@@ -0,0 +1,408 @@
+//!
+//! Provides the [`VoiceFrame`] and [`VideoFrame`] types, which carry an encoded frame together with its metadata.
+//!
+//! The frames are used by both the sending and the receiving APIs, so the payload, the timing and the author of a frame travel together instead of being threaded by hand.
+//! A frame is converted into a [`VoipHeader`] with [`VoiceFrame::to_header`] (or [`VideoFrame::to_header`]), and back with [`VoiceFrame::from_message`] (or [`VideoFrame::from_message`]).
+//! Video frames encoded in independent slices (or tiles) are sent as a message per slice (see [`VideoFrame::to_messages`]), so a lost message only corrupts a part of the frame.
+//!
+
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+
+use uuid::Uuid;
+
+use super::{HeaderFlags, MediaCodec, Position, SliceInfo, VoipHeader, VoipMessageType};
+
+/// Converts a media time into the microseconds carried by [`VoipHeader::timestamp`].
+pub(crate) fn duration_to_micros(duration: Duration) -> u64 {
+    duration.as_micros().min(u64::MAX as u128) as u64
+}
+
+///
+/// Voice frame type definition.
+///
+/// An encoded voice frame, and the metadata describing it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoiceFrame {
+    /// The author of the voice frame.
+    pub author: Uuid,
+
+    /// The channel (or room) the voice frame belongs to.
+    pub channel: u32,
+
+    /// The [`MediaCodec`] the payload is encoded with.
+    pub codec: MediaCodec,
+
+    /// The sequence number of the voice frame, if the sender has set one.
+    pub sequence: Option<u32>,
+
+    /// The media time of the voice frame since the start of the author's stream, if the sender has set one.
+    pub timestamp: Option<Duration>,
+
+    /// The duration of the audio the voice frame contains, if it is known.
+    /// This is not sent over the wire, the receivers calculate it from the payload.
+    pub duration: Option<Duration>,
+
+    /// The level of the audio in -dBov, if the sender has set one.
+    pub audio_level: Option<u8>,
+
+    /// The [`Position`] of the author, if the sender has set one.
+    pub position: Option<Position>,
+
+    /// The encoded bytes of the voice frame.
+    /// If frames are aggregated, this holds the consecutive frames one after the other (see [`VoiceFrame::frames`]).
+    pub payload: Vec<u8>,
+
+    /// The lengths of the frames aggregated into the payload except the last one, [`None`] if the payload is a single frame.
+    pub frame_lengths: Option<Vec<u16>>,
+
+    /// The redundant (usually lower bitrate) copy of the previous frame of the stream, if the sender has attached one.
+    /// The receivers decode it instead of concealing the previous frame, if that was lost.
+    pub redundant_payload: Option<Vec<u8>>,
+
+    /// The audio stream of the author the voice frame belongs to, [`None`] being the default stream.
+    pub stream: Option<u8>,
+
+    /// The device of the author the voice frame was sent from, [`None`] being the author's first device.
+    pub device: Option<u32>,
+}
+
+impl VoiceFrame {
+    /// Creates a new [`VoiceFrame`] instance with the encoded `payload`, on the channel `0` and without any optional metadata.
+    pub fn new(author: Uuid, codec: MediaCodec, payload: Vec<u8>) -> Self {
+        Self {
+            author,
+            channel: 0,
+            codec,
+            sequence: None,
+            timestamp: None,
+            duration: None,
+            audio_level: None,
+            position: None,
+            payload,
+            frame_lengths: None,
+            redundant_payload: None,
+            stream: None,
+            device: None,
+        }
+    }
+
+    /// Creates a [`VoiceFrame`] from the [`VoipHeader`] and the body of a received voice message.
+    /// The redundant copy of the previous frame is split off the body, if the message carries one.
+    pub fn from_message(voip_header: &VoipHeader, mut payload: Vec<u8>) -> Self {
+        let redundant_payload = match voip_header.split_redundancy(&payload) {
+            (frame, Some(_)) => Some(payload.split_off(frame.len())),
+            (_, None) => None,
+        };
+
+        Self {
+            author: voip_header.author(),
+            channel: voip_header.channel(),
+            codec: voip_header.codec(),
+            sequence: voip_header.sequence(),
+            timestamp: voip_header.timestamp().map(Duration::from_micros),
+            duration: None,
+            audio_level: voip_header.audio_level(),
+            position: voip_header.position(),
+            payload,
+            frame_lengths: voip_header.frame_lengths().map(<[u16]>::to_vec),
+            redundant_payload,
+            stream: voip_header.stream(),
+            device: voip_header.device(),
+        }
+    }
+
+    /// Creates the [`VoipHeader`] of the voice message carrying this frame.
+    /// The body of the message is created with [`VoiceFrame::to_body`].
+    pub fn to_header(&self) -> VoipHeader {
+        let redundancy = self
+            .redundant_payload
+            .as_ref()
+            .map(|redundant_payload| redundant_payload.len() as u16);
+
+        let mut voip_header = VoipHeader::new(
+            VoipMessageType::VoiceMessage(
+                (self.payload.len() + redundancy.unwrap_or_default() as usize) as u64,
+            ),
+            self.author,
+        )
+        .with_codec(self.codec)
+        .with_channel(self.channel)
+        .with_redundancy(redundancy)
+        .with_stream(self.stream)
+        .with_device(self.device)
+        .with_frame_lengths(self.frame_lengths.clone());
+
+        if let Some(sequence) = self.sequence {
+            voip_header = voip_header.with_sequence(sequence);
+        }
+
+        if let Some(timestamp) = self.timestamp {
+            voip_header = voip_header.with_timestamp(duration_to_micros(timestamp));
+        }
+
+        if let Some(audio_level) = self.audio_level {
+            voip_header = voip_header.with_audio_level(audio_level);
+        }
+
+        if let Some(position) = self.position {
+            voip_header = voip_header.with_position(position);
+        }
+
+        voip_header
+    }
+
+    ///
+    /// Aggregates the consecutive `frames` of a stream into a single [`VoiceFrame`], so they are sent in a single message.
+    ///
+    /// # Behavior
+    /// The sequence number, the timestamp and the redundant copy of the first frame are kept, the durations are summed and the loudest audio level is kept.
+    /// Returns [`None`] if there are no frames, and the frame itself if there is only one.
+    ///
+    pub fn aggregate(frames: Vec<VoiceFrame>) -> Option<Self> {
+        let mut frames = frames.into_iter();
+        let mut aggregated_frame = frames.next()?;
+        let mut last_frame_length = aggregated_frame.payload.len();
+
+        for frame in frames {
+            aggregated_frame
+                .frame_lengths
+                .get_or_insert_with(Vec::new)
+                .push(last_frame_length as u16);
+
+            last_frame_length = frame.payload.len();
+
+            aggregated_frame.payload.extend(frame.payload);
+            aggregated_frame.duration = aggregated_frame
+                .duration
+                .zip(frame.duration)
+                .map(|(duration, frame_duration)| duration + frame_duration);
+
+            //The lower level is the louder one
+            aggregated_frame.audio_level = aggregated_frame
+                .audio_level
+                .into_iter()
+                .chain(frame.audio_level)
+                .min();
+        }
+
+        Some(aggregated_frame)
+    }
+
+    /// Returns the frames aggregated into the payload, a single frame if the payload isn't aggregated.
+    pub fn frames(&self) -> Vec<&[u8]> {
+        self.to_header().split_frames(&self.payload)
+    }
+
+    /// Creates the body of the voice message carrying this frame, the payload followed by the redundant copy of the previous frame.
+    pub fn to_body(&self) -> Vec<u8> {
+        match &self.redundant_payload {
+            Some(redundant_payload) => {
+                [self.payload.as_slice(), redundant_payload.as_slice()].concat()
+            }
+            None => self.payload.clone(),
+        }
+    }
+}
+
+///
+/// Video frame type definition.
+///
+/// An encoded video frame, and the metadata describing it.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoFrame {
+    /// The author of the video frame.
+    pub author: Uuid,
+
+    /// The channel (or room) the video frame belongs to.
+    pub channel: u32,
+
+    /// The [`MediaCodec`] the payload is encoded with.
+    pub codec: MediaCodec,
+
+    /// The sequence number of the video frame, if the sender has set one.
+    pub sequence: Option<u32>,
+
+    /// The media time of the video frame since the start of the author's stream, if the sender has set one.
+    pub timestamp: Option<Duration>,
+
+    /// The duration the video frame is displayed for, if it is known.
+    /// This is not sent over the wire, the receivers can calculate it from the timestamp of the next frame.
+    pub duration: Option<Duration>,
+
+    /// Whether the video frame can be decoded on its own (it is sent with the [`HeaderFlags::MARKER`] flag).
+    pub is_keyframe: bool,
+
+    /// The simulcast layer of the video frame, if the author sends multiple layers (`0` being the lowest quality).
+    pub layer: Option<u8>,
+
+    /// The encoded bytes of the video frame.
+    /// The payload of a frame encoded in slices is the concatenation of its slices, without the ones which were lost.
+    pub payload: Vec<u8>,
+
+    /// The slices of the payload, if the frame was encoded in independent slices (or tiles), see [`VideoSlices`].
+    pub slices: Option<VideoSlices>,
+}
+
+///
+/// Video slices type definition.
+///
+/// Describes the slices (or tiles) the payload of a [`VideoFrame`] is made of.
+/// The decoders can decode the slices which have arrived, and conceal the part of the frame covered by the lost ones.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct VideoSlices {
+    /// The amount of slices the frame was encoded in.
+    pub count: u16,
+
+    /// The index and the length of every slice of the payload, in the order they follow each other in the payload.
+    /// The lost slices are missing.
+    pub lengths: Vec<(u16, usize)>,
+}
+
+impl VideoSlices {
+    /// Returns whether every slice of the frame is present.
+    pub fn is_complete(&self) -> bool {
+        self.lengths.len() == self.count as usize
+    }
+}
+
+impl VideoFrame {
+    /// Creates a new [`VideoFrame`] instance with the encoded `payload`, on the channel `0` and without any optional metadata.
+    pub fn new(author: Uuid, codec: MediaCodec, is_keyframe: bool, payload: Vec<u8>) -> Self {
+        Self {
+            author,
+            channel: 0,
+            codec,
+            sequence: None,
+            timestamp: None,
+            duration: None,
+            is_keyframe,
+            layer: None,
+            payload,
+            slices: None,
+        }
+    }
+
+    /// Creates a new [`VideoFrame`] instance from the encoded `slices` of the frame, like [`VideoFrame::new`].
+    /// A single slice is sent as a whole frame.
+    pub fn from_slices(
+        author: Uuid,
+        codec: MediaCodec,
+        is_keyframe: bool,
+        slices: Vec<Vec<u8>>,
+    ) -> Self {
+        let lengths: Vec<(u16, usize)> = slices
+            .iter()
+            .enumerate()
+            .map(|(index, slice)| (index as u16, slice.len()))
+            .collect();
+
+        let mut video_frame = Self::new(author, codec, is_keyframe, slices.concat());
+
+        if lengths.len() > 1 {
+            video_frame.slices = Some(VideoSlices {
+                count: lengths.len() as u16,
+                lengths,
+            });
+        }
+
+        video_frame
+    }
+
+    /// Creates a [`VideoFrame`] from the [`VoipHeader`] and the (reassembled) body of a received video message.
+    pub fn from_message(voip_header: &VoipHeader, payload: Vec<u8>) -> Self {
+        Self {
+            author: voip_header.author(),
+            channel: voip_header.channel(),
+            codec: voip_header.codec(),
+            sequence: voip_header.sequence(),
+            timestamp: voip_header.timestamp().map(Duration::from_micros),
+            duration: None,
+            is_keyframe: voip_header.is_marker(),
+            layer: voip_header.layer(),
+            slices: voip_header.slice().map(|slice| VideoSlices {
+                count: slice.count,
+                lengths: vec![(slice.index, payload.len())],
+            }),
+            payload,
+        }
+    }
+
+    /// Returns the slices of the payload with their indices, a frame which wasn't encoded in slices is a single slice.
+    pub fn slice_payloads(&self) -> Vec<(u16, &[u8])> {
+        let Some(slices) = &self.slices else {
+            return vec![(0, self.payload.as_slice())];
+        };
+
+        let mut slice_payloads = Vec::with_capacity(slices.lengths.len());
+        let mut rest = self.payload.as_slice();
+
+        for (index, length) in &slices.lengths {
+            let (slice_payload, remaining) = rest.split_at((*length).min(rest.len()));
+
+            slice_payloads.push((*index, slice_payload));
+            rest = remaining;
+        }
+
+        slice_payloads
+    }
+
+    /// Creates the [`VoipHeader`]s of the video messages carrying this frame, with the part of the payload each of them carries.
+    /// A frame encoded in slices is carried by a message per slice, tagged with its [`SliceInfo`], otherwise the frame is carried by the single message of [`VideoFrame::to_header`].
+    /// Every message can be split with [`fragment_message`](super::fragment::fragment_message).
+    pub fn to_messages(&self) -> Vec<(VoipHeader, &[u8])> {
+        let Some(slices) = &self.slices else {
+            return vec![(self.to_header(), self.payload.as_slice())];
+        };
+
+        let voip_header = self.to_header();
+
+        self.slice_payloads()
+            .into_iter()
+            .map(|(index, slice_payload)| {
+                (
+                    voip_header
+                        .clone()
+                        .with_voip_message_type(VoipMessageType::VideoMessage(
+                            slice_payload.len() as u64
+                        ))
+                        .with_slice(Some(SliceInfo {
+                            index,
+                            count: slices.count,
+                        })),
+                    slice_payload,
+                )
+            })
+            .collect()
+    }
+
+    /// Creates the [`VoipHeader`] of the video message carrying this frame.
+    /// The header describes the whole frame, it can be split with [`fragment_message`](super::fragment::fragment_message).
+    pub fn to_header(&self) -> VoipHeader {
+        let mut flags = HeaderFlags::default();
+
+        flags.set(HeaderFlags::MARKER, self.is_keyframe);
+
+        let mut voip_header = VoipHeader::new(
+            VoipMessageType::VideoMessage(self.payload.len() as u64),
+            self.author,
+        )
+        .with_codec(self.codec)
+        .with_channel(self.channel)
+        .with_flags(flags)
+        .with_layer(self.layer);
+
+        if let Some(sequence) = self.sequence {
+            voip_header = voip_header.with_sequence(sequence);
+        }
+
+        if let Some(timestamp) = self.timestamp {
+            voip_header = voip_header.with_timestamp(duration_to_micros(timestamp));
+        }
+
+        voip_header
+    }
+}
@@ -2,27 +2,267 @@
 //!  A feature provides functions and abstractions for creating for sending packets.
 //!
 
+use alloc::vec::Vec;
+use bytes::Bytes;
 use uuid::Uuid;
 
+use crate::MTU_MAX_PACKET_SIZE;
+
+use caption::CaptionInfo;
+use codec::{CodecError, DefaultCodec, HeaderCodec};
+use control::ControlMessage;
+
+pub mod caption;
+pub mod codec;
+pub mod conformance;
+pub mod control;
+pub mod fragment;
+pub mod frame;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+
+/// The size of the length prefix every message buffer starts with.
+/// The length is always encoded as a big endian [`u64`], so that peers with different pointer widths can communicate.
+pub(crate) const LENGTH_PREFIX_SIZE: usize = core::mem::size_of::<u64>();
+
+/// The longest serialized [`VoipHeader`] a message may contain.
+/// Longer headers are rejected when creating and decoding a message, so only the start of a malformed message is ever deserialized.
+pub const MAX_HEADER_SIZE: usize = 512;
+
 /// Voip message variant type definition.
 /// This enum contains the message variants the [`VoipPacket`] can contain.
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum VoipMessageType {
     /// This message type contains the length of the data of an Audio recording.
-    #[cfg(feature = "voice")]
     VoiceMessage(u64),
 
     /// This message type contains the length of the data of an Image.
-    #[cfg(feature = "video")]
     VideoMessage(u64),
+
+    /// This message type contains the length of an UTF-8 encoded text message.
+    TextMessage(u64),
+
+    /// This message type contains the length of an UTF-8 encoded live caption, and the [`CaptionInfo`] of the caption (see [`Caption`](caption::Caption)).
+    CaptionMessage(u64, CaptionInfo),
+
+    /// This message type contains a [`ControlMessage`].
+    /// Control messages have no data following the header.
+    Control(ControlMessage),
+}
+
+impl VoipMessageType {
+    /// Returns the length of the data which follows the header of this message type.
+    pub fn body_length(&self) -> u64 {
+        match self {
+            VoipMessageType::VoiceMessage(length) => *length,
+            VoipMessageType::VideoMessage(length) => *length,
+            VoipMessageType::TextMessage(length) => *length,
+            VoipMessageType::CaptionMessage(length, _) => *length,
+            VoipMessageType::Control(_) => 0,
+        }
+    }
+
+    /// Returns the same message type with the given body `length`.
+    /// [`VoipMessageType::Control`] is returned unchanged, as control messages have no body.
+    pub fn with_body_length(&self, length: u64) -> Self {
+        match self {
+            VoipMessageType::VoiceMessage(_) => VoipMessageType::VoiceMessage(length),
+            VoipMessageType::VideoMessage(_) => VoipMessageType::VideoMessage(length),
+            VoipMessageType::TextMessage(_) => VoipMessageType::TextMessage(length),
+            VoipMessageType::CaptionMessage(_, caption_info) => {
+                VoipMessageType::CaptionMessage(length, *caption_info)
+            }
+            VoipMessageType::Control(control_message) => {
+                VoipMessageType::Control(control_message.clone())
+            }
+        }
+    }
+}
+
+/// The codec the data of a message is encoded with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum MediaCodec {
+    /// The data is not encoded with a known codec (for example text, or a custom format).
+    #[default]
+    Raw,
+
+    /// The data is an [Opus](https://opus-codec.org/) packet.
+    Opus,
+
+    /// The data is an [AVIF](https://en.wikipedia.org/wiki/AVIF) image.
+    Avif,
+
+    /// The data is encoded with an application defined codec, identified by the contained id.
+    Custom(u16),
+}
+
+///
+/// Position type definition.
+///
+/// A position in 3D space, in millimetres, which can be attached to a [`VoipHeader`] for positional (spatial) audio.
+/// The coordinates are stored as integers, so that the header stays comparable and serializes the same way with every codec.
+/// The `x` axis points right, the `y` axis points up, and the `z` axis points forward.
+///
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Position {
+    /// The coordinate on the `x` (right) axis, in millimetres.
+    pub x: i32,
+
+    /// The coordinate on the `y` (up) axis, in millimetres.
+    pub y: i32,
+
+    /// The coordinate on the `z` (forward) axis, in millimetres.
+    pub z: i32,
+}
+
+impl Position {
+    /// Creates a new [`Position`] instance from coordinates in millimetres.
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Creates a new [`Position`] instance from coordinates in metres.
+    /// The coordinates are rounded towards zero to the nearest millimetre.
+    pub fn from_meters(x: f32, y: f32, z: f32) -> Self {
+        Self {
+            x: (x * 1000.) as i32,
+            y: (y * 1000.) as i32,
+            z: (z * 1000.) as i32,
+        }
+    }
+
+    /// Returns the coordinates in metres.
+    pub fn to_meters(&self) -> [f32; 3] {
+        [
+            self.x as f32 / 1000.,
+            self.y as f32 / 1000.,
+            self.z as f32 / 1000.,
+        ]
+    }
+}
+
+///
+/// Slice info type definition.
+///
+/// The position of a slice (or tile) in its video frame, which is attached to the [`VoipHeader`] of every message carrying a slice.
+/// The slices of a frame are encoded independently, so a lost slice only corrupts its part of the frame.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct SliceInfo {
+    /// The index of the slice in the frame, from `0` to `count - 1`.
+    pub index: u16,
+
+    /// The amount of slices the frame was split into.
+    pub count: u16,
+}
+
+///
+/// Header flags type definition.
+///
+/// A bitset of the flags a [`VoipHeader`] can have set.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct HeaderFlags(u8);
+
+impl HeaderFlags {
+    /// No flags are set.
+    pub const EMPTY: Self = Self(0);
+
+    /// Marks a significant message of the stream, like the first frame of a talkspurt or a keyframe.
+    pub const MARKER: Self = Self(1);
+
+    /// Marks the last fragment of a frame.
+    /// Messages which aren't fragmented always have this flag set.
+    pub const LAST_FRAGMENT: Self = Self(1 << 1);
+
+    /// Marks the first fragment of a frame, so that the receiver can tell if the beginning of a frame was lost.
+    /// This is set by [`fragment_message`](fragment::fragment_message) on the first message of every frame.
+    pub const FIRST_FRAGMENT: Self = Self(1 << 2);
+
+    /// Marks a padding message, whose body is filler (for example the bursts of a bandwidth probe).
+    /// The receivers discard the padding after accounting for its size, the server never forwards it.
+    pub const PADDING: Self = Self(1 << 3);
+
+    /// Marks the empty voice message ending an audio stream, which carries the next sequence number of the stream.
+    /// The receivers release the state of the stream (for example its decoder and jitter buffer) right away, instead of waiting for it to time out.
+    pub const END_OF_STREAM: Self = Self(1 << 4);
+
+    /// Returns whether every flag of `other` is set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Sets or clears every flag of `other`.
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+
+    /// Returns the raw bits of the flags.
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Creates the flags from raw bits.
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+impl Default for HeaderFlags {
+    fn default() -> Self {
+        Self::LAST_FRAGMENT
+    }
+}
+
+impl core::ops::BitOr for HeaderFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Custom packet decoding errors.
+#[derive(thiserror::Error, Debug)]
+pub enum PacketError {
+    /// This error is thrown when the message buffer is too short to contain the length prefix.
+    #[error("The message is too short to contain a length prefix.")]
+    MissingLength,
+
+    /// This error is thrown when the length prefix exceeds [`MTU_MAX_PACKET_SIZE`].
+    #[error("Message header with too large length: {0}.")]
+    TooLarge(u64),
+
+    /// This error is thrown when the header declares a body longer than [`MTU_MAX_PACKET_SIZE`].
+    #[error("Message header with too large body length: {0}.")]
+    BodyTooLarge(u64),
+
+    /// This error is thrown when the length prefix or the header doesn't match the actual length of the message.
+    #[error("Message length mismatch, expected {expected} bytes, got {actual}.")]
+    LengthMismatch {
+        /// The length the message claimed to have.
+        expected: usize,
+        /// The actual length of the message.
+        actual: usize,
+    },
+
+    /// This error is thrown when the [`VoipHeader`] could not be deserialized.
+    #[error("{0}")]
+    Codec(#[from] CodecError),
 }
 
 ///
 ///  Struct definition for a Voip packet.
 ///
-/// This Packet can contain a [`VoipMessageType::VoiceMessage`] or a [`VoipMessageType::VideoMessage`], with the author's [`Uuid`].
+/// This Packet can contain any [`VoipMessageType`] (for example a [`VoipMessageType::VoiceMessage`] or a [`VoipMessageType::VideoMessage`]), with the author's [`Uuid`].
 ///
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct VoipHeader {
     /// The [`VoipMessageType`] of this packet.
     /// Can either be a Voice packet or a Video packet.
@@ -31,45 +271,261 @@ pub struct VoipHeader {
     /// The author of this packet.
     /// This can be used to identify the sender of the [`VoipPacket`].
     author: Uuid,
+
+    /// The [`MediaCodec`] the data of this packet is encoded with.
+    codec: MediaCodec,
+
+    /// The channel (or room) this packet belongs to.
+    /// This can be used to route the [`VoipPacket`] to the right recipients.
+    channel: u32,
+
+    /// The [`HeaderFlags`] of this packet.
+    flags: HeaderFlags,
+
+    /// The level of the audio this packet carries in -dBov, from [`AUDIO_LEVEL_LOUDEST`] to [`AUDIO_LEVEL_SILENCE`] (like the RTP audio level extension).
+    /// This lets the receivers (for example the server) judge the loudness of a voice packet without decoding it.
+    audio_level: Option<u8>,
+
+    /// The [`Position`] of the source of this packet (for example the avatar of the speaker in a game).
+    position: Option<Position>,
+
+    /// The sequence number of the frame this packet belongs to, which is incremented for every frame of the author's stream.
+    /// The fragments of a frame share the same sequence number.
+    sequence: Option<u32>,
+
+    /// The media time of the frame this packet belongs to, in microseconds since the start of the author's stream.
+    timestamp: Option<u64>,
+
+    /// The length of the redundant copy of the previous frame, which is appended to the body of a voice message (like RFC 2198).
+    /// The receivers can decode the copy if the previous frame was lost, without waiting for a retransmission.
+    redundancy: Option<u16>,
+
+    /// The simulcast layer of a video message, `0` being the lowest quality.
+    /// The server forwards a single layer of every author to each receiver (see [`ControlMessage::SelectLayer`](control::ControlMessage::SelectLayer)).
+    layer: Option<u8>,
+
+    /// The audio stream of a voice message, when the author publishes more than one (for example a microphone and the audio of a shared screen).
+    /// Every stream is encoded and decoded independently, [`None`] is the author's default stream `0`.
+    stream: Option<u8>,
+
+    /// The lengths of the frames aggregated into the body of a voice message, except the last frame which takes the rest of the body.
+    /// Aggregating the frames of consecutive sequence numbers into a single message saves the overhead of their headers on low bitrate links, [`None`] marks the body as a single frame.
+    frame_lengths: Option<Vec<u16>>,
+
+    /// The slice of the video frame the body of a video message carries, if the frame was packetized by slices (see [`SliceInfo`]).
+    /// Every slice is fragmented on its own, and the slices of a frame share its sequence number.
+    slice: Option<SliceInfo>,
+
+    /// The device of the author a message was sent from, when the author is connected from more than one device (see [`DeviceNotice`](control::DeviceNotice)).
+    /// The receivers keep the streams of every device apart, [`None`] is the author's first device `0`.
+    device: Option<u32>,
 }
 
+/// The audio level of the loudest possible audio (0 dBov).
+pub const AUDIO_LEVEL_LOUDEST: u8 = 0;
+
+/// The audio level of silence (-127 dBov).
+pub const AUDIO_LEVEL_SILENCE: u8 = 127;
+
 /// Wrapper type for a buffer.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VoipPacket(Vec<u8>);
 
 impl VoipPacket {
+    /// Wraps a message buffer which was already validated (for example with [`decode_message`]).
+    #[cfg(feature = "server")]
+    pub(crate) fn from_validated(buffer: Vec<u8>) -> Self {
+        Self(buffer)
+    }
+
     /// Returns the inner buffer of this packet.
     pub fn inner(&self) -> &[u8] {
         &self.0
     }
+
+    ///
+    /// Parses a message buffer back into the [`VoipHeader`] and the data it describes.
+    ///
+    /// # Behavior
+    /// This is the inverse of [`VoipHeader::create_message_buffer`].
+    /// The returned data is a slice of the `bytes` passed in, so parsing doesn't copy the data.
+    ///
+    /// # Error
+    /// Returns an error if the length prefix is missing or too large, the header could not be deserialized or exceeds its limits, or the lengths dont match the actual size of the buffer.
+    ///
+    pub fn parse(bytes: impl Into<Bytes>) -> Result<(VoipHeader, Bytes), PacketError> {
+        Self::parse_with::<DefaultCodec>(bytes)
+    }
+
+    /// Parses a message buffer like [`VoipPacket::parse`], but deserializes the header with the given [`HeaderCodec`].
+    pub fn parse_with<C: HeaderCodec>(
+        bytes: impl Into<Bytes>,
+    ) -> Result<(VoipHeader, Bytes), PacketError> {
+        let bytes = bytes.into();
+
+        let (voip_header, body_offset) = split_message::<C>(&bytes)?;
+
+        Ok((voip_header, bytes.slice(body_offset..)))
+    }
+}
+
+impl From<VoipPacket> for Bytes {
+    fn from(voip_packet: VoipPacket) -> Self {
+        Bytes::from(voip_packet.0)
+    }
 }
 
 impl VoipHeader {
-    /// Creates a new [`VoipPacket`] instance.
+    /// Creates a new [`VoipHeader`] instance.
+    /// The header is created with the [`MediaCodec::Raw`] codec, on the channel `0`, and with the default [`HeaderFlags`].
     pub fn new(voip_message_type: VoipMessageType, author: Uuid) -> Self {
         Self {
             voip_message_type,
             author,
+            codec: MediaCodec::default(),
+            channel: 0,
+            flags: HeaderFlags::default(),
+            audio_level: None,
+            position: None,
+            sequence: None,
+            timestamp: None,
+            redundancy: None,
+            layer: None,
+            stream: None,
+            frame_lengths: None,
+            slice: None,
+            device: None,
         }
     }
 
+    /// Sets the [`MediaCodec`] the data of this packet is encoded with.
+    pub fn with_codec(mut self, codec: MediaCodec) -> Self {
+        self.codec = codec;
+
+        self
+    }
+
+    /// Sets the channel (or room) this packet belongs to.
+    pub fn with_channel(mut self, channel: u32) -> Self {
+        self.channel = channel;
+
+        self
+    }
+
+    /// Sets the [`VoipMessageType`] of this packet, keeping every other field.
+    pub fn with_voip_message_type(mut self, voip_message_type: VoipMessageType) -> Self {
+        self.voip_message_type = voip_message_type;
+
+        self
+    }
+
+    /// Sets the [`HeaderFlags`] of this packet.
+    pub fn with_flags(mut self, flags: HeaderFlags) -> Self {
+        self.flags = flags;
+
+        self
+    }
+
+    /// Sets the level of the audio this packet carries in -dBov.
+    /// Levels quieter than [`AUDIO_LEVEL_SILENCE`] are clamped to it.
+    pub fn with_audio_level(mut self, audio_level: u8) -> Self {
+        self.audio_level = Some(audio_level.min(AUDIO_LEVEL_SILENCE));
+
+        self
+    }
+
+    /// Sets the [`Position`] of the source of this packet.
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.position = Some(position);
+
+        self
+    }
+
+    /// Sets the sequence number of the frame this packet belongs to.
+    pub fn with_sequence(mut self, sequence: u32) -> Self {
+        self.sequence = Some(sequence);
+
+        self
+    }
+
+    /// Sets the media time of the frame this packet belongs to, in microseconds since the start of the stream.
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+
+        self
+    }
+
+    /// Sets the length of the redundant copy of the previous frame at the end of the body, [`None`] marks the body as a single frame.
+    /// The length is included in the body length of the [`VoipMessageType`].
+    pub fn with_redundancy(mut self, redundancy: Option<u16>) -> Self {
+        self.redundancy = redundancy;
+
+        self
+    }
+
+    /// Sets the simulcast layer of this packet, [`None`] marks the stream as a single layer.
+    pub fn with_layer(mut self, layer: Option<u8>) -> Self {
+        self.layer = layer;
+
+        self
+    }
+
+    /// Sets the audio stream of this packet, [`None`] marks the author's default stream.
+    pub fn with_stream(mut self, stream: Option<u8>) -> Self {
+        self.stream = stream;
+
+        self
+    }
+
+    /// Sets the lengths of the frames aggregated into the body of this packet except the last one, [`None`] marks the body as a single frame.
+    /// The sequence number and the timestamp of the packet belong to its first frame.
+    pub fn with_frame_lengths(mut self, frame_lengths: Option<Vec<u16>>) -> Self {
+        self.frame_lengths = frame_lengths;
+
+        self
+    }
+
+    /// Sets the slice of the video frame this packet carries, [`None`] marks the body as a whole frame.
+    pub fn with_slice(mut self, slice: Option<SliceInfo>) -> Self {
+        self.slice = slice;
+
+        self
+    }
+
+    /// Sets the device of the author this packet was sent from, [`None`] marks the author's first device.
+    pub fn with_device(mut self, device: Option<u32>) -> Self {
+        self.device = device;
+
+        self
+    }
+
     ///
     /// Creates a message buffer from a VoipPacket and the actual data.
     ///
     /// You must ensure that you are sending the correct set of bytes, matching the [VoipPacket::voip_message_type]'s variant.
+    /// The serialized header must not be longer than [`MAX_HEADER_SIZE`], so the messages of a [`CloseReason`](control::CloseReason) should be kept short.
     ///    
-    pub fn create_message_buffer(
+    pub fn create_message_buffer(&self, data: &[u8]) -> Result<VoipPacket, CodecError> {
+        self.create_message_buffer_with::<DefaultCodec>(data)
+    }
+
+    /// Creates a message buffer like [`VoipHeader::create_message_buffer`], but serializes the header with the given [`HeaderCodec`].
+    pub fn create_message_buffer_with<C: HeaderCodec>(
         &self,
         data: &[u8],
-    ) -> Result<VoipPacket, rmp_serde::encode::Error> {
+    ) -> Result<VoipPacket, CodecError> {
         //Create buffer
-        let mut buffer: Vec<u8> = vec![];
+        let mut buffer: Vec<u8> = Vec::new();
 
         //Serialize header
-        let serialized_packet = rmp_serde::to_vec(self)?;
+        let serialized_packet = C::encode(self)?;
+
+        if serialized_packet.len() > MAX_HEADER_SIZE {
+            return Err(CodecError::HeaderTooLarge(serialized_packet.len()));
+        }
 
         //Push length of the message
-        buffer.extend((serialized_packet.len() + data.len()).to_be_bytes());
+        buffer.extend(((serialized_packet.len() + data.len()) as u64).to_be_bytes());
 
         //Push serialized VoipPacket
         buffer.extend(serialized_packet);
@@ -84,4 +540,255 @@ impl VoipHeader {
     pub fn voip_message_type(&self) -> &VoipMessageType {
         &self.voip_message_type
     }
+
+    /// Fetches the author's [`Uuid`] of the [`VoipHeader`].
+    pub fn author(&self) -> Uuid {
+        self.author
+    }
+
+    /// Fetches the length of the data following the [`VoipHeader`].
+    pub fn body_length(&self) -> u64 {
+        self.voip_message_type.body_length()
+    }
+
+    /// Fetches the [`MediaCodec`] the data is encoded with.
+    pub fn codec(&self) -> MediaCodec {
+        self.codec
+    }
+
+    /// Fetches the channel (or room) of the [`VoipHeader`].
+    pub fn channel(&self) -> u32 {
+        self.channel
+    }
+
+    /// Fetches the [`HeaderFlags`] of the [`VoipHeader`].
+    pub fn flags(&self) -> HeaderFlags {
+        self.flags
+    }
+
+    /// Fetches the level of the audio in -dBov, if the sender has set one.
+    pub fn audio_level(&self) -> Option<u8> {
+        self.audio_level
+    }
+
+    /// Fetches the [`Position`] of the source, if the sender has set one.
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+
+    /// Fetches the sequence number of the frame, if the sender has set one.
+    pub fn sequence(&self) -> Option<u32> {
+        self.sequence
+    }
+
+    /// Fetches the media time of the frame in microseconds, if the sender has set one.
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    /// Fetches the length of the redundant copy of the previous frame, if the body carries one.
+    pub fn redundancy(&self) -> Option<u16> {
+        self.redundancy
+    }
+
+    /// Fetches the simulcast layer of this packet, if the stream has multiple layers.
+    pub fn layer(&self) -> Option<u8> {
+        self.layer
+    }
+
+    /// Fetches the audio stream of this packet, if the author publishes more than one.
+    pub fn stream(&self) -> Option<u8> {
+        self.stream
+    }
+
+    /// Fetches the lengths of the frames aggregated into the body of this packet except the last one, if the body carries more than one frame.
+    pub fn frame_lengths(&self) -> Option<&[u16]> {
+        self.frame_lengths.as_deref()
+    }
+
+    /// Fetches the slice of the video frame this packet carries, if the frame was packetized by slices.
+    pub fn slice(&self) -> Option<SliceInfo> {
+        self.slice
+    }
+
+    /// Fetches the device of the author this packet was sent from, if the author is connected from more than one device.
+    pub fn device(&self) -> Option<u32> {
+        self.device
+    }
+
+    /// Returns the amount of frames the body of this packet carries, the frames have consecutive sequence numbers.
+    pub fn frame_count(&self) -> u32 {
+        self.frame_lengths
+            .as_ref()
+            .map_or(1, |frame_lengths| frame_lengths.len() as u32 + 1)
+    }
+
+    /// Splits the `body` of this packet into the frame and the redundant copy of the previous frame.
+    /// The whole body is returned as the frame if it doesn't carry a copy, or the copy would be longer than the body.
+    pub fn split_redundancy<'a>(&self, body: &'a [u8]) -> (&'a [u8], Option<&'a [u8]>) {
+        match self.redundancy {
+            Some(redundancy) if (redundancy as usize) <= body.len() => {
+                let (frame, redundant_frame) = body.split_at(body.len() - redundancy as usize);
+
+                (frame, Some(redundant_frame))
+            }
+            _ => (body, None),
+        }
+    }
+
+    /// Splits the frame `body` of this packet (without the redundant copy, see [`VoipHeader::split_redundancy`]) into its aggregated frames.
+    /// The whole body is returned as a single frame if it doesn't aggregate frames, or the lengths of the frames would exceed the body.
+    pub fn split_frames<'a>(&self, body: &'a [u8]) -> Vec<&'a [u8]> {
+        let Some(frame_lengths) = &self.frame_lengths else {
+            return alloc::vec![body];
+        };
+
+        let mut frames = Vec::with_capacity(frame_lengths.len() + 1);
+        let mut rest = body;
+
+        for frame_length in frame_lengths {
+            if *frame_length as usize > rest.len() {
+                return alloc::vec![body];
+            }
+
+            let (frame, remaining) = rest.split_at(*frame_length as usize);
+
+            frames.push(frame);
+            rest = remaining;
+        }
+
+        frames.push(rest);
+
+        frames
+    }
+
+    /// Returns whether the [`HeaderFlags::MARKER`] flag is set.
+    pub fn is_marker(&self) -> bool {
+        self.flags.contains(HeaderFlags::MARKER)
+    }
+
+    /// Returns whether the [`HeaderFlags::LAST_FRAGMENT`] flag is set.
+    pub fn is_last_fragment(&self) -> bool {
+        self.flags.contains(HeaderFlags::LAST_FRAGMENT)
+    }
+}
+
+///
+/// Calculates the audio level of the raw `samples` in -dBov, which can be set with [`VoipHeader::with_audio_level`].
+///
+/// # Behavior
+/// The level is calculated from the RMS of the samples, where a full scale sample (`1.0`) is 0 dBov.
+/// An empty set of samples is reported as [`AUDIO_LEVEL_SILENCE`].
+///
+#[cfg(feature = "std")]
+pub fn audio_level(samples: &[f32]) -> u8 {
+    if samples.is_empty() {
+        return AUDIO_LEVEL_SILENCE;
+    }
+
+    let rms =
+        (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt();
+
+    //Convert the RMS to -dBov, silence has an infinite level which is clamped
+    let level = -20. * rms.log10();
+
+    level.clamp(AUDIO_LEVEL_LOUDEST as f32, AUDIO_LEVEL_SILENCE as f32) as u8
+}
+
+///
+/// Encodes a [`VoipHeader`] and the data it describes into a message buffer.
+///
+/// # Behavior
+/// This is the same as calling [`VoipHeader::create_message_buffer`], and is the inverse of [`decode_message`].
+///
+/// # Error
+/// Returns an error if the [`VoipHeader`] could not be serialized, or it is longer than [`MAX_HEADER_SIZE`].
+///
+pub fn encode_message(voip_header: &VoipHeader, data: &[u8]) -> Result<VoipPacket, CodecError> {
+    voip_header.create_message_buffer(data)
+}
+
+/// Encodes a message buffer like [`encode_message`], but serializes the header with the given [`HeaderCodec`].
+pub fn encode_message_with<C: HeaderCodec>(
+    voip_header: &VoipHeader,
+    data: &[u8],
+) -> Result<VoipPacket, CodecError> {
+    voip_header.create_message_buffer_with::<C>(data)
+}
+
+///
+/// Decodes a message buffer created by [`encode_message`] or [`VoipHeader::create_message_buffer`].
+///
+/// # Behavior
+/// Returns the [`VoipHeader`] and the data the message contains.
+///
+/// # Error
+/// Returns an error if the length prefix is missing or too large, the header could not be deserialized or exceeds its limits, or the lengths dont match the actual size of the buffer.
+///
+pub fn decode_message(buf: &[u8]) -> Result<(VoipHeader, Vec<u8>), PacketError> {
+    decode_message_with::<DefaultCodec>(buf)
+}
+
+/// Decodes a message buffer like [`decode_message`], but deserializes the header with the given [`HeaderCodec`].
+pub fn decode_message_with<C: HeaderCodec>(
+    buf: &[u8],
+) -> Result<(VoipHeader, Vec<u8>), PacketError> {
+    let (voip_header, body_offset) = split_message::<C>(buf)?;
+
+    Ok((voip_header, buf[body_offset..].to_vec()))
+}
+
+/// Decodes the [`VoipHeader`] of a message buffer like [`decode_message`], without copying the data.
+pub fn decode_header(buf: &[u8]) -> Result<VoipHeader, PacketError> {
+    split_message::<DefaultCodec>(buf).map(|(voip_header, _)| voip_header)
+}
+
+/// Validates a message buffer and deserializes its [`VoipHeader`].
+/// Returns the [`VoipHeader`] and the offset of the data in the buffer.
+fn split_message<C: HeaderCodec>(buf: &[u8]) -> Result<(VoipHeader, usize), PacketError> {
+    let (length_prefix, message) = buf
+        .split_first_chunk::<LENGTH_PREFIX_SIZE>()
+        .ok_or(PacketError::MissingLength)?;
+
+    let message_length = u64::from_be_bytes(*length_prefix);
+
+    //Check for invalid messages, to avoid overflowing buffer sizes
+    if message_length > MTU_MAX_PACKET_SIZE as u64 {
+        return Err(PacketError::TooLarge(message_length));
+    }
+
+    let message_length = message_length as usize;
+
+    if message_length != message.len() {
+        return Err(PacketError::LengthMismatch {
+            expected: message_length,
+            actual: message.len(),
+        });
+    }
+
+    //Fetch the length of the data from the header, the header takes up the rest of the message
+    //Only the bytes the header may take up are deserialized, so the declared lengths cant exceed them
+    let voip_header = C::decode(&message[..message_length.min(MAX_HEADER_SIZE)])?;
+    let body_length = voip_header.body_length();
+
+    if body_length > MTU_MAX_PACKET_SIZE as u64 {
+        return Err(PacketError::BodyTooLarge(body_length));
+    }
+
+    let body_length = body_length as usize;
+
+    let header_length = message_length
+        .checked_sub(body_length)
+        .ok_or(PacketError::LengthMismatch {
+            expected: body_length,
+            actual: message_length,
+        })?;
+
+    if header_length > MAX_HEADER_SIZE {
+        return Err(CodecError::HeaderTooLarge(header_length).into());
+    }
+
+    //The header isn't re-encoded to check that it is canonical, as that would double the cost of every decode
+    //The canonical encoding is verified by the conformance vectors instead (see the conformance module)
+    Ok((voip_header, LENGTH_PREFIX_SIZE + header_length))
 }
@@ -0,0 +1,425 @@
+//!
+//! Provides [`proptest`] strategies for the packet types.
+//!
+//! These can be used (by both this crate and downstream users) to property-test that every value round-trips through the wire format via [`encode_message`](super::encode_message) and [`decode_message`](super::decode_message).
+//!
+
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    collection::vec,
+    option, prop_oneof,
+    strategy::{BoxedStrategy, Just, Strategy, Union},
+};
+use uuid::Uuid;
+
+use super::{
+    caption::CaptionInfo,
+    control::{
+        CallSignal, CallSignalKind, CloseCode, CloseReason, ConnectionId, ControlMessage,
+        DeviceNotice, FloorState, LayerSelection, MediaState, PolicyViolation, PresenceState,
+        QualityReport, RecordingState, ResumptionTicket, RetryToken, RoomPolicy, ToneEvent,
+    },
+    HeaderFlags, MediaCodec, Position, SliceInfo, VoipHeader, VoipMessageType, AUDIO_LEVEL_LOUDEST,
+    AUDIO_LEVEL_SILENCE,
+};
+
+/// The maximum length of the data generated by [`message`].
+/// This leaves enough space for the header, so that every generated message fits in [`MTU_MAX_PACKET_SIZE`](crate::MTU_MAX_PACKET_SIZE).
+pub const MAX_GENERATED_BODY_LENGTH: usize = 1024;
+
+/// Creates a strategy generating random [`Uuid`]s.
+pub fn uuid() -> impl Strategy<Value = Uuid> {
+    any::<u128>().prop_map(Uuid::from_u128)
+}
+
+/// Creates a strategy generating random [`QualityReport`]s.
+pub fn quality_report() -> impl Strategy<Value = QualityReport> {
+    (
+        any::<u64>(),
+        any::<u64>(),
+        any::<u32>(),
+        any::<u32>(),
+        any::<u64>(),
+    )
+        .prop_map(
+            |(packets_received, packets_lost, jitter_ms, round_trip_time_ms, packets_ce)| {
+                QualityReport {
+                    packets_received,
+                    packets_lost,
+                    jitter_ms,
+                    round_trip_time_ms,
+                    packets_ce,
+                }
+            },
+        )
+}
+
+/// Creates a strategy generating random [`RoomPolicy`]s.
+pub fn room_policy() -> impl Strategy<Value = RoomPolicy> {
+    (
+        option::of(AUDIO_LEVEL_LOUDEST..=AUDIO_LEVEL_SILENCE),
+        option::of(any::<u32>()),
+        any::<bool>(),
+        option::of(vec(media_codec(), 0..4)),
+        option::of(any::<u32>()),
+        any::<bool>(),
+        any::<bool>(),
+    )
+        .prop_map(
+            |(
+                target_loudness,
+                max_bitrate,
+                mandatory_dtx,
+                allowed_codecs,
+                max_video_bitrate,
+                video_disabled,
+                screen_sharing_disabled,
+            )| RoomPolicy {
+                target_loudness,
+                max_bitrate,
+                mandatory_dtx,
+                allowed_codecs,
+                max_video_bitrate,
+                video_disabled,
+                screen_sharing_disabled,
+            },
+        )
+}
+
+/// Creates a strategy generating every [`LayerSelection`] variant.
+pub fn layer_selection() -> impl Strategy<Value = LayerSelection> {
+    prop_oneof![
+        Just(LayerSelection::Auto),
+        any::<u8>().prop_map(LayerSelection::Layer),
+    ]
+}
+
+/// Creates a strategy generating every [`CloseCode`] variant.
+pub fn close_code() -> impl Strategy<Value = CloseCode> {
+    prop_oneof![
+        Just(CloseCode::Normal),
+        Just(CloseCode::Kicked),
+        Just(CloseCode::Banned),
+        Just(CloseCode::Shutdown),
+        Just(CloseCode::AuthenticationFailed),
+        Just(CloseCode::ProtocolError),
+        any::<u16>().prop_map(CloseCode::Custom),
+        Just(CloseCode::DuplicateSession),
+        Just(CloseCode::Replaced),
+    ]
+}
+
+/// Creates a strategy generating random [`CloseReason`]s, with a short message so that the generated messages fit in [`MTU_MAX_PACKET_SIZE`](crate::MTU_MAX_PACKET_SIZE).
+pub fn close_reason() -> impl Strategy<Value = CloseReason> {
+    (close_code(), option::of(".{0,64}"))
+        .prop_map(|(code, message)| CloseReason::new(code, message))
+}
+
+/// Creates a strategy generating random [`Position`]s.
+pub fn position() -> impl Strategy<Value = Position> {
+    (any::<i32>(), any::<i32>(), any::<i32>()).prop_map(|(x, y, z)| Position::new(x, y, z))
+}
+
+/// Creates a strategy generating random [`SliceInfo`]s.
+pub fn slice_info() -> impl Strategy<Value = SliceInfo> {
+    (any::<u16>(), any::<u16>()).prop_map(|(index, count)| SliceInfo { index, count })
+}
+
+/// Creates a strategy generating random [`RetryToken`]s.
+pub fn retry_token() -> impl Strategy<Value = RetryToken> {
+    (any::<u64>(), any::<[u8; 16]>()).prop_map(|(issued_at, mac)| RetryToken::new(issued_at, mac))
+}
+
+/// Creates a strategy generating random [`ConnectionId`]s.
+pub fn connection_id() -> impl Strategy<Value = ConnectionId> {
+    any::<[u8; 16]>().prop_map(ConnectionId::new)
+}
+
+/// Creates a strategy generating random [`ResumptionTicket`]s.
+pub fn resumption_ticket() -> impl Strategy<Value = ResumptionTicket> {
+    (any::<u64>(), any::<[u8; 16]>())
+        .prop_map(|(issued_at, mac)| ResumptionTicket::new(issued_at, mac))
+}
+
+/// Creates a strategy generating random [`ToneEvent`]s.
+pub fn tone_event() -> impl Strategy<Value = ToneEvent> {
+    (any::<u8>(), any::<u32>()).prop_map(|(tone, duration_ms)| ToneEvent::new(tone, duration_ms))
+}
+
+/// Creates a strategy generating random [`CallSignal`]s, with every [`CallSignalKind`] variant.
+pub fn call_signal() -> impl Strategy<Value = CallSignal> {
+    let kind = prop_oneof![
+        Just(CallSignalKind::Invite),
+        Just(CallSignalKind::Ringing),
+        Just(CallSignalKind::Accept),
+        Just(CallSignalKind::Reject),
+        Just(CallSignalKind::Busy),
+        Just(CallSignalKind::Hangup),
+        Just(CallSignalKind::DoNotDisturb),
+    ];
+
+    (uuid(), uuid(), kind).prop_map(|(call_id, recipient, kind)| CallSignal {
+        call_id,
+        recipient,
+        kind,
+    })
+}
+
+/// Creates a strategy generating every [`PresenceState`] variant.
+pub fn presence_state() -> impl Strategy<Value = PresenceState> {
+    prop_oneof![
+        Just(PresenceState::Available),
+        Just(PresenceState::Busy),
+        Just(PresenceState::DoNotDisturb),
+        Just(PresenceState::Away),
+    ]
+}
+
+/// Creates a strategy generating any [`MediaState`].
+pub fn media_state() -> impl Strategy<Value = MediaState> {
+    (any::<bool>(), any::<bool>(), any::<bool>()).prop_map(
+        |(audio_muted, video_muted, screen_sharing)| MediaState {
+            audio_muted,
+            video_muted,
+            screen_sharing,
+        },
+    )
+}
+
+/// Creates a strategy generating every [`RecordingState`] variant.
+pub fn recording_state() -> impl Strategy<Value = RecordingState> {
+    prop_oneof![
+        Just(RecordingState::Stopped),
+        any::<bool>().prop_map(|consent_required| RecordingState::Recording { consent_required }),
+    ]
+}
+
+/// Creates a strategy generating any [`CaptionInfo`].
+pub fn caption_info() -> impl Strategy<Value = CaptionInfo> {
+    (uuid(), any::<u64>(), any::<u64>(), any::<bool>()).prop_map(
+        |(author, start, end, is_final)| CaptionInfo {
+            author,
+            start,
+            end,
+            is_final,
+        },
+    )
+}
+
+/// Creates a strategy generating every [`DeviceNotice`] variant.
+pub fn device_notice() -> impl Strategy<Value = DeviceNotice> {
+    prop_oneof![
+        any::<u32>().prop_map(DeviceNotice::Assigned),
+        any::<u32>().prop_map(DeviceNotice::Joined),
+        any::<u32>().prop_map(DeviceNotice::Left),
+    ]
+}
+
+/// Creates a strategy generating every [`PolicyViolation`] variant.
+pub fn policy_violation() -> impl Strategy<Value = PolicyViolation> {
+    prop_oneof![
+        media_codec().prop_map(PolicyViolation::Codec),
+        any::<u32>().prop_map(PolicyViolation::AudioBitrate),
+        any::<u32>().prop_map(PolicyViolation::VideoBitrate),
+        Just(PolicyViolation::Video),
+        Just(PolicyViolation::ScreenSharing),
+    ]
+}
+
+/// Creates a strategy generating every [`FloorState`] variant.
+pub fn floor_state() -> impl Strategy<Value = FloorState> {
+    prop_oneof![
+        Just(FloorState::Idle),
+        uuid().prop_map(FloorState::Granted),
+        any::<u32>().prop_map(FloorState::Queued),
+        Just(FloorState::Denied),
+    ]
+}
+
+/// Creates a strategy generating every [`ControlMessage`] variant.
+pub fn control_message() -> impl Strategy<Value = ControlMessage> {
+    prop_oneof![
+        uuid().prop_map(ControlMessage::ParticipantJoined),
+        uuid().prop_map(ControlMessage::ParticipantLeft),
+        Just(ControlMessage::Heartbeat),
+        quality_report().prop_map(ControlMessage::QualityReport),
+        option::of(any::<u32>()).prop_map(ControlMessage::MaxBitrate),
+        room_policy().prop_map(ControlMessage::RoomPolicy),
+        Just(ControlMessage::Goodbye),
+        close_reason().prop_map(ControlMessage::Close),
+        any::<u32>().prop_map(ControlMessage::Ping),
+        any::<u32>().prop_map(ControlMessage::Pong),
+        retry_token().prop_map(ControlMessage::Retry),
+        retry_token().prop_map(ControlMessage::RetryHeartbeat),
+        (uuid(), layer_selection())
+            .prop_map(|(author, selection)| ControlMessage::SelectLayer(author, selection)),
+        tone_event().prop_map(ControlMessage::Tone),
+        Just(ControlMessage::FloorRequest),
+        Just(ControlMessage::FloorRelease),
+        floor_state().prop_map(ControlMessage::Floor),
+        call_signal().prop_map(ControlMessage::Call),
+        presence_state().prop_map(ControlMessage::Presence),
+        vec(media_codec(), 0..4).prop_map(ControlMessage::Codecs),
+        media_state().prop_map(ControlMessage::MediaState),
+        recording_state().prop_map(ControlMessage::RecordingState),
+        any::<bool>().prop_map(ControlMessage::RecordingConsent),
+        (any::<u32>(), any::<u32>())
+            .prop_map(|(sequence, delay_ms)| ControlMessage::KeepaliveProbe(sequence, delay_ms)),
+        any::<bool>().prop_map(ControlMessage::PauseVideo),
+        resumption_ticket().prop_map(ControlMessage::ResumptionTicket),
+        resumption_ticket().prop_map(ControlMessage::Resume),
+        device_notice().prop_map(ControlMessage::Device),
+        policy_violation().prop_map(ControlMessage::PolicyViolation),
+        connection_id().prop_map(ControlMessage::ConnectionId),
+        connection_id().prop_map(ControlMessage::ConnectionHeartbeat),
+    ]
+}
+
+/// Creates a strategy generating every [`VoipMessageType`] variant with the given body length.
+/// [`VoipMessageType::Control`] is only generated if the `length` is 0, as control messages have no body.
+pub fn voip_message_type_with_length(length: u64) -> BoxedStrategy<VoipMessageType> {
+    let mut variants: Vec<BoxedStrategy<VoipMessageType>> = vec![
+        Just(VoipMessageType::VoiceMessage(length)).boxed(),
+        Just(VoipMessageType::VideoMessage(length)).boxed(),
+        Just(VoipMessageType::TextMessage(length)).boxed(),
+        caption_info()
+            .prop_map(move |caption_info| VoipMessageType::CaptionMessage(length, caption_info))
+            .boxed(),
+    ];
+
+    if length == 0 {
+        variants.push(control_message().prop_map(VoipMessageType::Control).boxed());
+    }
+
+    Union::new(variants).boxed()
+}
+
+/// Creates a strategy generating every [`VoipMessageType`] variant with any body length.
+pub fn voip_message_type() -> impl Strategy<Value = VoipMessageType> {
+    any::<u64>().prop_flat_map(voip_message_type_with_length)
+}
+
+/// Creates a strategy generating every [`MediaCodec`] variant.
+pub fn media_codec() -> impl Strategy<Value = MediaCodec> {
+    prop_oneof![
+        Just(MediaCodec::Raw),
+        Just(MediaCodec::Opus),
+        Just(MediaCodec::Avif),
+        any::<u16>().prop_map(MediaCodec::Custom),
+    ]
+}
+
+/// Creates a strategy generating every combination of [`HeaderFlags`].
+pub fn header_flags() -> impl Strategy<Value = HeaderFlags> {
+    any::<u8>().prop_map(HeaderFlags::from_bits)
+}
+
+/// Creates a strategy generating [`VoipHeader`]s with the given [`VoipMessageType`] strategy, and random values for every other field.
+fn voip_header_with(
+    voip_message_type: impl Strategy<Value = VoipMessageType>,
+) -> impl Strategy<Value = VoipHeader> {
+    (
+        voip_message_type,
+        uuid(),
+        media_codec(),
+        any::<u32>(),
+        header_flags(),
+        option::of(AUDIO_LEVEL_LOUDEST..=AUDIO_LEVEL_SILENCE),
+        option::of(position()),
+        option::of(any::<u32>()),
+        option::of(any::<u64>()),
+        option::of(any::<u16>()),
+        option::of(any::<u8>()),
+        (
+            option::of(any::<u8>()),
+            option::of(vec(any::<u16>(), 0..4)),
+            option::of(slice_info()),
+            option::of(any::<u32>()),
+        ),
+    )
+        .prop_map(
+            |(
+                voip_message_type,
+                author,
+                codec,
+                channel,
+                flags,
+                audio_level,
+                position,
+                sequence,
+                timestamp,
+                redundancy,
+                layer,
+                (stream, frame_lengths, slice, device),
+            )| {
+                let mut voip_header = VoipHeader::new(voip_message_type, author)
+                    .with_codec(codec)
+                    .with_channel(channel)
+                    .with_flags(flags)
+                    .with_redundancy(redundancy)
+                    .with_layer(layer)
+                    .with_stream(stream)
+                    .with_frame_lengths(frame_lengths)
+                    .with_slice(slice)
+                    .with_device(device);
+
+                if let Some(audio_level) = audio_level {
+                    voip_header = voip_header.with_audio_level(audio_level);
+                }
+
+                if let Some(position) = position {
+                    voip_header = voip_header.with_position(position);
+                }
+
+                if let Some(sequence) = sequence {
+                    voip_header = voip_header.with_sequence(sequence);
+                }
+
+                if let Some(timestamp) = timestamp {
+                    voip_header = voip_header.with_timestamp(timestamp);
+                }
+
+                voip_header
+            },
+        )
+}
+
+/// Creates a strategy generating [`VoipHeader`]s with any body length.
+pub fn voip_header() -> impl Strategy<Value = VoipHeader> {
+    voip_header_with(voip_message_type())
+}
+
+/// Creates a strategy generating valid messages, a [`VoipHeader`] and the data matching its body length.
+pub fn message() -> impl Strategy<Value = (VoipHeader, Vec<u8>)> {
+    vec(any::<u8>(), 0..=MAX_GENERATED_BODY_LENGTH).prop_flat_map(|data| {
+        voip_header_with(voip_message_type_with_length(data.len() as u64))
+            .prop_map(move |voip_header| (voip_header, data.clone()))
+    })
+}
+
+impl Arbitrary for ControlMessage {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        control_message().boxed()
+    }
+}
+
+impl Arbitrary for VoipMessageType {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        voip_message_type().boxed()
+    }
+}
+
+impl Arbitrary for VoipHeader {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        voip_header().boxed()
+    }
+}
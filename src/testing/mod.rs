@@ -0,0 +1,147 @@
+//!
+//! Provides a deterministic test harness for running a [`Client`] and a [`Server`] in the same process.
+//!
+//! The harness connects every peer through a [`MemoryNetwork`] instead of real sockets, and pauses [tokio's clock](tokio::time::pause) on creation.
+//! This means that time only moves forward when the test explicitly advances it, so timing dependant behavior can be asserted on without races.
+//!
+//! The harness must be created inside a current thread runtime (which is the default for `#[tokio::test]`).
+//!
+
+use std::{net::SocketAddr, time::Duration};
+
+use uuid::Uuid;
+
+use crate::{
+    audio::jitter::JitterStats,
+    udp::{
+        client::{Client, DEFAULT_HEARTBEAT_INTERVAL},
+        event::ClientError,
+        server::{Server, ServerError},
+        transport::memory::MemoryNetwork,
+    },
+};
+
+/// The amount of times the harness yields to the runtime when settling.
+/// This gives every spawned service enough opportunities to process the messages in flight.
+const SETTLE_YIELD_COUNT: usize = 64;
+
+///
+/// Playout probe type definition.
+///
+/// The state of the playout of the voice of an author at a [`Client`], see [`TestHarness::probe_playout`].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayoutProbe {
+    /// The delay the jitter buffer of the author is applying, [`None`] if nothing of the author is buffered.
+    pub playout_delay: Option<Duration>,
+
+    /// The statistics of the jitter buffer of the author (including the depth of the buffered audio), [`None`] if nothing of the author was played out yet.
+    pub jitter_stats: Option<JitterStats>,
+}
+
+///
+/// Deterministic test harness type definition.
+///
+/// Every [`Client`] and [`Server`] created with the harness exchange messages through the same [`MemoryNetwork`].
+///
+#[derive(Debug)]
+pub struct TestHarness {
+    /// The network every peer of the harness is bound to.
+    network: MemoryNetwork,
+}
+
+impl TestHarness {
+    /// Creates a new [`TestHarness`] instance, and pauses the runtime's clock.
+    ///
+    /// # Panics
+    /// Panics if the clock is already paused, or if it is called outside of a current thread runtime.
+    pub fn new() -> Self {
+        tokio::time::pause();
+
+        Self {
+            network: MemoryNetwork::new(),
+        }
+    }
+
+    /// Returns the [`MemoryNetwork`] the peers of this harness are bound to.
+    /// This can be used to simulate peers disappearing from the network.
+    pub fn network(&self) -> &MemoryNetwork {
+        &self.network
+    }
+
+    /// Creates a new [`Server`] instance bound to a free address of the network.
+    /// Returns the [`Server`] and the address it is bound to.
+    pub async fn server(&self) -> Result<(Server, SocketAddr), ServerError> {
+        let socket = self.network.bind_any().map_err(ServerError::SocketIo)?;
+        let local_addr = socket.local_addr();
+
+        Ok((Server::new_from_transport(socket).await?, local_addr))
+    }
+
+    /// Creates a new [`Client`] instance bound to a free address of the network, which will exchange messages with the `server_addr`.
+    /// Returns the [`Client`] and the address it is bound to.
+    pub async fn client(
+        &self,
+        uuid: Uuid,
+        server_addr: SocketAddr,
+    ) -> Result<(Client, SocketAddr), ClientError> {
+        let socket = self.network.bind_any().map_err(ClientError::SocketIo)?;
+        let local_addr = socket.local_addr();
+
+        Ok((
+            Client::new_from_transport(uuid, socket, server_addr).await?,
+            local_addr,
+        ))
+    }
+
+    /// Lets every spawned service process the messages currently in flight, without advancing the clock.
+    pub async fn settle(&self) {
+        for _ in 0..SETTLE_YIELD_COUNT {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Advances the paused clock by the `duration`, then lets every spawned service react to it.
+    pub async fn advance(&self, duration: Duration) {
+        self.settle().await;
+
+        tokio::time::advance(duration).await;
+
+        self.settle().await;
+    }
+
+    /// Advances the paused clock by `count` heartbeat intervals ([`DEFAULT_HEARTBEAT_INTERVAL`]) one at a time, so that every keepalive in between is sent and processed.
+    pub async fn advance_heartbeats(&self, count: u32) {
+        for _ in 0..count {
+            self.advance(DEFAULT_HEARTBEAT_INTERVAL).await;
+        }
+    }
+
+    /// Returns the [`PlayoutProbe`] of the voice of the `author`, as it is played out by the `client`.
+    pub fn probe_playout(&self, client: &Client, author: Uuid) -> PlayoutProbe {
+        PlayoutProbe {
+            playout_delay: client.playout().lock().playout_delay(author),
+            jitter_stats: client.jitter_stats(author),
+        }
+    }
+
+    /// Asserts that the peer at the `remote_addr` has been evicted from the registry of the `server`.
+    ///
+    /// # Panics
+    /// Panics if the peer is still registered.
+    #[track_caller]
+    pub fn assert_evicted(&self, server: &Server, remote_addr: SocketAddr) {
+        if let Some(peer) = server.peers().get(&remote_addr) {
+            panic!(
+                "The peer at {remote_addr} (author {}) hasn't been evicted.",
+                peer.author()
+            );
+        }
+    }
+}
+
+impl Default for TestHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,45 +1,7535 @@
 #[cfg(test)]
 mod test_functions {
+    #[cfg(feature = "all")]
+    use std::time::Duration;
+
+    #[cfg(feature = "all")]
     use uuid::Uuid;
 
+    #[cfg(feature = "all")]
     use crate::{
         packet::VoipHeader,
-        udp::{client::Client, server::Server},
+        testing::TestHarness,
+        udp::event::{ClientEvent, ConnectionState},
     };
 
     #[cfg(feature = "all")]
     #[tokio::test]
     async fn exchange_data() {
-        tokio::spawn(async move {
-            let mut server = Server::new(3004).await.unwrap();
-            let msg_recv = server.message_receiver();
+        let harness = TestHarness::new();
 
-            //Wait for incoming message
-            let (_packet, voip_body, _addr) = msg_recv.recv().await.unwrap();
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (mut client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
 
-            assert_eq!(voip_body, vec![1; 1]);
-            server.get_reply_to_list_mut().insert(_addr);
+        let packet = VoipHeader::new(
+            crate::packet::VoipMessageType::VoiceMessage(1),
+            client.uuid(),
+        );
+
+        client
+            .message_sender()
+            .send(packet.create_message_buffer(&[1; 1]).unwrap())
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        //Wait for incoming message
+        let (packet, voip_body, addr) = server.message_receiver().try_recv().unwrap();
+
+        assert_eq!(voip_body, vec![1; 1]);
+        assert_eq!(addr, client_addr);
+
+        server.get_reply_to_list_mut().insert(addr);
+
+        server
+            .reply_to_clients(packet.create_message_buffer(&voip_body).unwrap())
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+
+        match client.event_receiver().try_recv().unwrap() {
+            ClientEvent::VoiceFrame(voice_frame) => {
+                assert_eq!(voice_frame.author, client.uuid());
+                assert_eq!(voice_frame.payload, vec![1; 1]);
+            }
+            client_event => panic!("Unexpected event: {client_event:?}"),
+        }
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn heartbeat_and_goodbye() {
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (leaving_client, leaving_client_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut staying_client, staying_client_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //The first heartbeat registers the clients, and the echo reports the connection
+        assert!(server.peers().contains_key(&leaving_client_addr));
+        assert!(server.peers().contains_key(&staying_client_addr));
+        assert!(matches!(
+            staying_client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+
+        //Heartbeats are not forwarded to the application
+        assert!(server.message_receiver().try_recv().is_err());
+
+        server.get_reply_to_list_mut().insert(leaving_client_addr);
+        server.get_reply_to_list_mut().insert(staying_client_addr);
+
+        leaving_client.disconnect().await.unwrap();
 
+        harness.settle().await;
+
+        harness.assert_evicted(&server, leaving_client_addr);
+        assert!(!server
+            .get_reply_to_list_mut()
+            .contains(&leaving_client_addr));
+        assert!(matches!(
+            staying_client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ParticipantLeft(uuid) if uuid == leaving_client.uuid()
+        ));
+
+        //The heartbeats keep the registry up to date
+        let last_seen = server
+            .peers()
+            .get(&staying_client_addr)
+            .unwrap()
+            .last_seen();
+
+        harness
+            .advance(crate::udp::client::DEFAULT_HEARTBEAT_INTERVAL + Duration::from_millis(1))
+            .await;
+
+        assert!(
             server
-                .reply_to_clients(_packet.create_message_buffer(&voip_body).unwrap())
+                .peers()
+                .get(&staying_client_addr)
+                .unwrap()
+                .last_seen()
+                > last_seen
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn unregistered_goodbyes_are_dropped() {
+        use crate::{
+            packet::control::{CloseCode, CloseReason, ControlMessage},
+            udp::{client::send_control_message, server::ServerEvent},
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut other_client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        let mut server_events = server.subscribe_events();
+
+        while other_client.event_receiver().try_recv().is_ok() {}
+
+        //An unregistered address can't announce the departure of a registered author
+        let spoofed_socket = harness.network().bind_any().unwrap();
+
+        for control_message in [
+            ControlMessage::Goodbye,
+            ControlMessage::Close(CloseReason::new(CloseCode::Normal, None)),
+        ] {
+            send_control_message(&spoofed_socket, control_message, client.uuid(), server_addr)
                 .await
                 .unwrap();
-        });
+        }
+
+        harness.settle().await;
+
+        assert!(server.peers().contains_key(&client_addr));
+        assert!(server.message_receiver().try_recv().is_err());
+        assert!(!matches!(
+            server_events.try_recv(),
+            Ok(ServerEvent::PeerLeft { .. })
+        ));
+        assert!(!matches!(
+            other_client.event_receiver().try_recv(),
+            Ok(ClientEvent::ParticipantLeft(_))
+        ));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn kicked_client_is_closed() {
+        use crate::{
+            packet::{
+                control::{CloseCode, CloseReason},
+                VoipMessageType,
+            },
+            udp::{event::ClientError, server::ServerError},
+        };
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+        let (mut client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server
+            .close_client(
+                client_addr,
+                CloseReason::new(CloseCode::Kicked, Some(String::from("Spamming"))),
+            )
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        harness.assert_evicted(&server, client_addr);
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+
+        match client.event_receiver().try_recv().unwrap() {
+            ClientEvent::Closed(close_reason) => {
+                assert_eq!(close_reason.code(), CloseCode::Kicked);
+                assert_eq!(close_reason.message(), Some("Spamming"));
+            }
+            client_event => panic!("Unexpected event: {client_event:?}"),
+        }
+
+        //The closure is the final event
+        assert!(matches!(
+            client.event_receiver().try_recv(),
+            Err(tokio::sync::mpsc::error::TryRecvError::Disconnected)
+        ));
+
+        //The kicked client may rejoin, so its messages only fail because its service has shut down
+        assert!(matches!(
+            client
+                .send_bytes(VoipMessageType::TextMessage(2), &mut b"Hi".iter().copied())
+                .await,
+            Err(ClientError::ChannelClosed)
+        ));
+
+        //The client has left the peer registry, so it can't be targeted anymore
+        assert!(matches!(
+            server
+                .close_client(client_addr, CloseReason::new(CloseCode::Kicked, None))
+                .await,
+            Err(ServerError::NotConnected(remote_addr)) if remote_addr == client_addr
+        ));
+        assert!(matches!(
+            server.hold(client_addr).await,
+            Err(ServerError::NotConnected(_))
+        ));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn banned_client_is_unauthorized() {
+        use crate::{
+            packet::{control::CloseCode, VoipMessageType},
+            udp::event::ClientError,
+        };
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+        let (mut client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
 
-        tokio::spawn(async move {
-            let mut client = Client::new(Uuid::new_v4(), "[::1]:3004").await.unwrap();
+        server
+            .ban(client.uuid(), Some(String::from("Spamming")))
+            .await
+            .unwrap();
 
+        harness.settle().await;
+
+        while let Ok(client_event) = client.event_receiver().try_recv() {
+            if let ClientEvent::Closed(close_reason) = client_event {
+                assert_eq!(close_reason.code(), CloseCode::Banned);
+            }
+        }
+
+        //The messages sent after the ban report why the session was closed
+        match client
+            .send_bytes(VoipMessageType::TextMessage(2), &mut b"Hi".iter().copied())
+            .await
+        {
+            Err(ClientError::Unauthorized(close_reason)) => {
+                assert_eq!(close_reason.code(), CloseCode::Banned);
+                assert_eq!(close_reason.message(), Some("Spamming"));
+            }
+            result => panic!("Unexpected result: {result:?}"),
+        }
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn layered_transports_intercept_datagrams() {
+        use std::{
+            net::SocketAddr,
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+        };
+
+        use crate::udp::{
+            client::Client,
+            server::Server,
+            transport::layer::{Interceptor, InterceptorLayer, Stack, TransportExt},
+        };
+
+        //Scrambles every datagram, like an encryption layer would
+        #[derive(Clone)]
+        struct Scramble;
+
+        impl Interceptor for Scramble {
+            fn on_send(&self, datagram: Vec<u8>, _target: SocketAddr) -> Option<Vec<u8>> {
+                Some(datagram.into_iter().map(|byte| byte ^ 0x5A).collect())
+            }
+
+            fn on_recv(&self, datagram: Vec<u8>, _source: SocketAddr) -> Option<Vec<u8>> {
+                Some(datagram.into_iter().map(|byte| byte ^ 0x5A).collect())
+            }
+        }
+
+        //Counts the received datagrams, and drops every one of them once the limit is reached
+        #[derive(Clone)]
+        struct Limit(Arc<AtomicUsize>, usize);
+
+        impl Interceptor for Limit {
+            fn on_recv(&self, datagram: Vec<u8>, _source: SocketAddr) -> Option<Vec<u8>> {
+                (self.0.fetch_add(1, Ordering::Relaxed) < self.1).then_some(datagram)
+            }
+        }
+
+        let harness = TestHarness::new();
+        let received_count = Arc::new(AtomicUsize::new(0));
+
+        let server_socket = harness.network().bind_any().unwrap();
+        let server_addr = server_socket.local_addr();
+
+        //The limit is applied to the already unscrambled datagrams
+        let mut server = Server::new_from_transport(server_socket.layer(Stack::new(
+            InterceptorLayer::new(Scramble),
+            InterceptorLayer::new(Limit(received_count.clone(), 2)),
+        )))
+        .await
+        .unwrap();
+        let mut client = Client::new_from_transport(
+            Uuid::new_v4(),
+            harness
+                .network()
+                .bind_any()
+                .unwrap()
+                .layer(InterceptorLayer::new(Scramble)),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        //The first datagram is the heartbeat, the second one gets through, the third one is dropped
+        for byte in [1, 2] {
             let packet = VoipHeader::new(
                 crate::packet::VoipMessageType::VoiceMessage(1),
                 client.uuid(),
             );
 
-            let message_sender = client.message_sender();
+            client
+                .message_sender()
+                .send(packet.create_message_buffer(&[byte]).unwrap())
+                .await
+                .unwrap();
 
-            message_sender
-                .send(packet.create_message_buffer(&[1; 1]).unwrap())
+            harness.settle().await;
+        }
+
+        let (_packet, voip_body, _addr) = server.message_receiver().try_recv().unwrap();
+
+        assert_eq!(voip_body, vec![1]);
+        assert!(server.message_receiver().try_recv().is_err());
+        assert_eq!(received_count.load(Ordering::Relaxed), 3);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn datagrams_are_sealed_with_packet_ciphers() {
+        use std::{net::SocketAddr, sync::Arc};
+
+        use dashmap::DashMap;
+
+        use crate::udp::{
+            client::Client,
+            server::Server,
+            transport::{
+                cipher::{
+                    Aes256GcmCipher, ChaCha20Poly1305Cipher, CipherError, CipherInterceptor,
+                    PacketCipher, PeerCiphers, HEADER_SIZE, MAX_REPLAY_WINDOWS,
+                },
+                layer::{Interceptor, InterceptorLayer, TransportExt},
+            },
+        };
+
+        //Tampered datagrams, and datagrams with another session or sequence number can't be opened
+        let cipher = Aes256GcmCipher::new(&[1; 32], [2; 12]);
+        let mut datagram = vec![3; 16];
+
+        cipher.seal(&[1; 12], 7, b"header", &mut datagram).unwrap();
+
+        assert_ne!(&datagram[..16], &[3; 16]);
+        assert_eq!(
+            cipher.open(&[1; 12], 8, b"header", &mut datagram.clone()),
+            Err(CipherError::Open)
+        );
+        assert_eq!(
+            cipher.open(&[2; 12], 7, b"header", &mut datagram.clone()),
+            Err(CipherError::Open)
+        );
+
+        datagram[0] ^= 1;
+
+        assert_eq!(
+            cipher.open(&[1; 12], 7, b"header", &mut datagram),
+            Err(CipherError::Open)
+        );
+
+        //Every interceptor seals with a random session, and replayed datagrams are dropped
+        let peer: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let sender = CipherInterceptor::new(
+            Aes256GcmCipher::new(&[1; 32], [2; 12]),
+            Aes256GcmCipher::new(&[1; 32], [2; 12]),
+        );
+        let receiver = sender.clone();
+        let other_sender = CipherInterceptor::new(
+            Aes256GcmCipher::new(&[1; 32], [2; 12]),
+            Aes256GcmCipher::new(&[1; 32], [2; 12]),
+        );
+
+        assert_ne!(sender.session(), other_sender.session());
+
+        let first = sender.on_send(vec![1, 2, 3], peer).unwrap();
+        let second = sender.on_send(vec![1, 2, 3], peer).unwrap();
+
+        assert_ne!(
+            first[HEADER_SIZE..],
+            other_sender.on_send(vec![1, 2, 3], peer).unwrap()[HEADER_SIZE..]
+        );
+        assert_eq!(receiver.on_recv(second.clone(), peer), Some(vec![1, 2, 3]));
+        assert_eq!(receiver.on_recv(first.clone(), peer), Some(vec![1, 2, 3]));
+        assert_eq!(receiver.on_recv(first.clone(), peer), None);
+        assert_eq!(receiver.on_recv(second, peer), None);
+
+        //The windows of a peer are evicted once it is forgotten
+        receiver.forget(peer);
+
+        let replayed = other_sender.on_send(vec![4, 5, 6], peer).unwrap();
+
+        assert_eq!(
+            receiver.on_recv(replayed.clone(), peer),
+            Some(vec![4, 5, 6])
+        );
+        assert_eq!(receiver.on_recv(first, peer), Some(vec![1, 2, 3]));
+
+        //Only the windows of the most recently heard sessions are kept, so the session silent for the longest is forgotten first
+        for port in 2..=MAX_REPLAY_WINDOWS as u16 {
+            let datagram = sender.on_send(vec![1, 2, 3], peer).unwrap();
+
+            assert!(receiver
+                .on_recv(datagram, SocketAddr::new(peer.ip(), port))
+                .is_some());
+        }
+
+        assert!(receiver.on_recv(replayed, peer).is_some());
+
+        //The server opens the datagrams of every client with the keys of that client
+        let client_key = [4; 32];
+        let server_key = [5; 32];
+        let harness = TestHarness::new();
+        let peer_ciphers = Arc::new(DashMap::new());
+
+        let server_socket = harness.network().bind_any().unwrap();
+        let server_addr = server_socket.local_addr();
+        let mut server = Server::new_from_transport(server_socket.layer(InterceptorLayer::new(
+            CipherInterceptor::with_peer_ciphers(peer_ciphers.clone()),
+        )))
+        .await
+        .unwrap();
+        let client_socket = harness.network().bind_any().unwrap();
+        let client_addr = client_socket.local_addr();
+
+        peer_ciphers.insert(
+            client_addr,
+            Arc::new(PeerCiphers::new(
+                ChaCha20Poly1305Cipher::new(&server_key, [0; 12]),
+                ChaCha20Poly1305Cipher::new(&client_key, [0; 12]),
+            )),
+        );
+
+        let mut client = Client::new_from_transport(
+            Uuid::new_v4(),
+            client_socket.layer(InterceptorLayer::new(CipherInterceptor::new(
+                ChaCha20Poly1305Cipher::new(&client_key, [0; 12]),
+                ChaCha20Poly1305Cipher::new(&server_key, [0; 12]),
+            ))),
+            server_addr,
+        )
+        .await
+        .unwrap();
+        let (plaintext_client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //Only the heartbeat of the client could be opened
+        assert_eq!(server.peers().len(), 1);
+        assert!(server.peers().contains_key(&client_addr));
+
+        for (sender, bytes) in [(&client, [1, 2, 3]), (&plaintext_client, [4, 5, 6])] {
+            sender
+                .send_bytes(
+                    crate::packet::VoipMessageType::VoiceMessage(3),
+                    &mut bytes.into_iter(),
+                )
                 .await
                 .unwrap();
+        }
+
+        harness.settle().await;
+
+        let (packet, voip_body, addr) = server.message_receiver().try_recv().unwrap();
+
+        assert_eq!(voip_body, vec![1, 2, 3]);
+        assert_eq!(addr, client_addr);
+        assert!(server.message_receiver().try_recv().is_err());
+
+        server.get_reply_to_list_mut().insert(client_addr);
+        server
+            .reply_to_clients(packet.create_message_buffer(&voip_body).unwrap())
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn bridge_relays_origin_to_edge_clients() {
+        use crate::{
+            packet::{MediaCodec, VoipMessageType},
+            udp::bridge::Bridge,
+        };
+
+        let harness = TestHarness::new();
+
+        let (origin, origin_addr) = harness.server().await.unwrap();
+        let (edge, edge_addr) = harness.server().await.unwrap();
+        let bridge = Bridge::new_from_transport(
+            Uuid::new_v4(),
+            &edge,
+            harness.network().bind_any().unwrap(),
+            origin_addr,
+        )
+        .await
+        .unwrap();
+        let (mut client, client_addr) = harness.client(Uuid::new_v4(), edge_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert!(origin.peers().contains_key(&bridge.local_addr()));
+
+        origin.get_reply_to_list_mut().insert(bridge.local_addr());
+        edge.get_reply_to_list_mut().insert(client_addr);
+
+        let author = Uuid::new_v4();
+
+        origin
+            .reply_to_clients(
+                VoipHeader::new(VoipMessageType::VoiceMessage(3), author)
+                    .with_codec(MediaCodec::Opus)
+                    .create_message_buffer(&[1, 2, 3])
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+
+        match client.event_receiver().try_recv().unwrap() {
+            ClientEvent::VoiceFrame(voice_frame) => {
+                assert_eq!(voice_frame.author, author);
+                assert_eq!(voice_frame.payload, vec![1, 2, 3]);
+            }
+            client_event => panic!("Unexpected event: {client_event:?}"),
+        }
+
+        //Stopping the bridge leaves the origin's session
+        bridge.cancellation_token().cancel();
+
+        harness.settle().await;
+
+        assert!(!origin.peers().contains_key(&bridge.local_addr()));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn constrained_clients_receive_transcoded_voice() {
+        use crate::{
+            packet::{MediaCodec, VoipMessageType},
+            udp::{
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+                transcode::TranscodeConfig,
+            },
+        };
+        use silence_core::opus::opus::{Application, Bitrate, Channels, Encoder};
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                transcoding: Some(TranscodeConfig::default()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (mut constrained_client, constrained_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        constrained_client
+            .set_max_bitrate(Some(16_000))
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            server.peers().get(&constrained_addr).unwrap().max_bitrate(),
+            Some(16_000)
+        );
+
+        server.get_reply_to_list_mut().insert(constrained_addr);
+        server.get_reply_to_list_mut().insert(client_addr);
+
+        //Encode a 20ms stereo frame at a high bitrate
+        let mut encoder = Encoder::new(48000, Channels::Stereo, Application::Voip).unwrap();
+
+        encoder.set_bitrate(Bitrate::Bits(128_000)).unwrap();
+
+        let samples: Vec<f32> = (0..960 * 2)
+            .map(|sample| ((sample / 2) as f32 * 0.3).sin() * 0.5)
+            .collect();
+        let packet = encoder.encode_vec_float(&samples, 4000).unwrap();
+
+        let author = Uuid::new_v4();
+
+        server
+            .reply_to_clients(
+                VoipHeader::new(VoipMessageType::VoiceMessage(packet.len() as u64), author)
+                    .with_codec(MediaCodec::Opus)
+                    .create_message_buffer(&packet)
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let voice_frames = [&mut constrained_client, &mut client].map(|client| {
+            assert!(matches!(
+                client.event_receiver().try_recv().unwrap(),
+                ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+            ));
+
+            match client.event_receiver().try_recv().unwrap() {
+                ClientEvent::VoiceFrame(voice_frame) => {
+                    assert_eq!(voice_frame.author, author);
+
+                    voice_frame.payload
+                }
+                client_event => panic!("Unexpected event: {client_event:?}"),
+            }
         });
+
+        //The unconstrained client receives the original packet, the constrained one a smaller re-encoded packet
+        assert_eq!(voice_frames[1], packet);
+        assert!(voice_frames[0].len() < packet.len());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn silent_voice_messages_are_gated() {
+        use crate::{
+            packet::{VoipMessageType, AUDIO_LEVEL_SILENCE},
+            udp::{
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                silence_threshold: Some(100),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let author = Uuid::new_v4();
+        let (mut client, _) = harness.client(author, server_addr).await.unwrap();
+
+        for (audio_level, data) in [(AUDIO_LEVEL_SILENCE, 1), (30, 2)] {
+            client
+                .message_sender()
+                .send(
+                    VoipHeader::new(VoipMessageType::VoiceMessage(1), author)
+                        .with_audio_level(audio_level)
+                        .create_message_buffer(&[data])
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        //Only the loud message is forwarded
+        let (voip_header, voip_body, _) = server.message_receiver().try_recv().unwrap();
+
+        assert_eq!(voip_header.audio_level(), Some(30));
+        assert_eq!(voip_body, vec![2]);
+        assert!(server.message_receiver().try_recv().is_err());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn media_exceeding_the_bandwidth_caps_is_dropped() {
+        use crate::{
+            packet::VoipMessageType,
+            udp::{
+                bandwidth::BandwidthLimits,
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                bandwidth_limits: BandwidthLimits {
+                    max_client_bitrate: Some(16_000),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //A window fits three of the messages, the voice messages only compete with each other
+        for voip_message_type in [
+            VoipMessageType::VideoMessage(500),
+            VoipMessageType::VideoMessage(500),
+            VoipMessageType::VideoMessage(500),
+            VoipMessageType::VoiceMessage(500),
+            VoipMessageType::VoiceMessage(500),
+            VoipMessageType::VoiceMessage(500),
+            VoipMessageType::VideoMessage(500),
+            VoipMessageType::VoiceMessage(500),
+        ] {
+            client
+                .send_bytes(voip_message_type, &mut vec![0; 500].into_iter())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let mut forwarded = vec![];
+
+        while let Ok((voip_header, _, _)) = server.message_receiver().try_recv() {
+            forwarded.push(voip_header.voip_message_type().clone());
+        }
+
+        assert_eq!(
+            forwarded,
+            [
+                VoipMessageType::VideoMessage(500),
+                VoipMessageType::VideoMessage(500),
+                VoipMessageType::VideoMessage(500),
+                VoipMessageType::VoiceMessage(500),
+                VoipMessageType::VoiceMessage(500),
+                VoipMessageType::VoiceMessage(500),
+            ]
+        );
+
+        //The sender is notified about its cap
+        assert_eq!(client.bitrate_cap(), Some(16_000));
+
+        //The next window has a new budget
+        harness.advance(Duration::from_secs(1)).await;
+
+        client
+            .send_bytes(
+                VoipMessageType::VoiceMessage(500),
+                &mut vec![0; 500].into_iter(),
+            )
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        assert!(server.message_receiver().try_recv().is_ok());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn fanned_out_media_is_paced_by_the_egress_limits() {
+        use crate::{
+            packet::{control::ControlMessage, decode_message, VoipMessageType},
+            udp::{
+                egress::EgressLimits,
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+                transport::{memory::MemorySocket, Transport},
+            },
+        };
+
+        let video_packet = VoipHeader::new(VoipMessageType::VideoMessage(500), Uuid::new_v4())
+            .create_message_buffer(&vec![0; 500])
+            .unwrap();
+        let voice_packet = VoipHeader::new(VoipMessageType::VoiceMessage(50), Uuid::new_v4())
+            .create_message_buffer(&vec![0; 50])
+            .unwrap();
+
+        let harness = TestHarness::new();
+
+        //The bucket of a client holds a single video message, and refills in 100ms
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                egress_limits: EgressLimits {
+                    max_destination_bitrate: Some(video_packet.inner().len() as u32 * 80),
+                    burst: Duration::from_millis(100),
+                    max_queue_delay: Duration::from_millis(250),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let receiver = harness.network().bind_any().unwrap();
+        let receiver_addr = receiver.local_addr();
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //The heartbeat registers the receiver, so its statistics are kept
+
+        let heartbeat = VoipHeader::new(
+            VoipMessageType::Control(ControlMessage::Heartbeat),
+            Uuid::new_v4(),
+        )
+        .create_message_buffer(&[])
+        .unwrap();
+
+        receiver
+            .send_datagram(heartbeat.inner(), server_addr)
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        async fn received(receiver: &MemorySocket) -> Vec<VoipMessageType> {
+            let mut buf = vec![0; 2048];
+            let mut received = vec![];
+
+            while let Ok(Ok((byte_count, _))) =
+                tokio::time::timeout(Duration::ZERO, receiver.recv_datagram(&mut buf)).await
+            {
+                let (voip_header, _) = decode_message(&buf[..byte_count]).unwrap();
+
+                if !matches!(voip_header.voip_message_type(), VoipMessageType::Control(_)) {
+                    received.push(voip_header.voip_message_type().clone());
+                }
+            }
+
+            received
+        }
+
+        received(&receiver).await;
+
+        for voip_packet in [&video_packet, &video_packet, &video_packet, &voice_packet] {
+            server.reply_to_clients(voip_packet.clone()).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        //The first video message empties the bucket, the rest of the media is queued
+        assert_eq!(
+            received(&receiver).await,
+            [VoipMessageType::VideoMessage(500)]
+        );
+
+        //The voice is sent ahead of the queued video
+        harness.advance(Duration::from_millis(100)).await;
+
+        assert_eq!(
+            received(&receiver).await,
+            [VoipMessageType::VoiceMessage(50)]
+        );
+
+        harness.advance(Duration::from_millis(100)).await;
+
+        assert_eq!(
+            received(&receiver).await,
+            [VoipMessageType::VideoMessage(500)]
+        );
+
+        //The last video message has waited too long, so it is dropped
+        harness.advance(Duration::from_millis(300)).await;
+
+        assert!(received(&receiver).await.is_empty());
+        assert_eq!(server.stats().peers[&receiver_addr].packets_rate_limited, 1);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn overloaded_server_sheds_video_then_low_priority_rooms() {
+        use std::collections::HashMap;
+
+        use crate::{
+            packet::{control::ControlMessage, VoipMessageType},
+            udp::{
+                runtime::Tokio,
+                server::{Server, ServerConfig, ServerEvent},
+                shedding::{LoadSheddingConfig, Shedding},
+                transport::Transport,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                load_shedding: Some(LoadSheddingConfig {
+                    interval: Duration::from_secs(1),
+                    max_queue_depth: Some(4),
+                    max_loop_lag: None,
+                    room_priorities: HashMap::from([(2, 1)]),
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut server_events = server.subscribe_events();
+
+        //The peer of the first room, and the peer of the second room with a higher priority
+        let peers = [
+            (harness.network().bind_any().unwrap(), Uuid::new_v4(), 1),
+            (harness.network().bind_any().unwrap(), Uuid::new_v4(), 2),
+        ];
+
+        let send = |peer: usize, voip_message_type: VoipMessageType| {
+            let (socket, author, room) = &peers[peer];
+            let voip_packet = VoipHeader::new(voip_message_type.clone(), *author)
+                .with_channel(*room)
+                .create_message_buffer(&vec![0; voip_message_type.body_length() as usize])
+                .unwrap();
+
+            async move {
+                socket
+                    .send_datagram(voip_packet.inner(), server_addr)
+                    .await
+                    .unwrap();
+            }
+        };
+
+        for peer in 0..2 {
+            send(peer, VoipMessageType::Control(ControlMessage::Heartbeat)).await;
+            send(peer, VoipMessageType::TextMessage(1)).await;
+        }
+
+        harness.settle().await;
+
+        let mut forwarded = || {
+            std::iter::from_fn(|| server.message_receiver().try_recv().ok())
+                .filter(|(voip_header, _, _)| {
+                    !matches!(voip_header.voip_message_type(), VoipMessageType::Control(_))
+                })
+                .map(|(voip_header, _, _)| {
+                    (
+                        voip_header.voip_message_type().clone(),
+                        voip_header.channel(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        forwarded();
+
+        let mut load_events = || {
+            std::iter::from_fn(|| server_events.try_recv().ok())
+                .filter(|server_event| {
+                    matches!(
+                        server_event,
+                        ServerEvent::Overloaded { .. } | ServerEvent::OverloadRelieved { .. }
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        //The application falls behind, so the video is dropped first
+        for _ in 0..4 {
+            send(0, VoipMessageType::TextMessage(1)).await;
+        }
+
+        harness.advance(Duration::from_secs(1)).await;
+
+        assert_eq!(
+            load_events(),
+            vec![ServerEvent::Overloaded {
+                queue_depth: 4,
+                loop_lag: Duration::ZERO,
+                shedding: Shedding {
+                    video_dropped: true,
+                    paused_rooms: vec![],
+                },
+            }]
+        );
+
+        forwarded();
+
+        for peer in 0..2 {
+            send(peer, VoipMessageType::VideoMessage(1)).await;
+            send(peer, VoipMessageType::VoiceMessage(1)).await;
+        }
+
+        harness.settle().await;
+
+        assert_eq!(
+            forwarded(),
+            vec![
+                (VoipMessageType::VoiceMessage(1), 1),
+                (VoipMessageType::VoiceMessage(1), 2),
+            ]
+        );
+
+        //The application is still behind, so the room with the lowest priority is paused
+        for _ in 0..4 {
+            send(0, VoipMessageType::TextMessage(1)).await;
+        }
+
+        harness.advance(Duration::from_secs(1)).await;
+
+        assert_eq!(
+            load_events(),
+            vec![ServerEvent::Overloaded {
+                queue_depth: 4,
+                loop_lag: Duration::ZERO,
+                shedding: Shedding {
+                    video_dropped: true,
+                    paused_rooms: vec![1],
+                },
+            }]
+        );
+
+        forwarded();
+
+        for peer in 0..2 {
+            send(peer, VoipMessageType::VoiceMessage(1)).await;
+            send(peer, VoipMessageType::TextMessage(1)).await;
+        }
+
+        harness.settle().await;
+
+        //The text messages of the paused room are still forwarded
+        assert_eq!(
+            forwarded(),
+            vec![
+                (VoipMessageType::TextMessage(1), 1),
+                (VoipMessageType::VoiceMessage(1), 2),
+                (VoipMessageType::TextMessage(1), 2),
+            ]
+        );
+
+        //The application has caught up, so the shedding steps back one step at a time
+        harness.advance(Duration::from_secs(1)).await;
+        harness.advance(Duration::from_secs(1)).await;
+
+        assert_eq!(
+            load_events(),
+            vec![
+                ServerEvent::OverloadRelieved {
+                    shedding: Shedding {
+                        video_dropped: true,
+                        paused_rooms: vec![],
+                    },
+                },
+                ServerEvent::OverloadRelieved {
+                    shedding: Shedding::default(),
+                },
+            ]
+        );
+
+        send(0, VoipMessageType::VideoMessage(1)).await;
+
+        harness.settle().await;
+
+        assert_eq!(forwarded(), vec![(VoipMessageType::VideoMessage(1), 1)]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn sessions_are_handed_over_to_the_restarted_server() {
+        use crate::{
+            packet::{control::RoomPolicy, VoipMessageType},
+            udp::{
+                handoff::SessionHandoff,
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let (old_server, server_addr) = harness.server().await.unwrap();
+        let (mut client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        old_server.get_reply_to_list_mut().insert(client_addr);
+
+        let room_policy = RoomPolicy {
+            mandatory_dtx: true,
+            ..Default::default()
+        };
+
+        old_server
+            .create_room(3, room_policy.clone())
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let handoff = old_server.handoff().await.unwrap();
+
+        assert_eq!(handoff.reply_list, vec![client_addr]);
+        assert_eq!(handoff.peers.len(), 1);
+        assert_eq!(handoff.peers[0].remote_addr, client_addr);
+        assert_eq!(handoff.peers[0].author, client.uuid());
+        assert_eq!(handoff.room_policies.get(&3), Some(&room_policy));
+
+        //The old server has released its address, the handoff is passed to the new process serialized
+        harness.settle().await;
+
+        let handoff: SessionHandoff =
+            rmp_serde::from_slice(&rmp_serde::to_vec(&handoff).unwrap()).unwrap();
+
+        let mut new_server = Server::new_from_transport_with_config::<Tokio, _>(
+            harness.network().bind(server_addr).unwrap(),
+            ServerConfig {
+                handoff: Some(handoff),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            new_server.peers().get(&client_addr).unwrap().author(),
+            client.uuid()
+        );
+        assert_eq!(
+            new_server
+                .room_policies()
+                .get(&3)
+                .map(|room_policy| room_policy.clone()),
+            Some(room_policy)
+        );
+
+        //The client keeps its session with the new server, without being closed
+        client
+            .send_bytes(
+                VoipMessageType::TextMessage(5),
+                &mut b"Hello".iter().copied(),
+            )
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let (voip_header, voip_body, remote_addr) =
+            new_server.message_receiver().try_recv().unwrap();
+
+        assert_eq!(voip_header.author(), client.uuid());
+        assert_eq!(voip_body, b"Hello");
+        assert_eq!(remote_addr, client_addr);
+
+        new_server
+            .reply_to_clients(
+                VoipHeader::new(VoipMessageType::TextMessage(2), Uuid::new_v4())
+                    .create_message_buffer(b"Hi")
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let client_events: Vec<ClientEvent> =
+            std::iter::from_fn(|| client.event_receiver().try_recv().ok()).collect();
+
+        assert!(!client_events
+            .iter()
+            .any(|client_event| matches!(client_event, ClientEvent::Closed(_))));
+        assert!(client_events.iter().any(
+            |client_event| matches!(client_event, ClientEvent::Text { text, .. } if text == "Hi")
+        ));
+    }
+
+    #[cfg(all(feature = "all", target_os = "linux"))]
+    #[test]
+    fn systemd_sockets_are_selected_by_pid_and_name() {
+        use crate::udp::activation::{systemd_fds, SD_LISTEN_FDS_START};
+
+        //The process wasn't socket activated
+        assert_eq!(systemd_fds(None, None, None, 42, None).unwrap(), vec![]);
+
+        //The sockets were passed to another process
+        assert_eq!(
+            systemd_fds(Some("41"), Some("2"), None, 42, None).unwrap(),
+            vec![]
+        );
+
+        assert_eq!(
+            systemd_fds(Some("42"), Some("2"), None, 42, None).unwrap(),
+            vec![SD_LISTEN_FDS_START, SD_LISTEN_FDS_START + 1]
+        );
+
+        //Only the sockets with the name are taken
+        assert_eq!(
+            systemd_fds(
+                Some("42"),
+                Some("3"),
+                Some("voice:control:voice"),
+                42,
+                Some("voice")
+            )
+            .unwrap(),
+            vec![SD_LISTEN_FDS_START, SD_LISTEN_FDS_START + 2]
+        );
+        assert_eq!(
+            systemd_fds(Some("42"), Some("2"), None, 42, Some("voice")).unwrap(),
+            vec![]
+        );
+
+        assert!(systemd_fds(Some("42"), Some("many"), None, 42, None).is_err());
+        assert!(systemd_fds(Some("init"), Some("2"), None, 42, None).is_err());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn active_speaker_changes_are_reported() {
+        use crate::{
+            packet::{MediaCodec, VoipMessageType},
+            udp::speaker::{ActiveSpeakerChange, ActiveSpeakerConfig},
+        };
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+        let (mut client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(client_addr);
+
+        let speaker = Uuid::new_v4();
+
+        server
+            .reply_to_clients(
+                VoipHeader::new(VoipMessageType::VoiceMessage(1), speaker)
+                    .with_codec(MediaCodec::Opus)
+                    .with_channel(3)
+                    .with_audio_level(20)
+                    .create_message_buffer(&[1])
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::VoiceFrame(_)
+        ));
+
+        match client.event_receiver().try_recv().unwrap() {
+            ClientEvent::ActiveSpeakerChanged(active_speaker_change) => assert_eq!(
+                active_speaker_change,
+                ActiveSpeakerChange {
+                    channel: 3,
+                    speaker: Some(speaker),
+                }
+            ),
+            client_event => panic!("Unexpected event: {client_event:?}"),
+        }
+
+        //The speaker times out after falling silent
+        harness
+            .advance(ActiveSpeakerConfig::default().speaker_timeout + Duration::from_millis(1))
+            .await;
+
+        match client.event_receiver().try_recv().unwrap() {
+            ClientEvent::ActiveSpeakerChanged(active_speaker_change) => assert_eq!(
+                active_speaker_change,
+                ActiveSpeakerChange {
+                    channel: 3,
+                    speaker: None,
+                }
+            ),
+            client_event => panic!("Unexpected event: {client_event:?}"),
+        }
+    }
+
+    #[cfg(feature = "all")]
+    #[test]
+    fn prioritized_streams_duck_the_others() {
+        use crate::audio::mixer::{DuckingConfig, Mixer};
+
+        let mut mixer = Mixer::new(48000, 1);
+
+        mixer.set_ducking(Some(DuckingConfig::default()));
+
+        let (participant, dispatcher) = (Uuid::new_v4(), Uuid::new_v4());
+
+        mixer.set_prioritized(dispatcher, true);
+
+        let mut output = vec![0.; 960];
+
+        //The attenuation is ramped in over the first buffer
+        for _ in 0..2 {
+            mixer.push_samples(participant, &[0.5; 960]);
+            mixer.push_samples(dispatcher, &[0.2; 960]);
+            mixer.mix(&mut output);
+        }
+
+        let attenuated = 0.5 * 10_f32.powf(-DuckingConfig::default().attenuation_db / 20.) + 0.2;
+
+        assert!(output
+            .iter()
+            .all(|sample| (sample - attenuated).abs() < 1e-4));
+
+        //The other streams are restored after the release
+        for _ in 0..60 {
+            mixer.push_samples(participant, &[0.5; 960]);
+            mixer.mix(&mut output);
+        }
+
+        assert!(output.iter().all(|sample| (sample - 0.5).abs() < 1e-4));
+    }
+
+    #[cfg(feature = "all")]
+    #[test]
+    fn positioned_streams_are_spatialized() {
+        use crate::{
+            audio::mixer::{Mixer, SpatialConfig},
+            packet::Position,
+        };
+
+        let mut mixer = Mixer::new(48000, 2);
+
+        mixer.set_spatialization(Some(SpatialConfig::default()));
+        mixer.set_listener(Position::default(), 0.);
+
+        let (near, far) = (Uuid::new_v4(), Uuid::new_v4());
+
+        //The near source is on the right, the far source is in front of the listener
+        mixer.set_position(near, Some(Position::from_meters(1., 0., 0.)));
+        mixer.set_position(far, Some(Position::from_meters(0., 0., 4.)));
+
+        let mut output = vec![0.; 2];
+
+        mixer.push_samples(near, &[0.5; 2]);
+        mixer.mix(&mut output);
+
+        assert!(output[0].abs() < 1e-4);
+        assert!((output[1] - 0.5).abs() < 1e-4);
+
+        mixer.push_samples(far, &[0.5; 2]);
+        mixer.mix(&mut output);
+
+        //Centered with constant power, and attenuated by the distance
+        let expected = 0.5 * std::f32::consts::FRAC_1_SQRT_2 / 4.;
+
+        assert!(output.iter().all(|sample| (sample - expected).abs() < 1e-4));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn room_policies_are_advertised_at_join() {
+        use crate::{
+            packet::control::RoomPolicy,
+            udp::{
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let room_policy = RoomPolicy {
+            target_loudness: Some(23),
+            max_bitrate: Some(24_000),
+            mandatory_dtx: true,
+            ..Default::default()
+        };
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let _server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                room_policies: [(7, room_policy.clone())].into(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (mut client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+
+        match client.event_receiver().try_recv().unwrap() {
+            ClientEvent::RoomPolicyChanged { channel, policy } => {
+                assert_eq!(channel, 7);
+                assert_eq!(policy, room_policy);
+            }
+            client_event => panic!("Unexpected event: {client_event:?}"),
+        }
+
+        assert_eq!(client.room_policy(7), Some(room_policy));
+        assert_eq!(client.room_policy(0), None);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn music_profile_is_exempt_from_dtx() {
+        use crate::{
+            packet::{control::RoomPolicy, VoipMessageType},
+            udp::{
+                client::{Client, ClientConfig},
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+                voice::VoiceConfig,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                room_policies: [(
+                    0,
+                    RoomPolicy {
+                        mandatory_dtx: true,
+                        ..Default::default()
+                    },
+                )]
+                .into(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (voice_client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let music_client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            harness.network().bind_any().unwrap(),
+            server_addr,
+            ClientConfig {
+                voice: VoiceConfig::music(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        for client in [&voice_client, &music_client] {
+            let silence = vec![0.; client.voice_config().samples_per_frame()];
+
+            client.send_samples(&silence).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        let mut voice_authors = vec![];
+
+        while let Ok((voip_header, _, _)) = server.message_receiver().try_recv() {
+            if let VoipMessageType::VoiceMessage(_) = voip_header.voip_message_type() {
+                voice_authors.push(voip_header.author());
+            }
+        }
+
+        //Only the silent frame of the music is sent
+        assert_eq!(voice_authors, vec![music_client.uuid()]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn video_frames_are_fragmented_and_reassembled() {
+        use crate::{
+            packet::{HeaderFlags, MediaCodec},
+            udp::client::DEFAULT_VIDEO_PACING_INTERVAL,
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let frame: Vec<u8> = (0..5000).map(|index| index as u8).collect();
+
+        sender
+            .send_video_packet(&frame, MediaCodec::Avif, true)
+            .await
+            .unwrap();
+
+        //Let the client service pace out every fragment
+        for _ in 0..8 {
+            harness.advance(DEFAULT_VIDEO_PACING_INTERVAL).await;
+        }
+
+        let mut fragment_count = 0;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            assert!(voip_header.flags().contains(HeaderFlags::MARKER));
+            assert_eq!(voip_header.codec(), MediaCodec::Avif);
+
+            fragment_count += 1;
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        assert!(fragment_count > 1);
+
+        harness.settle().await;
+
+        assert!(matches!(
+            receiver.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+
+        match receiver.event_receiver().try_recv().unwrap() {
+            ClientEvent::VideoFrame(video_frame) => {
+                assert_eq!(video_frame.author, sender.uuid());
+                assert_eq!(video_frame.payload, frame);
+                assert_eq!(video_frame.sequence, Some(0));
+                assert!(video_frame.is_keyframe);
+            }
+            client_event => panic!("Unexpected event: {client_event:?}"),
+        }
+
+        assert!(receiver.event_receiver().try_recv().is_err());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn clients_encode_and_decode_voice_internally() {
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples_per_frame = sender.voice_config().samples_per_frame();
+        let samples: Vec<f32> = (0..samples_per_frame)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        //The encoder is reused between the calls
+        for _ in 0..3 {
+            sender.send_samples(&samples).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let decoded_frames = receiver.recv_frames();
+
+        assert_eq!(decoded_frames.len(), 3);
+
+        for (sequence, decoded_frame) in decoded_frames.into_iter().enumerate() {
+            assert_eq!(decoded_frame.author, sender.uuid());
+            assert_eq!(decoded_frame.samples.len(), samples_per_frame);
+            assert_eq!(decoded_frame.sequence, Some(sequence as u32));
+            assert_eq!(
+                decoded_frame.timestamp,
+                Some(decoded_frame.duration * sequence as u32)
+            );
+        }
+
+        assert!(receiver.recv_frames().is_empty());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn lost_voice_frames_are_concealed() {
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples_per_frame = sender.voice_config().samples_per_frame();
+        let samples: Vec<f32> = (0..samples_per_frame)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        for _ in 0..4 {
+            sender.send_samples(&samples).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        //Lose the frame with the sequence number 2
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            if voip_header.sequence() == Some(2) {
+                continue;
+            }
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let decoded_frames = receiver.recv_frames();
+
+        assert_eq!(decoded_frames.len(), 4);
+
+        for (sequence, decoded_frame) in decoded_frames.into_iter().enumerate() {
+            assert_eq!(decoded_frame.sequence, Some(sequence as u32));
+            assert_eq!(decoded_frame.concealed, sequence == 2);
+            assert_eq!(decoded_frame.samples.len(), samples_per_frame);
+            assert_eq!(
+                decoded_frame.timestamp,
+                Some(decoded_frame.duration * sequence as u32)
+            );
+        }
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn lost_voice_frames_are_recovered_from_redundancy() {
+        use crate::udp::{
+            client::{Client, ClientConfig},
+            runtime::Tokio,
+            voice::VoiceConfig,
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let sender = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            harness.network().bind_any().unwrap(),
+            server_addr,
+            ClientConfig {
+                voice: VoiceConfig {
+                    redundancy_bitrate: Some(16000),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples_per_frame = sender.voice_config().samples_per_frame();
+        let samples: Vec<f32> = (0..samples_per_frame)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        for _ in 0..4 {
+            sender.send_samples(&samples).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        //Lose the frame with the sequence number 2, every frame after the first one carries a copy of the previous frame
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            assert_eq!(
+                voip_header.redundancy().is_some(),
+                voip_header.sequence() != Some(0)
+            );
+
+            if voip_header.sequence() == Some(2) {
+                continue;
+            }
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let decoded_frames = receiver.recv_frames();
+
+        assert_eq!(decoded_frames.len(), 4);
+
+        for (sequence, decoded_frame) in decoded_frames.into_iter().enumerate() {
+            assert_eq!(decoded_frame.sequence, Some(sequence as u32));
+            assert!(!decoded_frame.concealed);
+            assert_eq!(decoded_frame.samples.len(), samples_per_frame);
+            assert_eq!(
+                decoded_frame.timestamp,
+                Some(decoded_frame.duration * sequence as u32)
+            );
+        }
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn duplicated_voice_survives_a_lost_copy_and_is_deduplicated() {
+        use crate::udp::{
+            client::{Client, ClientConfig},
+            duplicate::DuplicationConfig,
+            runtime::Tokio,
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let sender = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            harness.network().bind_any().unwrap(),
+            server_addr,
+            ClientConfig {
+                duplication: Some(DuplicationConfig {
+                    offset: Duration::from_millis(10),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples_per_frame = sender.voice_config().samples_per_frame();
+        let samples: Vec<f32> = (0..samples_per_frame)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        for _ in 0..3 {
+            sender.send_samples(&samples).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        //Lose the original of the frame with the sequence number 2, the copies are only sent once their offset has passed
+        let mut sequences = vec![];
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            sequences.extend(voip_header.sequence());
+
+            if voip_header.sequence() == Some(2) {
+                continue;
+            }
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(sequences, vec![0, 1, 2]);
+
+        harness.advance(Duration::from_millis(10)).await;
+
+        //Relay every copy, the receiver drops the ones it already has
+        sequences.clear();
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            sequences.extend(voip_header.sequence());
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(sequences, vec![0, 1, 2]);
+
+        harness.settle().await;
+
+        let decoded_frames = receiver.recv_frames();
+
+        assert_eq!(decoded_frames.len(), 3);
+
+        for (sequence, decoded_frame) in decoded_frames.into_iter().enumerate() {
+            assert_eq!(decoded_frame.sequence, Some(sequence as u32));
+            assert!(!decoded_frame.concealed);
+        }
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn aggregated_voice_frames_are_split_by_receivers() {
+        use crate::{
+            packet::VoipMessageType,
+            udp::{
+                client::{Client, ClientConfig},
+                runtime::Tokio,
+                voice::{VoiceConfig, VoiceError},
+            },
+        };
+
+        //The aggregated frames can't be longer than a single Opus packet
+        assert!(matches!(
+            VoiceConfig {
+                frame_duration_ms: 60,
+                frames_per_packet: 3,
+                ..Default::default()
+            }
+            .validate(),
+            Err(VoiceError::UnsupportedAggregation { .. })
+        ));
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let sender = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            harness.network().bind_any().unwrap(),
+            server_addr,
+            ClientConfig {
+                voice: VoiceConfig {
+                    frame_duration_ms: 10,
+                    frames_per_packet: 3,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples_per_frame = sender.voice_config().samples_per_frame();
+        let samples: Vec<f32> = (0..samples_per_frame * 6)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        sender.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        //Every message carries three frames
+        let mut sequences = vec![];
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            if let VoipMessageType::VoiceMessage(_) = voip_header.voip_message_type() {
+                assert_eq!(voip_header.frame_count(), 3);
+
+                sequences.push(voip_header.sequence());
+            }
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(sequences, vec![Some(0), Some(3)]);
+
+        harness.settle().await;
+
+        let decoded_frames = receiver.recv_frames();
+
+        assert_eq!(decoded_frames.len(), 6);
+
+        for (sequence, decoded_frame) in decoded_frames.into_iter().enumerate() {
+            assert_eq!(decoded_frame.sequence, Some(sequence as u32));
+            assert!(!decoded_frame.concealed);
+            assert_eq!(decoded_frame.samples.len(), samples_per_frame);
+            assert_eq!(
+                decoded_frame.timestamp,
+                Some(decoded_frame.duration * sequence as u32)
+            );
+        }
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn idle_decoders_are_torn_down_and_capped() {
+        use crate::{
+            packet::VoipMessageType,
+            udp::{
+                client::{Client, ClientConfig},
+                decoder::DecoderLimits,
+                runtime::Tokio,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (first_sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (second_sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        let receiver_transport = harness.network().bind_any().unwrap();
+        let receiver_addr = receiver_transport.local_addr();
+        let mut receiver = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            receiver_transport,
+            server_addr,
+            ClientConfig {
+                decoders: DecoderLimits {
+                    idle_timeout: Some(Duration::from_secs(10)),
+                    max_authors: Some(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        let samples: Vec<f32> = (0..first_sender.voice_config().samples_per_frame())
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        //Relays the voice messages received by the server to the receiver
+        async fn relay_voice(server: &mut crate::udp::server::Server) {
+            while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+                if let VoipMessageType::VoiceMessage(_) = voip_header.voip_message_type() {
+                    server
+                        .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+
+        first_sender.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        second_sender.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        relay_voice(&mut server).await;
+
+        harness.settle().await;
+
+        //Only a single author is decoded at a time
+        let authors: Vec<Uuid> = receiver
+            .recv_frames()
+            .into_iter()
+            .map(|decoded_frame| decoded_frame.author)
+            .collect();
+
+        assert_eq!(authors, vec![first_sender.uuid()]);
+
+        //The decoder of the first author is torn down once they have stopped sending
+        for _ in 0..15 {
+            harness.advance(Duration::from_secs(1)).await;
+        }
+
+        second_sender.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        relay_voice(&mut server).await;
+
+        harness.settle().await;
+
+        let authors: Vec<Uuid> = receiver
+            .recv_frames()
+            .into_iter()
+            .map(|decoded_frame| decoded_frame.author)
+            .collect();
+
+        assert_eq!(authors, vec![second_sender.uuid()]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn only_the_loudest_speakers_are_decoded() {
+        use crate::udp::{
+            client::{Client, ClientConfig},
+            decoder::DecoderLimits,
+            runtime::Tokio,
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (quiet_sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (loud_sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        let receiver_transport = harness.network().bind_any().unwrap();
+        let receiver_addr = receiver_transport.local_addr();
+        let mut receiver = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            receiver_transport,
+            server_addr,
+            ClientConfig {
+                decoders: DecoderLimits {
+                    max_speakers: Some(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples = |amplitude: f32| -> Vec<f32> {
+            (0..quiet_sender.voice_config().samples_per_frame())
+                .map(|index| (index as f32 / 20.).sin() * amplitude)
+                .collect()
+        };
+
+        //The quiet speaker is replaced by the louder one, and isn't decoded while the louder one speaks
+        for (sender, amplitude) in [
+            (&quiet_sender, 0.01),
+            (&loud_sender, 0.5),
+            (&quiet_sender, 0.01),
+        ] {
+            sender.send_samples(&samples(amplitude)).await.unwrap();
+
+            harness.settle().await;
+
+            while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+                server
+                    .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                    .await
+                    .unwrap();
+            }
+
+            harness.settle().await;
+        }
+
+        let authors: Vec<Uuid> = receiver
+            .recv_frames()
+            .into_iter()
+            .map(|decoded_frame| decoded_frame.author)
+            .collect();
+
+        assert_eq!(authors, vec![quiet_sender.uuid(), loud_sender.uuid()]);
+
+        //The statistics of the skipped speaker are kept
+        let speaker_stats = receiver.speaker_stats();
+
+        assert!(speaker_stats[&loud_sender.uuid()].is_decoded);
+        assert!(!speaker_stats[&quiet_sender.uuid()].is_decoded);
+        assert_eq!(speaker_stats[&quiet_sender.uuid()].skipped_messages, 1);
+        assert!(speaker_stats[&quiet_sender.uuid()].audio_level.is_some());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn pushed_samples_are_framed_with_capture_timestamps() {
+        use tokio::time::Instant;
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //Drain the heartbeat of the client
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples_per_frame = sender.voice_config().samples_per_frame();
+        let frame_duration = Duration::from_millis(sender.voice_config().frame_duration_ms as u64);
+        let samples: Vec<f32> = (0..samples_per_frame * 2)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        let capture_start = Instant::now();
+
+        //One and a half frames, then the remaining half frame
+        sender
+            .push_samples(&samples[..samples_per_frame * 3 / 2], capture_start)
+            .await
+            .unwrap();
+        sender
+            .push_samples(
+                &samples[samples_per_frame * 3 / 2..],
+                capture_start + frame_duration * 3 / 2,
+            )
+            .await
+            .unwrap();
+
+        //The capture resumes after a pause, with a frame and a half which is flushed
+        sender
+            .push_samples(
+                &samples[..samples_per_frame * 3 / 2],
+                capture_start + Duration::from_secs(1),
+            )
+            .await
+            .unwrap();
+        sender.flush_samples().await.unwrap();
+
+        harness.settle().await;
+
+        let timestamps: Vec<(Option<u32>, Option<u64>)> =
+            std::iter::from_fn(|| server.message_receiver().try_recv().ok())
+                .map(|(voip_header, _, _)| (voip_header.sequence(), voip_header.timestamp()))
+                .collect();
+
+        let frame_micros = frame_duration.as_micros() as u64;
+
+        assert_eq!(
+            timestamps,
+            vec![
+                (Some(0), Some(0)),
+                (Some(1), Some(frame_micros)),
+                (Some(2), Some(1_000_000)),
+                (Some(3), Some(1_000_000 + frame_micros)),
+            ]
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[test]
+    fn jitter_buffer_reorders_and_skips_lost_frames() {
+        use crate::audio::jitter::{JitterBuffer, JitterConfig};
+
+        //Frames of a single mono sample at 1kHz, so every frame is a millisecond long
+        let mut jitter_buffer = JitterBuffer::new(
+            JitterConfig {
+                min_delay: Duration::ZERO,
+                target_delay: Duration::from_millis(4),
+                max_delay: Duration::from_millis(10),
+            },
+            1000,
+            1,
+        );
+
+        let mut output = [0.; 1];
+
+        //The sequence numbers wrap around, the frame 1 is lost and the frame 0 arrives late
+        for sequence in [u32::MAX, 2, 0] {
+            jitter_buffer.push(Some(sequence), vec![sequence as f32]);
+            jitter_buffer.pull(&mut output);
+
+            //Nothing is played out until the target delay is buffered
+            assert_eq!(output, [0.]);
+        }
+
+        jitter_buffer.push(Some(3), vec![3.]);
+
+        let mut played_out = vec![];
+
+        while jitter_buffer.pull(&mut output) > 0 {
+            played_out.push(output[0]);
+        }
+
+        assert_eq!(played_out, vec![u32::MAX as f32, 0., 2., 3.]);
+
+        //Frames which are too late to be played are discarded
+        jitter_buffer.push(Some(1), vec![1.]);
+
+        assert!(jitter_buffer.is_empty());
+    }
+
+    #[cfg(feature = "all")]
+    #[test]
+    fn playout_delay_is_configurable_and_reported() {
+        use crate::audio::jitter::{JitterBuffer, JitterConfig};
+
+        //Frames of a single mono sample at 1kHz, so every frame is a millisecond long
+        let mut jitter_buffer = JitterBuffer::new(
+            JitterConfig {
+                min_delay: Duration::from_millis(2),
+                target_delay: Duration::from_millis(20),
+                max_delay: Duration::from_millis(8),
+            },
+            1000,
+            1,
+        );
+
+        //The target delay is clamped between the minimum and the maximum delay
+        assert_eq!(jitter_buffer.target_delay(), Duration::from_millis(8));
+        assert_eq!(
+            jitter_buffer.set_target_delay(Duration::ZERO),
+            Duration::from_millis(2)
+        );
+
+        let mut output = [0.; 1];
+
+        jitter_buffer.push(Some(0), vec![0.]);
+
+        //Nothing is played out until the lowered target delay is buffered
+        assert_eq!(jitter_buffer.pull(&mut output), 0);
+        assert_eq!(jitter_buffer.playout_delay(), Duration::from_millis(1));
+
+        for sequence in 1..4 {
+            jitter_buffer.push(Some(sequence), vec![sequence as f32]);
+        }
+
+        assert_eq!(jitter_buffer.playout_delay(), Duration::from_millis(4));
+        assert_eq!(jitter_buffer.pull(&mut output), 1);
+        assert_eq!(jitter_buffer.playout_delay(), Duration::from_millis(3));
+    }
+
+    #[cfg(feature = "all")]
+    #[test]
+    fn audio_pipeline_runs_its_stages_in_order() {
+        use crate::audio::pipeline::{AudioPipeline, AudioStage};
+
+        let gain = |samples: &mut [f32]| samples.iter_mut().for_each(|sample| *sample *= 2.);
+        let limiter = |samples: &mut [f32]| {
+            samples
+                .iter_mut()
+                .for_each(|sample| *sample = sample.clamp(-1., 1.))
+        };
+
+        //Pipelines are stages themselves, so they can be nested
+        let mut pipeline = AudioPipeline::new()
+            .with_stage(AudioPipeline::new().with_stage(gain).with_stage(gain))
+            .with_stage(limiter);
+
+        assert_eq!(pipeline.len(), 2);
+
+        let mut samples = [0.1, -0.2, 0.4];
+
+        pipeline.process(&mut samples);
+
+        assert_eq!(samples, [0.4, -0.8, 1.]);
+
+        //An empty pipeline leaves the samples untouched
+        AudioPipeline::new().process(&mut samples);
+
+        assert_eq!(samples, [0.4, -0.8, 1.]);
+    }
+
+    #[cfg(feature = "all")]
+    #[test]
+    fn jitter_buffer_reports_its_statistics() {
+        use crate::audio::jitter::{JitterBuffer, JitterConfig, JitterStats};
+
+        //Frames of a single mono sample at 1kHz, so every frame is a millisecond long
+        let mut jitter_buffer = JitterBuffer::new(
+            JitterConfig {
+                min_delay: Duration::ZERO,
+                target_delay: Duration::from_millis(2),
+                max_delay: Duration::from_millis(4),
+            },
+            1000,
+            1,
+        );
+
+        let mut output = [0.; 1];
+
+        //The playout is held back until the target delay is buffered
+        assert_eq!(jitter_buffer.pull(&mut output), 0);
+
+        //The frame 1 is lost, and the playout runs dry after the frame 2
+        jitter_buffer.push(Some(0), vec![0.]);
+        jitter_buffer.push(Some(2), vec![2.]);
+
+        for _ in 0..2 {
+            assert_eq!(jitter_buffer.pull(&mut output), 1);
+        }
+
+        assert_eq!(jitter_buffer.pull(&mut output), 0);
+
+        //The frame 1 arrives too late, and the frames after the concealed frame 3 exceed the maximum delay
+        jitter_buffer.push_concealed(Some(3), vec![3.]);
+        jitter_buffer.push(Some(1), vec![1.]);
+
+        for sequence in 4..8 {
+            jitter_buffer.push(Some(sequence), vec![sequence as f32]);
+        }
+
+        assert_eq!(
+            jitter_buffer.stats(),
+            JitterStats {
+                buffered: Duration::from_millis(4),
+                concealed_frames: 2,
+                late_frames: 1,
+                accelerations: 1,
+                decelerations: 1,
+                underruns: 1,
+                overruns: 1,
+            }
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn received_voice_is_pulled_mixed() {
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (receiver, receiver_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples_per_frame = sender.voice_config().samples_per_frame();
+        let mut frames = vec![0.; samples_per_frame];
+
+        //Nothing is received yet
+        receiver.pull_mixed_audio(&mut frames);
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+
+        let samples: Vec<f32> = (0..samples_per_frame * 4)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        sender.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        //Every frame is played out, then the output falls silent
+        for _ in 0..4 {
+            receiver.pull_mixed_audio(&mut frames);
+
+            assert!(frames.iter().any(|sample| *sample != 0.));
+        }
+
+        receiver.pull_mixed_audio(&mut frames);
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn sent_voice_is_played_out_as_sidetone() {
+        use crate::udp::{
+            client::{Client, ClientConfig},
+            runtime::Tokio,
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            harness.network().bind_any().unwrap(),
+            server_addr,
+            ClientConfig {
+                sidetone: Some(1.),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        let samples_per_frame = client.voice_config().samples_per_frame();
+        let mut frames = vec![0.; samples_per_frame];
+
+        let samples: Vec<f32> = (0..samples_per_frame * 4)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        client.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        //The sent voice is heard without being relayed back by the server
+        for _ in 0..4 {
+            client.pull_mixed_audio(&mut frames);
+
+            assert!(frames.iter().any(|sample| *sample != 0.));
+        }
+
+        client.pull_mixed_audio(&mut frames);
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+
+        //The sidetone can be disabled while the client is running
+        client.set_sidetone(None);
+        client.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        client.pull_mixed_audio(&mut frames);
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+
+        //The voice was sent to the server regardless of the sidetone
+        assert!(server.message_receiver().try_recv().is_ok());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn sounds_are_mixed_into_their_targets() {
+        use crate::{
+            audio::soundboard::SoundTarget,
+            packet::{VoipMessageType, AUDIO_LEVEL_SILENCE},
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //Drain the heartbeats of the client
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples_per_frame = client.voice_config().samples_per_frame();
+        let mut frames = vec![0.; samples_per_frame];
+
+        let sound: Vec<f32> = (0..samples_per_frame * 2)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+        let silence = vec![0.; samples_per_frame * 2];
+
+        //The sounds played back are only heard locally, with their own gain
+        client.set_sound_gain(SoundTarget::PLAYBACK, 0.5);
+        client.play_sound(&sound, SoundTarget::PLAYBACK);
+
+        for chunk in sound.chunks(samples_per_frame) {
+            client.pull_mixed_audio(&mut frames);
+
+            for (frame, sample) in frames.iter().zip(chunk) {
+                assert!((frame - sample * 0.5).abs() < 1e-6);
+            }
+        }
+
+        client.pull_mixed_audio(&mut frames);
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+
+        client.send_samples(&silence).await.unwrap();
+
+        harness.settle().await;
+
+        while let Ok((voip_header, _, _)) = server.message_receiver().try_recv() {
+            if let VoipMessageType::VoiceMessage(_) = voip_header.voip_message_type() {
+                assert_eq!(voip_header.audio_level(), Some(AUDIO_LEVEL_SILENCE));
+            }
+        }
+
+        //The outgoing sounds are sent mixed into the voice, without being heard locally
+        client.play_sound(&sound, SoundTarget::OUTGOING);
+        client.send_samples(&silence).await.unwrap();
+
+        harness.settle().await;
+
+        let audio_levels: Vec<u8> =
+            std::iter::from_fn(|| server.message_receiver().try_recv().ok())
+                .filter_map(|(voip_header, _, _)| voip_header.audio_level())
+                .collect();
+
+        assert!(audio_levels
+            .iter()
+            .any(|audio_level| *audio_level < AUDIO_LEVEL_SILENCE));
+
+        client.pull_mixed_audio(&mut frames);
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+
+        //Both targets can be stopped at once
+        client.play_sound(&sound, SoundTarget::PLAYBACK | SoundTarget::OUTGOING);
+        client.stop_sounds(SoundTarget::PLAYBACK | SoundTarget::OUTGOING);
+        client.pull_mixed_audio(&mut frames);
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn audio_streams_are_mixed_together() {
+        use crate::{
+            packet::VoipMessageType,
+            udp::{event::ClientError, voice::VoiceConfig},
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (receiver, receiver_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        assert!(matches!(
+            sender.add_audio_stream(0, VoiceConfig::music()),
+            Err(ClientError::DefaultAudioStream)
+        ));
+        assert!(matches!(
+            sender.flush_stream_samples(1).await,
+            Err(ClientError::UnknownAudioStream(1))
+        ));
+
+        //The screen audio is encoded with its own codec settings
+        sender.add_audio_stream(1, VoiceConfig::music()).unwrap();
+
+        let voice_samples: Vec<f32> = (0..sender.voice_config().samples_per_frame() * 4)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+        let music_samples: Vec<f32> = (0..VoiceConfig::music().samples_per_frame() * 4)
+            .map(|index| (index as f32 / 30.).sin() * 0.5)
+            .collect();
+
+        let now = tokio::time::Instant::now();
+
+        sender.push_samples(&voice_samples, now).await.unwrap();
+        sender
+            .push_stream_samples(1, &music_samples, now)
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let mut streams = vec![];
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            if let VoipMessageType::VoiceMessage(_) = voip_header.voip_message_type() {
+                streams.push(voip_header.stream());
+            }
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        streams.sort();
+
+        assert_eq!(streams, [[None; 4], [Some(1); 4]].concat());
+
+        harness.settle().await;
+
+        //The streams are played out at the same time, not one after the other
+        let mut frames = vec![0.; receiver.voice_config().samples_per_frame()];
+
+        for _ in 0..4 {
+            receiver.pull_mixed_audio(&mut frames);
+
+            assert!(frames.iter().any(|sample| *sample != 0.));
+        }
+
+        receiver.pull_mixed_audio(&mut frames);
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn gaps_are_filled_with_comfort_noise() {
+        use crate::{
+            audio::comfort_noise::ComfortNoiseConfig,
+            packet::audio_level,
+            udp::{
+                client::{Client, ClientConfig},
+                runtime::Tokio,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let receiver_socket = harness.network().bind_any().unwrap();
+        let receiver_addr = receiver_socket.local_addr();
+        let receiver = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            receiver_socket,
+            server_addr,
+            ClientConfig {
+                comfort_noise: Some(ComfortNoiseConfig {
+                    max_duration: Duration::from_secs(1),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples_per_frame = sender.voice_config().samples_per_frame();
+        let mut frames = vec![0.; samples_per_frame];
+
+        //Nothing is generated before anything is received
+        receiver.pull_mixed_audio(&mut frames);
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+
+        //A quiet background of about -57 dBov
+        let samples: Vec<f32> = (0..samples_per_frame * 4)
+            .map(|index| (index as f32 / 20.).sin() * 0.002)
+            .collect();
+
+        sender.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        for _ in 0..4 {
+            receiver.pull_mixed_audio(&mut frames);
+        }
+
+        //The stream has run dry, the gap is filled with noise at the level of the background
+        receiver.pull_mixed_audio(&mut frames);
+
+        let noise_level = audio_level(&frames);
+
+        assert!((50..=65).contains(&noise_level), "{noise_level}");
+
+        //The noise stops once the author has been missing for too long
+        for _ in 0..50 {
+            receiver.pull_mixed_audio(&mut frames);
+        }
+
+        assert!(frames.iter().all(|sample| *sample == 0.));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn received_voice_plays_through_rodio_sources() {
+        use rodio::Source;
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (receiver, receiver_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let mixed_source = receiver.mixed_source();
+        let stream_source = receiver.stream_source(sender.uuid());
+
+        assert_eq!(mixed_source.sample_rate(), 48000);
+        assert_eq!(mixed_source.channels(), 2);
+
+        let samples_per_frame = sender.voice_config().samples_per_frame();
+        let samples: Vec<f32> = (0..samples_per_frame * 4)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        sender.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        //The voice of the sender is only played out by its own source
+        assert!(mixed_source
+            .take(samples_per_frame)
+            .all(|sample| sample == 0.));
+        assert!(stream_source
+            .take(samples_per_frame)
+            .any(|sample| sample != 0.));
+    }
+
+    #[cfg(all(feature = "all", unix))]
+    #[tokio::test]
+    async fn exchange_data_over_unix_sockets() {
+        use crate::udp::{client::Client, server::Server, transport::unix::UnixTransport};
+
+        let socket_dir = std::env::temp_dir().join(format!("silence-{}", Uuid::new_v4()));
+        std::fs::create_dir(&socket_dir).unwrap();
+
+        let server_path = socket_dir.join("server.sock");
+        let client_transport = UnixTransport::bind(socket_dir.join("client.sock")).unwrap();
+        let server_addr = client_transport.register_peer(&server_path);
+
+        let mut server = Server::new_from_transport(UnixTransport::bind(&server_path).unwrap())
+            .await
+            .unwrap();
+        let mut client = Client::new_from_transport(Uuid::new_v4(), client_transport, server_addr)
+            .await
+            .unwrap();
+
+        //Wait for the heartbeat echo, so that the client is registered on the server
+        assert!(matches!(
+            client.event_receiver().recv().await.unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+
+        let packet = VoipHeader::new(
+            crate::packet::VoipMessageType::VoiceMessage(1),
+            client.uuid(),
+        );
+
+        client
+            .message_sender()
+            .send(packet.create_message_buffer(&[1; 1]).unwrap())
+            .await
+            .unwrap();
+
+        let (_packet, voip_body, addr) = server.message_receiver().recv().await.unwrap();
+
+        assert_eq!(voip_body, vec![1; 1]);
+        assert!(server.peers().contains_key(&addr));
+
+        std::fs::remove_dir_all(socket_dir).unwrap();
+    }
+
+    #[cfg(feature = "all")]
+    #[test]
+    fn exchange_data_on_smol() {
+        use crate::udp::{
+            client::Client, runtime::Smol, server::Server, transport::memory::MemoryNetwork,
+        };
+
+        smol::block_on(async {
+            let network = MemoryNetwork::new();
+
+            let server_socket = network.bind_any().unwrap();
+            let server_addr = server_socket.local_addr();
+
+            let mut server = Server::new_from_transport_with_runtime::<Smol, _>(server_socket)
+                .await
+                .unwrap();
+            let mut client = Client::new_from_transport_with_runtime::<Smol, _>(
+                Uuid::new_v4(),
+                network.bind_any().unwrap(),
+                server_addr,
+            )
+            .await
+            .unwrap();
+
+            let packet = VoipHeader::new(
+                crate::packet::VoipMessageType::VoiceMessage(1),
+                client.uuid(),
+            );
+
+            client
+                .message_sender()
+                .send(packet.create_message_buffer(&[1; 1]).unwrap())
+                .await
+                .unwrap();
+
+            let (_packet, voip_body, _addr) = server.message_receiver().recv().await.unwrap();
+
+            assert_eq!(voip_body, vec![1; 1]);
+        });
+    }
+
+    #[cfg(feature = "all")]
+    #[test]
+    fn exchange_data_with_a_blocking_client() {
+        use std::sync::mpsc;
+
+        use crate::{
+            packet::VoipMessageType,
+            udp::{blocking::BlockingClient, server::Server, transport::memory::MemoryNetwork},
+        };
+
+        let network = MemoryNetwork::new();
+
+        let server_socket = network.bind_any().unwrap();
+        let server_addr = server_socket.local_addr();
+
+        let mut client = BlockingClient::new_from_transport(
+            Uuid::new_v4(),
+            network.bind_any().unwrap(),
+            server_addr,
+        )
+        .unwrap();
+
+        //The server is driven by the runtime of the blocking client
+        let mut server = client
+            .runtime()
+            .block_on(Server::new_from_transport(server_socket))
+            .unwrap();
+
+        client
+            .send_bytes(VoipMessageType::TextMessage(5), &mut "Hello".bytes())
+            .unwrap();
+
+        //Skip the heartbeats of the client
+        let (voip_header, voip_body, client_addr) = loop {
+            let message = client
+                .runtime()
+                .block_on(tokio::time::timeout(
+                    Duration::from_secs(5),
+                    server.message_receiver().recv(),
+                ))
+                .unwrap()
+                .unwrap();
+
+            if matches!(
+                message.0.voip_message_type(),
+                VoipMessageType::TextMessage(_)
+            ) {
+                break message;
+            }
+        };
+
+        assert_eq!(voip_body, b"Hello");
+
+        server.get_reply_to_list_mut().insert(client_addr);
+
+        let reply = |client: &BlockingClient| {
+            client
+                .runtime()
+                .block_on(
+                    server.reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap()),
+                )
+                .unwrap();
+        };
+
+        reply(&client);
+
+        assert!(matches!(
+            client.recv_timeout(Duration::from_secs(5)),
+            Some(ClientEvent::ConnectionStateChanged(
+                ConnectionState::Connected
+            ))
+        ));
+        assert!(matches!(
+            client.recv_timeout(Duration::from_secs(5)),
+            Some(ClientEvent::Text { text, .. }) if text == "Hello"
+        ));
+
+        //Nothing else arrives
+        assert!(client.recv_timeout(Duration::from_millis(50)).is_none());
+
+        reply(&client);
+
+        let mut client_events = vec![];
+
+        for _ in 0..100 {
+            client_events = client.poll_all();
+
+            if !client_events.is_empty() {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(matches!(
+            client_events.as_slice(),
+            [ClientEvent::Text { text, .. }] if text == "Hello"
+        ));
+
+        //Dropping the client shuts down its runtime without hanging
+        let (dropped_sender, dropped_receiver) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            drop(server);
+            drop(client);
+
+            let _ = dropped_sender.send(());
+        });
+
+        assert!(dropped_receiver
+            .recv_timeout(Duration::from_secs(5))
+            .is_ok());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn received_video_is_decoded_into_rgba_frames() {
+        use crate::{
+            packet::{frame::VideoFrame, MediaCodec},
+            udp::{
+                client::DEFAULT_VIDEO_PACING_INTERVAL,
+                event::MediaError,
+                video::{RgbaImage, VideoDecoder},
+            },
+        };
+
+        //Interprets the payload as a 2x2 RGBA image
+        struct PassthroughDecoder;
+
+        impl VideoDecoder for PassthroughDecoder {
+            fn decode(
+                &mut self,
+                video_frame: &VideoFrame,
+            ) -> Result<Option<RgbaImage>, MediaError> {
+                Ok(Some(RgbaImage {
+                    width: 2,
+                    height: 2,
+                    rgba: video_frame.payload.clone(),
+                }))
+            }
+        }
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        receiver.register_video_decoder(MediaCodec::Custom(7), || Box::new(PassthroughDecoder));
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let pixels: Vec<u8> = (0..16).collect();
+
+        sender
+            .send_video_packet(&pixels, MediaCodec::Custom(7), true)
+            .await
+            .unwrap();
+
+        //Frames without a registered decoder are only reported as events
+        sender
+            .send_video_packet(&pixels, MediaCodec::Avif, true)
+            .await
+            .unwrap();
+
+        //Let the client service pace out both frames
+        for _ in 0..2 {
+            harness.advance(DEFAULT_VIDEO_PACING_INTERVAL).await;
+        }
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let decoded_video_frames = receiver.recv_video_frames();
+
+        assert_eq!(decoded_video_frames.len(), 1);
+        assert_eq!(decoded_video_frames[0].author, sender.uuid());
+        assert_eq!(decoded_video_frames[0].sequence, Some(0));
+        assert!(decoded_video_frames[0].timestamp.is_some());
+        assert_eq!(
+            decoded_video_frames[0].image,
+            RgbaImage {
+                width: 2,
+                height: 2,
+                rgba: pixels,
+            }
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn video_slices_survive_the_loss_of_other_slices() {
+        use crate::{
+            packet::{frame::VideoSlices, MediaCodec},
+            udp::{client::DEFAULT_VIDEO_PACING_INTERVAL, event::ClientEvent},
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats and the events of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+        while receiver.event_receiver().try_recv().is_ok() {}
+
+        for _ in 0..2 {
+            sender
+                .send_video_slices(
+                    vec![vec![1; 4], vec![2; 4], vec![3; 4]],
+                    MediaCodec::Custom(7),
+                    true,
+                )
+                .await
+                .unwrap();
+
+            for _ in 0..3 {
+                harness.advance(DEFAULT_VIDEO_PACING_INTERVAL).await;
+            }
+
+            //The middle slice of the first frame is lost
+            while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+                if voip_header.sequence() == Some(0)
+                    && voip_header.slice().is_some_and(|slice| slice.index == 1)
+                {
+                    continue;
+                }
+
+                server
+                    .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                    .await
+                    .unwrap();
+            }
+
+            harness.settle().await;
+        }
+
+        let video_frames: Vec<_> = std::iter::from_fn(|| receiver.event_receiver().try_recv().ok())
+            .filter_map(|client_event| match client_event {
+                ClientEvent::VideoFrame(video_frame) => Some(video_frame),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(video_frames.len(), 2);
+
+        //The first frame is reported without its lost slice once the next frame starts
+        assert_eq!(video_frames[0].sequence, Some(0));
+        assert!(video_frames[0].is_keyframe);
+        assert_eq!(
+            video_frames[0].slices,
+            Some(VideoSlices {
+                count: 3,
+                lengths: vec![(0, 4), (2, 4)],
+            })
+        );
+        assert_eq!(
+            video_frames[0].slice_payloads(),
+            vec![(0, &[1; 4][..]), (2, &[3; 4][..])]
+        );
+
+        //The second frame is reported as soon as all of its slices have arrived
+        assert_eq!(video_frames[1].sequence, Some(1));
+        assert!(video_frames[1]
+            .slices
+            .as_ref()
+            .is_some_and(|slices| slices.is_complete()));
+        assert_eq!(video_frames[1].payload, [[1; 4], [2; 4], [3; 4]].concat());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn sent_video_is_refreshed_by_the_encoder_config() {
+        use std::sync::Arc;
+
+        use parking_lot::Mutex;
+
+        use crate::{
+            packet::{MediaCodec, VoipMessageType},
+            udp::{
+                client::DEFAULT_VIDEO_PACING_INTERVAL,
+                event::{ClientError, MediaError},
+                video::{
+                    FrameRefresh, IntraRefreshConfig, RgbaImage, VideoEncoder, VideoEncoderConfig,
+                },
+            },
+        };
+
+        //Records the refreshes, and sends the pixels as they are
+        struct RecordingEncoder(Arc<Mutex<Vec<FrameRefresh>>>);
+
+        impl VideoEncoder for RecordingEncoder {
+            fn encode(
+                &mut self,
+                image: &RgbaImage,
+                refresh: FrameRefresh,
+            ) -> Result<Vec<u8>, MediaError> {
+                self.0.lock().push(refresh);
+
+                Ok(image.rgba.clone())
+            }
+        }
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //Drain the heartbeats of the client
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let image = RgbaImage {
+            width: 1,
+            height: 1,
+            rgba: vec![1, 2, 3, 4],
+        };
+
+        assert!(matches!(
+            client.send_video_image(&image).await,
+            Err(ClientError::NoVideoEncoder)
+        ));
+
+        let refreshes = Arc::new(Mutex::new(vec![]));
+
+        client.set_video_encoder(
+            MediaCodec::Custom(7),
+            Box::new(RecordingEncoder(refreshes.clone())),
+            VideoEncoderConfig {
+                keyframe_interval: Some(4),
+                intra_refresh: Some(IntraRefreshConfig { stripes: 2 }),
+            },
+        );
+
+        for index in 0..7 {
+            //A receiver has lost the video before the last frame
+            if index == 6 {
+                client.request_keyframe();
+            }
+
+            client.send_video_image(&image).await.unwrap();
+
+            harness.advance(DEFAULT_VIDEO_PACING_INTERVAL).await;
+        }
+
+        harness.settle().await;
+
+        //The picture is refreshed by stripes instead of periodic keyframes
+        assert_eq!(
+            *refreshes.lock(),
+            vec![
+                FrameRefresh::Keyframe,
+                FrameRefresh::Predicted,
+                FrameRefresh::Predicted,
+                FrameRefresh::Predicted,
+                FrameRefresh::IntraRefresh {
+                    stripe: 0,
+                    stripes: 2
+                },
+                FrameRefresh::IntraRefresh {
+                    stripe: 1,
+                    stripes: 2
+                },
+                FrameRefresh::Keyframe,
+            ]
+        );
+
+        //Only the keyframes are marked
+        let markers: Vec<bool> = std::iter::from_fn(|| server.message_receiver().try_recv().ok())
+            .filter(|(voip_header, _, _)| {
+                matches!(
+                    voip_header.voip_message_type(),
+                    VoipMessageType::VideoMessage(_)
+                )
+            })
+            .map(|(voip_header, _, _)| voip_header.is_marker())
+            .collect();
+
+        assert_eq!(markers, vec![true, false, false, false, false, false, true]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn late_video_frames_are_dropped_by_the_latency_budget() {
+        use std::sync::Arc;
+
+        use parking_lot::Mutex;
+
+        use crate::{
+            packet::{MediaCodec, VoipMessageType},
+            udp::{
+                client::{Client, ClientConfig},
+                event::{ClientEvent, MediaError},
+                runtime::Tokio,
+                video::{FrameRefresh, RgbaImage, VideoEncoder, VideoEncoderConfig},
+            },
+            MTU_MAX_PACKET_SIZE,
+        };
+
+        //Records the refreshes, and encodes every image into two fragments
+        struct RecordingEncoder(Arc<Mutex<Vec<FrameRefresh>>>);
+
+        impl VideoEncoder for RecordingEncoder {
+            fn encode(
+                &mut self,
+                _image: &RgbaImage,
+                refresh: FrameRefresh,
+            ) -> Result<Vec<u8>, MediaError> {
+                self.0.lock().push(refresh);
+
+                Ok(vec![0; MTU_MAX_PACKET_SIZE * 3 / 2])
+            }
+        }
+
+        let pacing_interval = Duration::from_millis(10);
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let mut client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            harness.network().bind_any().unwrap(),
+            server_addr,
+            ClientConfig {
+                video_pacing_interval: pacing_interval,
+                video_latency_budget: Some(Duration::from_millis(25)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        //Drain the heartbeats and the events of the client
+        while server.message_receiver().try_recv().is_ok() {}
+        while client.event_receiver().try_recv().is_ok() {}
+
+        let refreshes = Arc::new(Mutex::new(vec![]));
+
+        client.set_video_encoder(
+            MediaCodec::Custom(7),
+            Box::new(RecordingEncoder(refreshes.clone())),
+            VideoEncoderConfig {
+                keyframe_interval: None,
+                intra_refresh: None,
+            },
+        );
+
+        let image = RgbaImage {
+            width: 1,
+            height: 1,
+            rgba: vec![1, 2, 3, 4],
+        };
+
+        //The third frame waits behind the fragments of the first two for longer than the budget
+        for _ in 0..3 {
+            client.send_video_image(&image).await.unwrap();
+        }
+
+        for _ in 0..4 {
+            harness.advance(pacing_interval).await;
+        }
+
+        assert!(matches!(
+            std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+                .find(|client_event| matches!(client_event, ClientEvent::VideoFramesDropped(_))),
+            Some(ClientEvent::VideoFramesDropped(1))
+        ));
+
+        //The frame sent after the drop refreshes the picture
+        client.send_video_image(&image).await.unwrap();
+
+        for _ in 0..2 {
+            harness.advance(pacing_interval).await;
+        }
+
+        assert_eq!(
+            *refreshes.lock(),
+            vec![
+                FrameRefresh::Keyframe,
+                FrameRefresh::Predicted,
+                FrameRefresh::Predicted,
+                FrameRefresh::Keyframe,
+            ]
+        );
+
+        let mut sequences: Vec<u32> =
+            std::iter::from_fn(|| server.message_receiver().try_recv().ok())
+                .filter(|(voip_header, _, _)| {
+                    matches!(
+                        voip_header.voip_message_type(),
+                        VoipMessageType::VideoMessage(_)
+                    )
+                })
+                .filter_map(|(voip_header, _, _)| voip_header.sequence())
+                .collect();
+
+        //Every fragment of the frames which weren't dropped is sent
+        assert_eq!(sequences.len(), 6);
+
+        sequences.dedup();
+
+        assert_eq!(sequences, vec![0, 1, 3]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn frozen_video_is_reported_until_it_recovers() {
+        use crate::{
+            packet::{frame::VideoFrame, MediaCodec},
+            udp::{
+                client::DEFAULT_VIDEO_PACING_INTERVAL,
+                event::{ClientEvent, MediaError},
+                freeze::{FreezeChange, FreezeConfig},
+                video::{RgbaImage, VideoDecoder},
+            },
+        };
+
+        //Interprets the payload as a 1x1 RGBA image
+        struct PassthroughDecoder;
+
+        impl VideoDecoder for PassthroughDecoder {
+            fn decode(
+                &mut self,
+                video_frame: &VideoFrame,
+            ) -> Result<Option<RgbaImage>, MediaError> {
+                Ok(Some(RgbaImage {
+                    width: 1,
+                    height: 1,
+                    rgba: video_frame.payload.clone(),
+                }))
+            }
+        }
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        receiver.register_video_decoder(MediaCodec::Custom(7), || Box::new(PassthroughDecoder));
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let mut freeze_changes = vec![];
+
+        for pixels in [[1, 2, 3, 4], [5, 6, 7, 8]] {
+            sender
+                .send_video_packet(&pixels, MediaCodec::Custom(7), true)
+                .await
+                .unwrap();
+
+            harness.advance(DEFAULT_VIDEO_PACING_INTERVAL).await;
+
+            while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+                server
+                    .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                    .await
+                    .unwrap();
+            }
+
+            harness.settle().await;
+
+            //The video stops arriving after every frame
+            harness
+                .advance(FreezeConfig::default().timeout + Duration::from_millis(1))
+                .await;
+
+            while let Ok(client_event) = receiver.event_receiver().try_recv() {
+                if let ClientEvent::VideoFreezeChanged(freeze_change) = client_event {
+                    freeze_changes.push(freeze_change);
+                }
+            }
+        }
+
+        //The video is reported frozen with its last frame, and recovered by the next decoded frame
+        match freeze_changes.as_slice() {
+            [FreezeChange::Frozen {
+                frame: first_frame,
+                age,
+            }, FreezeChange::Recovered { author, frozen_for }, FreezeChange::Frozen {
+                frame: second_frame,
+                ..
+            }] => {
+                assert_eq!(first_frame.author, sender.uuid());
+                assert_eq!(first_frame.image.rgba, vec![1, 2, 3, 4]);
+                assert!(*age >= FreezeConfig::default().timeout);
+
+                assert_eq!(*author, sender.uuid());
+                assert!(*frozen_for > FreezeConfig::default().timeout);
+
+                assert_eq!(second_frame.image.rgba, vec![5, 6, 7, 8]);
+            }
+            freeze_changes => panic!("Unexpected freeze changes: {freeze_changes:?}"),
+        }
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn peer_stats_are_tracked_and_reported_to_moderators() {
+        use std::time::Duration;
+
+        use crate::{
+            packet::{frame::VoiceFrame, MediaCodec},
+            udp::{
+                runtime::Tokio,
+                server::{Server, ServerConfig, StatsReportConfig},
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let moderator_uuid = Uuid::new_v4();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                stats_report: Some(StatsReportConfig {
+                    interval: Duration::from_secs(1),
+                    moderators: [moderator_uuid].into(),
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (sender, sender_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut moderator, moderator_addr) =
+            harness.client(moderator_uuid, server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(moderator_addr);
+
+        //The frame with the sequence number 2 is lost
+        for sequence in [0, 1, 3] {
+            let mut voice_frame = VoiceFrame::new(sender.uuid(), MediaCodec::Raw, vec![1]);
+
+            voice_frame.sequence = Some(sequence);
+
+            sender.send_voice_frame(voice_frame).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let stats = server.stats();
+
+        assert_eq!(stats.peers[&sender_addr].author, sender.uuid());
+        assert_eq!(stats.peers[&sender_addr].packets_received, 3);
+        assert_eq!(stats.peers[&sender_addr].estimated_loss, 0.25);
+        assert_eq!(stats.peers[&moderator_addr].packets_sent, 3);
+
+        harness.advance(Duration::from_millis(1500)).await;
+
+        let quality_report = std::iter::from_fn(|| moderator.event_receiver().try_recv().ok())
+            .find_map(|client_event| match client_event {
+                ClientEvent::QualityReport { author, report } if author == sender.uuid() => {
+                    Some(report)
+                }
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(quality_report.packets_received, 3);
+        assert_eq!(quality_report.packets_lost, 1);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn tone_events_are_relayed() {
+        use crate::packet::control::ToneEvent;
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let tone = ToneEvent::dtmf('#', 160).unwrap();
+
+        assert_eq!(tone.tone, 11);
+        assert_eq!(tone.dtmf_digit(), Some('#'));
+        assert_eq!(ToneEvent::dtmf('x', 160), None);
+        assert_eq!(ToneEvent::new(200, 1000).dtmf_digit(), None);
+
+        sender.send_tone(tone).await.unwrap();
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let received_tone = std::iter::from_fn(|| receiver.event_receiver().try_recv().ok())
+            .find_map(|client_event| match client_event {
+                ClientEvent::Tone { author, tone } if author == sender.uuid() => Some(tone),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(received_tone, tone);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn floor_is_granted_to_one_speaker_at_a_time() {
+        use crate::{
+            packet::{control::FloorState, frame::VoiceFrame, MediaCodec},
+            udp::{
+                client::Client,
+                floor::FloorConfig,
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                floor_control: Some(FloorConfig {
+                    max_queue_length: 1,
+                    max_hold_time: Some(Duration::from_secs(5)),
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (mut speaker, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut listener, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut latecomer, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        let floor_states = |client: &mut Client| -> Vec<FloorState> {
+            std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::FloorChanged { channel: 0, state } => Some(state),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        //The floor is granted to the first requester, the next one is queued and the one after is denied
+        speaker.request_floor(0).await.unwrap();
+
+        harness.settle().await;
+
+        listener.request_floor(0).await.unwrap();
+
+        harness.settle().await;
+
+        latecomer.request_floor(0).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            floor_states(&mut speaker),
+            [FloorState::Granted(speaker.uuid())]
+        );
+        assert_eq!(
+            floor_states(&mut listener),
+            [FloorState::Granted(speaker.uuid()), FloorState::Queued(1)]
+        );
+        assert_eq!(
+            floor_states(&mut latecomer),
+            [FloorState::Granted(speaker.uuid()), FloorState::Denied]
+        );
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        //Only the voice of the holder is forwarded
+        for client in [&speaker, &listener] {
+            client
+                .send_voice_frame(VoiceFrame::new(client.uuid(), MediaCodec::Raw, vec![1]))
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let authors: Vec<Uuid> = std::iter::from_fn(|| server.message_receiver().try_recv().ok())
+            .map(|(voip_header, ..)| voip_header.author())
+            .collect();
+
+        assert_eq!(authors, [speaker.uuid()]);
+
+        //The released floor is passed on to the queued requester
+        speaker.release_floor(0).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            floor_states(&mut speaker),
+            [FloorState::Granted(listener.uuid())]
+        );
+
+        //The floor is revoked once it has been held for too long
+        for _ in 0..6 {
+            harness.advance(Duration::from_secs(1)).await;
+        }
+
+        assert_eq!(
+            floor_states(&mut latecomer),
+            [FloorState::Granted(listener.uuid()), FloorState::Idle]
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn calls_are_set_up_and_torn_down() {
+        use crate::{
+            packet::control::{CallSignal, CallSignalKind, ControlMessage},
+            udp::{
+                call::{CallEnd, CallState},
+                client::{send_control_message, Client},
+                event::ClientError,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let (_server, server_addr) = harness.server().await.unwrap();
+        let (mut caller, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut callee, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut third, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        let call_states = |client: &mut Client| -> Vec<CallState> {
+            std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::CallStateChanged(call) => Some(call.state),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        //The callee rings as soon as it is invited
+        let call_id = caller.invite(callee.uuid()).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(call_states(&mut callee), [CallState::Incoming]);
+        assert_eq!(call_states(&mut caller), [CallState::Ringing]);
+        assert_eq!(callee.calls()[0].peer, caller.uuid());
+
+        //The callee is busy while the call hasn't ended
+        third.invite(callee.uuid()).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(call_states(&mut third), [CallState::Ended(CallEnd::Busy)]);
+        assert!(third.calls().is_empty());
+
+        //Only the callee can accept the call
+        assert!(matches!(
+            caller.accept_call(call_id).await,
+            Err(ClientError::InvalidCallState(CallState::Ringing))
+        ));
+
+        callee.accept_call(call_id).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(call_states(&mut caller), [CallState::Active]);
+
+        caller.hang_up(call_id).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            call_states(&mut callee),
+            [CallState::Ended(CallEnd::HungUp)]
+        );
+        assert!(callee.calls().is_empty());
+        assert!(matches!(
+            callee.hang_up(call_id).await,
+            Err(ClientError::UnknownCall(_))
+        ));
+
+        //The server hangs up the calls to the peers which aren't in the session
+        caller.invite(Uuid::new_v4()).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            call_states(&mut caller),
+            [CallState::Ended(CallEnd::HungUp)]
+        );
+
+        //An unregistered address can't signal calls on behalf of a registered author
+        let spoofed_socket = harness.network().bind_any().unwrap();
+
+        send_control_message(
+            &spoofed_socket,
+            ControlMessage::Call(CallSignal {
+                call_id: Uuid::new_v4(),
+                recipient: callee.uuid(),
+                kind: CallSignalKind::Invite,
+            }),
+            caller.uuid(),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert!(call_states(&mut callee).is_empty());
+        assert!(callee.calls().is_empty());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn presence_is_propagated_and_do_not_disturb_rejects_calls() {
+        use crate::{
+            packet::control::{ControlMessage, PresenceState},
+            udp::{
+                call::{CallEnd, CallState},
+                client::send_control_message,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let (_server, server_addr) = harness.server().await.unwrap();
+        let (mut caller, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (callee, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(caller.presence(callee.uuid()), PresenceState::Available);
+
+        callee
+            .set_presence(PresenceState::DoNotDisturb)
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(caller.presence(callee.uuid()), PresenceState::DoNotDisturb);
+
+        //The peers joining later are sent the presence of the others
+        let (latecomer, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            latecomer.presence(callee.uuid()),
+            PresenceState::DoNotDisturb
+        );
+
+        //The server rejects the calls on behalf of the callee
+        caller.invite(callee.uuid()).await.unwrap();
+
+        harness.settle().await;
+
+        let call_states: Vec<CallState> =
+            std::iter::from_fn(|| caller.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::CallStateChanged(call) => Some(call.state),
+                    _ => None,
+                })
+                .collect();
+
+        assert_eq!(call_states, [CallState::Ended(CallEnd::DoNotDisturb)]);
+        assert!(callee.calls().is_empty());
+
+        callee.set_presence(PresenceState::Available).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(caller.presence(callee.uuid()), PresenceState::Available);
+
+        //An unregistered address can't change the presence of a registered author
+        let spoofed_socket = harness.network().bind_any().unwrap();
+
+        send_control_message(
+            &spoofed_socket,
+            ControlMessage::Presence(PresenceState::DoNotDisturb),
+            callee.uuid(),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(caller.presence(callee.uuid()), PresenceState::Available);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn held_clients_only_hear_the_hold_music() {
+        use crate::{
+            packet::{frame::VoiceFrame, MediaCodec},
+            udp::{
+                hold::HoldMusic,
+                runtime::Tokio,
+                server::{Server, ServerConfig, SERVER_AUTHOR},
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                hold_music: Some(HoldMusic::new(
+                    MediaCodec::Raw,
+                    vec![vec![1], vec![2]],
+                    Duration::from_millis(20),
+                )),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (mut held, held_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (speaker, speaker_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(held_addr);
+        server.get_reply_to_list_mut().insert(speaker_addr);
+
+        server.hold(held_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert!(server.peers().get(&held_addr).unwrap().is_on_hold());
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+        while held.event_receiver().try_recv().is_ok() {}
+
+        //The media of the held client isn't forwarded, and it isn't sent the media of the others
+        for client in [&held, &speaker] {
+            client
+                .send_voice_frame(VoiceFrame::new(client.uuid(), MediaCodec::Raw, vec![0]))
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            assert_eq!(voip_header.author(), speaker.uuid());
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        for _ in 0..5 {
+            harness.advance(Duration::from_millis(10)).await;
+        }
+
+        let payloads: Vec<Vec<u8>> = std::iter::from_fn(|| held.event_receiver().try_recv().ok())
+            .filter_map(|client_event| match client_event {
+                ClientEvent::VoiceFrame(voice_frame) => {
+                    assert_eq!(voice_frame.author, SERVER_AUTHOR);
+
+                    Some(voice_frame.payload)
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(payloads, [vec![1], vec![2], vec![1]]);
+
+        //The retrieved client hears the others again
+        server.retrieve(held_addr).await.unwrap();
+
+        harness.settle().await;
+
+        speaker
+            .send_voice_frame(VoiceFrame::new(speaker.uuid(), MediaCodec::Raw, vec![0]))
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.advance(Duration::from_millis(50)).await;
+
+        let authors: Vec<Uuid> = std::iter::from_fn(|| held.event_receiver().try_recv().ok())
+            .filter_map(|client_event| match client_event {
+                ClientEvent::VoiceFrame(voice_frame) => Some(voice_frame.author),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(authors, [speaker.uuid()]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn ecn_marks_lower_the_bitrate_before_any_loss() {
+        use crate::{
+            packet::{frame::VoiceFrame, MediaCodec},
+            udp::{
+                client::{Client, ClientConfig},
+                congestion::CongestionConfig,
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                congestion_feedback: Some(Duration::from_millis(500)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let client_transport = harness.network().bind_any().unwrap();
+        let client_addr = client_transport.local_addr();
+        let client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            client_transport,
+            server_addr,
+            ClientConfig {
+                congestion: Some(CongestionConfig::default()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(client.congestion_bitrate(), Some(64_000));
+
+        //Every datagram of the client is marked by the congested network, but none of them are dropped
+        harness.network().set_congested(client_addr, true);
+
+        for sequence in 0..4 {
+            let mut voice_frame = VoiceFrame::new(client.uuid(), MediaCodec::Raw, vec![0]);
+
+            voice_frame.sequence = Some(sequence);
+
+            client.send_voice_frame(voice_frame).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        let stats = server.stats();
+
+        assert_eq!(stats.peers[&client_addr].packets_ce, 4);
+        assert_eq!(stats.peers[&client_addr].estimated_loss, 0.);
+
+        harness.advance(Duration::from_millis(500)).await;
+
+        //Every message was marked, so the bitrate is halved
+        assert_eq!(client.congestion_bitrate(), Some(32_000));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn datagrams_are_marked_with_the_configured_qos() {
+        use std::sync::Arc;
+
+        use crate::udp::{
+            client::{Client, ClientConfig},
+            congestion::CongestionConfig,
+            runtime::Tokio,
+            server::{Server, ServerConfig},
+            transport::{
+                qos::{Dscp, QosConfig, MAX_FLOW_LABEL},
+                Transport,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let server_transport = Arc::new(server_transport);
+        let _server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport.clone(),
+            ServerConfig {
+                dscp: Some(Dscp::EF),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let client_transport = Arc::new(harness.network().bind_any().unwrap());
+        let _client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            client_transport.clone(),
+            server_addr,
+            ClientConfig {
+                congestion: Some(CongestionConfig::default()),
+                qos: Some(QosConfig {
+                    dscp: Some(Dscp::AF41),
+                    flow_label: Some(0x1_2345),
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(server_transport.dscp(), Dscp::EF);
+        assert_eq!(client_transport.dscp(), Dscp::AF41);
+        assert_eq!(client_transport.flow_label(), 0x1_2345);
+
+        //The DSCP and the ECN codepoint share the traffic class byte
+        assert_eq!(Dscp::EF.to_bits() | 0b10, 0b1011_1010);
+        assert_eq!(Dscp::from_bits(0b1011_1010), Dscp::EF);
+        assert_eq!(Dscp::new(64), None);
+
+        assert_eq!(
+            client_transport
+                .set_flow_label(MAX_FLOW_LABEL + 1)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn path_errors_are_reported_as_events() {
+        use crate::udp::{
+            client::{Client, ClientConfig},
+            event::ClientEvent,
+            runtime::Tokio,
+            server::{Server, ServerConfig, ServerEvent},
+            transport::path::{PathError, PathErrorKind},
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                hop_limit: Some(2),
+                path_errors: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut server_events = server.subscribe_events();
+
+        //The client is three routers away, so the heartbeat reaches the server, but the echo of the server expires on the way
+        let client_transport = harness.network().bind_any().unwrap();
+        let client_addr = client_transport.local_addr();
+
+        harness.network().set_hop_count(client_addr, 3);
+
+        let client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            client_transport,
+            server_addr,
+            ClientConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            std::iter::from_fn(|| server_events.try_recv().ok())
+                .find(|server_event| matches!(server_event, ServerEvent::PathError { .. })),
+            Some(ServerEvent::PathError {
+                remote_addr: client_addr,
+                author: Some(client.uuid()),
+                reporter: None,
+                kind: PathErrorKind::HopLimitExceeded,
+            })
+        );
+
+        //Nothing listens on the address of the stopped server
+        let stopped_addr = harness.network().bind_any().unwrap().local_addr();
+
+        let mut client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            harness.network().bind_any().unwrap(),
+            stopped_addr,
+            ClientConfig {
+                path_errors: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::PathError(PathError {
+                destination,
+                reporter: Some(reporter),
+                kind: PathErrorKind::PortUnreachable,
+            }) if destination == stopped_addr && reporter == stopped_addr.ip()
+        ));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn the_keepalive_interval_converges_on_the_nat_timeout() {
+        use crate::udp::{
+            client::{Client, ClientConfig},
+            event::ClientEvent,
+            keepalive::KeepaliveConfig,
+            runtime::Tokio,
+        };
+
+        let harness = TestHarness::new();
+        let (_server, server_addr) = harness.server().await.unwrap();
+
+        let client_transport = harness.network().bind_any().unwrap();
+
+        //The NAT of the client forgets its binding after 28 seconds of silence
+        harness
+            .network()
+            .set_nat_timeout(client_transport.local_addr(), Some(Duration::from_secs(28)));
+
+        let mut client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            client_transport,
+            server_addr,
+            ClientConfig {
+                heartbeat_interval: Duration::from_secs(5),
+                keepalive: Some(KeepaliveConfig {
+                    max_interval: Duration::from_secs(120),
+                    precision: Duration::from_secs(5),
+                    probe_timeout: Duration::from_secs(2),
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        for _ in 0..180 {
+            harness.advance(Duration::from_secs(1)).await;
+        }
+
+        //The silences of 10 and 20 seconds survive, 40 and 30 seconds expire, then 25 seconds survives
+        let intervals: Vec<Duration> =
+            std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::KeepaliveIntervalChanged(interval) => Some(interval),
+                    _ => None,
+                })
+                .collect();
+
+        assert_eq!(intervals, [10, 20, 25].map(Duration::from_secs).to_vec());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn low_power_mode_pauses_video_and_defers_control_messages() {
+        use crate::{
+            packet::control::PresenceState,
+            udp::{
+                client::{Client, ClientConfig},
+                power::{LowPowerConfig, PowerMode},
+                runtime::Tokio,
+            },
+        };
+
+        let harness = TestHarness::new();
+        let (server, server_addr) = harness.server().await.unwrap();
+
+        let client_transport = harness.network().bind_any().unwrap();
+        let client_addr = client_transport.local_addr();
+
+        let client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            client_transport,
+            server_addr,
+            ClientConfig {
+                heartbeat_interval: Duration::from_secs(5),
+                low_power: LowPowerConfig {
+                    heartbeat_interval: Duration::from_secs(30),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        client.set_power_mode(PowerMode::LowPower).await.unwrap();
+
+        harness.settle().await;
+
+        client.set_presence(PresenceState::Away).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(client.power_mode(), PowerMode::LowPower);
+        assert!(server.peers().get(&client_addr).unwrap().is_video_paused());
+
+        //The presence is held back until the next heartbeat wakes the radio up
+        assert_eq!(
+            server.peers().get(&client_addr).unwrap().presence(),
+            PresenceState::Available
+        );
+
+        harness.advance(Duration::from_secs(5)).await;
+
+        assert_eq!(
+            server.peers().get(&client_addr).unwrap().presence(),
+            PresenceState::Away
+        );
+
+        //The following heartbeats are sent at the low power interval
+        let last_seen = server.peers().get(&client_addr).unwrap().last_seen();
+
+        harness.advance(Duration::from_secs(20)).await;
+
+        assert_eq!(
+            server.peers().get(&client_addr).unwrap().last_seen(),
+            last_seen
+        );
+
+        harness.advance(Duration::from_secs(10)).await;
+
+        assert!(server.peers().get(&client_addr).unwrap().last_seen() > last_seen);
+
+        //Switching back resumes the video, and the control messages are sent right away
+        client.set_power_mode(PowerMode::Normal).await.unwrap();
+        client.set_presence(PresenceState::Busy).await.unwrap();
+
+        harness.settle().await;
+
+        assert!(!server.peers().get(&client_addr).unwrap().is_video_paused());
+        assert_eq!(
+            server.peers().get(&client_addr).unwrap().presence(),
+            PresenceState::Busy
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn the_data_usage_of_the_session_is_counted() {
+        use crate::packet::{
+            control::{CloseCode, CloseReason, ControlMessage, PresenceState},
+            VoipMessageType,
+        };
+
+        let harness = TestHarness::new();
+        let (_server, server_addr) = harness.server().await.unwrap();
+        let (client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //The first heartbeat was sent, and the server has answered it
+        let usage = client.data_usage();
+
+        assert!(usage.datagrams_sent >= 1 && usage.datagrams_received >= 1);
+        assert!(usage.bytes_sent > 0 && usage.bytes_received > 0);
+
+        //Every sent message is counted with its size
+        client.set_presence(PresenceState::Away).await.unwrap();
+
+        harness.settle().await;
+
+        let presence_message = VoipHeader::new(
+            VoipMessageType::Control(ControlMessage::Presence(PresenceState::Away)),
+            client.uuid(),
+        )
+        .create_message_buffer(&[])
+        .unwrap();
+
+        let sent_usage = client.data_usage();
+
+        assert_eq!(sent_usage.datagrams_sent, usage.datagrams_sent + 1);
+        assert_eq!(
+            sent_usage.bytes_sent,
+            usage.bytes_sent + presence_message.inner().len() as u64
+        );
+
+        harness.advance(Duration::from_secs(10)).await;
+
+        let usage = client.data_usage();
+
+        assert!(usage.duration >= Duration::from_secs(10));
+        assert_eq!(
+            usage.average_send_bitrate(),
+            (usage.bytes_sent as f64 * 8.0 / usage.duration.as_secs_f64()) as u32
+        );
+
+        //The duration stops growing once the session is closed
+        client
+            .close(CloseReason::new(CloseCode::Normal, None))
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let closed_usage = client.data_usage();
+
+        harness.advance(Duration::from_secs(10)).await;
+
+        assert_eq!(client.data_usage().duration, closed_usage.duration);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn the_first_answering_address_family_is_kept() {
+        use std::net::{Ipv6Addr, SocketAddr};
+
+        use tokio::net::UdpSocket;
+
+        use crate::udp::{resolve::race, server::Server};
+
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server_socket.local_addr().unwrap();
+        let _server = Server::new_from_transport(server_socket).await.unwrap();
+
+        //Nothing answers on the IPv6 address, like on a host with broken IPv6 connectivity
+        let broken_addr = SocketAddr::from((Ipv6Addr::LOCALHOST, server_addr.port()));
+
+        assert_eq!(race(&[broken_addr, server_addr]).await, Some(server_addr));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn servers_are_managed_through_grpc() {
+        use tonic::{client::Grpc, codec::ProstCodec, codegen::http::uri::PathAndQuery, Request};
+
+        use crate::udp::grpc::{
+            ControlService, CreateRoomRequest, EventKind, GetStatsRequest, KickClientRequest,
+            ListClientsRequest, ListClientsResponse, StreamEventsRequest,
+        };
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+        let (client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        let mut grpc = Grpc::new(ControlService::new(server.handle()));
+
+        grpc.ready().await.unwrap();
+
+        let list_clients: ListClientsResponse = grpc
+            .unary(
+                Request::new(ListClientsRequest {}),
+                PathAndQuery::from_static("/silence.Control/ListClients"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(list_clients.clients.len(), 1);
+        assert_eq!(list_clients.clients[0].remote_addr, client_addr.to_string());
+        assert_eq!(list_clients.clients[0].author, client.uuid().to_string());
+
+        //The room policy is advertised to the connected client
+        grpc.ready().await.unwrap();
+
+        let _: crate::udp::grpc::CreateRoomResponse = grpc
+            .unary(
+                Request::new(CreateRoomRequest {
+                    room: 3,
+                    target_loudness: None,
+                    max_bitrate: Some(16000),
+                    mandatory_dtx: true,
+                    ..Default::default()
+                }),
+                PathAndQuery::from_static("/silence.Control/CreateRoom"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+
+        harness.settle().await;
+
+        assert_eq!(client.room_policy(3).unwrap().max_bitrate, Some(16000));
+
+        grpc.ready().await.unwrap();
+
+        let mut events = grpc
+            .server_streaming(
+                Request::new(StreamEventsRequest {}),
+                PathAndQuery::from_static("/silence.Control/StreamEvents"),
+                ProstCodec::<StreamEventsRequest, crate::udp::grpc::Event>::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+
+        grpc.ready().await.unwrap();
+
+        let _: crate::udp::grpc::KickClientResponse = grpc
+            .unary(
+                Request::new(KickClientRequest {
+                    remote_addr: client_addr.to_string(),
+                    message: Some(String::from("Spamming")),
+                    ban: false,
+                }),
+                PathAndQuery::from_static("/silence.Control/KickClient"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+
+        harness.settle().await;
+
+        let event = events.message().await.unwrap().unwrap();
+
+        assert_eq!(event.kind(), EventKind::PeerClosed);
+        assert_eq!(event.author, client.uuid().to_string());
+        assert_eq!(event.message.as_deref(), Some("Spamming"));
+
+        grpc.ready().await.unwrap();
+
+        let stats: crate::udp::grpc::GetStatsResponse = grpc
+            .unary(
+                Request::new(GetStatsRequest {}),
+                PathAndQuery::from_static("/silence.Control/GetStats"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(stats.peer_count, 0);
+        assert_eq!(stats.room_count, 1);
+
+        //Kicking an unknown client fails
+        grpc.ready().await.unwrap();
+
+        let status = grpc
+            .unary::<_, crate::udp::grpc::KickClientResponse, _>(
+                Request::new(KickClientRequest {
+                    remote_addr: client_addr.to_string(),
+                    message: None,
+                    ban: false,
+                }),
+                PathAndQuery::from_static("/silence.Control/KickClient"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        //Banning a client adds its author to the ban list, so it can't rejoin
+        let (banned_client, banned_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        grpc.ready().await.unwrap();
+
+        let _: crate::udp::grpc::KickClientResponse = grpc
+            .unary(
+                Request::new(KickClientRequest {
+                    remote_addr: banned_addr.to_string(),
+                    message: Some(String::from("Harassment")),
+                    ban: true,
+                }),
+                PathAndQuery::from_static("/silence.Control/KickClient"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap()
+            .into_inner();
+
+        harness.settle().await;
+
+        assert_eq!(
+            server
+                .bans()
+                .get(&banned_client.uuid())
+                .unwrap()
+                .reason
+                .as_deref(),
+            Some("Harassment")
+        );
+        harness.assert_evicted(&server, banned_addr);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn server_events_are_fed_to_event_sinks() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::{packet::control::RoomPolicy, udp::server::ServerEvent};
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+
+        let received_events = Arc::new(Mutex::new(Vec::new()));
+        let sink_events = received_events.clone();
+
+        server
+            .handle()
+            .add_event_sink(move |server_event: ServerEvent| {
+                let sink_events = sink_events.clone();
+
+                async move {
+                    sink_events.lock().unwrap().push(server_event);
+                }
+            });
+
+        let (client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.create_room(2, RoomPolicy::default()).await.unwrap();
+
+        harness.settle().await;
+
+        server.handle().recording_started(2, false).await.unwrap();
+        server.handle().recording_stopped(2).await.unwrap();
+        server.destroy_room(2).await.unwrap();
+
+        client.disconnect().await.unwrap();
+
+        harness.settle().await;
+
+        let received_events = received_events.lock().unwrap().clone();
+
+        assert_eq!(
+            received_events,
+            vec![
+                ServerEvent::PeerJoined {
+                    remote_addr: client_addr,
+                    author: client.uuid(),
+                },
+                ServerEvent::RoomCreated {
+                    room: 2,
+                    room_policy: RoomPolicy::default(),
+                },
+                ServerEvent::RecordingStarted { room: 2 },
+                ServerEvent::RecordingStopped { room: 2 },
+                ServerEvent::RoomDestroyed { room: 2 },
+                ServerEvent::PeerLeft {
+                    remote_addr: client_addr,
+                    author: client.uuid(),
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn audit_log_records_security_events() {
+        use std::{io::Write, sync::Arc};
+
+        use parking_lot::Mutex;
+
+        use crate::udp::audit::AuditLog;
+
+        #[derive(Clone, Default)]
+        struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for SharedBuffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().extend_from_slice(buf);
+
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+
+        let buffer = SharedBuffer::default();
+        let audit_log = AuditLog::new(buffer.clone());
+
+        server.handle().add_event_sink(audit_log.clone());
+
+        let author = Uuid::new_v4();
+
+        let (_client, client_addr) = harness.client(author, server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server
+            .ban(author, Some(String::from("Spam")))
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        //The banned author is rejected when it tries to join again
+        let (_rejected_client, rejected_addr) = harness.client(author, server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //The events are not recorded while the log is disabled
+        audit_log.set_enabled(false);
+
+        server.unban(author).unwrap();
+
+        harness.settle().await;
+
+        audit_log.set_enabled(true);
+
+        server.handle().recording_started(0, false).await.unwrap();
+
+        harness.settle().await;
+
+        let records: Vec<serde_json::Value> = String::from_utf8(buffer.0.lock().clone())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        let events: Vec<&str> = records
+            .iter()
+            .map(|record| record["event"].as_str().unwrap())
+            .collect();
+
+        assert_eq!(
+            events,
+            [
+                "peer_joined",
+                "author_banned",
+                "peer_closed",
+                "connection_rejected",
+                "recording_started"
+            ]
+        );
+
+        assert!(records.iter().all(|record| record["timestamp"].is_u64()));
+        assert_eq!(records[0]["remote_addr"], client_addr.to_string());
+        assert_eq!(records[1]["reason"], "Spam");
+        assert_eq!(records[3]["remote_addr"], rejected_addr.to_string());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn bans_and_rooms_survive_a_restart() {
+        use crate::{
+            packet::control::{CloseCode, RoomPolicy},
+            udp::{
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+                store::{Invite, ServerStore},
+            },
+        };
+
+        let harness = TestHarness::new();
+        let store = ServerStore::temporary().unwrap();
+
+        let config = ServerConfig {
+            store: Some(store.clone()),
+            ..Default::default()
+        };
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let server =
+            Server::new_from_transport_with_config::<Tokio, _>(server_transport, config.clone())
+                .await
+                .unwrap();
+
+        let (mut client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server
+            .create_room(
+                4,
+                RoomPolicy {
+                    mandatory_dtx: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        server
+            .ban(client.uuid(), Some(String::from("Spamming")))
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        server.cancellation_token().cancel();
+
+        //The restarted server restores the room and the ban
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let server = Server::new_from_transport_with_config::<Tokio, _>(server_transport, config)
+            .await
+            .unwrap();
+
+        assert!(server.room_policies().get(&4).unwrap().mandatory_dtx);
+        assert_eq!(
+            server.bans().get(&client.uuid()).unwrap().reason.as_deref(),
+            Some("Spamming")
+        );
+
+        //The banned author is closed whenever it tries to join
+        let (mut banned_client, _) = harness.client(client.uuid(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert!(server.peers().is_empty());
+
+        for client in [&mut client, &mut banned_client] {
+            let close_reason = std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+                .find_map(|client_event| match client_event {
+                    ClientEvent::Closed(close_reason) => Some(close_reason),
+                    _ => None,
+                })
+                .unwrap();
+
+            assert_eq!(close_reason.code(), CloseCode::Banned);
+        }
+
+        //Invites are exhausted after their last use
+        store
+            .save_invite("invite", &Invite::new(4).with_remaining_uses(1))
+            .unwrap();
+
+        assert_eq!(store.redeem_invite("invite").unwrap().unwrap().room, 4);
+        assert_eq!(store.redeem_invite("invite").unwrap(), None);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn clustered_messages_are_enveloped_with_their_node() {
+        use crate::{
+            packet::{decode_message, VoipHeader, VoipMessageType},
+            udp::{
+                cluster::{decode_envelope, encode_envelope, ClusterConfig},
+                runtime::Tokio,
+                server::{Server, ServerConfig, ServerError},
+            },
+        };
+
+        let node_id = Uuid::new_v4();
+        let voip_packet = VoipHeader::new(VoipMessageType::TextMessage(5), Uuid::new_v4())
+            .with_channel(7)
+            .create_message_buffer(b"Hello")
+            .unwrap();
+
+        let envelope = encode_envelope(node_id, &voip_packet);
+        let (envelope_node_id, buffer) = decode_envelope(&envelope).unwrap();
+
+        assert_eq!(envelope_node_id, node_id);
+        assert_eq!(decode_message(buffer).unwrap().0.channel(), 7);
+        assert_eq!(decode_envelope(&envelope[..15]), None);
+
+        //The server isn't created without reaching its cluster
+        let harness = TestHarness::new();
+
+        let result = Server::new_from_transport_with_config::<Tokio, _>(
+            harness.network().bind_any().unwrap(),
+            ServerConfig {
+                cluster: Some(ClusterConfig::new("redis://127.0.0.1:1")),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        assert!(matches!(result, Err(ServerError::Cluster(_))));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn reachable_relay_is_picked_and_degradation_is_detected() {
+        use crate::udp::{
+            relay::{RelayPicker, RelayProbeConfig},
+            runtime::Tokio,
+        };
+
+        let harness = TestHarness::new();
+
+        let (unreachable_server, unreachable_addr) = harness.server().await.unwrap();
+        let (reachable_server, reachable_addr) = harness.server().await.unwrap();
+
+        harness.network().set_blocked(unreachable_addr, true);
+
+        let picker = RelayPicker::new_from_transport_with_runtime::<Tokio, _>(
+            harness.network().bind_any().unwrap(),
+            vec![unreachable_addr, reachable_addr],
+            RelayProbeConfig::default(),
+        );
+
+        assert_eq!(picker.pick().await, Some(reachable_addr));
+        assert_eq!(picker.better_relay(reachable_addr), None);
+        assert_eq!(picker.better_relay(unreachable_addr), Some(reachable_addr));
+
+        //The probes don't join the session
+        assert!(reachable_server.peers().is_empty());
+
+        //The chosen relay degrades while the other one recovers
+        harness.network().set_blocked(unreachable_addr, false);
+        harness.network().set_blocked(reachable_addr, true);
+
+        for _ in 0..5 {
+            harness.advance(Duration::from_millis(1500)).await;
+        }
+
+        assert!(picker.stats()[0].loss < 0.5);
+        assert!(picker.stats()[1].loss >= 0.5);
+        assert_eq!(picker.better_relay(reachable_addr), Some(unreachable_addr));
+        assert!(unreachable_server.peers().is_empty());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn unvalidated_addresses_are_not_registered() {
+        use crate::{
+            packet::{
+                control::{ControlMessage, RetryToken},
+                decode_message, VoipHeader, VoipMessageType,
+            },
+            udp::{
+                runtime::Tokio,
+                server::{RetryConfig, Server, ServerConfig},
+                transport::Transport,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                retry: Some(RetryConfig::default()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        //The client echoes the retry token, and is registered
+        let (_client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert!(server.peers().contains_key(&client_addr));
+
+        //A peer which doesn't echo a valid token is only answered with a retry
+        let socket = harness.network().bind_any().unwrap();
+        let mut buf = vec![0; 1024];
+
+        for control_message in [
+            ControlMessage::Heartbeat,
+            ControlMessage::RetryHeartbeat(RetryToken::new(0, [0; 16])),
+        ] {
+            let voip_packet =
+                VoipHeader::new(VoipMessageType::Control(control_message), Uuid::new_v4())
+                    .create_message_buffer(&[])
+                    .unwrap();
+
+            socket
+                .send_datagram(voip_packet.inner(), server_addr)
+                .await
+                .unwrap();
+
+            harness.settle().await;
+
+            let (byte_count, _) = socket.recv_datagram(&mut buf).await.unwrap();
+
+            assert!(matches!(
+                decode_message(&buf[..byte_count])
+                    .unwrap()
+                    .0
+                    .voip_message_type(),
+                VoipMessageType::Control(ControlMessage::Retry(_))
+            ));
+        }
+
+        assert_eq!(server.peers().len(), 1);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn unverified_addresses_are_not_amplified() {
+        use crate::{
+            packet::{control::ControlMessage, decode_message, VoipHeader, VoipMessageType},
+            udp::transport::Transport,
+        };
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+        let (mut client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        //An unverified address is only answered within its budget
+        let socket = harness.network().bind_any().unwrap();
+        let ping = VoipHeader::new(
+            VoipMessageType::Control(ControlMessage::Ping(1)),
+            Uuid::new_v4(),
+        )
+        .create_message_buffer(&[])
+        .unwrap();
+
+        socket
+            .send_datagram(ping.inner(), server_addr)
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        //Even if the application relays to it, large messages are dropped until it joins
+        server.get_reply_to_list_mut().insert(socket.local_addr());
+        server.get_reply_to_list_mut().insert(client_addr);
+
+        let text = "A".repeat(512);
+
+        server
+            .reply_to_clients(
+                VoipHeader::new(
+                    VoipMessageType::TextMessage(text.len() as u64),
+                    Uuid::new_v4(),
+                )
+                .create_message_buffer(text.as_bytes())
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let mut buf = vec![0; 2048];
+
+        let (byte_count, _) = socket.recv_datagram(&mut buf).await.unwrap();
+
+        assert!(matches!(
+            decode_message(&buf[..byte_count])
+                .unwrap()
+                .0
+                .voip_message_type(),
+            VoipMessageType::Control(ControlMessage::Pong(1))
+        ));
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), socket.recv_datagram(&mut buf))
+                .await
+                .is_err()
+        );
+
+        //The joined client isn't limited
+        let received_text = std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+            .find_map(|client_event| match client_event {
+                ClientEvent::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(received_text, text);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn filtered_sources_are_discarded() {
+        use crate::{
+            packet::{control::ControlMessage, VoipHeader, VoipMessageType},
+            udp::{
+                filter::{IpNetwork, SourceFilter},
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+                transport::Transport,
+            },
+        };
+
+        let network: IpNetwork = "10.0.0.0/8".parse().unwrap();
+
+        assert!(network.contains("10.1.2.3".parse().unwrap()));
+        assert!(network.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!network.contains("11.0.0.1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+
+        let harness = TestHarness::new();
+        let blocked_socket = harness.network().bind_any().unwrap();
+        let blocked_addr = blocked_socket.local_addr();
+        let server_socket = harness.network().bind_any().unwrap();
+        let server_addr = server_socket.local_addr();
+
+        let server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_socket,
+            ServerConfig {
+                source_filter: SourceFilter::new()
+                    .with_callback(move |source| source != blocked_addr),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let (_client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        let heartbeat = VoipHeader::new(
+            VoipMessageType::Control(ControlMessage::Heartbeat),
+            Uuid::new_v4(),
+        )
+        .create_message_buffer(&[])
+        .unwrap();
+
+        blocked_socket
+            .send_datagram(heartbeat.inner(), server_addr)
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        assert!(server.peers().contains_key(&client_addr));
+        assert!(!server.peers().contains_key(&blocked_addr));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn dropped_messages_are_counted_by_their_reason() {
+        use crate::{
+            packet::{control::ControlMessage, VoipHeader, VoipMessageType, AUDIO_LEVEL_SILENCE},
+            udp::{
+                drops::{DropLogConfig, DropReason},
+                filter::SourceFilter,
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+                transport::Transport,
+            },
+        };
+
+        let harness = TestHarness::new();
+        let blocked_socket = harness.network().bind_any().unwrap();
+        let blocked_addr = blocked_socket.local_addr();
+        let socket = harness.network().bind_any().unwrap();
+        let server_socket = harness.network().bind_any().unwrap();
+        let server_addr = server_socket.local_addr();
+
+        let server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_socket,
+            ServerConfig {
+                silence_threshold: Some(100),
+                source_filter: SourceFilter::new()
+                    .with_callback(move |source| source != blocked_addr),
+                drop_log: DropLogConfig {
+                    policy_drops: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let author = Uuid::new_v4();
+        let (mut client, _) = harness.client(author, server_addr).await.unwrap();
+
+        client
+            .message_sender()
+            .send(
+                VoipHeader::new(VoipMessageType::VoiceMessage(1), author)
+                    .with_audio_level(AUDIO_LEVEL_SILENCE)
+                    .create_message_buffer(&[1])
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let heartbeat = VoipHeader::new(
+            VoipMessageType::Control(ControlMessage::Heartbeat),
+            Uuid::new_v4(),
+        )
+        .create_message_buffer(&[])
+        .unwrap();
+
+        blocked_socket
+            .send_datagram(heartbeat.inner(), server_addr)
+            .await
+            .unwrap();
+
+        let banned_author = Uuid::new_v4();
+
+        server.ban(banned_author, None).await.unwrap();
+
+        for message in [
+            VoipHeader::new(VoipMessageType::TextMessage(2), banned_author)
+                .create_message_buffer(b"Hi")
+                .unwrap()
+                .inner()
+                .to_vec(),
+            vec![0; 16],
+            vec![0; 16],
+        ] {
+            socket.send_datagram(&message, server_addr).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        let stats = server.stats();
+
+        assert_eq!(stats.drops.get(DropReason::Silent), 1);
+        assert_eq!(stats.drops.get(DropReason::Filtered), 1);
+        assert_eq!(stats.drops.get(DropReason::Banned), 1);
+        assert_eq!(stats.drops.get(DropReason::Malformed), 2);
+        assert_eq!(stats.drops.total(), 5);
+        assert_eq!(stats.malformed_packets, 2);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn out_of_bounds_headers_are_rejected_and_counted() {
+        use crate::{
+            packet::{
+                codec::CodecError,
+                control::{CloseCode, CloseReason, ControlMessage},
+                decode_message, PacketError, VoipHeader, VoipMessageType, MAX_HEADER_SIZE,
+            },
+            udp::transport::Transport,
+        };
+
+        //Headers declaring huge bodies are rejected before anything is allocated for them
+        let oversized_body =
+            VoipHeader::new(VoipMessageType::TextMessage(u64::MAX), Uuid::new_v4())
+                .create_message_buffer(&[])
+                .unwrap();
+
+        assert!(matches!(
+            decode_message(oversized_body.inner()),
+            Err(PacketError::BodyTooLarge(u64::MAX))
+        ));
+
+        //Headers longer than the limit can't be created
+        let oversized_header = VoipHeader::new(
+            VoipMessageType::Control(ControlMessage::Close(CloseReason::new(
+                CloseCode::Custom(0),
+                Some("A".repeat(MAX_HEADER_SIZE)),
+            ))),
+            Uuid::new_v4(),
+        )
+        .create_message_buffer(&[]);
+
+        assert!(matches!(
+            oversized_header,
+            Err(CodecError::HeaderTooLarge(_))
+        ));
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+        let socket = harness.network().bind_any().unwrap();
+
+        for _ in 0..10 {
+            socket
+                .send_datagram(oversized_body.inner(), server_addr)
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        assert_eq!(server.stats().malformed_packets, 10);
+        assert!(server.peers().is_empty());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn oversized_messages_are_fragmented_or_rejected() {
+        use crate::{packet::VoipMessageType, udp::event::ClientError, MTU_MAX_PACKET_SIZE};
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        //Voice messages can't be fragmented
+        let oversized = vec![0; MTU_MAX_PACKET_SIZE];
+
+        match sender
+            .send_bytes(
+                VoipMessageType::VoiceMessage(oversized.len() as u64),
+                &mut oversized.into_iter(),
+            )
+            .await
+        {
+            Err(ClientError::Oversized { size, limit }) => {
+                assert_eq!(size, MTU_MAX_PACKET_SIZE);
+                assert!(limit < MTU_MAX_PACKET_SIZE);
+            }
+            result => panic!("Unexpected result: {result:?}"),
+        }
+
+        //Text messages are fragmented, and reassembled by the receiving clients
+        let text = "Silence ".repeat(MTU_MAX_PACKET_SIZE / 2);
+
+        sender
+            .send_bytes(
+                VoipMessageType::TextMessage(text.len() as u64),
+                &mut text.bytes(),
+            )
+            .await
+            .unwrap();
+
+        //Let the client pace out every fragment
+        for _ in 0..10 {
+            harness.advance(Duration::from_millis(2)).await;
+        }
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        let mut fragment_count = 0;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            if let VoipMessageType::TextMessage(_) = voip_header.voip_message_type() {
+                fragment_count += 1;
+
+                server
+                    .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                    .await
+                    .unwrap();
+            }
+        }
+
+        harness.settle().await;
+
+        assert!(fragment_count > 1);
+
+        let received_texts: Vec<String> =
+            std::iter::from_fn(|| receiver.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::Text { text, .. } => Some(text),
+                    _ => None,
+                })
+                .collect();
+
+        assert_eq!(received_texts, vec![text]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn receivers_select_simulcast_layers() {
+        use crate::packet::{control::LayerSelection, frame::VideoFrame, MediaCodec};
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        receiver
+            .select_layer(sender.uuid(), LayerSelection::Layer(0))
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        //Sends the frames of the layers, and relays them to the receiver
+        let send_frames = |frames: Vec<(u32, u8, bool)>| {
+            let sender = &sender;
+            let harness = &harness;
+
+            async move {
+                for (sequence, layer, is_keyframe) in frames {
+                    let mut video_frame =
+                        VideoFrame::new(sender.uuid(), MediaCodec::Raw, is_keyframe, vec![0; 16]);
+
+                    video_frame.sequence = Some(sequence);
+                    video_frame.layer = Some(layer);
+
+                    sender.send_video_frame(video_frame).await.unwrap();
+
+                    harness.advance(Duration::from_millis(10)).await;
+                }
+            }
+        };
+
+        send_frames(vec![
+            (0, 0, true),
+            (1, 1, true),
+            (2, 0, false),
+            (3, 1, false),
+        ])
+        .await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        //The automatic selection switches to the highest layer on its next keyframe
+        receiver
+            .select_layer(sender.uuid(), LayerSelection::Auto)
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        send_frames(vec![
+            (4, 0, false),
+            (5, 1, false),
+            (6, 1, true),
+            (7, 0, false),
+        ])
+        .await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let received_frames: Vec<(Option<u32>, Option<u8>)> =
+            std::iter::from_fn(|| receiver.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::VideoFrame(video_frame) => {
+                        Some((video_frame.sequence, video_frame.layer))
+                    }
+                    _ => None,
+                })
+                .collect();
+
+        assert_eq!(
+            received_frames,
+            [
+                (Some(0), Some(0)),
+                (Some(2), Some(0)),
+                (Some(4), Some(0)),
+                (Some(6), Some(1)),
+            ]
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn bound_addresses_are_exposed() {
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+        let (client, client_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        assert_eq!(server.local_addr(), server_addr);
+        assert_eq!(client.local_addr(), client_addr);
+        assert_eq!(client.peer_addr(), server_addr);
+
+        //The port chosen by the operating system is visible
+        let server = crate::udp::server::Server::new(0).await.unwrap();
+
+        assert_ne!(server.local_addr().port(), 0);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn client_binds_to_the_configured_address() {
+        use crate::udp::client::{BindConfig, Client};
+
+        let server = crate::udp::server::Server::new(0).await.unwrap();
+
+        //The first port of the range is taken, so the next free one is used
+        let taken_socket = tokio::net::UdpSocket::bind("[::1]:0").await.unwrap();
+        let taken_port = taken_socket.local_addr().unwrap().port();
+        let port_range = taken_port..=taken_port.saturating_add(50);
+
+        let client = Client::new_with_bind_config(
+            Uuid::new_v4(),
+            ("::1", server.local_addr().port()),
+            &BindConfig {
+                local_addr: "[::1]:0".parse().unwrap(),
+                port_range: Some(port_range.clone()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(client.local_addr().ip().is_loopback());
+        assert!(port_range.contains(&client.local_addr().port()));
+        assert_ne!(client.local_addr().port(), taken_port);
+
+        //A range without a free port can't be bound to
+        assert!(BindConfig {
+            local_addr: "[::1]:0".parse().unwrap(),
+            port_range: Some(taken_port..=taken_port),
+            ..Default::default()
+        }
+        .bind()
+        .await
+        .is_err());
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn client_fails_over_to_the_next_endpoint() {
+        use crate::{
+            packet::control::{CloseCode, ControlMessage},
+            udp::{
+                client::{send_control_message, Client, ClientConfig, FailoverConfig},
+                runtime::Tokio,
+                server::ServerEvent,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let (_primary, primary_addr) = harness.server().await.unwrap();
+        let (secondary, secondary_addr) = harness.server().await.unwrap();
+        let mut secondary_events = secondary.subscribe_events();
+
+        let socket = harness.network().bind_any().unwrap();
+        let client_addr = socket.local_addr();
+
+        let mut client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            socket,
+            primary_addr,
+            ClientConfig {
+                failover: Some(FailoverConfig {
+                    endpoints: vec![primary_addr.to_string(), secondary_addr.to_string()],
+                    timeout: Duration::from_secs(15),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+
+        //The primary endpoint stops answering, the client fails over once it has been silent for the timeout
+        harness.network().set_blocked(primary_addr, true);
+
+        harness.advance_heartbeats(4).await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::RemoteAddrChanged(remote_addr) if remote_addr == secondary_addr
+        ));
+        assert!(client.event_receiver().try_recv().is_err());
+        assert_eq!(client.peer_addr(), secondary_addr);
+
+        assert_eq!(
+            secondary_events.try_recv().unwrap(),
+            ServerEvent::PeerJoined {
+                remote_addr: client_addr,
+                author: client.uuid(),
+            }
+        );
+
+        //The author alone can be spoofed, so it doesn't move the session to another address
+        let spoofed_socket = harness.network().bind_any().unwrap();
+
+        send_control_message(
+            &spoofed_socket,
+            ControlMessage::Heartbeat,
+            client.uuid(),
+            secondary_addr,
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            secondary_events.try_recv().unwrap(),
+            ServerEvent::ConnectionRejected { remote_addr, close_reason, .. }
+                if remote_addr == spoofed_socket.local_addr()
+                    && close_reason.code() == CloseCode::DuplicateSession
+        ));
+        assert!(secondary_events.try_recv().is_err());
+        assert!(secondary.peers().contains_key(&client_addr));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn sessions_move_with_their_connection_id() {
+        use crate::{
+            packet::{
+                control::{ConnectionId, ControlMessage},
+                decode_message, VoipMessageType,
+            },
+            udp::{
+                client::send_control_message,
+                server::ServerEvent,
+                transport::{memory::MemorySocket, Transport},
+            },
+        };
+
+        async fn received(socket: &MemorySocket) -> Vec<ControlMessage> {
+            let mut buf = vec![0; 2048];
+            let mut received = vec![];
+
+            while let Ok(Ok((byte_count, _))) =
+                tokio::time::timeout(Duration::ZERO, socket.recv_datagram(&mut buf)).await
+            {
+                let (voip_header, _) = decode_message(&buf[..byte_count]).unwrap();
+
+                if let VoipMessageType::Control(control_message) = voip_header.voip_message_type() {
+                    received.push(control_message.clone());
+                }
+            }
+
+            received
+        }
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+        let mut server_events = server.subscribe_events();
+
+        //The joining session is issued its connection id
+        let uuid = Uuid::new_v4();
+        let socket = harness.network().bind_any().unwrap();
+
+        send_control_message(&socket, ControlMessage::Heartbeat, uuid, server_addr)
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let connection_id = received(&socket)
+            .await
+            .into_iter()
+            .find_map(|control_message| match control_message {
+                ControlMessage::ConnectionId(connection_id) => Some(connection_id),
+                _ => None,
+            })
+            .unwrap();
+
+        assert!(matches!(
+            server_events.try_recv().unwrap(),
+            ServerEvent::PeerJoined { .. }
+        ));
+
+        //Another id doesn't move the session
+        let migrated_socket = harness.network().bind_any().unwrap();
+        let migrated_addr = migrated_socket.local_addr();
+
+        send_control_message(
+            &migrated_socket,
+            ControlMessage::ConnectionHeartbeat(ConnectionId::new([0; 16])),
+            uuid,
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            server_events.try_recv().unwrap(),
+            ServerEvent::ConnectionRejected { remote_addr, .. } if remote_addr == migrated_addr
+        ));
+
+        received(&migrated_socket).await;
+
+        //The id moves the session once the new address has echoed the retry
+        send_control_message(
+            &migrated_socket,
+            ControlMessage::ConnectionHeartbeat(connection_id),
+            uuid,
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        let retry_token = received(&migrated_socket)
+            .await
+            .into_iter()
+            .find_map(|control_message| match control_message {
+                ControlMessage::Retry(retry_token) => Some(retry_token),
+                _ => None,
+            })
+            .unwrap();
+
+        assert!(server_events.try_recv().is_err());
+        assert!(server.peers().contains_key(&socket.local_addr()));
+        assert!(!server.peers().contains_key(&migrated_addr));
+
+        send_control_message(
+            &migrated_socket,
+            ControlMessage::RetryHeartbeat(retry_token),
+            uuid,
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            server_events.try_recv().unwrap(),
+            ServerEvent::PeerMigrated {
+                previous_addr: socket.local_addr(),
+                remote_addr: migrated_addr,
+                author: uuid,
+            }
+        );
+        assert!(server_events.try_recv().is_err());
+        assert_eq!(server.peers().len(), 1);
+        assert!(server.peers().contains_key(&migrated_addr));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn restarted_clients_take_their_stale_session_over() {
+        use crate::{
+            packet::control::{CloseCode, CloseReason},
+            udp::server::{ServerEvent, STALE_SESSION_TIMEOUT},
+        };
+
+        let harness = TestHarness::new();
+
+        let (server, server_addr) = harness.server().await.unwrap();
+
+        let uuid = Uuid::new_v4();
+        let (_crashed_client, crashed_addr) = harness.client(uuid, server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        let mut server_events = server.subscribe_events();
+
+        //The client crashes without saying goodbye, and is restarted on a new port
+        harness.network().set_blocked(crashed_addr, true);
+
+        let (mut early_client, early_addr) = harness.client(uuid, server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //The restarted client has lost the connection id, so it can't take over a session which may still be alive
+        assert!(matches!(
+            early_client.event_receiver().try_recv().unwrap(),
+            ClientEvent::Closed(close_reason) if close_reason.code() == CloseCode::DuplicateSession
+        ));
+        assert!(server.peers().contains_key(&crashed_addr));
+        assert!(!server.peers().contains_key(&early_addr));
+
+        //Once the session has gone silent, the restarted client takes it over
+        harness
+            .advance(STALE_SESSION_TIMEOUT + Duration::from_millis(1))
+            .await;
+
+        let (mut restarted_client, restarted_addr) =
+            harness.client(uuid, server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            restarted_client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+        assert_eq!(server.peers().len(), 1);
+        assert!(server.peers().contains_key(&restarted_addr));
+        harness.assert_evicted(&server, crashed_addr);
+
+        let server_events: Vec<ServerEvent> =
+            std::iter::from_fn(|| server_events.try_recv().ok()).collect();
+
+        assert!(server_events.contains(&ServerEvent::PeerClosed {
+            remote_addr: crashed_addr,
+            author: Some(uuid),
+            close_reason: CloseReason::new(CloseCode::Replaced, None),
+        }));
+        assert!(server_events.contains(&ServerEvent::PeerJoined {
+            remote_addr: restarted_addr,
+            author: uuid,
+        }));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn client_follows_the_changed_address_of_its_endpoint() {
+        use crate::udp::{
+            client::{Client, ClientConfig, FailoverConfig},
+            runtime::Tokio,
+            server::ServerEvent,
+        };
+
+        let harness = TestHarness::new();
+
+        let (_previous, previous_addr) = harness.server().await.unwrap();
+        let (current, current_addr) = harness.server().await.unwrap();
+        let mut current_events = current.subscribe_events();
+
+        let socket = harness.network().bind_any().unwrap();
+        let client_addr = socket.local_addr();
+
+        //The endpoint already resolves to the current address, while the client is still connected to the previous one
+        let mut client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            socket,
+            previous_addr,
+            ClientConfig {
+                failover: Some(FailoverConfig {
+                    endpoints: vec![current_addr.to_string()],
+                    re_resolve_interval: Some(Duration::from_secs(10)),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::ConnectionStateChanged(ConnectionState::Connected)
+        ));
+
+        harness.advance_heartbeats(2).await;
+
+        assert!(matches!(
+            client.event_receiver().try_recv().unwrap(),
+            ClientEvent::RemoteAddrChanged(remote_addr) if remote_addr == current_addr
+        ));
+        assert_eq!(client.peer_addr(), current_addr);
+
+        //The heartbeat sent right after the resolution reaches the new address
+        assert_eq!(
+            current_events.try_recv().unwrap(),
+            ServerEvent::PeerJoined {
+                remote_addr: client_addr,
+                author: client.uuid(),
+            }
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn custom_codec_is_used_while_every_peer_can_decode_it() {
+        use silence_core::opus::opus::{Bitrate, Channels};
+
+        use crate::{
+            packet::{MediaCodec, VoipMessageType},
+            udp::{
+                audio_codec::{AudioCodec, AudioCodecs, AudioDecoder, AudioEncoder},
+                client::{Client, ClientConfig},
+                event::MediaError,
+                runtime::Tokio,
+                voice::VoiceConfig,
+            },
+        };
+
+        //Sends the samples as raw little endian floats
+        struct Pcm;
+
+        impl AudioEncoder for Pcm {
+            fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>, MediaError> {
+                Ok(samples
+                    .iter()
+                    .flat_map(|sample| sample.to_le_bytes())
+                    .collect())
+            }
+        }
+
+        impl AudioDecoder for Pcm {
+            fn decode(&mut self, payload: &[u8]) -> Result<Vec<f32>, MediaError> {
+                Ok(payload
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect())
+            }
+        }
+
+        impl AudioCodec for Pcm {
+            fn encoder(
+                &self,
+                _voice_config: &VoiceConfig,
+                _bitrate: Bitrate,
+            ) -> Result<Box<dyn AudioEncoder>, MediaError> {
+                Ok(Box::new(Pcm))
+            }
+
+            fn decoder(
+                &self,
+                _sample_rate: u32,
+                _channels: Channels,
+            ) -> Result<Box<dyn AudioDecoder>, MediaError> {
+                Ok(Box::new(Pcm))
+            }
+        }
+
+        const PCM: MediaCodec = MediaCodec::Custom(1);
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+
+        let mut clients = vec![];
+
+        for _ in 0..2 {
+            let client = Client::new_from_transport_with_config::<Tokio, _>(
+                Uuid::new_v4(),
+                harness.network().bind_any().unwrap(),
+                server_addr,
+                ClientConfig {
+                    codecs: AudioCodecs::new().with_codec(PCM, Pcm),
+                    voice: VoiceConfig {
+                        codec: PCM,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+            clients.push(client);
+
+            harness.settle().await;
+        }
+
+        let mut sender = clients.remove(0);
+
+        assert_eq!(sender.session_codecs(), vec![MediaCodec::Opus, PCM]);
+
+        let mut negotiated = vec![];
+
+        while let Ok(event) = sender.event_receiver().try_recv() {
+            if let ClientEvent::CodecsNegotiated(codecs) = event {
+                negotiated.push(codecs);
+            }
+        }
+
+        assert_eq!(negotiated.last(), Some(&vec![MediaCodec::Opus, PCM]));
+
+        let samples = vec![0.25; sender.voice_config().samples_per_frame()];
+
+        //Every peer can decode the custom codec
+        while server.message_receiver().try_recv().is_ok() {}
+
+        sender.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        let (voip_header, _, _) = server.message_receiver().try_recv().unwrap();
+
+        assert!(matches!(
+            voip_header.voip_message_type(),
+            VoipMessageType::VoiceMessage(_)
+        ));
+        assert_eq!(voip_header.codec(), PCM);
+
+        //A peer without the custom codec joins, so the voice falls back to Opus
+        let (_plain_client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(sender.session_codecs(), vec![MediaCodec::Opus]);
+
+        while server.message_receiver().try_recv().is_ok() {}
+
+        sender.send_samples(&samples).await.unwrap();
+
+        harness.settle().await;
+
+        let (voip_header, _, _) = server.message_receiver().try_recv().unwrap();
+
+        assert_eq!(voip_header.codec(), MediaCodec::Opus);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn diagnostics_measure_the_connection_to_the_server() {
+        use crate::udp::diagnostics::{DiagnosticError, DiagnosticsConfig};
+
+        let harness = TestHarness::new();
+
+        let (_server, server_addr) = harness.server().await.unwrap();
+        let (client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        let config = DiagnosticsConfig {
+            echo_count: 3,
+            loss_test_duration: Duration::from_millis(200),
+            ..Default::default()
+        };
+
+        let report = client.run_diagnostics(&config).await;
+
+        assert!(report.stun.is_none());
+
+        let echo = report.echo.unwrap();
+
+        assert_eq!((echo.sent, echo.received), (3, 3));
+        assert!(echo.min_round_trip_time <= echo.max_round_trip_time);
+
+        let loss = report.loss.unwrap();
+
+        assert_eq!((loss.sent, loss.received), (10, 10));
+        assert_eq!(loss.loss(), 0.);
+
+        //The pings of an unreachable server are lost
+        harness.network().set_blocked(server_addr, true);
+
+        let report = client.run_diagnostics(&config).await;
+
+        assert!(matches!(report.echo, Err(DiagnosticError::Unanswered(3))));
+        assert_eq!(report.loss.unwrap().loss(), 1.);
+        assert!(!report.is_healthy(0.5));
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn bandwidth_probe_ramps_up_until_the_bursts_get_lost() {
+        use crate::udp::probe::ProbeConfig;
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //Drain the heartbeat of the client
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let config = ProbeConfig {
+            start_bitrate: 100_000,
+            max_bitrate: 400_000,
+            burst_duration: Duration::from_millis(80),
+            ..Default::default()
+        };
+
+        let probe_report = client.probe_bandwidth(&config).await.unwrap();
+
+        let bitrates: Vec<u32> = probe_report
+            .bursts
+            .iter()
+            .map(|burst| burst.bitrate)
+            .collect();
+
+        assert_eq!(bitrates, vec![100_000, 200_000, 400_000]);
+        assert!(probe_report
+            .bursts
+            .iter()
+            .all(|burst| burst.is_sustained(&config)));
+        assert_eq!(probe_report.available_bitrate, Some(400_000));
+
+        //The padding is never forwarded to the application
+        assert!(server.message_receiver().try_recv().is_err());
+
+        //Nothing gets through a blocked path, so not even the first burst is sustained
+        harness.network().set_blocked(server_addr, true);
+
+        let probe_report = client.probe_bandwidth(&config).await.unwrap();
+
+        assert_eq!(probe_report.bursts.len(), 1);
+        assert_eq!(probe_report.bursts[0].received, 0);
+        assert_eq!(probe_report.available_bitrate, None);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn preconnect_warms_the_session_up_before_the_call() {
+        use crate::udp::{
+            diagnostics::DiagnosticError, preconnect::PreconnectConfig, probe::ProbeConfig,
+        };
+
+        let harness = TestHarness::new();
+
+        let (_server, server_addr) = harness.server().await.unwrap();
+        let (client, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        let config = PreconnectConfig {
+            probe: Some(ProbeConfig {
+                start_bitrate: 100_000,
+                max_bitrate: 200_000,
+                burst_duration: Duration::from_millis(80),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let report = client.preconnect(&config).await;
+
+        assert!(report.is_ready());
+        assert!(report.stun.is_none());
+        assert_eq!(report.handshake.as_ref().unwrap().received, 3);
+        assert_eq!(report.available_bitrate(), Some(200_000));
+
+        //The bandwidth isn't probed towards an unreachable server
+        harness.network().set_blocked(server_addr, true);
+
+        let report = client.preconnect(&config).await;
+
+        assert!(!report.is_ready());
+        assert!(matches!(
+            report.handshake,
+            Err(DiagnosticError::Unanswered(3))
+        ));
+        assert!(report.probe.is_none());
+        assert_eq!(report.available_bitrate(), None);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn sessions_are_resumed_with_their_ticket() {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        use crate::{
+            packet::{
+                control::{ControlMessage, PresenceState, ResumptionTicket},
+                decode_message, VoipHeader, VoipMessageType,
+            },
+            udp::{
+                client::{Client, ClientConfig},
+                runtime::Tokio,
+                server::{ResumptionConfig, RetryConfig, Server, ServerConfig, ServerEvent},
+                transport::Transport,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                retry: Some(RetryConfig::default()),
+                resumption: Some(ResumptionConfig::default()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        //The server issues a ticket once the client has joined
+        let uuid = Uuid::new_v4();
+        let (client, client_addr) = harness.client(uuid, server_addr).await.unwrap();
+
+        client.set_presence(PresenceState::Away).await.unwrap();
+
+        harness.settle().await;
+
+        let resumption_ticket = client.resumption_ticket().unwrap();
+
+        //The restarted client presents the ticket from a new address, and takes its session over once it has echoed the retry
+        let mut server_events = server.subscribe_events();
+
+        let resumed_client = Client::new_from_transport_with_config::<Tokio, _>(
+            uuid,
+            harness.network().bind_any().unwrap(),
+            server_addr,
+            ClientConfig {
+                resumption_ticket: Some(resumption_ticket),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let resumed_addr = resumed_client.local_addr();
+
+        harness.settle().await;
+
+        assert!(matches!(
+            server_events.try_recv().unwrap(),
+            ServerEvent::PeerMigrated { previous_addr, remote_addr, author }
+                if previous_addr == client_addr && remote_addr == resumed_addr && author == uuid
+        ));
+        assert!(server_events.try_recv().is_err());
+
+        assert_eq!(server.peers().len(), 1);
+        assert_eq!(
+            server.peers().get(&resumed_addr).unwrap().presence(),
+            PresenceState::Away
+        );
+
+        //The resumed session is issued a new ticket
+        assert!(resumed_client.resumption_ticket().is_some());
+
+        //A valid ticket presented from an address which doesn't echo the retry doesn't move the session
+        let socket = harness.network().bind_any().unwrap();
+        let mut buf = vec![0; 1024];
+
+        let voip_packet = VoipHeader::new(
+            VoipMessageType::Control(ControlMessage::Resume(
+                resumed_client.resumption_ticket().unwrap(),
+            )),
+            uuid,
+        )
+        .create_message_buffer(&[])
+        .unwrap();
+
+        socket
+            .send_datagram(voip_packet.inner(), server_addr)
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let (byte_count, _) = socket.recv_datagram(&mut buf).await.unwrap();
+
+        assert!(matches!(
+            decode_message(&buf[..byte_count])
+                .unwrap()
+                .0
+                .voip_message_type(),
+            VoipMessageType::Control(ControlMessage::Retry(_))
+        ));
+        assert!(server_events.try_recv().is_err());
+        assert!(server.peers().contains_key(&resumed_addr));
+        assert!(!server.peers().contains_key(&socket.local_addr()));
+
+        //A forged ticket is rejected, and only answered with a retry
+        let socket = harness.network().bind_any().unwrap();
+
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let voip_packet = VoipHeader::new(
+            VoipMessageType::Control(ControlMessage::Resume(ResumptionTicket::new(
+                issued_at, [0; 16],
+            ))),
+            uuid,
+        )
+        .create_message_buffer(&[])
+        .unwrap();
+
+        socket
+            .send_datagram(voip_packet.inner(), server_addr)
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        let (byte_count, _) = socket.recv_datagram(&mut buf).await.unwrap();
+
+        assert!(matches!(
+            decode_message(&buf[..byte_count])
+                .unwrap()
+                .0
+                .voip_message_type(),
+            VoipMessageType::Control(ControlMessage::Retry(_))
+        ));
+        assert!(matches!(
+            server_events.try_recv().unwrap(),
+            ServerEvent::ConnectionRejected { author, .. } if author == uuid
+        ));
+        assert!(server.peers().contains_key(&resumed_addr));
+        assert_eq!(server.peers().len(), 1);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn captured_traffic_is_replayed_with_its_original_timing() {
+        use crate::{
+            packet::{control::ControlMessage, decode_header, VoipHeader, VoipMessageType},
+            udp::{
+                client::send_control_message,
+                replay::{Recording, ReplayConfig, ReplayError, ReplayServer},
+                transport::Transport,
+            },
+        };
+
+        //A little endian pcap capture without a link layer, the timestamps are in microseconds
+        let mut pcap = vec![];
+
+        pcap.extend(0xa1b2_c3d4_u32.to_le_bytes());
+        pcap.extend(2_u16.to_le_bytes());
+        pcap.extend(4_u16.to_le_bytes());
+        pcap.extend([0; 8]);
+        pcap.extend(65535_u32.to_le_bytes());
+        pcap.extend(101_u32.to_le_bytes());
+
+        let mut capture = |micros: u32, source_port: u16, payload: &[u8]| {
+            let mut ip_packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 17, 0, 0];
+
+            ip_packet.extend([127, 0, 0, 1, 127, 0, 0, 1]);
+            ip_packet.extend(source_port.to_be_bytes());
+            ip_packet.extend(50000_u16.to_be_bytes());
+            ip_packet.extend((payload.len() as u16 + 8).to_be_bytes());
+            ip_packet.extend([0; 2]);
+            ip_packet.extend(payload);
+
+            pcap.extend((1_700_000_000 + micros / 1_000_000).to_le_bytes());
+            pcap.extend((micros % 1_000_000).to_le_bytes());
+            pcap.extend((ip_packet.len() as u32).to_le_bytes());
+            pcap.extend((ip_packet.len() as u32).to_le_bytes());
+            pcap.extend(ip_packet);
+        };
+
+        let author = Uuid::new_v4();
+        let text_message = |text: &str| {
+            VoipHeader::new(VoipMessageType::TextMessage(1), author)
+                .create_message_buffer(text.as_bytes())
+                .unwrap()
+        };
+        let heartbeat = VoipHeader::new(
+            VoipMessageType::Control(ControlMessage::Heartbeat),
+            Uuid::nil(),
+        )
+        .create_message_buffer(&[])
+        .unwrap();
+
+        //Only the messages of the server are recorded, the heartbeat echoes and the other datagrams are left out
+        capture(500_000, 9000, text_message("first").inner());
+        capture(600_000, 9000, heartbeat.inner());
+        capture(700_000, 9000, b"not a message");
+        capture(800_000, 53, text_message("unrelated").inner());
+        capture(1_500_000, 9000, text_message("second").inner());
+
+        let recording = Recording::from_pcap(&pcap[..], Some(9000)).unwrap();
+        let limited_recording = recording.clone();
+
+        assert_eq!(recording.datagrams().len(), 2);
+        assert_eq!(recording.duration(), Duration::from_secs(1));
+
+        assert!(matches!(
+            Recording::from_pcap(&[0x0a, 0x0d, 0x0d, 0x0a].repeat(6)[..], None),
+            Err(ReplayError::Pcapng)
+        ));
+
+        //The connecting client is played the recording from its start
+        let harness = TestHarness::new();
+
+        let replay_server = ReplayServer::new_from_transport(
+            harness.network().bind_any().unwrap(),
+            recording,
+            ReplayConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        let (mut client, _) = harness
+            .client(Uuid::new_v4(), replay_server.local_addr())
+            .await
+            .unwrap();
+
+        let mut received_texts = || {
+            std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::Text { text, .. } => Some(text),
+                    _ => None,
+                })
+                .collect::<Vec<String>>()
+        };
+
+        harness.settle().await;
+
+        assert_eq!(received_texts(), vec![String::from("first")]);
+
+        harness.advance(Duration::from_millis(900)).await;
+
+        assert!(received_texts().is_empty());
+
+        harness.advance(Duration::from_millis(100)).await;
+
+        assert_eq!(received_texts(), vec![String::from("second")]);
+
+        //The recording isn't looped
+        harness.advance(Duration::from_secs(5)).await;
+
+        assert!(received_texts().is_empty());
+
+        //A spoofed heartbeat is only answered with a retry, so the recording isn't streamed at its victim
+        let victim = harness.network().bind_any().unwrap();
+
+        send_control_message(
+            &victim,
+            ControlMessage::Heartbeat,
+            Uuid::new_v4(),
+            replay_server.local_addr(),
+        )
+        .await
+        .unwrap();
+
+        harness.advance(Duration::from_secs(2)).await;
+
+        let mut buf = vec![0; 2048];
+        let mut received = vec![];
+
+        while let Ok(Ok((byte_count, _))) =
+            tokio::time::timeout(Duration::ZERO, victim.recv_datagram(&mut buf)).await
+        {
+            received.push(
+                decode_header(&buf[..byte_count])
+                    .unwrap()
+                    .voip_message_type()
+                    .clone(),
+            );
+        }
+
+        assert!(matches!(
+            received.as_slice(),
+            [VoipMessageType::Control(ControlMessage::Retry(_))]
+        ));
+
+        //The recording is only played to as many clients at the same time as the limit allows
+        let limited_server = ReplayServer::new_from_transport(
+            harness.network().bind_any().unwrap(),
+            limited_recording,
+            ReplayConfig {
+                max_playbacks: 1,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut played_texts = vec![];
+
+        for _ in 0..2 {
+            let (mut client, _) = harness
+                .client(Uuid::new_v4(), limited_server.local_addr())
+                .await
+                .unwrap();
+
+            harness.settle().await;
+
+            played_texts.push(
+                std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+                    .filter_map(|client_event| match client_event {
+                        ClientEvent::Text { text, .. } => Some(text),
+                        _ => None,
+                    })
+                    .collect::<Vec<String>>(),
+            );
+        }
+
+        assert_eq!(played_texts, [vec![String::from("first")], vec![]]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn cover_traffic_is_padded_to_a_constant_size() {
+        use crate::{
+            packet::{control::ControlMessage, decode_message, HeaderFlags, VoipMessageType},
+            udp::{
+                client::{Client, ClientConfig, TrafficShapingConfig},
+                runtime::Tokio,
+                transport::{
+                    layer::{Interceptor, InterceptorLayer, TransportExt},
+                    padding::PaddingInterceptor,
+                    Transport,
+                },
+            },
+        };
+
+        let padding = PaddingInterceptor::new(256);
+
+        //Malformed padding is dropped
+        let source = "[::1]:1".parse().unwrap();
+
+        assert_eq!(padding.on_recv(vec![1], source), None);
+        assert_eq!(padding.on_recv(vec![1, 0, 9], source), None);
+
+        let harness = TestHarness::new();
+
+        let server_socket = harness.network().bind_any().unwrap();
+        let client = Client::new_from_transport_with_config::<Tokio, _>(
+            Uuid::new_v4(),
+            harness
+                .network()
+                .bind_any()
+                .unwrap()
+                .layer(InterceptorLayer::new(padding)),
+            server_socket.local_addr(),
+            ClientConfig {
+                traffic_shaping: Some(TrafficShapingConfig::default()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut buf = vec![0; 2048];
+        let mut voip_headers = vec![];
+
+        //The heartbeat, then the cover messages in place of the voice
+        for _ in 0..4 {
+            let (byte_count, source) = server_socket.recv_datagram(&mut buf).await.unwrap();
+
+            assert_eq!(byte_count, padding.block_size());
+
+            let datagram = padding.on_recv(buf[..byte_count].to_vec(), source).unwrap();
+
+            voip_headers.push(decode_message(&datagram).unwrap().0);
+        }
+
+        assert_eq!(
+            voip_headers[0].voip_message_type(),
+            &VoipMessageType::Control(ControlMessage::Heartbeat)
+        );
+
+        for voip_header in &voip_headers[1..] {
+            assert!(matches!(
+                voip_header.voip_message_type(),
+                VoipMessageType::VoiceMessage(_)
+            ));
+            assert!(voip_header.flags().contains(HeaderFlags::PADDING));
+        }
+
+        //The voice takes the place of the cover message
+        client
+            .send_bytes(VoipMessageType::VoiceMessage(3), &mut [1, 2, 3].into_iter())
+            .await
+            .unwrap();
+
+        let (byte_count, source) = server_socket.recv_datagram(&mut buf).await.unwrap();
+        let datagram = padding.on_recv(buf[..byte_count].to_vec(), source).unwrap();
+        let (voip_header, voip_body) = decode_message(&datagram).unwrap();
+
+        assert!(!voip_header.flags().contains(HeaderFlags::PADDING));
+        assert_eq!(voip_body, [1, 2, 3]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn late_joiners_are_sent_the_roster_and_the_retained_media_states() {
+        use crate::{
+            packet::control::{ControlMessage, MediaState},
+            udp::client::send_control_message,
+        };
+
+        let harness = TestHarness::new();
+
+        let (_server, server_addr) = harness.server().await.unwrap();
+        let (presenter, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (listener, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        let media_state = MediaState {
+            audio_muted: true,
+            video_muted: false,
+            screen_sharing: true,
+        };
+
+        presenter.set_media_state(3, media_state).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(listener.media_state(presenter.uuid(), 3), media_state);
+        assert_eq!(
+            listener.media_state(presenter.uuid(), 0),
+            MediaState::default()
+        );
+
+        //The peers joining later are sent the roster and the retained states
+        let (mut latecomer, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(latecomer.media_state(presenter.uuid(), 3), media_state);
+
+        let mut roster: Vec<Uuid> =
+            std::iter::from_fn(|| latecomer.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::ParticipantJoined(author) => Some(author),
+                    _ => None,
+                })
+                .collect();
+        let mut participants = vec![presenter.uuid(), listener.uuid()];
+
+        roster.sort();
+        participants.sort();
+
+        assert_eq!(roster, participants);
+
+        //Clearing the state removes it from the retained ones
+        presenter
+            .set_media_state(3, MediaState::default())
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            latecomer.media_state(presenter.uuid(), 3),
+            MediaState::default()
+        );
+
+        let (second_latecomer, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            second_latecomer.media_state(presenter.uuid(), 3),
+            MediaState::default()
+        );
+
+        //An unregistered address can't change the media state of a registered author
+        let spoofed_socket = harness.network().bind_any().unwrap();
+
+        send_control_message(
+            &spoofed_socket,
+            ControlMessage::MediaState(media_state),
+            presenter.uuid(),
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(
+            listener.media_state(presenter.uuid(), 0),
+            MediaState::default()
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn recorded_media_is_only_forwarded_after_consent() {
+        use crate::{
+            packet::{control::RecordingState, frame::VoiceFrame, MediaCodec},
+            udp::server::ServerEvent,
+        };
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let mut server_events = server.handle().subscribe_events();
+        let (mut consenting, consenting_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (refusing, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.handle().recording_started(0, true).await.unwrap();
+
+        harness.settle().await;
+
+        let recording_state = RecordingState::Recording {
+            consent_required: true,
+        };
+
+        let recording_states: Vec<RecordingState> =
+            std::iter::from_fn(|| consenting.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::RecordingChanged { channel: 0, state } => Some(state),
+                    _ => None,
+                })
+                .collect();
+
+        assert_eq!(recording_states, [recording_state]);
+        assert_eq!(refusing.recording_state(0), recording_state);
+
+        consenting.consent_to_recording(0, true).await.unwrap();
+
+        harness.settle().await;
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        //Only the media of the consenting client is forwarded
+        for client in [&consenting, &refusing] {
+            client
+                .send_voice_frame(VoiceFrame::new(client.uuid(), MediaCodec::Raw, vec![1]))
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let authors: Vec<Uuid> = std::iter::from_fn(|| server.message_receiver().try_recv().ok())
+            .map(|(voip_header, ..)| voip_header.author())
+            .collect();
+
+        assert_eq!(authors, [consenting.uuid()]);
+
+        //The peers joining later are told about the recording
+        let (latecomer, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(latecomer.recording_state(0), recording_state);
+
+        //The consents are cleared when the recording stops
+        server.handle().recording_stopped(0).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(refusing.recording_state(0), RecordingState::Stopped);
+        assert!(!server
+            .handle()
+            .peers()
+            .get(&consenting_addr)
+            .unwrap()
+            .has_consented_to_recording(0));
+
+        let recording_events: Vec<ServerEvent> =
+            std::iter::from_fn(|| server_events.try_recv().ok())
+                .filter(|server_event| {
+                    matches!(
+                        server_event,
+                        ServerEvent::RecordingStarted { .. }
+                            | ServerEvent::RecordingStopped { .. }
+                            | ServerEvent::RecordingConsentChanged { .. }
+                    )
+                })
+                .collect();
+
+        assert_eq!(
+            recording_events,
+            [
+                ServerEvent::RecordingStarted { room: 0 },
+                ServerEvent::RecordingConsentChanged {
+                    remote_addr: consenting_addr,
+                    author: consenting.uuid(),
+                    room: 0,
+                    consent: true,
+                },
+                ServerEvent::RecordingStopped { room: 0 },
+            ]
+        );
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn decoded_voice_is_tapped_per_speaker() {
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (first_speaker, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (second_speaker, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let mut voice_tap = receiver.tap_voice();
+        let mut speaker_tap = receiver.tap_voice().with_author(second_speaker.uuid());
+
+        let samples_per_frame = first_speaker.voice_config().samples_per_frame();
+        let samples: Vec<f32> = (0..samples_per_frame)
+            .map(|index| (index as f32 / 20.).sin() * 0.5)
+            .collect();
+
+        for speaker in [&first_speaker, &second_speaker] {
+            speaker.send_samples(&samples).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let tapped_authors: Vec<Uuid> = std::iter::from_fn(|| voice_tap.try_recv())
+            .map(|tapped_frame| tapped_frame.frame.author)
+            .collect();
+
+        assert_eq!(
+            tapped_authors,
+            [first_speaker.uuid(), second_speaker.uuid()]
+        );
+
+        let tapped_frame = speaker_tap.try_recv().unwrap();
+
+        assert_eq!(tapped_frame.frame.author, second_speaker.uuid());
+        assert_eq!(tapped_frame.frame.samples.len(), samples_per_frame);
+        assert!(speaker_tap.try_recv().is_none());
+
+        //The tapped frames are still played out
+        let played_frames = receiver.recv_frames();
+
+        assert_eq!(played_frames.len(), 2);
+        assert_eq!(played_frames[1].samples, tapped_frame.frame.samples);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn captions_are_relayed_with_their_span() {
+        use crate::packet::caption::Caption;
+
+        let harness = TestHarness::new();
+
+        let (mut server, server_addr) = harness.server().await.unwrap();
+        let (transcriber, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+        while receiver.event_receiver().try_recv().is_ok() {}
+
+        let speaker = Uuid::new_v4();
+        let mut interim_caption = Caption::new(
+            speaker,
+            String::from("Hello"),
+            Duration::from_millis(1200),
+            Duration::from_millis(1500),
+            false,
+        );
+        interim_caption.channel = 4;
+
+        let final_caption = Caption {
+            text: String::from("Hello, world!"),
+            end: Duration::from_millis(2100),
+            is_final: true,
+            ..interim_caption.clone()
+        };
+
+        for caption in [&interim_caption, &final_caption] {
+            transcriber.send_caption(caption).await.unwrap();
+        }
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            assert_eq!(voip_header.author(), transcriber.uuid());
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let captions: Vec<Caption> =
+            std::iter::from_fn(|| receiver.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::Caption(caption) => Some(caption),
+                    _ => None,
+                })
+                .collect();
+
+        assert_eq!(captions, [interim_caption, final_caption]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn duplicate_logins_follow_the_policy_of_the_server() {
+        use crate::{
+            packet::control::{CloseCode, DeviceNotice},
+            udp::{
+                client::Client,
+                event::ClientEvent,
+                runtime::Tokio,
+                server::{DuplicateLoginPolicy, Server, ServerConfig},
+                transport::Transport,
+            },
+        };
+
+        fn close_code(client: &mut Client) -> Option<CloseCode> {
+            std::iter::from_fn(|| client.event_receiver().try_recv().ok()).find_map(
+                |client_event| match client_event {
+                    ClientEvent::Closed(close_reason) => Some(close_reason.code()),
+                    _ => None,
+                },
+            )
+        }
+
+        fn device_notices(client: &mut Client) -> Vec<DeviceNotice> {
+            std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::DeviceChanged(device_notice) => Some(device_notice),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        for duplicate_login in [
+            DuplicateLoginPolicy::Reject,
+            DuplicateLoginPolicy::Replace,
+            DuplicateLoginPolicy::MultiDevice,
+        ] {
+            let harness = TestHarness::new();
+
+            let server_transport = harness.network().bind_any().unwrap();
+            let server_addr = server_transport.local_addr();
+            let server = Server::new_from_transport_with_config::<Tokio, _>(
+                server_transport,
+                ServerConfig {
+                    duplicate_login,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+            let author = Uuid::new_v4();
+            let (mut first_client, first_addr) = harness.client(author, server_addr).await.unwrap();
+
+            harness.settle().await;
+
+            let (mut second_client, second_addr) =
+                harness.client(author, server_addr).await.unwrap();
+
+            harness.settle().await;
+
+            match duplicate_login {
+                DuplicateLoginPolicy::Reject => {
+                    assert_eq!(
+                        close_code(&mut second_client),
+                        Some(CloseCode::DuplicateSession)
+                    );
+                    assert_eq!(close_code(&mut first_client), None);
+                    assert!(server.peers().contains_key(&first_addr));
+                    assert!(!server.peers().contains_key(&second_addr));
+                }
+                DuplicateLoginPolicy::Replace => {
+                    assert_eq!(close_code(&mut first_client), Some(CloseCode::Replaced));
+                    assert_eq!(close_code(&mut second_client), None);
+                    assert!(!server.peers().contains_key(&first_addr));
+                    assert!(server.peers().contains_key(&second_addr));
+                }
+                DuplicateLoginPolicy::MultiDevice => {
+                    assert_eq!(
+                        device_notices(&mut first_client),
+                        [DeviceNotice::Assigned(0), DeviceNotice::Joined(1)]
+                    );
+                    assert_eq!(
+                        device_notices(&mut second_client),
+                        [DeviceNotice::Assigned(1)]
+                    );
+                    assert_eq!(server.peers().get(&second_addr).unwrap().device(), 1);
+
+                    //The other device is notified when the first one leaves
+                    first_client.disconnect().await.unwrap();
+
+                    harness.settle().await;
+
+                    assert_eq!(device_notices(&mut second_client), [DeviceNotice::Left(0)]);
+                    assert_eq!(server.peers().len(), 1);
+                }
+                DuplicateLoginPolicy::Migrate => unreachable!(),
+            }
+        }
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn devices_of_a_user_are_mixed_as_separate_streams() {
+        use crate::{
+            packet::VoipMessageType,
+            udp::{
+                runtime::Tokio,
+                server::{DuplicateLoginPolicy, Server, ServerConfig},
+                transport::Transport,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                duplicate_login: DuplicateLoginPolicy::MultiDevice,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let author = Uuid::new_v4();
+        let (first_device, first_addr) = harness.client(author, server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        let (second_device, second_addr) = harness.client(author, server_addr).await.unwrap();
+        let (receiver, receiver_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        assert_eq!(first_device.device(), Some(0));
+        assert_eq!(second_device.device(), Some(1));
+
+        //The moderation can target a single device of the author
+        assert_eq!(server.device_addr(author, 0), Some(first_addr));
+        assert_eq!(server.device_addr(author, 1), Some(second_addr));
+        assert_eq!(server.device_addr(author, 2), None);
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        //Both devices send the same frames, with the same sequence numbers
+        let samples: Vec<f32> = (0..first_device.voice_config().samples_per_frame() * 4)
+            .map(|index| (index as f32 / 20.).sin() * 0.3)
+            .collect();
+
+        let now = tokio::time::Instant::now();
+
+        first_device.push_samples(&samples, now).await.unwrap();
+        second_device.push_samples(&samples, now).await.unwrap();
+
+        harness.settle().await;
+
+        let mut devices = vec![];
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            if let VoipMessageType::VoiceMessage(_) = voip_header.voip_message_type() {
+                devices.push(voip_header.device());
+            }
+
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        devices.sort();
+
+        assert_eq!(devices, [[Some(0); 4], [Some(1); 4]].concat());
+
+        harness.settle().await;
+
+        //The frames of the second device aren't dropped as duplicates of the first one's, the devices are summed
+        let mut frames = vec![0.; receiver.voice_config().samples_per_frame()];
+        let mut peak: f32 = 0.;
+
+        for _ in 0..4 {
+            receiver.pull_mixed_audio(&mut frames);
+
+            peak = frames
+                .iter()
+                .fold(peak, |peak, sample| peak.max(sample.abs()));
+        }
+
+        assert!(peak > 0.45);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn media_violating_the_room_policy_is_rejected() {
+        use crate::{
+            packet::{
+                control::{PolicyViolation, RoomPolicy},
+                MediaCodec, VoipHeader, VoipMessageType,
+            },
+            udp::{
+                drops::DropReason,
+                event::ClientEvent,
+                runtime::Tokio,
+                server::{Server, ServerConfig},
+                transport::Transport,
+            },
+        };
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport_with_config::<Tokio, _>(
+            server_transport,
+            ServerConfig {
+                room_policies: [(
+                    0,
+                    RoomPolicy {
+                        max_bitrate: Some(8_000),
+                        allowed_codecs: Some(vec![MediaCodec::Opus]),
+                        video_disabled: true,
+                        ..Default::default()
+                    },
+                )]
+                .into(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let author = Uuid::new_v4();
+        let (mut client, _) = harness.client(author, server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        //The voice fitting the bitrate of the room is forwarded, the second message exceeds it
+        for voip_header in [
+            VoipHeader::new(VoipMessageType::VideoMessage(1_000), author)
+                .with_codec(MediaCodec::Avif),
+            VoipHeader::new(VoipMessageType::VoiceMessage(1_000), author)
+                .with_codec(MediaCodec::Raw),
+            VoipHeader::new(VoipMessageType::VoiceMessage(1_000), author)
+                .with_codec(MediaCodec::Opus),
+            VoipHeader::new(VoipMessageType::VoiceMessage(1_000), author)
+                .with_codec(MediaCodec::Opus),
+        ] {
+            client
+                .message_sender()
+                .send(voip_header.create_message_buffer(&[0; 1_000]).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let mut forwarded_messages = vec![];
+
+        while let Ok((voip_header, _, _)) = server.message_receiver().try_recv() {
+            forwarded_messages.push(voip_header.codec());
+        }
+
+        assert_eq!(forwarded_messages, [MediaCodec::Opus]);
+        assert_eq!(server.stats().drops.get(DropReason::PolicyViolation), 3);
+
+        //The sender is only notified once in every window
+        let violations: Vec<(u32, PolicyViolation)> =
+            std::iter::from_fn(|| client.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::PolicyViolated { channel, violation } => {
+                        Some((channel, violation))
+                    }
+                    _ => None,
+                })
+                .collect();
+
+        assert_eq!(violations, [(0, PolicyViolation::Video)]);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn ended_streams_are_released_right_away() {
+        use crate::udp::{event::ClientEvent, server::Server, transport::Transport};
+
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport(server_transport).await.unwrap();
+
+        let author = Uuid::new_v4();
+        let (sender, _) = harness.client(author, server_addr).await.unwrap();
+        let (mut receiver, receiver_addr) =
+            harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples: Vec<f32> = (0..sender.voice_config().samples_per_frame() * 4)
+            .map(|index| (index as f32 / 20.).sin() * 0.3)
+            .collect();
+
+        sender
+            .push_samples(&samples, tokio::time::Instant::now())
+            .await
+            .unwrap();
+        sender.end_stream(0).await.unwrap();
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let ended_streams: Vec<(Uuid, u32, u8)> =
+            std::iter::from_fn(|| receiver.event_receiver().try_recv().ok())
+                .filter_map(|client_event| match client_event {
+                    ClientEvent::StreamEnded {
+                        author,
+                        device,
+                        stream,
+                    } => Some((author, device, stream)),
+                    _ => None,
+                })
+                .collect();
+
+        assert_eq!(ended_streams, [(author, 0, 0)]);
+
+        //The tail of the stream is played out without waiting for the target delay, then the stream is released without an underrun
+        let mut frames = vec![0.; receiver.voice_config().samples_per_frame()];
+        let mut peak: f32 = 0.;
+
+        for _ in 0..6 {
+            receiver.pull_mixed_audio(&mut frames);
+
+            peak = frames
+                .iter()
+                .fold(peak, |peak, sample| peak.max(sample.abs()));
+        }
+
+        assert!(peak > 0.);
+        assert!(frames.iter().all(|sample| *sample == 0.));
+        let playout_probe = harness.probe_playout(&receiver, author);
+
+        assert_eq!(playout_probe.playout_delay, None);
+        assert_eq!(playout_probe.jitter_stats.unwrap().underruns, 0);
+    }
+
+    #[cfg(feature = "all")]
+    #[tokio::test]
+    async fn pipeline_profile_times_the_stages_and_detects_xruns() {
+        use std::time::Duration;
+
+        use crate::udp::{
+            profiling::{PipelineProfiler, PipelineStage, TimingHistogram},
+            server::Server,
+            transport::Transport,
+        };
+
+        //The percentiles are reported by the upper bounds of their buckets
+        let mut histogram = TimingHistogram::default();
+
+        for micros in [40, 40, 40, 800, 70_000] {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.percentile(0.5), Duration::from_micros(50));
+        assert_eq!(histogram.percentile(0.8), Duration::from_millis(1));
+        assert_eq!(histogram.percentile(1.), Duration::from_millis(70));
+
+        //Only the callbacks arriving later than the audio of the previous one lasts are xruns
+        let profiler = PipelineProfiler::new();
+        let frame_duration = Duration::from_millis(10);
+        let start = tokio::time::Instant::now();
+
+        for offset_ms in [0, 10, 21, 46, 56, 2_000] {
+            profiler.observe_playout(start + Duration::from_millis(offset_ms), frame_duration);
+        }
+
+        let profile = profiler.snapshot();
+
+        assert_eq!(profile.playout_underruns, 1);
+        assert_eq!(profile.capture_overruns, 0);
+        assert_eq!(profile.longest_stall, Duration::from_millis(15));
+
+        //Every stage of the sent and the received voice is timed
+        let harness = TestHarness::new();
+
+        let server_transport = harness.network().bind_any().unwrap();
+        let server_addr = server_transport.local_addr();
+        let mut server = Server::new_from_transport(server_transport).await.unwrap();
+
+        let (sender, _) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+        let (receiver, receiver_addr) = harness.client(Uuid::new_v4(), server_addr).await.unwrap();
+
+        harness.settle().await;
+
+        server.get_reply_to_list_mut().insert(receiver_addr);
+
+        //Drain the heartbeats of the clients
+        while server.message_receiver().try_recv().is_ok() {}
+
+        let samples = vec![0.1; sender.voice_config().samples_per_frame() * 4];
+
+        sender
+            .push_samples(&samples, tokio::time::Instant::now())
+            .await
+            .unwrap();
+
+        harness.settle().await;
+
+        while let Ok((voip_header, voip_body, _)) = server.message_receiver().try_recv() {
+            server
+                .reply_to_clients(voip_header.create_message_buffer(&voip_body).unwrap())
+                .await
+                .unwrap();
+        }
+
+        harness.settle().await;
+
+        let mut frames = vec![0.; receiver.voice_config().samples_per_frame()];
+
+        receiver.pull_mixed_audio(&mut frames);
+
+        let sender_profile = sender.pipeline_profile();
+        let receiver_profile = receiver.pipeline_profile();
+
+        assert_eq!(sender_profile.stage(PipelineStage::Capture).count(), 1);
+        assert_eq!(sender_profile.stage(PipelineStage::Encode).count(), 4);
+        assert_eq!(sender_profile.stage(PipelineStage::Send).count(), 4);
+        assert_eq!(receiver_profile.stage(PipelineStage::Receive).count(), 4);
+        assert_eq!(receiver_profile.stage(PipelineStage::Decode).count(), 4);
+        assert_eq!(receiver_profile.stage(PipelineStage::Playout).count(), 1);
+        assert_eq!(receiver_profile.xruns(), 0);
+
+        sender.reset_pipeline_profile();
+
+        assert_eq!(sender.pipeline_profile(), Default::default());
+    }
+
+    #[cfg(feature = "all")]
+    #[test]
+    fn conformance_vectors_verify_only_against_their_own_codec() {
+        use crate::packet::{
+            codec::{Bincode, MessagePack, Postcard},
+            conformance::{
+                emit_vectors, parse_vectors, test_vectors, verify_vectors, ConformanceError,
+            },
+        };
+
+        let vectors = emit_vectors::<MessagePack>().unwrap();
+        let mut encoded_vectors = parse_vectors(&vectors).unwrap();
+
+        assert_eq!(encoded_vectors.len(), test_vectors().len());
+        assert!(verify_vectors::<MessagePack>(&encoded_vectors)
+            .iter()
+            .all(|vector_report| vector_report.result.is_ok()));
+
+        //The vectors of another codec never verify
+        let bincode_vectors = parse_vectors(&emit_vectors::<Bincode>().unwrap()).unwrap();
+
+        assert!(verify_vectors::<Postcard>(&bincode_vectors)
+            .iter()
+            .all(|vector_report| vector_report.result.is_err()));
+
+        //A vector encoded differently is reported, even if it decodes
+        let last_byte = encoded_vectors[0].message.last_mut().unwrap();
+        *last_byte ^= 0xff;
+
+        let vector_reports = verify_vectors::<MessagePack>(&encoded_vectors);
+
+        assert!(matches!(
+            vector_reports[0].result,
+            Err(ConformanceError::MessageMismatch)
+        ));
+        assert!(vector_reports[1..]
+            .iter()
+            .all(|vector_report| vector_report.result.is_ok()));
+
+        assert!(matches!(
+            parse_vectors("# Comment\n\nvoice 00zz"),
+            Err(ConformanceError::InvalidHex(3))
+        ));
+        assert!(matches!(
+            parse_vectors("voice"),
+            Err(ConformanceError::InvalidLine(1))
+        ));
+    }
+
+    #[cfg(feature = "proptest")]
+    mod wire_format {
+        use proptest::{collection::vec, prelude::*};
+
+        use crate::packet::{
+            codec::HeaderCodec, decode_message, decode_message_with, encode_message,
+            encode_message_with, strategy, VoipHeader, VoipPacket,
+        };
+
+        fn round_trip_with<C: HeaderCodec>(
+            voip_header: &VoipHeader,
+            data: &[u8],
+        ) -> Result<(), TestCaseError> {
+            let voip_packet = encode_message_with::<C>(voip_header, data).unwrap();
+
+            let (decoded_header, decoded_data) =
+                decode_message_with::<C>(voip_packet.inner()).unwrap();
+
+            prop_assert_eq!(&decoded_header, voip_header);
+            prop_assert_eq!(decoded_data, data);
+
+            Ok(())
+        }
+
+        proptest! {
+            #[test]
+            fn message_round_trips((voip_header, data) in strategy::message()) {
+                let voip_packet = encode_message(&voip_header, &data).unwrap();
+
+                let (decoded_header, decoded_data) = decode_message(voip_packet.inner()).unwrap();
+
+                prop_assert_eq!(decoded_header, voip_header);
+                prop_assert_eq!(decoded_data, data);
+            }
+
+            #[test]
+            fn message_round_trips_with_every_codec((voip_header, data) in strategy::message()) {
+                #[cfg(feature = "msgpack")]
+                round_trip_with::<crate::packet::codec::MessagePack>(&voip_header, &data)?;
+                #[cfg(feature = "bincode")]
+                round_trip_with::<crate::packet::codec::Bincode>(&voip_header, &data)?;
+                #[cfg(feature = "postcard")]
+                round_trip_with::<crate::packet::codec::Postcard>(&voip_header, &data)?;
+            }
+
+            #[test]
+            fn message_parses_like_it_decodes((voip_header, data) in strategy::message()) {
+                let voip_packet = encode_message(&voip_header, &data).unwrap();
+
+                let (parsed_header, parsed_data) = VoipPacket::parse(voip_packet).unwrap();
+
+                prop_assert_eq!(parsed_header, voip_header);
+                prop_assert_eq!(&parsed_data[..], &data[..]);
+            }
+
+            #[test]
+            fn truncated_message_is_rejected((voip_header, data) in strategy::message(), cut in 1_usize..64) {
+                let voip_packet = encode_message(&voip_header, &data).unwrap();
+                let buffer = voip_packet.inner();
+
+                prop_assert!(decode_message(&buffer[..buffer.len().saturating_sub(cut)]).is_err());
+            }
+
+            #[test]
+            fn random_bytes_dont_panic(bytes in vec(any::<u8>(), 0..2048)) {
+                let _ = decode_message(&bytes);
+            }
+        }
     }
 }
@@ -0,0 +1,210 @@
+//!
+//! Provides the socket activation of the [`Server`](super::server::Server), which serves on a socket bound by the service manager instead of binding its own.
+//!
+//! The service manager binds the socket before it starts the server, so the server can serve a privileged port without running privileged itself.
+//! The socket is kept open by the service manager between the restarts of the server, so the datagrams sent during a restart are queued on it instead of being lost.
+//! Under systemd the sockets are passed with the `LISTEN_FDS` protocol (see [`sd_listen_fds`](https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html)), under launchd they are checked in with `launch_activate_socket`.
+//!
+
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+#[cfg(not(target_os = "macos"))]
+use parking_lot::Mutex;
+use tokio::net::UdpSocket;
+
+/// The first file descriptor systemd passes the sockets from, the rest follow it in order.
+pub const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// The name of the socket in the `Sockets` dictionary of the launchd job, which is checked in if no name is given.
+pub const LAUNCHD_SOCKET_NAME: &str = "Listeners";
+
+/// The descriptors passed by systemd which were already taken, so they aren't owned twice by the sockets of separate calls.
+#[cfg(not(target_os = "macos"))]
+static TAKEN_FDS: Mutex<Vec<RawFd>> = Mutex::new(Vec::new());
+
+///
+/// Returns the datagram sockets passed to the process by the service manager, in the order they were passed.
+///
+/// # Behavior
+/// Under systemd, only the sockets named `name` in `LISTEN_FDNAMES` are returned if a name is given (see the `FileDescriptorName=` option of the socket unit).
+/// The sockets of the other names are left to the later calls, every socket is only returned by the first call taking it.
+/// The `LISTEN_*` environment variables are only removed when every socket is taken (no name is given), so the sockets aren't passed on to the child processes.
+/// Under launchd, the sockets of the `name` entry of the `Sockets` dictionary are checked in, [`LAUNCHD_SOCKET_NAME`] if no name is given.
+/// Returns an empty list if the process wasn't socket activated.
+///
+/// # Error
+/// Returns an error if the environment of the activation is malformed, or a passed socket isn't a datagram socket.
+///
+pub fn activated_sockets(name: Option<&str>) -> io::Result<Vec<UdpSocket>> {
+    #[cfg(target_os = "macos")]
+    let fds = launchd::activated_fds(name.unwrap_or(LAUNCHD_SOCKET_NAME))?;
+
+    #[cfg(not(target_os = "macos"))]
+    let fds = {
+        let fds = systemd_fds(
+            std::env::var("LISTEN_PID").ok().as_deref(),
+            std::env::var("LISTEN_FDS").ok().as_deref(),
+            std::env::var("LISTEN_FDNAMES").ok().as_deref(),
+            std::process::id(),
+            name,
+        )?;
+
+        if name.is_none() {
+            for variable in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+                std::env::remove_var(variable);
+            }
+        }
+
+        //Skip the descriptors an earlier call has already taken the ownership of
+        let mut taken_fds = TAKEN_FDS.lock();
+        let fds: Vec<RawFd> = fds
+            .into_iter()
+            .filter(|fd| !taken_fds.contains(fd))
+            .collect();
+
+        taken_fds.extend(&fds);
+
+        fds
+    };
+
+    fds.into_iter().map(udp_socket).collect()
+}
+
+///
+/// Returns the file descriptors passed by systemd, from the values of the `LISTEN_PID`, `LISTEN_FDS` and `LISTEN_FDNAMES` environment variables.
+///
+/// # Behavior
+/// The descriptors are only taken if they were passed to the process with the `pid`, they were passed to another process otherwise (for example to the parent of the server).
+/// The descriptors are filtered by their names if a `name` is given.
+///
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn systemd_fds(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    listen_fdnames: Option<&str>,
+    pid: u32,
+    name: Option<&str>,
+) -> io::Result<Vec<RawFd>> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(vec![]);
+    };
+
+    let listen_pid: u32 = listen_pid.parse().map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, "LISTEN_PID isn't a process id.")
+    })?;
+
+    if listen_pid != pid {
+        return Ok(vec![]);
+    }
+
+    let listen_fds: RawFd = listen_fds
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "LISTEN_FDS isn't a count."))?;
+
+    let names: Vec<&str> = listen_fdnames
+        .map(|listen_fdnames| listen_fdnames.split(':').collect())
+        .unwrap_or_default();
+
+    Ok((0..listen_fds)
+        .filter(|index| {
+            name.is_none_or(|name| {
+                names
+                    .get(*index as usize)
+                    .is_some_and(|fd_name| *fd_name == name)
+            })
+        })
+        .map(|index| SD_LISTEN_FDS_START + index)
+        .collect())
+}
+
+/// Takes the ownership of the passed `fd`, and converts it into a non-blocking [`UdpSocket`].
+fn udp_socket(fd: RawFd) -> io::Result<UdpSocket> {
+    // SAFETY: The service manager has passed the descriptor to this process, and nothing else owns it.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut socket_type: libc::c_int = 0;
+    let mut length = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+    // SAFETY: The value and its length are valid for the call.
+    let result = unsafe {
+        libc::getsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            &mut socket_type as *mut libc::c_int as *mut libc::c_void,
+            &mut length,
+        )
+    };
+
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if socket_type != libc::SOCK_DGRAM {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The passed socket isn't a datagram socket.",
+        ));
+    }
+
+    //The descriptors were inherited without close-on-exec, so they would leak into the child processes
+    // SAFETY: Setting the flags of an owned descriptor has no further preconditions.
+    if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let socket = std::net::UdpSocket::from(fd);
+
+    socket.set_nonblocking(true)?;
+
+    UdpSocket::from_std(socket)
+}
+
+/// The check-in of the sockets of a launchd job.
+#[cfg(target_os = "macos")]
+mod launchd {
+    use std::{ffi::CString, io, os::fd::RawFd};
+
+    extern "C" {
+        fn launch_activate_socket(
+            name: *const libc::c_char,
+            fds: *mut *mut libc::c_int,
+            count: *mut libc::size_t,
+        ) -> libc::c_int;
+    }
+
+    /// Checks in the sockets of the `name` entry of the `Sockets` dictionary of the job.
+    /// Returns an empty list if the process isn't managed by launchd, or the job has no such sockets.
+    pub(super) fn activated_fds(name: &str) -> io::Result<Vec<RawFd>> {
+        let name = CString::new(name)?;
+        let mut fds: *mut libc::c_int = std::ptr::null_mut();
+        let mut count: libc::size_t = 0;
+
+        // SAFETY: The name is a valid C string, and the out pointers are valid for the call.
+        let result = unsafe { launch_activate_socket(name.as_ptr(), &mut fds, &mut count) };
+
+        match result {
+            0 => (),
+            libc::ESRCH | libc::ENOENT => return Ok(vec![]),
+            errno => return Err(io::Error::from_raw_os_error(errno)),
+        }
+
+        if fds.is_null() {
+            return Ok(vec![]);
+        }
+
+        // SAFETY: launchd has allocated `count` descriptors at `fds`, which are freed after they are copied.
+        let activated_fds = unsafe {
+            let activated_fds = std::slice::from_raw_parts(fds, count).to_vec();
+
+            libc::free(fds as *mut libc::c_void);
+
+            activated_fds
+        };
+
+        Ok(activated_fds)
+    }
+}
@@ -0,0 +1,208 @@
+//!
+//! Provides the anti-amplification limits of the [`Server`](super::server::Server).
+//!
+//! A UDP server answering unverified source addresses can be abused to reflect (and amplify) traffic at a victim, by spoofing the victim's address.
+//! Until an address has joined the session (sent a heartbeat the server has accepted), the server only sends it a small multiple of the bytes it has received from it, like QUIC does before validating an address.
+//!
+
+use std::{future::Future, io, net::SocketAddr, time::Duration};
+
+use dashmap::DashMap;
+use tokio::time::Instant;
+use tracing::{event, Level};
+
+use super::{
+    server::PeerRegistry,
+    transport::{ecn::Ecn, path::PathError, qos::Dscp, Transport},
+};
+
+///
+/// Amplification limit type definition.
+///
+/// Describes how many bytes the server may send to the addresses which haven't joined the session.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmplificationLimit {
+    /// The multiple of the received bytes the server may send to an unverified address.
+    /// A factor of `0` stops every message to the unverified addresses.
+    pub factor: usize,
+
+    /// The time the bytes received from an unverified address are counted for, after the first message of the address.
+    pub lifetime: Duration,
+
+    /// The highest amount of unverified addresses tracked at once.
+    /// Nothing is sent to the addresses which don't fit, so a flood of spoofed addresses can't grow the memory of the server.
+    pub max_tracked: usize,
+}
+
+impl Default for AmplificationLimit {
+    fn default() -> Self {
+        Self {
+            factor: 3,
+            lifetime: Duration::from_secs(30),
+            max_tracked: 65536,
+        }
+    }
+}
+
+/// The bytes exchanged with an unverified address.
+#[derive(Debug, Clone, Copy)]
+struct Budget {
+    /// The bytes received from the address.
+    received: usize,
+
+    /// The bytes sent to the address.
+    sent: usize,
+
+    /// The time the first message of the address was received at.
+    first_seen: Instant,
+}
+
+///
+/// Amplification guarded transport type definition.
+///
+/// Wraps the [`Transport`] of the server, counting the bytes received from and sent to the addresses which aren't in the [`PeerRegistry`].
+/// The datagrams exceeding the [`AmplificationLimit`] are dropped silently, like they would be on a congested network.
+///
+#[derive(Debug)]
+pub(crate) struct AmplificationGuard<T> {
+    /// The wrapped transport.
+    inner: T,
+
+    /// The registry of the peers which have joined, and aren't limited.
+    peers: PeerRegistry,
+
+    /// The limit of the unverified addresses.
+    limit: AmplificationLimit,
+
+    /// The budget of every tracked unverified address.
+    budgets: DashMap<SocketAddr, Budget>,
+}
+
+impl<T> AmplificationGuard<T> {
+    /// Creates a new [`AmplificationGuard`] instance.
+    pub(crate) fn new(inner: T, peers: PeerRegistry, limit: AmplificationLimit) -> Self {
+        Self {
+            inner,
+            peers,
+            limit,
+            budgets: DashMap::new(),
+        }
+    }
+
+    /// Counts the bytes received from the `source`, if it hasn't joined yet.
+    fn on_received(&self, byte_count: usize, source: SocketAddr) {
+        if self.peers.contains_key(&source) {
+            self.budgets.remove(&source);
+
+            return;
+        }
+
+        let now = Instant::now();
+
+        //Make room for the new address by forgetting the expired ones
+        if !self.budgets.contains_key(&source) && self.budgets.len() >= self.limit.max_tracked {
+            self.budgets
+                .retain(|_, budget| now.duration_since(budget.first_seen) < self.limit.lifetime);
+
+            if self.budgets.len() >= self.limit.max_tracked {
+                return;
+            }
+        }
+
+        let mut budget = self.budgets.entry(source).or_insert(Budget {
+            received: 0,
+            sent: 0,
+            first_seen: now,
+        });
+
+        //Start counting again once the budget has expired
+        if now.duration_since(budget.first_seen) >= self.limit.lifetime {
+            *budget = Budget {
+                received: 0,
+                sent: 0,
+                first_seen: now,
+            };
+        }
+
+        budget.received += byte_count;
+    }
+
+    /// Counts the bytes sent to the `target`, returning whether they fit its budget.
+    fn try_send(&self, byte_count: usize, target: SocketAddr) -> bool {
+        if self.peers.contains_key(&target) {
+            return true;
+        }
+
+        let Some(mut budget) = self.budgets.get_mut(&target) else {
+            return false;
+        };
+
+        if budget.sent + byte_count > budget.received.saturating_mul(self.limit.factor) {
+            return false;
+        }
+
+        budget.sent += byte_count;
+
+        true
+    }
+}
+
+impl<T: Transport> Transport for AmplificationGuard<T> {
+    async fn send_datagram(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        if !self.try_send(buf.len(), target) {
+            event!(
+                Level::DEBUG,
+                "Dropped a message to the unverified address {target}, as it exceeds the amplification limit."
+            );
+
+            return Ok(buf.len());
+        }
+
+        self.inner.send_datagram(buf, target).await
+    }
+
+    async fn recv_datagram(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (byte_count, source) = self.inner.recv_datagram(buf).await?;
+
+        self.on_received(byte_count, source);
+
+        Ok((byte_count, source))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    async fn recv_datagram_ecn(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Ecn)> {
+        let (byte_count, source, ecn) = self.inner.recv_datagram_ecn(buf).await?;
+
+        self.on_received(byte_count, source);
+
+        Ok((byte_count, source, ecn))
+    }
+
+    fn set_ecn(&self, ecn: Ecn) -> io::Result<()> {
+        self.inner.set_ecn(ecn)
+    }
+
+    fn set_traffic_class(&self, dscp: Dscp) -> io::Result<()> {
+        self.inner.set_traffic_class(dscp)
+    }
+
+    fn set_flow_label(&self, flow_label: u32) -> io::Result<()> {
+        self.inner.set_flow_label(flow_label)
+    }
+
+    fn set_hop_limit(&self, hop_limit: u8) -> io::Result<()> {
+        self.inner.set_hop_limit(hop_limit)
+    }
+
+    fn enable_path_errors(&self) -> io::Result<()> {
+        self.inner.enable_path_errors()
+    }
+
+    fn recv_path_error(&self) -> impl Future<Output = io::Result<PathError>> + Send {
+        self.inner.recv_path_error()
+    }
+}
@@ -0,0 +1,225 @@
+//!
+//! Provides the pluggable audio codecs of the [`Client`](super::client::Client).
+//!
+//! [`Opus`] is the default codec, every client encodes and decodes it.
+//! Other codecs (for example G.711 or G.722 for interop with telephony, or experimental codecs like Lyra) are plugged in through the [`AudioCodecs`] of the [`ClientConfig`](super::client::ClientConfig).
+//! The registered codecs are advertised to the server with [`ControlMessage::Codecs`](crate::packet::control::ControlMessage::Codecs), which answers with the codecs every peer of the session can decode.
+//! The voice is encoded with the [`VoiceConfig::codec`] while every peer can decode it, and with Opus otherwise.
+//!
+
+use std::{fmt::Debug, sync::Arc};
+
+use silence_core::opus::{
+    encode::create_opus_encoder,
+    opus::{Bitrate, Channels, Decoder, Encoder},
+};
+
+use super::{event::MediaError, voice::VoiceConfig};
+use crate::{packet::MediaCodec, MTU_MAX_PACKET_SIZE};
+
+/// The highest amount of samples (per channel) an Opus packet can contain, which is 120ms at 48kHz.
+const MAX_OPUS_FRAME_SIZE: usize = 5760;
+
+///
+/// Audio encoder trait definition.
+///
+/// Encodes the frames of a single audio stream, so it may keep state between the frames.
+///
+pub trait AudioEncoder: Send {
+    /// Encodes a single frame of interleaved `samples` (of [`VoiceConfig::samples_per_frame`] length) into its payload.
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>, MediaError>;
+
+    /// Sets the bitrate of the encoder, the codecs with a fixed bitrate ignore it.
+    fn set_bitrate(&mut self, _bitrate: Bitrate) -> Result<(), MediaError> {
+        Ok(())
+    }
+}
+
+///
+/// Audio decoder trait definition.
+///
+/// Decodes the frames of a single audio stream of a remote author, so it may keep state between the frames.
+///
+pub trait AudioDecoder: Send {
+    /// Decodes the `payload` of a single frame into interleaved samples.
+    fn decode(&mut self, payload: &[u8]) -> Result<Vec<f32>, MediaError>;
+
+    /// Generates `sample_count` interleaved samples in place of a lost frame (packet loss concealment).
+    /// The codecs without concealment fill the gap with silence.
+    fn conceal(&mut self, sample_count: usize) -> Result<Vec<f32>, MediaError> {
+        Ok(vec![0.; sample_count])
+    }
+}
+
+///
+/// Audio codec trait definition.
+///
+/// Creates the [`AudioEncoder`] of the sent audio streams, and an [`AudioDecoder`] for every received audio stream.
+///
+pub trait AudioCodec: Send + Sync {
+    /// Creates an encoder for the samples described by the [`VoiceConfig`], starting at the `bitrate`.
+    fn encoder(
+        &self,
+        voice_config: &VoiceConfig,
+        bitrate: Bitrate,
+    ) -> Result<Box<dyn AudioEncoder>, MediaError>;
+
+    /// Creates a decoder, which decodes to the `sample_rate` and the `channels`.
+    fn decoder(
+        &self,
+        sample_rate: u32,
+        channels: Channels,
+    ) -> Result<Box<dyn AudioDecoder>, MediaError>;
+}
+
+///
+/// Opus codec type definition.
+///
+/// The default [`AudioCodec`], encoding with the [`Application`](silence_core::opus::opus::Application) of the [`VoiceConfig`].
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Opus;
+
+impl AudioCodec for Opus {
+    fn encoder(
+        &self,
+        voice_config: &VoiceConfig,
+        bitrate: Bitrate,
+    ) -> Result<Box<dyn AudioEncoder>, MediaError> {
+        let encoder = create_opus_encoder(
+            voice_config.sample_rate,
+            voice_config.application,
+            bitrate,
+            voice_config.channels,
+        )
+        .map_err(|err| MediaError::Codec(err.into()))?;
+
+        Ok(Box::new(OpusEncoder(encoder)))
+    }
+
+    fn decoder(
+        &self,
+        sample_rate: u32,
+        channels: Channels,
+    ) -> Result<Box<dyn AudioDecoder>, MediaError> {
+        Ok(Box::new(OpusDecoder {
+            decoder: Decoder::new(sample_rate, channels)?,
+            channels: channels as usize,
+        }))
+    }
+}
+
+/// The [`AudioEncoder`] of the [`Opus`] codec.
+struct OpusEncoder(Encoder);
+
+impl AudioEncoder for OpusEncoder {
+    fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>, MediaError> {
+        Ok(self.0.encode_vec_float(samples, MTU_MAX_PACKET_SIZE)?)
+    }
+
+    fn set_bitrate(&mut self, bitrate: Bitrate) -> Result<(), MediaError> {
+        if self.0.get_bitrate()? != bitrate {
+            self.0.set_bitrate(bitrate)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The [`AudioDecoder`] of the [`Opus`] codec.
+struct OpusDecoder {
+    /// The decoder of the stream.
+    decoder: Decoder,
+
+    /// The amount of interleaved channels the stream is decoded to.
+    channels: usize,
+}
+
+impl AudioDecoder for OpusDecoder {
+    fn decode(&mut self, payload: &[u8]) -> Result<Vec<f32>, MediaError> {
+        let mut samples = vec![0f32; MAX_OPUS_FRAME_SIZE * self.channels];
+        let sample_count = self.decoder.decode_float(payload, &mut samples, false)?;
+
+        samples.truncate(sample_count * self.channels);
+
+        Ok(samples)
+    }
+
+    fn conceal(&mut self, sample_count: usize) -> Result<Vec<f32>, MediaError> {
+        let mut samples = vec![0f32; sample_count];
+        let decoded_count = self.decoder.decode_float(&[], &mut samples, false)?;
+
+        samples.truncate(decoded_count * self.channels);
+
+        Ok(samples)
+    }
+}
+
+///
+/// Audio codec registry type definition.
+///
+/// Holds the [`AudioCodec`] of every registered [`MediaCodec`], [`Opus`] is always registered.
+///
+#[derive(Clone)]
+pub struct AudioCodecs {
+    /// The registered codecs, in the order they were registered in.
+    codecs: Vec<(MediaCodec, Arc<dyn AudioCodec>)>,
+}
+
+impl Debug for AudioCodecs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioCodecs")
+            .field("codecs", &self.codecs())
+            .finish()
+    }
+}
+
+impl Default for AudioCodecs {
+    fn default() -> Self {
+        Self {
+            codecs: vec![(MediaCodec::Opus, Arc::new(Opus))],
+        }
+    }
+}
+
+impl AudioCodecs {
+    /// Creates a new [`AudioCodecs`] instance, with only [`Opus`] registered.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the `codec` for the `media_codec`, replacing the previously registered one.
+    pub fn register(&mut self, media_codec: MediaCodec, codec: impl AudioCodec + 'static) {
+        self.codecs
+            .retain(|(registered_codec, _)| *registered_codec != media_codec);
+        self.codecs.push((media_codec, Arc::new(codec)));
+    }
+
+    /// Returns the registry with the `codec` registered for the `media_codec` (see [`AudioCodecs::register`]).
+    pub fn with_codec(mut self, media_codec: MediaCodec, codec: impl AudioCodec + 'static) -> Self {
+        self.register(media_codec, codec);
+
+        self
+    }
+
+    /// Returns whether a codec is registered for the `media_codec`.
+    pub fn is_registered(&self, media_codec: MediaCodec) -> bool {
+        self.get(media_codec).is_some()
+    }
+
+    /// Returns the registered codecs, in the order they were registered in.
+    pub fn codecs(&self) -> Vec<MediaCodec> {
+        self.codecs
+            .iter()
+            .map(|(media_codec, _)| *media_codec)
+            .collect()
+    }
+
+    /// Returns the [`AudioCodec`] registered for the `media_codec`.
+    pub fn get(&self, media_codec: MediaCodec) -> Option<&Arc<dyn AudioCodec>> {
+        self.codecs
+            .iter()
+            .find(|(registered_codec, _)| *registered_codec == media_codec)
+            .map(|(_, codec)| codec)
+    }
+}
@@ -0,0 +1,166 @@
+//!
+//! Provides the [`AuditLog`], a structured record of the security relevant [`ServerEvent`]s for compliance sensitive deployments.
+//!
+//! The audit log records the joins, leaves, kicks, bans, rejected connections and recordings with their timestamps and source addresses.
+//! The records are written as JSON lines (for example to a file), or emitted as [`tracing`] events on the [`AUDIT_TARGET`] target, so they can be routed by the subscriber.
+//! Every record is the JSON object of the [`ServerEvent`] with a `timestamp` field, the milliseconds since the unix epoch:
+//! ```json
+//! {"timestamp":1700000000000,"event":"author_banned","author":"67e55044-10b1-426f-9247-bb680e5fe0c8","reason":"Spam"}
+//! ```
+//!
+
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::Mutex;
+use tracing::{event, Level};
+
+use super::{hook::EventSink, server::ServerEvent};
+
+/// The [`tracing`] target the records of a [`AuditLog::tracing`] log are emitted on.
+pub const AUDIT_TARGET: &str = "silence::audit";
+
+///
+/// Audit record type definition.
+///
+/// A single line of the [`AuditLog`].
+///
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AuditRecord<'a> {
+    /// The time the event was recorded at, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+
+    /// The recorded event.
+    #[serde(flatten)]
+    pub server_event: &'a ServerEvent,
+}
+
+/// The destination of the records of an [`AuditLog`].
+#[derive(Clone)]
+enum AuditOutput {
+    /// The records are written to the writer as JSON lines.
+    Writer(Arc<Mutex<Box<dyn Write + Send>>>),
+
+    /// The records are emitted as [`tracing`] events.
+    Tracing,
+}
+
+///
+/// Audit log type definition.
+///
+/// An [`EventSink`] recording the security relevant [`ServerEvent`]s (see [`AuditLog::is_audited`]), which can be toggled at runtime.
+/// The clones of the log share their output and their toggle, so a clone can be kept to toggle the log added with [`ServerHandle::add_event_sink`](super::server::ServerHandle::add_event_sink).
+///
+#[derive(Clone)]
+pub struct AuditLog {
+    /// The destination of the records.
+    output: AuditOutput,
+
+    /// Whether the events are recorded.
+    enabled: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("enabled", &self.is_enabled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl AuditLog {
+    /// Creates a new enabled [`AuditLog`] instance, writing the records to the `writer` as JSON lines.
+    /// The writer is flushed after every record, and it is written from the task of the sink, so it shouldn't block for long (like a file).
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            output: AuditOutput::Writer(Arc::new(Mutex::new(Box::new(writer)))),
+            enabled: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Creates a new enabled [`AuditLog`] instance, emitting the records as [`tracing`] events on the [`AUDIT_TARGET`] target.
+    pub fn tracing() -> Self {
+        Self {
+            output: AuditOutput::Tracing,
+            enabled: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Enables or disables the recording of the events, the events happening while the log is disabled are never recorded.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether the events are recorded.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the [`ServerEvent`] is recorded, the room lifecycle, the peers moving between the rooms, the load shedding and the path errors aren't.
+    pub fn is_audited(server_event: &ServerEvent) -> bool {
+        !matches!(
+            server_event,
+            ServerEvent::PeerMoved { .. }
+                | ServerEvent::RoomCreated { .. }
+                | ServerEvent::RoomDestroyed { .. }
+                | ServerEvent::Overloaded { .. }
+                | ServerEvent::OverloadRelieved { .. }
+                | ServerEvent::PathError { .. }
+        )
+    }
+
+    ///
+    /// Records the [`ServerEvent`], timestamped with the current time.
+    ///
+    /// # Behavior
+    /// Nothing is recorded if the log is disabled, or the event isn't audited.
+    /// The records which could not be written are logged, they aren't retried.
+    ///
+    pub fn record(&self, server_event: &ServerEvent) {
+        if !self.is_enabled() || !Self::is_audited(server_event) {
+            return;
+        }
+
+        let record = AuditRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|timestamp| timestamp.as_millis() as u64)
+                .unwrap_or_default(),
+            server_event,
+        };
+
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(err) => {
+                event!(Level::ERROR, "Failed to serialize an audit record: {err}");
+
+                return;
+            }
+        };
+
+        match &self.output {
+            AuditOutput::Writer(writer) => {
+                let mut writer = writer.lock();
+
+                if let Err(err) = writeln!(writer, "{line}").and_then(|_| writer.flush()) {
+                    event!(Level::ERROR, "Failed to write an audit record: {err}");
+                }
+            }
+            AuditOutput::Tracing => {
+                event!(target: AUDIT_TARGET, Level::INFO, "{line}");
+            }
+        }
+    }
+}
+
+impl EventSink for AuditLog {
+    async fn send_event(&self, server_event: ServerEvent) {
+        self.record(&server_event);
+    }
+}
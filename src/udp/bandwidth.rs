@@ -0,0 +1,130 @@
+//!
+//! Provides the bandwidth caps of the [`Server`](super::server::Server), which limit the bitrate of the media forwarded from every client and in every room.
+//!
+//! The forwarded bytes are counted over fixed windows, and the messages exceeding a cap are dropped by priority: the video messages are dropped first, the voice messages are only dropped once the voice alone exceeds the cap.
+//! The text and control messages are never dropped.
+//! The sender of a dropped message is notified with a [`ControlMessage::MaxBitrate`](crate::packet::control::ControlMessage::MaxBitrate) carrying its cap, once per window.
+//!
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+///
+/// Bandwidth limits type definition.
+///
+/// Describes the highest bitrate the [`Server`](super::server::Server) forwards the media at.
+/// The default limits don't cap anything.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandwidthLimits {
+    /// The highest bitrate (in bits per second) forwarded from a single client.
+    pub max_client_bitrate: Option<u32>,
+
+    /// The highest bitrate (in bits per second) forwarded in a single room, from all of its clients together.
+    pub max_room_bitrate: Option<u32>,
+
+    /// The window the forwarded bytes are counted over, longer windows allow longer bursts.
+    pub window: Duration,
+}
+
+impl Default for BandwidthLimits {
+    fn default() -> Self {
+        Self {
+            max_client_bitrate: None,
+            max_room_bitrate: None,
+            window: Duration::from_secs(1),
+        }
+    }
+}
+
+impl BandwidthLimits {
+    /// Returns whether any cap is set.
+    pub fn is_enabled(&self) -> bool {
+        self.max_client_bitrate.is_some() || self.max_room_bitrate.is_some()
+    }
+
+    /// Returns the bytes the `max_bitrate` allows in a window.
+    fn budget(&self, max_bitrate: u32) -> u64 {
+        (max_bitrate as f64 * self.window.as_secs_f64() / 8.) as u64
+    }
+}
+
+/// The bytes forwarded in the current window of a client or a room.
+#[derive(Debug, Clone)]
+pub(crate) struct BandwidthMeter {
+    /// The start of the current window.
+    window_start: Instant,
+
+    /// The bytes of the voice messages forwarded in the current window.
+    voice_bytes: u64,
+
+    /// The bytes of every media message forwarded in the current window.
+    total_bytes: u64,
+
+    /// Whether the sender was notified about its cap in the current window.
+    is_notified: bool,
+}
+
+impl BandwidthMeter {
+    /// Creates a new [`BandwidthMeter`] instance, with its window starting at `now`.
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            voice_bytes: 0,
+            total_bytes: 0,
+            is_notified: false,
+        }
+    }
+
+    /// Returns whether the window of the meter has ended before `now`.
+    pub(crate) fn is_expired(&self, now: Instant, limits: &BandwidthLimits) -> bool {
+        now.duration_since(self.window_start) >= limits.window
+    }
+
+    /// Starts a new window if the current one has ended.
+    fn roll(&mut self, now: Instant, limits: &BandwidthLimits) {
+        if self.is_expired(now, limits) {
+            *self = Self::new(now);
+        }
+    }
+
+    /// Returns whether a message of `bytes` fits the `max_bitrate` cap in the current window.
+    /// Voice messages only compete with the other voice messages, the rest of the media competes with everything.
+    pub(crate) fn fits(
+        &mut self,
+        now: Instant,
+        limits: &BandwidthLimits,
+        max_bitrate: Option<u32>,
+        bytes: u64,
+        is_voice: bool,
+    ) -> bool {
+        self.roll(now, limits);
+
+        let Some(max_bitrate) = max_bitrate else {
+            return true;
+        };
+
+        let used_bytes = if is_voice {
+            self.voice_bytes
+        } else {
+            self.total_bytes
+        };
+
+        used_bytes + bytes <= limits.budget(max_bitrate)
+    }
+
+    /// Counts a forwarded message of `bytes` in the current window.
+    pub(crate) fn record(&mut self, bytes: u64, is_voice: bool) {
+        self.total_bytes += bytes;
+
+        if is_voice {
+            self.voice_bytes += bytes;
+        }
+    }
+
+    /// Marks the sender notified in the current window, returning whether it hadn't been yet.
+    pub(crate) fn notify(&mut self) -> bool {
+        !std::mem::replace(&mut self.is_notified, true)
+    }
+}
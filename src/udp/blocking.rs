@@ -0,0 +1,168 @@
+//!
+//! Provides a synchronous facade over the [`Client`], for applications which aren't async (game engines, GUI toolkits with their own loops).
+//!
+//! The [`BlockingClient`] owns an internal [tokio](https://crates.io/crates/tokio) runtime, which drives the client service in the background.
+//! ***The blocking methods must not be called from inside an async context, as they would block the executor.***
+//!
+
+use std::{net::SocketAddr, time::Duration};
+
+use tokio::{net::ToSocketAddrs, runtime::Runtime, sync::mpsc::error::TryRecvError};
+use uuid::Uuid;
+
+use super::{
+    client::Client,
+    event::{ClientError, ClientEvent},
+    power::PowerMode,
+    transport::Transport,
+};
+use crate::packet::{control::CloseReason, VoipMessageType, VoipPacket};
+
+/// The amount of worker threads the internal runtime of a [`BlockingClient`] uses.
+const RUNTIME_WORKER_THREADS: usize = 1;
+
+///
+/// Blocking client type definition.
+///
+/// Wraps a [`Client`] and the runtime driving it, and exposes blocking and polling methods instead of async ones.
+///
+#[derive(Debug)]
+pub struct BlockingClient {
+    /// The wrapped [`Client`] instance.
+    client: Client,
+
+    /// The runtime driving the client service.
+    /// This must be dropped after the [`Client`], so it is declared after it.
+    runtime: Runtime,
+}
+
+impl BlockingClient {
+    /// Creates a new [`BlockingClient`] instance, automaticly sets up the internal runtime and the [`UdpSocket`](tokio::net::UdpSocket).
+    pub fn new<T: ToSocketAddrs>(
+        uuid: Uuid,
+        remote_addr: T,
+    ) -> std::result::Result<Self, ClientError> {
+        let runtime = create_runtime()?;
+
+        let client = runtime.block_on(Client::new(uuid, remote_addr))?;
+
+        Ok(Self { client, runtime })
+    }
+
+    /// Creates a new [`BlockingClient`] instance from any [`Transport`], which will exchange messages with the `remote_addr`.
+    pub fn new_from_transport<T: Transport>(
+        uuid: Uuid,
+        transport: T,
+        remote_addr: SocketAddr,
+    ) -> std::result::Result<Self, ClientError> {
+        let runtime = create_runtime()?;
+
+        let client = runtime.block_on(Client::new_from_transport(uuid, transport, remote_addr))?;
+
+        Ok(Self { client, runtime })
+    }
+
+    /// Returns the [`Uuid`] this [`BlockingClient`] instance was created with.
+    pub fn uuid(&self) -> Uuid {
+        self.client.uuid()
+    }
+
+    /// Returns the local address the wrapped [`Client`] is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.client.local_addr()
+    }
+
+    /// Returns the remote address the wrapped [`Client`] exchanges messages with.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.client.peer_addr()
+    }
+
+    /// Returns a reference to the wrapped [`Client`].
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Returns the handle of the internal runtime.
+    /// This can be used to run async functions of the wrapped [`Client`].
+    pub fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+
+    /// Sends a [`VoipPacket`] to the remote address, blocking until there is capacity in the outgoing channel.
+    pub fn send(&mut self, voip_packet: VoipPacket) -> std::result::Result<(), ClientError> {
+        Ok(self.client.message_sender().blocking_send(voip_packet)?)
+    }
+
+    /// Creates a [`VoipPacket`] from the arguments passed in, and sends it to the remote address.
+    /// Blocks until the message is handed to the client service.
+    pub fn send_bytes(
+        &self,
+        voip_message_type: VoipMessageType,
+        bytes: &mut dyn Iterator<Item = u8>,
+    ) -> std::result::Result<(), ClientError> {
+        self.runtime
+            .block_on(self.client.send_bytes(voip_message_type, bytes))
+    }
+
+    /// Notifies the remote address that this client is leaving the session.
+    /// Blocks until the message is handed to the client service.
+    pub fn disconnect(&self) -> std::result::Result<(), ClientError> {
+        self.runtime.block_on(self.client.disconnect())
+    }
+
+    /// Closes the session with the [`CloseReason`].
+    /// Blocks until the closure is handed to the client service.
+    pub fn close(&self, close_reason: CloseReason) -> std::result::Result<(), ClientError> {
+        self.runtime.block_on(self.client.close(close_reason))
+    }
+
+    /// Switches the client service to the [`PowerMode`], like [`Client::set_power_mode`].
+    /// Blocks until the mode is handed to the client service.
+    pub fn set_power_mode(&self, power_mode: PowerMode) -> std::result::Result<(), ClientError> {
+        self.runtime
+            .block_on(self.client.set_power_mode(power_mode))
+    }
+
+    /// Blocks until a [`ClientEvent`] is received from the client service.
+    /// Returns [`None`] if the client service has shut down.
+    pub fn recv(&mut self) -> Option<ClientEvent> {
+        self.client.event_receiver().blocking_recv()
+    }
+
+    /// Blocks until a [`ClientEvent`] is received from the client service, or the `timeout` has elapsed.
+    /// Returns [`None`] if the `timeout` has elapsed, or the client service has shut down.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<ClientEvent> {
+        let event_receiver = self.client.event_receiver();
+
+        self.runtime
+            .block_on(async { tokio::time::timeout(timeout, event_receiver.recv()).await })
+            .ok()
+            .flatten()
+    }
+
+    /// Returns a [`ClientEvent`] if there is one available, without blocking.
+    /// This is meant to be called once per frame from an application's own loop.
+    pub fn poll(&mut self) -> std::result::Result<ClientEvent, TryRecvError> {
+        self.client.event_receiver().try_recv()
+    }
+
+    /// Returns every [`ClientEvent`] received since the last poll, without blocking.
+    pub fn poll_all(&mut self) -> Vec<ClientEvent> {
+        let mut client_events = vec![];
+
+        while let Ok(client_event) = self.poll() {
+            client_events.push(client_event);
+        }
+
+        client_events
+    }
+}
+
+/// Creates the runtime driving the client service of a [`BlockingClient`].
+fn create_runtime() -> std::result::Result<Runtime, ClientError> {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(RUNTIME_WORKER_THREADS)
+        .enable_all()
+        .build()
+        .map_err(ClientError::Runtime)
+}
@@ -0,0 +1,255 @@
+//!
+//! Provides the [`Bridge`], which connects an edge [`Server`] to an origin [`Server`] (cascading).
+//!
+//! The bridge subscribes to the origin as a pseudo-client, and re-fans every message it receives to the local clients of the edge.
+//! This allows one origin to serve thousands of listeners through regional edges, as the origin only has to send every message once per edge.
+//!
+//! The origin has to reply to the bridge like to any other client, so add [`Bridge::local_addr`] to the origin's reply list.
+//! Messages from the local clients are not relayed automaticly, the edge application decides what to send upstream with [`Bridge::upstream_sender`].
+//!
+
+use std::net::SocketAddr;
+
+use tokio::{
+    net::{ToSocketAddrs, UdpSocket},
+    select,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use super::{
+    client::{send_control_message, ClientConfig},
+    event::ClientError,
+    runtime::{Runtime, Tokio},
+    server::Server,
+    transport::Transport,
+};
+use crate::{
+    packet::{
+        control::ControlMessage, decode_message, VoipMessageType, VoipPacket, LENGTH_PREFIX_SIZE,
+    },
+    MTU_MAX_PACKET_SIZE,
+};
+
+///
+/// Bridge type definition.
+///
+/// Relays the messages of an origin server to the local clients of an edge [`Server`].
+/// The bridge service stops when the origin closes the session, the edge shuts down, or the [`CancellationToken`] is cancelled.
+///
+#[derive(Debug)]
+pub struct Bridge {
+    /// The [`Uuid`] the bridge subscribes to the origin with.
+    uuid: Uuid,
+
+    /// The local address of the upstream transport.
+    local_addr: SocketAddr,
+
+    /// This local channel sends messages which will be sent to the origin.
+    upstream_sender: Sender<VoipPacket>,
+
+    /// The bridge service's [`CancellationToken`].
+    /// This can be used to stop the bridge, which notifies the origin with a [`ControlMessage::Goodbye`].
+    cancellation_token: CancellationToken,
+}
+
+impl Bridge {
+    /// Creates a new [`Bridge`] instance, binds to the local `[::]:0` address and subscribes to the origin at the `origin_addr`.
+    pub async fn new<T: ToSocketAddrs>(
+        uuid: Uuid,
+        edge: &Server,
+        origin_addr: T,
+    ) -> std::result::Result<Self, ClientError> {
+        let socket_handle = UdpSocket::bind("[::]:0")
+            .await
+            .map_err(ClientError::SocketIo)?;
+
+        socket_handle
+            .connect(origin_addr)
+            .await
+            .map_err(ClientError::NotConnected)?;
+
+        let origin_addr = socket_handle
+            .peer_addr()
+            .map_err(ClientError::NotConnected)?;
+
+        Self::new_from_transport(uuid, edge, socket_handle, origin_addr).await
+    }
+
+    /// Creates a new [`Bridge`] instance, which subscribes to the origin at the `origin_addr` through any [`Transport`].
+    /// The bridge service is spawned on the [`Tokio`] runtime.
+    pub async fn new_from_transport<T: Transport>(
+        uuid: Uuid,
+        edge: &Server,
+        upstream: T,
+        origin_addr: SocketAddr,
+    ) -> std::result::Result<Self, ClientError> {
+        Self::new_from_transport_with_runtime::<Tokio, T>(
+            uuid,
+            edge,
+            upstream,
+            origin_addr,
+            ClientConfig::default(),
+        )
+        .await
+    }
+
+    /// Creates a new [`Bridge`] instance, which subscribes to the origin at the `origin_addr` through any [`Transport`].
+    /// The bridge service is spawned on the [`Runtime`] `R`, and sends heartbeats according to the [`ClientConfig`].
+    pub async fn new_from_transport_with_runtime<R: Runtime, T: Transport>(
+        uuid: Uuid,
+        edge: &Server,
+        upstream: T,
+        origin_addr: SocketAddr,
+        config: ClientConfig,
+    ) -> std::result::Result<Self, ClientError> {
+        let local_addr = upstream.local_addr().map_err(ClientError::SocketIo)?;
+        let (upstream_sender, upstream_receiver) = channel::<VoipPacket>(255);
+        let cancellation_token = CancellationToken::new();
+
+        Self::create_bridge_service::<R, T>(
+            uuid,
+            config,
+            upstream,
+            origin_addr,
+            edge.reply_sender(),
+            upstream_receiver,
+            cancellation_token.clone(),
+        );
+
+        Ok(Self {
+            uuid,
+            local_addr,
+            upstream_sender,
+            cancellation_token,
+        })
+    }
+
+    /// Returns the [`Uuid`] the bridge subscribes to the origin with.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Returns the local address of the upstream transport.
+    /// This is the address the origin has to reply to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Gets the upstream message sender handle.
+    /// Every [`VoipPacket`] sent through it is sent to the origin.
+    pub fn upstream_sender(&self) -> &Sender<VoipPacket> {
+        &self.upstream_sender
+    }
+
+    /// Bridge service cancellation token ([`CancellationToken`]) for stopping the bridge.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    fn create_bridge_service<R: Runtime, T: Transport>(
+        uuid: Uuid,
+        config: ClientConfig,
+        upstream: T,
+        origin_addr: SocketAddr,
+        edge_sender: Sender<VoipPacket>,
+        mut upstream_receiver: Receiver<VoipPacket>,
+        cancellation_token: CancellationToken,
+    ) {
+        R::spawn(async move {
+            //Create buffer for reading incoming messages, the length prefix is not included in the MTU
+            let mut buf = vec![0; MTU_MAX_PACKET_SIZE + LENGTH_PREFIX_SIZE];
+
+            //The first heartbeat is sent right away, so that the origin registers the bridge
+            if let Err(err) =
+                send_control_message(&upstream, ControlMessage::Heartbeat, uuid, origin_addr).await
+            {
+                event!(
+                    Level::ERROR,
+                    "Failed to send heartbeat to the origin: {err}"
+                );
+            }
+
+            let mut next_heartbeat = Instant::now() + config.heartbeat_interval;
+
+            loop {
+                select! {
+                    //Await incoming messages from the origin, and re-fan them to the local clients
+                    incoming_bytes = upstream.recv_datagram(&mut buf) => {
+                        match incoming_bytes {
+                            Ok((byte_count, socket_addr)) => {
+                                //Discard messages which werent sent by the origin
+                                if socket_addr != origin_addr {
+                                    continue;
+                                }
+
+                                //Validate the message before relaying it
+                                match decode_message(&buf[..byte_count]) {
+                                    Ok((voip_header, _)) => match voip_header.voip_message_type() {
+                                        //Heartbeat echoes and connection ids are meant for the bridge only
+                                        VoipMessageType::Control(ControlMessage::Heartbeat | ControlMessage::ConnectionId(_)) => continue,
+                                        //Echo the retry token of the origin, so that it registers the bridge
+                                        VoipMessageType::Control(ControlMessage::Retry(retry_token)) => {
+                                            if let Err(err) = send_control_message(&upstream, ControlMessage::RetryHeartbeat(*retry_token), uuid, origin_addr).await {
+                                                event!(Level::ERROR, "Failed to answer the retry of the origin: {err}");
+                                            }
+                                        },
+                                        VoipMessageType::Control(ControlMessage::Close(close_reason)) => {
+                                            event!(Level::INFO, "The origin has closed the session: {close_reason:?}");
+
+                                            cancellation_token.cancel();
+
+                                            break;
+                                        },
+                                        _ => {
+                                            //Relay the original buffer, so that every header field is preserved. If the edge was shut down, stop the bridge.
+                                            if edge_sender.send(VoipPacket::from_validated(buf[..byte_count].to_vec())).await.is_err() {
+                                                cancellation_token.cancel();
+
+                                                break;
+                                            }
+                                        },
+                                    },
+                                    Err(err) => {
+                                        event!(Level::ERROR, "Failed to decode a VoipPacket from the origin: {err}. Discarding message.");
+                                    },
+                                }
+                            },
+                            Err(err) => {
+                                event!(Level::ERROR, "Failed to receive message from the origin: {err}");
+                            },
+                        }
+                    }
+
+                    //Await outgoing message requests from the edge application
+                    Some(outgoing_message) = upstream_receiver.recv() => {
+                        if let Err(err) = upstream.send_datagram(outgoing_message.inner(), origin_addr).await {
+                            event!(Level::ERROR, "Failed to send message to the origin: {err}");
+                        }
+                    }
+
+                    //Send a heartbeat to the origin periodically
+                    _ = R::sleep(next_heartbeat.saturating_duration_since(Instant::now())) => {
+                        next_heartbeat = Instant::now() + config.heartbeat_interval;
+
+                        if let Err(err) = send_control_message(&upstream, ControlMessage::Heartbeat, uuid, origin_addr).await {
+                            event!(Level::ERROR, "Failed to send heartbeat to the origin: {err}");
+                        }
+                    }
+
+                    //Await bridge cancellation, and leave the origin's session
+                    _ = cancellation_token.cancelled() => {
+                        if let Err(err) = send_control_message(&upstream, ControlMessage::Goodbye, uuid, origin_addr).await {
+                            event!(Level::ERROR, "Failed to send goodbye to the origin: {err}");
+                        }
+
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
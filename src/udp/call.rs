@@ -0,0 +1,223 @@
+//!
+//! Provides the one-to-one call signaling of the [`Client`](super::client::Client), built on [`ControlMessage::Call`](crate::packet::control::ControlMessage::Call).
+//!
+//! A call is set up by the caller inviting the callee, the callee's client answers with ringing (or busy if it is in another call already), and the callee's user accepts or rejects it.
+//! Either side can hang up afterwards, the caller can hang up while the callee is ringing to cancel the invitation.
+//! Every change of the state of a call is reported with [`ClientEvent::CallStateChanged`](super::event::ClientEvent::CallStateChanged).
+//!
+
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use super::event::ClientError;
+use crate::packet::control::{CallSignal, CallSignalKind};
+
+/// The state of a one-to-one call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallState {
+    /// The invitation was sent, and the callee hasn't answered yet.
+    Inviting,
+
+    /// The callee is alerting its user.
+    Ringing,
+
+    /// An invitation was received, and can be accepted or rejected.
+    Incoming,
+
+    /// The call was accepted.
+    Active,
+
+    /// The call has ended, with the contained [`CallEnd`].
+    Ended(CallEnd),
+}
+
+/// The reason a call has ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallEnd {
+    /// The callee has rejected the call.
+    Rejected,
+
+    /// The callee was already in another call.
+    Busy,
+
+    /// Either side has hung up (or the caller has cancelled the invitation).
+    HungUp,
+
+    /// The callee doesn't want to be disturbed, and the server has rejected the call on its behalf.
+    DoNotDisturb,
+}
+
+///
+/// Call type definition.
+///
+/// Describes a one-to-one call of the [`Client`](super::client::Client) with a remote peer.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call {
+    /// The id of the call.
+    pub call_id: Uuid,
+
+    /// The [`Uuid`] of the remote peer.
+    pub peer: Uuid,
+
+    /// Whether the call was placed by this client.
+    pub is_outgoing: bool,
+
+    /// The current state of the call.
+    pub state: CallState,
+}
+
+/// The calls of a [`Client`](super::client::Client), which drives the state machine of every call.
+#[derive(Debug, Default)]
+pub(crate) struct CallRegistry {
+    /// The calls which haven't ended yet, by their id.
+    calls: HashMap<Uuid, Call>,
+}
+
+impl CallRegistry {
+    /// Returns the calls which haven't ended yet.
+    pub(crate) fn calls(&self) -> Vec<Call> {
+        self.calls.values().copied().collect()
+    }
+
+    /// Places a new call to the `peer`, returning the [`CallSignal`] inviting it.
+    pub(crate) fn invite(&mut self, peer: Uuid) -> CallSignal {
+        let call_id = Uuid::new_v4();
+
+        self.calls.insert(
+            call_id,
+            Call {
+                call_id,
+                peer,
+                is_outgoing: true,
+                state: CallState::Inviting,
+            },
+        );
+
+        CallSignal {
+            call_id,
+            recipient: peer,
+            kind: CallSignalKind::Invite,
+        }
+    }
+
+    ///
+    /// Applies a local action to the call with the `call_id`, returning the [`CallSignal`] to send to the remote peer.
+    ///
+    /// # Behavior
+    /// Only the incoming calls can be accepted or rejected, and only while they haven't been answered.
+    /// Every call which hasn't ended can be hung up.
+    ///
+    /// # Error
+    /// Returns [`ClientError::UnknownCall`] if the call doesn't exist (or has ended), and [`ClientError::InvalidCallState`] if the action isn't allowed in its state.
+    ///
+    pub(crate) fn act(
+        &mut self,
+        call_id: Uuid,
+        kind: CallSignalKind,
+    ) -> Result<(Call, CallSignal), ClientError> {
+        let call = self
+            .calls
+            .get_mut(&call_id)
+            .ok_or(ClientError::UnknownCall(call_id))?;
+
+        call.state = match (kind, call.state) {
+            (CallSignalKind::Accept, CallState::Incoming) => CallState::Active,
+            (CallSignalKind::Reject, CallState::Incoming) => CallState::Ended(CallEnd::Rejected),
+            (CallSignalKind::Hangup, _) => CallState::Ended(CallEnd::HungUp),
+            (_, state) => return Err(ClientError::InvalidCallState(state)),
+        };
+
+        let call = *call;
+
+        if matches!(call.state, CallState::Ended(_)) {
+            self.calls.remove(&call_id);
+        }
+
+        Ok((
+            call,
+            CallSignal {
+                call_id,
+                recipient: call.peer,
+                kind,
+            },
+        ))
+    }
+
+    ///
+    /// Handles the [`CallSignal`] received from the `author`.
+    ///
+    /// # Behavior
+    /// Returns the call whose state has changed, and the signal to answer the `author` with, if any.
+    /// An invitation is answered with [`CallSignalKind::Ringing`], or with [`CallSignalKind::Busy`] if another call hasn't ended yet.
+    /// The signals which don't belong to a known call of the `author`, or which aren't allowed in the state of their call are ignored.
+    ///
+    pub(crate) fn receive(
+        &mut self,
+        author: Uuid,
+        call_signal: CallSignal,
+    ) -> (Option<Call>, Option<CallSignal>) {
+        let reply = |kind| CallSignal {
+            call_id: call_signal.call_id,
+            recipient: author,
+            kind,
+        };
+
+        if call_signal.kind == CallSignalKind::Invite {
+            if self.calls.contains_key(&call_signal.call_id) {
+                return (None, None);
+            }
+
+            if !self.calls.is_empty() {
+                return (None, Some(reply(CallSignalKind::Busy)));
+            }
+
+            let call = Call {
+                call_id: call_signal.call_id,
+                peer: author,
+                is_outgoing: false,
+                state: CallState::Incoming,
+            };
+
+            self.calls.insert(call.call_id, call);
+
+            return (Some(call), Some(reply(CallSignalKind::Ringing)));
+        }
+
+        //The server (whose author is nil) hangs up the calls of the recipients which aren't in the session
+        let Some(call) = self.calls.get_mut(&call_signal.call_id).filter(|call| {
+            call.peer == author || (author.is_nil() && call_signal.kind == CallSignalKind::Hangup)
+        }) else {
+            return (None, None);
+        };
+
+        let state = match (call_signal.kind, call.is_outgoing, call.state) {
+            (CallSignalKind::Ringing, true, CallState::Inviting) => CallState::Ringing,
+            (CallSignalKind::Accept, true, CallState::Inviting | CallState::Ringing) => {
+                CallState::Active
+            }
+            (CallSignalKind::Reject, true, CallState::Inviting | CallState::Ringing) => {
+                CallState::Ended(CallEnd::Rejected)
+            }
+            (CallSignalKind::Busy, true, CallState::Inviting | CallState::Ringing) => {
+                CallState::Ended(CallEnd::Busy)
+            }
+            (CallSignalKind::DoNotDisturb, true, CallState::Inviting | CallState::Ringing) => {
+                CallState::Ended(CallEnd::DoNotDisturb)
+            }
+            (CallSignalKind::Hangup, ..) => CallState::Ended(CallEnd::HungUp),
+            _ => return (None, None),
+        };
+
+        call.state = state;
+
+        let call = *call;
+
+        if matches!(call.state, CallState::Ended(_)) {
+            self.calls.remove(&call.call_id);
+        }
+
+        (Some(call), None)
+    }
+}
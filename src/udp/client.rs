@@ -1,254 +1,3183 @@
 //! Provides functions and helpers for the client side of the Voip service.
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use super::Result;
-use super::UdpError;
+use super::audio_codec::{AudioCodec, AudioCodecs, Opus};
+use super::decoder::{DecoderLimits, SpeakerStats};
+use super::diagnostics::{
+    bind_probe_socket, check_clock, echo_report, probe_server, query_stun, DiagnosticError,
+    DiagnosticsConfig, DiagnosticsReport, LossReport, PingProbe, PingTiming,
+};
+use super::duplicate::{DuplicateFilter, DuplicateQueue, DuplicationConfig};
+use super::event::{ClientError, ClientEvent, ConnectionState, MediaError};
+use super::freeze::{FreezeConfig, FreezeDetector};
+use super::keepalive::{KeepaliveConfig, KeepaliveLearner};
+use super::pacing::PacedQueue;
+use super::playout::Playout;
+use super::power::{self, LowPowerConfig, PowerMode};
+use super::preconnect::{PreconnectConfig, PreconnectReport};
+use super::probe::{ProbeBurst, ProbeConfig, ProbeReport};
+use super::profiling::{PipelineProfile, PipelineProfiler, PipelineStage};
+use super::resolve::resolve;
+use super::runtime::{sleep_fn, Runtime, SleepFn, Tokio};
+use super::speaker::{ActiveSpeakerConfig, ActiveSpeakerDetector};
+use super::tap::{TappedVoiceFrame, VoiceTap, VOICE_TAP_CAPACITY};
+use super::call::{Call, CallRegistry};
+use super::congestion::{CongestionConfig, CongestionController};
+use super::transport::{ecn::Ecn, path, qos::QosConfig, Transport};
+use super::usage::{DataUsage, Metered, UsageMeter};
+use super::video::{
+    DecodedVideoFrame, FrameRefresh, RefreshScheduler, RgbaImage, VideoDecoder, VideoDecoders,
+    VideoEncoder, VideoEncoderConfig, VideoEncoderState,
+};
+use super::voice::{
+    AudioProfile, AudioStream, DecodedVoiceFrame, VoiceConfig, VoiceDecoders, VoiceEncoderState,
+};
+use crate::audio::comfort_noise::ComfortNoiseConfig;
+use crate::audio::jitter::{JitterConfig, JitterStats};
+use crate::audio::pipeline::{AudioPipeline, AudioStage};
+use crate::audio::soundboard::{SoundBoard, SoundTarget};
+use crate::packet::audio_level;
+use crate::packet::caption::Caption;
+use crate::packet::control::CallSignalKind;
+use crate::packet::control::CloseCode;
+use crate::packet::control::CloseReason;
+use crate::packet::control::ConnectionId;
+use crate::packet::control::ControlMessage;
+use crate::packet::control::DeviceNotice;
+use crate::packet::control::LayerSelection;
+use crate::packet::control::MediaState;
+use crate::packet::control::PresenceState;
+use crate::packet::control::QualityReport;
+use crate::packet::control::RecordingState;
+use crate::packet::control::ResumptionTicket;
+use crate::packet::control::RoomPolicy;
+use crate::packet::control::ToneEvent;
+use crate::packet::decode_message;
+use crate::packet::fragment::{fragment_message, Reassembler, SliceReassembler};
+use crate::packet::frame::{VideoFrame, VoiceFrame};
+use crate::packet::HeaderFlags;
+use crate::packet::MediaCodec;
 use crate::packet::VoipHeader;
 use crate::packet::VoipMessageType;
 use crate::packet::VoipPacket;
+use crate::packet::AUDIO_LEVEL_SILENCE;
+use crate::packet::LENGTH_PREFIX_SIZE;
 use crate::MTU_MAX_PACKET_SIZE;
-use parking_lot::Mutex;
+use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
 use silence_core::avif::encoding::encode_raw_image;
 use silence_core::avif::ravif;
 use silence_core::cam::Webcam;
-use silence_core::opus::encode::encode_samples_opus;
-use silence_core::opus::opus::Encoder;
-use tokio::net::{ToSocketAddrs, UdpSocket};
+use silence_core::opus::opus::{self, Bitrate};
+use tokio::net::{lookup_host, ToSocketAddrs, UdpSocket};
 use tokio::select;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::channel;
+use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::mpsc::Sender;
-use tracing::event;
-use tracing::Level;
+use tokio::time::Instant;
+use tracing::{event, Level};
 use uuid::Uuid;
 
+/// The default interval of the heartbeats the [`Client`] sends.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The default interval between the fragments of the video frames the [`Client`] sends.
+pub const DEFAULT_VIDEO_PACING_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The audio level (in -dBov) at or above which a voice frame is considered silent, when discontinuous transmission is mandated by a [`RoomPolicy`].
+pub const DTX_SILENCE_LEVEL: u8 = 70;
+
+/// The highest gain (in dB) the loudness normalization of a [`RoomPolicy`] may apply, so that background noise isn't amplified.
+const MAX_NORMALIZATION_GAIN_DB: f32 = 20.;
+
+/// Configuration of the client service.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// The interval of the [`ControlMessage::Heartbeat`]s sent to the remote address.
+    pub heartbeat_interval: Duration,
+
+    /// The configuration of the [`ActiveSpeakerDetector`], which reports [`ClientEvent::ActiveSpeakerChanged`] from the audio levels of the received voice messages.
+    /// Active speaker detection is disabled if this is [`None`].
+    pub active_speaker: Option<ActiveSpeakerConfig>,
+
+    /// The configuration of the [`FreezeDetector`], which reports [`ClientEvent::VideoFreezeChanged`] from the decoded video of the remote authors.
+    /// The last decoded frame of every author is kept to be reported with the freeze. Freeze detection is disabled if this is [`None`].
+    pub video_freeze: Option<FreezeConfig>,
+
+    /// The interval between the fragments of the video frames sent with [`Client::send_video_packet`].
+    /// Pacing the fragments prevents large frames from being sent in a single burst, which could overflow the queues of the network.
+    /// The fragments are sent without pacing if this is [`Duration::ZERO`].
+    pub video_pacing_interval: Duration,
+
+    /// The longest a video frame may wait to be sent (for example behind the fragments of the frames before it on a congested link), before it is dropped by the [`PacedQueue`].
+    /// Dropping the late frames keeps the sent video real-time, the dropped frames are reported with [`ClientEvent::VideoFramesDropped`]. No frame is dropped if this is [`None`].
+    pub video_latency_budget: Option<Duration>,
+
+    /// The configuration of the voice codec, the encoder and the decoders are owned by the [`Client`].
+    pub voice: VoiceConfig,
+
+    /// The configuration of the jitter buffers, which the decoded voice of every author is played out through by [`Client::pull_mixed_audio`].
+    pub jitter_buffer: JitterConfig,
+
+    /// The configuration of the comfort noise, which fills the gaps of the played out voice (for example during the silence of discontinuous transmission) instead of silence.
+    /// Comfort noise is disabled if this is [`None`].
+    pub comfort_noise: Option<ComfortNoiseConfig>,
+
+    /// The endpoints the client fails over to when the remote address stops answering.
+    /// The client stays with its remote address if this is [`None`].
+    pub failover: Option<FailoverConfig>,
+
+    /// The limits of the voice and video decoders of the remote authors, which decide when the decoders are torn down and which new authors are decoded.
+    pub decoders: DecoderLimits,
+
+    /// The linear gain of the sidetone, the voice sent by the client after it was encoded and decoded again, which is mixed into [`Client::pull_mixed_audio`].
+    /// This lets the users verify what they sound like on the wire. The sidetone is disabled if this is [`None`].
+    pub sidetone: Option<f32>,
+
+    /// The configuration of the [`CongestionController`], which adapts the bitrate of the voice to the congestion feedback of the server.
+    /// The datagrams of the client are marked as ECN capable, so that the congested routers mark them instead of dropping them.
+    /// The bitrate is only capped by the room policies and the server if this is [`None`].
+    pub congestion: Option<CongestionConfig>,
+
+    /// The audio codecs the client can encode and decode, which are advertised to the server if any codec besides Opus is registered.
+    /// The voice is encoded with the [`VoiceConfig::codec`] while every peer of the session can decode it.
+    pub codecs: AudioCodecs,
+
+    /// The configuration of the cover traffic, which keeps the rate of the sent messages constant while the voice pauses (for example during silence or while muted).
+    /// Combined with a [`PaddingInterceptor`](super::transport::padding::PaddingInterceptor) under end-to-end encryption, traffic analysis can't tell when the user speaks.
+    /// No cover traffic is sent if this is [`None`].
+    pub traffic_shaping: Option<TrafficShapingConfig>,
+
+    /// The markings of the datagrams, which let the networks honoring them prioritize the voice and keep it on a consistent path (see the [`qos`](super::transport::qos) module).
+    /// The datagrams are sent with the markings of the operating system if this is [`None`].
+    pub qos: Option<QosConfig>,
+
+    /// The hop limit (or TTL) the datagrams are sent with, the hop limit of the operating system is kept if this is [`None`].
+    pub hop_limit: Option<u8>,
+
+    /// Whether the errors the network reports about the sent datagrams (for example an unreachable server) are reported with [`ClientEvent::PathError`], instead of the datagrams being silently lost.
+    /// Only the transports (and the platforms) which can read them report them, see the [`path`](super::transport::path) module.
+    pub path_errors: bool,
+
+    /// The configuration of the [`KeepaliveLearner`], which learns the longest heartbeat interval the NAT of the client tolerates, starting from the [`ClientConfig::heartbeat_interval`] (see the [`keepalive`](super::keepalive) module).
+    /// The learning only progresses while the client is silent, the learned interval is reported with [`ClientEvent::KeepaliveIntervalChanged`].
+    /// The heartbeats are sent at the [`ClientConfig::heartbeat_interval`] if this is [`None`].
+    pub keepalive: Option<KeepaliveConfig>,
+
+    /// The [`PowerMode`] the client starts in, which can be switched with [`Client::set_power_mode`].
+    pub power_mode: PowerMode,
+
+    /// The behavior of the client in [`PowerMode::LowPower`] (see the [`power`](super::power) module).
+    pub low_power: LowPowerConfig,
+
+    /// The [`ResumptionTicket`] of a previous session of the same [`Uuid`] (see [`Client::resumption_ticket`]), which is presented to the server in the first heartbeat.
+    /// If it is still valid, a server issuing tickets restores the session at the address of the client once the client has echoed its [`ControlMessage::Retry`], even from a new address.
+    /// Otherwise the client joins with the usual address validation.
+    pub resumption_ticket: Option<ResumptionTicket>,
+
+    /// The configuration of the duplicate transmission, which sends every voice message twice for the lossy links where the latency of the redundancy can't be afforded (see the [`duplicate`](super::duplicate) module).
+    /// Every voice message is sent once if this is [`None`], the received copies are dropped either way.
+    pub duplication: Option<DuplicationConfig>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            active_speaker: Some(ActiveSpeakerConfig::default()),
+            video_freeze: Some(FreezeConfig::default()),
+            video_pacing_interval: DEFAULT_VIDEO_PACING_INTERVAL,
+            video_latency_budget: None,
+            voice: VoiceConfig::default(),
+            jitter_buffer: JitterConfig::default(),
+            comfort_noise: None,
+            failover: None,
+            decoders: DecoderLimits::default(),
+            sidetone: None,
+            congestion: None,
+            codecs: AudioCodecs::default(),
+            traffic_shaping: None,
+            qos: None,
+            hop_limit: None,
+            path_errors: false,
+            keepalive: None,
+            power_mode: PowerMode::Normal,
+            low_power: LowPowerConfig::default(),
+            resumption_ticket: None,
+            duplication: None,
+        }
+    }
+}
+
+///
+/// Failover configuration type definition.
+///
+/// Describes the prioritized endpoints (servers or relays) of a session, and when the client moves on to the next one.
+/// The endpoints are resolved again on every failover, so DNS changes are picked up without recreating the [`Client`].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailoverConfig {
+    /// The endpoints in the order of their priority, as `host:port` strings (for example `voice.example.com:3004`).
+    /// The first endpoint has to be the remote address the client was created with.
+    pub endpoints: Vec<String>,
+
+    /// The time the remote address may stay silent for, before the client fails over to the next endpoint.
+    /// This should be a multiple of the [`ClientConfig::heartbeat_interval`], as the failover is only evaluated when a heartbeat is sent.
+    pub timeout: Duration,
+
+    /// The interval the current endpoint is resolved again at, even while it answers.
+    /// If its address has changed (for example after a DNS based failover of the server), the client moves to the new address, and the server resumes its session there.
+    /// This should be a multiple of the [`ClientConfig::heartbeat_interval`], as the resolution is only renewed when a heartbeat is sent.
+    /// The endpoint is only resolved again when it stops answering if this is [`None`].
+    pub re_resolve_interval: Option<Duration>,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            timeout: DEFAULT_HEARTBEAT_INTERVAL * 3,
+            re_resolve_interval: None,
+        }
+    }
+}
+
+///
+/// Traffic shaping configuration type definition.
+///
+/// Describes the cover traffic the client sends in place of the voice, so that the messages are sent at a constant rate.
+/// The cover messages are voice messages marked with [`HeaderFlags::PADDING`], which the server counts but never forwards.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficShapingConfig {
+    /// The interval the messages are sent at, this should be the [`VoiceConfig::frame_duration_ms`] of the voice (times the aggregated frames).
+    /// A cover message is sent whenever no other message was sent during an interval.
+    pub interval: Duration,
+
+    /// The size (in bytes) of the body of the cover messages.
+    /// This should be close to the size of the voice messages, or the datagrams should be padded to the same size with a [`PaddingInterceptor`](super::transport::padding::PaddingInterceptor).
+    pub padding_size: usize,
+}
+
+impl Default for TrafficShapingConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(20),
+            padding_size: 160,
+        }
+    }
+}
+
+///
+/// Bind configuration type definition.
+///
+/// Describes the local address (and interface) the [`UdpSocket`] of [`Client::new_with_bind_config`] is bound to.
+/// This lets multi-homed hosts and VPN setups control which interface carries the media.
+///
+#[derive(Debug, Clone)]
+pub struct BindConfig {
+    /// The local address the socket is bound to, its port is ignored if a [`BindConfig::port_range`] is set.
+    pub local_addr: SocketAddr,
+
+    /// The range of the local ports the socket may be bound to, the first free port is used (for example to match the rules of a firewall).
+    /// The port of the [`BindConfig::local_addr`] is used if this is [`None`].
+    pub port_range: Option<RangeInclusive<u16>>,
+
+    /// The name of the network interface the socket is bound to with `SO_BINDTODEVICE` (for example `wg0`), which usually requires the `CAP_NET_RAW` capability.
+    /// The interface is chosen by the routing table if this is [`None`].
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    pub device: Option<String>,
+}
+
+impl Default for BindConfig {
+    fn default() -> Self {
+        Self {
+            local_addr: SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+            port_range: None,
+            #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+            device: None,
+        }
+    }
+}
+
+impl BindConfig {
+    ///
+    /// Binds a [`UdpSocket`] according to the configuration.
+    ///
+    /// # Error
+    /// Returns an error if the socket could not be bound to the local address, every port of the [`BindConfig::port_range`] is taken, or the socket could not be bound to the device.
+    ///
+    pub async fn bind(&self) -> std::io::Result<UdpSocket> {
+        let udp_socket = match &self.port_range {
+            Some(port_range) => {
+                let mut last_err =
+                    std::io::Error::new(ErrorKind::InvalidInput, "The port range is empty.");
+                let mut bound_socket = None;
+
+                for port in port_range.clone() {
+                    match UdpSocket::bind(SocketAddr::new(self.local_addr.ip(), port)).await {
+                        Ok(udp_socket) => {
+                            bound_socket = Some(udp_socket);
+
+                            break;
+                        }
+                        //Try the next port if this one is taken
+                        Err(err) if err.kind() == ErrorKind::AddrInUse => last_err = err,
+                        Err(err) => return Err(err),
+                    }
+                }
+
+                bound_socket.ok_or(last_err)?
+            }
+            None => UdpSocket::bind(self.local_addr).await?,
+        };
+
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if let Some(device) = &self.device {
+            udp_socket.bind_device(Some(device.as_bytes()))?;
+        }
+
+        Ok(udp_socket)
+    }
+}
+
 /// Client struct definition, mnade to simplify the usage of a client.
 #[derive(Debug)]
 pub struct Client {
     /// The unique identificator for this [`Client`] instance.
     uuid: Uuid,
 
-    /// The receiver used to receive messages from the server.
-    inbound_message_receiver: Receiver<(VoipHeader, Vec<u8>)>,
+    /// The receiver used to receive the [`ClientEvent`]s of the client service.
+    event_receiver: Receiver<ClientEvent>,
+
+    /// This local channel sends messages which will be sent to the server.
+    outbound_message_sender: Sender<VoipPacket>,
+
+    /// This local channel sends the fragments of the video frames, which will be paced by the client service.
+    video_sender: Sender<Vec<VoipPacket>>,
+
+    /// This local channel sends the [`CloseReason`] the client service closes the session with.
+    close_sender: Sender<CloseReason>,
+
+    /// This local channel sends the [`PowerMode`]s the client service switches to.
+    power_mode_sender: Sender<PowerMode>,
+
+    /// The [`PowerMode`] the client service was last switched to.
+    power_mode: Mutex<PowerMode>,
+
+    /// The state shared with the client service.
+    state: ClientState,
+
+    /// The audio codecs this client can encode and decode.
+    codecs: AudioCodecs,
+
+    /// The configuration of the voice codec.
+    voice_config: VoiceConfig,
+
+    /// The encoder, and the sequence and timing state of the sent voice stream.
+    voice_encoder: Mutex<VoiceEncoderState>,
+
+    /// The audio streams sent besides the default voice stream, by their stream id.
+    audio_streams: Mutex<HashMap<u8, AudioStream>>,
+
+    /// The pipeline the samples of the default voice stream are processed with, before they are encoded.
+    capture_pipeline: Mutex<AudioPipeline>,
+
+    /// The sound board of the local sounds sent to the other participants, which are mixed into the default voice stream after the capture pipeline.
+    outgoing_sounds: Mutex<SoundBoard>,
+
+    /// The sequence number of the next sent video frame.
+    video_sequence: AtomicU32,
+
+    /// This local channel broadcasts the sequence numbers of the [`ControlMessage::Pong`]s received by the client service to the running diagnostics.
+    pong_sender: broadcast::Sender<u32>,
+
+    /// This local channel broadcasts the voice decoded by the client service to the [`VoiceTap`]s.
+    voice_tap_sender: broadcast::Sender<TappedVoiceFrame>,
+
+    /// The sequence number of the next diagnostic ping.
+    ping_sequence: AtomicU32,
+
+    /// The [`Runtime::sleep`] of the runtime the client service was spawned on, which times the diagnostics.
+    sleep: SleepFn,
+
+    /// The time this [`Client`] was created at, the timestamps of the sent video frames are measured from it.
+    created_at: Instant,
+
+    /// The playout of the voice frames decoded by the client service.
+    playout: Arc<Mutex<Playout>>,
+
+    /// The decoders of the sent voice, which is played out as the sidetone.
+    /// These are created when the sidetone is first played out.
+    sidetone_decoders: Mutex<Option<VoiceDecoders>>,
+
+    /// The receiver of the video frames decoded by the client service.
+    decoded_video_receiver: Receiver<DecodedVideoFrame>,
+
+    /// The local address the transport of the client is bound to.
+    local_addr: SocketAddr,
+}
+
+/// The state shared by the [`Client`] and its client service.
+#[derive(Debug, Clone)]
+struct ClientState {
+    /// The remote address the client exchanges messages with, updated by the client service on failover.
+    remote_addr: Arc<Mutex<SocketAddr>>,
+
+    /// The data used by the session, counted by the client service on every datagram it sends and receives.
+    usage_meter: Arc<UsageMeter>,
+
+    /// The profiler of the audio pipeline, the client service times the received voice, and the [`Playout`] its playout.
+    pipeline_profiler: Arc<PipelineProfiler>,
+
+    /// The latest [`ResumptionTicket`] issued by the server.
+    resumption_ticket: Arc<Mutex<Option<ResumptionTicket>>>,
+
+    /// The device id the server has assigned to this client, if the user of the client is connected from more than one device.
+    device: Arc<Mutex<Option<u32>>>,
+
+    /// The [`CloseReason`] the remote address has closed the session with, set by the client service before it shuts down.
+    remote_close_reason: Arc<Mutex<Option<CloseReason>>>,
+
+    /// The [`RoomPolicy`] of every channel (or room), as advertised by the server.
+    room_policies: Arc<Mutex<HashMap<u32, RoomPolicy>>>,
+
+    /// The highest bitrate (in bits per second) the server forwards the media of this client at, as signaled with a [`ControlMessage::MaxBitrate`].
+    bitrate_cap: Arc<Mutex<Option<u32>>>,
+
+    /// The target bitrate (in bits per second) of the [`CongestionController`], which the client service feeds the feedback of the server.
+    congestion_bitrate: Arc<Mutex<Option<u32>>>,
+
+    /// The voice statistics of every remote speaker, including the speakers whose voice isn't decoded.
+    speaker_stats: Arc<Mutex<HashMap<Uuid, SpeakerStats>>>,
+
+    /// The one-to-one calls which haven't ended yet, the client service answers the signals of the remote peers.
+    calls: Arc<Mutex<CallRegistry>>,
+
+    /// The presence state of every participant which isn't [`PresenceState::Available`], as sent by the server.
+    presences: Arc<Mutex<HashMap<Uuid, PresenceState>>>,
+
+    /// The media state of every participant in every channel (or room) which isn't the default, as sent by the server.
+    media_states: Arc<Mutex<HashMap<(Uuid, u32), MediaState>>>,
+
+    /// The recording state of every recorded channel (or room), as signaled by the server.
+    recordings: Arc<Mutex<HashMap<u32, RecordingState>>>,
+
+    /// The audio codecs every peer of the session can decode, as negotiated by the server.
+    session_codecs: Arc<Mutex<Vec<MediaCodec>>>,
+
+    /// The encoder of the sent video, and the scheduler of its refreshes, if one is set.
+    /// The client service requests a keyframe when it drops late frames.
+    video_encoder: Arc<Mutex<Option<VideoEncoderState>>>,
+
+    /// The decoders of the received video.
+    video_decoders: Arc<Mutex<VideoDecoders>>,
+}
+
+impl ClientState {
+    ///
+    /// Stores the control state the server has sent with the `voip_header`.
+    ///
+    /// # Behavior
+    /// * [`ControlMessage::ResumptionTicket`]: Keeps the latest ticket of the session, so that it can be restored after a crash or a network blip.
+    /// * [`ControlMessage::Device`]: Keeps the device id assigned by the server, so that the sent voice is tagged with it.
+    /// * [`ControlMessage::Codecs`]: Stores the codecs negotiated by the server, the voice is encoded with them from the next frame on.
+    /// * [`ControlMessage::Presence`] and [`ControlMessage::MediaState`]: Tracks the states of the participants, the default states aren't stored.
+    /// * [`ControlMessage::ParticipantLeft`]: Forgets the states of the participant.
+    /// * [`ControlMessage::RecordingState`]: Stores the signaled recordings, the rooms which aren't recorded aren't stored.
+    /// * [`ControlMessage::RoomPolicy`]: Stores the advertised room policies, so that they can be applied when sending.
+    /// * [`ControlMessage::MaxBitrate`]: Stores the bitrate cap of the server, so that it can be applied when sending.
+    ///
+    /// Every other message is ignored.
+    ///
+    fn store_control_message(&self, voip_header: &VoipHeader) {
+        let VoipMessageType::Control(control_message) = voip_header.voip_message_type() else {
+            return;
+        };
+
+        match control_message {
+            ControlMessage::ResumptionTicket(ticket) => {
+                *self.resumption_ticket.lock() = Some(*ticket);
+            }
+            ControlMessage::Device(DeviceNotice::Assigned(assigned_device)) => {
+                *self.device.lock() = Some(*assigned_device);
+            }
+            ControlMessage::Codecs(codecs) => {
+                *self.session_codecs.lock() = codecs.clone();
+            }
+            ControlMessage::Presence(PresenceState::Available) => {
+                self.presences.lock().remove(&voip_header.author());
+            }
+            ControlMessage::Presence(presence) => {
+                self.presences
+                    .lock()
+                    .insert(voip_header.author(), *presence);
+            }
+            ControlMessage::MediaState(media_state) if *media_state == MediaState::default() => {
+                self.media_states
+                    .lock()
+                    .remove(&(voip_header.author(), voip_header.channel()));
+            }
+            ControlMessage::MediaState(media_state) => {
+                self.media_states
+                    .lock()
+                    .insert((voip_header.author(), voip_header.channel()), *media_state);
+            }
+            ControlMessage::ParticipantLeft(author) => {
+                self.presences.lock().remove(author);
+                self.media_states
+                    .lock()
+                    .retain(|(media_author, _), _| media_author != author);
+            }
+            ControlMessage::RecordingState(RecordingState::Stopped) => {
+                self.recordings.lock().remove(&voip_header.channel());
+            }
+            ControlMessage::RecordingState(recording_state) => {
+                self.recordings
+                    .lock()
+                    .insert(voip_header.channel(), *recording_state);
+            }
+            ControlMessage::RoomPolicy(room_policy) => {
+                self.room_policies
+                    .lock()
+                    .insert(voip_header.channel(), room_policy.clone());
+            }
+            ControlMessage::MaxBitrate(max_bitrate) => {
+                *self.bitrate_cap.lock() = *max_bitrate;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The channels the client service exchanges the messages, the events and the requests of the [`Client`] through.
+struct ServiceChannels {
+    /// The sender of the [`ClientEvent`]s.
+    event_sender: Sender<ClientEvent>,
+
+    /// The receiver of the messages sent by the user.
+    outbound_message_receiver: Receiver<VoipPacket>,
+
+    /// The receiver of the fragments of the video frames, which are sent paced.
+    video_receiver: Receiver<Vec<VoipPacket>>,
+
+    /// The receiver of the [`CloseReason`] the session is closed with.
+    close_receiver: Receiver<CloseReason>,
+
+    /// The receiver of the [`PowerMode`]s requested by the user.
+    power_mode_receiver: Receiver<PowerMode>,
+
+    /// The sender of the decoded voice frames, which are played out by the [`Playout`].
+    decoded_frame_sender: Sender<DecodedVoiceFrame>,
+
+    /// The sender of the decoded video frames.
+    decoded_video_sender: Sender<DecodedVideoFrame>,
+
+    /// The sender of the sequence numbers of the received [`ControlMessage::Pong`]s.
+    pong_sender: broadcast::Sender<u32>,
+
+    /// The sender of the decoded voice to the [`VoiceTap`]s.
+    voice_tap_sender: broadcast::Sender<TappedVoiceFrame>,
+}
+
+impl Client {
+    /// Creates a new [`Client`] instance, automaticly sets up the [`UdpSocket`].
+    /// The socket is bound to the local `[::]:0` address.
+    pub async fn new<T: ToSocketAddrs>(
+        uuid: Uuid,
+        remote_addr: T,
+    ) -> std::result::Result<Self, ClientError> {
+        Self::new_with_bind_config(uuid, remote_addr, &BindConfig::default()).await
+    }
+
+    /// Creates a new [`Client`] instance like [`Client::new`], but binds the [`UdpSocket`] according to the [`BindConfig`].
+    pub async fn new_with_bind_config<T: ToSocketAddrs>(
+        uuid: Uuid,
+        remote_addr: T,
+        bind_config: &BindConfig,
+    ) -> std::result::Result<Self, ClientError> {
+        //Bind UdpSocket to local address
+        let socket_handle = establish_connection(remote_addr, bind_config).await?;
+
+        Self::new_from_udp_socket(uuid, socket_handle).await
+    }
+
+    ///
+    /// Creates a new [`Client`] instance which fails over between the `endpoints` (in the order of their priority), automaticly sets up the [`UdpSocket`].
+    /// The socket is bound to the local `[::]:0` address, and isn't connected so that it can exchange messages with any of the endpoints.
+    ///
+    /// # Behavior
+    /// The client starts with the first endpoint which could be resolved, the other settings of the [`FailoverConfig`] are taken from the `config`.
+    ///
+    /// # Error
+    /// Returns an error if the socket could not be bound, or none of the `endpoints` could be resolved.
+    ///
+    pub async fn new_with_endpoints(
+        uuid: Uuid,
+        mut endpoints: Vec<String>,
+        mut config: ClientConfig,
+    ) -> std::result::Result<Self, ClientError> {
+        let socket_handle = BindConfig::default()
+            .bind()
+            .await
+            .map_err(ClientError::SocketIo)?;
+        let local_addr = socket_handle.local_addr().map_err(ClientError::SocketIo)?;
+
+        let (index, remote_addr) =
+            resolve_next_endpoint(&endpoints, endpoints.len().saturating_sub(1), local_addr)
+                .await
+                .ok_or_else(|| {
+                    ClientError::NotConnected(std::io::Error::new(
+                        ErrorKind::NotFound,
+                        "None of the endpoints could be resolved.",
+                    ))
+                })?;
+
+        //The endpoint the client starts with has to be the first one
+        endpoints.rotate_left(index);
+
+        config.failover = Some(FailoverConfig {
+            endpoints,
+            ..config.failover.unwrap_or_default()
+        });
+
+        Self::new_from_transport_with_config::<Tokio, _>(uuid, socket_handle, remote_addr, config)
+            .await
+    }
+
+    /// Creates a new [`Client`] instance from an already existing [`UdpSocket`].
+    /// The [`UdpSocket`] must already be connected to the remote address.
+    pub async fn new_from_udp_socket(
+        uuid: Uuid,
+        socket_handle: UdpSocket,
+    ) -> std::result::Result<Self, ClientError> {
+        let remote_addr = socket_handle
+            .peer_addr()
+            .map_err(ClientError::NotConnected)?;
+
+        Self::new_from_transport(uuid, socket_handle, remote_addr).await
+    }
+
+    /// Creates a new [`Client`] instance from any [`Transport`], which will exchange messages with the `remote_addr`.
+    /// The client service is spawned on the [`Tokio`] runtime.
+    pub async fn new_from_transport<T: Transport>(
+        uuid: Uuid,
+        transport: T,
+        remote_addr: SocketAddr,
+    ) -> std::result::Result<Self, ClientError> {
+        Self::new_from_transport_with_runtime::<Tokio, T>(uuid, transport, remote_addr).await
+    }
+
+    /// Creates a new [`Client`] instance from any [`Transport`], which will exchange messages with the `remote_addr`.
+    /// The client service is spawned on the [`Runtime`] `R`.
+    pub async fn new_from_transport_with_runtime<R: Runtime, T: Transport>(
+        uuid: Uuid,
+        transport: T,
+        remote_addr: SocketAddr,
+    ) -> std::result::Result<Self, ClientError> {
+        Self::new_from_transport_with_config::<R, T>(
+            uuid,
+            transport,
+            remote_addr,
+            ClientConfig::default(),
+        )
+        .await
+    }
+
+    /// Creates a new [`Client`] instance from any [`Transport`], which will exchange messages with the `remote_addr`.
+    /// The client service is spawned on the [`Runtime`] `R`, and behaves according to the [`ClientConfig`].
+    pub async fn new_from_transport_with_config<R: Runtime, T: Transport>(
+        uuid: Uuid,
+        transport: T,
+        remote_addr: SocketAddr,
+        config: ClientConfig,
+    ) -> std::result::Result<Self, ClientError> {
+        let local_addr = transport.local_addr().map_err(ClientError::SocketIo)?;
+
+        //Create I/O channels
+        let (outbound_message_sender, outbound_message_receiver) = channel::<VoipPacket>(255);
+        let (event_sender, event_receiver) = channel::<ClientEvent>(255);
+        let (video_sender, video_receiver) = channel::<Vec<VoipPacket>>(16);
+        let (close_sender, close_receiver) = channel::<CloseReason>(1);
+        let (power_mode_sender, power_mode_receiver) = channel::<PowerMode>(4);
+        let (decoded_frame_sender, decoded_frame_receiver) = channel::<DecodedVoiceFrame>(255);
+        let (decoded_video_sender, decoded_video_receiver) = channel::<DecodedVideoFrame>(16);
+        let (pong_sender, _) = broadcast::channel::<u32>(255);
+        let (voice_tap_sender, _) = broadcast::channel::<TappedVoiceFrame>(VOICE_TAP_CAPACITY);
+        let codecs = config.codecs.clone();
+        let voice_config = config.voice.clone();
+        let pipeline_profiler = Arc::new(PipelineProfiler::new());
+        let mut playout = Playout::new(
+            decoded_frame_receiver,
+            event_sender.clone(),
+            &voice_config,
+            config.jitter_buffer.clone(),
+            config.comfort_noise.clone(),
+            pipeline_profiler.clone(),
+        );
+
+        playout.set_sidetone(config.sidetone);
+
+        let outgoing_sounds =
+            SoundBoard::new(voice_config.sample_rate, voice_config.channels as usize);
+
+        let power_mode = config.power_mode;
+
+        let client_state = ClientState {
+            remote_addr: Arc::new(Mutex::new(remote_addr)),
+            usage_meter: Arc::new(UsageMeter::new()),
+            pipeline_profiler,
+            resumption_ticket: Arc::new(Mutex::new(config.resumption_ticket)),
+            device: Arc::new(Mutex::new(None)),
+            remote_close_reason: Arc::new(Mutex::new(None)),
+            room_policies: Arc::new(Mutex::new(HashMap::new())),
+            bitrate_cap: Arc::new(Mutex::new(None)),
+            congestion_bitrate: Arc::new(Mutex::new(
+                config
+                    .congestion
+                    .as_ref()
+                    .map(|congestion| congestion.max_bitrate),
+            )),
+            speaker_stats: Arc::new(Mutex::new(HashMap::new())),
+            calls: Arc::new(Mutex::new(CallRegistry::default())),
+            presences: Arc::new(Mutex::new(HashMap::new())),
+            media_states: Arc::new(Mutex::new(HashMap::new())),
+            recordings: Arc::new(Mutex::new(HashMap::new())),
+            session_codecs: Arc::new(Mutex::new(vec![MediaCodec::Opus])),
+            video_encoder: Arc::new(Mutex::new(None)),
+            video_decoders: Arc::new(Mutex::new(
+                VideoDecoders::new().with_limits(config.decoders.clone()),
+            )),
+        };
+
+        //Establish client service
+        Self::create_client_service::<R, T>(
+            uuid,
+            config,
+            transport,
+            local_addr,
+            client_state.clone(),
+            ServiceChannels {
+                event_sender,
+                outbound_message_receiver,
+                video_receiver,
+                close_receiver,
+                power_mode_receiver,
+                decoded_frame_sender,
+                decoded_video_sender,
+                pong_sender: pong_sender.clone(),
+                voice_tap_sender: voice_tap_sender.clone(),
+            },
+        );
+
+        Ok(Self {
+            uuid,
+            event_receiver,
+            outbound_message_sender,
+            video_sender,
+            close_sender,
+            power_mode_sender,
+            power_mode: Mutex::new(power_mode),
+            state: client_state,
+            codecs,
+            voice_config,
+            voice_encoder: Mutex::new(VoiceEncoderState::default()),
+            audio_streams: Mutex::new(HashMap::new()),
+            capture_pipeline: Mutex::new(AudioPipeline::new()),
+            outgoing_sounds: Mutex::new(outgoing_sounds),
+            video_sequence: AtomicU32::new(0),
+            pong_sender,
+            voice_tap_sender,
+            ping_sequence: AtomicU32::new(0),
+            sleep: sleep_fn::<R>(),
+            created_at: Instant::now(),
+            playout: Arc::new(Mutex::new(playout)),
+            sidetone_decoders: Mutex::new(None),
+            decoded_video_receiver,
+            local_addr,
+        })
+    }
+
+    /// Returns the [`Uuid`] this [`Client`] instance was created with.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Returns the local address the client is bound to, including the port the operating system has chosen if it was bound to port `0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the remote address (the server or the relay) the client exchanges messages with.
+    /// This changes when the client fails over to another endpoint of its [`FailoverConfig`].
+    pub fn peer_addr(&self) -> SocketAddr {
+        *self.state.remote_addr.lock()
+    }
+
+    /// Writes the message buffer to the [`Client`]'s underlying [`UdpSocket`].
+    pub fn message_sender(&mut self) -> &mut Sender<VoipPacket> {
+        &mut self.outbound_message_sender
+    }
+
+    /// Gets the [`ClientEvent`] receiver handle.
+    /// This is created at the instance creation of [`Client`].
+    /// The client service has ownership of the sender, and sends an event for every incoming message and state change to the receiver.
+    pub fn event_receiver(&mut self) -> &mut Receiver<ClientEvent> {
+        &mut self.event_receiver
+    }
+
+    /// Returns the [`RoomPolicy`] the server has advertised for the `channel` (or room), if there is one.
+    pub fn room_policy(&self, channel: u32) -> Option<RoomPolicy> {
+        self.state.room_policies.lock().get(&channel).cloned()
+    }
+
+    /// Returns the highest bitrate (in bits per second) the server forwards the media of this client at, if it has signaled one with a [`ControlMessage::MaxBitrate`].
+    /// The bitrate of the voice messages is capped to it.
+    pub fn bitrate_cap(&self) -> Option<u32> {
+        *self.state.bitrate_cap.lock()
+    }
+
+    /// Returns the target bitrate (in bits per second) of the [`CongestionController`], if [`ClientConfig::congestion`] is set.
+    /// The bitrate of the voice messages is capped to it.
+    pub fn congestion_bitrate(&self) -> Option<u32> {
+        *self.state.congestion_bitrate.lock()
+    }
+
+    /// Returns the [`SpeakerStats`] of every remote speaker heard, including the speakers whose voice isn't decoded because of [`DecoderLimits::max_speakers`].
+    /// The statistics are only recorded if [`VoiceConfig::decode_received`] is enabled.
+    pub fn speaker_stats(&self) -> HashMap<Uuid, SpeakerStats> {
+        self.state.speaker_stats.lock().clone()
+    }
+
+    fn create_client_service<R: Runtime, T: Transport>(
+        uuid: Uuid,
+        config: ClientConfig,
+        socket_handle: T,
+        local_addr: SocketAddr,
+        client_state: ClientState,
+        service_channels: ServiceChannels,
+    ) {
+        let ServiceChannels {
+            event_sender,
+            mut outbound_message_receiver,
+            mut video_receiver,
+            mut close_receiver,
+            mut power_mode_receiver,
+            decoded_frame_sender,
+            decoded_video_sender,
+            pong_sender,
+            voice_tap_sender,
+        } = service_channels;
+
+        //The remote address changes when the client fails over, or the endpoint moves
+        let mut remote_addr = *client_state.remote_addr.lock();
+
+        //Count the data of the session
+        let socket_handle = Metered::new(socket_handle, client_state.usage_meter.clone());
+
+        R::spawn(async move {
+            //Create buffer for reading incoming messages, the length prefix is not included in the MTU
+            let mut buf = vec![0; MTU_MAX_PACKET_SIZE + LENGTH_PREFIX_SIZE];
+
+            //The last connection state reported, so that every change is only reported once
+            let mut connection_state = None;
+
+            //Mark the datagrams as ECN capable, so that the congested routers mark them instead of dropping them
+            let mut congestion_controller = config.congestion.clone().map(CongestionController::new);
+
+            if congestion_controller.is_some() {
+                if let Err(err) = socket_handle.set_ecn(Ecn::Ect0) {
+                    event!(Level::WARN, "Failed to enable ECN on the transport, the congestion controller only reacts to losses: {err}");
+                }
+            }
+
+            //Mark the datagrams for the networks prioritizing the voice
+            if let Some(qos) = config.qos {
+                if let Some(dscp) = qos.dscp {
+                    if let Err(err) = socket_handle.set_traffic_class(dscp) {
+                        event!(Level::WARN, "Failed to set the traffic class of the transport: {err}");
+                    }
+                }
+
+                if let Some(flow_label) = qos.flow_label {
+                    if let Err(err) = socket_handle.set_flow_label(flow_label) {
+                        event!(Level::WARN, "Failed to set the flow label of the transport: {err}");
+                    }
+                }
+            }
+
+            if let Some(hop_limit) = config.hop_limit {
+                if let Err(err) = socket_handle.set_hop_limit(hop_limit) {
+                    event!(Level::WARN, "Failed to set the hop limit of the transport: {err}");
+                }
+            }
+
+            //Read the errors the network reports about the sent datagrams, so that an unreachable server isn't silently blackholed
+            let path_errors = config.path_errors && match socket_handle.enable_path_errors() {
+                Ok(()) => true,
+                Err(err) => {
+                    event!(Level::WARN, "Failed to enable the path errors of the transport: {err}");
+
+                    false
+                },
+            };
+
+            //The id the server has issued to the session, which lets the session follow the client to a new address
+            let mut connection_id: Option<ConnectionId> = None;
+
+            //The first heartbeat is sent right away, so that the server registers the client (or restores its previous session)
+            if let Err(client_error) = send_control_message(&socket_handle, session_heartbeat(connection_id, &client_state.resumption_ticket), uuid, remote_addr).await {
+                if event_sender.send(ClientEvent::Error(client_error)).await.is_err() {
+                    return;
+                }
+            }
+
+            //The codecs besides Opus are advertised once the server has registered the client, every peer decodes Opus anyway
+            let advertised_codecs: Vec<MediaCodec> = config.codecs.codecs().into_iter().filter(|codec| *codec != MediaCodec::Opus).collect();
+
+            if !advertised_codecs.is_empty() {
+                if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::Codecs(advertised_codecs.clone()), uuid, remote_addr).await {
+                    if event_sender.send(ClientEvent::Error(client_error)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+
+            //The server keeps forwarding the video until it is paused
+            let mut power_mode = config.power_mode;
+
+            if power_mode == PowerMode::LowPower {
+                if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::PauseVideo(true), uuid, remote_addr).await {
+                    if event_sender.send(ClientEvent::Error(client_error)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+
+            //The control messages held back in low power mode, until something else wakes the radio up
+            let mut deferred_messages: Vec<VoipPacket> = Vec::new();
+
+            //The heartbeats are sent at the longest interval the NAT is known to tolerate, if it is being learned
+            let mut keepalive_learner = config.keepalive.map(|keepalive| KeepaliveLearner::new(keepalive, config.heartbeat_interval));
+
+            let mut next_heartbeat = Instant::now() + heartbeat_interval(&config, power_mode, keepalive_learner.as_ref());
+
+            //The time the remote address has last sent a message at, and the index of its endpoint if failover is configured
+            let mut last_received = Instant::now();
+            let mut endpoint_index = 0;
+
+            //The time the current endpoint is resolved again at
+            let mut next_re_resolution = config.failover.as_ref().and_then(|failover| failover.re_resolve_interval).map(|interval| Instant::now() + interval);
+
+            let mut active_speaker_detector = config.active_speaker.map(ActiveSpeakerDetector::new);
+            let mut freeze_detector = config.video_freeze.filter(|_| power_mode == PowerMode::Normal).map(FreezeDetector::new);
+
+            //The time the next cover message is sent at, unless another message is sent before it
+            let mut next_cover_message = config.traffic_shaping.as_ref().filter(|_| power_mode == PowerMode::Normal).map(|traffic_shaping| Instant::now() + traffic_shaping.interval);
+
+            //The video fragments waiting to be sent, and the reassemblers of the received video frames and text messages
+            let mut paced_queue = PacedQueue::new(config.video_latency_budget);
+            let mut next_paced_send = Instant::now();
+            let mut reassembler = Reassembler::new();
+            let mut slice_reassembler = SliceReassembler::new();
+            let mut text_reassembler = Reassembler::new();
+
+            //The decoders of the remote authors, if the received voice is decoded
+            let mut voice_decoders = config
+                .voice
+                .decode_received
+                .then(|| VoiceDecoders::new(&config.voice).with_limits(config.decoders.clone()).with_speaker_stats(client_state.speaker_stats.clone()).with_codecs(config.codecs.clone()));
+
+            //The copies of the sent voice messages waiting for their offset, and the voice messages already received
+            let mut duplicate_queue = config.duplication.map(|duplication| DuplicateQueue::new(duplication.offset));
+            let mut duplicate_filter = DuplicateFilter::default();
+
+            loop {
+                //The dominant speakers have to be re-evaluated when they time out, as silent speakers may not send anything
+                let next_speaker_expiry = active_speaker_detector.as_ref().and_then(|detector| detector.next_expiry());
+
+                //The videos have to be checked for freezes, as their authors don't send anything while they are frozen
+                let next_freeze_expiry = freeze_detector.as_ref().and_then(|detector| detector.next_expiry());
+
+                //The copies of the voice messages are sent once their offset has passed
+                let next_duplicate = duplicate_queue.as_ref().and_then(DuplicateQueue::next_due);
+
+                select! {
+                    //Await incoming messages from the server.
+                    //If received send the matching event through the `event_sender`.
+                    incoming_bytes = socket_handle.recv_datagram(&mut buf) => {
+                        let mut active_speaker_change = None;
+                        let mut freeze_change = None;
+
+                        let client_event = match incoming_bytes {
+                            Ok((byte_count, socket_addr)) => {
+                                //Discard messages which werent sent by the server
+                                if socket_addr != remote_addr {
+                                    continue;
+                                }
+
+                                last_received = Instant::now();
+
+                                //Try deserializing the bytes
+                                match decode_message(&buf[..byte_count]) {
+                                    //The server has left the session
+                                    Ok((voip_header, _)) if *voip_header.voip_message_type() == VoipMessageType::Control(ControlMessage::Goodbye) => {
+                                        if connection_state == Some(ConnectionState::Disconnected) {
+                                            continue;
+                                        }
+
+                                        connection_state = Some(ConnectionState::Disconnected);
+
+                                        ClientEvent::ConnectionStateChanged(ConnectionState::Disconnected)
+                                    },
+                                    //The padding only probes the bandwidth of the path, it carries nothing to report
+                                    Ok((voip_header, _)) if voip_header.flags().contains(HeaderFlags::PADDING) => continue,
+                                    //The copy of a voice message which has already arrived carries nothing new
+                                    Ok((voip_header, _)) if duplicate_filter.is_duplicate(&voip_header) => continue,
+                                    Ok((voip_header, voip_body)) => {
+                                        //The server has closed the session, report it as the final event
+                                        if let VoipMessageType::Control(ControlMessage::Close(close_reason)) = voip_header.voip_message_type() {
+                                            *client_state.remote_close_reason.lock() = Some(close_reason.clone());
+
+                                            let _ = event_sender.send(ClientEvent::Closed(close_reason.clone())).await;
+
+                                            break;
+                                        }
+
+                                        //Report the connection the first time a valid message arrives
+                                        if connection_state != Some(ConnectionState::Connected) {
+                                            connection_state = Some(ConnectionState::Connected);
+
+                                            if event_sender.send(ClientEvent::ConnectionStateChanged(ConnectionState::Connected)).await.is_err() {
+                                                break;
+                                            }
+                                        }
+
+                                        //Echo the retry token of the server, so that it registers this client
+                                        if let VoipMessageType::Control(ControlMessage::Retry(retry_token)) = voip_header.voip_message_type() {
+                                            cancel_keepalive_probe(&mut keepalive_learner);
+
+                                            if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::RetryHeartbeat(*retry_token), uuid, remote_addr).await {
+                                                let _ = event_sender.send(ClientEvent::Error(client_error)).await;
+                                            }
+
+                                            //The advertisement and the video pause were ignored before the client was registered
+                                            if !advertised_codecs.is_empty() {
+                                                if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::Codecs(advertised_codecs.clone()), uuid, remote_addr).await {
+                                                    let _ = event_sender.send(ClientEvent::Error(client_error)).await;
+                                                }
+                                            }
+
+                                            if power_mode == PowerMode::LowPower {
+                                                if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::PauseVideo(true), uuid, remote_addr).await {
+                                                    let _ = event_sender.send(ClientEvent::Error(client_error)).await;
+                                                }
+                                            }
+                                        }
+
+                                        //Store the control state sent by the server (for example the tickets, the room policies and the states of the participants)
+                                        client_state.store_control_message(&voip_header);
+
+                                        //Keep the id of the session, it is presented in every heartbeat from now on
+                                        if let VoipMessageType::Control(ControlMessage::ConnectionId(issued_connection_id)) = voip_header.voip_message_type() {
+                                            connection_id = Some(*issued_connection_id);
+                                        }
+
+                                        //Hand the answers of the diagnostic pings to the running diagnostics, if there are any
+                                        if let VoipMessageType::Control(ControlMessage::Pong(sequence)) = voip_header.voip_message_type() {
+                                            let _ = pong_sender.send(*sequence);
+
+                                            //The binding has survived the silence of the keepalive probe, the next heartbeat starts probing a longer one
+                                            if let Some(interval) = keepalive_learner.as_mut().and_then(|keepalive_learner| keepalive_learner.confirm(*sequence)) {
+                                                next_heartbeat = Instant::now();
+
+                                                if event_sender.send(ClientEvent::KeepaliveIntervalChanged(interval)).await.is_err() {
+                                                    break;
+                                                }
+                                            }
+                                        }
+
+                                        //Drive the state machine of the calls, the invitations are answered right away
+                                        if let VoipMessageType::Control(ControlMessage::Call(call_signal)) = voip_header.voip_message_type() {
+                                            let (call, reply) = client_state.calls.lock().receive(voip_header.author(), *call_signal);
+
+                                            if let Some(reply) = reply {
+                                                cancel_keepalive_probe(&mut keepalive_learner);
+
+                                                if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::Call(reply), uuid, remote_addr).await {
+                                                    let _ = event_sender.send(ClientEvent::Error(client_error)).await;
+                                                }
+                                            }
+
+                                            if let Some(call) = call {
+                                                if event_sender.send(ClientEvent::CallStateChanged(call)).await.is_err() {
+                                                    break;
+                                                }
+                                            }
+
+                                            continue;
+                                        }
+
+                                        //Adapt the bitrate to the congestion feedback of the server, which is sent with the nil author
+                                        if let (Some(congestion_controller), VoipMessageType::Control(ControlMessage::QualityReport(quality_report))) = (congestion_controller.as_mut(), voip_header.voip_message_type()) {
+                                            if voip_header.author().is_nil() {
+                                                if let Some(bitrate) = congestion_controller.on_feedback(quality_report) {
+                                                    *client_state.congestion_bitrate.lock() = Some(bitrate);
+                                                }
+                                            }
+                                        }
+
+                                        //Track the loudness of the speakers
+                                        if let (Some(detector), VoipMessageType::VoiceMessage(_), Some(audio_level)) = (active_speaker_detector.as_mut(), voip_header.voip_message_type(), voip_header.audio_level()) {
+                                            active_speaker_change = detector.observe(voip_header.channel(), voip_header.author(), audio_level, Instant::now());
+                                        }
+
+                                        //Decode the received voice (concealing the lost frames), the frames are dropped if the user doesn't keep up reading them
+                                        if let Some(voice_decoders) = voice_decoders.as_mut() {
+                                            match voip_header.voip_message_type() {
+                                                VoipMessageType::VoiceMessage(_) if voice_decoders.is_registered(voip_header.codec()) => {
+                                                    //Time the handling of the message until now, and its decoding
+                                                    let decode_start = Instant::now();
+
+                                                    client_state.pipeline_profiler.record(PipelineStage::Receive, decode_start.saturating_duration_since(last_received));
+
+                                                    let decoded_frames = voice_decoders.decode_message(&voip_header, &voip_body);
+
+                                                    client_state.pipeline_profiler.record_since(PipelineStage::Decode, decode_start);
+
+                                                    match decoded_frames {
+                                                        Ok(decoded_frames) => {
+                                                            for decoded_frame in decoded_frames {
+                                                                //Tap the decoded voice, the frames are only copied while someone is listening
+                                                                if voice_tap_sender.receiver_count() > 0 {
+                                                                    let _ = voice_tap_sender.send(TappedVoiceFrame { decoded_at: SystemTime::now(), frame: Arc::new(decoded_frame.clone()) });
+                                                                }
+
+                                                                let _ = decoded_frame_sender.try_send(decoded_frame);
+                                                            }
+                                                        },
+                                                        Err(err) => event!(Level::ERROR, "Failed to decode a voice message: {err}"),
+                                                    }
+                                                },
+                                                VoipMessageType::Control(ControlMessage::ParticipantLeft(author)) => voice_decoders.remove_author(*author),
+                                                _ => (),
+                                            }
+                                        }
+
+                                        //Forget the received voice of the authors who have left
+                                        if let VoipMessageType::Control(ControlMessage::ParticipantLeft(author)) = voip_header.voip_message_type() {
+                                            duplicate_filter.remove_author(*author);
+                                        }
+
+                                        //Reassemble the fragmented video frames and text messages
+                                        let voip_body = match voip_header.voip_message_type() {
+                                            VoipMessageType::VideoMessage(_) => match reassembler.push(&voip_header, voip_body) {
+                                                Some(frame) => frame,
+                                                None => continue,
+                                            },
+                                            VoipMessageType::TextMessage(_) => match text_reassembler.push(&voip_header, voip_body) {
+                                                Some(text) => text,
+                                                None => continue,
+                                            },
+                                            _ => voip_body,
+                                        };
+
+                                        //Collect the slices of the video frames encoded in slices, a frame missing slices is finished by the next frame
+                                        let mut video_frames = match voip_header.voip_message_type() {
+                                            VoipMessageType::VideoMessage(_) => slice_reassembler.push(&voip_header, voip_body.clone()),
+                                            _ => vec![],
+                                        };
+
+                                        //Decode the reassembled video frames, the frames are dropped if the user doesn't keep up reading them
+                                        match voip_header.voip_message_type() {
+                                            VoipMessageType::VideoMessage(_) => {
+                                                let mut video_decoders = client_state.video_decoders.lock();
+
+                                                if video_decoders.is_registered(voip_header.codec()) {
+                                                    for video_frame in &video_frames {
+                                                        match video_decoders.decode(video_frame) {
+                                                            Ok(Some(decoded_video_frame)) => {
+                                                                freeze_change = freeze_detector.as_mut().and_then(|detector| detector.observe(&decoded_video_frame, Instant::now())).or(freeze_change);
+
+                                                                let _ = decoded_video_sender.try_send(decoded_video_frame);
+                                                            },
+                                                            Ok(None) => (),
+                                                            Err(err) => event!(Level::ERROR, "Failed to decode a video message: {err}"),
+                                                        }
+                                                    }
+                                                }
+                                            },
+                                            VoipMessageType::Control(ControlMessage::ParticipantLeft(author)) => {
+                                                client_state.video_decoders.lock().remove_author(*author);
+                                                slice_reassembler.remove_author(*author);
+
+                                                if let Some(detector) = freeze_detector.as_mut() {
+                                                    detector.remove_author(*author);
+                                                }
+                                            },
+                                            _ => (),
+                                        }
+
+                                        //Report the finished video frames, every frame but the last one is reported right away
+                                        if matches!(voip_header.voip_message_type(), VoipMessageType::VideoMessage(_)) {
+                                            let Some(video_frame) = video_frames.pop() else {
+                                                continue;
+                                            };
+
+                                            for video_frame in video_frames {
+                                                if event_sender.send(ClientEvent::VideoFrame(video_frame)).await.is_err() {
+                                                    return;
+                                                }
+                                            }
+
+                                            ClientEvent::VideoFrame(video_frame)
+                                        } else {
+                                            //Skip the messages which dont have to be reported (for example heartbeats)
+                                            match ClientEvent::from_message(voip_header, voip_body) {
+                                                //The duration of the Opus frames can be read from the packet itself
+                                                Some(ClientEvent::VoiceFrame(mut voice_frame)) => {
+                                                    if voice_frame.codec == MediaCodec::Opus {
+                                                        voice_frame.duration = opus::packet::get_nb_samples(&voice_frame.payload, config.voice.sample_rate).ok().map(|sample_count| Duration::from_secs_f64(sample_count as f64 / config.voice.sample_rate as f64));
+                                                    }
+
+                                                    ClientEvent::VoiceFrame(voice_frame)
+                                                },
+                                                Some(client_event) => client_event,
+                                                None => continue,
+                                            }
+                                        }
+                                    },
+                                    Err(err) => ClientEvent::Error(err.into()),
+                                }
+                            },
+                            Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+                                //The remote address is unreachable, only report it if it was reachable before
+                                if connection_state == Some(ConnectionState::Disconnected) {
+                                    continue;
+                                }
+
+                                connection_state = Some(ConnectionState::Disconnected);
+
+                                ClientEvent::ConnectionStateChanged(ConnectionState::Disconnected)
+                            },
+                            //The error is reported again as a path error from the error queue
+                            Err(err) if path_errors && path::is_path_error(&err) => continue,
+                            Err(err) => ClientEvent::Error(ClientError::Receive(err)),
+                        };
+
+                        //Send the event through the channel, if the receiver was dropped the client was shut down
+                        if event_sender.send(client_event).await.is_err() {
+                            break;
+                        }
+
+                        //Report the change of the dominant speaker after the voice frame which caused it
+                        if let Some(active_speaker_change) = active_speaker_change {
+                            if event_sender.send(ClientEvent::ActiveSpeakerChanged(active_speaker_change)).await.is_err() {
+                                break;
+                            }
+                        }
+
+                        //Report the recovery of a frozen video after the video frame which caused it
+                        if let Some(freeze_change) = freeze_change {
+                            if event_sender.send(ClientEvent::VideoFreezeChanged(freeze_change)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+
+                    //Report the errors the network has reported about the sent datagrams
+                    path_error = socket_handle.recv_path_error(), if path_errors => {
+                        let client_event = match path_error {
+                            Ok(path_error) => ClientEvent::PathError(path_error),
+                            Err(err) => ClientEvent::Error(ClientError::Receive(err)),
+                        };
+
+                        if event_sender.send(client_event).await.is_err() {
+                            break;
+                        }
+                    }
+
+                    //Await outgoing message requests from the user.
+                    //If the channel receives a [`VoipPacket`] this function will send it to the connected [`SocketAddr`].
+                    Some(outgoing_message) = outbound_message_receiver.recv() => {
+                        if power_mode == PowerMode::LowPower {
+                            //No video is sent in low power mode
+                            if power::is_video(outgoing_message.inner()) {
+                                continue;
+                            }
+
+                            //Hold back the messages which can wait, so that they don't wake the radio up on their own
+                            if deferred_messages.len() < config.low_power.max_deferred_messages && power::is_deferrable(outgoing_message.inner()) {
+                                deferred_messages.push(outgoing_message);
+
+                                continue;
+                            }
+                        }
+
+                        //The sent message refreshes the NAT binding, so the silence of the keepalive probe is broken
+                        cancel_keepalive_probe(&mut keepalive_learner);
+
+                        //The radio is awake anyway, so the held back messages are sent along
+                        if send_deferred_messages(&socket_handle, &mut deferred_messages, remote_addr, &event_sender).await.is_err() {
+                            break;
+                        }
+
+                        //Send the VoipPacket to the remote address
+                        if let Err(err) = socket_handle.send_datagram(outgoing_message.inner(), remote_addr).await {
+                            if event_sender.send(ClientEvent::Error(ClientError::Send(err))).await.is_err() {
+                                break;
+                            }
+                        }
+
+                        //Queue the copy of the voice message, which is sent after the offset
+                        if let Some(duplicate_queue) = duplicate_queue.as_mut() {
+                            duplicate_queue.push(&outgoing_message, Instant::now());
+                        }
+
+                        //The sent message takes the place of the cover message
+                        if let Some(traffic_shaping) = config.traffic_shaping.as_ref() {
+                            next_cover_message = Some(Instant::now() + traffic_shaping.interval);
+                        }
+                    }
+
+                    //Send a cover message if nothing was sent during the interval, so that the pauses of the voice don't show
+                    _ = R::sleep(next_cover_message.unwrap_or(next_heartbeat).saturating_duration_since(Instant::now())), if next_cover_message.is_some() => {
+                        let Some(traffic_shaping) = config.traffic_shaping.as_ref() else {
+                            continue;
+                        };
+
+                        next_cover_message = Some(Instant::now() + traffic_shaping.interval);
+
+                        cancel_keepalive_probe(&mut keepalive_learner);
+
+                        let cover_message = VoipHeader::new(VoipMessageType::VoiceMessage(traffic_shaping.padding_size as u64), uuid)
+                            .with_flags(HeaderFlags::default() | HeaderFlags::PADDING)
+                            .create_message_buffer(&vec![0; traffic_shaping.padding_size]);
+
+                        let client_error = match cover_message {
+                            Ok(cover_message) => match socket_handle.send_datagram(cover_message.inner(), remote_addr).await {
+                                Ok(_) => continue,
+                                Err(err) => ClientError::Send(err),
+                            },
+                            Err(err) => ClientError::Encode(err),
+                        };
+
+                        if event_sender.send(ClientEvent::Error(client_error)).await.is_err() {
+                            break;
+                        }
+                    }
+
+                    //Await video frames from the user, their fragments are sent paced
+                    Some(fragments) = video_receiver.recv() => {
+                        //No video is sent in low power mode
+                        if power_mode == PowerMode::LowPower {
+                            continue;
+                        }
+
+                        paced_queue.push(fragments, Instant::now());
+                    }
+
+                    //Send the next video fragment when the pacing interval has elapsed
+                    _ = R::sleep(next_paced_send.saturating_duration_since(Instant::now())), if !paced_queue.is_empty() => {
+                        next_paced_send = Instant::now() + config.video_pacing_interval;
+
+                        let (outgoing_message, dropped_frames) = paced_queue.pop(Instant::now());
+
+                        //The frames sent after the dropped ones reference them, so the picture is refreshed with a keyframe
+                        if dropped_frames > 0 {
+                            if let Some(video_encoder) = client_state.video_encoder.lock().as_mut() {
+                                video_encoder.scheduler.request_keyframe();
+                            }
+
+                            if event_sender.send(ClientEvent::VideoFramesDropped(dropped_frames)).await.is_err() {
+                                break;
+                            }
+                        }
+
+                        if let Some(outgoing_message) = outgoing_message {
+                            cancel_keepalive_probe(&mut keepalive_learner);
+
+                            if let Err(err) = socket_handle.send_datagram(outgoing_message.inner(), remote_addr).await {
+                                if event_sender.send(ClientEvent::Error(ClientError::Send(err))).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+
+                    //Switch to the power mode requested by the user
+                    Some(requested_power_mode) = power_mode_receiver.recv() => {
+                        if requested_power_mode == power_mode {
+                            continue;
+                        }
+
+                        power_mode = requested_power_mode;
+
+                        match power_mode {
+                            PowerMode::LowPower => {
+                                //The queued video is dropped, and the videos of the others stop arriving so they aren't watched for freezes
+                                paced_queue.drain();
+
+                                freeze_detector = None;
+                                next_cover_message = None;
+                            },
+                            PowerMode::Normal => {
+                                cancel_keepalive_probe(&mut keepalive_learner);
+
+                                if send_deferred_messages(&socket_handle, &mut deferred_messages, remote_addr, &event_sender).await.is_err() {
+                                    break;
+                                }
+
+                                //The receivers have missed the frames sent in low power mode, so the video resumes with a keyframe
+                                if let Some(video_encoder) = client_state.video_encoder.lock().as_mut() {
+                                    video_encoder.scheduler.request_keyframe();
+                                }
+
+                                freeze_detector = config.video_freeze.map(FreezeDetector::new);
+                                next_cover_message = config.traffic_shaping.as_ref().map(|traffic_shaping| Instant::now() + traffic_shaping.interval);
+                                next_heartbeat = next_heartbeat.min(Instant::now() + heartbeat_interval(&config, power_mode, keepalive_learner.as_ref()));
+                            },
+                        }
+
+                        if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::PauseVideo(power_mode == PowerMode::LowPower), uuid, remote_addr).await {
+                            if event_sender.send(ClientEvent::Error(client_error)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+
+                    //Await the closure of the session requested by the user
+                    Some(close_reason) = close_receiver.recv() => {
+                        //Flush the messages which were queued before the closure
+                        while let Ok(fragments) = video_receiver.try_recv() {
+                            paced_queue.push(fragments, Instant::now());
+                        }
+
+                        let queued_messages: Vec<VoipPacket> = deferred_messages.drain(..).chain(std::iter::from_fn(|| outbound_message_receiver.try_recv().ok())).chain(paced_queue.drain()).collect();
+
+                        for outgoing_message in queued_messages {
+                            if let Err(err) = socket_handle.send_datagram(outgoing_message.inner(), remote_addr).await {
+                                let _ = event_sender.send(ClientEvent::Error(ClientError::Send(err))).await;
+                            }
+                        }
+
+                        //Notify the remote address, then report the closure as the final event
+                        if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::Close(close_reason.clone()), uuid, remote_addr).await {
+                            let _ = event_sender.send(ClientEvent::Error(client_error)).await;
+                        }
+
+                        let _ = event_sender.send(ClientEvent::Closed(close_reason)).await;
+
+                        break;
+                    }
+
+                    //Send the copies of the voice messages whose offset has passed
+                    _ = R::sleep(next_duplicate.unwrap_or(next_heartbeat).saturating_duration_since(Instant::now())), if next_duplicate.is_some() => {
+                        let Some(copy) = duplicate_queue.as_mut().and_then(|duplicate_queue| duplicate_queue.pop_due(Instant::now())) else {
+                            continue;
+                        };
+
+                        let copy_addr = config.duplication.and_then(|duplication| duplication.copy_addr).unwrap_or(remote_addr);
+
+                        if let Err(err) = socket_handle.send_datagram(copy.inner(), copy_addr).await {
+                            if event_sender.send(ClientEvent::Error(ClientError::Send(err))).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+
+                    //Re-evaluate the dominant speakers when one of them times out
+                    _ = R::sleep(next_speaker_expiry.unwrap_or(next_heartbeat).saturating_duration_since(Instant::now())), if next_speaker_expiry.is_some() => {
+                        let active_speaker_changes = active_speaker_detector.as_mut().map(|detector| detector.expire(Instant::now())).unwrap_or_default();
+
+                        for active_speaker_change in active_speaker_changes {
+                            if event_sender.send(ClientEvent::ActiveSpeakerChanged(active_speaker_change)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+
+                    //Report the videos which have frozen
+                    _ = R::sleep(next_freeze_expiry.unwrap_or(next_heartbeat).saturating_duration_since(Instant::now())), if next_freeze_expiry.is_some() => {
+                        let freeze_changes = freeze_detector.as_mut().map(|detector| detector.expire(Instant::now())).unwrap_or_default();
+
+                        for freeze_change in freeze_changes {
+                            if event_sender.send(ClientEvent::VideoFreezeChanged(freeze_change)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+
+                    //Send a heartbeat to the remote address periodically, the heartbeats are held back while a keepalive probe is pending
+                    _ = R::sleep(keepalive_learner.as_ref().and_then(KeepaliveLearner::probe_deadline).unwrap_or(next_heartbeat).saturating_duration_since(Instant::now())) => {
+                        //The binding hasn't survived the silence of the unanswered keepalive probe, the heartbeat below binds the client again
+                        if let Some(keepalive_learner) = keepalive_learner.as_mut() {
+                            if keepalive_learner.expire(Instant::now()) {
+                                event!(Level::DEBUG, "The NAT binding has expired during a keepalive probe, keeping the heartbeat interval at {:?}.", keepalive_learner.interval());
+
+                                //The remote address couldn't answer during the probe, which doesn't count towards the failover
+                                last_received = Instant::now();
+                            }
+                        }
+
+                        next_heartbeat = Instant::now() + heartbeat_interval(&config, power_mode, keepalive_learner.as_ref());
+
+                        let previous_addr = remote_addr;
+
+                        //Tear down the decoders of the authors who have stopped sending
+                        if let Some(voice_decoders) = voice_decoders.as_mut() {
+                            voice_decoders.remove_idle(Instant::now());
+                        }
+
+                        client_state.video_decoders.lock().remove_idle(Instant::now());
+
+                        //Fail over to the next endpoint which can be resolved, if the remote address hasn't answered in time
+                        if let Some(failover) = config.failover.as_ref().filter(|failover| last_received.elapsed() >= failover.timeout) {
+                            last_received = Instant::now();
+
+                            if let Some((index, failover_addr)) = resolve_next_endpoint(&failover.endpoints, endpoint_index, local_addr).await {
+                                endpoint_index = index;
+
+                                if failover_addr != remote_addr {
+                                    event!(Level::WARN, "The remote address {remote_addr} has stopped answering, failing over to {failover_addr}.");
+
+                                    remote_addr = failover_addr;
+                                    *client_state.remote_addr.lock() = failover_addr;
+
+                                    if event_sender.send(ClientEvent::RemoteAddrChanged(failover_addr)).await.is_err() {
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+
+                        //Move to the new address of the current endpoint if its record has changed, the heartbeat below resumes the session there
+                        if let (Some(failover), true) = (config.failover.as_ref(), next_re_resolution.is_some_and(|re_resolution| Instant::now() >= re_resolution)) {
+                            next_re_resolution = failover.re_resolve_interval.map(|interval| Instant::now() + interval);
+
+                            match re_resolve(&failover.endpoints[endpoint_index], remote_addr, local_addr).await {
+                                Ok(resolved_addr) => {
+                                    if resolved_addr != remote_addr {
+                                        event!(Level::INFO, "The endpoint {} has moved from {remote_addr} to {resolved_addr}.", failover.endpoints[endpoint_index]);
+
+                                        remote_addr = resolved_addr;
+                                        *client_state.remote_addr.lock() = resolved_addr;
+
+                                        if event_sender.send(ClientEvent::RemoteAddrChanged(resolved_addr)).await.is_err() {
+                                            break;
+                                        }
+                                    }
+                                },
+                                Err(err) => event!(Level::WARN, "Failed to resolve the endpoint {} again: {err}", failover.endpoints[endpoint_index]),
+                            }
+                        }
+
+                        //The server follows the session to a new address (of the client or of the server) by its connection id, or by its ticket until it has issued an id
+                        if let Err(client_error) = send_control_message(&socket_handle, session_heartbeat(connection_id, &client_state.resumption_ticket), uuid, remote_addr).await {
+                            if event_sender.send(ClientEvent::Error(client_error)).await.is_err() {
+                                break;
+                            }
+                        }
+
+                        //The held back messages are sent along with the heartbeat, while the radio is awake
+                        if send_deferred_messages(&socket_handle, &mut deferred_messages, remote_addr, &event_sender).await.is_err() {
+                            break;
+                        }
+
+                        //The new remote address may not know the codecs of this client yet
+                        if remote_addr != previous_addr && !advertised_codecs.is_empty() {
+                            if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::Codecs(advertised_codecs.clone()), uuid, remote_addr).await {
+                                if event_sender.send(ClientEvent::Error(client_error)).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+
+                        //Nor whether this client has paused its video
+                        if remote_addr != previous_addr && power_mode == PowerMode::LowPower {
+                            if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::PauseVideo(true), uuid, remote_addr).await {
+                                if event_sender.send(ClientEvent::Error(client_error)).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+
+                        //Probe a longer silence right after the heartbeat, while the binding is fresh
+                        if let Some((sequence, interval)) = keepalive_learner.as_mut().and_then(|keepalive_learner| keepalive_learner.start_probe(Instant::now())) {
+                            let delay_ms = interval.as_millis().min(u32::MAX as u128) as u32;
+
+                            if let Err(client_error) = send_control_message(&socket_handle, ControlMessage::KeepaliveProbe(sequence, delay_ms), uuid, remote_addr).await {
+                                if event_sender.send(ClientEvent::Error(client_error)).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            //The session has ended, its duration stops growing
+            client_state.usage_meter.end();
+        });
+    }
+
+    /// Returns the configuration of the voice codec.
+    pub fn voice_config(&self) -> &VoiceConfig {
+        &self.voice_config
+    }
+
+    /// Automaticly fetches the samples from the buffer, and sends them to the remote address like [`Client::push_samples`].
+    /// The samples are assumed to have been captured right before this call.
+    pub async fn send_voice_packet(
+        &self,
+        buffer: Arc<Mutex<VecDeque<f32>>>,
+    ) -> std::result::Result<(), ClientError> {
+        let samples: Vec<f32> = buffer.lock().drain(..).collect();
+        let now = Instant::now();
+        let capture_instant = now
+            .checked_sub(self.voice_config.duration_of(samples.len()))
+            .unwrap_or(now);
+
+        self.push_samples(&samples, capture_instant).await
+    }
+
+    /// Encodes the interleaved `samples` like [`Client::push_samples`], then flushes the remaining samples like [`Client::flush_samples`].
+    /// The samples are assumed to have been captured right before this call.
+    pub async fn send_samples(&self, samples: &[f32]) -> std::result::Result<(), ClientError> {
+        let now = Instant::now();
+        let capture_instant = now
+            .checked_sub(self.voice_config.duration_of(samples.len()))
+            .unwrap_or(now);
+
+        self.encode_samples(0, samples, capture_instant, true).await
+    }
+
+    ///
+    /// Queues the interleaved `samples` captured at `capture_instant`, and sends every complete frame to the remote address.
+    ///
+    /// # Behavior
+    /// The samples have to match the sample rate and the channels of the [`VoiceConfig`].
+    /// The samples are split into frames of exactly [`VoiceConfig::frame_duration_ms`], the samples which dont fill a whole frame are carried over to the next call.
+    /// The frames are timestamped with their capture time, measured from the capture time of the first samples of the stream.
+    /// The `capture_instant` is only used if there are no samples carried over, so a continuous stream keeps continuous timestamps.
+    /// The encoder is kept between the calls, so the Opus stream stays continuous.
+    /// If [`VoiceConfig::frames_per_packet`] is set, the frames are sent once enough of them are aggregated into a single message.
+    /// The [`RoomPolicy`] of the channel the voice messages are sent on is applied (see [`Client::room_policy`]).
+    ///
+    /// # Error
+    /// Returns an error if the [`VoiceConfig`] is invalid, the encoder could not be created, or the samples could not be encoded.
+    ///
+    pub async fn push_samples(
+        &self,
+        samples: &[f32],
+        capture_instant: Instant,
+    ) -> std::result::Result<(), ClientError> {
+        self.encode_samples(0, samples, capture_instant, false).await
+    }
+
+    /// Sends the samples carried over by [`Client::push_samples`] as a final frame, padded with silence.
+    /// This should be called when the capture stops, so the end of the speech isn't cut off.
+    pub async fn flush_samples(&self) -> std::result::Result<(), ClientError> {
+        self.encode_samples(0, &[], Instant::now(), true).await
+    }
+
+    ///
+    /// Adds an audio stream sent besides the default voice stream (for example the audio of a shared screen next to the microphone), encoded with its own [`VoiceConfig`].
+    ///
+    /// # Behavior
+    /// Every voice message of the stream is tagged with the `stream` id, so the receivers decode and buffer it separately, and mix it with the other streams of this client.
+    /// The samples of the stream are sent with [`Client::push_stream_samples`] and [`Client::flush_stream_samples`].
+    /// Adding a stream which already exists replaces its codec settings, and restarts its encoder.
+    ///
+    /// # Error
+    /// Returns an error if the `stream` is the default stream `0`, or the [`VoiceConfig`] is invalid.
+    ///
+    pub fn add_audio_stream(
+        &self,
+        stream: u8,
+        voice_config: VoiceConfig,
+    ) -> std::result::Result<(), ClientError> {
+        if stream == 0 {
+            return Err(ClientError::DefaultAudioStream);
+        }
+
+        voice_config.validate()?;
+
+        self.audio_streams.lock().insert(
+            stream,
+            AudioStream {
+                config: voice_config,
+                encoder: VoiceEncoderState::default(),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Removes an audio stream added with [`Client::add_audio_stream`], the samples carried over are dropped.
+    pub fn remove_audio_stream(&self, stream: u8) {
+        self.audio_streams.lock().remove(&stream);
+    }
+
+    /// Queues the interleaved `samples` of the audio `stream` like [`Client::push_samples`], the stream `0` being the default voice stream.
+    /// The samples have to match the sample rate and the channels of the [`VoiceConfig`] of the stream.
+    pub async fn push_stream_samples(
+        &self,
+        stream: u8,
+        samples: &[f32],
+        capture_instant: Instant,
+    ) -> std::result::Result<(), ClientError> {
+        self.encode_samples(stream, samples, capture_instant, false)
+            .await
+    }
+
+    /// Sends the samples of the audio `stream` carried over as a final frame, like [`Client::flush_samples`].
+    pub async fn flush_stream_samples(&self, stream: u8) -> std::result::Result<(), ClientError> {
+        self.encode_samples(stream, &[], Instant::now(), true).await
+    }
+
+    ///
+    /// Ends the audio `stream` (the stream `0` being the default voice stream), so the receivers release its state right away instead of waiting for it to time out.
+    ///
+    /// # Behavior
+    /// The samples carried over are flushed first (see [`Client::flush_stream_samples`]), then an empty voice message marked with [`HeaderFlags::END_OF_STREAM`] is sent.
+    /// The receivers play out the rest of the stream without waiting for their target delay, then release its decoder and its jitter buffer, and report it with [`ClientEvent::StreamEnded`].
+    /// The encoder of the stream is restarted, so the samples sent after this call start a new stream, the sequence numbers continue where they have stopped.
+    ///
+    /// # Error
+    /// Returns an error if the `stream` doesn't exist, the remaining samples could not be encoded, or the message could not be sent.
+    ///
+    pub async fn end_stream(&self, stream: u8) -> std::result::Result<(), ClientError> {
+        self.flush_stream_samples(stream).await?;
+
+        let codec = self.send_codec(self.audio_stream_config(stream)?.codec).0;
+
+        let sequence = {
+            let mut voice_encoder = self.lock_audio_stream(stream)?;
+            let sequence = voice_encoder.sequence;
+
+            *voice_encoder = VoiceEncoderState {
+                sequence: sequence.wrapping_add(1),
+                ..Default::default()
+            };
+
+            sequence
+        };
+
+        let voip_packet = VoipHeader::new(VoipMessageType::VoiceMessage(0), self.uuid)
+            .with_codec(codec)
+            .with_flags(HeaderFlags::default() | HeaderFlags::END_OF_STREAM)
+            .with_stream((stream != 0).then_some(stream))
+            .with_device(self.device())
+            .with_sequence(sequence)
+            .create_message_buffer(&[])?;
+
+        self.outbound_message_sender
+            .send(voip_packet)
+            .await
+            .map_err(|_| self.service_stopped())?;
+
+        Ok(())
+    }
+
+    /// Returns the [`VoiceConfig`] of the audio `stream`.
+    fn audio_stream_config(&self, stream: u8) -> std::result::Result<VoiceConfig, ClientError> {
+        if stream == 0 {
+            return Ok(self.voice_config.clone());
+        }
+
+        self.audio_streams
+            .lock()
+            .get(&stream)
+            .map(|audio_stream| audio_stream.config.clone())
+            .ok_or(ClientError::UnknownAudioStream(stream))
+    }
+
+    /// Locks the encoder state of the audio `stream`.
+    fn lock_audio_stream(
+        &self,
+        stream: u8,
+    ) -> std::result::Result<MappedMutexGuard<'_, VoiceEncoderState>, ClientError> {
+        if stream == 0 {
+            return Ok(MutexGuard::map(self.voice_encoder.lock(), |voice_encoder| {
+                voice_encoder
+            }));
+        }
+
+        MutexGuard::try_map(self.audio_streams.lock(), |audio_streams| {
+            audio_streams
+                .get_mut(&stream)
+                .map(|audio_stream| &mut audio_stream.encoder)
+        })
+        .map_err(|_| ClientError::UnknownAudioStream(stream))
+    }
+
+    /// Returns the codec the voice is sent with, which is the configured `codec` if every peer of the session can decode it, [`MediaCodec::Opus`] otherwise.
+    fn send_codec(&self, codec: MediaCodec) -> (MediaCodec, Arc<dyn AudioCodec>) {
+        let is_negotiated = self.state.session_codecs.lock().contains(&codec);
+
+        match self.codecs.get(codec).filter(|_| is_negotiated) {
+            Some(audio_codec) => (codec, audio_codec.clone()),
+            None => (
+                MediaCodec::Opus,
+                self.codecs
+                    .get(MediaCodec::Opus)
+                    .cloned()
+                    .unwrap_or_else(|| Arc::new(Opus)),
+            ),
+        }
+    }
+
+    /// Returns the audio codecs every peer of the session can decode, as negotiated by the server.
+    /// This is only [`MediaCodec::Opus`] until a peer of the session advertises other codecs (see [`ClientConfig::codecs`]).
+    pub fn session_codecs(&self) -> Vec<MediaCodec> {
+        self.state.session_codecs.lock().clone()
+    }
+
+    /// Encodes the `samples` of the audio `stream` into frames, and sends them to the remote address.
+    /// If `flush` is set, the remaining samples are padded with silence and sent as a final frame.
+    async fn encode_samples(
+        &self,
+        stream: u8,
+        samples: &[f32],
+        capture_instant: Instant,
+        flush: bool,
+    ) -> std::result::Result<(), ClientError> {
+        let voice_config = self.audio_stream_config(stream)?;
+
+        voice_config.validate()?;
+
+        let mut sample_buf = samples.to_vec();
+        let capture_start = Instant::now();
+
+        //Watch the capture callbacks of the microphone for overruns
+        if stream == 0 && !sample_buf.is_empty() {
+            self.state
+                .pipeline_profiler
+                .observe_capture(capture_start, voice_config.duration_of(sample_buf.len()));
+        }
+
+        //Run the captured voice through the custom processing of the user
+        if stream == 0 {
+            self.capture_pipeline.lock().process(&mut sample_buf);
+
+            //Mix in the sounds played to the other participants, so they aren't altered by the processing of the microphone
+            self.outgoing_sounds.lock().mix_into(&mut sample_buf);
+        }
+
+        //The voice messages are sent on the default channel
+        let room_policy = self.room_policy(0).unwrap_or_default();
+
+        //Normalize the loudness of the samples
+        if let Some(target_loudness) = room_policy.target_loudness {
+            normalize_loudness(&mut sample_buf, target_loudness);
+        }
+
+        if !sample_buf.is_empty() {
+            self.state
+                .pipeline_profiler
+                .record_since(PipelineStage::Capture, capture_start);
+        }
+
+        let samples_per_frame = voice_config.samples_per_frame();
+        let frame_duration = voice_config.duration_of(samples_per_frame);
+
+        //Encode every frame, so that every packet is tagged with the audio level of its own samples
+        let mut voice_frames = vec![];
+
+        {
+            let mut voice_encoder = self.lock_audio_stream(stream)?;
+            let voice_encoder = &mut *voice_encoder;
+
+            //Encode with the configured codec while every peer can decode it, the encoders are recreated when the codec changes
+            let (codec, audio_codec) = self.send_codec(voice_config.codec);
+
+            let encoder = match voice_encoder.encoder.take() {
+                Some((encoder_codec, encoder)) if encoder_codec == codec => encoder,
+                _ => {
+                    //The aggregated frames of the previous codec are sent on their own
+                    voice_frames.extend(VoiceFrame::aggregate(std::mem::take(
+                        &mut voice_encoder.aggregated_frames,
+                    )));
+
+                    voice_encoder.redundancy_encoder = None;
+                    voice_encoder.redundant_payload = None;
+
+                    audio_codec
+                        .encoder(&voice_config, voice_config.bitrate)
+                        .map_err(ClientError::Media)?
+                }
+            };
+
+            let (_, encoder) = voice_encoder.encoder.insert((codec, encoder));
+
+            //Cap the bitrate of the encoder to the room policy, the cap of the server and the congestion controller
+            let max_bitrate = [
+                room_policy.max_bitrate,
+                self.bitrate_cap(),
+                self.congestion_bitrate(),
+            ]
+                .into_iter()
+                .flatten()
+                .min();
+
+            let bitrate = match max_bitrate {
+                Some(max_bitrate) => match voice_config.bitrate {
+                    Bitrate::Bits(bitrate) if bitrate as u32 <= max_bitrate => {
+                        voice_config.bitrate
+                    }
+                    _ => Bitrate::Bits(max_bitrate.min(i32::MAX as u32) as i32),
+                },
+                None => voice_config.bitrate,
+            };
+
+            encoder.set_bitrate(bitrate).map_err(ClientError::Media)?;
+
+            //The redundant copies are encoded by a separate encoder, at a lower bitrate
+            if let Some(redundancy_bitrate) = voice_config
+                .redundancy_bitrate
+                .filter(|_| voice_encoder.redundancy_encoder.is_none())
+            {
+                voice_encoder.redundancy_encoder = Some(
+                    audio_codec
+                        .encoder(&voice_config, Bitrate::Bits(redundancy_bitrate))
+                        .map_err(ClientError::Media)?,
+                );
+            }
+
+            //Resynchronize the timing if there are no samples carried over, the timestamps never go backwards
+            if !sample_buf.is_empty() {
+                let expected_capture = *voice_encoder.stream_start.get_or_insert(capture_instant);
+                let expected_capture = voice_encoder.pending_capture.unwrap_or(expected_capture);
+
+                if voice_encoder.pending_samples.is_empty() {
+                    voice_encoder.pending_capture = Some(capture_instant.max(expected_capture));
+                }
+
+                voice_encoder.pending_samples.extend(sample_buf);
+            }
+
+            while voice_encoder.pending_samples.len() >= samples_per_frame
+                || (flush && !voice_encoder.pending_samples.is_empty())
+            {
+                let sample_count = samples_per_frame.min(voice_encoder.pending_samples.len());
+                let mut frame: Vec<f32> = voice_encoder
+                    .pending_samples
+                    .drain(..sample_count)
+                    .collect();
+
+                let capture = voice_encoder.pending_capture.unwrap_or(capture_instant);
+                let timestamp = voice_encoder
+                    .stream_start
+                    .map(|stream_start| capture.saturating_duration_since(stream_start))
+                    .unwrap_or_default();
+
+                voice_encoder.pending_capture = Some(capture + frame_duration);
+
+                let audio_level = audio_level(&frame);
+
+                //Silent frames are not sent at all with discontinuous transmission, except for music
+                if room_policy.mandatory_dtx
+                    && voice_config.profile == AudioProfile::Voice
+                    && audio_level >= DTX_SILENCE_LEVEL
+                {
+                    //The aggregated frames have to be continuous, so the frames before the silence are sent on their own
+                    voice_frames.extend(VoiceFrame::aggregate(std::mem::take(
+                        &mut voice_encoder.aggregated_frames,
+                    )));
+
+                    continue;
+                }
+
+                frame.resize(samples_per_frame, 0.);
+
+                let encode_start = Instant::now();
+
+                let mut voice_frame = VoiceFrame::new(
+                    self.uuid,
+                    codec,
+                    encoder.encode(&frame).map_err(ClientError::Media)?,
+                );
+
+                voice_frame.stream = (stream != 0).then_some(stream);
+                voice_frame.device = self.device();
+                voice_frame.sequence = Some(voice_encoder.sequence);
+                voice_frame.timestamp = Some(timestamp);
+                voice_frame.duration = Some(frame_duration);
+                voice_frame.audio_level = Some(audio_level);
+
+                //Attach the copy of the previous frame, and keep the copy of this frame for the next one
+                if let Some(redundancy_encoder) = voice_encoder.redundancy_encoder.as_mut() {
+                    let redundant_payload =
+                        redundancy_encoder.encode(&frame).map_err(ClientError::Media)?;
+
+                    voice_frame.redundant_payload =
+                        voice_encoder.redundant_payload.replace(redundant_payload);
+                }
+
+                self.state
+                    .pipeline_profiler
+                    .record_since(PipelineStage::Encode, encode_start);
+
+                voice_encoder.sequence = voice_encoder.sequence.wrapping_add(1);
+
+                voice_encoder.aggregated_frames.push(voice_frame);
+
+                if voice_encoder.aggregated_frames.len() >= voice_config.frames_per_packet as usize {
+                    voice_frames.extend(VoiceFrame::aggregate(std::mem::take(
+                        &mut voice_encoder.aggregated_frames,
+                    )));
+                }
+            }
+
+            //Send the frames which are waiting for aggregation, if the stream is flushed
+            if flush {
+                voice_frames.extend(VoiceFrame::aggregate(std::mem::take(
+                    &mut voice_encoder.aggregated_frames,
+                )));
+            }
+        }
+
+        //Play out what is actually sent, after the processing and the encoding
+        self.play_sidetone(&voice_frames);
+
+        for voice_frame in voice_frames {
+            let send_start = Instant::now();
+
+            self.send_voice_frame(voice_frame).await?;
+
+            self.state
+                .pipeline_profiler
+                .record_since(PipelineStage::Send, send_start);
+        }
+
+        Ok(())
+    }
+
+    /// Sends an encoded [`VoiceFrame`] to the remote address.
+    /// The author of the frame is replaced with the [`Uuid`] of this [`Client`].
+    pub async fn send_voice_frame(
+        &self,
+        mut voice_frame: VoiceFrame,
+    ) -> std::result::Result<(), ClientError> {
+        voice_frame.author = self.uuid;
+
+        let voip_body = voice_frame.to_body();
+        let voip_packet = voice_frame.to_header().create_message_buffer(&voip_body)?;
+
+        check_message_size(&voip_packet, voip_body.len())?;
+
+        self.outbound_message_sender
+            .send(voip_packet)
+            .await
+            .map_err(|_| self.service_stopped())?;
+
+        Ok(())
+    }
+
+    /// Sends a live [`Caption`] to the remote address, which relays it like the other messages (for example to every participant of the channel).
+    /// The caption has to fit in a single message, as captions aren't fragmented.
+    pub async fn send_caption(&self, caption: &Caption) -> std::result::Result<(), ClientError> {
+        let voip_packet = caption
+            .to_header(self.uuid)
+            .create_message_buffer(caption.text.as_bytes())?;
+
+        check_message_size(&voip_packet, caption.text.len())?;
+
+        self.outbound_message_sender
+            .send(voip_packet)
+            .await
+            .map_err(|_| self.service_stopped())?;
+
+        Ok(())
+    }
+
+    /// Returns the voice frames the client service has decoded since the last call, without waiting for new ones.
+    /// The received voice is only decoded if [`VoiceConfig::decode_received`] is enabled.
+    /// The frames returned here aren't played out by the [`Playout`], so only one of them should be used.
+    pub fn recv_frames(&mut self) -> Vec<DecodedVoiceFrame> {
+        self.playout.lock().take_frames()
+    }
+
+    /// Returns the [`Playout`] of the received voice, which can be shared with the audio output (for example the callback of an audio engine).
+    /// The [`Mixer`](crate::audio::mixer::Mixer) of the playout can be used to set the gain, the priority or the position of the authors.
+    pub fn playout(&self) -> Arc<Mutex<Playout>> {
+        self.playout.clone()
+    }
+
+    /// Returns the [`JitterStats`] of the voice of the `author`, [`None`] if nothing of the author was played out yet (see [`Playout::jitter_stats`]).
+    pub fn jitter_stats(&self, author: Uuid) -> Option<JitterStats> {
+        self.playout.lock().jitter_stats(author)
+    }
+
+    ///
+    /// Fills the `frames` with the next playout-ready samples of the received voice, mixed together.
+    ///
+    /// # Behavior
+    /// This is meant to be called from the callback of a custom audio engine, whenever it needs the next samples.
+    /// The samples are interleaved, with the sample rate and the channels of the [`VoiceConfig`].
+    /// The decoded frames of every author are played out through a [`JitterBuffer`](crate::audio::jitter::JitterBuffer), which reorders them and absorbs the jitter of the network.
+    /// The `frames` are filled with silence while nothing is ready to be played out.
+    /// The received voice is only decoded if [`VoiceConfig::decode_received`] is enabled.
+    ///
+    pub fn pull_mixed_audio(&self, frames: &mut [f32]) {
+        self.playout.lock().pull_mixed(frames);
+    }
+
+    ///
+    /// Taps the decoded voice of every speaker, for example to transcribe it with a speech-to-text engine.
+    ///
+    /// # Behavior
+    /// The returned [`VoiceTap`] receives every [`DecodedVoiceFrame`] decoded after the call, as it is decoded (before it is played out), with the time it was decoded at.
+    /// The decoded frames are shared with the playout, so the voice isn't decoded twice.
+    /// The oldest frames are skipped if the tap lags behind by more than [`VOICE_TAP_CAPACITY`] frames.
+    /// The received voice is only decoded if [`VoiceConfig::decode_received`] is enabled.
+    ///
+    pub fn tap_voice(&self) -> VoiceTap {
+        VoiceTap::new(self.voice_tap_sender.subscribe())
+    }
+
+    ///
+    /// Sets the [`AudioPipeline`] the captured samples of the default voice stream are processed with, before they are encoded (for example denoise → AGC → VAD).
+    ///
+    /// # Behavior
+    /// The samples are processed in the chunks they are passed to [`Client::send_samples`] and [`Client::push_samples`] in, before the loudness of the [`RoomPolicy`] is applied.
+    /// The audio streams added with [`Client::add_audio_stream`] aren't processed, as they usually don't carry the voice of the microphone.
+    /// The previous pipeline is returned, an empty pipeline disables the processing.
+    ///
+    pub fn set_capture_pipeline(&self, capture_pipeline: AudioPipeline) -> AudioPipeline {
+        std::mem::replace(&mut *self.capture_pipeline.lock(), capture_pipeline)
+    }
+
+    /// Sets the [`AudioPipeline`] the mixed samples are processed with before they are handed to the audio output (for example EQ → limiter), returning the previous pipeline (see [`Playout::set_playback_pipeline`]).
+    pub fn set_playback_pipeline(&self, playback_pipeline: AudioPipeline) -> AudioPipeline {
+        self.playout.lock().set_playback_pipeline(playback_pipeline)
+    }
+
+    /// Sets the linear gain of the sidetone (see [`ClientConfig::sidetone`]), or disables it if the gain is [`None`].
+    pub fn set_sidetone(&self, sidetone_gain: Option<f32>) {
+        self.playout.lock().set_sidetone(sidetone_gain);
+    }
+
+    ///
+    /// Plays the interleaved `samples` of a local sound (for example a notification, or the clip of a soundboard) on the `target` [`SoundBoard`]s.
+    ///
+    /// # Behavior
+    /// The samples have to match the sample rate and the channels of the [`VoiceConfig`], the overlapping sounds are summed.
+    /// The sounds played on [`SoundTarget::PLAYBACK`] are mixed into [`Client::pull_mixed_audio`], after the sidetone.
+    /// The sounds played on [`SoundTarget::OUTGOING`] are mixed into the samples of the default voice stream after the capture [`AudioPipeline`], so they don't disturb the processing of the microphone.
+    /// The outgoing sounds are sent as the captured samples are, so they are only sent while [`Client::push_samples`] (or [`Client::send_samples`]) is called.
+    ///
+    pub fn play_sound(&self, samples: &[f32], target: SoundTarget) {
+        if target.contains(SoundTarget::PLAYBACK) {
+            self.playout.lock().sound_board_mut().play(samples);
+        }
+
+        if target.contains(SoundTarget::OUTGOING) {
+            self.outgoing_sounds.lock().play(samples);
+        }
+    }
+
+    /// Stops every sound playing on the `target` [`SoundBoard`]s.
+    pub fn stop_sounds(&self, target: SoundTarget) {
+        if target.contains(SoundTarget::PLAYBACK) {
+            self.playout.lock().sound_board_mut().stop();
+        }
+
+        if target.contains(SoundTarget::OUTGOING) {
+            self.outgoing_sounds.lock().stop();
+        }
+    }
+
+    /// Sets the linear gain of the sounds of the `target` [`SoundBoard`]s, independently of the gain of the voice.
+    pub fn set_sound_gain(&self, target: SoundTarget, gain: f32) {
+        if target.contains(SoundTarget::PLAYBACK) {
+            self.playout.lock().sound_board_mut().set_gain(gain);
+        }
+
+        if target.contains(SoundTarget::OUTGOING) {
+            self.outgoing_sounds.lock().set_gain(gain);
+        }
+    }
+
+    /// Decodes the sent `voice_frames` and queues them for the sidetone, if it is enabled.
+    fn play_sidetone(&self, voice_frames: &[VoiceFrame]) {
+        let mut playout = self.playout.lock();
+
+        if playout.sidetone().is_none() {
+            return;
+        }
+
+        let mut sidetone_decoders = self.sidetone_decoders.lock();
+        let sidetone_decoders = sidetone_decoders
+            .get_or_insert_with(|| VoiceDecoders::new(&self.voice_config).with_codecs(self.codecs.clone()));
+
+        for voice_frame in voice_frames {
+            match sidetone_decoders.decode_message(&voice_frame.to_header(), &voice_frame.to_body()) {
+                Ok(decoded_frames) => {
+                    for decoded_frame in decoded_frames {
+                        playout.push_sidetone(&decoded_frame.samples);
+                    }
+                }
+                Err(err) => event!(Level::ERROR, "Failed to decode the sidetone: {err}"),
+            }
+        }
+    }
+
+    /// Creates a [`rodio`] source, playing out the received voice of every author mixed together.
+    #[cfg(feature = "rodio")]
+    pub fn mixed_source(&self) -> super::sink::MixedSource {
+        super::sink::MixedSource::new(self.playout())
+    }
+
+    /// Creates a [`rodio`] source, playing out the received voice of the `author`.
+    /// The voice of the author is left out of the [mixed source](Client::mixed_source) while the source exists.
+    #[cfg(feature = "rodio")]
+    pub fn stream_source(&self, author: Uuid) -> super::sink::StreamSource {
+        super::sink::StreamSource::new(self.playout(), author)
+    }
 
-    /// This local channel sends messages which will be sent to the server.
-    outbound_message_sender: Sender<VoipPacket>,
-}
+    ///
+    /// Registers the `factory` of the [`VideoDecoder`]s of the `codec`, replacing the previously registered one.
+    ///
+    /// # Behavior
+    /// The client service creates a decoder with the factory for every remote author sending video with the codec, and decodes the reassembled frames into RGBA images.
+    /// The decoded frames can be read with [`Client::recv_video_frames`], the received video of the codecs without a registered decoder is only reported as [`ClientEvent::VideoFrame`].
+    ///
+    pub fn register_video_decoder(
+        &self,
+        codec: MediaCodec,
+        factory: impl Fn() -> Box<dyn VideoDecoder> + Send + Sync + 'static,
+    ) {
+        self.state
+            .video_decoders
+            .lock()
+            .register(codec, Arc::new(factory));
+    }
 
-impl Client {
-    /// Creates a new [`Client`] instance, automaticly sets up the [`UdpSocket`].
-    pub async fn new<T: ToSocketAddrs>(uuid: Uuid, remote_addr: T) -> Result<Self> {
-        //Create I/O channels
-        let (outbound_message_sender, outbound_message_receiver) = channel::<VoipPacket>(255);
-        let (inbound_message_sender, inbound_message_receiver) =
-            channel::<(VoipHeader, Vec<u8>)>(255);
+    /// Returns the video frames the client service has decoded since the last call, without waiting for new ones.
+    /// The frames are dropped by the client service if they aren't read fast enough, so GUI clients should call this every time they render (and usually only draw the latest frame of every author).
+    pub fn recv_video_frames(&mut self) -> Vec<DecodedVideoFrame> {
+        std::iter::from_fn(|| self.decoded_video_receiver.try_recv().ok()).collect()
+    }
 
-        //Bind UdpSocket to local address
-        let socket_handle = establish_connection(remote_addr).await?;
+    /// Automaticly fetches the image from the client's webcam, and sends it to the remote address.
+    pub async fn send_image(
+        &self,
+        encoder: ravif::Encoder,
+        mut webcam: Webcam,
+    ) -> std::result::Result<(), ClientError> {
+        let (bytes, size) = webcam
+            .get_frame()
+            .map_err(|err| MediaError::Codec(err.into()))?;
 
-        //Establish client service
-        Self::create_client_service(
-            socket_handle,
-            inbound_message_sender,
-            outbound_message_receiver,
-        );
+        self.send_raw_video_frame(encoder, &bytes, size.width as usize, size.height as usize)
+            .await
+    }
 
-        Ok(Self {
-            uuid,
-            inbound_message_receiver,
-            outbound_message_sender,
-        })
+    /// Encodes a raw RGB frame with the AVIF encoder, and sends it to the remote address like [`Client::send_video_packet`].
+    /// Every AVIF frame is self contained, so it is sent as a keyframe.
+    pub async fn send_raw_video_frame(
+        &self,
+        encoder: ravif::Encoder,
+        bytes: &[u8],
+        width: usize,
+        height: usize,
+    ) -> std::result::Result<(), ClientError> {
+        let encoded_image = encode_raw_image(encoder, bytes, width, height)
+            .map_err(|err| MediaError::Codec(err.into()))?;
+
+        self.send_video_packet(&encoded_image.avif_file, MediaCodec::Avif, true)
+            .await
     }
 
-    /// Creates a new [`Client`] instance from an already existing [`UdpSocket`].
-    pub async fn new_from_udp_socket(uuid: Uuid, socket_handle: UdpSocket) -> Result<Self> {
-        //Create I/O channels
-        let (outbound_message_sender, outbound_message_receiver) = channel::<VoipPacket>(255);
-        let (inbound_message_sender, inbound_message_receiver) =
-            channel::<(VoipHeader, Vec<u8>)>(255);
+    ///
+    /// Sets the [`VideoEncoder`] of the `codec` the images sent with [`Client::send_video_image`] are encoded with, replacing the previous one.
+    ///
+    /// # Behavior
+    /// The [`VideoEncoderConfig`] decides which frames refresh the picture, either periodic keyframes or a rolling intra refresh (see [`FrameRefresh`]).
+    /// The first frame encoded by the new encoder is a keyframe.
+    /// The server only switches the simulcast layers on keyframes, so [`Client::request_keyframe`] should be called when a receiver selects a layer of an intra refreshed video.
+    ///
+    pub fn set_video_encoder(
+        &self,
+        codec: MediaCodec,
+        encoder: Box<dyn VideoEncoder>,
+        config: VideoEncoderConfig,
+    ) {
+        *self.state.video_encoder.lock() = Some(VideoEncoderState {
+            codec,
+            encoder,
+            scheduler: RefreshScheduler::new(config),
+        });
+    }
 
-        //Establish client service
-        Self::create_client_service(
-            socket_handle,
-            inbound_message_sender,
-            outbound_message_receiver,
-        );
+    /// Removes the [`VideoEncoder`] set with [`Client::set_video_encoder`].
+    pub fn remove_video_encoder(&self) {
+        *self.state.video_encoder.lock() = None;
+    }
 
-        Ok(Self {
-            uuid,
-            inbound_message_receiver,
-            outbound_message_sender,
-        })
+    /// Makes the next image sent with [`Client::send_video_image`] a keyframe (for example after a receiver has lost the video), the periodic refreshes are counted from it.
+    pub fn request_keyframe(&self) {
+        if let Some(video_encoder) = self.state.video_encoder.lock().as_mut() {
+            video_encoder.scheduler.request_keyframe();
+        }
     }
 
-    /// Returns the [`Uuid`] this [`Client`] instance was created with.
-    pub fn uuid(&self) -> Uuid {
-        self.uuid
+    ///
+    /// Encodes the [`RgbaImage`] with the [`VideoEncoder`] set with [`Client::set_video_encoder`], and sends it to the remote address like [`Client::send_video_packet`].
+    ///
+    /// # Behavior
+    /// The encoder is told which part of the frame to refresh by the [`FrameRefresh`] scheduled for it, only the keyframes are sent as keyframes.
+    /// The frames the encoder splits into slices (see [`VideoEncoder::encode_slices`]) are sent like [`Client::send_video_slices`].
+    ///
+    /// # Error
+    /// Returns an error if no video encoder is set, or the encoder has failed to encode the image.
+    ///
+    pub async fn send_video_image(
+        &self,
+        image: &RgbaImage,
+    ) -> std::result::Result<(), ClientError> {
+        let (codec, slices, refresh) = {
+            let mut video_encoder = self.state.video_encoder.lock();
+            let video_encoder = video_encoder.as_mut().ok_or(ClientError::NoVideoEncoder)?;
+
+            let refresh = video_encoder.scheduler.next_refresh();
+            let slices = video_encoder
+                .encoder
+                .encode_slices(image, refresh)
+                .map_err(ClientError::Media)?;
+
+            (video_encoder.codec, slices, refresh)
+        };
+
+        self.send_video_slices(slices, codec, refresh == FrameRefresh::Keyframe)
+            .await
     }
 
-    /// Writes the message buffer to the [`Client`]'s underlying [`UdpSocket`].
-    pub fn message_sender(&mut self) -> &mut Sender<VoipPacket> {
-        &mut self.outbound_message_sender
+    /// Sends an encoded video frame to the remote address like [`Client::send_video_frame`].
+    /// The frame is sent with the next sequence number, and timestamped with the time elapsed since the creation of this [`Client`].
+    pub async fn send_video_packet(
+        &self,
+        frame: &[u8],
+        codec: MediaCodec,
+        is_keyframe: bool,
+    ) -> std::result::Result<(), ClientError> {
+        self.send_video_slices(vec![frame.to_vec()], codec, is_keyframe)
+            .await
     }
 
-    /// Gets the incoming message receiver ([`Receiver<VoipPacket>`]) handle.
-    /// This is created at the instance creation of [`Server`].
-    /// The server listener threads has ownership of the sender, and sends every incoming message to the receiver.
-    pub fn message_receiver(&mut self) -> &mut Receiver<(VoipHeader, Vec<u8>)> {
-        &mut self.inbound_message_receiver
+    /// Sends a video frame encoded in independent slices (or tiles) to the remote address like [`Client::send_video_packet`].
+    /// Every slice is sent in its own messages, so the receivers can decode the slices which have arrived even if others were lost (see [`VideoFrame::slices`]).
+    pub async fn send_video_slices(
+        &self,
+        slices: Vec<Vec<u8>>,
+        codec: MediaCodec,
+        is_keyframe: bool,
+    ) -> std::result::Result<(), ClientError> {
+        let mut video_frame = VideoFrame::from_slices(self.uuid, codec, is_keyframe, slices);
+
+        video_frame.sequence = Some(self.video_sequence.fetch_add(1, Ordering::Relaxed));
+        video_frame.timestamp = Some(self.created_at.elapsed());
+
+        self.send_video_frame(video_frame).await
     }
 
-    fn create_client_service(
-        socket_handle: UdpSocket,
-        inbound_message_sender: Sender<(VoipHeader, Vec<u8>)>,
-        mut outbound_message_receiver: Receiver<VoipPacket>,
-    ) {
-        tokio::spawn(async move {
-            loop {
-                let mut buf = Vec::with_capacity(8);
+    ///
+    /// Sends an encoded [`VideoFrame`] to the remote address.
+    ///
+    /// # Behavior
+    /// The author of the frame is replaced with the [`Uuid`] of this [`Client`].
+    /// The frame is split into fragments which fit in [`MTU_MAX_PACKET_SIZE`] (see [`fragment_message`]), and the fragments are paced by the client service according to [`ClientConfig::video_pacing_interval`].
+    /// Keyframes have the [`MARKER`](crate::packet::HeaderFlags::MARKER) flag set on every fragment.
+    /// The frames encoded in slices (see [`VideoFrame::from_slices`]) are fragmented slice by slice, so a lost fragment only discards its slice instead of the whole frame.
+    /// The receiving clients reassemble the fragments, and report the whole frame as a single [`ClientEvent::VideoFrame`].
+    ///
+    pub async fn send_video_frame(
+        &self,
+        mut video_frame: VideoFrame,
+    ) -> std::result::Result<(), ClientError> {
+        video_frame.author = self.uuid;
 
-                select! {
-                    //Await incoming messages from the server.
-                    //If received send it through the `inbound_message_receiver`.
-                    incoming_bytes = socket_handle.recv_from(&mut buf) => {
-                        match incoming_bytes {
-                            Ok((_byte_count, _socket_addr)) => {
-                                let body_length = usize::from_be_bytes(buf.try_into().unwrap());
-
-                                //Check for invalid messages
-                                if body_length > MTU_MAX_PACKET_SIZE {
-                                    //Log error
-                                    event!(Level::ERROR, "Message header with too large length: {body_length}. Discarding message.");
-
-                                    //If an inavlid message was provided discard it, to avoid overflowing buffer sizes
-                                    continue;
-                                }
+        let mut fragments = vec![];
 
-                                //This cannot block as the header and the body is included in one message
-                                let mut body_buf = Vec::with_capacity(body_length);
+        for (voip_header, payload) in video_frame.to_messages() {
+            fragments.extend(fragment_message(&voip_header, payload)?);
+        }
 
-                                //Read from UdpSocket
-                                socket_handle.recv(&mut body_buf).await.unwrap();
+        self.video_sender
+            .send(fragments)
+            .await
+            .map_err(|_| self.service_stopped())?;
 
-                                //Try serializing the bytes
-                                match rmp_serde::from_slice::<VoipHeader>(&body_buf) {
-                                    Ok(voip_header) => {
-                                        let voip_body_length = match voip_header.voip_message_type() {
-                                            crate::packet::VoipMessageType::VoiceMessage(length) => length,
-                                            crate::packet::VoipMessageType::VideoMessage(length) => length,
-                                        };
+        Ok(())
+    }
 
-                                        //Create voip body buf allocate the length by fetching the header
-                                        let mut voip_body_buf = Vec::with_capacity(*voip_body_length as usize);
+    /// Sends a [`QualityReport`] to the remote address.
+    pub async fn send_quality_report(
+        &self,
+        report: QualityReport,
+    ) -> std::result::Result<(), ClientError> {
+        self.send_bytes(
+            VoipMessageType::Control(ControlMessage::QualityReport(report)),
+            &mut std::iter::empty(),
+        )
+        .await
+    }
 
-                                        //Read the body into the buffer
-                                        socket_handle.recv(&mut voip_body_buf).await.unwrap();
+    /// Signals the highest bitrate (in bits per second) this [`Client`] can receive, by sending a [`ControlMessage::MaxBitrate`] to the remote address.
+    /// Passing [`None`] removes the limit.
+    pub async fn set_max_bitrate(
+        &self,
+        max_bitrate: Option<u32>,
+    ) -> std::result::Result<(), ClientError> {
+        self.send_bytes(
+            VoipMessageType::Control(ControlMessage::MaxBitrate(max_bitrate)),
+            &mut std::iter::empty(),
+        )
+        .await
+    }
 
-                                        //Send the serialized message through the channel
-                                        inbound_message_sender.send((voip_header, voip_body_buf)).await.unwrap();
-                                    },
-                                    Err(err) => {
-                                        event!(Level::ERROR, "Failed to deserialize a VoipPacket: {err}");
-                                    },
-                                }
-                            },
-                            Err(err) => {
-                                event!(Level::ERROR, "Failed to receive message: {err}");
-                            },
-                        }
-                    }
+    /// Selects the simulcast layer of the `author`'s video this [`Client`] receives, by sending a [`ControlMessage::SelectLayer`] to the remote address.
+    /// The server switches to the layer on its next keyframe.
+    pub async fn select_layer(
+        &self,
+        author: Uuid,
+        layer_selection: LayerSelection,
+    ) -> std::result::Result<(), ClientError> {
+        self.send_bytes(
+            VoipMessageType::Control(ControlMessage::SelectLayer(author, layer_selection)),
+            &mut std::iter::empty(),
+        )
+        .await
+    }
 
-                    //Await outgoing message requests from the user.
-                    //If the channel receives a [`VoipPacket`] this function will send it to the connected [`SocketAddr`].
-                    Some(outgoing_message) = outbound_message_receiver.recv() => {
-                        //Send the VoipPacket to the remote address
-                        socket_handle.send(outgoing_message.inner()).await.unwrap();
-                    }
-                }
+    /// Signals a [`ToneEvent`] (for example a DTMF digit created with [`ToneEvent::dtmf`]), by sending a [`ControlMessage::Tone`] to the remote address.
+    pub async fn send_tone(&self, tone: ToneEvent) -> std::result::Result<(), ClientError> {
+        self.send_bytes(
+            VoipMessageType::Control(ControlMessage::Tone(tone)),
+            &mut std::iter::empty(),
+        )
+        .await
+    }
+
+    /// Requests the floor of the `channel` (or room), by sending a [`ControlMessage::FloorRequest`] to the remote address.
+    /// The server answers with a [`ClientEvent::FloorChanged`], and only forwards the voice of this [`Client`] once the floor is granted to it.
+    pub async fn request_floor(&self, channel: u32) -> std::result::Result<(), ClientError> {
+        self.send_channel_message(ControlMessage::FloorRequest, channel)
+            .await
+    }
+
+    /// Releases the floor of the `channel` (or room), or leaves its queue, by sending a [`ControlMessage::FloorRelease`] to the remote address.
+    pub async fn release_floor(&self, channel: u32) -> std::result::Result<(), ClientError> {
+        self.send_channel_message(ControlMessage::FloorRelease, channel)
+            .await
+    }
+
+    /// Sends a control message about the `channel` (or room) to the remote address.
+    async fn send_channel_message(
+        &self,
+        control_message: ControlMessage,
+        channel: u32,
+    ) -> std::result::Result<(), ClientError> {
+        let voip_packet = VoipHeader::new(VoipMessageType::Control(control_message), self.uuid)
+            .with_channel(channel)
+            .create_message_buffer(&[])?;
+
+        self.outbound_message_sender
+            .send(voip_packet)
+            .await
+            .map_err(|_| self.service_stopped())?;
+
+        Ok(())
+    }
+
+    /// Places a one-to-one call to the `peer`, by sending it a [`CallSignalKind::Invite`].
+    /// Returns the id of the call, whose changes are reported with [`ClientEvent::CallStateChanged`].
+    pub async fn invite(&self, peer: Uuid) -> std::result::Result<Uuid, ClientError> {
+        let call_signal = self.state.calls.lock().invite(peer);
+
+        self.send_bytes(
+            VoipMessageType::Control(ControlMessage::Call(call_signal)),
+            &mut std::iter::empty(),
+        )
+        .await?;
+
+        Ok(call_signal.call_id)
+    }
+
+    /// Accepts the incoming call with the `call_id`, by sending a [`CallSignalKind::Accept`] to its caller.
+    pub async fn accept_call(&self, call_id: Uuid) -> std::result::Result<Call, ClientError> {
+        self.act_on_call(call_id, CallSignalKind::Accept).await
+    }
+
+    /// Rejects the incoming call with the `call_id`, by sending a [`CallSignalKind::Reject`] to its caller.
+    pub async fn reject_call(&self, call_id: Uuid) -> std::result::Result<Call, ClientError> {
+        self.act_on_call(call_id, CallSignalKind::Reject).await
+    }
+
+    /// Hangs up the call with the `call_id` (or cancels the invitation if it hasn't been answered yet), by sending a [`CallSignalKind::Hangup`] to the remote peer.
+    pub async fn hang_up(&self, call_id: Uuid) -> std::result::Result<Call, ClientError> {
+        self.act_on_call(call_id, CallSignalKind::Hangup).await
+    }
+
+    /// Sets the [`PresenceState`] of this [`Client`], by sending a [`ControlMessage::Presence`] to the remote address.
+    /// The server rejects the calls to this client while its state is [`PresenceState::DoNotDisturb`].
+    pub async fn set_presence(
+        &self,
+        presence: PresenceState,
+    ) -> std::result::Result<(), ClientError> {
+        self.send_bytes(
+            VoipMessageType::Control(ControlMessage::Presence(presence)),
+            &mut std::iter::empty(),
+        )
+        .await
+    }
+
+    /// Returns the [`PresenceState`] the participant with the `author` [`Uuid`] has set, [`PresenceState::Available`] if it hasn't set any.
+    pub fn presence(&self, author: Uuid) -> PresenceState {
+        self.state
+            .presences
+            .lock()
+            .get(&author)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Sets the [`MediaState`] of this [`Client`] in the `channel` (or room), by sending a [`ControlMessage::MediaState`] to the remote address.
+    /// The server retains the state, and sends it to the participants joining later.
+    pub async fn set_media_state(
+        &self,
+        channel: u32,
+        media_state: MediaState,
+    ) -> std::result::Result<(), ClientError> {
+        self.send_channel_message(ControlMessage::MediaState(media_state), channel)
+            .await
+    }
+
+    /// Returns the [`MediaState`] the participant with the `author` [`Uuid`] has signaled in the `channel` (or room), the default state if it hasn't signaled any.
+    pub fn media_state(&self, author: Uuid, channel: u32) -> MediaState {
+        self.state
+            .media_states
+            .lock()
+            .get(&(author, channel))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Gives (`true`) or withdraws (`false`) the consent of this [`Client`] to the recording of the `channel` (or room), by sending a [`ControlMessage::RecordingConsent`] to the remote address.
+    /// While the recording of the channel requires consent, the server only forwards the media of the clients which have consented (see [`ClientEvent::RecordingChanged`]).
+    pub async fn consent_to_recording(
+        &self,
+        channel: u32,
+        consent: bool,
+    ) -> std::result::Result<(), ClientError> {
+        self.send_channel_message(ControlMessage::RecordingConsent(consent), channel)
+            .await
+    }
+
+    /// Returns the [`RecordingState`] of the `channel` (or room), as signaled by the server.
+    pub fn recording_state(&self, channel: u32) -> RecordingState {
+        self.state
+            .recordings
+            .lock()
+            .get(&channel)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the one-to-one calls of this [`Client`] which haven't ended yet.
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.calls.lock().calls()
+    }
+
+    /// Applies the action of the `kind` to the call with the `call_id`, and signals it to the remote peer.
+    /// Returns the call in its new state.
+    async fn act_on_call(
+        &self,
+        call_id: Uuid,
+        kind: CallSignalKind,
+    ) -> std::result::Result<Call, ClientError> {
+        let (call, call_signal) = self.state.calls.lock().act(call_id, kind)?;
+
+        self.send_bytes(
+            VoipMessageType::Control(ControlMessage::Call(call_signal)),
+            &mut std::iter::empty(),
+        )
+        .await?;
+
+        Ok(call)
+    }
+
+    ///
+    /// Runs the connectivity diagnostics configured by the [`DiagnosticsConfig`], and returns their [`DiagnosticsReport`] (for example for a "test my connection" screen).
+    ///
+    /// # Behavior
+    /// The checks are run one after the other, so the diagnostics take about the [`DiagnosticsConfig::loss_test_duration`] plus the echo and the STUN checks:
+    /// - A new local socket is bound, and the public address of the host is queried through it from the [`DiagnosticsConfig::stun_server`].
+    /// - The round trip time of the server is measured with [`DiagnosticsConfig::echo_count`] [`ControlMessage::Ping`]s.
+    /// - The loss is measured with pings sent every [`DiagnosticsConfig::loss_test_interval`], each following enough padding to reach the [`DiagnosticsConfig::loss_test_bitrate`].
+    /// - The system clock is compared with the monotonic clock over the whole diagnostics.
+    ///
+    /// The pings are sent through the session of this [`Client`], so the voice and the video sent meanwhile compete with them for the bandwidth.
+    /// The pings are timed with the runtime of the client service, but the local socket and the STUN check are driven by Tokio, so outside of a Tokio runtime the local bind reports [`DiagnosticError::UnsupportedRuntime`] and the STUN check is skipped.
+    ///
+    pub async fn run_diagnostics(&self, config: &DiagnosticsConfig) -> DiagnosticsReport {
+        let started_at = std::time::Instant::now();
+        let system_started_at = std::time::SystemTime::now();
+
+        let (local_bind, stun) = match bind_probe_socket(self.peer_addr()).await {
+            Ok(probe_socket) => {
+                let stun = match &config.stun_server {
+                    Some(stun_server) => Some(
+                        query_stun(&probe_socket, stun_server, config.timeout, self.sleep).await,
+                    ),
+                    None => None,
+                };
+
+                (
+                    probe_socket.local_addr().map_err(DiagnosticError::Bind),
+                    stun,
+                )
             }
-        });
+            Err(err) => (Err(err), None),
+        };
+
+        let echo = self
+            .probe_server(config.echo_count, config.echo_interval, 0, config.timeout)
+            .await
+            .and_then(|ping_timings| echo_report(&ping_timings));
+
+        //The padding carries the bits of a single interval at the test bitrate
+        let loss_test_count = (config.loss_test_duration.as_nanos()
+            / config.loss_test_interval.as_nanos().max(1)) as u32;
+        let padding = (config.loss_test_bitrate as f64 / 8.
+            * config.loss_test_interval.as_secs_f64()) as usize;
+
+        let loss = self
+            .probe_server(
+                loss_test_count,
+                config.loss_test_interval,
+                padding,
+                config.timeout,
+            )
+            .await
+            .map(|ping_timings| LossReport {
+                bitrate: config.loss_test_bitrate,
+                sent: ping_timings.len() as u32,
+                received: ping_timings
+                    .iter()
+                    .filter(|ping_timing| ping_timing.answered_at.is_some())
+                    .count() as u32,
+            });
+
+        DiagnosticsReport {
+            local_bind,
+            stun,
+            echo,
+            loss,
+            clock: check_clock(started_at, system_started_at, config.max_clock_drift),
+        }
+    }
+
+    ///
+    /// Probes the bandwidth available towards the server with bursts of padding, before the quality of the video is ramped up (for example before [`Client::send_video_frame`] switches to a higher resolution).
+    ///
+    /// # Behavior
+    /// The first burst is sent at the [`ProbeConfig::start_bitrate`], every sustained burst is followed by a burst at a bitrate higher by the [`ProbeConfig::growth_factor`].
+    /// The probing stops at the first burst which isn't sustained (see [`ProbeBurst::is_sustained`]), or after the burst at the [`ProbeConfig::max_bitrate`].
+    /// The bursts are sent through the session of this [`Client`] on top of its media, so the measured headroom is what is left besides the media.
+    ///
+    /// # Error
+    /// Returns an error if the pings could not be encoded, or the client service has shut down.
+    ///
+    pub async fn probe_bandwidth(
+        &self,
+        config: &ProbeConfig,
+    ) -> std::result::Result<ProbeReport, DiagnosticError> {
+        let mut probe_report = ProbeReport::default();
+        let mut bitrate = Some(config.start_bitrate);
+
+        while let Some(burst_bitrate) = bitrate {
+            let (count, interval) = config.burst(burst_bitrate);
+
+            let ping_timings = self
+                .probe_server(count, interval, config.packet_size, config.timeout)
+                .await?;
+            let burst = ProbeBurst::measure(burst_bitrate, &ping_timings, config.packet_size);
+
+            probe_report.bursts.push(burst);
+
+            if !burst.is_sustained(config) {
+                break;
+            }
+
+            //A burst may get through faster than it was sent, the headroom is only proven up to its bitrate
+            let delivered_bitrate = burst
+                .delivered_bitrate
+                .map_or(burst_bitrate, |delivered_bitrate| delivered_bitrate.min(burst_bitrate));
+
+            probe_report.available_bitrate =
+                probe_report.available_bitrate.max(Some(delivered_bitrate));
+            bitrate = config.next_bitrate(burst_bitrate);
+        }
+
+        Ok(probe_report)
     }
 
-    /// Automaticly fetches the samples from the buffer, and sends them to the remote address.
-    pub async fn send_voice_packet(&self, encoder: Encoder, channels: silence_core::opus::opus::Channels, buffer: Arc<Mutex<VecDeque<f32>>>) -> anyhow::Result<()> {
-        let mut sample_buf = vec![];
-        while let Some(sample) = buffer.lock().pop_front() {
-            sample_buf.push(sample);
+    ///
+    /// Warms the session up before the user joins a call (see the [`preconnect`](super::preconnect) module), and returns the [`PreconnectReport`] of its checks.
+    ///
+    /// # Behavior
+    /// The encoder of the voice is created first, if it doesn't exist yet.
+    /// Then the [`PreconnectConfig::handshake_pings`] are exchanged with the server, while the public address of the host is queried from the [`PreconnectConfig::stun_server`] through a new local socket.
+    /// Once the server has answered, the bandwidth is probed like [`Client::probe_bandwidth`] with the [`PreconnectConfig::probe`].
+    /// The STUN query needs a Tokio runtime like the one of [`Client::run_diagnostics`], it is skipped outside of one.
+    ///
+    pub async fn preconnect(&self, config: &PreconnectConfig) -> PreconnectReport {
+        //The first captured samples shouldn't wait for the encoder
+        let (codec, audio_codec) = self.send_codec(self.voice_config.codec);
+
+        {
+            let mut voice_encoder = self.voice_encoder.lock();
+
+            if voice_encoder.encoder.is_none() {
+                match audio_codec.encoder(&self.voice_config, self.voice_config.bitrate) {
+                    Ok(encoder) => voice_encoder.encoder = Some((codec, encoder)),
+                    Err(err) => event!(Level::WARN, "Failed to create the voice encoder: {err}"),
+                }
+            }
         }
 
-        let sound_packets = encode_samples_opus(encoder, &sample_buf, 20, channels)?;
+        let stun = async {
+            let stun_server = config.stun_server.as_ref()?;
+            let probe_socket = bind_probe_socket(self.peer_addr()).await.ok()?;
+
+            Some(query_stun(&probe_socket, stun_server, config.timeout, self.sleep).await)
+        };
+
+        let handshake = async {
+            self.probe_server(
+                config.handshake_pings,
+                config.handshake_interval,
+                0,
+                config.timeout,
+            )
+            .await
+            .and_then(|ping_timings| echo_report(&ping_timings))
+        };
 
-        for sound_packet in sound_packets {
-            self.outbound_message_sender.send(VoipHeader::new(VoipMessageType::VoiceMessage(1), self.uuid).create_message_buffer(&sound_packet.bytes)?).await?;
+        let (handshake, stun) = tokio::join!(handshake, stun);
+
+        let probe = match (&config.probe, &handshake) {
+            (Some(probe_config), Ok(_)) => Some(self.probe_bandwidth(probe_config).await),
+            _ => None,
+        };
+
+        PreconnectReport {
+            handshake,
+            stun,
+            probe,
+        }
+    }
+
+    /// Returns the error of a request sent to the client service which has already shut down.
+    /// This is [`ClientError::Unauthorized`] if the server has closed the session because the client has failed to authenticate or was banned, [`ClientError::ChannelClosed`] otherwise.
+    fn service_stopped(&self) -> ClientError {
+        match self.state.remote_close_reason.lock().clone() {
+            Some(close_reason)
+                if matches!(
+                    close_reason.code(),
+                    CloseCode::AuthenticationFailed | CloseCode::Banned
+                ) =>
+            {
+                ClientError::Unauthorized(close_reason)
+            }
+            _ => ClientError::ChannelClosed,
         }
+    }
+
+    /// Probes the server with `count` diagnostic pings (see [`probe_server`]), returning the [`PingTiming`] of every ping.
+    async fn probe_server(
+        &self,
+        count: u32,
+        interval: Duration,
+        padding: usize,
+        timeout: Duration,
+    ) -> std::result::Result<Vec<PingTiming>, DiagnosticError> {
+        //Every probe has its own sequence numbers, so the late answers of a previous one are ignored
+        let first_sequence = self.ping_sequence.fetch_add(count, Ordering::Relaxed);
+
+        probe_server(
+            &self.outbound_message_sender,
+            self.pong_sender.subscribe(),
+            self.uuid,
+            PingProbe {
+                first_sequence,
+                count,
+                interval,
+                padding,
+                timeout,
+            },
+            self.sleep,
+        )
+        .await
+    }
+
+    /// Notifies the remote address that this [`Client`] is leaving the session, by sending a [`ControlMessage::Goodbye`].
+    pub async fn disconnect(&self) -> std::result::Result<(), ClientError> {
+        self.send_bytes(
+            VoipMessageType::Control(ControlMessage::Goodbye),
+            &mut std::iter::empty(),
+        )
+        .await
+    }
+
+    /// Closes the session with the [`CloseReason`], by sending a [`ControlMessage::Close`] to the remote address.
+    /// The client service shuts down afterwards, reporting [`ClientEvent::Closed`] as its final event.
+    pub async fn close(&self, close_reason: CloseReason) -> std::result::Result<(), ClientError> {
+        self.close_sender
+            .send(close_reason)
+            .await
+            .map_err(|_| self.service_stopped())?;
 
         Ok(())
     }
-    
-    /// Automaticly fetches the image from the client's webcam, and sends it to the remote address.
-    pub async fn send_image(&self, encoder: ravif::Encoder, mut webcam: Webcam) -> anyhow::Result<()> {
-        let (bytes, size) = webcam.get_frame()?;
 
-        let encoded_image = encode_raw_image(
-            encoder.clone(),
-            &bytes,
-            size.width as usize,
-            size.height as usize,
-        )?;
+    ///
+    /// Switches the client service to the [`PowerMode`], for example to [`PowerMode::LowPower`] when the application is moved to the background.
+    ///
+    /// # Behavior
+    /// In low power mode the heartbeats are sent at the [`LowPowerConfig::heartbeat_interval`], the deferrable control messages are held back until the next heartbeat (or the next urgent message), and the cover traffic is paused.
+    /// The video sent by this client is dropped, and the server is asked with a [`ControlMessage::PauseVideo`] to stop forwarding the video of the others.
+    /// Switching back to [`PowerMode::Normal`] sends the held back messages right away, and the sent video resumes with a keyframe.
+    ///
+    pub async fn set_power_mode(
+        &self,
+        power_mode: PowerMode,
+    ) -> std::result::Result<(), ClientError> {
+        self.power_mode_sender
+            .send(power_mode)
+            .await
+            .map_err(|_| self.service_stopped())?;
 
-        self.outbound_message_sender.send(VoipHeader::new(VoipMessageType::VideoMessage(encoded_image.avif_file.len() as u64), self.uuid).create_message_buffer(&encoded_image.avif_file)?).await?;
+        *self.power_mode.lock() = power_mode;
 
         Ok(())
     }
 
+    /// Returns the [`PowerMode`] the client was last switched to.
+    pub fn power_mode(&self) -> PowerMode {
+        *self.power_mode.lock()
+    }
+
+    /// Returns the [`DataUsage`] of the session so far, counting every datagram the client service has sent and received since this [`Client`] was created.
+    /// The duration of the session stops growing once the client service has shut down (for example after [`Client::close`]).
+    pub fn data_usage(&self) -> DataUsage {
+        self.state.usage_meter.snapshot()
+    }
+
+    ///
+    /// Returns the [`PipelineProfile`] of the audio pipeline, so the crackling caused by the scheduling of the audio threads can be told apart from the one caused by the network.
+    ///
+    /// # Behavior
+    /// Every stage of the sent and the received voice is timed, from the processing of the captured samples to the playout (see [`PipelineStage`]).
+    /// The calls of [`Client::push_samples`] (and [`Client::send_samples`]) are treated as the callbacks of the capture device, and the calls of [`Playout::pull_mixed`] as the callbacks of the playback device.
+    /// A callback arriving later than the audio of the previous one lasts is counted as an xrun of its device.
+    /// The profile is kept for the whole session, unless it is reset with [`Client::reset_pipeline_profile`].
+    ///
+    pub fn pipeline_profile(&self) -> PipelineProfile {
+        self.state.pipeline_profiler.snapshot()
+    }
+
+    /// Clears the [`PipelineProfile`] of the audio pipeline, for example after the audio devices were changed.
+    pub fn reset_pipeline_profile(&self) {
+        self.state.pipeline_profiler.reset();
+    }
+
+    ///
+    /// Returns the latest [`ResumptionTicket`] the server has issued to this client, if it issues any.
+    ///
+    /// # Behavior
+    /// The ticket should be stored by the application (for example next to the [`Uuid`] of the client), and set as the [`ClientConfig::resumption_ticket`] of the client created after a crash or a network blip.
+    /// The server then restores the session of the [`Uuid`], keeping its state (the room, the presence and media states, the consents, ...) if it still has the session.
+    /// The ticket only proves that the client owns the session, the server still validates the address of the new client with a [`ControlMessage::Retry`] round trip (which the client answers by itself) before the session moves there.
+    ///
+    /// The ticket doesn't restore the keys of the packet ciphers, which are set up by the application: the transport of the new client has to seal with fresh keys, or at least with a fresh IV, as the sequence numbers of its datagrams start over.
+    /// The `CipherInterceptor` of the `crypto` feature takes care of the latter by sealing with a random session identifier, other ciphers have to be given a new IV.
+    /// The server renews the ticket before it expires, so the latest one should always be stored.
+    ///
+    pub fn resumption_ticket(&self) -> Option<ResumptionTicket> {
+        *self.state.resumption_ticket.lock()
+    }
+
+    /// Returns the device id the server has assigned to this client, if the user is connected from more than one device (see [`DeviceNotice::Assigned`]).
+    /// The voice sent by the client is tagged with it, so that the receivers keep the voice of the devices of the user apart.
+    pub fn device(&self) -> Option<u32> {
+        *self.state.device.lock()
+    }
+
+    ///
     /// Creates a message manually, you can set the message_type and the bytes manually.
     /// Writes a [`VoipPacket`] to the client's underlying [`UdpSocket`].
     /// Creates a [`VoipPacket`] from the arguments passed in.
+    ///
+    /// # Behavior
+    /// Video and text messages which don't fit in [`MTU_MAX_PACKET_SIZE`] are split into fragments (see [`fragment_message`]), which are paced like [`Client::send_video_frame`] and reassembled by the receiving clients.
+    ///
+    /// # Error
+    /// Returns [`ClientError::Oversized`] if a voice or control message doesn't fit in [`MTU_MAX_PACKET_SIZE`], or [`ClientError::ChannelClosed`] if the client service has shut down.
+    /// Returns [`ClientError::Unauthorized`] instead if the server has closed the session because the client has failed to authenticate or was banned.
+    ///
     pub async fn send_bytes(
         &self,
         voip_message_type: VoipMessageType,
         bytes: &mut dyn Iterator<Item = u8>,
-    ) -> anyhow::Result<()> {
+    ) -> std::result::Result<(), ClientError> {
         // Collect the data
         let data: Vec<u8> = bytes.collect();
 
         // Create the VoipPacket
-        let voip_packet =
-            VoipHeader::new(voip_message_type, self.uuid).create_message_buffer(&data)?;
+        let voip_header = VoipHeader::new(voip_message_type, self.uuid);
+        let voip_packet = voip_header.create_message_buffer(&data)?;
 
-        if voip_packet.inner().len() > MTU_MAX_PACKET_SIZE {
-            panic!("The manually constructed packet is too large.")
+        match check_message_size(&voip_packet, data.len()) {
+            Ok(()) => self
+                .outbound_message_sender
+                .send(voip_packet)
+                .await
+                .map_err(|_| self.service_stopped())?,
+            //Split the oversized messages the receivers can reassemble
+            Err(ClientError::Oversized { .. })
+                if matches!(
+                    voip_header.voip_message_type(),
+                    VoipMessageType::VideoMessage(_) | VoipMessageType::TextMessage(_)
+                ) =>
+            {
+                self.video_sender
+                    .send(fragment_message(&voip_header, &data)?)
+                    .await?
+            }
+            Err(err) => return Err(err),
         }
 
-        // Send it to the receving part
-        self.outbound_message_sender.send(voip_packet).await?;
-
         Ok(())
     }
 }
 
+/// Checks that the `voip_packet` fits in [`MTU_MAX_PACKET_SIZE`], returning [`ClientError::Oversized`] with the largest `payload_length` it could have had otherwise.
+fn check_message_size(
+    voip_packet: &VoipPacket,
+    payload_length: usize,
+) -> std::result::Result<(), ClientError> {
+    //The length prefix is not included in the MTU
+    let message_length = voip_packet.inner().len() - LENGTH_PREFIX_SIZE;
+
+    if message_length > MTU_MAX_PACKET_SIZE {
+        return Err(ClientError::Oversized {
+            size: payload_length,
+            limit: MTU_MAX_PACKET_SIZE.saturating_sub(message_length - payload_length),
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the interval of the heartbeats in the `power_mode`, which is the interval learned by the `keepalive_learner` if there is one.
+fn heartbeat_interval(
+    config: &ClientConfig,
+    power_mode: PowerMode,
+    keepalive_learner: Option<&KeepaliveLearner>,
+) -> Duration {
+    match (keepalive_learner, power_mode) {
+        (Some(keepalive_learner), _) => keepalive_learner.interval(),
+        (None, PowerMode::Normal) => config.heartbeat_interval,
+        (None, PowerMode::LowPower) => config.low_power.heartbeat_interval,
+    }
+}
+
+/// Sends the `deferred_messages` held back in low power mode to the `remote_addr`, reporting the failed sends.
+/// Returns an error if the events can't be reported anymore.
+async fn send_deferred_messages<T: Transport>(
+    socket_handle: &T,
+    deferred_messages: &mut Vec<VoipPacket>,
+    remote_addr: SocketAddr,
+    event_sender: &Sender<ClientEvent>,
+) -> std::result::Result<(), SendError<ClientEvent>> {
+    for deferred_message in deferred_messages.drain(..) {
+        if let Err(err) = socket_handle
+            .send_datagram(deferred_message.inner(), remote_addr)
+            .await
+        {
+            event_sender
+                .send(ClientEvent::Error(ClientError::Send(err)))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Abandons the pending probe of the `keepalive_learner`, as the message sent during its silence refreshes the NAT binding.
+fn cancel_keepalive_probe(keepalive_learner: &mut Option<KeepaliveLearner>) {
+    if let Some(keepalive_learner) = keepalive_learner {
+        keepalive_learner.cancel();
+    }
+}
+
+/// Returns the heartbeat presenting the [`ConnectionId`] issued by the server, or the held [`ResumptionTicket`] until the server has issued an id, or a plain [`ControlMessage::Heartbeat`] if neither is held.
+fn session_heartbeat(
+    connection_id: Option<ConnectionId>,
+    resumption_ticket: &Mutex<Option<ResumptionTicket>>,
+) -> ControlMessage {
+    match (connection_id, *resumption_ticket.lock()) {
+        (Some(connection_id), _) => ControlMessage::ConnectionHeartbeat(connection_id),
+        (None, Some(ticket)) => ControlMessage::Resume(ticket),
+        (None, None) => ControlMessage::Heartbeat,
+    }
+}
+
+/// Sends a [`ControlMessage`] created by the client service to the remote address.
+pub(crate) async fn send_control_message<T: Transport>(
+    socket_handle: &T,
+    control_message: ControlMessage,
+    uuid: Uuid,
+    remote_addr: SocketAddr,
+) -> std::result::Result<(), ClientError> {
+    let voip_packet = VoipHeader::new(VoipMessageType::Control(control_message), uuid)
+        .create_message_buffer(&[])?;
+
+    socket_handle
+        .send_datagram(voip_packet.inner(), remote_addr)
+        .await
+        .map_err(ClientError::Send)?;
+
+    Ok(())
+}
+
+///
+/// Resolves the first endpoint after the `current` one (wrapping around) which can be resolved, racing its IPv6 and IPv4 addresses (see [`resolve`]).
+/// Returns the index of the endpoint and its address, mapped to IPv6 if the `local_addr` is an IPv6 address, so that it matches the source addresses of a dual-stack socket.
+///
+async fn resolve_next_endpoint(
+    endpoints: &[String],
+    current: usize,
+    local_addr: SocketAddr,
+) -> Option<(usize, SocketAddr)> {
+    for offset in 1..=endpoints.len() {
+        let index = (current + offset) % endpoints.len();
+
+        let Ok(mut remote_addr) = resolve(endpoints[index].as_str()).await else {
+            event!(Level::WARN, "Failed to resolve the endpoint {}.", endpoints[index]);
+
+            continue;
+        };
+
+        map_to_local_family(&mut remote_addr, local_addr);
+
+        return Some((index, remote_addr));
+    }
+
+    None
+}
+
+/// Maps the IPv4 `remote_addr` to IPv6 if the `local_addr` is an IPv6 address, so that it matches the source addresses of a dual-stack socket.
+fn map_to_local_family(remote_addr: &mut SocketAddr, local_addr: SocketAddr) {
+    if let (IpAddr::V6(_), IpAddr::V4(v4_addr)) = (local_addr.ip(), remote_addr.ip()) {
+        remote_addr.set_ip(IpAddr::V6(v4_addr.to_ipv6_mapped()));
+    }
+}
+
+///
+/// Resolves the `endpoint` again, returning the address the client should send to.
+///
+/// # Behavior
+/// The current `remote_addr` is kept as long as the endpoint still resolves to it, so that the dual-stack endpoints aren't raced on every resolution.
+/// Otherwise the endpoint is resolved like it was when connecting (see [`resolve`]).
+///
+async fn re_resolve(
+    endpoint: &str,
+    remote_addr: SocketAddr,
+    local_addr: SocketAddr,
+) -> std::io::Result<SocketAddr> {
+    let mut resolved_addrs = lookup_host(endpoint).await?.map(|mut resolved_addr| {
+        map_to_local_family(&mut resolved_addr, local_addr);
+
+        resolved_addr
+    });
+
+    if resolved_addrs.any(|resolved_addr| resolved_addr == remote_addr) {
+        return Ok(remote_addr);
+    }
+
+    let mut resolved_addr = resolve(endpoint).await?;
+
+    map_to_local_family(&mut resolved_addr, local_addr);
+
+    Ok(resolved_addr)
+}
+
 ///
 /// Establises a connection* with a remote address
 ///
 /// # Behavior
-/// Binds to local `[::]:0` address in order to be able to listen for incoming messages.
+/// Binds to the local address of the [`BindConfig`] in order to be able to listen for incoming messages.
 /// The function then automaticly connects* to the specified remote address.
+/// If the remote address has both IPv6 and IPv4 addresses, the first one answering is connected to (see [`resolve`]).
 ///
 /// # Error
 /// Returns an error if it failed to bind to the local address, or failed to resolve remote address from the argument.
 ///
 /// ***Udp is actually connectionless, please refer to [`UdpSocket::connect`] for its behavior.**
 ///
-async fn establish_connection<T: ToSocketAddrs>(remote_addr: T) -> Result<UdpSocket> {
-    let udp_socket = UdpSocket::bind("[::]:0")
+async fn establish_connection<T: ToSocketAddrs>(
+    remote_addr: T,
+    bind_config: &BindConfig,
+) -> std::result::Result<UdpSocket, ClientError> {
+    let mut remote_addr = resolve(remote_addr)
         .await
-        .map_err(UdpError::BindError)?;
+        .map_err(ClientError::NotConnected)?;
+
+    let udp_socket = bind_config.bind().await.map_err(ClientError::SocketIo)?;
+
+    map_to_local_family(
+        &mut remote_addr,
+        udp_socket.local_addr().map_err(ClientError::SocketIo)?,
+    );
 
     udp_socket
         .connect(remote_addr)
         .await
-        .map_err(UdpError::ConnectionError)?;
+        .map_err(ClientError::NotConnected)?;
 
     Ok(udp_socket)
 }
+
+///
+/// Normalizes the loudness of the `samples` to the `target_loudness` (in -dBov).
+///
+/// # Behavior
+/// The gain is calculated from the audio level of every sample, and is limited to [`MAX_NORMALIZATION_GAIN_DB`] so that background noise isn't amplified.
+/// Silent samples are left untouched, and the normalized samples are clamped to the `-1.0..=1.0` range.
+///
+fn normalize_loudness(samples: &mut [f32], target_loudness: u8) {
+    let audio_level = audio_level(samples);
+
+    if audio_level >= AUDIO_LEVEL_SILENCE {
+        return;
+    }
+
+    let gain_db = (audio_level as f32 - target_loudness as f32).min(MAX_NORMALIZATION_GAIN_DB);
+    let gain = 10_f32.powf(gain_db / 20.);
+
+    for sample in samples.iter_mut() {
+        *sample = (*sample * gain).clamp(-1., 1.);
+    }
+}
@@ -0,0 +1,408 @@
+//!
+//! Provides the clustering of [`Server`](super::server::Server)s through Redis, so a single room can span multiple relay processes (or machines).
+//!
+//! Every node of the cluster shares the membership of its peers in the `{prefix}:members` Redis hash, which maps the [`Uuid`] of every member to its [`ClusterMember`] entry.
+//! The messages a node relays are published to the `{prefix}:room:{channel}` Redis channel of their room, and every node with local members in a room is subscribed to its channel, and relays the messages of the other nodes to its local members.
+//! A peer is a member of the room (channel) of the latest voice, video or text message it has sent, and of room `0` until then.
+//!
+//! The Redis connections are driven by Tokio, so clustered servers have to run inside a Tokio runtime whichever [`Runtime`] their service is spawned on.
+//!
+
+use std::{collections::HashMap, net::SocketAddr};
+
+use redis::{aio::PubSubSink, AsyncCommands, RedisResult};
+use tokio::{
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc::{channel, Receiver, Sender},
+    },
+};
+use tokio_stream::StreamExt;
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use super::{runtime::Runtime, server::ServerEvent};
+use crate::packet::{decode_message, VoipPacket};
+
+///
+/// Cluster configuration type definition.
+///
+/// Describes the Redis server the nodes of the cluster share, and the identity of the local node.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterConfig {
+    /// The URL of the Redis server (for example `redis://127.0.0.1:6379`).
+    pub url: String,
+
+    /// The prefix of every Redis key and channel of the cluster, which allows multiple clusters to share a Redis server.
+    pub prefix: String,
+
+    /// The identifier of the local node, which has to be unique in the cluster.
+    pub node_id: Uuid,
+}
+
+impl ClusterConfig {
+    /// Creates a new [`ClusterConfig`] instance for the Redis server at the `url`, with the `silence` prefix and a random node identifier.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            prefix: String::from("silence"),
+            node_id: Uuid::new_v4(),
+        }
+    }
+
+    /// Sets the prefix of every Redis key and channel of the cluster.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the identifier of the local node.
+    pub fn with_node_id(mut self, node_id: Uuid) -> Self {
+        self.node_id = node_id;
+        self
+    }
+
+    ///
+    /// Fetches the members of every node of the cluster.
+    ///
+    /// # Behavior
+    /// The entries which could not be parsed are skipped.
+    /// The members of the nodes which have crashed remain in the hash until their authors join again, their [`ClusterMember::node_id`] can be used to filter them out.
+    ///
+    /// # Error
+    /// Returns an error if the Redis server could not be reached.
+    ///
+    pub async fn members(&self) -> RedisResult<HashMap<Uuid, ClusterMember>> {
+        let mut connection = redis::Client::open(self.url.as_str())?
+            .get_multiplexed_tokio_connection()
+            .await?;
+
+        let members: HashMap<String, String> = connection.hgetall(self.members_key()).await?;
+
+        Ok(members
+            .into_iter()
+            .filter_map(|(author, member)| {
+                Some((author.parse().ok()?, serde_json::from_str(&member).ok()?))
+            })
+            .collect())
+    }
+
+    /// Returns the name of the Redis channel of the `room`.
+    fn room_channel(&self, room: u32) -> String {
+        format!("{}:room:{room}", self.prefix)
+    }
+
+    /// Returns the name of the Redis hash of the members.
+    fn members_key(&self) -> String {
+        format!("{}:members", self.prefix)
+    }
+}
+
+///
+/// Cluster member type definition.
+///
+/// Describes where a member of the cluster is connected, and which room it is in.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ClusterMember {
+    /// The identifier of the node the member is connected to.
+    pub node_id: Uuid,
+
+    /// The address of the member, as seen by its node.
+    pub remote_addr: SocketAddr,
+
+    /// The room (channel) the member is in.
+    pub room: u32,
+}
+
+/// Prefixes the [`VoipPacket`] with the identifier of the node publishing it, so the node can skip its own messages.
+pub fn encode_envelope(node_id: Uuid, voip_packet: &VoipPacket) -> Vec<u8> {
+    let mut envelope = Vec::with_capacity(16 + voip_packet.inner().len());
+
+    envelope.extend_from_slice(node_id.as_bytes());
+    envelope.extend_from_slice(voip_packet.inner());
+
+    envelope
+}
+
+/// Splits a published envelope into the identifier of the node which has published it, and the message buffer.
+/// Returns [`None`] if the envelope is too short.
+pub fn decode_envelope(envelope: &[u8]) -> Option<(Uuid, &[u8])> {
+    let (node_id, message) = envelope.split_at_checked(16)?;
+
+    Some((Uuid::from_slice(node_id).ok()?, message))
+}
+
+/// The handle of the server service to the cluster task, which publishes the messages relayed by the server.
+#[derive(Debug)]
+pub(crate) struct ClusterLink {
+    /// This local channel sends the relayed messages to the cluster task.
+    publish_sender: Sender<VoipPacket>,
+}
+
+impl ClusterLink {
+    /// Publishes the relayed [`VoipPacket`] to the other nodes of the cluster.
+    /// The message is dropped (and a warning is logged) if the cluster task falls behind, so the server service is never blocked by Redis.
+    pub(crate) fn publish(&self, voip_packet: &VoipPacket) {
+        if self.publish_sender.try_send(voip_packet.clone()).is_err() {
+            event!(
+                Level::WARN,
+                "The cluster task has fallen behind, dropping a relayed message."
+            );
+        }
+    }
+}
+
+///
+/// Connects the local node to the cluster, and spawns the cluster task on the [`Runtime`] `R`.
+///
+/// # Behavior
+/// The cluster task keeps the membership of the local peers up to date from the [`ServerEvent`]s, publishes the messages sent through the returned [`ClusterLink`], and sends the messages published by the other nodes through the returned receiver, along with their rooms.
+/// The task stops, and removes the local members from the cluster, once the [`ClusterLink`] has been dropped.
+///
+/// # Error
+/// Returns an error if the Redis server could not be reached.
+///
+pub(crate) async fn connect<R: Runtime>(
+    config: ClusterConfig,
+    mut event_receiver: broadcast::Receiver<ServerEvent>,
+) -> RedisResult<(ClusterLink, Receiver<(u32, VoipPacket)>)> {
+    let client = redis::Client::open(config.url.as_str())?;
+    let connection = client.get_multiplexed_tokio_connection().await?;
+    let (subscriptions, mut messages) = client.get_async_pubsub().await?.split();
+
+    let (publish_sender, mut publish_receiver) = channel::<VoipPacket>(255);
+    let (remote_message_sender, remote_message_receiver) = channel::<(u32, VoipPacket)>(255);
+
+    let mut node = ClusterNode {
+        config,
+        connection,
+        subscriptions,
+        members: HashMap::new(),
+        rooms: HashMap::new(),
+    };
+
+    R::spawn(async move {
+        loop {
+            select! {
+                //Await the messages relayed by the server
+                voip_packet = publish_receiver.recv() => {
+                    let Some(voip_packet) = voip_packet else {
+                        break;
+                    };
+
+                    node.publish(&voip_packet).await;
+                }
+
+                //Await the membership changes of the local peers
+                server_event = event_receiver.recv() => {
+                    match server_event {
+                        Ok(ServerEvent::PeerJoined { remote_addr, author }) => node.update_member(author, Some((remote_addr, 0))).await,
+                        Ok(ServerEvent::PeerMoved { remote_addr, author, room }) => node.update_member(author, Some((remote_addr, room))).await,
+                        Ok(ServerEvent::PeerMigrated { remote_addr, author, .. }) => {
+                            if let Some(room) = node.members.get(&author).copied() {
+                                node.update_member(author, Some((remote_addr, room))).await;
+                            }
+                        },
+                        Ok(ServerEvent::PeerLeft { author, .. } | ServerEvent::PeerClosed { author: Some(author), .. }) => node.update_member(author, None).await,
+                        Ok(_) => (),
+                        Err(RecvError::Lagged(skipped)) => event!(Level::WARN, "The cluster task has skipped {skipped} server events."),
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+
+                //Await the messages published by the other nodes
+                Some(message) = messages.next() => {
+                    let Some(room) = message.get_channel_name().rsplit(':').next().and_then(|room| room.parse::<u32>().ok()) else {
+                        continue;
+                    };
+
+                    let Some((node_id, buffer)) = decode_envelope(message.get_payload_bytes()) else {
+                        event!(Level::ERROR, "Received a malformed envelope from the cluster. Discarding message.");
+
+                        continue;
+                    };
+
+                    if node_id == node.config.node_id {
+                        continue;
+                    }
+
+                    if let Err(err) = decode_message(buffer) {
+                        event!(Level::ERROR, "Failed to decode a VoipPacket from node {node_id}: {err}. Discarding message.");
+
+                        continue;
+                    }
+
+                    //If the receiver was dropped the server was shut down
+                    if remote_message_sender.send((room, VoipPacket::from_validated(buffer.to_vec()))).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        node.leave().await;
+    });
+
+    Ok((ClusterLink { publish_sender }, remote_message_receiver))
+}
+
+/// The state of the local node, owned by the cluster task.
+struct ClusterNode {
+    /// The configuration of the cluster.
+    config: ClusterConfig,
+
+    /// The connection the messages are published, and the members are shared with.
+    connection: redis::aio::MultiplexedConnection,
+
+    /// The subscriptions to the channels of the rooms.
+    subscriptions: PubSubSink,
+
+    /// The room of every local member.
+    members: HashMap<Uuid, u32>,
+
+    /// The amount of local members in every room, which the node is subscribed to.
+    rooms: HashMap<u32, usize>,
+}
+
+impl ClusterNode {
+    /// Publishes the [`VoipPacket`] to the channel of its room, logging any errors.
+    async fn publish(&mut self, voip_packet: &VoipPacket) {
+        let Ok((voip_header, _)) = decode_message(voip_packet.inner()) else {
+            return;
+        };
+
+        if let Err(err) = self
+            .connection
+            .publish::<_, _, ()>(
+                self.config.room_channel(voip_header.channel()),
+                encode_envelope(self.config.node_id, voip_packet),
+            )
+            .await
+        {
+            event!(Level::ERROR, "Failed to publish a message: {err}");
+        }
+    }
+
+    ///
+    /// Moves the local member `author` to a room, or removes it from the cluster if `member` is [`None`].
+    ///
+    /// # Behavior
+    /// The node subscribes to the channel of a room when its first local member joins, and unsubscribes once its last local member leaves.
+    /// Any errors are logged.
+    ///
+    async fn update_member(&mut self, author: Uuid, member: Option<(SocketAddr, u32)>) {
+        let previous_room = match member {
+            Some((_, room)) => self.members.insert(author, room),
+            None => self.members.remove(&author),
+        };
+
+        //The room subscriptions only change if the member has changed rooms, its address may have changed either way
+        let is_same_room = previous_room.is_some() && previous_room == member.map(|(_, room)| room);
+
+        if let Some(previous_room) = previous_room.filter(|_| !is_same_room) {
+            self.leave_room(previous_room).await;
+        }
+
+        let members_key = self.config.members_key();
+
+        let result = match member {
+            Some((remote_addr, room)) => {
+                if !is_same_room {
+                    self.join_room(room).await;
+                }
+
+                let cluster_member = ClusterMember {
+                    node_id: self.config.node_id,
+                    remote_addr,
+                    room,
+                };
+
+                match serde_json::to_string(&cluster_member) {
+                    Ok(cluster_member) => {
+                        self.connection
+                            .hset::<_, _, _, ()>(members_key, author.to_string(), cluster_member)
+                            .await
+                    }
+                    Err(err) => {
+                        event!(Level::ERROR, "Failed to serialize a member: {err}");
+
+                        return;
+                    }
+                }
+            }
+            None => {
+                self.connection
+                    .hdel::<_, _, ()>(members_key, author.to_string())
+                    .await
+            }
+        };
+
+        if let Err(err) = result {
+            event!(
+                Level::ERROR,
+                "Failed to share the membership of {author}: {err}"
+            );
+        }
+    }
+
+    /// Counts a local member joining the `room`, subscribing to its channel if it is the first one.
+    async fn join_room(&mut self, room: u32) {
+        let member_count = self.rooms.entry(room).or_default();
+
+        *member_count += 1;
+
+        if *member_count == 1 {
+            if let Err(err) = self
+                .subscriptions
+                .subscribe(self.config.room_channel(room))
+                .await
+            {
+                event!(Level::ERROR, "Failed to subscribe to room {room}: {err}");
+            }
+        }
+    }
+
+    /// Counts a local member leaving the `room`, unsubscribing from its channel if it was the last one.
+    async fn leave_room(&mut self, room: u32) {
+        let Some(member_count) = self.rooms.get_mut(&room) else {
+            return;
+        };
+
+        *member_count -= 1;
+
+        if *member_count == 0 {
+            self.rooms.remove(&room);
+
+            if let Err(err) = self
+                .subscriptions
+                .unsubscribe(self.config.room_channel(room))
+                .await
+            {
+                event!(
+                    Level::ERROR,
+                    "Failed to unsubscribe from room {room}: {err}"
+                );
+            }
+        }
+    }
+
+    /// Removes every local member from the cluster.
+    async fn leave(&mut self) {
+        if self.members.is_empty() {
+            return;
+        }
+
+        let authors: Vec<String> = self.members.keys().map(Uuid::to_string).collect();
+
+        if let Err(err) = self
+            .connection
+            .hdel::<_, _, ()>(self.config.members_key(), authors)
+            .await
+        {
+            event!(Level::ERROR, "Failed to remove the local members: {err}");
+        }
+    }
+}
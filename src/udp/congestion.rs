@@ -0,0 +1,113 @@
+//!
+//! Provides the [`CongestionController`] of the [`Client`](super::client::Client), which adapts the bitrate of the sent voice to the congestion feedback of the server.
+//!
+//! The server reports the messages it has received from the client periodically (see [`ServerConfig::congestion_feedback`](super::server::ServerConfig::congestion_feedback)).
+//! The messages marked with ECN-CE signal congestion before anything is lost, so the bitrate is lowered in proportion to the marked share, like DCTCP does.
+//! Without ECN on the path the losses are the only signal, the bitrate is lowered by a fixed factor once they exceed a threshold.
+//!
+
+use crate::packet::control::QualityReport;
+
+///
+/// Congestion configuration type definition.
+///
+/// Describes the range of the bitrate, and how fast the [`CongestionController`] moves within it.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct CongestionConfig {
+    /// The lowest bitrate (in bits per second) the voice is lowered to.
+    pub min_bitrate: u32,
+
+    /// The highest bitrate (in bits per second) the voice is raised to, this is also the bitrate the controller starts at.
+    pub max_bitrate: u32,
+
+    /// The bitrate (in bits per second) added after every feedback without any congestion.
+    pub increase_step: u32,
+
+    /// The factor the bitrate is multiplied with when the loss exceeds the [`CongestionConfig::loss_threshold`].
+    pub loss_decrease_factor: f32,
+
+    /// The share (between `0` and `1`) of the lost messages above which the path is considered congested.
+    pub loss_threshold: f32,
+}
+
+impl Default for CongestionConfig {
+    fn default() -> Self {
+        Self {
+            min_bitrate: 6_000,
+            max_bitrate: 64_000,
+            increase_step: 2_000,
+            loss_decrease_factor: 0.85,
+            loss_threshold: 0.02,
+        }
+    }
+}
+
+///
+/// Congestion controller type definition.
+///
+/// Calculates the target bitrate of the sent voice from the [`QualityReport`]s the server sends back about the messages it has received.
+///
+#[derive(Debug, Clone)]
+pub struct CongestionController {
+    /// The configuration of the controller.
+    config: CongestionConfig,
+
+    /// The current target bitrate, in bits per second.
+    bitrate: u32,
+}
+
+impl CongestionController {
+    /// Creates a new [`CongestionController`] instance, starting at the [`CongestionConfig::max_bitrate`].
+    pub fn new(config: CongestionConfig) -> Self {
+        Self {
+            bitrate: config.max_bitrate,
+            config,
+        }
+    }
+
+    /// Returns the current target bitrate, in bits per second.
+    pub fn bitrate(&self) -> u32 {
+        self.bitrate
+    }
+
+    ///
+    /// Updates the target bitrate with the feedback of the server.
+    ///
+    /// # Behavior
+    /// If any message was marked with ECN-CE, the bitrate is lowered by half of the marked share (so a fully marked window halves it).
+    /// Otherwise if the loss exceeds the [`CongestionConfig::loss_threshold`] the bitrate is multiplied with the [`CongestionConfig::loss_decrease_factor`], or raised by the [`CongestionConfig::increase_step`] if it doesn't.
+    /// The reports without any messages are ignored, as the client hasn't sent anything to measure.
+    ///
+    /// Returns the new target bitrate if it has changed.
+    ///
+    pub fn on_feedback(&mut self, quality_report: &QualityReport) -> Option<u32> {
+        if quality_report.packets_received == 0 {
+            return None;
+        }
+
+        let expected = quality_report.packets_received + quality_report.packets_lost;
+        let loss = quality_report.packets_lost as f32 / expected as f32;
+        let ce_share = quality_report
+            .packets_ce
+            .min(quality_report.packets_received) as f32
+            / quality_report.packets_received as f32;
+
+        let bitrate = if ce_share > 0. {
+            (self.bitrate as f32 * (1. - ce_share / 2.)) as u32
+        } else if loss > self.config.loss_threshold {
+            (self.bitrate as f32 * self.config.loss_decrease_factor) as u32
+        } else {
+            self.bitrate.saturating_add(self.config.increase_step)
+        }
+        .clamp(self.config.min_bitrate, self.config.max_bitrate);
+
+        if bitrate == self.bitrate {
+            return None;
+        }
+
+        self.bitrate = bitrate;
+
+        Some(bitrate)
+    }
+}
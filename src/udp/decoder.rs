@@ -0,0 +1,246 @@
+//!
+//! Provides the [`DecoderLimits`], which manage the lifecycle of the per-author decoders of the [`Client`](super::client::Client).
+//!
+//! The decoders of an author (see [`VoiceDecoders`](super::voice::VoiceDecoders) and [`VideoDecoders`](super::video::VideoDecoders)) are created the first time the author is seen, and they are torn down when the author leaves the session or stops sending for [`DecoderLimits::idle_timeout`].
+//! Decoding is expensive in large sessions, so the amount of concurrently decoded authors can be capped, and the application can decide which new authors are decoded with a [`DecoderAdmission`] callback.
+//! The voice can be limited to the loudest speakers instead (see [`DecoderLimits::max_speakers`]), the voice messages of the other speakers are only counted in their [`SpeakerStats`].
+//!
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::packet::AUDIO_LEVEL_SILENCE;
+
+/// The time after which a decoded speaker who hasn't been heard can be replaced by any other speaker.
+pub const SPEAKER_WINDOW: Duration = Duration::from_secs(1);
+
+/// The amount (in dB) a speaker has to be louder than the quietest decoded speaker to replace them.
+const SPEAKER_SWITCH_MARGIN: f32 = 6.;
+
+/// The weight of the latest audio level in the smoothed loudness of a speaker.
+const LOUDNESS_SMOOTHING: f32 = 0.3;
+
+/// Decoder admission callback type definition.
+/// Decides whether the decoders of a new author are created, from the [`Uuid`] of the author and the amount of authors decoded already.
+pub type DecoderAdmission = Arc<dyn Fn(Uuid, usize) -> bool + Send + Sync>;
+
+///
+/// Decoder limits type definition.
+///
+/// Describes when the decoders of the remote authors are torn down, and which new authors are decoded.
+/// The default limits tear the decoders down after 30 seconds of silence, and decode every author.
+///
+#[derive(Clone)]
+pub struct DecoderLimits {
+    /// The time after which the decoders of an author who has stopped sending are torn down, they are recreated once the author sends again.
+    /// The decoders are only torn down when the author leaves the session if this is [`None`].
+    pub idle_timeout: Option<Duration>,
+
+    /// The highest amount of authors decoded at the same time, the media of the other authors isn't decoded until a decoder is torn down.
+    pub max_authors: Option<usize>,
+
+    /// The callback deciding about the new authors, which is only asked if [`DecoderLimits::max_authors`] isn't reached.
+    pub admission: Option<DecoderAdmission>,
+
+    /// The highest amount of speakers whose voice is decoded at the same time, the loudest recently heard speakers are decoded.
+    /// A speaker only replaces a decoded one if they are louder by a margin, or the decoded speaker hasn't been heard for the [`SPEAKER_WINDOW`], so the decoded speakers don't flicker.
+    /// Every speaker is decoded if this is [`None`].
+    pub max_speakers: Option<usize>,
+}
+
+impl Default for DecoderLimits {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Some(Duration::from_secs(30)),
+            max_authors: None,
+            admission: None,
+            max_speakers: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for DecoderLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecoderLimits")
+            .field("idle_timeout", &self.idle_timeout)
+            .field("max_authors", &self.max_authors)
+            .field("admission", &self.admission.is_some())
+            .field("max_speakers", &self.max_speakers)
+            .finish()
+    }
+}
+
+impl DecoderLimits {
+    /// Sets the [`DecoderAdmission`] callback deciding about the new authors.
+    pub fn with_admission(
+        mut self,
+        admission: impl Fn(Uuid, usize) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.admission = Some(Arc::new(admission));
+
+        self
+    }
+
+    /// Returns whether the decoders of the new `author` are created, while `decoded_authors` authors are decoded already.
+    pub fn admits(&self, author: Uuid, decoded_authors: usize) -> bool {
+        self.max_authors
+            .is_none_or(|max_authors| decoded_authors < max_authors)
+            && self
+                .admission
+                .as_ref()
+                .is_none_or(|admission| admission(author, decoded_authors))
+    }
+
+    /// Returns whether a decoder last used at `last_used` has been idle for too long at `now`.
+    pub(crate) fn is_idle(&self, last_used: Instant, now: Instant) -> bool {
+        self.idle_timeout
+            .is_some_and(|idle_timeout| now.duration_since(last_used) >= idle_timeout)
+    }
+}
+
+/// The voice statistics of a remote speaker, which are kept even if their voice isn't decoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpeakerStats {
+    /// Whether the voice of the speaker is currently decoded.
+    pub is_decoded: bool,
+
+    /// The audio level (in -dBov) of the last voice message of the speaker, if the speaker has set one.
+    pub audio_level: Option<u8>,
+
+    /// The amount of voice messages of the speaker which weren't decoded, as louder speakers were decoded instead.
+    pub skipped_messages: u64,
+}
+
+/// The loudness of a single speaker.
+#[derive(Debug, Clone, Copy)]
+struct SpeakerLoudness {
+    /// The smoothed loudness of the speaker, in dB above silence.
+    loudness: f32,
+
+    /// The time the last voice message was received from the speaker.
+    last_heard: Instant,
+}
+
+/// Selects the loudest speakers whose voice is decoded, see [`DecoderLimits::max_speakers`].
+#[derive(Debug, Default)]
+pub(crate) struct SpeakerSelector {
+    /// The loudness of every speaker heard.
+    speakers: HashMap<Uuid, SpeakerLoudness>,
+
+    /// The speakers whose voice is decoded.
+    selected: HashSet<Uuid>,
+
+    /// The statistics of every speaker heard, shared with the [`Client`](super::client::Client).
+    stats: Arc<Mutex<HashMap<Uuid, SpeakerStats>>>,
+}
+
+impl SpeakerSelector {
+    /// Creates a new [`SpeakerSelector`] instance, recording the statistics of the speakers into `stats`.
+    pub(crate) fn new(stats: Arc<Mutex<HashMap<Uuid, SpeakerStats>>>) -> Self {
+        Self {
+            speakers: HashMap::new(),
+            selected: HashSet::new(),
+            stats,
+        }
+    }
+
+    ///
+    /// Records a voice message of the `author` with the `audio_level`, received at `now`.
+    ///
+    /// # Behavior
+    /// Returns whether the message is decoded, and the speaker whose voice isn't decoded anymore as the author has replaced them.
+    /// The messages without an audio level count as silent.
+    ///
+    pub(crate) fn observe(
+        &mut self,
+        author: Uuid,
+        audio_level: Option<u8>,
+        now: Instant,
+        max_speakers: Option<usize>,
+    ) -> (bool, Option<Uuid>) {
+        let level = audio_level.unwrap_or(AUDIO_LEVEL_SILENCE);
+        let loudness = (AUDIO_LEVEL_SILENCE - level.min(AUDIO_LEVEL_SILENCE)) as f32;
+
+        let speaker = self
+            .speakers
+            .entry(author)
+            .and_modify(|speaker| {
+                speaker.loudness += (loudness - speaker.loudness) * LOUDNESS_SMOOTHING;
+                speaker.last_heard = now;
+            })
+            .or_insert(SpeakerLoudness {
+                loudness,
+                last_heard: now,
+            });
+
+        let author_loudness = speaker.loudness;
+
+        let (is_decoded, replaced) = match max_speakers {
+            None => (true, None),
+            Some(_) if self.selected.contains(&author) => (true, None),
+            Some(max_speakers) if self.selected.len() < max_speakers => {
+                self.selected.insert(author);
+
+                (true, None)
+            }
+            Some(_) => {
+                //The decoded speakers who haven't been heard recently are replaced first
+                let quietest = self
+                    .selected
+                    .iter()
+                    .map(|selected| {
+                        let loudness = self
+                            .speakers
+                            .get(selected)
+                            .filter(|speaker| {
+                                now.duration_since(speaker.last_heard) < SPEAKER_WINDOW
+                            })
+                            .map_or(f32::NEG_INFINITY, |speaker| speaker.loudness);
+
+                        (*selected, loudness)
+                    })
+                    .min_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs));
+
+                match quietest {
+                    Some((quietest, quietest_loudness))
+                        if author_loudness > quietest_loudness + SPEAKER_SWITCH_MARGIN =>
+                    {
+                        self.selected.remove(&quietest);
+                        self.selected.insert(author);
+
+                        (true, Some(quietest))
+                    }
+                    _ => (false, None),
+                }
+            }
+        };
+
+        let mut stats = self.stats.lock();
+
+        let author_stats = stats.entry(author).or_default();
+
+        author_stats.is_decoded = is_decoded;
+        author_stats.audio_level = audio_level;
+        author_stats.skipped_messages += !is_decoded as u64;
+
+        if let Some(replaced_stats) = replaced.and_then(|replaced| stats.get_mut(&replaced)) {
+            replaced_stats.is_decoded = false;
+        }
+
+        (is_decoded, replaced)
+    }
+
+    /// Forgets the `author` (for example when the author has left the session).
+    pub(crate) fn remove(&mut self, author: Uuid) {
+        self.speakers.remove(&author);
+        self.selected.remove(&author);
+        self.stats.lock().remove(&author);
+    }
+}
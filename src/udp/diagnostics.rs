@@ -0,0 +1,630 @@
+//!
+//! Provides the connectivity diagnostics of the [`Client`](super::client::Client), which back a "test my connection" screen of the applications.
+//!
+//! [`Client::run_diagnostics`](super::client::Client::run_diagnostics) checks that a local socket can be bound, the public address of the host through a STUN server, the round trip time of the server, the sustained loss at a test bitrate, and the sanity of the system clock.
+//! The server is probed with [`ControlMessage::Ping`]s sent through the session of the client, which the server answers with [`ControlMessage::Pong`]s without forwarding them.
+//! The pings are timed with the [`Runtime`](super::runtime::Runtime) of the client, while the local socket and the STUN check need a Tokio runtime, they report [`DiagnosticError::UnsupportedRuntime`] outside of one.
+//!
+
+use std::{
+    io::{self, ErrorKind},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, SystemTime},
+};
+
+use tokio::{
+    net::{lookup_host, UdpSocket},
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc::Sender,
+    },
+    time::Instant,
+};
+use uuid::Uuid;
+
+use super::{event::ClientError, runtime::SleepFn};
+use crate::{
+    packet::{
+        control::ControlMessage, HeaderFlags, VoipHeader, VoipMessageType, VoipPacket,
+        LENGTH_PREFIX_SIZE,
+    },
+    MTU_MAX_PACKET_SIZE,
+};
+
+/// The magic cookie of the STUN messages, as defined by [RFC 5389](https://www.rfc-editor.org/rfc/rfc5389).
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+
+/// The type of the STUN binding requests.
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+
+/// The type of the successful STUN binding responses.
+const STUN_BINDING_RESPONSE: u16 = 0x0101;
+
+/// The type of the MAPPED-ADDRESS attribute, sent by the servers predating RFC 5389.
+const STUN_MAPPED_ADDRESS: u16 = 0x0001;
+
+/// The type of the XOR-MAPPED-ADDRESS attribute.
+const STUN_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+/// The length of the header of the STUN messages.
+const STUN_HEADER_LENGTH: usize = 20;
+
+/// The interval the STUN binding request is retransmitted at, until it is answered.
+const STUN_RETRANSMISSION_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The earliest time the system clock may be set to, the clocks set before it are considered unset (for example a device without a battery backed clock).
+const EARLIEST_SANE_TIME: Duration = Duration::from_secs(1_577_836_800);
+
+///
+/// Diagnostics configuration type definition.
+///
+/// Describes which checks [`Client::run_diagnostics`](super::client::Client::run_diagnostics) runs, and how long they take.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticsConfig {
+    /// The STUN server the public address of the host is queried from, as a `host:port` string (for example `stun.example.com:3478`).
+    /// The STUN check is skipped if this is [`None`].
+    pub stun_server: Option<String>,
+
+    /// The amount of the [`ControlMessage::Ping`]s the round trip time of the server is measured with.
+    pub echo_count: u32,
+
+    /// The interval between the pings of the round trip time measurement.
+    pub echo_interval: Duration,
+
+    /// The bitrate (in bits per second) the loss is measured at, every ping of the loss test follows a padding message to reach it.
+    /// The padding in front of a single ping is capped at [`MTU_MAX_PACKET_SIZE`].
+    pub loss_test_bitrate: u32,
+
+    /// The interval between the pings of the loss test, which should match the frame duration of the voice.
+    pub loss_test_interval: Duration,
+
+    /// The duration of the loss test.
+    pub loss_test_duration: Duration,
+
+    /// The time the pings and the STUN request are waited for after they were sent, before they are considered lost.
+    pub timeout: Duration,
+
+    /// The largest difference between the time elapsed on the system clock and on the monotonic clock during the diagnostics, before the system clock is considered insane.
+    pub max_clock_drift: Duration,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            stun_server: None,
+            echo_count: 5,
+            echo_interval: Duration::from_millis(200),
+            loss_test_bitrate: 64_000,
+            loss_test_interval: Duration::from_millis(20),
+            loss_test_duration: Duration::from_secs(3),
+            timeout: Duration::from_secs(1),
+            max_clock_drift: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Diagnostic errors.
+#[derive(thiserror::Error, Debug)]
+pub enum DiagnosticError {
+    /// This error is thrown when no local socket could be bound.
+    #[error("Failed to bind a local socket: {0}")]
+    Bind(io::Error),
+
+    /// This error is thrown when the STUN server could not be resolved, or it hasn't answered the binding request.
+    #[error("Failed to reach the STUN server: {0}")]
+    Stun(io::Error),
+
+    /// This error is thrown when the STUN server has answered without a mapped address.
+    #[error("The STUN server has answered without a mapped address.")]
+    InvalidStunResponse,
+
+    /// This error is thrown when the server hasn't answered any of the pings.
+    #[error("The server hasn't answered any of the {0} pings.")]
+    Unanswered(u32),
+
+    /// This error is thrown when the pings could not be sent through the client service.
+    #[error("Failed to send the pings: {0}")]
+    Client(#[from] ClientError),
+
+    /// This error is thrown when the check needs a Tokio runtime, but it was run outside of one.
+    #[error("The check can only be run in a Tokio runtime.")]
+    UnsupportedRuntime,
+}
+
+///
+/// Echo report type definition.
+///
+/// The round trip times of the pings answered by the server.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoReport {
+    /// The amount of the pings sent.
+    pub sent: u32,
+
+    /// The amount of the pings answered.
+    pub received: u32,
+
+    /// The lowest round trip time.
+    pub min_round_trip_time: Duration,
+
+    /// The average round trip time.
+    pub average_round_trip_time: Duration,
+
+    /// The highest round trip time.
+    pub max_round_trip_time: Duration,
+}
+
+///
+/// Loss report type definition.
+///
+/// The outcome of the pings sent at the test bitrate.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LossReport {
+    /// The bitrate (in bits per second) the pings were sent at.
+    pub bitrate: u32,
+
+    /// The amount of the pings sent.
+    pub sent: u32,
+
+    /// The amount of the pings answered, the pings lost in either direction aren't answered.
+    pub received: u32,
+}
+
+impl LossReport {
+    /// Returns the loss (between `0` and `1`) of the pings.
+    pub fn loss(&self) -> f32 {
+        if self.sent == 0 {
+            return 0.;
+        }
+
+        1. - self.received as f32 / self.sent as f32
+    }
+}
+
+///
+/// Clock report type definition.
+///
+/// The state of the system clock, which the timestamps of the recordings and the logs depend on.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockReport {
+    /// The time of the system clock at the end of the diagnostics.
+    pub system_time: SystemTime,
+
+    /// The difference between the time elapsed on the system clock and on the monotonic clock during the diagnostics (for example because the clock was stepped).
+    pub drift: Duration,
+
+    /// Whether the system clock is set, and its drift is within the [`DiagnosticsConfig::max_clock_drift`].
+    pub is_sane: bool,
+}
+
+///
+/// Diagnostics report type definition.
+///
+/// The outcome of every check of [`Client::run_diagnostics`](super::client::Client::run_diagnostics), which the applications can show to the user.
+///
+#[derive(Debug)]
+pub struct DiagnosticsReport {
+    /// The local address a new socket was bound to, of the same family as the remote address of the client.
+    pub local_bind: Result<SocketAddr, DiagnosticError>,
+
+    /// The public address of the host, as seen by the STUN server.
+    /// This is [`None`] if no [`DiagnosticsConfig::stun_server`] is set, or no local socket could be bound.
+    pub stun: Option<Result<SocketAddr, DiagnosticError>>,
+
+    /// The round trip times of the server.
+    pub echo: Result<EchoReport, DiagnosticError>,
+
+    /// The loss at the [`DiagnosticsConfig::loss_test_bitrate`].
+    pub loss: Result<LossReport, DiagnosticError>,
+
+    /// The state of the system clock.
+    pub clock: ClockReport,
+}
+
+impl DiagnosticsReport {
+    /// Returns whether every check has passed, and the loss of the loss test is below the `max_loss` (between `0` and `1`).
+    pub fn is_healthy(&self, max_loss: f32) -> bool {
+        self.local_bind.is_ok()
+            && !matches!(self.stun, Some(Err(_)))
+            && self.echo.is_ok()
+            && self
+                .loss
+                .as_ref()
+                .is_ok_and(|loss_report| loss_report.loss() < max_loss)
+            && self.clock.is_sane
+    }
+}
+
+/// Binds a new socket to the unspecified address of the family of the `remote_addr`, which checks that the host can bind local sockets at all.
+/// Returns [`DiagnosticError::UnsupportedRuntime`] outside of a Tokio runtime.
+pub(crate) async fn bind_probe_socket(
+    remote_addr: SocketAddr,
+) -> Result<UdpSocket, DiagnosticError> {
+    //The probe socket and the resolution of the STUN server are driven by Tokio
+    if tokio::runtime::Handle::try_current().is_err() {
+        return Err(DiagnosticError::UnsupportedRuntime);
+    }
+
+    let local_addr = match remote_addr {
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+    };
+
+    UdpSocket::bind(local_addr)
+        .await
+        .map_err(DiagnosticError::Bind)
+}
+
+///
+/// Queries the public address of the host from the `stun_server` with a STUN binding request, sent from the `socket_handle`.
+///
+/// # Behavior
+/// The request is retransmitted every [`STUN_RETRANSMISSION_INTERVAL`], until the server answers or the `timeout` elapses, timed with the `sleep` of the runtime of the client.
+/// The IPv4 addresses of the server are mapped to IPv6 if the socket is an IPv6 one.
+///
+/// # Error
+/// Returns an error if the server could not be resolved, it hasn't answered in time, or its answer has no mapped address.
+///
+pub(crate) async fn query_stun(
+    socket_handle: &UdpSocket,
+    stun_server: &str,
+    timeout: Duration,
+    sleep: SleepFn,
+) -> Result<SocketAddr, DiagnosticError> {
+    let local_addr = socket_handle.local_addr().map_err(DiagnosticError::Stun)?;
+    let stun_addrs: Vec<SocketAddr> = lookup_host(stun_server)
+        .await
+        .map_err(DiagnosticError::Stun)?
+        .collect();
+
+    //Prefer the addresses of the family of the socket, the IPv6 sockets reach the IPv4 addresses through their mapped form
+    let stun_addr = stun_addrs
+        .iter()
+        .find(|stun_addr| stun_addr.is_ipv6() == local_addr.is_ipv6())
+        .copied()
+        .or_else(|| {
+            stun_addrs.first().map(|stun_addr| match stun_addr.ip() {
+                IpAddr::V4(ip) if local_addr.is_ipv6() => {
+                    SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), stun_addr.port())
+                }
+                _ => *stun_addr,
+            })
+        })
+        .ok_or_else(|| {
+            DiagnosticError::Stun(io::Error::new(
+                ErrorKind::NotFound,
+                "The STUN server could not be resolved to any address.",
+            ))
+        })?;
+
+    let mut transaction_id = [0; 12];
+
+    transaction_id.copy_from_slice(&Uuid::new_v4().as_bytes()[..12]);
+
+    let mut request = Vec::with_capacity(STUN_HEADER_LENGTH);
+
+    request.extend_from_slice(&STUN_BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0_u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&transaction_id);
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = vec![0; MTU_MAX_PACKET_SIZE];
+
+    while Instant::now() < deadline {
+        socket_handle
+            .send_to(&request, stun_addr)
+            .await
+            .map_err(DiagnosticError::Stun)?;
+
+        let retransmit_at = (Instant::now() + STUN_RETRANSMISSION_INTERVAL).min(deadline);
+
+        //Wait for the answer until the next retransmission
+        loop {
+            let (byte_count, remote_addr) = select! {
+                received = socket_handle.recv_from(&mut buf) => received.map_err(DiagnosticError::Stun)?,
+                _ = sleep(retransmit_at.saturating_duration_since(Instant::now())) => break,
+            };
+
+            if remote_addr != stun_addr {
+                continue;
+            }
+
+            if let Some(mapped_addr) = parse_stun_response(&buf[..byte_count], &transaction_id) {
+                return mapped_addr;
+            }
+        }
+    }
+
+    Err(DiagnosticError::Stun(io::Error::new(
+        ErrorKind::TimedOut,
+        "The STUN server hasn't answered the binding request.",
+    )))
+}
+
+/// Parses the mapped address of a STUN binding response to the request with the `transaction_id`, returning [`None`] if the `response` doesn't answer the request.
+fn parse_stun_response(
+    response: &[u8],
+    transaction_id: &[u8; 12],
+) -> Option<Result<SocketAddr, DiagnosticError>> {
+    if response.len() < STUN_HEADER_LENGTH
+        || u16::from_be_bytes([response[0], response[1]]) != STUN_BINDING_RESPONSE
+        || response[4..8] != STUN_MAGIC_COOKIE.to_be_bytes()
+        || &response[8..20] != transaction_id
+    {
+        return None;
+    }
+
+    let length = u16::from_be_bytes([response[2], response[3]]) as usize;
+    let mut attributes = response.get(STUN_HEADER_LENGTH..STUN_HEADER_LENGTH + length)?;
+    let mut mapped_addr = None;
+
+    //The attributes are padded to a multiple of 4 bytes
+    while attributes.len() >= 4 {
+        let attribute_type = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let attribute_length = u16::from_be_bytes([attributes[2], attributes[3]]) as usize;
+        let value = attributes.get(4..4 + attribute_length)?;
+
+        match attribute_type {
+            STUN_XOR_MAPPED_ADDRESS => {
+                return Some(
+                    parse_stun_address(value, Some(transaction_id))
+                        .ok_or(DiagnosticError::InvalidStunResponse),
+                )
+            }
+            STUN_MAPPED_ADDRESS => mapped_addr = parse_stun_address(value, None),
+            _ => (),
+        }
+
+        attributes = attributes
+            .get((4 + attribute_length).next_multiple_of(4)..)
+            .unwrap_or_default();
+    }
+
+    Some(mapped_addr.ok_or(DiagnosticError::InvalidStunResponse))
+}
+
+/// Parses the value of a (XOR-)MAPPED-ADDRESS attribute, the address is XOR-ed with the magic cookie and the `transaction_id` if it is set.
+fn parse_stun_address(value: &[u8], transaction_id: Option<&[u8; 12]>) -> Option<SocketAddr> {
+    let mut mask = [0; 16];
+
+    if let Some(transaction_id) = transaction_id {
+        mask[..4].copy_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+        mask[4..].copy_from_slice(transaction_id);
+    }
+
+    let port = u16::from_be_bytes([value.get(2)? ^ mask[0], value.get(3)? ^ mask[1]]);
+
+    let ip = match value.get(1)? {
+        0x01 => {
+            let octets: [u8; 4] = value.get(4..8)?.try_into().ok()?;
+
+            IpAddr::V4(Ipv4Addr::from(
+                u32::from_be_bytes(octets) ^ u32::from_be_bytes(mask[..4].try_into().ok()?),
+            ))
+        }
+        0x02 => {
+            let octets: [u8; 16] = value.get(4..20)?.try_into().ok()?;
+
+            IpAddr::V6(Ipv6Addr::from(
+                u128::from_be_bytes(octets) ^ u128::from_be_bytes(mask),
+            ))
+        }
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(ip, port))
+}
+
+/// The time a diagnostic ping was sent at, and the time its answer was received at.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PingTiming {
+    /// The time the ping was sent at.
+    pub sent_at: Instant,
+
+    /// The time the answer was received at, [`None`] if the ping was lost.
+    pub answered_at: Option<Instant>,
+}
+
+impl PingTiming {
+    /// Returns the round trip time of the ping, [`None`] if it was lost.
+    pub fn round_trip_time(&self) -> Option<Duration> {
+        self.answered_at
+            .map(|answered_at| answered_at.saturating_duration_since(self.sent_at))
+    }
+}
+
+/// The diagnostic pings of a single probe of the server, see [`probe_server`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PingProbe {
+    /// The sequence number of the first ping, the sequence numbers of the others follow it.
+    pub first_sequence: u32,
+
+    /// The amount of pings sent.
+    pub count: u32,
+
+    /// The interval the pings are sent at.
+    pub interval: Duration,
+
+    /// The bytes of padding sent in front of every ping.
+    pub padding: usize,
+
+    /// The time the answers are waited for after the last ping was sent, the pings which weren't answered by then are considered lost.
+    pub timeout: Duration,
+}
+
+///
+/// Probes the server with the pings of the [`PingProbe`], which are sent through the client service as [`ControlMessage::Ping`]s.
+/// The answers of the pings are received from the `pong_receiver`.
+///
+/// # Behavior
+/// Returns the [`PingTiming`] of every ping in the order they were sent.
+/// The padding is sent as a voice message marked with [`HeaderFlags::PADDING`] right before every ping, and it is capped so that the message fits in [`MTU_MAX_PACKET_SIZE`].
+/// The ping queues up behind its padding, so it is delayed and lost like the padding is.
+/// The pings are timed with the `sleep` of the runtime of the client.
+///
+/// # Error
+/// Returns an error if the pings could not be encoded, or the client service has shut down.
+///
+pub(crate) async fn probe_server(
+    outbound_message_sender: &Sender<VoipPacket>,
+    mut pong_receiver: broadcast::Receiver<u32>,
+    uuid: Uuid,
+    ping_probe: PingProbe,
+    sleep: SleepFn,
+) -> Result<Vec<PingTiming>, DiagnosticError> {
+    let PingProbe {
+        first_sequence,
+        count,
+        interval,
+        padding,
+        timeout,
+    } = ping_probe;
+
+    //Control messages can't have a body, so the padding is sent as a message of its own in front of every ping
+    let padding_message = match padding {
+        0 => None,
+        padding => {
+            //The header of the longest body is the longest one
+            let header_length = VoipHeader::new(
+                VoipMessageType::VoiceMessage(MTU_MAX_PACKET_SIZE as u64),
+                uuid,
+            )
+            .create_message_buffer(&[])
+            .map_err(ClientError::from)?
+            .inner()
+            .len()
+                - LENGTH_PREFIX_SIZE;
+            let padding = vec![0; padding.min(MTU_MAX_PACKET_SIZE.saturating_sub(header_length))];
+
+            Some(
+                VoipHeader::new(VoipMessageType::VoiceMessage(padding.len() as u64), uuid)
+                    .with_flags(HeaderFlags::default() | HeaderFlags::PADDING)
+                    .create_message_buffer(&padding)
+                    .map_err(ClientError::from)?,
+            )
+        }
+    };
+
+    let mut sent_at: Vec<Instant> = Vec::with_capacity(count as usize);
+    let mut answered_at: Vec<Option<Instant>> = vec![None; count as usize];
+    let mut next_send = Instant::now();
+
+    loop {
+        let is_sending = sent_at.len() < count as usize;
+
+        //Wait for the answers until the timeout after the last ping, unless every ping was answered
+        let deadline = match sent_at.last() {
+            _ if is_sending => next_send,
+            Some(last_sent_at) if answered_at.iter().any(Option::is_none) => {
+                *last_sent_at + timeout
+            }
+            _ => break,
+        };
+
+        select! {
+            _ = sleep(deadline.saturating_duration_since(Instant::now())) => {
+                if !is_sending {
+                    break;
+                }
+
+                if let Some(padding_message) = padding_message.as_ref() {
+                    outbound_message_sender
+                        .send(padding_message.clone())
+                        .await
+                        .map_err(ClientError::from)?;
+                }
+
+                let sequence = first_sequence.wrapping_add(sent_at.len() as u32);
+                let voip_packet = VoipHeader::new(VoipMessageType::Control(ControlMessage::Ping(sequence)), uuid)
+                    .create_message_buffer(&[])
+                    .map_err(ClientError::from)?;
+
+                outbound_message_sender
+                    .send(voip_packet)
+                    .await
+                    .map_err(ClientError::from)?;
+
+                sent_at.push(Instant::now());
+                next_send += interval;
+            }
+
+            pong = pong_receiver.recv() => match pong {
+                Ok(sequence) => {
+                    let index = sequence.wrapping_sub(first_sequence) as usize;
+
+                    //Only the first answer of a ping counts
+                    if let Some(ping_answered_at) = answered_at.get_mut(index).filter(|_| index < sent_at.len()) {
+                        ping_answered_at.get_or_insert_with(Instant::now);
+                    }
+                },
+                //The answers missed are counted as lost
+                Err(RecvError::Lagged(_)) => (),
+                Err(RecvError::Closed) => return Err(ClientError::ChannelClosed.into()),
+            },
+        }
+    }
+
+    Ok(sent_at
+        .into_iter()
+        .zip(answered_at)
+        .map(|(sent_at, answered_at)| PingTiming {
+            sent_at,
+            answered_at,
+        })
+        .collect())
+}
+
+/// Summarizes the round trip times of the answered pings, returning [`DiagnosticError::Unanswered`] if none of them was answered.
+pub(crate) fn echo_report(ping_timings: &[PingTiming]) -> Result<EchoReport, DiagnosticError> {
+    let answered: Vec<Duration> = ping_timings
+        .iter()
+        .filter_map(PingTiming::round_trip_time)
+        .collect();
+    let sent = ping_timings.len() as u32;
+
+    let (Some(min_round_trip_time), Some(max_round_trip_time)) =
+        (answered.iter().min(), answered.iter().max())
+    else {
+        return Err(DiagnosticError::Unanswered(sent));
+    };
+
+    Ok(EchoReport {
+        sent,
+        received: answered.len() as u32,
+        min_round_trip_time: *min_round_trip_time,
+        average_round_trip_time: answered.iter().sum::<Duration>() / answered.len() as u32,
+        max_round_trip_time: *max_round_trip_time,
+    })
+}
+
+/// Checks the system clock, from the times of the system and the monotonic clock sampled at the start of the diagnostics.
+pub(crate) fn check_clock(
+    started_at: std::time::Instant,
+    system_started_at: SystemTime,
+    max_clock_drift: Duration,
+) -> ClockReport {
+    let system_time = SystemTime::now();
+    let elapsed = started_at.elapsed();
+
+    //A clock stepped backwards can't be measured, so the whole elapsed time is counted as drift
+    let drift = match system_time.duration_since(system_started_at) {
+        Ok(system_elapsed) if system_elapsed > elapsed => system_elapsed - elapsed,
+        Ok(system_elapsed) => elapsed - system_elapsed,
+        Err(err) => err.duration() + elapsed,
+    };
+
+    let is_set = system_time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .is_ok_and(|since_epoch| since_epoch >= EARLIEST_SANE_TIME);
+
+    ClockReport {
+        system_time,
+        drift,
+        is_sane: is_set && drift <= max_clock_drift,
+    }
+}
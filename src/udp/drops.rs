@@ -0,0 +1,291 @@
+//!
+//! Provides the accounting of the messages dropped by the [`Server`](super::server::Server), so that the operators can tell where the messages went.
+//!
+//! Every message the server service discards is counted by its [`DropReason`] (see [`ServerStats::drops`](super::server::ServerStats::drops)).
+//! The first dropped message of every reason is logged in each [`DropLogConfig::interval`], the ones dropped in between are only counted, so a flood of dropped messages doesn't flood the logs too.
+//!
+
+use std::{
+    fmt::Display,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use tracing::{event, Level};
+
+use crate::packet::PacketError;
+
+/// The reason the [`Server`](super::server::Server) has dropped a message for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DropReason {
+    /// The source address of the datagram is filtered by the [`ServerConfig::source_filter`](super::server::ServerConfig::source_filter).
+    Filtered,
+
+    /// The message declares a length longer than [`MTU_MAX_PACKET_SIZE`](crate::MTU_MAX_PACKET_SIZE).
+    Oversized,
+
+    /// The message could not be decoded.
+    Malformed,
+
+    /// The author of the message is banned.
+    Banned,
+
+    /// The heartbeat of an unregistered sender presented an invalid (forged or expired) retry token or resumption ticket.
+    AuthenticationFailed,
+
+    /// The media was sent by a client on hold.
+    OnHold,
+
+    /// The media was sent by a client which hasn't consented to the recording of its room.
+    WithoutConsent,
+
+    /// The voice was sent by a client not holding the floor of its room.
+    WithoutFloor,
+
+    /// The voice message is silent.
+    Silent,
+
+    /// The media was shed, as the server is overloaded.
+    Shed,
+
+    /// The media exceeds the [`ServerConfig::bandwidth_limits`](super::server::ServerConfig::bandwidth_limits).
+    RateLimited,
+
+    /// The message has waited in the queues of the [`ServerConfig::egress_limits`](super::server::ServerConfig::egress_limits) for too long.
+    Stale,
+
+    /// The message could not be sent to a peer, for example because the send buffer of the transport was full.
+    SendFailed,
+
+    /// The media violates the [`RoomPolicy`](crate::packet::control::RoomPolicy) of its room.
+    PolicyViolation,
+}
+
+impl DropReason {
+    /// Every [`DropReason`], in the order of their counters.
+    pub const ALL: [Self; 14] = [
+        Self::Filtered,
+        Self::Oversized,
+        Self::Malformed,
+        Self::Banned,
+        Self::AuthenticationFailed,
+        Self::OnHold,
+        Self::WithoutConsent,
+        Self::WithoutFloor,
+        Self::Silent,
+        Self::Shed,
+        Self::RateLimited,
+        Self::Stale,
+        Self::SendFailed,
+        Self::PolicyViolation,
+    ];
+
+    /// Returns the [`DropReason`] of a message which could not be decoded because of the `err`.
+    pub fn of_error(err: &PacketError) -> Self {
+        match err {
+            PacketError::TooLarge(_) | PacketError::BodyTooLarge(_) => Self::Oversized,
+            _ => Self::Malformed,
+        }
+    }
+
+    /// Returns whether the message was dropped because its sender is misbehaving, instead of by a policy of the server.
+    /// These drops are always logged, as a warning.
+    pub fn is_suspicious(&self) -> bool {
+        matches!(
+            self,
+            Self::Oversized | Self::Malformed | Self::AuthenticationFailed
+        )
+    }
+
+    /// Returns the index of the counter of the reason.
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl Display for DropReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Filtered => "filtered source",
+            Self::Oversized => "oversized",
+            Self::Malformed => "malformed",
+            Self::Banned => "banned author",
+            Self::AuthenticationFailed => "authentication failed",
+            Self::OnHold => "sender on hold",
+            Self::WithoutConsent => "no recording consent",
+            Self::WithoutFloor => "not holding the floor",
+            Self::Silent => "silent",
+            Self::Shed => "shed under overload",
+            Self::RateLimited => "rate limited",
+            Self::Stale => "stale",
+            Self::SendFailed => "send failed",
+            Self::PolicyViolation => "room policy violated",
+        })
+    }
+}
+
+///
+/// Drop log configuration type definition.
+///
+/// Describes which dropped messages the server service logs, and how often.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DropLogConfig {
+    /// The shortest time between two logs of the messages dropped for the same reason.
+    pub interval: Duration,
+
+    /// Whether the messages dropped by the policies of the server (for example the silent voice, or the media shed under overload) are logged too, on the debug level.
+    /// Only the [suspicious](DropReason::is_suspicious) drops are logged if this is `false`.
+    pub policy_drops: bool,
+}
+
+impl Default for DropLogConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            policy_drops: false,
+        }
+    }
+}
+
+///
+/// Drop statistics type definition.
+///
+/// The amount of messages dropped for every [`DropReason`].
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DropStats([u64; DropReason::ALL.len()]);
+
+impl DropStats {
+    /// Returns the amount of messages dropped for the `reason`.
+    pub fn get(&self, reason: DropReason) -> u64 {
+        self.0[reason.index()]
+    }
+
+    /// Returns the amount of messages dropped for any reason.
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    /// Iterates over the amount of messages dropped for every [`DropReason`].
+    pub fn iter(&self) -> impl Iterator<Item = (DropReason, u64)> + '_ {
+        DropReason::ALL.into_iter().zip(self.0.iter().copied())
+    }
+}
+
+/// The counters of every [`DropReason`], shared between the server service and the [`ServerHandle`](super::server::ServerHandle).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DropCounters(Arc<[AtomicU64; DropReason::ALL.len()]>);
+
+impl DropCounters {
+    /// Returns a snapshot of the counters.
+    pub(crate) fn stats(&self) -> DropStats {
+        DropStats(std::array::from_fn(|index| {
+            self.0[index].load(Ordering::Relaxed)
+        }))
+    }
+}
+
+/// The time the last drop of a reason was logged at, and the amount of the drops only counted since.
+#[derive(Debug, Clone, Copy, Default)]
+struct LogState {
+    /// The time the last dropped message was logged at.
+    logged_at: Option<Instant>,
+
+    /// The amount of messages dropped without being logged since the last log.
+    suppressed: u64,
+}
+
+/// Counts the messages dropped by the server service, and logs them without letting a flood of them flood the logs too.
+#[derive(Debug)]
+pub(crate) struct DropLog {
+    /// The counters of every reason.
+    counters: DropCounters,
+
+    /// Which drops are logged, and how often.
+    config: DropLogConfig,
+
+    /// The log state of every reason.
+    states: [LogState; DropReason::ALL.len()],
+}
+
+impl DropLog {
+    /// Creates a new [`DropLog`] instance, counting into the `counters`.
+    pub(crate) fn new(counters: DropCounters, config: DropLogConfig) -> Self {
+        Self {
+            counters,
+            config,
+            states: Default::default(),
+        }
+    }
+
+    /// Counts a message from (or to) the `remote_addr` dropped for the `reason`.
+    pub(crate) fn record(&mut self, reason: DropReason, remote_addr: SocketAddr) {
+        self.record_with(reason, Some(remote_addr), None::<&str>);
+    }
+
+    /// Counts a message sent by the application, which was dropped for the `reason` before reaching any peer.
+    pub(crate) fn record_outgoing(&mut self, reason: DropReason) {
+        self.record_with(reason, None, None::<&str>);
+    }
+
+    /// Counts a message from the `remote_addr` which could not be decoded because of the `err`.
+    pub(crate) fn record_error(&mut self, remote_addr: SocketAddr, err: &PacketError) {
+        self.record_with(DropReason::of_error(err), Some(remote_addr), Some(err));
+    }
+
+    /// Counts a dropped message, logging it with its `detail` if nothing was logged for the `reason` in the last [`DropLogConfig::interval`].
+    fn record_with(
+        &mut self,
+        reason: DropReason,
+        remote_addr: Option<SocketAddr>,
+        detail: Option<impl Display>,
+    ) {
+        self.counters.0[reason.index()].fetch_add(1, Ordering::Relaxed);
+
+        if !reason.is_suspicious() && !self.config.policy_drops {
+            return;
+        }
+
+        let state = &mut self.states[reason.index()];
+
+        if state
+            .logged_at
+            .is_some_and(|logged_at| logged_at.elapsed() < self.config.interval)
+        {
+            state.suppressed += 1;
+
+            return;
+        }
+
+        let origin = match remote_addr {
+            Some(remote_addr) => remote_addr.to_string(),
+            None => String::from("the application"),
+        };
+        let detail = detail
+            .map(|detail| format!(": {detail}"))
+            .unwrap_or_default();
+
+        if reason.is_suspicious() {
+            event!(
+                Level::WARN,
+                "Dropped a message of {origin} ({reason}){detail}. {} more were dropped for the same reason since the last log.",
+                state.suppressed
+            );
+        } else {
+            event!(
+                Level::DEBUG,
+                "Dropped a message of {origin} ({reason}){detail}. {} more were dropped for the same reason since the last log.",
+                state.suppressed
+            );
+        }
+
+        state.logged_at = Some(Instant::now());
+        state.suppressed = 0;
+    }
+}
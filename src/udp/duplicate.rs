@@ -0,0 +1,201 @@
+//!
+//! Provides the duplicate transmission of the voice, for the lossy radio and satellite links where the latency of forward error correction (or retransmissions) is unacceptable.
+//!
+//! With [`ClientConfig::duplication`](super::client::ClientConfig::duplication) set, the client service sends a copy of every voice message [`DuplicationConfig::offset`] after the original.
+//! A loss which lasts shorter than the offset only loses one of the copies, and the surviving copy arrives with no more latency than the offset, at the cost of doubling the bandwidth of the voice.
+//! The copies can also be sent to another address of the server (see [`DuplicationConfig::copy_addr`]), so that they take another path.
+//!
+//! Every client drops the copies it has already received (see [`DuplicateFilter`]), whether or not it duplicates its own voice, so the copies never reach the decoders or the application.
+//!
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::packet::{decode_header, HeaderFlags, VoipHeader, VoipMessageType, VoipPacket};
+
+/// The amount of sequence numbers behind the highest received one, which are remembered by the [`DuplicateFilter`].
+pub const DUPLICATE_WINDOW: u32 = 64;
+
+///
+/// Duplication configuration type definition.
+///
+/// Describes how the client service sends the copies of the voice messages.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicationConfig {
+    /// The time the copy of a voice message is sent after the original.
+    /// The copy survives the losses shorter than the offset, a longer offset survives longer bursts, at the cost of the latency of the recovered messages.
+    pub offset: Duration,
+
+    /// The remote address the copies are sent to, for example another address of the server which is reached over another link.
+    /// The copies follow the originals on the same path if this is [`None`].
+    pub copy_addr: Option<SocketAddr>,
+}
+
+impl Default for DuplicationConfig {
+    fn default() -> Self {
+        Self {
+            offset: Duration::from_millis(10),
+            copy_addr: None,
+        }
+    }
+}
+
+///
+/// Duplicate queue type definition.
+///
+/// Holds the copies of the sent voice messages until their offset has passed.
+///
+#[derive(Debug, Clone)]
+pub(crate) struct DuplicateQueue {
+    /// The time a copy is sent after its original.
+    offset: Duration,
+
+    /// The copies waiting to be sent, with the time they are sent at.
+    copies: VecDeque<(Instant, VoipPacket)>,
+}
+
+impl DuplicateQueue {
+    /// Creates a new [`DuplicateQueue`] instance, sending the copies `offset` after their originals.
+    pub(crate) fn new(offset: Duration) -> Self {
+        Self {
+            offset,
+            copies: VecDeque::new(),
+        }
+    }
+
+    /// Queues the copy of the `message` sent at `now`, if it is a voice message.
+    pub(crate) fn push(&mut self, message: &VoipPacket, now: Instant) {
+        if is_voice(message.inner()) {
+            self.copies.push_back((now + self.offset, message.clone()));
+        }
+    }
+
+    /// Returns the time the next copy is sent at, if there is one.
+    pub(crate) fn next_due(&self) -> Option<Instant> {
+        self.copies.front().map(|(due, _)| *due)
+    }
+
+    /// Returns the next copy which is due at `now`.
+    pub(crate) fn pop_due(&mut self, now: Instant) -> Option<VoipPacket> {
+        if self.next_due()? > now {
+            return None;
+        }
+
+        self.copies.pop_front().map(|(_, copy)| copy)
+    }
+}
+
+/// Returns whether the encoded `message` is a voice message carrying audio, the cover messages aren't duplicated.
+fn is_voice(message: &[u8]) -> bool {
+    decode_header(message).is_ok_and(|voip_header| {
+        matches!(
+            voip_header.voip_message_type(),
+            VoipMessageType::VoiceMessage(_)
+        ) && !voip_header.flags().contains(HeaderFlags::PADDING)
+    })
+}
+
+/// The sequence numbers received from a stream, relative to the highest one.
+#[derive(Debug, Clone, Copy)]
+struct SequenceWindow {
+    /// The highest sequence number received.
+    highest_sequence: u32,
+
+    /// The sequence numbers received behind the highest one, the lowest bit is the highest sequence number itself.
+    received: u64,
+}
+
+///
+/// Duplicate filter type definition.
+///
+/// Remembers the recently received sequence numbers of every voice stream, and recognizes the copies of the messages which were already received.
+///
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DuplicateFilter {
+    /// The window of every stream of every device of every author.
+    windows: HashMap<(Uuid, Option<u32>, Option<u8>), SequenceWindow>,
+}
+
+impl DuplicateFilter {
+    ///
+    /// Records the voice message of the `voip_header`, and returns whether it was already received.
+    ///
+    /// # Behavior
+    /// Only the voice messages with a sequence number are recorded, every other message is reported as new.
+    /// The messages further behind the highest sequence number than [`DUPLICATE_WINDOW`] are reported as new too, and start the window over, as the stream has most likely restarted.
+    ///
+    pub(crate) fn is_duplicate(&mut self, voip_header: &VoipHeader) -> bool {
+        let (VoipMessageType::VoiceMessage(_), Some(sequence)) =
+            (voip_header.voip_message_type(), voip_header.sequence())
+        else {
+            return false;
+        };
+
+        if voip_header.flags().contains(HeaderFlags::PADDING) {
+            return false;
+        }
+
+        let key = (
+            voip_header.author(),
+            voip_header.device(),
+            voip_header.stream(),
+        );
+
+        let Some(window) = self.windows.get_mut(&key) else {
+            self.windows.insert(
+                key,
+                SequenceWindow {
+                    highest_sequence: sequence,
+                    received: 1,
+                },
+            );
+
+            return false;
+        };
+
+        //The sequence numbers wrap around, so compare them by their distance
+        let distance = sequence.wrapping_sub(window.highest_sequence) as i32;
+
+        if distance > 0 {
+            window.received = window
+                .received
+                .checked_shl(distance as u32)
+                .unwrap_or_default()
+                | 1;
+            window.highest_sequence = sequence;
+
+            return false;
+        }
+
+        let behind = distance.unsigned_abs();
+
+        //The stream has most likely restarted (for example the author has rejoined), so the window starts over
+        if behind >= DUPLICATE_WINDOW {
+            *window = SequenceWindow {
+                highest_sequence: sequence,
+                received: 1,
+            };
+
+            return false;
+        }
+
+        let is_duplicate = window.received & (1 << behind) != 0;
+
+        window.received |= 1 << behind;
+
+        is_duplicate
+    }
+
+    /// Forgets the streams of the `author`, for example when it has left the session.
+    pub(crate) fn remove_author(&mut self, author: Uuid) {
+        self.windows
+            .retain(|(stream_author, ..), _| *stream_author != author);
+    }
+}
@@ -0,0 +1,432 @@
+//!
+//! Provides the outbound rate limits of the [`Server`](super::server::Server), which pace the messages fanned out to the clients with token buckets.
+//!
+//! Every message fanned out passes the bucket of its destination and a global bucket shared by every destination, so a single high-bitrate stream (like a screen share) can't saturate the uplink of the server and starve the other rooms.
+//! The media messages exceeding the limits are queued instead of dropped. The queued destinations are served in turns, so every destination gets its share of the global rate, and the voice messages of a destination are sent ahead of its video messages.
+//! The messages which have waited for the [`EgressLimits::max_queue_delay`] are dropped, as they would arrive too late to be played anyway.
+//! The text and control messages are never queued, but they use up the tokens of the buckets too.
+//!
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::packet::{VoipHeader, VoipMessageType};
+
+///
+/// Egress limits type definition.
+///
+/// Describes the highest bitrate the [`Server`](super::server::Server) sends the fanned out messages at.
+/// The default limits don't cap anything.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EgressLimits {
+    /// The highest bitrate (in bits per second) the server sends at, to all of the clients together.
+    pub max_bitrate: Option<u32>,
+
+    /// The highest bitrate (in bits per second) the server sends at to a single client.
+    pub max_destination_bitrate: Option<u32>,
+
+    /// The time the buckets can save their tokens up for, longer bursts allow sending more messages at once after a pause.
+    pub burst: Duration,
+
+    /// The longest a message may wait in the queues, the messages which have waited this long are dropped.
+    pub max_queue_delay: Duration,
+}
+
+impl Default for EgressLimits {
+    fn default() -> Self {
+        Self {
+            max_bitrate: None,
+            max_destination_bitrate: None,
+            burst: Duration::from_millis(50),
+            max_queue_delay: Duration::from_millis(250),
+        }
+    }
+}
+
+impl EgressLimits {
+    /// Returns whether any cap is set.
+    pub fn is_enabled(&self) -> bool {
+        self.max_bitrate.is_some() || self.max_destination_bitrate.is_some()
+    }
+}
+
+/// A token bucket, which refills with the bytes of its bitrate over time.
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    /// The bytes the bucket refills with per second.
+    rate: f64,
+
+    /// The most bytes the bucket can hold.
+    capacity: f64,
+
+    /// The bytes the bucket currently holds, which goes negative if the unpaced messages have overdrawn it.
+    tokens: f64,
+
+    /// The time the tokens were last refilled at.
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a new [`TokenBucket`] instance of the `max_bitrate`, which is full at `now`.
+    fn new(max_bitrate: u32, burst: Duration, now: Instant) -> Self {
+        let rate = max_bitrate.max(1) as f64 / 8.;
+        let capacity = rate * burst.as_secs_f64();
+
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: now,
+        }
+    }
+
+    /// Refills the tokens gained since the last refill.
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Returns the tokens a message of `bytes` needs, the messages larger than the bucket only need a full bucket.
+    fn needed(&self, bytes: usize) -> f64 {
+        (bytes as f64).min(self.capacity)
+    }
+
+    /// Returns whether a message of `bytes` can be sent at `now`.
+    fn fits(&mut self, bytes: usize, now: Instant) -> bool {
+        self.refill(now);
+
+        self.tokens >= self.needed(bytes)
+    }
+
+    /// Returns whether the bucket has refilled completely at `now`.
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+
+        self.tokens >= self.capacity
+    }
+
+    /// Takes the tokens of a sent message of `bytes`.
+    fn take(&mut self, bytes: usize) {
+        self.tokens -= bytes as f64;
+    }
+
+    /// Returns the time the bucket has refilled enough to send a message of `bytes`.
+    fn ready_at(&self, bytes: usize) -> Instant {
+        let missing = self.needed(bytes) - self.tokens;
+
+        if missing <= 0. {
+            return self.last_refill;
+        }
+
+        //Round up, so the bucket surely fits the message once it is woken up
+        self.last_refill + Duration::from_nanos((missing / self.rate * 1e9).ceil() as u64 + 1)
+    }
+}
+
+/// The pacing of a message, decided by its kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pacing {
+    /// A voice message, which is sent ahead of the queued video messages of its destination.
+    Voice,
+
+    /// A video message.
+    Video,
+
+    /// A text or control message, which is never queued.
+    Unpaced,
+}
+
+impl Pacing {
+    /// Returns the [`Pacing`] of the message with the decoded `voip_header`, the messages which couldn't be decoded are [`Pacing::Unpaced`].
+    pub(crate) fn of(voip_header: Option<&VoipHeader>) -> Self {
+        let Some(voip_header) = voip_header else {
+            return Self::Unpaced;
+        };
+
+        match voip_header.voip_message_type() {
+            VoipMessageType::VoiceMessage(_) => Self::Voice,
+            VoipMessageType::VideoMessage(_) => Self::Video,
+            _ => Self::Unpaced,
+        }
+    }
+}
+
+/// A queued datagram, with the time it was queued at.
+type Queued = (Instant, Vec<u8>);
+
+/// The bucket and the queued messages of a single destination.
+#[derive(Debug, Clone)]
+struct Destination {
+    /// The bucket of the destination, if its bitrate is capped.
+    bucket: Option<TokenBucket>,
+
+    /// The queued voice messages.
+    voice: VecDeque<Queued>,
+
+    /// The queued video messages.
+    video: VecDeque<Queued>,
+}
+
+impl Destination {
+    /// Returns the queue the next message of the destination is sent from.
+    fn front_queue(&mut self) -> Option<&mut VecDeque<Queued>> {
+        if !self.voice.is_empty() {
+            Some(&mut self.voice)
+        } else if !self.video.is_empty() {
+            Some(&mut self.video)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the next message of the destination.
+    fn front(&self) -> Option<&Queued> {
+        self.voice.front().or_else(|| self.video.front())
+    }
+
+    /// Returns whether no message of the destination is queued.
+    fn is_empty(&self) -> bool {
+        self.voice.is_empty() && self.video.is_empty()
+    }
+}
+
+/// The messages released by the [`EgressScheduler`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Released {
+    /// The datagrams to send, with their destinations.
+    pub(crate) datagrams: Vec<(SocketAddr, Vec<u8>)>,
+
+    /// The destination of every message dropped after waiting longer than the [`EgressLimits::max_queue_delay`].
+    pub(crate) dropped: Vec<SocketAddr>,
+}
+
+/// Paces the messages fanned out by the server with the [`EgressLimits`].
+#[derive(Debug, Clone)]
+pub(crate) struct EgressScheduler {
+    /// The limits the messages are paced with.
+    limits: EgressLimits,
+
+    /// The bucket shared by every destination, if the total bitrate is capped.
+    bucket: Option<TokenBucket>,
+
+    /// The state of every destination the server has sent to.
+    destinations: HashMap<SocketAddr, Destination>,
+
+    /// The destinations with queued messages, in the order they are served.
+    turns: VecDeque<SocketAddr>,
+
+    /// The amount of queued messages.
+    queued: usize,
+}
+
+impl EgressScheduler {
+    /// Creates a new [`EgressScheduler`] instance, with its buckets full at `now`.
+    pub(crate) fn new(limits: EgressLimits, now: Instant) -> Self {
+        let bucket = limits
+            .max_bitrate
+            .map(|max_bitrate| TokenBucket::new(max_bitrate, limits.burst, now));
+
+        Self {
+            limits,
+            bucket,
+            destinations: HashMap::new(),
+            turns: VecDeque::new(),
+            queued: 0,
+        }
+    }
+
+    /// Returns the amount of queued messages.
+    pub(crate) fn queued(&self) -> usize {
+        self.queued
+    }
+
+    ///
+    /// Paces the `datagram` of a message fanned out to the `remote_addr` at `now`.
+    /// Returns whether the message can be sent right away, the message is queued otherwise.
+    ///
+    /// # Behavior
+    /// The [`Pacing::Unpaced`] messages are always sent right away, and the media messages are sent right away if nothing is queued before them and the buckets have enough tokens.
+    ///
+    pub(crate) fn admit(
+        &mut self,
+        remote_addr: SocketAddr,
+        datagram: &[u8],
+        pacing: Pacing,
+        now: Instant,
+    ) -> bool {
+        let bytes = datagram.len();
+
+        //Forget the idle destinations whenever a new one is seen, so the departed clients aren't kept around
+        if !self.destinations.contains_key(&remote_addr) {
+            self.prune(now);
+        }
+
+        let limits = &self.limits;
+        let destination = self
+            .destinations
+            .entry(remote_addr)
+            .or_insert_with(|| Destination {
+                bucket: limits
+                    .max_destination_bitrate
+                    .map(|max_bitrate| TokenBucket::new(max_bitrate, limits.burst, now)),
+                voice: VecDeque::new(),
+                video: VecDeque::new(),
+            });
+
+        let is_sent = match pacing {
+            Pacing::Unpaced => true,
+            //The voice only waits for the other queued voice messages of the destination
+            Pacing::Voice => {
+                destination.voice.is_empty()
+                    && fits(&mut destination.bucket, &mut self.bucket, bytes, now)
+            }
+            Pacing::Video => {
+                destination.is_empty()
+                    && fits(&mut destination.bucket, &mut self.bucket, bytes, now)
+            }
+        };
+
+        if is_sent {
+            take(&mut destination.bucket, &mut self.bucket, bytes);
+
+            return true;
+        }
+
+        if destination.is_empty() {
+            self.turns.push_back(remote_addr);
+        }
+
+        let queue = match pacing {
+            Pacing::Voice => &mut destination.voice,
+            _ => &mut destination.video,
+        };
+
+        queue.push_back((now, datagram.to_vec()));
+        self.queued += 1;
+
+        false
+    }
+
+    /// Returns the time the next queued message can be sent (or dropped) at, if any message is queued.
+    pub(crate) fn next_release(&self) -> Option<Instant> {
+        self.turns
+            .iter()
+            .filter_map(|remote_addr| {
+                let destination = self.destinations.get(remote_addr)?;
+                let (queued_at, datagram) = destination.front()?;
+                let bytes = datagram.len();
+
+                let ready_at = [destination.bucket.as_ref(), self.bucket.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .map(|bucket| bucket.ready_at(bytes))
+                    .max()
+                    .unwrap_or(*queued_at);
+
+                Some(ready_at.min(*queued_at + self.limits.max_queue_delay))
+            })
+            .min()
+    }
+
+    ///
+    /// Releases the queued messages which can be sent at `now`, and drops the ones which have waited too long.
+    ///
+    /// # Behavior
+    /// The destinations are served one message at a time in turns, until the buckets run out of tokens.
+    ///
+    pub(crate) fn release(&mut self, now: Instant) -> Released {
+        let mut released = Released::default();
+        let mut waiting = VecDeque::new();
+
+        while let Some(remote_addr) = self.turns.pop_front() {
+            let Some(destination) = self.destinations.get_mut(&remote_addr) else {
+                continue;
+            };
+
+            //Drop the messages which would arrive too late
+            for queue in [&mut destination.voice, &mut destination.video] {
+                while queue.front().is_some_and(|(queued_at, _)| {
+                    now.saturating_duration_since(*queued_at) >= self.limits.max_queue_delay
+                }) {
+                    queue.pop_front();
+                    self.queued -= 1;
+                    released.dropped.push(remote_addr);
+                }
+            }
+
+            let Some(bytes) = destination.front().map(|(_, datagram)| datagram.len()) else {
+                continue;
+            };
+
+            if !fits(&mut destination.bucket, &mut self.bucket, bytes, now) {
+                waiting.push_back(remote_addr);
+
+                continue;
+            }
+
+            take(&mut destination.bucket, &mut self.bucket, bytes);
+
+            if let Some((_, datagram)) = destination.front_queue().and_then(VecDeque::pop_front) {
+                self.queued -= 1;
+                released.datagrams.push((remote_addr, datagram));
+            }
+
+            if !destination.is_empty() {
+                self.turns.push_back(remote_addr);
+            }
+        }
+
+        self.turns = waiting;
+
+        released
+    }
+
+    /// Forgets the destinations without queued messages whose buckets are full at `now`, as they would be created the same way again.
+    fn prune(&mut self, now: Instant) {
+        self.destinations.retain(|_, destination| {
+            !destination.is_empty()
+                || destination
+                    .bucket
+                    .as_mut()
+                    .is_some_and(|bucket| !bucket.is_full(now))
+        });
+    }
+
+    /// Forgets the `remote_addr`, dropping its queued messages (for example when its client has left).
+    pub(crate) fn remove(&mut self, remote_addr: &SocketAddr) {
+        if let Some(destination) = self.destinations.remove(remote_addr) {
+            self.queued -= destination.voice.len() + destination.video.len();
+            self.turns.retain(|turn| turn != remote_addr);
+        }
+    }
+}
+
+/// Returns whether a message of `bytes` fits both the `destination` and the `global` bucket at `now`.
+fn fits(
+    destination: &mut Option<TokenBucket>,
+    global: &mut Option<TokenBucket>,
+    bytes: usize,
+    now: Instant,
+) -> bool {
+    destination
+        .as_mut()
+        .is_none_or(|bucket| bucket.fits(bytes, now))
+        && global.as_mut().is_none_or(|bucket| bucket.fits(bytes, now))
+}
+
+/// Takes the tokens of a sent message of `bytes` from both the `destination` and the `global` bucket.
+fn take(destination: &mut Option<TokenBucket>, global: &mut Option<TokenBucket>, bytes: usize) {
+    for bucket in [destination, global].into_iter().flatten() {
+        bucket.take(bytes);
+    }
+}
@@ -0,0 +1,452 @@
+//!
+//! Provides the [`ClientEvent`] type, which the [`Client`](super::client::Client) emits for every message or state change it observes.
+//!
+//! The client service parses the incoming messages, so the consumers can match on the events instead of re-parsing headers and lengths themselves.
+//!
+
+use std::{net::SocketAddr, string::FromUtf8Error, time::Duration};
+
+use silence_core::opus::opus;
+use tokio::sync::mpsc::error::SendError;
+use uuid::Uuid;
+
+use super::{
+    call::{Call, CallState},
+    freeze::FreezeChange,
+    speaker::ActiveSpeakerChange,
+    transport::path::PathError,
+    voice::VoiceError,
+};
+use crate::packet::{
+    caption::Caption,
+    codec::CodecError,
+    control::{
+        CloseReason, ControlMessage, DeviceNotice, FloorState, MediaState, PolicyViolation,
+        PresenceState, QualityReport, RecordingState, RoomPolicy, ToneEvent,
+    },
+    frame::{VideoFrame, VoiceFrame},
+    HeaderFlags, MediaCodec, PacketError, VoipHeader, VoipMessageType,
+};
+
+///
+/// Client event type definition.
+///
+/// These events are received through the [`Client::event_receiver`](super::client::Client::event_receiver).
+///
+#[derive(Debug)]
+pub enum ClientEvent {
+    /// An encoded voice frame was received.
+    VoiceFrame(VoiceFrame),
+
+    /// A remote author has ended an audio stream (see [`Client::end_stream`](super::client::Client::end_stream)), so its state can be released right away.
+    /// The decoder and the jitter buffer of the stream are released by the client service itself, the voice tap receives the end of the stream as an empty [`DecodedVoiceFrame`](super::voice::DecodedVoiceFrame).
+    StreamEnded {
+        /// The author of the audio stream.
+        author: Uuid,
+        /// The device of the author the audio stream was sent from.
+        device: u32,
+        /// The audio stream of the author.
+        stream: u8,
+    },
+
+    /// An encoded video frame was received, the fragments of the frame are already reassembled.
+    /// A frame encoded in slices is reported once all of its slices have arrived, or without its lost slices once the next frame starts.
+    VideoFrame(VideoFrame),
+
+    /// A text message was received.
+    Text {
+        /// The author of the text message.
+        author: Uuid,
+        /// The content of the text message.
+        text: String,
+    },
+
+    /// A live caption was received, it is shown in sync with the voice by matching its span with the timestamps of the author's voice frames.
+    /// The interim captions are replaced by the next caption with the same start.
+    Caption(Caption),
+
+    /// A participant has joined the session.
+    ParticipantJoined(Uuid),
+
+    /// A participant has left the session.
+    ParticipantLeft(Uuid),
+
+    /// A [`QualityReport`] was received.
+    QualityReport {
+        /// The author of the report.
+        author: Uuid,
+        /// The quality of the stream the author has received.
+        report: QualityReport,
+    },
+
+    /// A [`ToneEvent`] was received (for example a DTMF digit, or a doorbell trigger).
+    Tone {
+        /// The author of the tone.
+        author: Uuid,
+        /// The signaled tone.
+        tone: ToneEvent,
+    },
+
+    /// The server has advertised the [`RoomPolicy`] of a channel (or room).
+    /// The policy of the channel the voice messages are sent on is applied by [`Client::send_samples`](super::client::Client::send_samples).
+    RoomPolicyChanged {
+        /// The channel (or room) the policy applies to.
+        channel: u32,
+        /// The policy of the channel.
+        policy: RoomPolicy,
+    },
+
+    /// The server has rejected a media message of the [`Client`](super::client::Client), as it violates the [`RoomPolicy`] of a channel (or room).
+    PolicyViolated {
+        /// The channel (or room) whose policy was violated.
+        channel: u32,
+        /// The rule of the policy which was violated.
+        violation: PolicyViolation,
+    },
+
+    /// The server has signaled the [`FloorState`] of a channel (or room), either because its holder has changed, or as the answer to a floor request of the [`Client`](super::client::Client).
+    FloorChanged {
+        /// The channel (or room) of the floor.
+        channel: u32,
+        /// The state of the floor.
+        state: FloorState,
+    },
+
+    /// The server has signaled that the recording of a channel (or room) has started or stopped, either because it has changed, or because the channel was recorded when this client has joined.
+    /// While the recording requires consent, the media of this client is only forwarded after it has consented with [`Client::consent_to_recording`](super::client::Client::consent_to_recording).
+    RecordingChanged {
+        /// The channel (or room) of the recording.
+        channel: u32,
+        /// The recording state of the channel.
+        state: RecordingState,
+    },
+
+    /// A participant has set its [`PresenceState`], or the server has sent the state of a participant set before this client has joined.
+    PresenceChanged {
+        /// The participant whose presence has changed.
+        author: Uuid,
+        /// The presence state of the participant.
+        state: PresenceState,
+    },
+
+    /// A participant has signaled its [`MediaState`] in a channel (or room), or the server has sent the state of a participant signaled before this client has joined.
+    MediaStateChanged {
+        /// The participant whose media state has changed.
+        author: Uuid,
+        /// The channel (or room) the state applies to.
+        channel: u32,
+        /// The media state of the participant.
+        state: MediaState,
+    },
+
+    /// The server has negotiated the audio codecs every peer of the session can decode, the voice is encoded with the [`VoiceConfig::codec`](super::voice::VoiceConfig::codec) if it is among them.
+    /// This is only reported if a peer of the session has advertised codecs besides Opus (see [`ClientConfig::codecs`](super::client::ClientConfig::codecs)).
+    CodecsNegotiated(Vec<MediaCodec>),
+
+    /// The state of a one-to-one [`Call`] has changed, either by a signal of the remote peer or by an action of the [`Client`](super::client::Client).
+    /// The calls which have ended are reported with [`CallState::Ended`] once.
+    CallStateChanged(Call),
+
+    /// The server has signaled a change of the devices of this client's author, as the server allows the authors to join from several devices.
+    /// The id of this device is reported with [`DeviceNotice::Assigned`] when it joins.
+    DeviceChanged(DeviceNotice),
+
+    /// The dominant speaker of a channel has changed.
+    /// This is only reported if active speaker detection is enabled in the [`ClientConfig`](super::client::ClientConfig).
+    ActiveSpeakerChanged(ActiveSpeakerChange),
+
+    /// The video of a remote author has frozen (with the last frame decoded from it), or has recovered after it was frozen.
+    /// This is only reported if freeze detection is enabled in the [`ClientConfig`](super::client::ClientConfig), and a video decoder is registered for the codec of the author.
+    VideoFreezeChanged(FreezeChange),
+
+    /// The client service has dropped video frames which have waited longer than the [`ClientConfig::video_latency_budget`](super::client::ClientConfig::video_latency_budget) to be sent, with the amount of frames dropped.
+    /// The next frame encoded with [`Client::send_video_image`](super::client::Client::send_video_image) is a keyframe, so the receivers recover from the dropped frames.
+    VideoFramesDropped(u32),
+
+    /// The jitter buffer of an audio stream has run dry, so the voice of its author has stopped until enough is buffered again.
+    /// This is reported by the [`Playout`](super::playout::Playout) when the samples are pulled, and dropped if the event queue is full.
+    PlayoutUnderrun {
+        /// The author of the audio stream.
+        author: Uuid,
+        /// The audio stream of the author.
+        stream: u8,
+    },
+
+    /// The jitter buffer of an audio stream has buffered more than its maximum delay, so its oldest samples were discarded.
+    /// This is reported by the [`Playout`](super::playout::Playout) when the samples are pulled, and dropped if the event queue is full.
+    PlayoutOverrun {
+        /// The author of the audio stream.
+        author: Uuid,
+        /// The audio stream of the author.
+        stream: u8,
+    },
+
+    /// The session was closed, either by the remote address or by the [`Client`](super::client::Client) itself.
+    /// This is always the final event, the client service shuts down after reporting it.
+    Closed(CloseReason),
+
+    /// The state of the connection with the remote address has changed.
+    ConnectionStateChanged(ConnectionState),
+
+    /// The remote address has stopped answering and the client has failed over to the next endpoint, or the current endpoint has been resolved to a new address.
+    /// The new address is included, this is only reported if failover is configured in the [`ClientConfig`](super::client::ClientConfig).
+    RemoteAddrChanged(SocketAddr),
+
+    /// The network has reported an error about a datagram sent by the client, for example the server has become unreachable or the hop limit of the datagram has expired on the way.
+    /// This is only reported if [`ClientConfig::path_errors`](super::client::ClientConfig::path_errors) is enabled.
+    PathError(PathError),
+
+    /// The heartbeats are sent at a new interval, as the NAT binding of the client has survived a longer silence.
+    /// This is only reported if [`ClientConfig::keepalive`](super::client::ClientConfig::keepalive) is configured.
+    KeepaliveIntervalChanged(Duration),
+
+    /// The client service has encountered an error.
+    /// The client service keeps running after an error.
+    Error(ClientError),
+}
+
+impl ClientEvent {
+    /// Creates the [`ClientEvent`] matching a decoded message.
+    /// Returns [`None`] for the messages which are handled by the client service itself ([`ControlMessage::Heartbeat`], [`ControlMessage::MaxBitrate`], [`ControlMessage::Goodbye`] and [`ControlMessage::Close`]), and for the relay probes ([`ControlMessage::Ping`] and [`ControlMessage::Pong`]), the address validation ([`ControlMessage::Retry`] and [`ControlMessage::RetryHeartbeat`]), the session resumption ([`ControlMessage::ResumptionTicket`], which the client service stores, and [`ControlMessage::Resume`]), the connection ids ([`ControlMessage::ConnectionId`], which the client service stores, and [`ControlMessage::ConnectionHeartbeat`]), the layer selections ([`ControlMessage::SelectLayer`]), the floor requests ([`ControlMessage::FloorRequest`] and [`ControlMessage::FloorRelease`]), the recording consents ([`ControlMessage::RecordingConsent`]), the keepalive probes ([`ControlMessage::KeepaliveProbe`]), the video pauses ([`ControlMessage::PauseVideo`]) and the call signals ([`ControlMessage::Call`], whose changes are reported with [`ClientEvent::CallStateChanged`] by the client service).
+    pub(crate) fn from_message(voip_header: VoipHeader, voip_body: Vec<u8>) -> Option<Self> {
+        let author = voip_header.author();
+
+        let client_event = match voip_header.voip_message_type() {
+            VoipMessageType::VoiceMessage(_)
+                if voip_header.flags().contains(HeaderFlags::END_OF_STREAM) =>
+            {
+                Self::StreamEnded {
+                    author,
+                    device: voip_header.device().unwrap_or_default(),
+                    stream: voip_header.stream().unwrap_or_default(),
+                }
+            }
+            VoipMessageType::VoiceMessage(_) => {
+                Self::VoiceFrame(VoiceFrame::from_message(&voip_header, voip_body))
+            }
+            VoipMessageType::VideoMessage(_) => {
+                Self::VideoFrame(VideoFrame::from_message(&voip_header, voip_body))
+            }
+            VoipMessageType::TextMessage(_) => match String::from_utf8(voip_body) {
+                Ok(text) => Self::Text { author, text },
+                Err(err) => Self::Error(err.into()),
+            },
+            VoipMessageType::CaptionMessage(_, caption_info) => {
+                match String::from_utf8(voip_body) {
+                    Ok(text) => {
+                        Self::Caption(Caption::from_message(&voip_header, caption_info, text))
+                    }
+                    Err(err) => Self::Error(err.into()),
+                }
+            }
+            VoipMessageType::Control(ControlMessage::ParticipantJoined(uuid)) => {
+                Self::ParticipantJoined(*uuid)
+            }
+            VoipMessageType::Control(ControlMessage::ParticipantLeft(uuid)) => {
+                Self::ParticipantLeft(*uuid)
+            }
+            VoipMessageType::Control(ControlMessage::QualityReport(report)) => {
+                Self::QualityReport {
+                    author,
+                    report: *report,
+                }
+            }
+            VoipMessageType::Control(ControlMessage::Presence(state)) => Self::PresenceChanged {
+                author,
+                state: *state,
+            },
+            VoipMessageType::Control(ControlMessage::MediaState(state)) => {
+                Self::MediaStateChanged {
+                    author,
+                    channel: voip_header.channel(),
+                    state: *state,
+                }
+            }
+            VoipMessageType::Control(ControlMessage::RecordingState(state)) => {
+                Self::RecordingChanged {
+                    channel: voip_header.channel(),
+                    state: *state,
+                }
+            }
+            VoipMessageType::Control(ControlMessage::Codecs(codecs)) => {
+                Self::CodecsNegotiated(codecs.clone())
+            }
+            VoipMessageType::Control(ControlMessage::Floor(state)) => Self::FloorChanged {
+                channel: voip_header.channel(),
+                state: *state,
+            },
+            VoipMessageType::Control(ControlMessage::Tone(tone)) => Self::Tone {
+                author,
+                tone: *tone,
+            },
+            VoipMessageType::Control(ControlMessage::Device(device_notice)) => {
+                Self::DeviceChanged(*device_notice)
+            }
+            VoipMessageType::Control(ControlMessage::RoomPolicy(policy)) => {
+                Self::RoomPolicyChanged {
+                    channel: voip_header.channel(),
+                    policy: policy.clone(),
+                }
+            }
+            VoipMessageType::Control(ControlMessage::PolicyViolation(violation)) => {
+                Self::PolicyViolated {
+                    channel: voip_header.channel(),
+                    violation: *violation,
+                }
+            }
+            VoipMessageType::Control(
+                ControlMessage::Heartbeat
+                | ControlMessage::MaxBitrate(_)
+                | ControlMessage::Goodbye
+                | ControlMessage::Close(_)
+                | ControlMessage::Ping(_)
+                | ControlMessage::Pong(_)
+                | ControlMessage::Retry(_)
+                | ControlMessage::RetryHeartbeat(_)
+                | ControlMessage::SelectLayer(..)
+                | ControlMessage::FloorRequest
+                | ControlMessage::FloorRelease
+                | ControlMessage::Call(_)
+                | ControlMessage::RecordingConsent(_)
+                | ControlMessage::KeepaliveProbe(..)
+                | ControlMessage::PauseVideo(_)
+                | ControlMessage::ResumptionTicket(_)
+                | ControlMessage::Resume(_)
+                | ControlMessage::ConnectionId(_)
+                | ControlMessage::ConnectionHeartbeat(_),
+            ) => return None,
+        };
+
+        Some(client_event)
+    }
+}
+
+/// The state of the connection between the [`Client`](super::client::Client) and the remote address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// A valid message has been received from the remote address.
+    Connected,
+
+    /// The remote address has become unreachable, or has left the session.
+    /// This is reported when the transport refuses the connection (for example when an ICMP port unreachable message is received), or a [`ControlMessage::Goodbye`] is received.
+    Disconnected,
+}
+
+/// Custom client errors, returned by the methods of the [`Client`](super::client::Client) and reported through [`ClientEvent::Error`].
+#[derive(thiserror::Error, Debug)]
+pub enum ClientError {
+    /// This error is thrown when an incoming message could not be decoded.
+    #[error("Failed to decode a message: {0}")]
+    Decode(#[from] PacketError),
+
+    /// This error is thrown when an outgoing control message could not be encoded.
+    #[error("Failed to encode a message: {0}")]
+    Encode(#[from] CodecError),
+
+    /// This error is thrown when a text message (or a caption) doesn't contain valid UTF-8.
+    #[error("Failed to decode a text message: {0}")]
+    InvalidText(#[from] FromUtf8Error),
+
+    /// This error is thrown when the transport has failed to receive a message.
+    #[error("Failed to receive message: {0}")]
+    Receive(std::io::Error),
+
+    /// This error is thrown when the transport has failed to send a message.
+    #[error("Failed to send message: {0}")]
+    Send(std::io::Error),
+
+    /// This error is thrown when the local socket could not be bound, or its address could not be read.
+    #[error("Socket error: {0}")]
+    SocketIo(std::io::Error),
+
+    /// This error is thrown when the remote address could not be resolved, or the socket could not be connected to it.
+    #[error("Failed to connect to the remote address: {0}")]
+    NotConnected(std::io::Error),
+
+    /// This error is thrown when a message is sent after the remote address has closed the session, because the client has failed to authenticate or was banned.
+    #[error("The session was closed by the remote address: {0:?}")]
+    Unauthorized(CloseReason),
+
+    /// This error is thrown when the payload of an outgoing message can't fit in [`MTU_MAX_PACKET_SIZE`](crate::MTU_MAX_PACKET_SIZE), and the message can't be fragmented.
+    #[error("The payload is {size} bytes long, which exceeds the limit of {limit} bytes.")]
+    Oversized {
+        /// The size of the payload.
+        size: usize,
+        /// The largest size the payload could have had, with the header of the message.
+        limit: usize,
+    },
+
+    /// This error is thrown when a message is sent to the client service, which has already shut down.
+    #[error("The client service has already shut down.")]
+    ChannelClosed,
+
+    /// This error is thrown when the internal runtime of a [`BlockingClient`](super::blocking::BlockingClient) could not be created.
+    #[error("Failed to create the internal runtime: {0}")]
+    Runtime(std::io::Error),
+
+    /// This error is thrown when the [`VoiceConfig`](super::voice::VoiceConfig) can't be used by the encoder.
+    #[error("Invalid voice configuration: {0}")]
+    Voice(#[from] VoiceError),
+
+    /// This error is thrown when samples are sent on an audio stream which hasn't been added with [`Client::add_audio_stream`](super::client::Client::add_audio_stream).
+    #[error("The audio stream {0} hasn't been added.")]
+    UnknownAudioStream(u8),
+
+    /// This error is thrown when the default audio stream `0` is added, its codec is configured by the [`ClientConfig`](super::client::ClientConfig).
+    #[error("The default audio stream can't be added.")]
+    DefaultAudioStream,
+
+    /// This error is thrown when a call is answered or hung up, which doesn't exist or has already ended.
+    #[error("The call {0} doesn't exist.")]
+    UnknownCall(Uuid),
+
+    /// This error is thrown when a call is accepted or rejected in a [`CallState`] which doesn't allow it (for example an outgoing call).
+    #[error("The action isn't allowed in the {0:?} state of the call.")]
+    InvalidCallState(CallState),
+
+    /// This error is thrown when an image is sent, before a video encoder was set with [`Client::set_video_encoder`](super::client::Client::set_video_encoder).
+    #[error("No video encoder has been set.")]
+    NoVideoEncoder,
+
+    /// This error is thrown when the Opus encoder has failed to configure itself or to encode the samples.
+    #[error("Failed to encode the voice: {0}")]
+    Opus(#[from] opus::Error),
+
+    /// This error is thrown when an audio or a video codec, or the capture device has failed (see [`MediaError`]).
+    #[error("Media error: {0}")]
+    Media(#[from] MediaError),
+}
+
+/// Custom media errors, returned by the audio and the video codecs and their decoders.
+#[derive(thiserror::Error, Debug)]
+pub enum MediaError {
+    /// This error is thrown when a message is decoded, which is encoded with a codec no decoder is registered for.
+    #[error("No decoder is registered for the codec {0:?}.")]
+    UnregisteredCodec(MediaCodec),
+
+    /// This error is thrown when a decoded image doesn't have 4 bytes for every pixel of its resolution.
+    #[error("The decoded image has {size} bytes instead of {width}x{height}x4.")]
+    InvalidImageSize {
+        /// The amount of bytes of the image.
+        size: usize,
+        /// The width of the image in pixels.
+        width: u32,
+        /// The height of the image in pixels.
+        height: u32,
+    },
+
+    /// This error is thrown when the Opus encoder or decoder has failed.
+    #[error("Opus error: {0}")]
+    Opus(#[from] opus::Error),
+
+    /// This error is thrown when a codec (or the capture device of [`silence_core`]) has failed, with the error it has failed with.
+    /// The [`AudioCodec`](super::audio_codec::AudioCodec)s and the video codecs implemented by the applications report their own errors through it.
+    #[error("Codec error: {0}")]
+    Codec(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl<T> From<SendError<T>> for ClientError {
+    fn from(_: SendError<T>) -> Self {
+        Self::ChannelClosed
+    }
+}
@@ -0,0 +1,234 @@
+//!
+//! Provides the source address filtering of the [`Server`](super::server::Server).
+//!
+//! The [`SourceFilter`] is evaluated for every received datagram before it is deserialized, so the filtered addresses can't reach any other part of the server.
+//!
+
+use std::{
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// Custom source filter errors.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum FilterError {
+    /// This error is thrown when the address of a network could not be parsed.
+    #[error("Invalid network address: {0}")]
+    InvalidAddress(String),
+
+    /// This error is thrown when the prefix length of a network is longer than its address.
+    #[error("Invalid prefix length: {0}")]
+    InvalidPrefixLength(String),
+}
+
+///
+/// Ip network type definition.
+///
+/// Describes a range of addresses in the CIDR notation (for example `10.0.0.0/8` or `2001:db8::/32`).
+/// IPv4 networks match the IPv4-mapped IPv6 addresses too, as dual-stack sockets receive IPv4 datagrams from them.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNetwork {
+    /// The address of the network, the bits after the prefix are ignored.
+    addr: IpAddr,
+
+    /// The amount of leading bits of the address the network is defined by.
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    ///
+    /// Creates a new [`IpNetwork`] instance.
+    ///
+    /// # Error
+    /// Returns an error if the `prefix_len` is longer than the `addr` (32 bits for IPv4, 128 bits for IPv6).
+    ///
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, FilterError> {
+        let max_prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        if prefix_len > max_prefix_len {
+            return Err(FilterError::InvalidPrefixLength(prefix_len.to_string()));
+        }
+
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// Returns the address of the network.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the prefix length of the network.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns whether the `addr` is inside the network.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        //Compare the IPv4-mapped addresses as IPv4 addresses
+        let addr = match addr {
+            IpAddr::V6(v6_addr) => v6_addr.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr),
+            IpAddr::V4(_) => addr,
+        };
+
+        match (self.addr, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+
+                u32::from(network) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+
+                u128::from(network) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<IpAddr> for IpNetwork {
+    /// Creates a network containing the `addr` only.
+    fn from(addr: IpAddr) -> Self {
+        Self {
+            addr,
+            prefix_len: match addr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            },
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = FilterError;
+
+    /// Parses a network in the CIDR notation, an address without a prefix length is parsed as a single address network.
+    fn from_str(network: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match network.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (network, None),
+        };
+
+        let addr =
+            IpAddr::from_str(addr).map_err(|_| FilterError::InvalidAddress(addr.to_string()))?;
+
+        match prefix_len {
+            Some(prefix_len) => Self::new(
+                addr,
+                prefix_len
+                    .parse()
+                    .map_err(|_| FilterError::InvalidPrefixLength(prefix_len.to_string()))?,
+            ),
+            None => Ok(Self::from(addr)),
+        }
+    }
+}
+
+impl Display for IpNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Source callback type definition.
+/// Decides whether the datagrams of a source address are accepted, after the allow and deny lists.
+pub type SourceCallback = Arc<dyn Fn(SocketAddr) -> bool + Send + Sync>;
+
+///
+/// Source filter type definition.
+///
+/// Describes which source addresses the [`Server`](super::server::Server) accepts datagrams from.
+/// The default filter accepts every address.
+///
+#[derive(Clone, Default)]
+pub struct SourceFilter {
+    /// The networks the datagrams are accepted from, every network is allowed if this is empty.
+    allow: Vec<IpNetwork>,
+
+    /// The networks the datagrams are discarded from, even if they are allowed.
+    deny: Vec<IpNetwork>,
+
+    /// The callback deciding about the addresses which have passed the lists.
+    callback: Option<SourceCallback>,
+}
+
+impl std::fmt::Debug for SourceFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceFilter")
+            .field("allow", &self.allow)
+            .field("deny", &self.deny)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
+
+impl SourceFilter {
+    /// Creates a new [`SourceFilter`] instance, which accepts every address.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the `network` to the allow list, once the list isn't empty only the listed networks are accepted.
+    pub fn with_allowed(mut self, network: IpNetwork) -> Self {
+        self.allow.push(network);
+        self
+    }
+
+    /// Adds the `network` to the deny list.
+    pub fn with_denied(mut self, network: IpNetwork) -> Self {
+        self.deny.push(network);
+        self
+    }
+
+    /// Sets the callback deciding about the addresses which have passed the allow and deny lists.
+    /// The callback is invoked for every received datagram, so it shouldn't block.
+    pub fn with_callback(
+        mut self,
+        callback: impl Fn(SocketAddr) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Returns the allowed networks.
+    pub fn allowed(&self) -> &[IpNetwork] {
+        &self.allow
+    }
+
+    /// Returns the denied networks.
+    pub fn denied(&self) -> &[IpNetwork] {
+        &self.deny
+    }
+
+    ///
+    /// Returns whether the datagrams of the `source` are accepted.
+    ///
+    /// # Behavior
+    /// The deny list takes precedence over the allow list, then the callback is invoked for the addresses which have passed both lists.
+    ///
+    pub fn is_allowed(&self, source: SocketAddr) -> bool {
+        let ip = source.ip();
+
+        if self.deny.iter().any(|network| network.contains(ip)) {
+            return false;
+        }
+
+        if !self.allow.is_empty() && !self.allow.iter().any(|network| network.contains(ip)) {
+            return false;
+        }
+
+        self.callback
+            .as_ref()
+            .is_none_or(|callback| callback(source))
+    }
+}
@@ -0,0 +1,220 @@
+//!
+//! Provides the floor control of the [`Server`](super::server::Server), which lets only one speaker talk in a room at a time (for example in walkie-talkie style apps).
+//!
+//! The clients request the floor of a room with a [`ControlMessage::FloorRequest`](crate::packet::control::ControlMessage::FloorRequest), and release it with a [`ControlMessage::FloorRelease`](crate::packet::control::ControlMessage::FloorRelease).
+//! The floor is granted to the first requester, the later requests are queued (or denied if the queue is full), and the floor is passed to the next queued requester when it is released.
+//! The voice messages of the authors not holding the floor of their room are discarded.
+//!
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::packet::{control::FloorState, VoipHeader, VoipMessageType};
+
+///
+/// Floor control configuration type definition.
+///
+/// Describes how the [`Server`](super::server::Server) arbitrates the floor of the rooms.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FloorConfig {
+    /// The highest amount of requesters waiting for the floor of a room, the requests are denied once the queue is full.
+    /// The requests are denied whenever the floor is taken if this is `0`.
+    pub max_queue_length: usize,
+
+    /// The longest time the floor can be held for, the floor is revoked and passed on afterwards.
+    /// The floor is held until it is released if this is [`None`].
+    pub max_hold_time: Option<Duration>,
+}
+
+impl Default for FloorConfig {
+    fn default() -> Self {
+        Self {
+            max_queue_length: 8,
+            max_hold_time: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+/// A change of the floor, which has to be signaled with a [`ControlMessage::Floor`](crate::packet::control::ControlMessage::Floor).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FloorNotice {
+    /// The holder of the floor of the room has changed, which is signaled to every peer.
+    Room(u32, FloorState),
+
+    /// The request of the peer at the address was queued or denied, which is only signaled to the peer.
+    Peer(SocketAddr, u32, FloorState),
+}
+
+/// The holder and the queue of the floor of a single room.
+#[derive(Debug, Default)]
+struct RoomFloor {
+    /// The author holding the floor, their address and the time the floor was granted at.
+    holder: Option<(Uuid, SocketAddr, Instant)>,
+
+    /// The requesters waiting for the floor, in the order of their requests.
+    queue: VecDeque<(Uuid, SocketAddr)>,
+}
+
+impl RoomFloor {
+    /// Grants the floor to the next queued requester, or leaves it idle if nobody is waiting.
+    fn pass_on(&mut self, room: u32, now: Instant, notices: &mut Vec<FloorNotice>) {
+        self.holder = self
+            .queue
+            .pop_front()
+            .map(|(author, remote_addr)| (author, remote_addr, now));
+
+        let floor_state = self
+            .holder
+            .map_or(FloorState::Idle, |(author, ..)| FloorState::Granted(author));
+
+        notices.push(FloorNotice::Room(room, floor_state));
+
+        //Every remaining requester has moved forward
+        notices.extend(
+            self.queue
+                .iter()
+                .enumerate()
+                .map(|(position, (_, remote_addr))| {
+                    FloorNotice::Peer(*remote_addr, room, FloorState::Queued(position as u32 + 1))
+                }),
+        );
+    }
+}
+
+/// Arbitrates the floor of every room, see [`FloorConfig`].
+#[derive(Debug)]
+pub(crate) struct FloorControl {
+    /// The configuration of the floor control.
+    config: FloorConfig,
+
+    /// The floor of every room which has been requested.
+    rooms: HashMap<u32, RoomFloor>,
+}
+
+impl FloorControl {
+    /// Creates a new [`FloorControl`] instance.
+    pub(crate) fn new(config: FloorConfig) -> Self {
+        Self {
+            config,
+            rooms: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Handles the floor request of the `author` at the `remote_addr` in the `room`.
+    ///
+    /// # Behavior
+    /// The floor is granted if it is idle, otherwise the request is queued, or denied if the queue is full.
+    /// Repeated requests of the holder or of a queued requester are answered with their current state.
+    ///
+    pub(crate) fn request(
+        &mut self,
+        room: u32,
+        author: Uuid,
+        remote_addr: SocketAddr,
+        now: Instant,
+    ) -> Vec<FloorNotice> {
+        let room_floor = self.rooms.entry(room).or_default();
+
+        let floor_state = match room_floor.holder {
+            None => {
+                room_floor.holder = Some((author, remote_addr, now));
+
+                return vec![FloorNotice::Room(room, FloorState::Granted(author))];
+            }
+            Some((holder, ..)) if holder == author => FloorState::Granted(author),
+            Some(_) => match room_floor
+                .queue
+                .iter()
+                .position(|(queued, _)| *queued == author)
+            {
+                Some(position) => FloorState::Queued(position as u32 + 1),
+                None if room_floor.queue.len() < self.config.max_queue_length => {
+                    room_floor.queue.push_back((author, remote_addr));
+
+                    FloorState::Queued(room_floor.queue.len() as u32)
+                }
+                None => FloorState::Denied,
+            },
+        };
+
+        vec![FloorNotice::Peer(remote_addr, room, floor_state)]
+    }
+
+    /// Releases the floor of the `room` if the `author` holds it (passing it on to the next requester), or removes the `author` from its queue.
+    pub(crate) fn release(&mut self, room: u32, author: Uuid, now: Instant) -> Vec<FloorNotice> {
+        let mut notices = vec![];
+
+        if let Some(room_floor) = self.rooms.get_mut(&room) {
+            if room_floor
+                .holder
+                .is_some_and(|(holder, ..)| holder == author)
+            {
+                room_floor.pass_on(room, now, &mut notices);
+            } else {
+                room_floor.queue.retain(|(queued, _)| *queued != author);
+            }
+
+            if room_floor.holder.is_none() {
+                self.rooms.remove(&room);
+            }
+        }
+
+        notices
+    }
+
+    /// Releases every floor the `author` holds or waits for (for example when the author has left the session).
+    pub(crate) fn remove(&mut self, author: Uuid, now: Instant) -> Vec<FloorNotice> {
+        let rooms: Vec<u32> = self.rooms.keys().copied().collect();
+
+        rooms
+            .into_iter()
+            .flat_map(|room| self.release(room, author, now))
+            .collect()
+    }
+
+    /// Revokes the floors which have been held for longer than [`FloorConfig::max_hold_time`], passing them on to the next requesters.
+    pub(crate) fn expire(&mut self, now: Instant) -> Vec<FloorNotice> {
+        let Some(max_hold_time) = self.config.max_hold_time else {
+            return vec![];
+        };
+
+        let mut notices = vec![];
+
+        for (room, room_floor) in self.rooms.iter_mut() {
+            if room_floor
+                .holder
+                .is_some_and(|(.., granted_at)| now.duration_since(granted_at) >= max_hold_time)
+            {
+                room_floor.pass_on(*room, now, &mut notices);
+            }
+        }
+
+        self.rooms
+            .retain(|_, room_floor| room_floor.holder.is_some());
+
+        notices
+    }
+
+    /// Returns whether the message is forwarded, only the voice messages of the holder of the floor of their room are.
+    pub(crate) fn admits(&self, voip_header: &VoipHeader) -> bool {
+        if !matches!(
+            voip_header.voip_message_type(),
+            VoipMessageType::VoiceMessage(_)
+        ) {
+            return true;
+        }
+
+        self.rooms
+            .get(&voip_header.channel())
+            .and_then(|room_floor| room_floor.holder)
+            .is_some_and(|(holder, ..)| holder == voip_header.author())
+    }
+}
@@ -0,0 +1,158 @@
+//!
+//! Provides the [`FreezeDetector`], which notices when the video of a remote author stops (because its packets are lost, or the author has paused it), and when it resumes.
+//!
+//! Video UIs can use the [`FreezeChange`]s to grey out the stale video of an author consistently, instead of every application timing the frames itself.
+//! A frozen video is reported with the last image decoded from it, so the UI can keep showing it (dimmed, or blurred as a background fill) until the video recovers.
+//! Only the decoded frames keep a video alive, so the frames which can't be decoded (for example while waiting for a keyframe after a loss) don't hide a freeze.
+//!
+
+use std::{collections::HashMap, time::Duration};
+
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use super::video::DecodedVideoFrame;
+
+///
+/// Freeze detection configuration type definition.
+///
+/// Contains the timeout the [`FreezeDetector`] uses.
+///
+#[derive(Debug, Clone)]
+pub struct FreezeConfig {
+    /// The duration without a decoded frame after which the video of an author is considered frozen.
+    pub timeout: Duration,
+}
+
+impl Default for FreezeConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+/// A change of the freeze state of the video of an author.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FreezeChange {
+    /// The video of an author has frozen.
+    Frozen {
+        /// The last frame decoded from the video, which includes its author.
+        frame: DecodedVideoFrame,
+        /// The time passed since the last frame was decoded.
+        age: Duration,
+    },
+
+    /// The video of an author has recovered after it was frozen.
+    Recovered {
+        /// The author of the video.
+        author: Uuid,
+        /// The duration the video was frozen for, since its last frame was decoded before the freeze.
+        frozen_for: Duration,
+    },
+}
+
+/// The activity of the video of a single author.
+#[derive(Debug, Clone)]
+struct VideoActivity {
+    /// The last frame decoded from the video.
+    last_frame: DecodedVideoFrame,
+
+    /// The time the last frame was decoded at.
+    last_decoded: Instant,
+
+    /// Whether the video was reported frozen.
+    frozen: bool,
+}
+
+///
+/// Freeze detector type definition.
+///
+/// Tracks the decoded frames of every author, and reports when their video freezes and recovers.
+///
+#[derive(Debug, Clone)]
+pub struct FreezeDetector {
+    /// The configuration of the detector.
+    config: FreezeConfig,
+
+    /// The video activity of every author.
+    authors: HashMap<Uuid, VideoActivity>,
+}
+
+impl FreezeDetector {
+    /// Creates a new [`FreezeDetector`] instance.
+    pub fn new(config: FreezeConfig) -> Self {
+        Self {
+            config,
+            authors: HashMap::new(),
+        }
+    }
+
+    /// Returns whether the video of the `author` is frozen.
+    pub fn is_frozen(&self, author: Uuid) -> bool {
+        self.authors
+            .get(&author)
+            .is_some_and(|activity| activity.frozen)
+    }
+
+    /// Records the `decoded_frame`, decoded at `now`.
+    /// Returns [`FreezeChange::Recovered`] if the video of its author was frozen.
+    pub fn observe(
+        &mut self,
+        decoded_frame: &DecodedVideoFrame,
+        now: Instant,
+    ) -> Option<FreezeChange> {
+        let previous = self.authors.insert(
+            decoded_frame.author,
+            VideoActivity {
+                last_frame: decoded_frame.clone(),
+                last_decoded: now,
+                frozen: false,
+            },
+        )?;
+
+        previous.frozen.then(|| FreezeChange::Recovered {
+            author: decoded_frame.author,
+            frozen_for: now.duration_since(previous.last_decoded),
+        })
+    }
+
+    /// Marks the videos which haven't decoded a frame for the timeout frozen.
+    /// Returns the [`FreezeChange::Frozen`] of every video which has frozen since the last call.
+    pub fn expire(&mut self, now: Instant) -> Vec<FreezeChange> {
+        let timeout = self.config.timeout;
+
+        self.authors
+            .values_mut()
+            .filter(|activity| !activity.frozen)
+            .filter_map(|activity| {
+                let age = now.duration_since(activity.last_decoded);
+
+                if age < timeout {
+                    return None;
+                }
+
+                activity.frozen = true;
+
+                Some(FreezeChange::Frozen {
+                    frame: activity.last_frame.clone(),
+                    age,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the time the first video which isn't frozen yet freezes at, which is when [`FreezeDetector::expire`] should be called next.
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.authors
+            .values()
+            .filter(|activity| !activity.frozen)
+            .map(|activity| activity.last_decoded + self.config.timeout)
+            .min()
+    }
+
+    /// Forgets the video of the `author` (for example when the author has left the session), without reporting it.
+    pub fn remove_author(&mut self, author: Uuid) {
+        self.authors.remove(&author);
+    }
+}
@@ -0,0 +1,621 @@
+//!
+//! Provides a [gRPC](https://grpc.io/) control plane for the [`Server`](super::server::Server), so orchestration systems can manage fleets of relays programmatically.
+//!
+//! The [`ControlService`] implements the `silence.Control` service (see `proto/control.proto`) on top of a [`ServerHandle`]:
+//! * `ListClients`: Lists every registered peer, with its statistics.
+//! * `KickClient`: Closes the session of a peer, or bans its author.
+//! * `CreateRoom`: Creates a room, and advertises its policy to every peer.
+//! * `GetStats`: Returns the aggregated statistics of the server.
+//! * `StreamEvents`: Streams the [`ServerEvent`]s of the server.
+//!
+//! The service can be served with [`serve`], or added to an existing [`tonic`] server.
+//!
+
+use std::{convert::Infallible, future::Future, net::SocketAddr};
+
+use tokio::time::Instant;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use tonic::{
+    body::BoxBody,
+    codec::ProstCodec,
+    codegen::{http, Body, BoxFuture, BoxStream, Context, Poll, Service, StdError},
+    server::{Grpc, NamedService},
+    Request, Response, Status,
+};
+
+use super::server::{ServerError, ServerEvent, ServerHandle};
+use crate::packet::control::{CloseCode, CloseReason, RoomPolicy};
+
+/// The name of the gRPC service.
+pub const SERVICE_NAME: &str = "silence.Control";
+
+/// The request of `ListClients`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListClientsRequest {}
+
+/// The information of a registered peer.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ClientInfo {
+    /// The address of the peer.
+    #[prost(string, tag = "1")]
+    pub remote_addr: String,
+
+    /// The [`Uuid`](uuid::Uuid) the peer sends its messages with.
+    #[prost(string, tag = "2")]
+    pub author: String,
+
+    /// The milliseconds elapsed since the last heartbeat of the peer.
+    #[prost(uint64, tag = "3")]
+    pub last_seen_ms_ago: u64,
+
+    /// The milliseconds elapsed since the last message of the peer.
+    #[prost(uint64, tag = "4")]
+    pub last_packet_ms_ago: u64,
+
+    /// The amount of messages received from the peer.
+    #[prost(uint64, tag = "5")]
+    pub packets_received: u64,
+
+    /// The amount of messages relayed to the peer.
+    #[prost(uint64, tag = "6")]
+    pub packets_sent: u64,
+
+    /// The estimated loss (between `0` and `1`) of the voice messages received from the peer.
+    #[prost(float, tag = "7")]
+    pub estimated_loss: f32,
+}
+
+/// The response of `ListClients`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListClientsResponse {
+    /// Every registered peer.
+    #[prost(message, repeated, tag = "1")]
+    pub clients: Vec<ClientInfo>,
+}
+
+/// The request of `KickClient`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct KickClientRequest {
+    /// The address of the peer.
+    #[prost(string, tag = "1")]
+    pub remote_addr: String,
+
+    /// The message the session is closed with.
+    #[prost(string, optional, tag = "2")]
+    pub message: Option<String>,
+
+    /// Whether the author of the peer is banned (see [`ServerHandle::ban`]), which closes every session of the author with [`CloseCode::Banned`] instead of [`CloseCode::Kicked`].
+    #[prost(bool, tag = "3")]
+    pub ban: bool,
+}
+
+/// The response of `KickClient`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct KickClientResponse {}
+
+/// The request of `CreateRoom`, containing the fields of the [`RoomPolicy`] of the room.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CreateRoomRequest {
+    /// The channel of the room.
+    #[prost(uint32, tag = "1")]
+    pub room: u32,
+
+    /// The level (in -dBov) the voice of the clients is normalized to.
+    #[prost(uint32, optional, tag = "2")]
+    pub target_loudness: Option<u32>,
+
+    /// The highest bitrate (in bits per second) the clients may send voice messages at.
+    #[prost(uint32, optional, tag = "3")]
+    pub max_bitrate: Option<u32>,
+
+    /// Whether the clients must use discontinuous transmission.
+    #[prost(bool, tag = "4")]
+    pub mandatory_dtx: bool,
+
+    /// The highest bitrate (in bits per second) the clients may send video messages at.
+    #[prost(uint32, optional, tag = "5")]
+    pub max_video_bitrate: Option<u32>,
+
+    /// Whether the clients may not send video at all.
+    #[prost(bool, tag = "6")]
+    pub video_disabled: bool,
+
+    /// Whether the clients may not send video while they are sharing their screen.
+    #[prost(bool, tag = "7")]
+    pub screen_sharing_disabled: bool,
+}
+
+/// The response of `CreateRoom`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CreateRoomResponse {}
+
+/// The request of `GetStats`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetStatsRequest {}
+
+/// The response of `GetStats`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetStatsResponse {
+    /// The amount of registered peers.
+    #[prost(uint32, tag = "1")]
+    pub peer_count: u32,
+
+    /// The amount of rooms.
+    #[prost(uint32, tag = "2")]
+    pub room_count: u32,
+
+    /// The amount of messages received from the registered peers.
+    #[prost(uint64, tag = "3")]
+    pub packets_received: u64,
+
+    /// The amount of messages relayed to the registered peers.
+    #[prost(uint64, tag = "4")]
+    pub packets_sent: u64,
+}
+
+/// The request of `StreamEvents`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StreamEventsRequest {}
+
+/// The kind of an [`Event`], matching the variants of [`ServerEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum EventKind {
+    /// [`ServerEvent::PeerJoined`].
+    PeerJoined = 0,
+
+    /// [`ServerEvent::PeerLeft`].
+    PeerLeft = 1,
+
+    /// [`ServerEvent::PeerClosed`].
+    PeerClosed = 2,
+
+    /// [`ServerEvent::RoomCreated`].
+    RoomCreated = 3,
+
+    /// [`ServerEvent::RoomDestroyed`].
+    RoomDestroyed = 4,
+
+    /// [`ServerEvent::RecordingStarted`].
+    RecordingStarted = 5,
+
+    /// [`ServerEvent::RecordingStopped`].
+    RecordingStopped = 6,
+
+    /// [`ServerEvent::PeerMoved`].
+    PeerMoved = 7,
+
+    /// [`ServerEvent::PeerMigrated`].
+    PeerMigrated = 8,
+
+    /// [`ServerEvent::AuthorBanned`].
+    AuthorBanned = 9,
+
+    /// [`ServerEvent::AuthorUnbanned`].
+    AuthorUnbanned = 10,
+
+    /// [`ServerEvent::ConnectionRejected`].
+    ConnectionRejected = 11,
+
+    /// [`ServerEvent::RecordingConsentChanged`], with the consent given.
+    RecordingConsentGiven = 12,
+
+    /// [`ServerEvent::RecordingConsentChanged`], with the consent withdrawn.
+    RecordingConsentWithdrawn = 13,
+
+    /// [`ServerEvent::Overloaded`].
+    Overloaded = 14,
+
+    /// [`ServerEvent::OverloadRelieved`].
+    OverloadRelieved = 15,
+
+    /// [`ServerEvent::PathError`].
+    PathError = 16,
+}
+
+/// A [`ServerEvent`] streamed by `StreamEvents`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Event {
+    /// The kind of the event.
+    #[prost(enumeration = "EventKind", tag = "1")]
+    pub kind: i32,
+
+    /// The address of the peer, if the event is about a peer.
+    #[prost(string, tag = "2")]
+    pub remote_addr: String,
+
+    /// The [`Uuid`](uuid::Uuid) of the peer, if the event is about a registered peer.
+    #[prost(string, tag = "3")]
+    pub author: String,
+
+    /// The channel of the room, if the event is about a room.
+    #[prost(uint32, tag = "4")]
+    pub room: u32,
+
+    /// The message the session of the peer was closed (or rejected) with, the reason of the ban, or the kind of the path error, if there was one.
+    #[prost(string, optional, tag = "5")]
+    pub message: Option<String>,
+
+    /// The previous address of the peer, if the event is about a migrated peer.
+    #[prost(string, tag = "6")]
+    pub previous_addr: String,
+}
+
+impl From<ServerEvent> for Event {
+    fn from(server_event: ServerEvent) -> Self {
+        match server_event {
+            ServerEvent::PeerJoined {
+                remote_addr,
+                author,
+            } => Self {
+                kind: EventKind::PeerJoined as i32,
+                remote_addr: remote_addr.to_string(),
+                author: author.to_string(),
+                ..Default::default()
+            },
+            ServerEvent::PeerLeft {
+                remote_addr,
+                author,
+            } => Self {
+                kind: EventKind::PeerLeft as i32,
+                remote_addr: remote_addr.to_string(),
+                author: author.to_string(),
+                ..Default::default()
+            },
+            ServerEvent::PeerClosed {
+                remote_addr,
+                author,
+                close_reason,
+            } => Self {
+                kind: EventKind::PeerClosed as i32,
+                remote_addr: remote_addr.to_string(),
+                author: author.map(|author| author.to_string()).unwrap_or_default(),
+                message: close_reason.message().map(String::from),
+                ..Default::default()
+            },
+            ServerEvent::RoomCreated { room, .. } => Self {
+                kind: EventKind::RoomCreated as i32,
+                room,
+                ..Default::default()
+            },
+            ServerEvent::PeerMoved {
+                remote_addr,
+                author,
+                room,
+            } => Self {
+                kind: EventKind::PeerMoved as i32,
+                remote_addr: remote_addr.to_string(),
+                author: author.to_string(),
+                room,
+                ..Default::default()
+            },
+            ServerEvent::PeerMigrated {
+                previous_addr,
+                remote_addr,
+                author,
+            } => Self {
+                kind: EventKind::PeerMigrated as i32,
+                remote_addr: remote_addr.to_string(),
+                author: author.to_string(),
+                previous_addr: previous_addr.to_string(),
+                ..Default::default()
+            },
+            ServerEvent::RoomDestroyed { room } => Self {
+                kind: EventKind::RoomDestroyed as i32,
+                room,
+                ..Default::default()
+            },
+            ServerEvent::RecordingStarted { room } => Self {
+                kind: EventKind::RecordingStarted as i32,
+                room,
+                ..Default::default()
+            },
+            ServerEvent::RecordingStopped { room } => Self {
+                kind: EventKind::RecordingStopped as i32,
+                room,
+                ..Default::default()
+            },
+            ServerEvent::RecordingConsentChanged {
+                remote_addr,
+                author,
+                room,
+                consent,
+            } => Self {
+                kind: if consent {
+                    EventKind::RecordingConsentGiven as i32
+                } else {
+                    EventKind::RecordingConsentWithdrawn as i32
+                },
+                remote_addr: remote_addr.to_string(),
+                author: author.to_string(),
+                room,
+                ..Default::default()
+            },
+            ServerEvent::AuthorBanned { author, reason } => Self {
+                kind: EventKind::AuthorBanned as i32,
+                author: author.to_string(),
+                message: reason,
+                ..Default::default()
+            },
+            ServerEvent::AuthorUnbanned { author } => Self {
+                kind: EventKind::AuthorUnbanned as i32,
+                author: author.to_string(),
+                ..Default::default()
+            },
+            ServerEvent::ConnectionRejected {
+                remote_addr,
+                author,
+                close_reason,
+            } => Self {
+                kind: EventKind::ConnectionRejected as i32,
+                remote_addr: remote_addr.to_string(),
+                author: author.to_string(),
+                message: close_reason.message().map(String::from),
+                ..Default::default()
+            },
+            ServerEvent::Overloaded { .. } => Self {
+                kind: EventKind::Overloaded as i32,
+                ..Default::default()
+            },
+            ServerEvent::OverloadRelieved { .. } => Self {
+                kind: EventKind::OverloadRelieved as i32,
+                ..Default::default()
+            },
+            ServerEvent::PathError {
+                remote_addr,
+                author,
+                kind,
+                ..
+            } => Self {
+                kind: EventKind::PathError as i32,
+                remote_addr: remote_addr.to_string(),
+                author: author.map(|author| author.to_string()).unwrap_or_default(),
+                message: Some(format!("{kind:?}")),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+///
+/// Control service type definition.
+///
+/// Implements the `silence.Control` gRPC service, managing the [`Server`](super::server::Server) of the [`ServerHandle`].
+///
+#[derive(Debug, Clone)]
+pub struct ControlService {
+    /// The handle of the managed server.
+    handle: ServerHandle,
+}
+
+impl ControlService {
+    /// Creates a new [`ControlService`] instance, managing the server of the [`ServerHandle`].
+    pub fn new(handle: ServerHandle) -> Self {
+        Self { handle }
+    }
+}
+
+impl NamedService for ControlService {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+impl<B> Service<http::Request<B>> for ControlService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let handle = self.handle.clone();
+
+        match request.uri().path() {
+            "/silence.Control/ListClients" => Box::pin(async move {
+                Ok(Grpc::new(ProstCodec::default())
+                    .unary(
+                        Handler(move |_: Request<ListClientsRequest>| list_clients(handle.clone())),
+                        request,
+                    )
+                    .await)
+            }),
+            "/silence.Control/KickClient" => Box::pin(async move {
+                Ok(Grpc::new(ProstCodec::default())
+                    .unary(
+                        Handler(move |request| kick_client(handle.clone(), request)),
+                        request,
+                    )
+                    .await)
+            }),
+            "/silence.Control/CreateRoom" => Box::pin(async move {
+                Ok(Grpc::new(ProstCodec::default())
+                    .unary(
+                        Handler(move |request| create_room(handle.clone(), request)),
+                        request,
+                    )
+                    .await)
+            }),
+            "/silence.Control/GetStats" => Box::pin(async move {
+                Ok(Grpc::new(ProstCodec::default())
+                    .unary(
+                        Handler(move |_: Request<GetStatsRequest>| get_stats(handle.clone())),
+                        request,
+                    )
+                    .await)
+            }),
+            "/silence.Control/StreamEvents" => Box::pin(async move {
+                Ok(Grpc::new(ProstCodec::default())
+                    .server_streaming(
+                        Handler(move |_: Request<StreamEventsRequest>| {
+                            stream_events(handle.clone())
+                        }),
+                        request,
+                    )
+                    .await)
+            }),
+            _ => Box::pin(async move { Ok(Status::unimplemented("").into_http()) }),
+        }
+    }
+}
+
+/// Adapts an async function handling the requests of a method to a [`Service`].
+struct Handler<F>(F);
+
+impl<F, Fut, M1, M2> Service<Request<M1>> for Handler<F>
+where
+    F: FnMut(Request<M1>) -> Fut,
+    Fut: Future<Output = Result<Response<M2>, Status>>,
+{
+    type Response = Response<M2>;
+    type Error = Status;
+    type Future = Fut;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<M1>) -> Self::Future {
+        (self.0)(request)
+    }
+}
+
+/// Handles `ListClients`.
+async fn list_clients(handle: ServerHandle) -> Result<Response<ListClientsResponse>, Status> {
+    let now = Instant::now();
+
+    let clients = handle
+        .stats()
+        .peers
+        .into_iter()
+        .map(|(remote_addr, peer_stats)| ClientInfo {
+            remote_addr: remote_addr.to_string(),
+            author: peer_stats.author.to_string(),
+            last_seen_ms_ago: now
+                .saturating_duration_since(peer_stats.last_seen)
+                .as_millis() as u64,
+            last_packet_ms_ago: now
+                .saturating_duration_since(peer_stats.last_packet)
+                .as_millis() as u64,
+            packets_received: peer_stats.packets_received,
+            packets_sent: peer_stats.packets_sent,
+            estimated_loss: peer_stats.estimated_loss,
+        })
+        .collect();
+
+    Ok(Response::new(ListClientsResponse { clients }))
+}
+
+/// Handles `KickClient`.
+async fn kick_client(
+    handle: ServerHandle,
+    request: Request<KickClientRequest>,
+) -> Result<Response<KickClientResponse>, Status> {
+    let request = request.into_inner();
+
+    let remote_addr: SocketAddr = request
+        .remote_addr
+        .parse()
+        .map_err(|_| Status::invalid_argument("Invalid remote address."))?;
+
+    let Some(author) = handle.peers().get(&remote_addr).map(|peer| peer.author()) else {
+        return Err(Status::not_found("No such client."));
+    };
+
+    //Banning adds the author to the ban list, which closes every session of the author
+    if request.ban {
+        handle
+            .ban(author, request.message)
+            .await
+            .map_err(|err| Status::unavailable(err.to_string()))?;
+    } else {
+        handle
+            .close_client(
+                remote_addr,
+                CloseReason::new(CloseCode::Kicked, request.message),
+            )
+            .await
+            .map_err(|err| match err {
+                //The client may have left since it was looked up
+                ServerError::NotConnected(_) => Status::not_found("No such client."),
+                err => Status::unavailable(err.to_string()),
+            })?;
+    }
+
+    Ok(Response::new(KickClientResponse {}))
+}
+
+/// Handles `CreateRoom`.
+async fn create_room(
+    handle: ServerHandle,
+    request: Request<CreateRoomRequest>,
+) -> Result<Response<CreateRoomResponse>, Status> {
+    let request = request.into_inner();
+
+    let target_loudness = request
+        .target_loudness
+        .map(u8::try_from)
+        .transpose()
+        .map_err(|_| Status::invalid_argument("The target loudness must fit in a byte."))?;
+
+    handle
+        .create_room(
+            request.room,
+            RoomPolicy {
+                target_loudness,
+                max_bitrate: request.max_bitrate,
+                mandatory_dtx: request.mandatory_dtx,
+                allowed_codecs: None,
+                max_video_bitrate: request.max_video_bitrate,
+                video_disabled: request.video_disabled,
+                screen_sharing_disabled: request.screen_sharing_disabled,
+            },
+        )
+        .await
+        .map_err(|err| Status::unavailable(err.to_string()))?;
+
+    Ok(Response::new(CreateRoomResponse {}))
+}
+
+/// Handles `GetStats`.
+async fn get_stats(handle: ServerHandle) -> Result<Response<GetStatsResponse>, Status> {
+    let stats = handle.stats();
+
+    Ok(Response::new(GetStatsResponse {
+        peer_count: stats.peers.len() as u32,
+        room_count: handle.room_policies().len() as u32,
+        packets_received: stats
+            .peers
+            .values()
+            .map(|peer_stats| peer_stats.packets_received)
+            .sum(),
+        packets_sent: stats
+            .peers
+            .values()
+            .map(|peer_stats| peer_stats.packets_sent)
+            .sum(),
+    }))
+}
+
+/// Handles `StreamEvents`.
+/// The events skipped because the stream lagged behind are left out.
+async fn stream_events(handle: ServerHandle) -> Result<Response<BoxStream<Event>>, Status> {
+    let events = BroadcastStream::new(handle.subscribe_events())
+        .filter_map(Result::ok)
+        .map(Event::from)
+        .map(Ok);
+
+    Ok(Response::new(Box::pin(events)))
+}
+
+/// Serves the [`ControlService`] of the [`ServerHandle`] at the `addr`, until the [`tonic`] server fails.
+pub async fn serve(handle: ServerHandle, addr: SocketAddr) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(ControlService::new(handle))
+        .serve(addr)
+        .await
+}
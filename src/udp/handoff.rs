@@ -0,0 +1,222 @@
+//!
+//! Provides the [`SessionHandoff`], which moves the sessions of a running [`Server`](super::server::Server) to a new server instance, so the relays can be upgraded without dropping the active calls.
+//!
+//! The clients keep sending to the same port during the upgrade, so both servers have to bind their sockets with [`bind_reuse_port`]:
+//! 1. The new process binds its socket to the port of the old server with [`bind_reuse_port`]. The datagrams the kernel routes to it are queued until it serves them.
+//! 2. The old server is stopped with [`ServerHandle::handoff`](super::server::ServerHandle::handoff), which returns the state of its sessions without closing them.
+//! 3. The [`SessionHandoff`] is passed to the new process (it can be serialized with any [`serde`] format, for example into a file or a pipe), which creates its server with it in [`ServerConfig::handoff`](super::server::ServerConfig::handoff).
+//!
+//! The clients aren't notified, and they keep their peers, rooms and recordings. Only the datagrams received by the old socket after it was stopped are lost.
+//!
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
+
+use uuid::Uuid;
+
+use super::server::Ban;
+use crate::packet::{
+    control::{
+        ConnectionId, LayerSelection, MediaState, PresenceState, RecordingState, RoomPolicy,
+    },
+    MediaCodec,
+};
+
+///
+/// Session handoff type definition.
+///
+/// The state of the sessions of a [`Server`](super::server::Server), which is restored by the server it is handed over to.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SessionHandoff {
+    /// The registered peers, with their addresses.
+    pub peers: Vec<HandoffPeer>,
+
+    /// The addresses on the reply list of the server (see [`Server::get_reply_to_list_mut`](super::server::Server::get_reply_to_list_mut)).
+    pub reply_list: Vec<SocketAddr>,
+
+    /// The [`RoomPolicy`] of every room.
+    pub room_policies: HashMap<u32, RoomPolicy>,
+
+    /// The [`RecordingState`] of every recorded room.
+    pub recordings: HashMap<u32, RecordingState>,
+
+    /// The [`Ban`] of every banned author, including the ones banned since the server was created.
+    pub bans: HashMap<Uuid, Ban>,
+}
+
+///
+/// Handoff peer type definition.
+///
+/// The state of a single peer in a [`SessionHandoff`]. The statistics of the peer aren't handed over, they start over on the new server.
+///
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HandoffPeer {
+    /// The address of the peer.
+    pub remote_addr: SocketAddr,
+
+    /// The [`Uuid`] the peer sends its messages with.
+    pub author: Uuid,
+
+    /// The channel (or room) the peer is talking in.
+    pub room: u32,
+
+    /// The presence state the peer has set.
+    pub presence: PresenceState,
+
+    /// The latest media state the peer has signaled in every room.
+    pub media_states: HashMap<u32, MediaState>,
+
+    /// The recorded rooms the peer has consented to the recording of.
+    pub recording_consents: HashSet<u32>,
+
+    /// The audio codecs the peer has advertised, besides [`MediaCodec::Opus`].
+    pub codecs: Option<Vec<MediaCodec>>,
+
+    /// The highest bitrate (in bits per second) the peer can receive, if it has signaled one.
+    pub max_bitrate: Option<u32>,
+
+    /// The simulcast layer the peer has selected from every author.
+    pub layer_selections: HashMap<Uuid, LayerSelection>,
+
+    /// Whether the peer was placed on hold.
+    pub on_hold: bool,
+
+    /// Whether the peer has paused receiving video with a [`ControlMessage::PauseVideo`](crate::packet::control::ControlMessage::PauseVideo).
+    #[serde(default)]
+    pub video_paused: bool,
+
+    /// The id of the peer among the devices of its author.
+    #[serde(default)]
+    pub device: u32,
+
+    /// The [`ConnectionId`] issued to the session of the peer, a new one is issued if this is [`None`].
+    #[serde(default)]
+    pub connection_id: Option<ConnectionId>,
+}
+
+///
+/// Binds a [`UdpSocket`](tokio::net::UdpSocket) to the `addr` with `SO_REUSEPORT`, so that the old and the new server of an upgrade can be bound to the same port.
+///
+/// # Behavior
+/// IPv6 sockets accept the IPv4 mapped peers too, like the socket bound by [`Server::new`](super::server::Server::new).
+///
+/// # Error
+/// Returns an error if the socket could not be created, configured or bound (for example if the port is bound without `SO_REUSEPORT`).
+///
+#[cfg(unix)]
+pub fn bind_reuse_port(addr: SocketAddr) -> std::io::Result<tokio::net::UdpSocket> {
+    let socket = sys::bind_reuse_port(addr)?;
+
+    socket.set_nonblocking(true)?;
+
+    tokio::net::UdpSocket::from_std(socket)
+}
+
+/// The socket options and the binding of the reusable sockets on unix.
+#[cfg(unix)]
+mod sys {
+    use std::{
+        io, mem,
+        net::{SocketAddr, UdpSocket},
+        os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    };
+
+    /// Sets an integer socket option on the `fd`.
+    fn set_option(
+        fd: RawFd,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: libc::c_int,
+    ) -> io::Result<()> {
+        // SAFETY: The value is a valid `c_int` which outlives the call, and its size is passed along.
+        let result = unsafe {
+            libc::setsockopt(
+                fd,
+                level,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Creates a datagram socket with `SO_REUSEADDR` and `SO_REUSEPORT` set, and binds it to the `addr`.
+    pub(super) fn bind_reuse_port(addr: SocketAddr) -> io::Result<UdpSocket> {
+        let domain = match addr {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6,
+        };
+
+        // SAFETY: Creating a socket has no preconditions, the returned descriptor is checked before it is owned.
+        let fd = unsafe { libc::socket(domain, libc::SOCK_DGRAM, 0) };
+
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: The descriptor was just created, and nothing else owns it.
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        set_option(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1)?;
+        set_option(fd, libc::SOL_SOCKET, libc::SO_REUSEPORT, 1)?;
+
+        // SAFETY: An all zero `sockaddr_storage` is valid, and it is large enough for both address families.
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+
+        let length = match addr {
+            SocketAddr::V4(addr) => {
+                // SAFETY: The storage is aligned and large enough for a `sockaddr_in`.
+                let sockaddr = unsafe {
+                    &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in)
+                };
+
+                sockaddr.sin_family = libc::AF_INET as libc::sa_family_t;
+                sockaddr.sin_port = addr.port().to_be();
+                sockaddr.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+
+                mem::size_of::<libc::sockaddr_in>()
+            }
+            SocketAddr::V6(addr) => {
+                //Accept the IPv4 mapped peers, regardless of the default of the system
+                set_option(fd, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY, 0)?;
+
+                // SAFETY: The storage is aligned and large enough for a `sockaddr_in6`.
+                let sockaddr = unsafe {
+                    &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6)
+                };
+
+                sockaddr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sockaddr.sin6_port = addr.port().to_be();
+                sockaddr.sin6_addr.s6_addr = addr.ip().octets();
+                sockaddr.sin6_flowinfo = addr.flowinfo();
+                sockaddr.sin6_scope_id = addr.scope_id();
+
+                mem::size_of::<libc::sockaddr_in6>()
+            }
+        };
+
+        // SAFETY: The storage contains a valid socket address of the advertised length.
+        let result = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                &storage as *const libc::sockaddr_storage as *const libc::sockaddr,
+                length as libc::socklen_t,
+            )
+        };
+
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(UdpSocket::from(socket))
+    }
+}
@@ -0,0 +1,72 @@
+//!
+//! Provides the [`HoldMusic`] of the [`Server`](super::server::Server), which is played to the clients placed on hold (for example parked calls waiting at a reception).
+//!
+//! The media of a held client isn't forwarded, and it isn't sent the media of the other clients, it is sent the [`HoldMusic`] in a loop instead, until it is retrieved.
+//! The control messages of a held client are still handled, so it stays in the session.
+//!
+
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::packet::{frame::VoiceFrame, MediaCodec};
+
+///
+/// Hold music type definition.
+///
+/// An audio loop made of encoded voice frames, which the [`Server`](super::server::Server) sends to the held clients one after the other.
+/// The frames are sent as they are, so they have to be encoded with a codec and settings the clients can decode (for example Opus at the sample rate of their voice configuration).
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoldMusic {
+    /// The [`MediaCodec`] the frames are encoded with.
+    pub codec: MediaCodec,
+
+    /// The encoded frames of the loop.
+    pub frames: Vec<Vec<u8>>,
+
+    /// The duration of a single frame, which is also the interval the frames are sent at.
+    pub frame_duration: Duration,
+}
+
+impl HoldMusic {
+    /// Creates a new [`HoldMusic`] instance from the encoded `frames` of the loop.
+    pub fn new(codec: MediaCodec, frames: Vec<Vec<u8>>, frame_duration: Duration) -> Self {
+        Self {
+            codec,
+            frames,
+            frame_duration,
+        }
+    }
+}
+
+/// The position of a held client in the [`HoldMusic`].
+#[derive(Debug, Default)]
+pub(crate) struct HoldPlayback {
+    /// The sequence number of the next frame, which is also the amount of frames sent so far.
+    sequence: u32,
+}
+
+impl HoldPlayback {
+    /// Returns the next frame of the `hold_music` with the `author`, or [`None`] if the loop is empty.
+    pub(crate) fn next_frame(
+        &mut self,
+        hold_music: &HoldMusic,
+        author: Uuid,
+    ) -> Option<VoiceFrame> {
+        if hold_music.frames.is_empty() {
+            return None;
+        }
+
+        let payload = hold_music.frames[self.sequence as usize % hold_music.frames.len()].clone();
+
+        let mut voice_frame = VoiceFrame::new(author, hold_music.codec, payload);
+
+        voice_frame.sequence = Some(self.sequence);
+        voice_frame.timestamp = Some(hold_music.frame_duration * self.sequence);
+
+        self.sequence = self.sequence.wrapping_add(1);
+
+        Some(voice_frame)
+    }
+}
@@ -0,0 +1,117 @@
+//!
+//! Provides event sinks, which keep external systems (for example billing, analytics or chat bridges) in sync with the lifecycle of the [`Server`](super::server::Server).
+//!
+//! An [`EventSink`] is fed every [`ServerEvent`] by a task spawned with [`ServerHandle::add_event_sink`].
+//! Any async closure taking a [`ServerEvent`] is an [`EventSink`], and the [`WebhookSink`] (enabled with the `webhook` feature) posts the events to an HTTP endpoint.
+//!
+
+use std::future::Future;
+
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{event, Level};
+
+use super::{
+    runtime::{Runtime, Tokio},
+    server::{ServerEvent, ServerHandle},
+};
+
+///
+/// Event sink trait definition.
+///
+/// Receives the [`ServerEvent`]s of a [`Server`](super::server::Server), in the order they have happened.
+///
+pub trait EventSink: Send + Sync + 'static {
+    /// Handles the [`ServerEvent`].
+    /// The next event is only handled once the returned future has completed, so slow sinks should hand the events off to their own tasks.
+    fn send_event(&self, server_event: ServerEvent) -> impl Future<Output = ()> + Send;
+}
+
+impl<F, Fut> EventSink for F
+where
+    F: Fn(ServerEvent) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    fn send_event(&self, server_event: ServerEvent) -> impl Future<Output = ()> + Send {
+        self(server_event)
+    }
+}
+
+impl ServerHandle {
+    /// Feeds every [`ServerEvent`] happening from now on to the [`EventSink`], from a task spawned on the [`Tokio`] runtime.
+    pub fn add_event_sink(&self, event_sink: impl EventSink) {
+        self.add_event_sink_with_runtime::<Tokio>(event_sink)
+    }
+
+    ///
+    /// Feeds every [`ServerEvent`] happening from now on to the [`EventSink`], from a task spawned on the [`Runtime`] `R`.
+    ///
+    /// # Behavior
+    /// The task stops once the server service has shut down.
+    /// If the sink falls too far behind, the oldest events are skipped (and a warning is logged).
+    ///
+    pub fn add_event_sink_with_runtime<R: Runtime>(&self, event_sink: impl EventSink) {
+        let mut event_receiver = self.subscribe_events();
+
+        R::spawn(async move {
+            loop {
+                match event_receiver.recv().await {
+                    Ok(server_event) => event_sink.send_event(server_event).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        event!(Level::WARN, "An event sink has skipped {skipped} events.");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+///
+/// Webhook sink type definition.
+///
+/// Posts every [`ServerEvent`] as a JSON object to an HTTP endpoint.
+/// The failed requests are logged, they aren't retried.
+///
+#[cfg(feature = "webhook")]
+#[derive(Debug, Clone)]
+pub struct WebhookSink {
+    /// The client the requests are sent with.
+    client: reqwest::Client,
+
+    /// The URL of the endpoint.
+    url: reqwest::Url,
+}
+
+#[cfg(feature = "webhook")]
+impl WebhookSink {
+    /// Creates a new [`WebhookSink`] instance, posting the events to the `url`.
+    pub fn new(url: reqwest::Url) -> Self {
+        Self::with_client(reqwest::Client::new(), url)
+    }
+
+    /// Creates a new [`WebhookSink`] instance, posting the events to the `url` with the `client` (for example to set timeouts or default headers).
+    pub fn with_client(client: reqwest::Client, url: reqwest::Url) -> Self {
+        Self { client, url }
+    }
+}
+
+#[cfg(feature = "webhook")]
+impl EventSink for WebhookSink {
+    async fn send_event(&self, server_event: ServerEvent) {
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(&server_event)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+
+        if let Err(err) = response {
+            event!(
+                Level::ERROR,
+                "Failed to post an event to {}: {err}",
+                self.url
+            );
+        }
+    }
+}
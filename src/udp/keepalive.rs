@@ -0,0 +1,180 @@
+//!
+//! Provides the learning of the heartbeat interval of the [`Client`](super::client::Client), the longest interval which still keeps its NAT binding alive.
+//!
+//! The NATs forget the bindings of the UDP flows which stay silent for too long, after which the messages of the server can't reach the client anymore.
+//! Sending the heartbeats more often than needed wakes the radio of the mobile clients up for nothing, so the [`KeepaliveLearner`] searches for the longest interval the NAT tolerates.
+//! The interval is probed by staying silent for it: the server answers a [`ControlMessage::KeepaliveProbe`](crate::packet::control::ControlMessage::KeepaliveProbe) once its delay has passed, and the answer only arrives if the binding has survived the silence.
+//! The probed interval is doubled while the binding survives, then the interval is binary searched between the longest survived and the shortest expired silence.
+//!
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::packet::control::MAX_KEEPALIVE_PROBE_DELAY_MS;
+
+/// The first sequence number of the keepalive probes, so that their answers aren't mistaken for the answers of the diagnostic pings counting from `0`.
+const PROBE_SEQUENCE_START: u32 = 0x8000_0000;
+
+///
+/// Keepalive configuration type definition.
+///
+/// Describes the range and the precision of the heartbeat interval learned by the [`KeepaliveLearner`].
+/// The search starts from the [`ClientConfig::heartbeat_interval`](super::client::ClientConfig::heartbeat_interval), which should be short enough for any NAT.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    /// The longest interval which is probed, the server caps the probes at [`MAX_KEEPALIVE_PROBE_DELAY_MS`].
+    pub max_interval: Duration,
+
+    /// The search stops once the longest survived and the shortest expired silence are closer than this.
+    pub precision: Duration,
+
+    /// The time the answer of a probe is awaited for after its delay, which should cover the round trip time to the server.
+    pub probe_timeout: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            max_interval: Duration::from_secs(300),
+            precision: Duration::from_secs(5),
+            probe_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// A probe of the [`KeepaliveLearner`] waiting for its answer.
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    /// The sequence number the answer of the probe carries.
+    sequence: u32,
+
+    /// The silence the probe tests the binding with.
+    interval: Duration,
+
+    /// The time the probe expires at, if it isn't answered before.
+    deadline: Instant,
+}
+
+///
+/// Keepalive learner type definition.
+///
+/// Learns the longest heartbeat interval the NAT of the client tolerates, from the answers of the keepalive probes.
+///
+#[derive(Debug, Clone)]
+pub struct KeepaliveLearner {
+    /// The range and the precision of the learned interval.
+    config: KeepaliveConfig,
+
+    /// The longest silence the binding has survived, which is the interval of the heartbeats.
+    tolerated: Duration,
+
+    /// The shortest silence the binding has expired after, if any has expired yet.
+    expired: Option<Duration>,
+
+    /// The probe waiting for its answer.
+    probe: Option<Probe>,
+
+    /// The sequence number of the next probe.
+    next_sequence: u32,
+}
+
+impl KeepaliveLearner {
+    /// Creates a new [`KeepaliveLearner`] instance, starting the search from the `initial_interval`.
+    pub fn new(config: KeepaliveConfig, initial_interval: Duration) -> Self {
+        Self {
+            config,
+            tolerated: initial_interval,
+            expired: None,
+            probe: None,
+            next_sequence: PROBE_SEQUENCE_START,
+        }
+    }
+
+    /// Returns the interval the heartbeats should be sent at, the longest silence the binding has survived.
+    pub fn interval(&self) -> Duration {
+        self.tolerated
+    }
+
+    /// Returns whether the search has finished, no more probes are sent afterwards.
+    pub fn is_converged(&self) -> bool {
+        self.tolerated >= self.max_interval()
+            || self.expired.is_some_and(|expired| {
+                expired.saturating_sub(self.tolerated) <= self.config.precision
+            })
+    }
+
+    /// Returns the time the pending probe expires at, the heartbeats are held back until then so that the binding stays silent.
+    pub fn probe_deadline(&self) -> Option<Instant> {
+        self.probe.map(|probe| probe.deadline)
+    }
+
+    ///
+    /// Starts probing the next interval of the search, right after a heartbeat was sent.
+    ///
+    /// # Behavior
+    /// Returns the sequence number and the delay of the probe which has to be sent to the server, or [`None`] if the search has converged or a probe is already pending.
+    ///
+    pub fn start_probe(&mut self, now: Instant) -> Option<(u32, Duration)> {
+        if self.probe.is_some() || self.is_converged() {
+            return None;
+        }
+
+        let interval = match self.expired {
+            Some(expired) => (self.tolerated + expired) / 2,
+            None => (self.tolerated * 2).min(self.max_interval()),
+        };
+
+        let sequence = self.next_sequence;
+
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.probe = Some(Probe {
+            sequence,
+            interval,
+            deadline: now + interval + self.config.probe_timeout,
+        });
+
+        Some((sequence, interval))
+    }
+
+    /// Records the answer of the probe with the `sequence` number, the binding has survived its silence.
+    /// Returns the new interval of the heartbeats, or [`None`] if the answer doesn't belong to the pending probe.
+    pub fn confirm(&mut self, sequence: u32) -> Option<Duration> {
+        let probe = self.probe.filter(|probe| probe.sequence == sequence)?;
+
+        self.probe = None;
+        self.tolerated = self.tolerated.max(probe.interval);
+
+        Some(self.tolerated)
+    }
+
+    /// Records the expiry of the pending probe if its deadline has passed, the binding hasn't survived its silence.
+    /// Returns whether the probe has expired.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let Some(probe) = self.probe.filter(|probe| now >= probe.deadline) else {
+            return false;
+        };
+
+        self.probe = None;
+        self.expired = Some(
+            self.expired
+                .map_or(probe.interval, |expired| expired.min(probe.interval)),
+        );
+
+        true
+    }
+
+    /// Abandons the pending probe, as a message was sent during its silence (which has refreshed the binding).
+    /// The interval is probed again after the next heartbeat.
+    pub fn cancel(&mut self) {
+        self.probe = None;
+    }
+
+    /// Returns the longest interval which may be probed.
+    fn max_interval(&self) -> Duration {
+        self.config
+            .max_interval
+            .min(Duration::from_millis(MAX_KEEPALIVE_PROBE_DELAY_MS as u64))
+    }
+}
@@ -1,25 +1,121 @@
 //!  This feature provides functions and abstractions for sending both Voice and Video packets.
 
+#[cfg(all(feature = "server", unix))]
+pub mod activation;
+#[cfg(feature = "server")]
+pub mod amplification;
+#[cfg(feature = "client")]
+pub mod audio_codec;
+#[cfg(feature = "audit")]
+pub mod audit;
+#[cfg(feature = "server")]
+pub mod bandwidth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(all(feature = "client", feature = "server"))]
+pub mod bridge;
+#[cfg(feature = "client")]
+pub mod call;
 #[cfg(feature = "client")]
 pub mod client;
+#[cfg(feature = "cluster")]
+pub mod cluster;
+#[cfg(feature = "client")]
+pub mod congestion;
+#[cfg(feature = "client")]
+pub mod decoder;
+#[cfg(feature = "client")]
+pub mod diagnostics;
+#[cfg(feature = "server")]
+pub mod drops;
+#[cfg(feature = "client")]
+pub mod duplicate;
+#[cfg(feature = "server")]
+pub mod egress;
+#[cfg(feature = "client")]
+pub mod event;
+#[cfg(feature = "server")]
+pub mod filter;
+#[cfg(feature = "server")]
+pub mod floor;
+#[cfg(feature = "client")]
+pub mod freeze;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "server")]
+pub mod handoff;
+#[cfg(feature = "server")]
+pub mod hold;
+#[cfg(feature = "server")]
+pub mod hook;
+#[cfg(feature = "client")]
+pub mod keepalive;
+#[cfg(feature = "client")]
+pub mod pacing;
+#[cfg(feature = "client")]
+pub mod playout;
+#[cfg(feature = "server")]
+pub mod policy;
+#[cfg(feature = "client")]
+pub mod power;
+#[cfg(feature = "client")]
+pub mod preconnect;
+#[cfg(feature = "client")]
+pub mod probe;
+#[cfg(feature = "client")]
+pub mod profiling;
+#[cfg(feature = "client")]
+pub mod relay;
+#[cfg(feature = "server")]
+pub mod replay;
+#[cfg(feature = "client")]
+pub mod resolve;
+pub mod runtime;
 #[cfg(feature = "server")]
 pub mod server;
+#[cfg(feature = "server")]
+pub mod shedding;
+#[cfg(feature = "server")]
+pub mod simulcast;
+#[cfg(feature = "rodio")]
+pub mod sink;
+#[cfg(feature = "client")]
+pub mod speaker;
+#[cfg(feature = "persistence")]
+pub mod store;
+#[cfg(feature = "client")]
+pub mod tap;
+#[cfg(feature = "transcode")]
+pub mod transcode;
+pub mod transport;
+#[cfg(feature = "client")]
+pub mod usage;
+#[cfg(feature = "client")]
+pub mod video;
+#[cfg(feature = "client")]
+pub mod voice;
 
 /// Custom networking (udp) errors.
+/// Every component returns its own typed error, this error combines them for the applications running several components (for example a client and a server in the same process), so that their errors can be propagated with `?`.
+#[cfg(any(feature = "client", feature = "server"))]
 #[derive(thiserror::Error, Debug)]
 pub enum UdpError {
-    /// This error is thrown when a message has failed to send.
-    #[error("Failed to send message.")]
-    SendError(std::io::Error),
+    /// This error is thrown when a [`Client`](client::Client), or a component acting as a client (for example a bridge or a blocking client), has failed.
+    #[cfg(feature = "client")]
+    #[error("Client error: {0}")]
+    ClientError(#[from] event::ClientError),
 
-    /// This error is thrown when the [`UdpSocket`] has failed to bind to the local address.
-    #[error("Failed to bind to local address.")]
-    BindError(std::io::Error),
+    /// This error is thrown when a [`Server`](server::Server) has failed.
+    #[cfg(feature = "server")]
+    #[error("Server error: {0}")]
+    ServerError(#[from] server::ServerError),
 
-    /// This error is thrown when no remote address could be resolved.
-    #[error("Failed to resolve remote address.")]
-    ConnectionError(std::io::Error),
+    /// This error is thrown when a [`ReplayServer`](replay::ReplayServer) has failed, or its recording could not be read.
+    #[cfg(feature = "server")]
+    #[error("Replay error: {0}")]
+    ReplayError(#[from] replay::ReplayError),
 }
 
 /// Defines the Result enum with the [`UdpError`] error type.
+#[cfg(any(feature = "client", feature = "server"))]
 pub type Result<T> = ::std::result::Result<T, UdpError>;
@@ -0,0 +1,96 @@
+//!
+//! Provides the [`PacedQueue`], which holds the fragments of the video frames the [`Client`](super::client::Client) sends paced.
+//!
+//! The frames wait in the queue while the fragments of the frames before them are sent, so a congested link (or a pacing interval too long for the bitrate) makes them late.
+//! With a latency budget (see [`ClientConfig::video_latency_budget`](super::client::ClientConfig::video_latency_budget)), the frames which have waited longer than the budget are dropped before their first fragment is sent, instead of delaying every frame behind them.
+//! The frame being sent is always finished, as the receivers would discard it without its remaining fragments.
+//!
+
+use std::{collections::VecDeque, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::packet::VoipPacket;
+
+///
+/// Paced queue type definition.
+///
+/// Queues the fragments of the video frames, and drops the frames which have exceeded the latency budget.
+///
+#[derive(Debug, Clone)]
+pub struct PacedQueue {
+    /// The longest a frame may wait before its first fragment is sent, the frames are never dropped if this is [`None`].
+    latency_budget: Option<Duration>,
+
+    /// The fragments of the frame being sent.
+    current_frame: VecDeque<VoipPacket>,
+
+    /// The frames waiting to be sent, with the time they were queued at.
+    queued_frames: VecDeque<(Instant, Vec<VoipPacket>)>,
+}
+
+impl PacedQueue {
+    /// Creates a new [`PacedQueue`] instance, dropping the frames which have waited longer than the `latency_budget`.
+    pub fn new(latency_budget: Option<Duration>) -> Self {
+        Self {
+            latency_budget,
+            current_frame: VecDeque::new(),
+            queued_frames: VecDeque::new(),
+        }
+    }
+
+    /// Returns whether no fragment is waiting to be sent.
+    pub fn is_empty(&self) -> bool {
+        self.current_frame.is_empty() && self.queued_frames.is_empty()
+    }
+
+    /// Queues the `fragments` of a frame, queued at `now`.
+    pub fn push(&mut self, fragments: Vec<VoipPacket>, now: Instant) {
+        if !fragments.is_empty() {
+            self.queued_frames.push_back((now, fragments));
+        }
+    }
+
+    ///
+    /// Returns the next fragment to send at `now`, and the amount of frames dropped before it.
+    ///
+    /// # Behavior
+    /// Once the frame being sent is finished, the frames which have waited longer than the latency budget are dropped, and the next frame is started.
+    /// The frames following a dropped frame usually can't be decoded until the next keyframe, so the caller should refresh the picture when frames are dropped.
+    ///
+    pub fn pop(&mut self, now: Instant) -> (Option<VoipPacket>, u32) {
+        let mut dropped_frames = 0;
+
+        if self.current_frame.is_empty() {
+            while let Some((queued_at, fragments)) = self.queued_frames.pop_front() {
+                let is_late = self.latency_budget.is_some_and(|latency_budget| {
+                    now.saturating_duration_since(queued_at) > latency_budget
+                });
+
+                if is_late {
+                    dropped_frames += 1;
+
+                    continue;
+                }
+
+                self.current_frame.extend(fragments);
+
+                break;
+            }
+        }
+
+        (self.current_frame.pop_front(), dropped_frames)
+    }
+
+    /// Removes every fragment from the queue without dropping any frame, in the order they would have been sent.
+    pub fn drain(&mut self) -> Vec<VoipPacket> {
+        self.current_frame
+            .drain(..)
+            .chain(
+                self.queued_frames
+                    .drain(..)
+                    .flat_map(|(_, fragments)| fragments),
+            )
+            .collect()
+    }
+}
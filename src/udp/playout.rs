@@ -0,0 +1,470 @@
+//!
+//! Provides the [`Playout`], which plays out the voice decoded by the [`Client`](super::client::Client) through a [`JitterBuffer`] per author, and a [`Mixer`].
+//! The authors publishing more than one audio stream (for example a microphone and the audio of a shared screen), or connected from more than one device, get a [`JitterBuffer`] per stream of every device, and their streams are summed before they are mixed.
+//!
+//! The playout is shared between the [`Client`](super::client::Client) and the audio outputs (for example an audio engine callback, or the [rodio sources](super::sink)), so the samples can be pulled from any thread.
+//! The gaps of the streams are filled with [`ComfortNoise`] instead of silence, if it is enabled in the [`ClientConfig`](super::client::ClientConfig).
+//! The streams ended by their authors (see [`Client::end_stream`](super::client::Client::end_stream)) are played out without waiting for the target delay, and released once they have run dry.
+//! The voice sent by the user can be mixed in as a sidetone (see [`Playout::set_sidetone`]), so the user hears what they sound like on the wire.
+//! Local sounds (for example notifications) can be mixed in with their own gain through the [`SoundBoard`] of the playout (see [`Playout::sound_board_mut`]).
+//! The mixed samples can be processed with a custom [`AudioPipeline`] before they reach the audio output (see [`Playout::set_playback_pipeline`]).
+//! The underruns and the overruns of the jitter buffers are reported as [`ClientEvent`]s, and their [`JitterStats`] are kept per author (see [`Playout::jitter_stats`]).
+//!
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{
+    sync::mpsc::{Receiver, Sender},
+    time::Instant,
+};
+use uuid::Uuid;
+
+use super::{
+    event::ClientEvent,
+    profiling::{PipelineProfiler, PipelineStage},
+    voice::{DecodedVoiceFrame, VoiceConfig},
+};
+use crate::audio::{
+    comfort_noise::{ComfortNoise, ComfortNoiseConfig},
+    jitter::{JitterBuffer, JitterConfig, JitterStats},
+    mixer::Mixer,
+    pipeline::{AudioPipeline, AudioStage},
+    soundboard::SoundBoard,
+};
+
+///
+/// Playout type definition.
+///
+/// Buffers the decoded voice frames of every author, and hands out the playout-ready samples on demand.
+///
+#[derive(Debug)]
+pub struct Playout {
+    /// The receiver of the voice frames decoded by the client service.
+    decoded_frame_receiver: Receiver<DecodedVoiceFrame>,
+
+    /// The configuration of the jitter buffers.
+    jitter_config: JitterConfig,
+
+    /// The sample rate of the decoded samples.
+    sample_rate: u32,
+
+    /// The amount of interleaved channels of the decoded samples.
+    channels: usize,
+
+    /// The jitter buffer of every audio stream of every author, by the author, its device and the stream.
+    jitter_buffers: HashMap<(Uuid, u32, u8), JitterBuffer>,
+
+    /// The statistics of the jitter buffers which were already forgotten, by their authors.
+    forgotten_stats: HashMap<Uuid, JitterStats>,
+
+    /// The sender of the events of the client, the underruns and the overruns are reported through it.
+    event_sender: Sender<ClientEvent>,
+
+    /// The configuration of the comfort noise, the gaps are filled with silence if this is [`None`].
+    comfort_noise_config: Option<ComfortNoiseConfig>,
+
+    /// The comfort noise generator of every author, these outlive the jitter buffers until [`ComfortNoiseConfig::max_duration`].
+    comfort_noise: HashMap<Uuid, ComfortNoise>,
+
+    /// The mixer of the played out voice of every author.
+    mixer: Mixer,
+
+    /// The authors whose voice is pulled separately, so they are left out of the mix.
+    detached_authors: HashSet<Uuid>,
+
+    /// The linear gain of the sidetone, the sidetone is disabled if this is [`None`].
+    sidetone_gain: Option<f32>,
+
+    /// The decoded samples of the sent voice, waiting to be mixed in as the sidetone.
+    sidetone: VecDeque<f32>,
+
+    /// The sound board of the local sounds, which are mixed in after the sidetone.
+    sound_board: SoundBoard,
+
+    /// The pipeline the mixed samples are processed with.
+    playback_pipeline: AudioPipeline,
+
+    /// The profiler of the audio pipeline of the client, the pulls are timed and watched for xruns.
+    profiler: Arc<PipelineProfiler>,
+}
+
+impl Playout {
+    /// Creates a new [`Playout`] instance, playing out the frames of the `decoded_frame_receiver`.
+    pub(crate) fn new(
+        decoded_frame_receiver: Receiver<DecodedVoiceFrame>,
+        event_sender: Sender<ClientEvent>,
+        voice_config: &VoiceConfig,
+        jitter_config: JitterConfig,
+        comfort_noise_config: Option<ComfortNoiseConfig>,
+        profiler: Arc<PipelineProfiler>,
+    ) -> Self {
+        let channels = voice_config.channels as usize;
+
+        Self {
+            decoded_frame_receiver,
+            jitter_config,
+            sample_rate: voice_config.sample_rate,
+            channels,
+            jitter_buffers: HashMap::new(),
+            forgotten_stats: HashMap::new(),
+            event_sender,
+            comfort_noise_config,
+            comfort_noise: HashMap::new(),
+            mixer: Mixer::new(voice_config.sample_rate, channels),
+            detached_authors: HashSet::new(),
+            sidetone_gain: None,
+            sidetone: VecDeque::new(),
+            sound_board: SoundBoard::new(voice_config.sample_rate, channels),
+            playback_pipeline: AudioPipeline::new(),
+            profiler,
+        }
+    }
+
+    /// Returns the sample rate of the played out samples.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the amount of interleaved channels of the played out samples.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Returns the [`JitterConfig`] of the jitter buffers, including the current target delay.
+    pub fn jitter_config(&self) -> &JitterConfig {
+        &self.jitter_config
+    }
+
+    /// Sets the target delay of the jitter buffer of every author (including the authors joining later), returning it after it was clamped between the minimum and the maximum delay of the [`JitterConfig`].
+    /// This can be used to trade robustness for latency at runtime, see [`JitterBuffer::set_target_delay`].
+    pub fn set_target_delay(&mut self, target_delay: Duration) -> Duration {
+        let target_delay = self.jitter_config.clamp_delay(target_delay);
+
+        self.jitter_config.target_delay = target_delay;
+
+        for jitter_buffer in self.jitter_buffers.values_mut() {
+            jitter_buffer.set_target_delay(target_delay);
+        }
+
+        target_delay
+    }
+
+    /// Returns the delay the playout of the `author` is currently applying (see [`JitterBuffer::playout_delay`]), [`None`] if nothing of the author is buffered.
+    /// The longest delay is returned if the author publishes more than one audio stream.
+    pub fn playout_delay(&self, author: Uuid) -> Option<Duration> {
+        self.jitter_buffers
+            .iter()
+            .filter(|((stream_author, ..), _)| *stream_author == author)
+            .map(|(_, jitter_buffer)| jitter_buffer.playout_delay())
+            .max()
+    }
+
+    /// Returns the [`JitterStats`] of the `author`, [`None`] if nothing of the author was played out yet.
+    /// The counters are summed over every audio stream of the author since it has started sending, and the longest buffered duration is returned.
+    pub fn jitter_stats(&self, author: Uuid) -> Option<JitterStats> {
+        let mut jitter_stats = self.forgotten_stats.get(&author).copied();
+
+        for ((stream_author, ..), jitter_buffer) in &self.jitter_buffers {
+            if *stream_author == author {
+                jitter_stats
+                    .get_or_insert_with(JitterStats::default)
+                    .accumulate(&jitter_buffer.stats());
+            }
+        }
+
+        jitter_stats
+    }
+
+    /// Sets the linear gain of the sidetone, the voice sent by this client after it was encoded and decoded again, which is mixed into [`Playout::pull_mixed`].
+    /// The sidetone is disabled if the gain is [`None`], and the samples waiting to be mixed in are dropped.
+    pub fn set_sidetone(&mut self, sidetone_gain: Option<f32>) {
+        self.sidetone_gain = sidetone_gain;
+
+        if sidetone_gain.is_none() {
+            self.sidetone.clear();
+        }
+    }
+
+    /// Returns the linear gain of the sidetone, if it is enabled.
+    pub fn sidetone(&self) -> Option<f32> {
+        self.sidetone_gain
+    }
+
+    /// Queues the decoded samples of the sent voice for the sidetone, if it is enabled.
+    /// At most [`JitterConfig::max_delay`] is queued, the oldest samples are dropped beyond it so the sidetone doesn't lag behind.
+    pub(crate) fn push_sidetone(&mut self, samples: &[f32]) {
+        if self.sidetone_gain.is_none() {
+            return;
+        }
+
+        self.sidetone.extend(samples);
+
+        let max_samples = (self.jitter_config.max_delay.as_secs_f64()
+            * self.sample_rate as f64
+            * self.channels as f64) as usize;
+
+        if let Some(excess) = self.sidetone.len().checked_sub(max_samples) {
+            self.sidetone.drain(..excess);
+        }
+    }
+
+    /// Returns the [`SoundBoard`] of the local sounds mixed into [`Playout::pull_mixed`], which can be used to play a clip or to set the gain of the sounds.
+    pub fn sound_board_mut(&mut self) -> &mut SoundBoard {
+        &mut self.sound_board
+    }
+
+    /// Sets the [`AudioPipeline`] the mixed samples of [`Playout::pull_mixed`] are processed with, after the sidetone and the local sounds are mixed in, returning the previous pipeline.
+    /// The samples pulled with [`Playout::pull_author`] aren't processed, an empty pipeline disables the processing.
+    pub fn set_playback_pipeline(&mut self, playback_pipeline: AudioPipeline) -> AudioPipeline {
+        std::mem::replace(&mut self.playback_pipeline, playback_pipeline)
+    }
+
+    /// Returns the [`Mixer`] the voice of every author is mixed with.
+    /// This can be used to set the gain, the priority or the position of the authors.
+    pub fn mixer_mut(&mut self) -> &mut Mixer {
+        &mut self.mixer
+    }
+
+    /// Returns the voice frames decoded since the last call, without playing them out.
+    pub(crate) fn take_frames(&mut self) -> Vec<DecodedVoiceFrame> {
+        std::iter::from_fn(|| self.decoded_frame_receiver.try_recv().ok()).collect()
+    }
+
+    ///
+    /// Fills the `frames` with the next playout-ready samples of every author, mixed together.
+    ///
+    /// # Behavior
+    /// The authors whose voice is pulled with [`Playout::pull_author`] are left out of the mix.
+    /// The sidetone is mixed in after the voice of the authors, if it is enabled, followed by the clips of the [`SoundBoard`], then the samples are processed with the playback [`AudioPipeline`].
+    /// The `frames` are filled with silence (or comfort noise if it is enabled) while nothing is ready to be played out.
+    /// The calls are timed, and watched for the underruns of the playback device (see [`PipelineProfile`](super::profiling::PipelineProfile)).
+    ///
+    pub fn pull_mixed(&mut self, frames: &mut [f32]) {
+        let started_at = Instant::now();
+
+        self.profiler.observe_playout(
+            started_at,
+            Duration::from_secs_f64(
+                frames.len() as f64 / (self.sample_rate as usize * self.channels) as f64,
+            ),
+        );
+
+        self.receive_frames();
+
+        let mut samples = vec![0.; frames.len()];
+
+        let authors: HashSet<Uuid> = self
+            .jitter_buffers
+            .keys()
+            .map(|(author, ..)| author)
+            .chain(self.comfort_noise.keys())
+            .filter(|author| !self.detached_authors.contains(author))
+            .copied()
+            .collect();
+
+        for author in authors {
+            let sample_count = self.pull_stream(author, &mut samples);
+
+            self.mixer.push_samples(author, &samples[..sample_count]);
+        }
+
+        self.forget_silent_authors();
+
+        self.mixer.mix(frames);
+
+        if let Some(sidetone_gain) = self.sidetone_gain {
+            let sample_count = frames.len().min(self.sidetone.len());
+
+            for (frame, sample) in frames.iter_mut().zip(self.sidetone.drain(..sample_count)) {
+                *frame = (*frame + sample * sidetone_gain).clamp(-1., 1.);
+            }
+        }
+
+        self.sound_board.mix_into(frames);
+
+        self.playback_pipeline.process(frames);
+
+        self.profiler
+            .record_since(PipelineStage::Playout, started_at);
+    }
+
+    ///
+    /// Fills the `frames` with the next playout-ready samples of the `author`.
+    ///
+    /// # Behavior
+    /// Returns the amount of samples played out (including the comfort noise), the rest of the `frames` is filled with silence.
+    /// The voice of the author is left out of [`Playout::pull_mixed`] until [`Playout::attach_author`] is called, so the samples aren't played out twice.
+    /// The samples aren't processed by the [`Mixer`].
+    ///
+    pub fn pull_author(&mut self, author: Uuid, frames: &mut [f32]) -> usize {
+        let started_at = Instant::now();
+
+        self.detach_author(author);
+
+        self.receive_frames();
+
+        let sample_count = self.pull_stream(author, frames);
+
+        self.forget_silent_authors();
+
+        self.profiler
+            .record_since(PipelineStage::Playout, started_at);
+
+        sample_count
+    }
+
+    /// Fills the `output` with the next samples of the `author`, and fills the gap after them with comfort noise.
+    /// The audio streams of the author are summed, and the longest of them decides the amount of samples played out.
+    /// Returns the amount of samples played out, including the comfort noise.
+    fn pull_stream(&mut self, author: Uuid, output: &mut [f32]) -> usize {
+        output.fill(0.);
+
+        let mut sample_count = 0;
+        let mut stream_samples = vec![0.; output.len()];
+
+        for ((_, _, stream), jitter_buffer) in self
+            .jitter_buffers
+            .iter_mut()
+            .filter(|((stream_author, ..), _)| *stream_author == author)
+        {
+            let underruns = jitter_buffer.stats().underruns;
+            let stream_sample_count = jitter_buffer.pull(&mut stream_samples);
+
+            //The playout runs on the thread of the audio output, so the event is dropped instead of waiting for the consumer
+            if jitter_buffer.stats().underruns > underruns {
+                let _ = self.event_sender.try_send(ClientEvent::PlayoutUnderrun {
+                    author,
+                    stream: *stream,
+                });
+            }
+
+            for (sample, stream_sample) in output
+                .iter_mut()
+                .zip(&stream_samples[..stream_sample_count])
+            {
+                *sample += stream_sample;
+            }
+
+            sample_count = sample_count.max(stream_sample_count);
+        }
+
+        let Some(comfort_noise) = self.comfort_noise.get_mut(&author) else {
+            return sample_count;
+        };
+
+        comfort_noise.observe(&output[..sample_count]);
+
+        if sample_count == output.len() {
+            return sample_count;
+        }
+
+        sample_count + comfort_noise.generate(&mut output[sample_count..])
+    }
+
+    /// Leaves the voice of the `author` out of the mix of [`Playout::pull_mixed`], as it is pulled separately.
+    pub fn detach_author(&mut self, author: Uuid) {
+        self.detached_authors.insert(author);
+    }
+
+    /// Puts the voice of the `author` back into the mix of [`Playout::pull_mixed`].
+    pub fn attach_author(&mut self, author: Uuid) {
+        self.detached_authors.remove(&author);
+    }
+
+    /// Moves the decoded frames into the jitter buffer of their streams.
+    /// The jitter buffers of the ended streams play out the rest of their audio right away, and the comfort noise of the author stops once every stream of the author has ended.
+    fn receive_frames(&mut self) {
+        while let Ok(decoded_frame) = self.decoded_frame_receiver.try_recv() {
+            if decoded_frame.end_of_stream {
+                self.end_stream(
+                    decoded_frame.author,
+                    decoded_frame.device,
+                    decoded_frame.stream,
+                );
+
+                continue;
+            }
+
+            self.mixer
+                .set_position(decoded_frame.author, decoded_frame.position);
+
+            let jitter_buffer = self
+                .jitter_buffers
+                .entry((
+                    decoded_frame.author,
+                    decoded_frame.device,
+                    decoded_frame.stream,
+                ))
+                .or_insert_with(|| {
+                    JitterBuffer::new(self.jitter_config.clone(), self.sample_rate, self.channels)
+                });
+            let overruns = jitter_buffer.stats().overruns;
+
+            if decoded_frame.concealed {
+                jitter_buffer.push_concealed(decoded_frame.sequence, decoded_frame.samples);
+            } else {
+                jitter_buffer.push(decoded_frame.sequence, decoded_frame.samples);
+            }
+
+            if jitter_buffer.stats().overruns > overruns {
+                let _ = self.event_sender.try_send(ClientEvent::PlayoutOverrun {
+                    author: decoded_frame.author,
+                    stream: decoded_frame.stream,
+                });
+            }
+
+            if let Some(comfort_noise_config) = &self.comfort_noise_config {
+                self.comfort_noise
+                    .entry(decoded_frame.author)
+                    .or_insert_with(|| {
+                        ComfortNoise::new(
+                            comfort_noise_config.clone(),
+                            self.sample_rate,
+                            self.channels,
+                        )
+                    });
+            }
+        }
+    }
+
+    /// Ends the jitter buffer of the stream, and forgets the comfort noise of the `author` if none of the author's streams are left.
+    fn end_stream(&mut self, author: Uuid, device: u32, stream: u8) {
+        if let Some(jitter_buffer) = self.jitter_buffers.get_mut(&(author, device, stream)) {
+            jitter_buffer.end();
+        }
+
+        let is_streaming =
+            self.jitter_buffers
+                .iter()
+                .any(|((stream_author, ..), jitter_buffer)| {
+                    *stream_author == author && !jitter_buffer.is_ended()
+                });
+
+        if !is_streaming {
+            self.comfort_noise.remove(&author);
+        }
+    }
+
+    /// Forgets the jitter buffers of the authors who have stopped speaking (keeping their statistics), and the comfort noise of the authors who have been missing for too long.
+    fn forget_silent_authors(&mut self) {
+        self.jitter_buffers.retain(|(author, ..), jitter_buffer| {
+            if !jitter_buffer.is_empty() {
+                return true;
+            }
+
+            self.forgotten_stats
+                .entry(*author)
+                .or_default()
+                .accumulate(&jitter_buffer.stats());
+
+            false
+        });
+
+        self.comfort_noise
+            .retain(|_, comfort_noise| comfort_noise.is_active());
+    }
+}
@@ -0,0 +1,118 @@
+//!
+//! Provides the enforcement of the [`RoomPolicy`]s by the [`Server`](super::server::Server), which rejects the media messages violating the policy of their room.
+//!
+//! The codec and the video rules are checked on every message, the bitrates are measured from the bodies of the messages over fixed windows.
+//! The bitrate caps are applied with some headroom, as the variable bitrate encoders briefly overshoot their targets.
+//! The sender of a rejected message is notified with a [`ControlMessage::PolicyViolation`](crate::packet::control::ControlMessage::PolicyViolation), once per window.
+//!
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::packet::{
+    control::{MediaState, PolicyViolation, RoomPolicy},
+    VoipHeader, VoipMessageType,
+};
+
+/// The window the bitrates of the senders are measured over.
+pub const POLICY_WINDOW: Duration = Duration::from_secs(1);
+
+/// The ratio the bitrate caps of the [`RoomPolicy`] are exceeded by before the media is rejected.
+pub const BITRATE_HEADROOM: f64 = 1.25;
+
+/// The media bytes a peer has sent in the current window, measured against the bitrate caps of the [`RoomPolicy`] of its room.
+#[derive(Debug, Clone)]
+pub(crate) struct PolicyMeter {
+    /// The start of the current window.
+    window_start: Instant,
+
+    /// The bytes of the voice bodies sent in the current window.
+    voice_bytes: u64,
+
+    /// The bytes of the video bodies sent in the current window.
+    video_bytes: u64,
+
+    /// Whether the sender was notified about a violation in the current window.
+    is_notified: bool,
+}
+
+impl PolicyMeter {
+    /// Creates a new [`PolicyMeter`] instance, with its window starting at `now`.
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            voice_bytes: 0,
+            video_bytes: 0,
+            is_notified: false,
+        }
+    }
+
+    ///
+    /// Checks the media message of the [`VoipHeader`] against the [`RoomPolicy`], counting it in the current window if it is admitted.
+    ///
+    /// # Behavior
+    /// Only the voice and video messages are checked, every other message is admitted.
+    /// The video messages are checked against the [`MediaState`] of the sender in the room, so that the shared screens can be told apart.
+    ///
+    /// # Error
+    /// Returns the [`PolicyViolation`] of the message if it violates the policy.
+    ///
+    pub(crate) fn check(
+        &mut self,
+        now: Instant,
+        room_policy: &RoomPolicy,
+        media_state: MediaState,
+        voip_header: &VoipHeader,
+    ) -> Result<(), PolicyViolation> {
+        let (is_voice, length) = match voip_header.voip_message_type() {
+            VoipMessageType::VoiceMessage(length) => (true, *length),
+            VoipMessageType::VideoMessage(length) => (false, *length),
+            _ => return Ok(()),
+        };
+
+        if !is_voice && room_policy.video_disabled {
+            return Err(PolicyViolation::Video);
+        }
+
+        if !is_voice && room_policy.screen_sharing_disabled && media_state.screen_sharing {
+            return Err(PolicyViolation::ScreenSharing);
+        }
+
+        if !room_policy.allows_codec(voip_header.codec()) {
+            return Err(PolicyViolation::Codec(voip_header.codec()));
+        }
+
+        if now.duration_since(self.window_start) >= POLICY_WINDOW {
+            *self = Self::new(now);
+        }
+
+        let (used_bytes, max_bitrate) = if is_voice {
+            (&mut self.voice_bytes, room_policy.max_bitrate)
+        } else {
+            (&mut self.video_bytes, room_policy.max_video_bitrate)
+        };
+
+        if let Some(max_bitrate) = max_bitrate {
+            let budget =
+                (max_bitrate as f64 * BITRATE_HEADROOM * POLICY_WINDOW.as_secs_f64() / 8.) as u64;
+
+            if *used_bytes + length > budget {
+                return Err(if is_voice {
+                    PolicyViolation::AudioBitrate(max_bitrate)
+                } else {
+                    PolicyViolation::VideoBitrate(max_bitrate)
+                });
+            }
+        }
+
+        *used_bytes += length;
+
+        Ok(())
+    }
+
+    /// Marks the sender notified in the current window, returning whether it hadn't been yet.
+    pub(crate) fn notify(&mut self) -> bool {
+        !std::mem::replace(&mut self.is_notified, true)
+    }
+}
@@ -0,0 +1,73 @@
+//!
+//! Provides the power modes of the [`Client`](super::client::Client), which trade the responsiveness of the session for the battery of mobile devices.
+//!
+//! Every datagram sent or received wakes the radio of a mobile device up, which then stays powered for a few seconds before it sleeps again.
+//! In [`PowerMode::LowPower`] the client service wakes the radio up as rarely as it can: the heartbeats are sent at the longer [`LowPowerConfig::heartbeat_interval`],
+//! the non-urgent control messages are held back until something else has to be sent, the cover traffic is paused, and no video is sent or received.
+//! The mode can be switched at runtime with [`Client::set_power_mode`](super::client::Client::set_power_mode), for example when the application is moved to the background.
+//!
+
+use std::time::Duration;
+
+use crate::packet::{control::ControlMessage, decode_header, VoipMessageType};
+
+/// The power modes of the [`Client`](super::client::Client).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PowerMode {
+    /// Every message is sent as soon as it is requested.
+    #[default]
+    Normal,
+
+    /// The client wakes the radio up as rarely as it can, see the [`power`](self) module.
+    LowPower,
+}
+
+///
+/// Low power configuration type definition.
+///
+/// Describes how the client service behaves in [`PowerMode::LowPower`].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LowPowerConfig {
+    /// The interval of the heartbeats in low power mode, which should still be shorter than the binding timeout of the usual NATs.
+    /// The interval learned by the [`KeepaliveLearner`](super::keepalive::KeepaliveLearner) is used instead, if [`ClientConfig::keepalive`](super::client::ClientConfig::keepalive) is set.
+    pub heartbeat_interval: Duration,
+
+    /// The most control messages which are held back, the held back messages are sent right away once there are more.
+    pub max_deferred_messages: usize,
+}
+
+impl Default for LowPowerConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: Duration::from_secs(30),
+            max_deferred_messages: 32,
+        }
+    }
+}
+
+/// Returns whether the encoded `message` is a control message which can wait for the next wakeup of the radio in low power mode.
+/// These are the messages which only refresh a state shown to the other participants ([`ControlMessage::QualityReport`], [`ControlMessage::Presence`], [`ControlMessage::MediaState`] and [`ControlMessage::SelectLayer`]).
+pub(crate) fn is_deferrable(message: &[u8]) -> bool {
+    decode_header(message).is_ok_and(|voip_header| {
+        matches!(
+            voip_header.voip_message_type(),
+            VoipMessageType::Control(
+                ControlMessage::QualityReport(_)
+                    | ControlMessage::Presence(_)
+                    | ControlMessage::MediaState(_)
+                    | ControlMessage::SelectLayer(..)
+            )
+        )
+    })
+}
+
+/// Returns whether the encoded `message` is a video message, which isn't sent in low power mode.
+pub(crate) fn is_video(message: &[u8]) -> bool {
+    decode_header(message).is_ok_and(|voip_header| {
+        matches!(
+            voip_header.voip_message_type(),
+            VoipMessageType::VideoMessage(_)
+        )
+    })
+}
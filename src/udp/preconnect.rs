@@ -0,0 +1,86 @@
+//!
+//! Provides the warm-up of the [`Client`](super::client::Client), which prepares the session before the user joins a call, so that the first second of the call isn't spent negotiating while the speech is lost.
+//!
+//! [`Client::preconnect`](super::client::Client::preconnect) exchanges a few [`ControlMessage::Ping`](crate::packet::control::ControlMessage::Ping)s with the server (which wakes the radio up, opens the NAT binding and measures the round trip time), queries the public address of the host from a STUN server meanwhile,
+//! then probes the bandwidth towards the server with a short [`ProbeConfig`]. The encoder of the voice is created before any of these, so the first captured samples don't wait for it either.
+//!
+
+use std::{net::SocketAddr, time::Duration};
+
+use super::{
+    diagnostics::{DiagnosticError, EchoReport},
+    probe::{ProbeConfig, ProbeReport},
+};
+
+///
+/// Preconnect configuration type definition.
+///
+/// Describes the checks [`Client::preconnect`](super::client::Client::preconnect) runs, which should be short enough to finish before the user joins the call.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreconnectConfig {
+    /// The STUN server the public address of the host is queried from, as a `host:port` string (for example `stun.example.com:3478`).
+    /// The STUN check is skipped if this is [`None`].
+    pub stun_server: Option<String>,
+
+    /// The amount of the pings exchanged with the server during the handshake.
+    pub handshake_pings: u32,
+
+    /// The interval between the pings of the handshake.
+    pub handshake_interval: Duration,
+
+    /// The time the pings and the STUN request are waited for after they were sent, before they are considered lost.
+    pub timeout: Duration,
+
+    /// The bandwidth probe run after a successful handshake, which should be brief (a few bursts at the bitrates of the call).
+    /// The bandwidth isn't probed if this is [`None`].
+    pub probe: Option<ProbeConfig>,
+}
+
+impl Default for PreconnectConfig {
+    fn default() -> Self {
+        Self {
+            stun_server: None,
+            handshake_pings: 3,
+            handshake_interval: Duration::from_millis(50),
+            timeout: Duration::from_millis(500),
+            probe: Some(ProbeConfig {
+                max_bitrate: 1_000_000,
+                burst_duration: Duration::from_millis(50),
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+///
+/// Preconnect report type definition.
+///
+/// The outcome of every check of [`Client::preconnect`](super::client::Client::preconnect).
+///
+#[derive(Debug)]
+pub struct PreconnectReport {
+    /// The round trip times of the handshake with the server.
+    pub handshake: Result<EchoReport, DiagnosticError>,
+
+    /// The public address of the host, as seen by the STUN server.
+    /// This is [`None`] if no [`PreconnectConfig::stun_server`] is set, or no local socket could be bound (for example outside of a Tokio runtime).
+    pub stun: Option<Result<SocketAddr, DiagnosticError>>,
+
+    /// The bandwidth available towards the server.
+    /// This is [`None`] if no [`PreconnectConfig::probe`] is set, or the handshake has failed.
+    pub probe: Option<Result<ProbeReport, DiagnosticError>>,
+}
+
+impl PreconnectReport {
+    /// Returns whether the server has answered the handshake, so the call can be joined right away.
+    pub fn is_ready(&self) -> bool {
+        self.handshake.is_ok()
+    }
+
+    /// Returns the bitrate (in bits per second) the bandwidth probe has proven available, if it has proven any.
+    /// The media of the call can start at this bitrate, instead of ramping up from a conservative one.
+    pub fn available_bitrate(&self) -> Option<u32> {
+        self.probe.as_ref()?.as_ref().ok()?.available_bitrate
+    }
+}
@@ -0,0 +1,168 @@
+//!
+//! Provides the bandwidth probing of the [`Client`](super::client::Client), which measures the headroom of the path to the server before the quality of the video is ramped up.
+//!
+//! The probe sends short bursts of [`ControlMessage::Ping`](crate::packet::control::ControlMessage::Ping)s at increasing bitrates, each following a padding message marked with [`HeaderFlags::PADDING`](crate::packet::HeaderFlags::PADDING) so that no receiver forwards or decodes it.
+//! The server answers every ping with a small [`ControlMessage::Pong`](crate::packet::control::ControlMessage::Pong), so the spacing of the answers shows the rate the burst got through the bottleneck at (like a packet train).
+//! The probing stops at the first burst which loses too much, or gets through slower than it was sent.
+//!
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use super::diagnostics::PingTiming;
+
+///
+/// Probe configuration type definition.
+///
+/// Describes the bitrates the bursts of [`Client::probe_bandwidth`](super::client::Client::probe_bandwidth) are sent at, and when a burst is considered sustained.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeConfig {
+    /// The bitrate (in bits per second) of the first burst.
+    pub start_bitrate: u32,
+
+    /// The bitrate (in bits per second) the probing stops at, this should be the highest bitrate the application would send at.
+    pub max_bitrate: u32,
+
+    /// The factor the bitrate of the next burst is multiplied with, after a sustained burst.
+    pub growth_factor: f32,
+
+    /// The duration of a single burst.
+    pub burst_duration: Duration,
+
+    /// The amount of padding (in bytes) sent in front of every ping of the bursts, the bitrates are measured from the padding.
+    pub packet_size: usize,
+
+    /// The loss (between `0` and `1`) above which a burst isn't considered sustained.
+    pub max_loss: f32,
+
+    /// The share of the bitrate of a burst it has to get through at, to be considered sustained.
+    pub min_delivery_ratio: f32,
+
+    /// The time the answers are waited for after the last ping of a burst, before the unanswered pings are considered lost.
+    pub timeout: Duration,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            start_bitrate: 250_000,
+            max_bitrate: 4_000_000,
+            growth_factor: 2.,
+            burst_duration: Duration::from_millis(100),
+            packet_size: 1000,
+            max_loss: 0.05,
+            min_delivery_ratio: 0.9,
+            timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+impl ProbeConfig {
+    /// Returns the amount of the pings of a burst at the `bitrate`, and the interval they are sent at.
+    /// Every burst has at least two pings, so its delivery rate can be measured.
+    pub(crate) fn burst(&self, bitrate: u32) -> (u32, Duration) {
+        let packet_bits = (self.packet_size.max(1) * 8) as f64;
+        let bitrate = bitrate.max(1) as f64;
+
+        let count = (bitrate * self.burst_duration.as_secs_f64() / packet_bits).ceil() as u32;
+
+        (count.max(2), Duration::from_secs_f64(packet_bits / bitrate))
+    }
+
+    /// Returns the bitrate of the burst after a sustained burst at the `bitrate`, or [`None`] if the [`ProbeConfig::max_bitrate`] has been reached.
+    pub(crate) fn next_bitrate(&self, bitrate: u32) -> Option<u32> {
+        if bitrate >= self.max_bitrate {
+            return None;
+        }
+
+        let next_bitrate = (bitrate as f32 * self.growth_factor.max(1.)) as u32;
+
+        Some(next_bitrate.max(bitrate + 1).min(self.max_bitrate))
+    }
+}
+
+///
+/// Probe burst type definition.
+///
+/// The outcome of a single burst of the probing.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeBurst {
+    /// The bitrate (in bits per second) the burst was sent at.
+    pub bitrate: u32,
+
+    /// The amount of the pings sent.
+    pub sent: u32,
+
+    /// The amount of the pings answered.
+    pub received: u32,
+
+    /// The bitrate (in bits per second) the burst got through at, measured from the spacing of the answers.
+    /// This is [`None`] if less than two pings were answered, or they were answered at once.
+    pub delivered_bitrate: Option<u32>,
+}
+
+impl ProbeBurst {
+    /// Measures the burst sent at the `bitrate`, from the [`PingTiming`]s of its pings each following `packet_size` bytes of padding.
+    pub(crate) fn measure(bitrate: u32, ping_timings: &[PingTiming], packet_size: usize) -> Self {
+        let answered_at: Vec<Instant> = ping_timings
+            .iter()
+            .filter_map(|ping_timing| ping_timing.answered_at)
+            .collect();
+
+        //The first answer only marks the start of the train, the rest were delivered during the span
+        let delivered_bitrate = answered_at
+            .iter()
+            .min()
+            .zip(answered_at.iter().max())
+            .map(|(first_answer, last_answer)| last_answer.duration_since(*first_answer))
+            .filter(|span| !span.is_zero())
+            .map(|span| {
+                let delivered_bits = (answered_at.len() - 1) * packet_size * 8;
+
+                (delivered_bits as f64 / span.as_secs_f64()) as u32
+            });
+
+        Self {
+            bitrate,
+            sent: ping_timings.len() as u32,
+            received: answered_at.len() as u32,
+            delivered_bitrate,
+        }
+    }
+
+    /// Returns the loss (between `0` and `1`) of the burst.
+    pub fn loss(&self) -> f32 {
+        if self.sent == 0 {
+            return 0.;
+        }
+
+        1. - self.received as f32 / self.sent as f32
+    }
+
+    /// Returns whether the burst has lost at most the [`ProbeConfig::max_loss`], and got through at the [`ProbeConfig::min_delivery_ratio`] of its bitrate.
+    /// The bursts whose delivery rate couldn't be measured are judged by their loss alone.
+    pub fn is_sustained(&self, config: &ProbeConfig) -> bool {
+        self.loss() <= config.max_loss
+            && self.delivered_bitrate.is_none_or(|delivered_bitrate| {
+                delivered_bitrate as f32 >= self.bitrate as f32 * config.min_delivery_ratio
+            })
+    }
+}
+
+///
+/// Probe report type definition.
+///
+/// The outcome of [`Client::probe_bandwidth`](super::client::Client::probe_bandwidth).
+///
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProbeReport {
+    /// The bursts sent, in the order of their increasing bitrates.
+    pub bursts: Vec<ProbeBurst>,
+
+    /// The highest bitrate (in bits per second) a sustained burst got through at, which is the measured headroom of the path.
+    /// This is [`None`] if not even the first burst was sustained.
+    pub available_bitrate: Option<u32>,
+}
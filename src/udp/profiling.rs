@@ -0,0 +1,313 @@
+//!
+//! Provides the profiling of the audio pipeline of a [`Client`](super::client::Client), so that the crackling caused by the scheduling of the audio threads can be told apart from the crackling caused by the network.
+//!
+//! Every stage of the pipeline is timed into a [`TimingHistogram`]: the processing, the encoding and the sending of the captured voice, and the receiving, the decoding and the playout of the received voice (see [`PipelineStage`]).
+//! The callbacks of the audio devices are watched for xruns: a callback arriving later than the audio of the previous callback lasts means the buffer of the device has overflowed (a capture overrun) or has run dry (a playout underrun).
+//! The capture callbacks are the calls feeding the default voice stream (see [`Client::push_samples`](super::client::Client::push_samples)), the playout callbacks are the calls pulling the mixed voice (see [`Playout::pull_mixed`](super::playout::Playout::pull_mixed)).
+//! Unlike the underruns of the jitter buffers (see [`JitterStats`](crate::audio::jitter::JitterStats)), which are caused by the network, the xruns are caused by the audio threads not being scheduled in time, or by a stage taking too long.
+//!
+
+use std::{fmt::Display, time::Duration};
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+/// The upper bounds of the buckets of the [`TimingHistogram`]s, the timings longer than the last bound are counted in an overflow bucket.
+pub const TIMING_BUCKETS: [Duration; 10] = [
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2_500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+];
+
+/// The ratio of the audio duration of a callback the next callback may be late by, before it is counted as an xrun.
+/// The audio threads are never woken up exactly on time, so some lateness is expected.
+pub const XRUN_TOLERANCE: f64 = 1.5;
+
+/// The gap between two callbacks after which the device is considered stopped and restarted, instead of having an xrun.
+pub const DEVICE_RESTART_GAP: Duration = Duration::from_secs(1);
+
+/// A stage of the audio pipeline of the [`Client`](super::client::Client).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PipelineStage {
+    /// The processing of the captured samples before they are encoded, including the capture pipeline, the mixed in sounds and the loudness normalization.
+    Capture,
+
+    /// The encoding of a single voice frame, including its redundant copy.
+    Encode,
+
+    /// The handover of a voice message to the client service, which waits if the outbound queue is full.
+    Send,
+
+    /// The handling of a received voice message by the client service, from the arrival of its datagram until it is decoded.
+    Receive,
+
+    /// The decoding of a received voice message, including the concealment of the frames lost before it.
+    Decode,
+
+    /// The pulling of the samples played out, including the jitter buffers, the mixing and the playback pipeline.
+    Playout,
+}
+
+impl PipelineStage {
+    /// Every [`PipelineStage`], in the order of the pipeline.
+    pub const ALL: [Self; 6] = [
+        Self::Capture,
+        Self::Encode,
+        Self::Send,
+        Self::Receive,
+        Self::Decode,
+        Self::Playout,
+    ];
+
+    /// Returns the index of the histogram of the stage.
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl Display for PipelineStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Capture => "capture",
+            Self::Encode => "encode",
+            Self::Send => "send",
+            Self::Receive => "receive",
+            Self::Decode => "decode",
+            Self::Playout => "playout",
+        })
+    }
+}
+
+///
+/// Timing histogram type definition.
+///
+/// Counts the timings of a [`PipelineStage`] in the buckets of [`TIMING_BUCKETS`].
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimingHistogram {
+    /// The amount of timings in every bucket of [`TIMING_BUCKETS`], followed by the overflow bucket.
+    counts: [u64; TIMING_BUCKETS.len() + 1],
+
+    /// The sum of every timing.
+    total: Duration,
+
+    /// The longest timing.
+    max: Duration,
+}
+
+impl TimingHistogram {
+    /// Counts the `duration` in its bucket.
+    pub fn record(&mut self, duration: Duration) {
+        let bucket = TIMING_BUCKETS
+            .iter()
+            .position(|bound| duration <= *bound)
+            .unwrap_or(TIMING_BUCKETS.len());
+
+        self.counts[bucket] += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    /// Returns the amount of timings in every bucket of [`TIMING_BUCKETS`], followed by the overflow bucket.
+    pub fn bucket_counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns the amount of recorded timings.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the average timing, [`Duration::ZERO`] if nothing was recorded.
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            count => self.total / count.min(u32::MAX as u64) as u32,
+        }
+    }
+
+    /// Returns the longest timing.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Returns the upper bound of the bucket the `percentile` (between `0` and `1`) of the timings falls into, capped to the longest timing.
+    /// Returns [`Duration::ZERO`] if nothing was recorded.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let rank = (self.count() as f64 * percentile.clamp(0., 1.))
+            .ceil()
+            .max(1.) as u64;
+        let mut seen = 0;
+
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+
+            if seen >= rank {
+                return TIMING_BUCKETS
+                    .get(bucket)
+                    .map_or(self.max, |bound| (*bound).min(self.max));
+            }
+        }
+
+        Duration::ZERO
+    }
+
+    /// Adds the timings of `other` to the histogram.
+    pub fn accumulate(&mut self, other: &TimingHistogram) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts) {
+            *count += other_count;
+        }
+
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+}
+
+///
+/// Pipeline profile type definition.
+///
+/// A snapshot of the profile of the audio pipeline, as returned by [`Client::pipeline_profile`](super::client::Client::pipeline_profile).
+/// The profile can be printed as a report, with a line for every stage.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipelineProfile {
+    /// The timings of every [`PipelineStage`], in the order of [`PipelineStage::ALL`].
+    stages: [TimingHistogram; PipelineStage::ALL.len()],
+
+    /// The capture callbacks which have arrived too late, so the buffer of the capture device has most likely overflowed.
+    pub capture_overruns: u64,
+
+    /// The playout callbacks which have arrived too late, so the buffer of the playback device has most likely run dry.
+    pub playout_underruns: u64,
+
+    /// The longest time a callback has arrived after the audio of the previous callback has ended.
+    pub longest_stall: Duration,
+}
+
+impl PipelineProfile {
+    /// Returns the [`TimingHistogram`] of the `stage`.
+    pub fn stage(&self, stage: PipelineStage) -> &TimingHistogram {
+        &self.stages[stage.index()]
+    }
+
+    /// Returns the amount of xruns of the capture and the playback devices.
+    pub fn xruns(&self) -> u64 {
+        self.capture_overruns + self.playout_underruns
+    }
+}
+
+impl Display for PipelineProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for stage in PipelineStage::ALL {
+            let histogram = self.stage(stage);
+
+            writeln!(
+                f,
+                "{stage}: {} timings, mean {:?}, p50 {:?}, p99 {:?}, max {:?}",
+                histogram.count(),
+                histogram.mean(),
+                histogram.percentile(0.5),
+                histogram.percentile(0.99),
+                histogram.max(),
+            )?;
+        }
+
+        write!(
+            f,
+            "xruns: {} capture overruns, {} playout underruns, longest stall {:?}",
+            self.capture_overruns, self.playout_underruns, self.longest_stall,
+        )
+    }
+}
+
+///
+/// Pipeline profiler type definition.
+///
+/// Records the [`PipelineProfile`] of a [`Client`](super::client::Client), shared between the client, its client service and its [`Playout`](super::playout::Playout).
+///
+#[derive(Debug, Default)]
+pub(crate) struct PipelineProfiler {
+    /// The profile recorded so far.
+    profile: Mutex<PipelineProfile>,
+
+    /// The time of the last capture callback, and the duration of the audio it has delivered.
+    last_capture: Mutex<Option<(Instant, Duration)>>,
+
+    /// The time of the last playout callback, and the duration of the audio it has requested.
+    last_playout: Mutex<Option<(Instant, Duration)>>,
+}
+
+impl PipelineProfiler {
+    /// Creates a new [`PipelineProfiler`] instance, with an empty profile.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the [`PipelineProfile`] recorded so far.
+    pub(crate) fn snapshot(&self) -> PipelineProfile {
+        self.profile.lock().clone()
+    }
+
+    /// Clears the profile, the callbacks are watched from the next one.
+    pub(crate) fn reset(&self) {
+        *self.profile.lock() = PipelineProfile::default();
+        *self.last_capture.lock() = None;
+        *self.last_playout.lock() = None;
+    }
+
+    /// Records a timing of the `stage`, measured from `started_at` until now.
+    pub(crate) fn record_since(&self, stage: PipelineStage, started_at: Instant) {
+        self.record(stage, started_at.elapsed());
+    }
+
+    /// Records the `duration` as a timing of the `stage`.
+    pub(crate) fn record(&self, stage: PipelineStage, duration: Duration) {
+        self.profile.lock().stages[stage.index()].record(duration);
+    }
+
+    /// Watches a capture callback at `now`, delivering `duration` of audio, counting a capture overrun if it has arrived too late.
+    pub(crate) fn observe_capture(&self, now: Instant, duration: Duration) {
+        if let Some(stall) = observe_callback(&mut self.last_capture.lock(), now, duration) {
+            let mut profile = self.profile.lock();
+
+            profile.capture_overruns += 1;
+            profile.longest_stall = profile.longest_stall.max(stall);
+        }
+    }
+
+    /// Watches a playout callback at `now`, requesting `duration` of audio, counting a playout underrun if it has arrived too late.
+    pub(crate) fn observe_playout(&self, now: Instant, duration: Duration) {
+        if let Some(stall) = observe_callback(&mut self.last_playout.lock(), now, duration) {
+            let mut profile = self.profile.lock();
+
+            profile.playout_underruns += 1;
+            profile.longest_stall = profile.longest_stall.max(stall);
+        }
+    }
+}
+
+/// Records the callback at `now` handling `duration` of audio as the `last_callback`, returning the stall if it has arrived too late after the previous one.
+/// The callbacks are compared against the longer of the two audio durations, as a capture callback delivers the audio captured since the previous one, while a playout callback requests the audio played out until the next one.
+fn observe_callback(
+    last_callback: &mut Option<(Instant, Duration)>,
+    now: Instant,
+    duration: Duration,
+) -> Option<Duration> {
+    let (last_time, last_duration) = last_callback.replace((now, duration))?;
+    let gap = now.saturating_duration_since(last_time);
+
+    if gap >= DEVICE_RESTART_GAP {
+        return None;
+    }
+
+    let expected_gap = last_duration.max(duration);
+
+    (gap > expected_gap.mul_f64(XRUN_TOLERANCE)).then(|| gap - expected_gap)
+}
@@ -0,0 +1,373 @@
+//!
+//! Provides the [`RelayPicker`], which picks the relay (server) with the lowest latency from a list of candidates.
+//!
+//! The picker probes every relay with timed [`ControlMessage::Ping`]s, which the servers answer without registering the picker as a peer.
+//! It keeps measuring after the pick, so the application can migrate to another relay (by creating a new [`Client`](super::client::Client)) if the chosen one degrades.
+//!
+
+use std::{collections::VecDeque, net::SocketAddr, time::Duration};
+
+use tokio::{net::UdpSocket, select, sync::watch, time::Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use super::{
+    client::send_control_message,
+    event::ClientError,
+    runtime::{Runtime, Tokio},
+    transport::Transport,
+};
+use crate::{
+    packet::{control::ControlMessage, decode_message, VoipMessageType, LENGTH_PREFIX_SIZE},
+    MTU_MAX_PACKET_SIZE,
+};
+
+///
+/// Relay probe configuration type definition.
+///
+/// Describes how often the relays are probed, and when a relay is considered worse than another one.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelayProbeConfig {
+    /// The interval of the probes, a probe which isn't answered before the next one is sent is considered lost.
+    pub interval: Duration,
+
+    /// The amount of the latest probes the loss of a relay is calculated from.
+    pub window: usize,
+
+    /// The loss (between `0` and `1`) from which a relay is considered unusable.
+    pub max_loss: f32,
+
+    /// The amount of latency another relay has to save, before migrating to it is recommended by [`RelayPicker::better_relay`].
+    /// This prevents flapping between relays with similar latencies.
+    pub hysteresis: Duration,
+}
+
+impl Default for RelayProbeConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            window: 10,
+            max_loss: 0.5,
+            hysteresis: Duration::from_millis(20),
+        }
+    }
+}
+
+///
+/// Relay statistics type definition.
+///
+/// A snapshot of the measurements of a relay.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelayStats {
+    /// The address of the relay.
+    pub remote_addr: SocketAddr,
+
+    /// The smoothed round trip time to the relay, [`None`] if it hasn't answered any probes yet.
+    pub latency: Option<Duration>,
+
+    /// The round trip time of the latest answered probe.
+    pub last_round_trip_time: Option<Duration>,
+
+    /// The loss (between `0` and `1`) of the latest probes.
+    pub loss: f32,
+}
+
+impl RelayStats {
+    /// Returns whether the relay has answered, and isn't losing more probes than the `max_loss`.
+    pub fn is_usable(&self, max_loss: f32) -> bool {
+        self.latency.is_some() && self.loss < max_loss
+    }
+}
+
+/// The measurements of a relay, owned by the picker task.
+#[derive(Debug)]
+struct RelayState {
+    /// The address of the relay.
+    remote_addr: SocketAddr,
+
+    /// The sequence number and the send time of the unanswered probe of the current round.
+    pending: Option<(u32, Instant)>,
+
+    /// The smoothed round trip time.
+    latency: Option<Duration>,
+
+    /// The round trip time of the latest answered probe.
+    last_round_trip_time: Option<Duration>,
+
+    /// Whether each of the latest probes was answered.
+    outcomes: VecDeque<bool>,
+}
+
+impl RelayState {
+    /// Creates a new [`RelayState`] instance, without any measurements.
+    fn new(remote_addr: SocketAddr) -> Self {
+        Self {
+            remote_addr,
+            pending: None,
+            latency: None,
+            last_round_trip_time: None,
+            outcomes: VecDeque::new(),
+        }
+    }
+
+    /// Records the answer to the probe with the `sequence` number, answers to earlier probes are ignored.
+    fn answer(&mut self, sequence: u32, window: usize) {
+        let Some((pending_sequence, sent_at)) = self.pending else {
+            return;
+        };
+
+        if pending_sequence != sequence {
+            return;
+        }
+
+        let round_trip_time = sent_at.elapsed();
+
+        //Smooth the round trip time like TCP does (RFC 6298)
+        self.latency = Some(match self.latency {
+            Some(latency) => (latency * 7 + round_trip_time) / 8,
+            None => round_trip_time,
+        });
+        self.last_round_trip_time = Some(round_trip_time);
+        self.pending = None;
+
+        self.record(true, window);
+    }
+
+    /// Ends the current round, counting its probe as lost if it wasn't answered.
+    fn finish_round(&mut self, window: usize) {
+        if self.pending.take().is_some() {
+            self.record(false, window);
+        }
+    }
+
+    /// Records the outcome of a probe, keeping the latest `window` outcomes.
+    fn record(&mut self, answered: bool, window: usize) {
+        self.outcomes.push_back(answered);
+
+        while self.outcomes.len() > window {
+            self.outcomes.pop_front();
+        }
+    }
+
+    /// Returns the [`RelayStats`] of the relay.
+    fn stats(&self) -> RelayStats {
+        let lost = self.outcomes.iter().filter(|answered| !**answered).count();
+
+        RelayStats {
+            remote_addr: self.remote_addr,
+            latency: self.latency,
+            last_round_trip_time: self.last_round_trip_time,
+            loss: match self.outcomes.len() {
+                0 => 0.,
+                probes => lost as f32 / probes as f32,
+            },
+        }
+    }
+}
+
+///
+/// Relay picker type definition.
+///
+/// Probes a list of relays in the background, and picks the one with the lowest latency.
+/// The probing stops once the picker is dropped.
+///
+#[derive(Debug)]
+pub struct RelayPicker {
+    /// The configuration of the probes.
+    config: RelayProbeConfig,
+
+    /// This local channel receives the [`RelayStats`] of every relay, whenever they change.
+    stats_receiver: watch::Receiver<Vec<RelayStats>>,
+
+    /// The picker task's [`CancellationToken`].
+    cancellation_token: CancellationToken,
+}
+
+impl RelayPicker {
+    /// Creates a new [`RelayPicker`] instance probing the `relays`, bound to the local `[::]:0` address.
+    /// The picker task is spawned on the [`Tokio`] runtime.
+    pub async fn new(
+        relays: Vec<SocketAddr>,
+        config: RelayProbeConfig,
+    ) -> std::result::Result<Self, ClientError> {
+        let socket_handle = UdpSocket::bind("[::]:0")
+            .await
+            .map_err(ClientError::SocketIo)?;
+
+        Ok(Self::new_from_transport_with_runtime::<Tokio, _>(
+            socket_handle,
+            relays,
+            config,
+        ))
+    }
+
+    ///
+    /// Creates a new [`RelayPicker`] instance probing the `relays` from any already bound [`Transport`].
+    /// The picker task is spawned on the [`Runtime`] `R`.
+    ///
+    /// # Behavior
+    /// Every relay is probed with a [`ControlMessage::Ping`] right away, then every [`RelayProbeConfig::interval`].
+    /// The [`RelayStats`] are updated whenever every relay has answered the current round, or the round ends.
+    ///
+    pub fn new_from_transport_with_runtime<R: Runtime, T: Transport>(
+        socket_handle: T,
+        relays: Vec<SocketAddr>,
+        config: RelayProbeConfig,
+    ) -> Self {
+        let mut relays: Vec<RelayState> = relays.into_iter().map(RelayState::new).collect();
+        let (stats_sender, stats_receiver) =
+            watch::channel(relays.iter().map(RelayState::stats).collect());
+        let cancellation_token = CancellationToken::new();
+        let cancellation_token_clone = cancellation_token.clone();
+        let uuid = Uuid::new_v4();
+
+        R::spawn(async move {
+            let mut buf = vec![0; MTU_MAX_PACKET_SIZE + LENGTH_PREFIX_SIZE];
+            let mut sequence: u32 = 0;
+            let mut next_round = Instant::now();
+
+            loop {
+                select! {
+                    //Start the next round of probes
+                    _ = R::sleep(next_round.saturating_duration_since(Instant::now())) => {
+                        if relays.iter().any(|relay| relay.pending.is_some()) {
+                            for relay in relays.iter_mut() {
+                                relay.finish_round(config.window);
+                            }
+
+                            let _ = stats_sender.send(relays.iter().map(RelayState::stats).collect());
+                        }
+
+                        sequence = sequence.wrapping_add(1);
+                        next_round += config.interval;
+
+                        for relay in relays.iter_mut() {
+                            relay.pending = Some((sequence, Instant::now()));
+
+                            if let Err(err) = send_control_message(&socket_handle, ControlMessage::Ping(sequence), uuid, relay.remote_addr).await {
+                                event!(Level::ERROR, "Failed to probe relay {}: {err}", relay.remote_addr);
+                            }
+                        }
+                    }
+
+                    //Await the answers of the relays
+                    incoming_bytes = socket_handle.recv_datagram(&mut buf) => {
+                        let (byte_count, remote_addr) = match incoming_bytes {
+                            Ok(incoming_bytes) => incoming_bytes,
+                            Err(err) => {
+                                event!(Level::ERROR, "Failed to receive message: {err}");
+
+                                continue;
+                            }
+                        };
+
+                        let Ok((voip_header, _)) = decode_message(&buf[..byte_count]) else {
+                            continue;
+                        };
+
+                        let VoipMessageType::Control(ControlMessage::Pong(answered_sequence)) = voip_header.voip_message_type() else {
+                            continue;
+                        };
+
+                        let Some(relay) = relays.iter_mut().find(|relay| relay.remote_addr == remote_addr) else {
+                            continue;
+                        };
+
+                        relay.answer(*answered_sequence, config.window);
+
+                        //Publish the round as soon as every relay has answered
+                        if relays.iter().all(|relay| relay.pending.is_none()) {
+                            let _ = stats_sender.send(relays.iter().map(RelayState::stats).collect());
+                        }
+                    }
+
+                    //Await thread cancellation
+                    _ = cancellation_token_clone.cancelled() => break,
+                }
+            }
+        });
+
+        Self {
+            config,
+            stats_receiver,
+            cancellation_token,
+        }
+    }
+
+    /// Returns the [`RelayStats`] of every relay, in the order they were given in.
+    pub fn stats(&self) -> Vec<RelayStats> {
+        self.stats_receiver.borrow().clone()
+    }
+
+    /// Returns a receiver of the [`RelayStats`] of every relay, which is notified whenever they change (for example to monitor the chosen relay).
+    pub fn stats_receiver(&self) -> watch::Receiver<Vec<RelayStats>> {
+        self.stats_receiver.clone()
+    }
+
+    /// Returns the usable relay with the lowest latency, or [`None`] if no relay is usable.
+    pub fn best(&self) -> Option<RelayStats> {
+        self.stats_receiver
+            .borrow()
+            .iter()
+            .filter(|relay| relay.is_usable(self.config.max_loss))
+            .min_by_key(|relay| relay.latency)
+            .copied()
+    }
+
+    ///
+    /// Waits for the first round of probes, then picks the usable relay with the lowest latency.
+    ///
+    /// # Behavior
+    /// Returns as soon as every relay has answered, or the first round has ended (after [`RelayProbeConfig::interval`]).
+    /// Returns [`None`] if no relay is usable, or the picker task has stopped.
+    ///
+    pub async fn pick(&self) -> Option<SocketAddr> {
+        let mut stats_receiver = self.stats_receiver.clone();
+
+        stats_receiver
+            .wait_for(|relays| {
+                relays
+                    .iter()
+                    .all(|relay| relay.latency.is_some() || relay.loss > 0.)
+            })
+            .await
+            .ok()?;
+
+        self.best().map(|relay| relay.remote_addr)
+    }
+
+    ///
+    /// Returns the relay the application should migrate to from the `current` one, if there is a better one.
+    ///
+    /// # Behavior
+    /// A relay is recommended if the `current` relay has become unusable (or isn't probed), or the best relay is faster by at least [`RelayProbeConfig::hysteresis`].
+    ///
+    pub fn better_relay(&self, current: SocketAddr) -> Option<SocketAddr> {
+        let best = self.best()?;
+
+        if best.remote_addr == current {
+            return None;
+        }
+
+        let current = self
+            .stats()
+            .into_iter()
+            .find(|relay| relay.remote_addr == current)
+            .filter(|relay| relay.is_usable(self.config.max_loss));
+
+        match current.and_then(|relay| relay.latency) {
+            Some(current_latency) => (best.latency? + self.config.hysteresis < current_latency)
+                .then_some(best.remote_addr),
+            None => Some(best.remote_addr),
+        }
+    }
+}
+
+impl Drop for RelayPicker {
+    fn drop(&mut self) {
+        self.cancellation_token.cancel();
+    }
+}
@@ -0,0 +1,547 @@
+//!
+//! Provides the [`ReplayServer`], which plays a captured [`Recording`] of the traffic of a server back to the connecting clients, with its original timing.
+//!
+//! This lets the changes of the client (for example of the jitter buffer, or of the decoders) be tested against the real traffic of a session, including its jitter, losses and reordering, instead of synthetic streams.
+//! The recording is either read from a pcap capture of the server (see [`Recording::from_pcap`]), or built from [`VoipPacket`]s (see [`Recording::new`]).
+//!
+//! Every client which sends a heartbeat is played the recording from its start, the heartbeats are echoed and the pings are answered, every other message of the clients is ignored.
+//! The first heartbeat of a client is answered with a [`ControlMessage::Retry`], and the playback only starts once the client has echoed it, so a spoofed heartbeat can't have the recording streamed at its victim.
+//! The recording is played to at most [`ReplayConfig::max_playbacks`] clients at the same time, and the playbacks of the clients which have gone silent are forgotten.
+//! The messages which only concern the session of the recorded client (its heartbeat echoes, the address validation, the resumption tickets, the connection ids, the device notices, the bitrate limits, the policy violations and the closure of its session) are left out of the recording.
+//!
+
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use tokio::{net::UdpSocket, select, time::Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{event, Level};
+
+use super::{
+    runtime::{Runtime, Tokio},
+    server::{send_control_message, RetryConfig},
+    transport::Transport,
+};
+use crate::{
+    packet::{
+        control::ControlMessage, decode_header, VoipMessageType, VoipPacket, LENGTH_PREFIX_SIZE,
+    },
+    MTU_MAX_PACKET_SIZE,
+};
+
+/// The magic number of the pcap files with microsecond timestamps.
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+
+/// The magic number of the pcap files with nanosecond timestamps.
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+
+/// The magic number of the pcapng files, which aren't supported.
+const PCAPNG_MAGIC: u32 = 0x0a0d_0d0a;
+
+/// The link type of the captures of the BSD loopback interface.
+const LINKTYPE_NULL: u32 = 0;
+
+/// The link type of the captures of Ethernet interfaces.
+const LINKTYPE_ETHERNET: u32 = 1;
+
+/// The link type of the captures without a link layer header.
+const LINKTYPE_RAW: u32 = 101;
+
+/// The link type of the captures of the `any` interface on Linux.
+const LINKTYPE_LINUX_SLL: u32 = 113;
+
+/// The IP protocol number of UDP.
+const IPPROTO_UDP: u8 = 17;
+
+/// The longest record of a pcap file, which is the largest snapshot length of the capturing tools.
+const MAX_RECORD_LENGTH: usize = 262_144;
+
+/// The default number of clients the recording is played to at the same time.
+pub const DEFAULT_MAX_PLAYBACKS: usize = 16;
+
+/// The time a client may go without a heartbeat, before its playback is stopped and forgotten.
+/// This is three times the default heartbeat interval of the clients.
+const PLAYBACK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Errors of reading a [`Recording`], and of creating a [`ReplayServer`].
+#[derive(thiserror::Error, Debug)]
+pub enum ReplayError {
+    /// This error is thrown when the replay server could not bind to its address.
+    #[error("Failed to bind the replay server: {0}")]
+    Bind(io::Error),
+
+    /// This error is thrown when the capture could not be read.
+    #[error("Failed to read the capture: {0}")]
+    Io(#[from] io::Error),
+
+    /// This error is thrown when the capture isn't a pcap file.
+    #[error("The capture isn't a pcap file (magic number {0:#010x}).")]
+    InvalidMagic(u32),
+
+    /// This error is thrown when the capture is a pcapng file, which has to be converted to pcap first (for example with `editcap -F pcap`).
+    #[error("The capture is a pcapng file, convert it to pcap first.")]
+    Pcapng,
+
+    /// This error is thrown when a record of the capture is longer than any capturing tool writes, as the capture is most likely corrupted.
+    #[error("A record of the capture is {0} bytes long.")]
+    OversizedRecord(usize),
+
+    /// This error is thrown when the link layer of the capture isn't supported.
+    #[error("The link type {0} of the capture isn't supported.")]
+    UnsupportedLinkType(u32),
+}
+
+///
+/// Recorded datagram type definition.
+///
+/// A single message of a [`Recording`], with the time it was captured at.
+///
+#[derive(Debug, Clone)]
+pub struct RecordedDatagram {
+    /// The time the message was captured at, since the first message of the recording.
+    pub offset: Duration,
+
+    /// The captured message.
+    pub message: VoipPacket,
+}
+
+///
+/// Recording type definition.
+///
+/// The messages a server has sent to one of its clients, which the [`ReplayServer`] plays back.
+///
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    /// The replayed messages, in the order of their offsets.
+    datagrams: Vec<RecordedDatagram>,
+}
+
+impl Recording {
+    /// Creates a new [`Recording`] instance from the `datagrams`, which are ordered by their offsets.
+    /// The messages which only concern the session of the recorded client are left out (see the [`replay`](self) module).
+    pub fn new(datagrams: impl IntoIterator<Item = RecordedDatagram>) -> Self {
+        let mut datagrams: Vec<RecordedDatagram> = datagrams
+            .into_iter()
+            .filter(|datagram| is_replayable(datagram.message.inner()))
+            .collect();
+
+        //The sort is stable, so the messages captured at the same time keep their order
+        datagrams.sort_by_key(|datagram| datagram.offset);
+
+        Self { datagrams }
+    }
+
+    ///
+    /// Reads a [`Recording`] from a pcap capture of the traffic of a server (for example one made with `tcpdump -w`).
+    ///
+    /// # Behavior
+    /// The UDP datagrams sent from the `source_port` (the port of the server) are recorded, every UDP datagram is recorded if this is [`None`].
+    /// The datagrams which aren't [`VoipPacket`]s, and the fragmented IP packets are skipped. The captures of Ethernet, raw IP, Linux `any` and BSD loopback interfaces are supported.
+    /// The offsets of the datagrams start from the first recorded one.
+    ///
+    /// # Error
+    /// Returns an error if the capture can't be read, or it isn't a supported pcap file.
+    ///
+    pub fn from_pcap<R: Read>(
+        mut reader: R,
+        source_port: Option<u16>,
+    ) -> std::result::Result<Self, ReplayError> {
+        let mut global_header = [0; 24];
+
+        reader.read_exact(&mut global_header)?;
+
+        let magic = u32::from_le_bytes(global_header[..4].try_into().unwrap());
+
+        //The magic number is written in the byte order of the capturing host
+        let (is_little_endian, is_nanos) = match magic {
+            PCAP_MAGIC_MICROS => (true, false),
+            PCAP_MAGIC_NANOS => (true, true),
+            _ if magic.swap_bytes() == PCAP_MAGIC_MICROS => (false, false),
+            _ if magic.swap_bytes() == PCAP_MAGIC_NANOS => (false, true),
+            PCAPNG_MAGIC => return Err(ReplayError::Pcapng),
+            _ => return Err(ReplayError::InvalidMagic(magic)),
+        };
+
+        let read_u32 = |bytes: &[u8]| {
+            let bytes: [u8; 4] = bytes[..4].try_into().unwrap();
+
+            if is_little_endian {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            }
+        };
+
+        let link_type = read_u32(&global_header[20..]) & 0x0fff_ffff;
+
+        if !matches!(
+            link_type,
+            LINKTYPE_NULL | LINKTYPE_ETHERNET | LINKTYPE_RAW | LINKTYPE_LINUX_SLL
+        ) {
+            return Err(ReplayError::UnsupportedLinkType(link_type));
+        }
+
+        let mut first_timestamp = None;
+        let mut datagrams = Vec::new();
+        let mut record_header = [0; 16];
+        let mut record = Vec::new();
+
+        loop {
+            match reader.read_exact(&mut record_header) {
+                Ok(()) => (),
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err.into()),
+            }
+
+            let seconds = read_u32(&record_header[..4]) as u64;
+            let fraction = read_u32(&record_header[4..8]) as u64;
+            let captured_length = read_u32(&record_header[8..12]) as usize;
+
+            let timestamp = Duration::from_secs(seconds)
+                + if is_nanos {
+                    Duration::from_nanos(fraction)
+                } else {
+                    Duration::from_micros(fraction)
+                };
+
+            if captured_length > MAX_RECORD_LENGTH {
+                return Err(ReplayError::OversizedRecord(captured_length));
+            }
+
+            record.resize(captured_length, 0);
+            reader.read_exact(&mut record)?;
+
+            let Some((port, payload)) = udp_payload(link_type, &record) else {
+                continue;
+            };
+
+            if source_port.is_some_and(|source_port| source_port != port)
+                || decode_header(payload).is_err()
+            {
+                continue;
+            }
+
+            let first_timestamp = *first_timestamp.get_or_insert(timestamp);
+
+            datagrams.push(RecordedDatagram {
+                offset: timestamp.saturating_sub(first_timestamp),
+                message: VoipPacket::from_validated(payload.to_vec()),
+            });
+        }
+
+        Ok(Self::new(datagrams))
+    }
+
+    /// Returns the recorded datagrams, in the order they are played back in.
+    pub fn datagrams(&self) -> &[RecordedDatagram] {
+        &self.datagrams
+    }
+
+    /// Returns whether the recording has no datagrams.
+    pub fn is_empty(&self) -> bool {
+        self.datagrams.is_empty()
+    }
+
+    /// Returns the offset of the last datagram of the recording.
+    pub fn duration(&self) -> Duration {
+        self.datagrams
+            .last()
+            .map(|datagram| datagram.offset)
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the source port and the payload of the UDP datagram captured in the `record`, if it is one.
+fn udp_payload(link_type: u32, record: &[u8]) -> Option<(u16, &[u8])> {
+    let ip_packet = match link_type {
+        LINKTYPE_NULL => record.get(4..)?,
+        LINKTYPE_ETHERNET => {
+            //Skip the 802.1Q tag of the VLANs
+            match u16::from_be_bytes(record.get(12..14)?.try_into().ok()?) {
+                0x8100 => record.get(18..)?,
+                _ => record.get(14..)?,
+            }
+        }
+        LINKTYPE_LINUX_SLL => record.get(16..)?,
+        _ => record,
+    };
+
+    let udp_datagram = match ip_packet.first()? >> 4 {
+        4 => {
+            let header_length = (ip_packet[0] & 0x0f) as usize * 4;
+            let fragment = u16::from_be_bytes(ip_packet.get(6..8)?.try_into().ok()?);
+
+            //The fragments don't carry whole datagrams
+            if *ip_packet.get(9)? != IPPROTO_UDP || fragment & 0x3fff != 0 {
+                return None;
+            }
+
+            ip_packet.get(header_length..)?
+        }
+        6 => {
+            if *ip_packet.get(6)? != IPPROTO_UDP {
+                return None;
+            }
+
+            ip_packet.get(40..)?
+        }
+        _ => return None,
+    };
+
+    let source_port = u16::from_be_bytes(udp_datagram.get(..2)?.try_into().ok()?);
+    let length = u16::from_be_bytes(udp_datagram.get(4..6)?.try_into().ok()?) as usize;
+
+    Some((source_port, udp_datagram.get(8..length)?))
+}
+
+/// Returns whether the encoded `message` is played back, see the [`replay`](self) module.
+fn is_replayable(message: &[u8]) -> bool {
+    decode_header(message).is_ok_and(|voip_header| {
+        !matches!(
+            voip_header.voip_message_type(),
+            VoipMessageType::Control(
+                ControlMessage::Heartbeat
+                    | ControlMessage::Retry(_)
+                    | ControlMessage::ResumptionTicket(_)
+                    | ControlMessage::ConnectionId(_)
+                    | ControlMessage::Device(_)
+                    | ControlMessage::MaxBitrate(_)
+                    | ControlMessage::PolicyViolation(_)
+                    | ControlMessage::Goodbye
+                    | ControlMessage::Close(_)
+            )
+        )
+    })
+}
+
+///
+/// Replay configuration type definition.
+///
+/// Describes how the [`ReplayServer`] plays the recording back.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayConfig {
+    /// The pause between the end of the recording and its next playback to the same client.
+    /// The recording is only played once to every client if this is [`None`].
+    pub loop_gap: Option<Duration>,
+
+    /// The maximum number of clients the recording is played to at the same time, the clients joining once it is reached are ignored.
+    /// The finished playbacks count towards the limit until their clients go silent.
+    pub max_playbacks: usize,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            loop_gap: None,
+            max_playbacks: DEFAULT_MAX_PLAYBACKS,
+        }
+    }
+}
+
+/// The playback of the recording to a single client.
+#[derive(Debug, Clone, Copy)]
+struct Playback {
+    /// The time the recording was started at.
+    started_at: Instant,
+
+    /// The index of the next datagram played.
+    next_index: usize,
+
+    /// The time the last heartbeat of the client was received at.
+    last_heard: Instant,
+}
+
+///
+/// Replay server type definition.
+///
+/// Plays a [`Recording`] back to every client which connects to it, see the [`replay`](self) module.
+/// The replay service stops when the [`CancellationToken`] is cancelled.
+///
+#[derive(Debug)]
+pub struct ReplayServer {
+    /// The local address the clients connect to.
+    local_addr: SocketAddr,
+
+    /// The replay service's [`CancellationToken`].
+    cancellation_token: CancellationToken,
+}
+
+impl ReplayServer {
+    /// Creates a new [`ReplayServer`] instance, which plays the `recording` back on the local `[::]:port` address.
+    pub async fn new(
+        port: u32,
+        recording: Recording,
+        config: ReplayConfig,
+    ) -> std::result::Result<Self, ReplayError> {
+        let socket_handle = UdpSocket::bind(format!("[::]:{port}"))
+            .await
+            .map_err(ReplayError::Bind)?;
+
+        Self::new_from_transport(socket_handle, recording, config).await
+    }
+
+    /// Creates a new [`ReplayServer`] instance, which plays the `recording` back through any [`Transport`].
+    /// The replay service is spawned on the [`Tokio`] runtime.
+    pub async fn new_from_transport<T: Transport>(
+        transport: T,
+        recording: Recording,
+        config: ReplayConfig,
+    ) -> std::result::Result<Self, ReplayError> {
+        Self::new_from_transport_with_runtime::<Tokio, T>(transport, recording, config).await
+    }
+
+    /// Creates a new [`ReplayServer`] instance, which plays the `recording` back through any [`Transport`].
+    /// The replay service is spawned on the [`Runtime`] `R`.
+    pub async fn new_from_transport_with_runtime<R: Runtime, T: Transport>(
+        transport: T,
+        recording: Recording,
+        config: ReplayConfig,
+    ) -> std::result::Result<Self, ReplayError> {
+        let local_addr = transport.local_addr().map_err(ReplayError::Bind)?;
+        let cancellation_token = CancellationToken::new();
+
+        Self::create_replay_service::<R, T>(
+            transport,
+            recording,
+            config,
+            cancellation_token.clone(),
+        );
+
+        Ok(Self {
+            local_addr,
+            cancellation_token,
+        })
+    }
+
+    /// Returns the local address the clients connect to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Replay service cancellation token ([`CancellationToken`]) for stopping the replay server.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    fn create_replay_service<R: Runtime, T: Transport>(
+        socket_handle: T,
+        recording: Recording,
+        config: ReplayConfig,
+        cancellation_token: CancellationToken,
+    ) {
+        R::spawn(async move {
+            //Create buffer for reading incoming messages, the length prefix is not included in the MTU
+            let mut buf = vec![0; MTU_MAX_PACKET_SIZE + LENGTH_PREFIX_SIZE];
+
+            //The playback of every connected client
+            let mut playbacks: HashMap<SocketAddr, Playback> = HashMap::new();
+
+            //The addresses of the clients are validated with the retry tokens of a random secret, as nothing has to survive a restart
+            let retry = RetryConfig::default();
+
+            let datagrams = recording.datagrams();
+
+            loop {
+                //The datagrams are sent once their offset has passed since the start of the playback
+                let next_datagram = playbacks
+                    .values()
+                    .filter_map(|playback| {
+                        datagrams
+                            .get(playback.next_index)
+                            .map(|datagram| playback.started_at + datagram.offset)
+                    })
+                    .min();
+
+                select! {
+                    //Await the heartbeats of the clients
+                    incoming_bytes = socket_handle.recv_datagram(&mut buf) => {
+                        let (byte_count, socket_addr) = match incoming_bytes {
+                            Ok(incoming) => incoming,
+                            Err(err) => {
+                                event!(Level::ERROR, "Failed to receive message from a client: {err}");
+
+                                continue;
+                            },
+                        };
+
+                        let Ok(voip_header) = decode_header(&buf[..byte_count]) else {
+                            continue;
+                        };
+
+                        match voip_header.voip_message_type() {
+                            VoipMessageType::Control(ControlMessage::RetryHeartbeat(retry_token)) if !playbacks.contains_key(&socket_addr) && retry.validate(retry_token, socket_addr) => {
+                                //Forget the clients which have gone silent, so that they free up their playbacks
+                                playbacks.retain(|_, playback| playback.last_heard.elapsed() < PLAYBACK_TIMEOUT);
+
+                                if playbacks.len() >= config.max_playbacks {
+                                    event!(Level::WARN, "Ignored {socket_addr}: the recording is already played to {} clients.", playbacks.len());
+
+                                    continue;
+                                }
+
+                                //The recording is played from its start to the joining clients
+                                playbacks.insert(socket_addr, Playback { started_at: Instant::now(), next_index: 0, last_heard: Instant::now() });
+
+                                send_control_message(&socket_handle, ControlMessage::Heartbeat, socket_addr).await;
+                            },
+                            VoipMessageType::Control(ControlMessage::Heartbeat | ControlMessage::RetryHeartbeat(_) | ControlMessage::Resume(_) | ControlMessage::ConnectionHeartbeat(_)) => {
+                                match playbacks.get_mut(&socket_addr) {
+                                    Some(playback) => {
+                                        playback.last_heard = Instant::now();
+
+                                        send_control_message(&socket_handle, ControlMessage::Heartbeat, socket_addr).await;
+                                    },
+                                    //Nothing is played to an address before it has echoed a retry token, so the heartbeats with spoofed addresses can't be used for amplification
+                                    None => send_control_message(&socket_handle, ControlMessage::Retry(retry.issue(socket_addr)), socket_addr).await,
+                                }
+                            },
+                            VoipMessageType::Control(ControlMessage::Ping(sequence)) => {
+                                send_control_message(&socket_handle, ControlMessage::Pong(*sequence), socket_addr).await;
+                            },
+                            VoipMessageType::Control(ControlMessage::Goodbye | ControlMessage::Close(_)) => {
+                                playbacks.remove(&socket_addr);
+                            },
+                            _ => (),
+                        }
+                    }
+
+                    //Send the datagrams whose offset has passed
+                    _ = R::sleep(next_datagram.map(|next_datagram| next_datagram.saturating_duration_since(Instant::now())).unwrap_or_default()), if next_datagram.is_some() => {
+                        let now = Instant::now();
+
+                        for (remote_addr, playback) in playbacks.iter_mut() {
+                            while let Some(datagram) = datagrams.get(playback.next_index).filter(|datagram| playback.started_at + datagram.offset <= now) {
+                                if let Err(err) = socket_handle.send_datagram(datagram.message.inner(), *remote_addr).await {
+                                    event!(Level::ERROR, "Failed to replay a message to {remote_addr}: {err}");
+                                }
+
+                                playback.next_index += 1;
+                            }
+
+                            //Start the recording over after the gap, the recordings which last for no time are only played once
+                            if let Some(loop_gap) = config.loop_gap.filter(|loop_gap| !(recording.duration() + *loop_gap).is_zero()) {
+                                if playback.next_index == datagrams.len() {
+                                    playback.started_at += recording.duration() + loop_gap;
+                                    playback.next_index = 0;
+                                }
+                            }
+                        }
+
+                        //The finished playbacks are kept until their clients go silent, so that the heartbeats of their clients don't start them over
+                        playbacks.retain(|_, playback| playback.last_heard.elapsed() < PLAYBACK_TIMEOUT);
+                    }
+
+                    //Await the cancellation of the replay server
+                    _ = cancellation_token.cancelled() => {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
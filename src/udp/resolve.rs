@@ -0,0 +1,164 @@
+//!
+//! Provides the resolution of the remote hostnames the [`Client`](super::client::Client) connects to.
+//!
+//! When a hostname has both IPv6 and IPv4 addresses, they are raced like happy eyeballs ([RFC 8305](https://www.rfc-editor.org/rfc/rfc8305)) does:
+//! every address is probed with a [`ControlMessage::Ping`] after a short stagger, IPv6 first, and the first address answering with a [`ControlMessage::Pong`] is kept.
+//! This keeps the hosts with broken IPv6 connectivity working, without slowing down the hosts whose IPv6 works.
+//!
+
+use std::{
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use tokio::{
+    net::{lookup_host, ToSocketAddrs, UdpSocket},
+    task::JoinSet,
+    time::{timeout, Instant},
+};
+use uuid::Uuid;
+
+use super::client::send_control_message;
+use crate::{
+    packet::{control::ControlMessage, decode_message, VoipMessageType, LENGTH_PREFIX_SIZE},
+    MTU_MAX_PACKET_SIZE,
+};
+
+/// The delay between the starts of two probes, the attempt delay recommended by RFC 8305.
+pub const HAPPY_EYEBALLS_DELAY: Duration = Duration::from_millis(250);
+
+/// The time the addresses have to answer in, after their probes were started.
+pub const HAPPY_EYEBALLS_TIMEOUT: Duration = Duration::from_secs(2);
+
+///
+/// Resolves the `remote_addr`, racing its addresses if it has both IPv6 and IPv4 ones.
+///
+/// # Behavior
+/// Addresses of a single family are returned without probing, the first one being used.
+/// Otherwise the addresses are probed with the families interleaved (IPv6 first), one after the other every [`HAPPY_EYEBALLS_DELAY`], and the first one answering is returned.
+/// If none of them answers in [`HAPPY_EYEBALLS_TIMEOUT`], the first address is returned, so the caller behaves like it did without racing.
+///
+/// # Error
+/// Returns an error if the `remote_addr` could not be resolved to any address.
+///
+pub async fn resolve<T: ToSocketAddrs>(remote_addr: T) -> io::Result<SocketAddr> {
+    let (ipv6_addrs, ipv4_addrs): (Vec<SocketAddr>, Vec<SocketAddr>) = lookup_host(remote_addr)
+        .await?
+        .partition(SocketAddr::is_ipv6);
+
+    //Interleave the families, starting with IPv6
+    let mut candidates = Vec::with_capacity(ipv6_addrs.len() + ipv4_addrs.len());
+    let (mut ipv6_addrs, mut ipv4_addrs) = (ipv6_addrs.into_iter(), ipv4_addrs.into_iter());
+
+    loop {
+        let (ipv6_addr, ipv4_addr) = (ipv6_addrs.next(), ipv4_addrs.next());
+
+        if ipv6_addr.is_none() && ipv4_addr.is_none() {
+            break;
+        }
+
+        candidates.extend(ipv6_addr);
+        candidates.extend(ipv4_addr);
+    }
+
+    let Some(first_addr) = candidates.first().copied() else {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            "The remote address could not be resolved to any address.",
+        ));
+    };
+
+    let is_dual_stack =
+        candidates.iter().any(SocketAddr::is_ipv6) && candidates.iter().any(SocketAddr::is_ipv4);
+
+    if !is_dual_stack {
+        return Ok(first_addr);
+    }
+
+    Ok(race(&candidates).await.unwrap_or(first_addr))
+}
+
+///
+/// Probes the `candidates` one after the other every [`HAPPY_EYEBALLS_DELAY`], returning the first one which answers.
+/// Returns [`None`] if none of them answers in [`HAPPY_EYEBALLS_TIMEOUT`] after its probe was started.
+///
+pub(crate) async fn race(candidates: &[SocketAddr]) -> Option<SocketAddr> {
+    let mut attempts = JoinSet::new();
+
+    for (index, remote_addr) in candidates.iter().copied().enumerate() {
+        let delay = HAPPY_EYEBALLS_DELAY * index as u32;
+
+        attempts.spawn(async move {
+            tokio::time::sleep(delay).await;
+
+            probe(remote_addr).await.map(|_| remote_addr)
+        });
+    }
+
+    //The attempts still running are aborted when the set is dropped
+    while let Some(attempt) = attempts.join_next().await {
+        if let Ok(Ok(remote_addr)) = attempt {
+            return Some(remote_addr);
+        }
+    }
+
+    None
+}
+
+///
+/// Probes the `remote_addr` with a [`ControlMessage::Ping`] from a socket of its family, retransmitting it every [`HAPPY_EYEBALLS_DELAY`].
+///
+/// # Error
+/// Returns an error if the socket could not be bound, the address is unreachable, or it hasn't answered with a [`ControlMessage::Pong`] in [`HAPPY_EYEBALLS_TIMEOUT`].
+///
+async fn probe(remote_addr: SocketAddr) -> io::Result<()> {
+    let local_addr = match remote_addr {
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+    };
+
+    let socket_handle = UdpSocket::bind(local_addr).await?;
+
+    socket_handle.connect(remote_addr).await?;
+
+    let deadline = Instant::now() + HAPPY_EYEBALLS_TIMEOUT;
+    let mut buf = vec![0; MTU_MAX_PACKET_SIZE + LENGTH_PREFIX_SIZE];
+
+    while Instant::now() < deadline {
+        send_control_message(
+            &socket_handle,
+            ControlMessage::Ping(0),
+            Uuid::nil(),
+            remote_addr,
+        )
+        .await
+        .map_err(io::Error::other)?;
+
+        let retransmit_at = (Instant::now() + HAPPY_EYEBALLS_DELAY).min(deadline);
+
+        //Wait for the answer until the next retransmission, the unreachable addresses fail right away
+        while let Ok(byte_count) = timeout(
+            retransmit_at.saturating_duration_since(Instant::now()),
+            socket_handle.recv(&mut buf),
+        )
+        .await
+        {
+            let Ok((voip_header, _)) = decode_message(&buf[..byte_count?]) else {
+                continue;
+            };
+
+            if matches!(
+                voip_header.voip_message_type(),
+                VoipMessageType::Control(ControlMessage::Pong(_))
+            ) {
+                return Ok(());
+            }
+        }
+    }
+
+    Err(io::Error::new(
+        ErrorKind::TimedOut,
+        "The remote address hasn't answered the probe.",
+    ))
+}
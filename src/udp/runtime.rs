@@ -0,0 +1,94 @@
+//!
+//! Provides the [`Runtime`] abstraction, which the client and server services use to spawn their tasks and wait for timers.
+//!
+//! The services are built on runtime agnostic primitives (channels and [`select!`](tokio::select)), so they can run on any executor implementing this trait.
+//! The crate provides implementations for:
+//! * [tokio](https://crates.io/crates/tokio) ([`Tokio`]), which is the default.
+//! * [async-std](https://crates.io/crates/async-std) ([`AsyncStd`]), with the `async-std` feature.
+//! * [smol](https://crates.io/crates/smol) ([`Smol`]), with the `smol` feature.
+//!
+//! The runtime is selected when creating a [`Client`](super::client::Client) or a [`Server`](super::server::Server) with `new_from_transport_with_runtime`.
+//! Make sure to use a [`Transport`](super::transport::Transport) which is driven by the same runtime (for example `async_std::net::UdpSocket` with [`AsyncStd`]).
+//!
+
+use std::{future::Future, pin::Pin, time::Duration};
+
+///
+/// Async runtime definition.
+///
+/// Implement this trait to run the services on a custom executor (for example an embedded one).
+///
+pub trait Runtime: Send + Sync + 'static {
+    /// Spawns a future onto the runtime, without waiting for its completion.
+    fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static;
+
+    /// Waits until the `duration` has elapsed.
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send;
+}
+
+/// A type erased [`Runtime::sleep`], for the types which aren't generic over the runtime their service was spawned on (for example the [`Client`](super::client::Client)).
+pub(crate) type SleepFn = fn(Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Returns the type erased [`Runtime::sleep`] of the runtime `R`.
+pub(crate) fn sleep_fn<R: Runtime>() -> SleepFn {
+    |duration| Box::pin(R::sleep(duration))
+}
+
+/// The [tokio](https://crates.io/crates/tokio) runtime.
+/// This is the runtime used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tokio;
+
+impl Runtime for Tokio {
+    fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        tokio::spawn(future);
+    }
+
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+        tokio::time::sleep(duration)
+    }
+}
+
+/// The [async-std](https://crates.io/crates/async-std) runtime.
+#[cfg(feature = "async-std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncStd;
+
+#[cfg(feature = "async-std")]
+impl Runtime for AsyncStd {
+    fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        async_std::task::spawn(future);
+    }
+
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+        async_std::task::sleep(duration)
+    }
+}
+
+/// The [smol](https://crates.io/crates/smol) runtime.
+#[cfg(feature = "smol")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Smol;
+
+#[cfg(feature = "smol")]
+impl Runtime for Smol {
+    fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        //Detach the task, so that it keeps running after the handle is dropped
+        smol::spawn(future).detach();
+    }
+
+    async fn sleep(duration: Duration) {
+        smol::Timer::after(duration).await;
+    }
+}
@@ -1,22 +1,67 @@
 //! Provides functions and helpers for the server side of the Voip service.
-use super::{Result, UdpError};
+#[cfg(unix)]
+use super::activation::activated_sockets;
+#[cfg(feature = "cluster")]
+use super::cluster::{self, ClusterConfig, ClusterLink};
+#[cfg(feature = "persistence")]
+use super::store::ServerStore;
+#[cfg(feature = "transcode")]
+use super::transcode::{TranscodeConfig, Transcoder};
+use super::{
+    amplification::{AmplificationGuard, AmplificationLimit},
+    bandwidth::{BandwidthLimits, BandwidthMeter},
+    drops::{DropCounters, DropLog, DropLogConfig, DropReason, DropStats},
+    egress::{EgressLimits, EgressScheduler, Pacing},
+    filter::SourceFilter,
+    floor::{FloorConfig, FloorControl, FloorNotice},
+    handoff::{HandoffPeer, SessionHandoff},
+    hold::{HoldMusic, HoldPlayback},
+    policy::PolicyMeter,
+    runtime::{Runtime, Tokio},
+    shedding::{LoadChange, LoadShedder, LoadSheddingConfig, Shedding},
+    simulcast::{ActiveLayers, LayerRouting},
+    transport::{
+        ecn::Ecn,
+        path::{self, PathErrorKind},
+        qos::Dscp,
+        Transport,
+    },
+};
 use crate::{
-    packet::{VoipHeader, VoipPacket},
+    packet::{
+        control::{
+            CallSignal, CallSignalKind, CloseCode, CloseReason, ConnectionId, ControlMessage,
+            DeviceNotice, MediaState, PresenceState, QualityReport, RecordingState,
+            ResumptionTicket, RetryToken, RoomPolicy, MAX_KEEPALIVE_PROBE_DELAY_MS,
+        },
+        decode_header, decode_message, HeaderFlags, MediaCodec, VoipHeader, VoipMessageType,
+        VoipPacket, LENGTH_PREFIX_SIZE,
+    },
     MTU_MAX_PACKET_SIZE,
 };
-use dashmap::DashSet;
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use std::{
-    net::SocketAddr,
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
     ops::{Deref, DerefMut},
     sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
     net::UdpSocket,
     select,
-    sync::mpsc::{channel, Receiver, Sender},
+    sync::{
+        broadcast,
+        mpsc::{channel, Receiver, Sender},
+        oneshot,
+    },
+    time::Instant,
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{event, Level};
+use uuid::Uuid;
 
 ///
 /// Server instance type definition.
@@ -28,6 +73,9 @@ pub struct Server {
     /// The currently connected clients' list.
     connected_clients: ClientList,
 
+    /// The handle to the management functions of the server.
+    handle: ServerHandle,
+
     /// The locally bound server's [`CancellationToken`].
     /// This can be used to shut down the server.
     cancellation_token: CancellationToken,
@@ -37,148 +85,4032 @@ pub struct Server {
 
     /// This local channel receives messages which will be sent to listening clients at their remote addresses.
     outbound_message_sender: Sender<VoipPacket>,
+
+    /// The local address the transport of the server is bound to.
+    local_addr: SocketAddr,
 }
 
-#[derive(Debug, Default, Clone)]
-/// Client list type definition.
-pub struct ClientList(Arc<DashSet<SocketAddr>>);
+/// Custom server errors, returned by the methods of the [`Server`] and the [`ServerHandle`].
+#[derive(thiserror::Error, Debug)]
+pub enum ServerError {
+    /// This error is thrown when the socket of the server could not be bound, or its local address could not be read.
+    #[error("Socket error: {0}")]
+    SocketIo(std::io::Error),
 
-/// Implement [`Deref`] for the Wrapper type, so that it automaticly 'inherits' the traits and functions of the wrapped type.
-impl Deref for ClientList {
-    type Target = Arc<DashSet<SocketAddr>>;
+    /// This error is thrown when a client is targeted (for example closed or placed on hold), which isn't connected to the server.
+    #[error("The client at {0} isn't connected.")]
+    NotConnected(SocketAddr),
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    /// This error is thrown when a request (or a reply) is sent to the server service, which has already shut down.
+    #[error("The server service has already shut down.")]
+    ChannelClosed,
+
+    /// This error is thrown when the [`ServerStore`] could not be accessed.
+    #[cfg(feature = "persistence")]
+    #[error("Failed to access the store: {0}")]
+    Store(super::store::StoreError),
+
+    /// This error is thrown when the Redis server of the [`ClusterConfig`] could not be reached.
+    #[cfg(feature = "cluster")]
+    #[error("Failed to connect to the cluster: {0}")]
+    Cluster(redis::RedisError),
+}
+
+/// A request to the server service of the [`Server`].
+#[derive(Debug)]
+enum ServiceRequest {
+    /// Close the session of a single client.
+    Client(SocketAddr, CloseReason),
+
+    /// Close the session of every client, then shut down the server service.
+    Shutdown(CloseReason),
+
+    /// Create (or update) a room with its policy, and advertise it to every peer.
+    CreateRoom(u32, RoomPolicy),
+
+    /// Destroy a room, and advertise the removal of its policy to every peer.
+    DestroyRoom(u32),
+
+    /// Place a single client on hold, or retrieve it from hold.
+    Hold(SocketAddr, bool),
+
+    /// Start or stop recording a room, and signal its recording state to every peer.
+    Recording(u32, RecordingState),
+
+    /// Shut down the server service without notifying the clients, and send the state of their sessions back.
+    Handoff(oneshot::Sender<SessionHandoff>),
+}
+
+///
+/// Server event type definition.
+///
+/// The lifecycle events of the [`Server`], which can be received with [`Server::subscribe_events`], or with an [`EventSink`](super::hook::EventSink).
+/// The events serialize to objects tagged with their snake case name in the `event` field (for example `{"event": "peer_joined", ...}`).
+///
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// A peer has sent its first heartbeat.
+    PeerJoined {
+        /// The address of the peer.
+        remote_addr: SocketAddr,
+        /// The [`Uuid`] the peer sends its messages with.
+        author: Uuid,
+    },
+
+    /// A peer has sent a voice, video or text message on a different channel (or room) than before.
+    PeerMoved {
+        /// The address of the peer.
+        remote_addr: SocketAddr,
+        /// The [`Uuid`] the peer sends its messages with.
+        author: Uuid,
+        /// The channel of the room the peer has moved to.
+        room: u32,
+    },
+
+    /// A peer has resumed its session from a new address (for example after failing over to another address of the server), keeping its statistics and its room.
+    PeerMigrated {
+        /// The address the peer has sent its messages from before.
+        previous_addr: SocketAddr,
+        /// The new address of the peer.
+        remote_addr: SocketAddr,
+        /// The [`Uuid`] the peer sends its messages with.
+        author: Uuid,
+    },
+
+    /// A peer has left the session (by sending a [`ControlMessage::Goodbye`] or a [`ControlMessage::Close`]).
+    PeerLeft {
+        /// The address of the peer.
+        remote_addr: SocketAddr,
+        /// The [`Uuid`] the peer sent its messages with.
+        author: Uuid,
+    },
+
+    /// The session of a peer was closed by the server (for example when it was kicked).
+    PeerClosed {
+        /// The address of the peer.
+        remote_addr: SocketAddr,
+        /// The [`Uuid`] the peer sent its messages with, if it was registered.
+        author: Option<Uuid>,
+        /// The reason the session was closed with.
+        close_reason: CloseReason,
+    },
+
+    /// A room was created (or its policy was updated).
+    RoomCreated {
+        /// The channel of the room.
+        room: u32,
+        /// The policy of the room.
+        room_policy: RoomPolicy,
+    },
+
+    /// A room was destroyed.
+    RoomDestroyed {
+        /// The channel of the room.
+        room: u32,
+    },
+
+    /// The application has started recording a room (see [`ServerHandle::recording_started`]).
+    RecordingStarted {
+        /// The channel of the room.
+        room: u32,
+    },
+
+    /// The application has stopped recording a room (see [`ServerHandle::recording_stopped`]).
+    RecordingStopped {
+        /// The channel of the room.
+        room: u32,
+    },
+
+    /// A peer has given or withdrawn its consent to the recording of a room (by sending a [`ControlMessage::RecordingConsent`]).
+    RecordingConsentChanged {
+        /// The address of the peer.
+        remote_addr: SocketAddr,
+        /// The [`Uuid`] the peer sends its messages with.
+        author: Uuid,
+        /// The channel of the recorded room.
+        room: u32,
+        /// Whether the peer has consented to the recording.
+        consent: bool,
+    },
+
+    /// An author was banned from the server (see [`ServerHandle::ban`]).
+    AuthorBanned {
+        /// The banned author.
+        author: Uuid,
+        /// The human readable reason of the ban.
+        reason: Option<String>,
+    },
+
+    /// The ban of an author was lifted (see [`ServerHandle::unban`]).
+    AuthorUnbanned {
+        /// The author whose ban was lifted.
+        author: Uuid,
+    },
+
+    /// A peer has failed to join (for example because it is banned, or it has echoed an invalid [`RetryToken`]).
+    ConnectionRejected {
+        /// The address the peer has tried to join from.
+        remote_addr: SocketAddr,
+        /// The [`Uuid`] the peer has sent its heartbeat with.
+        author: Uuid,
+        /// The reason the peer was rejected with.
+        close_reason: CloseReason,
+    },
+
+    /// The server is overloaded, and has started shedding more (or different) media to keep the rest of it real-time (see [`ServerConfig::load_shedding`]).
+    Overloaded {
+        /// The amount of messages which were waiting in the queues of the server.
+        queue_depth: usize,
+        /// The delay of the evaluation of the load behind its schedule.
+        loop_lag: Duration,
+        /// The media dropped from now on.
+        shedding: Shedding,
+    },
+
+    /// The load of the server has dropped, and it sheds less media. Nothing is dropped anymore once the [`Shedding`] is empty.
+    OverloadRelieved {
+        /// The media still dropped.
+        shedding: Shedding,
+    },
+
+    /// The network has reported an error about a datagram sent by the server, for example the peer has become unreachable or the hop limit of the datagram has expired on the way.
+    /// This is only reported if [`ServerConfig::path_errors`] is enabled.
+    PathError {
+        /// The address the datagram was sent to.
+        remote_addr: SocketAddr,
+        /// The [`Uuid`] of the peer at the address, if it has joined.
+        author: Option<Uuid>,
+        /// The address of the router (or host) which has reported the error, if the operating system passes it on.
+        reporter: Option<IpAddr>,
+        /// The kind of the error.
+        kind: PathErrorKind,
+    },
+}
+
+impl From<LoadChange> for ServerEvent {
+    fn from(load_change: LoadChange) -> Self {
+        match load_change {
+            LoadChange::Overloaded {
+                queue_depth,
+                loop_lag,
+                shedding,
+            } => Self::Overloaded {
+                queue_depth,
+                loop_lag,
+                shedding,
+            },
+            LoadChange::Relieved(shedding) => Self::OverloadRelieved { shedding },
+        }
     }
 }
 
-/// Implement [`DerefMut`] for the Wrapper type, so that it automaticly 'inherits' the mutable traits and functions of the wrapped type.
-impl DerefMut for ClientList {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+/// Room policies type definition.
+/// Maps the channel of every room to its [`RoomPolicy`].
+pub type RoomPolicies = Arc<DashMap<u32, RoomPolicy>>;
+
+/// Recordings type definition.
+/// Maps the channel of every recorded room to its [`RecordingState`].
+pub type Recordings = Arc<DashMap<u32, RecordingState>>;
+
+///
+/// Ban type definition.
+///
+/// Describes why and when an author was banned from the [`Server`].
+///
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Ban {
+    /// The human readable reason of the ban, which is sent to the banned author whenever it tries to join.
+    pub reason: Option<String>,
+
+    /// The time the author was banned at.
+    pub banned_at: SystemTime,
+}
+
+impl Ban {
+    /// Creates a new [`Ban`] instance, banning from now on.
+    pub fn new(reason: Option<String>) -> Self {
+        Self {
+            reason,
+            banned_at: SystemTime::now(),
+        }
     }
 }
 
-impl Server {
-    /// Creates a new [`Server`] instance, and bind to the local IPV6 address with the given port.
-    pub async fn new(port: u32) -> Result<Self> {
-        let socket_handle = UdpSocket::bind(format!("[::]:{port}"))
-            .await
-            .map_err(UdpError::BindError)?;
+/// Ban list type definition.
+/// Maps the [`Uuid`] of every banned author to its [`Ban`].
+pub type BanList = Arc<DashMap<Uuid, Ban>>;
 
-        let (outbound_message_sender, mut outbound_message_receiver) = channel::<VoipPacket>(255);
-        let (inbound_message_sender, inbound_message_receiver) =
-            channel::<(VoipHeader, Vec<u8>, SocketAddr)>(255);
-        let cancellation_token = CancellationToken::new();
-        let client_list = ClientList::default();
-        let client_list_clone = client_list.clone();
-        let cancellation_token_clone = cancellation_token.clone();
+///
+/// Server configuration type definition.
+///
+/// Contains the settings of the server service.
+///
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    /// The audio level (in -dBov) from which voice messages are considered silent, and are discarded instead of being forwarded to the application.
+    /// This reduces the fan-out bandwidth in sessions with many open microphones, when the senders dont use voice activity detection.
+    /// Voice messages without an [audio level](VoipHeader::audio_level) are always forwarded, gating is disabled if this is [`None`].
+    pub silence_threshold: Option<u8>,
 
-        tokio::spawn(async move {
-            loop {
-                //Create buffer for reading incoming messages
-                let mut buf = Vec::with_capacity(8);
+    /// The [`RoomPolicy`] of every channel (or room), which is advertised to every client when it joins (sends its first heartbeat).
+    pub room_policies: HashMap<u32, RoomPolicy>,
 
-                select! {
-                    //Await receving said amounts of bytes
-                    incoming_bytes = socket_handle.recv_from(&mut buf) => {
-                        match incoming_bytes {
-                            Ok((_byte_count, socket_addr)) => {
-                                let body_length = usize::from_be_bytes(buf.try_into().unwrap());
+    /// The configuration of the [`Transcoder`], which re-encodes the Opus voice messages for the clients which have signaled a [`ControlMessage::MaxBitrate`].
+    /// Transcoding is disabled if this is [`None`].
+    #[cfg(feature = "transcode")]
+    pub transcoding: Option<TranscodeConfig>,
 
-                                //Check for invalid messages
-                                if body_length > MTU_MAX_PACKET_SIZE {
-                                    //Log error
-                                    event!(Level::ERROR, "Message header with too large length: {body_length}. Discarding message.");
+    /// The configuration of the periodic [`QualityReport`]s the server sends to the moderators about every peer.
+    /// The reports are disabled if this is [`None`], the statistics are still available through [`Server::stats`].
+    pub stats_report: Option<StatsReportConfig>,
 
-                                    //If an inavlid message was provided discard it, to avoid overflowing buffer sizes
-                                    continue;
-                                }
+    /// The [`Ban`] of every author banned from the server, their messages are discarded and their heartbeats are answered with a [`ControlMessage::Close`].
+    pub bans: HashMap<Uuid, Ban>,
 
-                                //This cannot block as the header and the body is included in one message
-                                let mut body_buf = Vec::with_capacity(body_length);
+    /// The [`ServerStore`] the bans and the rooms are persisted in.
+    /// The stored bans and rooms are restored on top of the ones in the configuration when the server is created, and every change made through the [`ServerHandle`] is saved.
+    #[cfg(feature = "persistence")]
+    pub store: Option<ServerStore>,
 
-                                //Read from UdpSocket
-                                socket_handle.recv(&mut body_buf).await.unwrap();
+    /// The configuration of the Redis cluster the server is a node of, which lets a room span multiple servers (see the [`cluster`] module).
+    /// The server isn't clustered if this is [`None`].
+    #[cfg(feature = "cluster")]
+    pub cluster: Option<ClusterConfig>,
 
-                                //Try serializing the bytes
-                                match rmp_serde::from_slice::<VoipHeader>(&body_buf) {
-                                    Ok(voip_header) => {
-                                        let voip_body_length = match voip_header.voip_message_type() {
-                                            crate::packet::VoipMessageType::VoiceMessage(length) => length,
-                                            crate::packet::VoipMessageType::VideoMessage(length) => length,
-                                        };
+    /// The configuration of the address validation, which makes the unregistered addresses echo a [`RetryToken`] before they are registered.
+    /// This prevents floods of heartbeats with spoofed source addresses from filling the [`PeerRegistry`].
+    /// Every heartbeat registers its sender if this is [`None`].
+    pub retry: Option<RetryConfig>,
 
-                                        //Create voip body buf allocate the length by fetching the header
-                                        let mut voip_body_buf = Vec::with_capacity(*voip_body_length as usize);
+    /// The configuration of the [`ResumptionTicket`]s issued to the joined peers, which let them restore their session after a crash or a network blip.
+    /// A peer presenting a valid ticket in a [`ControlMessage::Resume`] takes its session over from its previous address, once it has echoed the [`ControlMessage::Retry`] sent to its new address (see [`DuplicateLoginPolicy::Migrate`]).
+    /// No tickets are issued if this is [`None`].
+    pub resumption: Option<ResumptionConfig>,
 
-                                        //Read the body into the buffer
-                                        socket_handle.recv(&mut voip_body_buf).await.unwrap();
+    /// What happens when an author already in the session joins from another address (see [`DuplicateLoginPolicy`]).
+    pub duplicate_login: DuplicateLoginPolicy,
 
-                                        //Send the serialized message through the channel
-                                        inbound_message_sender.send((voip_header, voip_body_buf, socket_addr)).await.unwrap();
-                                    },
-                                    Err(err) => {
-                                        event!(Level::ERROR, "Failed to deserialize a VoipPacket: {err}");
-                                    },
-                                }
+    /// The limit of the bytes sent to the addresses which haven't joined the session, which prevents the server from being abused for amplification attacks.
+    pub amplification_limit: AmplificationLimit,
 
-                            },
-                            Err(err) => {
-                                event!(Level::ERROR, "Failed to receive message: {err}");
-                            },
-                        }
-                    }
+    /// The [`SourceFilter`] deciding which addresses the datagrams are accepted from, it is evaluated before the datagrams are deserialized.
+    /// The datagrams of the filtered addresses are discarded without a reply.
+    pub source_filter: SourceFilter,
 
-                    //Await outbound channel request
-                    Some(outgoing_message) = outbound_message_receiver.recv() => {
-                        //Clone the client list becasue it doesnt implement Send
+    /// The caps of the bitrate forwarded from every client and in every room.
+    /// The media exceeding a cap is dropped (the video before the voice), and its sender is notified with a [`ControlMessage::MaxBitrate`].
+    pub bandwidth_limits: BandwidthLimits,
 
-                        //Iter over all the remote_addresses and echo back the VoipPacket to everyone.
-                        for remote_addr in client_list_clone.iter() {
-                            //Send the VoipPacket to the remote address
-                            socket_handle.send_to(outgoing_message.inner(), remote_addr.key()).await.unwrap();
-                        }
-                    }
+    /// The caps of the bitrate the server sends the fanned out messages at, in total and to every client (see the [`egress`](super::egress) module).
+    /// The media exceeding a cap is queued until the caps allow sending it (the voice before the video), and dropped if it has waited for too long.
+    pub egress_limits: EgressLimits,
 
-                    //Await thread cancellation
-                    _ = cancellation_token_clone.cancelled() => break,
-                }
-            }
-        });
+    /// The configuration of the floor control, which only forwards the voice of the client holding the floor of each room (see the [`floor`](super::floor) module).
+    /// Every client can talk at the same time if this is [`None`].
+    pub floor_control: Option<FloorConfig>,
 
-        Ok(Self {
-            connected_clients: client_list,
-            inbound_message_receiver,
-            cancellation_token,
-            outbound_message_sender,
-        })
+    /// The [`HoldMusic`] played to the clients placed on hold with [`ServerHandle::hold`].
+    /// The held clients are only cut off from the session if this is [`None`].
+    pub hold_music: Option<HoldMusic>,
+
+    /// The interval of the [`QualityReport`]s the server sends back to every peer about the messages received from it, including the ones marked with ECN-CE.
+    /// The transport is asked to mark and read the [`Ecn`] codepoints, so that the congestion controllers of the clients can react to congestion before anything is lost.
+    /// No feedback is sent if this is [`None`].
+    pub congestion_feedback: Option<Duration>,
+
+    /// The configuration of the load shedding, which drops the video, then pauses the rooms with the lowest priority while the server is overloaded (see the [`shedding`](super::shedding) module).
+    /// Every stream degrades alike under overload if this is [`None`].
+    pub load_shedding: Option<LoadSheddingConfig>,
+
+    /// The sessions handed over by the server this one replaces (see the [`handoff`](super::handoff) module).
+    /// The peers, the rooms, the recordings and the bans of the handoff are restored on top of the ones in the configuration (and the store), without notifying the clients.
+    pub handoff: Option<SessionHandoff>,
+
+    /// The [`Dscp`] the datagrams of the server are marked with, so that the networks honoring it prioritize the relayed voice (see the [`qos`](super::transport::qos) module).
+    /// The datagrams are sent with the traffic class of the operating system if this is [`None`].
+    pub dscp: Option<Dscp>,
+
+    /// The hop limit (or TTL) the datagrams of the server are sent with, the hop limit of the operating system is kept if this is [`None`].
+    pub hop_limit: Option<u8>,
+
+    /// Whether the errors the network reports about the sent datagrams (for example a peer which has become unreachable) are reported with [`ServerEvent::PathError`], instead of the datagrams being silently lost.
+    /// Only the transports (and the platforms) which can read them report them, see the [`path`](super::transport::path) module.
+    pub path_errors: bool,
+
+    /// Which of the dropped messages are logged, and how often (see the [`drops`](super::drops) module).
+    /// Every dropped message is counted in the [`ServerStats::drops`] either way.
+    pub drop_log: DropLogConfig,
+}
+
+///
+/// Statistics report configuration type definition.
+///
+/// Describes how often, and to whom the [`Server`] reports the statistics of its peers.
+///
+#[derive(Debug, Clone)]
+pub struct StatsReportConfig {
+    /// The interval of the reports.
+    pub interval: Duration,
+
+    /// The [`Uuid`]s of the peers receiving the reports.
+    pub moderators: HashSet<Uuid>,
+}
+
+impl Default for StatsReportConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            moderators: HashSet::new(),
+        }
     }
+}
 
-    /// Gets the incoming message receiver ([`Receiver<VoipPacket>`]) handle.
-    /// This is created at the instance creation of [`Server`].
-    /// The server listener threads has ownership of the sender, and sends every incoming message to the receiver.
-    pub fn message_receiver(&mut self) -> &mut Receiver<(VoipHeader, Vec<u8>, SocketAddr)> {
-        &mut self.inbound_message_receiver
+///
+/// Retry configuration type definition.
+///
+/// Describes how the [`Server`] issues and validates the [`RetryToken`]s, without storing any state about the addresses.
+///
+#[derive(Clone)]
+pub struct RetryConfig {
+    /// The secret the tokens are authenticated with.
+    /// Servers sharing their addresses (for example the restarted instances of a server) should share the secret too.
+    pub secret: [u8; 32],
+
+    /// The time a token is valid for after it was issued.
+    pub lifetime: Duration,
+}
+
+impl std::fmt::Debug for RetryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryConfig")
+            .field("lifetime", &self.lifetime)
+            .finish_non_exhaustive()
     }
+}
 
-    /// Server thread cancellation token ([`CancellationToken`]) for shutting down the server.
-    /// This can be cancelled in a sync environment.
-    pub fn cancellation_token(&self) -> &CancellationToken {
-        &self.cancellation_token
+impl Default for RetryConfig {
+    /// Creates a [`RetryConfig`] with a random secret, and a lifetime of 10 seconds.
+    fn default() -> Self {
+        let mut secret = [0; 32];
+
+        secret[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+        secret[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+
+        Self {
+            secret,
+            lifetime: Duration::from_secs(10),
+        }
     }
+}
 
-    /// This gets the list of [`SocketAddr`]s which the UdpSocket should reply to.
-    pub fn get_reply_to_list_mut(&self) -> Arc<DashSet<SocketAddr>> {
-        self.connected_clients.0.clone()
+impl RetryConfig {
+    /// Issues a [`RetryToken`] for the `remote_addr`, valid from now on.
+    pub fn issue(&self, remote_addr: SocketAddr) -> RetryToken {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut mac = [0; 16];
+        mac.copy_from_slice(&self.mac(remote_addr, issued_at).finalize().into_bytes()[..16]);
+
+        RetryToken::new(issued_at, mac)
     }
 
-    /// Replies to all of the [`SocketAddr`]-es specified in `self.connected_clients` through the [`UdpSocket`] the server is bound to.
-    /// Sends the [`VoipPacket`] through a channel, which the server async thread is awaiting.
-    pub async fn reply_to_clients(
-        &self,
-        voip_packet: VoipPacket,
-    ) -> std::result::Result<(), tokio::sync::mpsc::error::SendError<VoipPacket>> {
-        self.outbound_message_sender.send(voip_packet).await
+    /// Returns whether the [`RetryToken`] was issued for the `remote_addr` with this secret, and hasn't expired yet.
+    pub fn validate(&self, retry_token: &RetryToken, remote_addr: SocketAddr) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let is_fresh = retry_token.issued_at() <= now
+            && now - retry_token.issued_at() <= self.lifetime.as_secs();
+
+        is_fresh
+            && self
+                .mac(remote_addr, retry_token.issued_at())
+                .verify_truncated_left(&retry_token.mac())
+                .is_ok()
+    }
+
+    /// Creates the message authentication code of the `remote_addr` and the issue time.
+    fn mac(&self, remote_addr: SocketAddr, issued_at: u64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("Hmac accepts keys of any length");
+
+        match remote_addr.ip() {
+            std::net::IpAddr::V4(ip) => mac.update(&ip.to_ipv6_mapped().octets()),
+            std::net::IpAddr::V6(ip) => mac.update(&ip.octets()),
+        }
+
+        mac.update(&remote_addr.port().to_be_bytes());
+        mac.update(&issued_at.to_be_bytes());
+
+        mac
+    }
+}
+
+///
+/// Resumption configuration type definition.
+///
+/// Describes how the [`Server`] issues and validates the [`ResumptionTicket`]s, which let a client restore its session after a crash or a network blip.
+/// The ticket only proves that the client owns the session, its new address is still validated with a round trip before the session moves there.
+///
+#[derive(Clone)]
+pub struct ResumptionConfig {
+    /// The secret the tickets are authenticated with.
+    /// Servers sharing their addresses (for example the restarted instances of a server) should share the secret too, so the sessions survive the restart.
+    pub secret: [u8; 32],
+
+    /// The time a ticket is valid for after it was issued.
+    /// The server issues a new ticket once half of the lifetime of the previous one has passed.
+    pub lifetime: Duration,
+}
+
+impl std::fmt::Debug for ResumptionConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResumptionConfig")
+            .field("lifetime", &self.lifetime)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ResumptionConfig {
+    /// Creates a [`ResumptionConfig`] with a random secret, and a lifetime of an hour.
+    fn default() -> Self {
+        let mut secret = [0; 32];
+
+        secret[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+        secret[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+
+        Self {
+            secret,
+            lifetime: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl ResumptionConfig {
+    /// Issues a [`ResumptionTicket`] for the `author`, valid from now on.
+    pub fn issue(&self, author: Uuid) -> ResumptionTicket {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut mac = [0; 16];
+        mac.copy_from_slice(&self.mac(author, issued_at).finalize().into_bytes()[..16]);
+
+        ResumptionTicket::new(issued_at, mac)
+    }
+
+    /// Returns whether the [`ResumptionTicket`] was issued for the `author` with this secret, and hasn't expired yet.
+    pub fn validate(&self, ticket: &ResumptionTicket, author: Uuid) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let is_fresh =
+            ticket.issued_at() <= now && now - ticket.issued_at() <= self.lifetime.as_secs();
+
+        is_fresh
+            && self
+                .mac(author, ticket.issued_at())
+                .verify_truncated_left(&ticket.mac())
+                .is_ok()
+    }
+
+    /// Creates the message authentication code of the `author` and the issue time.
+    fn mac(&self, author: Uuid, issued_at: u64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("Hmac accepts keys of any length");
+
+        mac.update(author.as_bytes());
+        mac.update(&issued_at.to_be_bytes());
+
+        mac
+    }
+}
+
+/// The policy of the [`Server`] for the authors joining from a second address, while their session at the first address is alive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateLoginPolicy {
+    /// The session moves to the new address, keeping its state and its statistics (for example after the NAT binding of the client has changed).
+    /// The author alone can be spoofed, so the new address has to prove that it owns the session by presenting its [`ConnectionId`] (or a [`ResumptionTicket`]), then echo the [`ControlMessage::Retry`] sent to it before the session moves there.
+    /// The addresses joining as the author without a proof are rejected like with [`DuplicateLoginPolicy::Reject`], unless the session hasn't sent a heartbeat for the [`STALE_SESSION_TIMEOUT`].
+    /// A stale session is removed like with [`DuplicateLoginPolicy::Replace`], so a restarted client (which has lost its [`ConnectionId`]) can rejoin from its new port.
+    #[default]
+    Migrate,
+
+    /// The new address is sent a [`ControlMessage::Close`] with [`CloseCode::DuplicateSession`], and the session at the first address is kept.
+    /// The clients whose address has changed can't continue their session until their previous one has left.
+    Reject,
+
+    /// The session at the first address is sent a [`ControlMessage::Close`] with [`CloseCode::Replaced`] and removed, then the new address joins as a new session.
+    Replace,
+
+    /// Every address joins as a separate device of the author, with the lowest id not taken by its other devices.
+    /// The devices are signaled to each other with [`ControlMessage::Device`], and the other peers are only sent a [`ControlMessage::ParticipantLeft`] once the last device of the author has left.
+    /// The clients tag their voice with the device id they were assigned, so the receivers keep the streams of the devices apart and mix them together.
+    /// A single device can be targeted by the moderation (for example kicked or placed on hold) through its address, see [`ServerHandle::device_addr`].
+    MultiDevice,
+}
+
+/// The author of the messages the [`Server`] creates itself (for example the heartbeat replies).
+pub const SERVER_AUTHOR: Uuid = Uuid::nil();
+
+/// The time a session may go without a heartbeat, before an address joining as its author without a proof takes it over (see [`DuplicateLoginPolicy::Migrate`]).
+/// This is three times the default heartbeat interval of the clients.
+pub const STALE_SESSION_TIMEOUT: Duration = Duration::from_secs(15);
+
+///
+/// Peer type definition.
+///
+/// Contains the liveness and quality information the [`Server`] knows about a peer.
+///
+#[derive(Debug, Clone)]
+pub struct Peer {
+    /// The [`Uuid`] the peer sends its messages with.
+    author: Uuid,
+
+    /// The time the last heartbeat was received from the peer.
+    last_seen: Instant,
+
+    /// The latest [`QualityReport`] received from the peer.
+    quality_report: Option<QualityReport>,
+
+    /// The highest bitrate (in bits per second) the peer can receive, if it has signaled one.
+    max_bitrate: Option<u32>,
+
+    /// The time the last message of any kind was received from the peer.
+    last_packet: Instant,
+
+    /// The amount of messages received from the peer.
+    packets_received: u64,
+
+    /// The amount of messages relayed to the peer.
+    packets_sent: u64,
+
+    /// The loss of the voice messages received from the peer, estimated from their sequence numbers.
+    loss: LossEstimator,
+
+    /// The channel (or room) of the latest voice, video or text message received from the peer.
+    room: u32,
+
+    /// The amount of messages received from the peer with the ECN-CE codepoint.
+    packets_ce: u64,
+
+    /// The amount of messages to the peer dropped by the [`EgressLimits`], after waiting too long.
+    packets_rate_limited: u64,
+
+    /// The counters at the time of the last [`QualityReport`] sent about the peer to the moderators.
+    reported: ReportMark,
+
+    /// The counters at the time of the last [`QualityReport`] sent back to the peer as congestion feedback.
+    fed_back: ReportMark,
+
+    /// The media forwarded from the peer in the current window of the [`BandwidthLimits`].
+    bandwidth: BandwidthMeter,
+
+    /// The media sent by the peer in the current window of the [`RoomPolicy`] bitrate caps.
+    policy: PolicyMeter,
+
+    /// The simulcast layers the peer has selected, and the layers it is being forwarded.
+    layer_routing: LayerRouting,
+
+    /// The presence state the peer has set.
+    presence: PresenceState,
+
+    /// The latest media state the peer has signaled in every room, the default states aren't stored.
+    media_states: HashMap<u32, MediaState>,
+
+    /// The recorded rooms the peer has consented to the recording of.
+    recording_consents: HashSet<u32>,
+
+    /// The audio codecs the peer has advertised, besides [`MediaCodec::Opus`].
+    codecs: Option<Vec<MediaCodec>>,
+
+    /// Whether the peer was placed on hold.
+    on_hold: bool,
+
+    /// The time the pending [`ControlMessage::KeepaliveProbe`] of the peer is answered at, and its sequence number.
+    keepalive_probe: Option<(Instant, u32)>,
+
+    /// Whether the peer has paused receiving video with a [`ControlMessage::PauseVideo`].
+    video_paused: bool,
+
+    /// The time the latest [`ResumptionTicket`] was issued to the peer at.
+    resumption_issued_at: Option<Instant>,
+
+    /// The new address the session of the peer moves to once it has echoed the [`RetryToken`] sent to it, see [`migration_challenge`].
+    pending_migration: Option<(SocketAddr, RetryToken)>,
+
+    /// The id of the peer among the devices of its author, see [`DuplicateLoginPolicy::MultiDevice`].
+    device: u32,
+
+    /// The unguessable id issued to the session of the peer, which proves that a heartbeat from a new address belongs to the session.
+    connection_id: ConnectionId,
+}
+
+impl Peer {
+    /// Creates a new [`Peer`] instance, for a peer which has just sent its first heartbeat.
+    fn new(author: Uuid) -> Self {
+        let now = Instant::now();
+
+        Self {
+            author,
+            last_seen: now,
+            quality_report: None,
+            max_bitrate: None,
+            last_packet: now,
+            packets_received: 0,
+            packets_sent: 0,
+            loss: LossEstimator::default(),
+            room: 0,
+            packets_ce: 0,
+            packets_rate_limited: 0,
+            reported: ReportMark::default(),
+            fed_back: ReportMark::default(),
+            bandwidth: BandwidthMeter::new(now),
+            policy: PolicyMeter::new(now),
+            layer_routing: LayerRouting::default(),
+            presence: PresenceState::default(),
+            media_states: HashMap::new(),
+            recording_consents: HashSet::new(),
+            codecs: None,
+            on_hold: false,
+            keepalive_probe: None,
+            video_paused: false,
+            resumption_issued_at: None,
+            pending_migration: None,
+            device: 0,
+            connection_id: ConnectionId::new(*Uuid::new_v4().as_bytes()),
+        }
+    }
+
+    /// Restores a [`Peer`] instance from its [`HandoffPeer`], its statistics start over.
+    fn from_handoff(handoff_peer: HandoffPeer) -> Self {
+        let mut peer = Self::new(handoff_peer.author);
+
+        peer.room = handoff_peer.room;
+        peer.presence = handoff_peer.presence;
+        peer.media_states = handoff_peer.media_states;
+        peer.recording_consents = handoff_peer.recording_consents;
+        peer.codecs = handoff_peer.codecs;
+        peer.max_bitrate = handoff_peer.max_bitrate;
+        peer.on_hold = handoff_peer.on_hold;
+        peer.video_paused = handoff_peer.video_paused;
+        peer.device = handoff_peer.device;
+
+        if let Some(connection_id) = handoff_peer.connection_id {
+            peer.connection_id = connection_id;
+        }
+
+        for (author, layer_selection) in handoff_peer.layer_selections {
+            peer.layer_routing.select(author, layer_selection);
+        }
+
+        peer
+    }
+
+    /// Returns the [`HandoffPeer`] of the peer at the `remote_addr`.
+    fn to_handoff(&self, remote_addr: SocketAddr) -> HandoffPeer {
+        HandoffPeer {
+            remote_addr,
+            author: self.author,
+            room: self.room,
+            presence: self.presence,
+            media_states: self.media_states.clone(),
+            recording_consents: self.recording_consents.clone(),
+            codecs: self.codecs.clone(),
+            max_bitrate: self.max_bitrate,
+            layer_selections: self.layer_routing.selections().clone(),
+            on_hold: self.on_hold,
+            video_paused: self.video_paused,
+            device: self.device,
+            connection_id: Some(self.connection_id),
+        }
+    }
+
+    /// Returns the [`Uuid`] the peer sends its messages with.
+    pub fn author(&self) -> Uuid {
+        self.author
+    }
+
+    /// Returns the id of the peer among the devices of its author, which is `0` unless the [`ServerConfig::duplicate_login`] is [`DuplicateLoginPolicy::MultiDevice`].
+    pub fn device(&self) -> u32 {
+        self.device
+    }
+
+    /// Returns the time the last heartbeat was received from the peer.
+    pub fn last_seen(&self) -> Instant {
+        self.last_seen
+    }
+
+    /// Returns the latest [`QualityReport`] received from the peer.
+    pub fn quality_report(&self) -> Option<QualityReport> {
+        self.quality_report
+    }
+
+    /// Returns the highest bitrate (in bits per second) the peer can receive, if it has signaled one.
+    pub fn max_bitrate(&self) -> Option<u32> {
+        self.max_bitrate
+    }
+
+    /// Returns the channel (or room) of the latest voice, video or text message received from the peer, `0` if it hasn't sent any yet.
+    pub fn room(&self) -> u32 {
+        self.room
+    }
+
+    /// Returns the presence state the peer has set, [`PresenceState::Available`] if it hasn't set any.
+    pub fn presence(&self) -> PresenceState {
+        self.presence
+    }
+
+    /// Returns the latest media state the peer has signaled in the `room`, the default state if it hasn't signaled any.
+    pub fn media_state(&self, room: u32) -> MediaState {
+        self.media_states.get(&room).copied().unwrap_or_default()
+    }
+
+    /// Returns whether the peer has consented to the recording of the `room`, which is only possible while the room is recorded.
+    pub fn has_consented_to_recording(&self, room: u32) -> bool {
+        self.recording_consents.contains(&room)
+    }
+
+    /// Returns the audio codecs the peer can decode, which is only [`MediaCodec::Opus`] if it hasn't advertised any.
+    pub fn codecs(&self) -> Vec<MediaCodec> {
+        let mut codecs = vec![MediaCodec::Opus];
+
+        codecs.extend(self.codecs.iter().flatten().filter(|codec| **codec != MediaCodec::Opus));
+
+        codecs
+    }
+
+    /// Returns whether the peer was placed on hold (see [`ServerHandle::hold`]).
+    pub fn is_on_hold(&self) -> bool {
+        self.on_hold
+    }
+
+    /// Returns whether the peer has paused receiving video (see [`ControlMessage::PauseVideo`]).
+    pub fn is_video_paused(&self) -> bool {
+        self.video_paused
+    }
+
+    /// Returns the time the last message of any kind was received from the peer.
+    pub fn last_packet(&self) -> Instant {
+        self.last_packet
+    }
+
+    /// Returns the amount of messages received from the peer since it has joined.
+    pub fn packets_received(&self) -> u64 {
+        self.packets_received
+    }
+
+    /// Returns the amount of messages relayed to the peer since it has joined.
+    pub fn packets_sent(&self) -> u64 {
+        self.packets_sent
+    }
+
+    /// Returns the amount of messages received from the peer with the ECN-CE (congestion experienced) codepoint since it has joined.
+    pub fn packets_ce(&self) -> u64 {
+        self.packets_ce
+    }
+
+    /// Returns the estimated loss (between `0` and `1`) of the voice messages received from the peer since it has joined.
+    /// The loss is estimated from the gaps in the sequence numbers of the voice messages.
+    pub fn estimated_loss(&self) -> f32 {
+        self.loss.loss_since(&LossEstimator::default())
+    }
+
+    /// Returns the statistics of the peer.
+    pub fn stats(&self) -> PeerStats {
+        PeerStats {
+            author: self.author,
+            last_seen: self.last_seen,
+            last_packet: self.last_packet,
+            packets_received: self.packets_received,
+            packets_sent: self.packets_sent,
+            estimated_loss: self.estimated_loss(),
+            packets_ce: self.packets_ce,
+            packets_rate_limited: self.packets_rate_limited,
+        }
+    }
+
+    /// Creates a [`QualityReport`] of the messages received from the peer since the last report sent to the moderators.
+    fn take_report(&mut self) -> QualityReport {
+        let quality_report = self.report_since(&self.reported);
+
+        self.reported = self.mark();
+
+        quality_report
+    }
+
+    /// Creates a [`QualityReport`] of the messages received from the peer since the last congestion feedback sent to it.
+    fn take_feedback(&mut self) -> QualityReport {
+        let quality_report = self.report_since(&self.fed_back);
+
+        self.fed_back = self.mark();
+
+        quality_report
+    }
+
+    /// Creates a [`QualityReport`] of the messages received from the peer since the `earlier` [`ReportMark`].
+    fn report_since(&self, earlier: &ReportMark) -> QualityReport {
+        QualityReport {
+            packets_received: self.packets_received - earlier.packets_received,
+            packets_lost: self.loss.lost_since(&earlier.loss),
+            jitter_ms: 0,
+            round_trip_time_ms: 0,
+            packets_ce: self.packets_ce - earlier.packets_ce,
+        }
+    }
+
+    /// Returns the current counters of the peer, which the next report is measured from.
+    fn mark(&self) -> ReportMark {
+        ReportMark {
+            packets_received: self.packets_received,
+            loss: self.loss.clone(),
+            packets_ce: self.packets_ce,
+        }
+    }
+}
+
+/// The counters of a [`Peer`] at the time of a [`QualityReport`], the next report describes the messages received since.
+#[derive(Debug, Clone, Default)]
+struct ReportMark {
+    /// The amount of messages received.
+    packets_received: u64,
+
+    /// The loss estimation.
+    loss: LossEstimator,
+
+    /// The amount of messages received with the ECN-CE codepoint.
+    packets_ce: u64,
+}
+
+/// Estimates the loss of a stream from the gaps in its sequence numbers.
+#[derive(Debug, Clone, Default)]
+struct LossEstimator {
+    /// The highest sequence number received.
+    highest_sequence: Option<u32>,
+
+    /// The amount of messages expected, from the first and the highest sequence number.
+    expected: u64,
+
+    /// The amount of messages received with a sequence number.
+    received: u64,
+}
+
+impl LossEstimator {
+    /// Records a message received with the `sequence` number.
+    fn observe(&mut self, sequence: u32) {
+        match self.highest_sequence {
+            Some(highest_sequence) => {
+                //The sequence numbers wrap around, so compare them by their distance
+                let distance = sequence.wrapping_sub(highest_sequence) as i32;
+
+                if distance > 0 {
+                    self.expected += distance as u64;
+                    self.highest_sequence = Some(sequence);
+                }
+            }
+            None => {
+                self.expected = 1;
+                self.highest_sequence = Some(sequence);
+            }
+        }
+
+        self.received += 1;
+    }
+
+    /// Returns the amount of messages lost since the `earlier` state of the estimator.
+    fn lost_since(&self, earlier: &Self) -> u64 {
+        (self.expected - earlier.expected).saturating_sub(self.received - earlier.received)
+    }
+
+    /// Returns the ratio of the messages lost since the `earlier` state of the estimator.
+    fn loss_since(&self, earlier: &Self) -> f32 {
+        match self.expected - earlier.expected {
+            0 => 0.,
+            expected => self.lost_since(earlier) as f32 / expected as f32,
+        }
+    }
+}
+
+///
+/// Peer statistics type definition.
+///
+/// A snapshot of the liveness and relay counters of a peer.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeerStats {
+    /// The [`Uuid`] the peer sends its messages with.
+    pub author: Uuid,
+
+    /// The time the last heartbeat was received from the peer.
+    pub last_seen: Instant,
+
+    /// The time the last message of any kind was received from the peer.
+    pub last_packet: Instant,
+
+    /// The amount of messages received from the peer since it has joined.
+    pub packets_received: u64,
+
+    /// The amount of messages relayed to the peer since it has joined.
+    pub packets_sent: u64,
+
+    /// The estimated loss (between `0` and `1`) of the voice messages received from the peer since it has joined.
+    pub estimated_loss: f32,
+
+    /// The amount of messages received from the peer with the ECN-CE (congestion experienced) codepoint since it has joined.
+    pub packets_ce: u64,
+
+    /// The amount of messages to the peer dropped by the [`ServerConfig::egress_limits`] since it has joined, as they have waited too long to be sent.
+    pub packets_rate_limited: u64,
+}
+
+///
+/// Server statistics type definition.
+///
+/// A snapshot of the statistics of the [`Server`], returned by [`Server::stats`].
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerStats {
+    /// The statistics of every peer, by their addresses.
+    pub peers: HashMap<SocketAddr, PeerStats>,
+
+    /// The amount of malformed messages the server has discarded since it was created.
+    pub malformed_packets: u64,
+
+    /// The amount of messages the server has dropped since it was created, by the [`DropReason`] they were dropped for.
+    pub drops: DropStats,
+}
+
+/// Peer registry type definition.
+/// Maps the address of every peer to the information the [`Server`] knows about it.
+pub type PeerRegistry = Arc<DashMap<SocketAddr, Peer>>;
+
+#[derive(Debug, Default, Clone)]
+/// Client list type definition.
+pub struct ClientList(Arc<DashSet<SocketAddr>>);
+
+/// Implement [`Deref`] for the Wrapper type, so that it automaticly 'inherits' the traits and functions of the wrapped type.
+impl Deref for ClientList {
+    type Target = Arc<DashSet<SocketAddr>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Implement [`DerefMut`] for the Wrapper type, so that it automaticly 'inherits' the mutable traits and functions of the wrapped type.
+impl DerefMut for ClientList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Server {
+    /// Creates a new [`Server`] instance, and bind to the local IPV6 address with the given port.
+    pub async fn new(port: u32) -> std::result::Result<Self, ServerError> {
+        let socket_handle = UdpSocket::bind(format!("[::]:{port}"))
+            .await
+            .map_err(ServerError::SocketIo)?;
+
+        Self::new_from_transport(socket_handle).await
+    }
+
+    ///
+    /// Creates a new [`Server`] instance, serving on the socket passed by the service manager (see the [`activation`](super::activation) module).
+    ///
+    /// # Behavior
+    /// The first socket named `name` is served if a name is given (see [`activated_sockets`]), the first passed socket otherwise.
+    ///
+    /// # Error
+    /// Returns [`ServerError::SocketIo`] if the process wasn't passed a datagram socket.
+    ///
+    #[cfg(unix)]
+    pub async fn new_activated(name: Option<&str>) -> std::result::Result<Self, ServerError> {
+        let socket_handle = activated_sockets(name)
+            .map_err(ServerError::SocketIo)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                ServerError::SocketIo(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "No socket was passed by the service manager.",
+                ))
+            })?;
+
+        Self::new_from_transport(socket_handle).await
+    }
+
+    /// Creates a new [`Server`] instance from any already bound [`Transport`].
+    /// The server service is spawned on the [`Tokio`] runtime.
+    pub async fn new_from_transport<T: Transport>(
+        socket_handle: T,
+    ) -> std::result::Result<Self, ServerError> {
+        Self::new_from_transport_with_runtime::<Tokio, T>(socket_handle).await
+    }
+
+    /// Creates a new [`Server`] instance from any already bound [`Transport`].
+    /// The server service is spawned on the [`Runtime`] `R`.
+    pub async fn new_from_transport_with_runtime<R: Runtime, T: Transport>(
+        socket_handle: T,
+    ) -> std::result::Result<Self, ServerError> {
+        Self::new_from_transport_with_config::<R, T>(socket_handle, ServerConfig::default()).await
+    }
+
+    /// Creates a new [`Server`] instance from any already bound [`Transport`].
+    /// The server service is spawned on the [`Runtime`] `R`, and configured with the [`ServerConfig`].
+    pub async fn new_from_transport_with_config<R: Runtime, T: Transport>(
+        socket_handle: T,
+        config: ServerConfig,
+    ) -> std::result::Result<Self, ServerError> {
+        let local_addr = socket_handle.local_addr().map_err(ServerError::SocketIo)?;
+        let (outbound_message_sender, mut outbound_message_receiver) = channel::<VoipPacket>(255);
+        let (inbound_message_sender, inbound_message_receiver) =
+            channel::<(VoipHeader, Vec<u8>, SocketAddr)>(255);
+        let (request_sender, mut request_receiver) = channel::<ServiceRequest>(255);
+        let (event_sender, _) = broadcast::channel::<ServerEvent>(255);
+        let cancellation_token = CancellationToken::new();
+        let client_list = ClientList::default();
+        let peers = PeerRegistry::default();
+        //Restore the state persisted by the previous runs of the server
+        #[cfg(feature = "persistence")]
+        let (stored_room_policies, stored_bans) = match &config.store {
+            Some(store) => (
+                store.rooms().map_err(ServerError::Store)?,
+                store.bans().map_err(ServerError::Store)?,
+            ),
+            None => Default::default(),
+        };
+        #[cfg(not(feature = "persistence"))]
+        let (stored_room_policies, stored_bans) = (HashMap::new(), HashMap::new());
+
+        //Restore the sessions handed over by the server this one replaces, the clients are unaware of the handoff
+        let handoff = config.handoff.unwrap_or_default();
+
+        for remote_addr in handoff.reply_list {
+            client_list.insert(remote_addr);
+        }
+
+        for handoff_peer in handoff.peers {
+            peers.insert(handoff_peer.remote_addr, Peer::from_handoff(handoff_peer));
+        }
+
+        let room_policies: RoomPolicies = Arc::new(
+            config
+                .room_policies
+                .into_iter()
+                .chain(stored_room_policies)
+                .chain(handoff.room_policies)
+                .collect(),
+        );
+        let recordings: Recordings = Arc::new(handoff.recordings.into_iter().collect());
+        let bans: BanList = Arc::new(
+            config
+                .bans
+                .into_iter()
+                .chain(stored_bans)
+                .chain(handoff.bans)
+                .collect(),
+        );
+        let stats_report = config.stats_report;
+        let congestion_feedback = config.congestion_feedback;
+        let drop_counters = DropCounters::default();
+
+        //Join the cluster before serving, so the local peers are shared from their first heartbeat
+        #[cfg(feature = "cluster")]
+        let (cluster_link, mut cluster_receiver) = match config.cluster {
+            Some(cluster_config) => {
+                let (cluster_link, cluster_receiver) =
+                    cluster::connect::<R>(cluster_config, event_sender.subscribe())
+                        .await
+                        .map_err(ServerError::Cluster)?;
+
+                (Some(cluster_link), Some(cluster_receiver))
+            }
+            None => (None, None),
+        };
+        #[cfg(not(feature = "cluster"))]
+        let mut cluster_receiver: Option<Receiver<(u32, VoipPacket)>> = None;
+
+        //Mark the datagrams as ECN capable, so that the congested routers mark them instead of dropping them
+        if congestion_feedback.is_some() {
+            if let Err(err) = socket_handle.set_ecn(Ecn::Ect0) {
+                event!(Level::WARN, "Failed to enable ECN on the transport, the congestion feedback only reports the losses: {err}");
+            }
+        }
+
+        //Mark the datagrams for the networks prioritizing the voice
+        if let Some(dscp) = config.dscp {
+            if let Err(err) = socket_handle.set_traffic_class(dscp) {
+                event!(Level::WARN, "Failed to set the traffic class of the transport: {err}");
+            }
+        }
+
+        if let Some(hop_limit) = config.hop_limit {
+            if let Err(err) = socket_handle.set_hop_limit(hop_limit) {
+                event!(Level::WARN, "Failed to set the hop limit of the transport: {err}");
+            }
+        }
+
+        //Read the errors the network reports about the sent datagrams, so that the unreachable peers aren't silently blackholed
+        let path_errors = config.path_errors && match socket_handle.enable_path_errors() {
+            Ok(()) => true,
+            Err(err) => {
+                event!(Level::WARN, "Failed to enable the path errors of the transport: {err}");
+
+                false
+            },
+        };
+
+        //Limit the replies to the addresses which haven't joined yet
+        let socket_handle =
+            AmplificationGuard::new(socket_handle, peers.clone(), config.amplification_limit);
+
+        //The clients handed over on hold keep hearing the hold music
+        let held_clients: HashMap<SocketAddr, HoldPlayback> = peers
+            .iter()
+            .filter(|peer| peer.on_hold)
+            .map(|peer| (*peer.key(), HoldPlayback::default()))
+            .collect();
+
+        let mut server_state = ServerState {
+            socket_handle,
+            client_list: client_list.clone(),
+            peers: peers.clone(),
+            room_policies: room_policies.clone(),
+            recordings: recordings.clone(),
+            bans: bans.clone(),
+            event_sender: event_sender.clone(),
+            inbound_message_sender,
+            cancellation_token: cancellation_token.clone(),
+            silence_threshold: config.silence_threshold,
+            retry: config.retry,
+            resumption: config.resumption,
+            duplicate_login: config.duplicate_login,
+            source_filter: config.source_filter,
+            bandwidth_limits: config.bandwidth_limits,
+            egress_scheduler: config
+                .egress_limits
+                .is_enabled()
+                .then(|| EgressScheduler::new(config.egress_limits, Instant::now())),
+            floor_control: config.floor_control.map(FloorControl::new),
+            hold_music: config.hold_music,
+            load_shedder: config
+                .load_shedding
+                .map(|load_shedding| LoadShedder::new(load_shedding, Instant::now())),
+            drop_log: DropLog::new(drop_counters.clone(), config.drop_log),
+            #[cfg(feature = "cluster")]
+            cluster_link,
+            #[cfg(feature = "transcode")]
+            transcoder: config.transcoding.map(Transcoder::new),
+            room_meters: HashMap::new(),
+            active_layers: ActiveLayers::default(),
+            held_clients,
+            next_hold_frame: Instant::now(),
+            next_keepalive_probe: None,
+        };
+
+        R::spawn(async move {
+            //Create buffer for reading incoming messages, the length prefix is not included in the MTU
+            let mut buf = vec![0; MTU_MAX_PACKET_SIZE + LENGTH_PREFIX_SIZE];
+
+            let mut next_stats_report = stats_report
+                .as_ref()
+                .map(|stats_report| Instant::now() + stats_report.interval);
+
+            let mut next_congestion_feedback = congestion_feedback.map(|interval| Instant::now() + interval);
+
+            loop {
+                select! {
+                    //Await receving said amounts of bytes
+                    incoming_bytes = server_state.socket_handle.recv_datagram_ecn(&mut buf) => {
+                        match incoming_bytes {
+                            Ok((byte_count, socket_addr, ecn)) => {
+                                //If the receiver was dropped the server was shut down
+                                if !server_state.handle_datagram(&buf[..byte_count], socket_addr, ecn).await {
+                                    break;
+                                }
+                            },
+                            //The error is reported again as a path error from the error queue
+                            Err(err) if path_errors && path::is_path_error(&err) => (),
+                            Err(err) => {
+                                event!(Level::ERROR, "Failed to receive message: {err}");
+                            },
+                        }
+                    }
+
+                    //Report the errors the network has reported about the sent datagrams
+                    path_error = server_state.socket_handle.recv_path_error(), if path_errors => {
+                        match path_error {
+                            Ok(path_error) => {
+                                let author = server_state.peers.get(&path_error.destination).map(|peer| peer.author);
+
+                                let _ = server_state.event_sender.send(ServerEvent::PathError { remote_addr: path_error.destination, author, reporter: path_error.reporter, kind: path_error.kind });
+                            },
+                            Err(err) => {
+                                event!(Level::ERROR, "Failed to receive path error: {err}");
+                            },
+                        }
+                    }
+
+                    //Await outbound channel request
+                    Some(outgoing_message) = outbound_message_receiver.recv() => {
+                        server_state.fan_out(outgoing_message).await;
+                    }
+
+                    //Await the messages relayed by the other nodes of the cluster
+                    Some((room, remote_message)) = recv_optional(&mut cluster_receiver) => {
+                        server_state.relay_remote_message(room, remote_message).await;
+                    }
+
+                    //Send the messages queued by the outbound rate limits, once the buckets have refilled
+                    _ = R::sleep(server_state.egress_scheduler.as_ref().and_then(EgressScheduler::next_release).unwrap_or_else(Instant::now).saturating_duration_since(Instant::now())), if server_state.egress_scheduler.as_ref().is_some_and(|egress_scheduler| egress_scheduler.queued() > 0) => {
+                        server_state.release_queued_messages().await;
+                    }
+
+                    //Play the next frame of the hold music to the clients on hold
+                    _ = R::sleep(server_state.next_hold_frame.saturating_duration_since(Instant::now())), if !server_state.held_clients.is_empty() && server_state.hold_music.is_some() => {
+                        server_state.play_hold_music().await;
+                    }
+
+                    //Report the statistics of every peer to the moderators
+                    _ = R::sleep(next_stats_report.unwrap_or_else(Instant::now).saturating_duration_since(Instant::now())), if next_stats_report.is_some() => {
+                        let Some(stats_report) = stats_report.as_ref() else {
+                            continue;
+                        };
+
+                        next_stats_report = Some(Instant::now() + stats_report.interval);
+
+                        send_stats_reports(&server_state.socket_handle, &server_state.peers, &stats_report.moderators).await;
+                    }
+
+                    //Send the congestion feedback to every peer
+                    _ = R::sleep(next_congestion_feedback.unwrap_or_else(Instant::now).saturating_duration_since(Instant::now())), if next_congestion_feedback.is_some() => {
+                        let Some(interval) = congestion_feedback else {
+                            continue;
+                        };
+
+                        next_congestion_feedback = Some(Instant::now() + interval);
+
+                        send_congestion_feedback(&server_state.socket_handle, &server_state.peers).await;
+                    }
+
+                    //Answer the keepalive probes whose delay has passed
+                    _ = R::sleep(server_state.next_keepalive_probe.unwrap_or_else(Instant::now).saturating_duration_since(Instant::now())), if server_state.next_keepalive_probe.is_some() => {
+                        send_keepalive_answers(&server_state.socket_handle, &server_state.peers).await;
+
+                        server_state.next_keepalive_probe = earliest_keepalive_probe(&server_state.peers);
+                    }
+
+                    //Evaluate the load of the server, and shed the media accordingly
+                    _ = R::sleep(server_state.load_shedder.as_ref().map_or_else(Instant::now, LoadShedder::next_evaluation).saturating_duration_since(Instant::now())), if server_state.load_shedder.is_some() => {
+                        server_state.evaluate_load(outbound_message_receiver.len());
+                    }
+
+                    //Await the requests of the user
+                    Some(service_request) = request_receiver.recv() => {
+                        if !server_state.handle_service_request(service_request).await {
+                            break;
+                        }
+                    }
+
+                    //Await thread cancellation
+                    _ = server_state.cancellation_token.cancelled() => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            connected_clients: client_list,
+            handle: ServerHandle {
+                peers,
+                request_sender,
+                room_policies,
+                recordings,
+                bans,
+                event_sender,
+                drops: drop_counters,
+                #[cfg(feature = "persistence")]
+                store: config.store,
+            },
+            inbound_message_receiver,
+            cancellation_token,
+            outbound_message_sender,
+            local_addr,
+        })
+    }
+
+    /// Returns the local address the server is bound to, including the port the operating system has chosen if it was bound to port `0`.
+    /// The address is unspecified (for example `[::]`) if the server was bound to every interface, so it can't be advertised to the clients as is.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Gets the incoming message receiver ([`Receiver<VoipPacket>`]) handle.
+    /// This is created at the instance creation of [`Server`].
+    /// The server listener threads has ownership of the sender, and sends every incoming message to the receiver.
+    pub fn message_receiver(&mut self) -> &mut Receiver<(VoipHeader, Vec<u8>, SocketAddr)> {
+        &mut self.inbound_message_receiver
+    }
+
+    /// Server thread cancellation token ([`CancellationToken`]) for shutting down the server.
+    /// This can be cancelled in a sync environment.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    /// This gets the list of [`SocketAddr`]s which the UdpSocket should reply to.
+    pub fn get_reply_to_list_mut(&self) -> Arc<DashSet<SocketAddr>> {
+        self.connected_clients.0.clone()
+    }
+
+    /// Returns a [`ServerHandle`], which can be shared with the tasks managing the server (for example a control plane).
+    pub fn handle(&self) -> ServerHandle {
+        self.handle.clone()
+    }
+
+    /// This gets the registry of the peers which have sent a heartbeat to the server.
+    pub fn peers(&self) -> PeerRegistry {
+        self.handle.peers()
+    }
+
+    /// Returns a snapshot of the statistics of every peer (see [`PeerStats`]).
+    pub fn stats(&self) -> ServerStats {
+        self.handle.stats()
+    }
+
+    /// Closes the session of the client at the `remote_addr`, by sending it a [`ControlMessage::Close`] with the [`CloseReason`] (for example when kicking or banning it).
+    /// The client is removed from the reply list and the peer registry.
+    pub async fn close_client(
+        &self,
+        remote_addr: SocketAddr,
+        close_reason: CloseReason,
+    ) -> std::result::Result<(), ServerError> {
+        self.handle.close_client(remote_addr, close_reason).await
+    }
+
+    /// Returns the address of the `device` of the `author` (see [`ServerHandle::device_addr`]).
+    pub fn device_addr(&self, author: Uuid, device: u32) -> Option<SocketAddr> {
+        self.handle.device_addr(author, device)
+    }
+
+    /// Places the client at the `remote_addr` on hold (see [`ServerHandle::hold`]).
+    pub async fn hold(&self, remote_addr: SocketAddr) -> std::result::Result<(), ServerError> {
+        self.handle.hold(remote_addr).await
+    }
+
+    /// Retrieves the client at the `remote_addr` from hold (see [`ServerHandle::retrieve`]).
+    pub async fn retrieve(&self, remote_addr: SocketAddr) -> std::result::Result<(), ServerError> {
+        self.handle.retrieve(remote_addr).await
+    }
+
+    /// Creates a room on the `room` channel with the [`RoomPolicy`] (or updates the policy of an existing room).
+    /// The policy is advertised to every peer right away, and to every peer joining later.
+    pub async fn create_room(
+        &self,
+        room: u32,
+        room_policy: RoomPolicy,
+    ) -> std::result::Result<(), ServerError> {
+        self.handle.create_room(room, room_policy).await
+    }
+
+    /// Destroys the room on the `room` channel, the peers are advertised a [`RoomPolicy`] without any restrictions in its place.
+    /// Nothing happens if the room doesn't exist.
+    pub async fn destroy_room(&self, room: u32) -> std::result::Result<(), ServerError> {
+        self.handle.destroy_room(room).await
+    }
+
+    /// Returns the [`RoomPolicy`] of every room.
+    pub fn room_policies(&self) -> RoomPolicies {
+        self.handle.room_policies()
+    }
+
+    /// Bans the `author` from the server, closing its sessions with a [`CloseCode::Banned`] [`CloseReason`] (see [`ServerHandle::ban`]).
+    pub async fn ban(
+        &self,
+        author: Uuid,
+        reason: Option<String>,
+    ) -> std::result::Result<(), ServerError> {
+        self.handle.ban(author, reason).await
+    }
+
+    /// Lifts the ban of the `author`, returning it if there was one.
+    pub fn unban(&self, author: Uuid) -> std::result::Result<Option<Ban>, ServerError> {
+        self.handle.unban(author)
+    }
+
+    /// Returns the [`Ban`] of every banned author.
+    pub fn bans(&self) -> BanList {
+        self.handle.bans()
+    }
+
+    /// Subscribes to the [`ServerEvent`]s of the server service.
+    /// Only the events happening after the subscription are received, and the oldest events are skipped if the receiver lags behind.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ServerEvent> {
+        self.handle.subscribe_events()
+    }
+
+    /// Closes the session of every client with the [`CloseReason`], then shuts down the server service.
+    /// Unlike cancelling the [`CancellationToken`], this notifies the clients.
+    pub async fn shutdown(
+        &self,
+        close_reason: CloseReason,
+    ) -> std::result::Result<(), ServerError> {
+        self.handle.shutdown(close_reason).await
+    }
+
+    /// Shuts down the server service without notifying the clients, and returns the state of their sessions to be handed over to a new server (see [`ServerHandle::handoff`]).
+    pub async fn handoff(&self) -> std::result::Result<SessionHandoff, ServerError> {
+        self.handle.handoff().await
+    }
+
+    /// Returns a handle to the channel [`Server::reply_to_clients`] sends through.
+    #[cfg(feature = "client")]
+    pub(crate) fn reply_sender(&self) -> Sender<VoipPacket> {
+        self.outbound_message_sender.clone()
+    }
+
+    /// Replies to all of the [`SocketAddr`]-es specified in `self.connected_clients` through the [`UdpSocket`] the server is bound to.
+    /// Sends the [`VoipPacket`] through a channel, which the server async thread is awaiting.
+    /// Returns [`ServerError::ChannelClosed`] if the server service has already shut down.
+    pub async fn reply_to_clients(
+        &self,
+        voip_packet: VoipPacket,
+    ) -> std::result::Result<(), ServerError> {
+        self.outbound_message_sender
+            .send(voip_packet)
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+}
+
+///
+/// Server handle type definition.
+///
+/// A cloneable handle to the management functions of a [`Server`], which can be shared between tasks.
+/// The handle doesn't keep the server service alive.
+///
+#[derive(Debug, Clone)]
+pub struct ServerHandle {
+    /// The registry of the peers which have sent a heartbeat to the server.
+    peers: PeerRegistry,
+
+    /// This local channel receives the requests the server service should handle (for example closing sessions).
+    request_sender: Sender<ServiceRequest>,
+
+    /// The [`RoomPolicy`] of every channel (or room), shared with the server service.
+    room_policies: RoomPolicies,
+
+    /// The [`RecordingState`] of every recorded channel (or room), shared with the server service.
+    recordings: Recordings,
+
+    /// The [`Ban`] of every banned author, shared with the server service.
+    bans: BanList,
+
+    /// The [`ServerStore`] the changes are persisted in.
+    #[cfg(feature = "persistence")]
+    store: Option<ServerStore>,
+
+    /// This local channel broadcasts the [`ServerEvent`]s of the server service.
+    event_sender: broadcast::Sender<ServerEvent>,
+
+    /// The amount of messages the server service has dropped, by the [`DropReason`] they were dropped for.
+    drops: DropCounters,
+}
+
+impl ServerHandle {
+    /// This gets the registry of the peers which have sent a heartbeat to the server.
+    pub fn peers(&self) -> PeerRegistry {
+        self.peers.clone()
+    }
+
+    /// Returns a snapshot of the statistics of every peer (see [`PeerStats`]).
+    pub fn stats(&self) -> ServerStats {
+        let drops = self.drops.stats();
+
+        ServerStats {
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| (*peer.key(), peer.stats()))
+                .collect(),
+            malformed_packets: drops.get(DropReason::Oversized)
+                + drops.get(DropReason::Malformed),
+            drops,
+        }
+    }
+
+    /// Closes the session of the client at the `remote_addr`, by sending it a [`ControlMessage::Close`] with the [`CloseReason`] (for example when kicking or banning it).
+    /// The client is removed from the reply list and the peer registry.
+    /// If the [`CloseCode`] is [`CloseCode::Banned`], the author of the client is also added to the [`BanList`].
+    /// Returns [`ServerError::NotConnected`] if the client isn't in the peer registry.
+    pub async fn close_client(
+        &self,
+        remote_addr: SocketAddr,
+        close_reason: CloseReason,
+    ) -> std::result::Result<(), ServerError> {
+        let author = self
+            .peers
+            .get(&remote_addr)
+            .map(|peer| peer.author)
+            .ok_or(ServerError::NotConnected(remote_addr))?;
+
+        if close_reason.code() == CloseCode::Banned {
+            self.insert_ban(author, Ban::new(close_reason.message().map(String::from)))?;
+        }
+
+        self.request_sender
+            .send(ServiceRequest::Client(remote_addr, close_reason))
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+
+    /// Returns the address the `device` of the `author` is connected from, [`None`] if the author hasn't joined from that device.
+    /// The moderation actions taking an address (for example [`ServerHandle::close_client`] and [`ServerHandle::hold`]) target a single device of the author through it.
+    pub fn device_addr(&self, author: Uuid, device: u32) -> Option<SocketAddr> {
+        self.peers
+            .iter()
+            .find(|peer| peer.author == author && peer.device == device)
+            .map(|peer| *peer.key())
+    }
+
+    ///
+    /// Places the client at the `remote_addr` on hold.
+    ///
+    /// # Behavior
+    /// The media of the client isn't forwarded, and it isn't sent the media of the other clients until it is retrieved with [`ServerHandle::retrieve`].
+    /// It is sent the [`ServerConfig::hold_music`] in a loop instead, if there is one.
+    ///
+    /// # Error
+    /// Returns [`ServerError::NotConnected`] if the client isn't in the peer registry, or an error if the server service has already shut down.
+    ///
+    pub async fn hold(&self, remote_addr: SocketAddr) -> std::result::Result<(), ServerError> {
+        self.ensure_connected(remote_addr)?;
+
+        self.request_sender
+            .send(ServiceRequest::Hold(remote_addr, true))
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+
+    /// Retrieves the client at the `remote_addr` from hold, its media is forwarded and it is sent the media of the other clients again.
+    /// Returns [`ServerError::NotConnected`] if the client isn't in the peer registry.
+    pub async fn retrieve(&self, remote_addr: SocketAddr) -> std::result::Result<(), ServerError> {
+        self.ensure_connected(remote_addr)?;
+
+        self.request_sender
+            .send(ServiceRequest::Hold(remote_addr, false))
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+
+    /// Creates a room on the `room` channel with the [`RoomPolicy`] (or updates the policy of an existing room).
+    /// The policy is advertised to every peer right away, and to every peer joining later.
+    /// The media violating the codec, bitrate and video rules of the policy is rejected, see the [`policy`](super::policy) module.
+    pub async fn create_room(
+        &self,
+        room: u32,
+        room_policy: RoomPolicy,
+    ) -> std::result::Result<(), ServerError> {
+        #[cfg(feature = "persistence")]
+        if let Some(store) = &self.store {
+            store
+                .save_room(room, &room_policy)
+                .map_err(ServerError::Store)?;
+        }
+
+        self.request_sender
+            .send(ServiceRequest::CreateRoom(room, room_policy))
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+
+    /// Destroys the room on the `room` channel, the peers are advertised a [`RoomPolicy`] without any restrictions in its place.
+    /// Nothing happens if the room doesn't exist.
+    pub async fn destroy_room(&self, room: u32) -> std::result::Result<(), ServerError> {
+        #[cfg(feature = "persistence")]
+        if let Some(store) = &self.store {
+            store.remove_room(room).map_err(ServerError::Store)?;
+        }
+
+        self.request_sender
+            .send(ServiceRequest::DestroyRoom(room))
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+
+    /// Returns the [`RoomPolicy`] of every room.
+    pub fn room_policies(&self) -> RoomPolicies {
+        self.room_policies.clone()
+    }
+
+    ///
+    /// Bans the `author` from the server.
+    ///
+    /// # Behavior
+    /// Every session of the author is closed with a [`CloseCode::Banned`] [`CloseReason`] carrying the `reason`.
+    /// The later messages of the author are discarded, and its heartbeats are answered with the same [`CloseReason`].
+    ///
+    /// # Error
+    /// Returns an error if the ban could not be persisted, or the server service has already shut down.
+    ///
+    pub async fn ban(
+        &self,
+        author: Uuid,
+        reason: Option<String>,
+    ) -> std::result::Result<(), ServerError> {
+        self.insert_ban(author, Ban::new(reason.clone()))?;
+
+        let remote_addrs: Vec<SocketAddr> = self
+            .peers
+            .iter()
+            .filter(|peer| peer.author == author)
+            .map(|peer| *peer.key())
+            .collect();
+
+        for remote_addr in remote_addrs {
+            self.request_sender
+                .send(ServiceRequest::Client(
+                    remote_addr,
+                    CloseReason::new(CloseCode::Banned, reason.clone()),
+                ))
+                .await
+                .map_err(|_| ServerError::ChannelClosed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Lifts the ban of the `author`, returning it if there was one.
+    pub fn unban(&self, author: Uuid) -> std::result::Result<Option<Ban>, ServerError> {
+        #[cfg(feature = "persistence")]
+        if let Some(store) = &self.store {
+            store.remove_ban(author).map_err(ServerError::Store)?;
+        }
+
+        let ban = self.bans.remove(&author).map(|(_, ban)| ban);
+
+        if ban.is_some() {
+            let _ = self
+                .event_sender
+                .send(ServerEvent::AuthorUnbanned { author });
+        }
+
+        Ok(ban)
+    }
+
+    /// Returns the [`Ban`] of every banned author.
+    pub fn bans(&self) -> BanList {
+        self.bans.clone()
+    }
+
+    ///
+    /// Shuts down the server service without notifying the clients, and returns the state of their sessions.
+    ///
+    /// # Behavior
+    /// The sessions are meant to be restored by a new server bound to the same port (see the [`handoff`](super::handoff) module), the clients keep sending to the port as if nothing has happened.
+    /// The messages the application hasn't replied to the clients yet are discarded.
+    ///
+    /// # Error
+    /// Returns an error if the server service has already shut down.
+    ///
+    pub async fn handoff(&self) -> std::result::Result<SessionHandoff, ServerError> {
+        let (handoff_sender, handoff_receiver) = oneshot::channel();
+
+        self.request_sender
+            .send(ServiceRequest::Handoff(handoff_sender))
+            .await
+            .map_err(|_| ServerError::ChannelClosed)?;
+
+        handoff_receiver
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+
+    /// Returns the [`ServerStore`] the server persists its state in, for example to manage the invite tokens.
+    #[cfg(feature = "persistence")]
+    pub fn store(&self) -> Option<&ServerStore> {
+        self.store.as_ref()
+    }
+
+    /// Returns [`ServerError::NotConnected`] if the client at the `remote_addr` isn't in the peer registry.
+    fn ensure_connected(&self, remote_addr: SocketAddr) -> std::result::Result<(), ServerError> {
+        match self.peers.contains_key(&remote_addr) {
+            true => Ok(()),
+            false => Err(ServerError::NotConnected(remote_addr)),
+        }
+    }
+
+    /// Adds the [`Ban`] of the `author` to the [`BanList`], persists it and broadcasts [`ServerEvent::AuthorBanned`].
+    fn insert_ban(&self, author: Uuid, ban: Ban) -> std::result::Result<(), ServerError> {
+        #[cfg(feature = "persistence")]
+        if let Some(store) = &self.store {
+            store.save_ban(author, &ban).map_err(ServerError::Store)?;
+        }
+
+        let _ = self.event_sender.send(ServerEvent::AuthorBanned {
+            author,
+            reason: ban.reason.clone(),
+        });
+
+        self.bans.insert(author, ban);
+
+        Ok(())
+    }
+
+    ///
+    /// Signals that the application has started recording the `room`, as the server doesn't record the rooms itself.
+    /// Applications recording the messages of a room should call this, so that the peers and the [`EventSink`](super::hook::EventSink)s are notified.
+    ///
+    /// # Behavior
+    /// A [`ControlMessage::RecordingState`] is sent to every peer right away, and to every peer joining later, then [`ServerEvent::RecordingStarted`] is broadcast.
+    /// If `consent_required` is set, the media the peers send in the room is only forwarded (and so recorded) after they have consented with a [`ControlMessage::RecordingConsent`], supporting the jurisdictions which require the consent of every participant.
+    /// Calling this for a room which is already recorded only updates whether consent is required.
+    ///
+    /// # Error
+    /// Returns an error if the server service has already shut down.
+    ///
+    pub async fn recording_started(
+        &self,
+        room: u32,
+        consent_required: bool,
+    ) -> std::result::Result<(), ServerError> {
+        self.request_sender
+            .send(ServiceRequest::Recording(
+                room,
+                RecordingState::Recording { consent_required },
+            ))
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+
+    /// Signals that the application has stopped recording the `room` to every peer, clears the consents given to the recording, and broadcasts [`ServerEvent::RecordingStopped`].
+    /// Nothing happens if the room isn't recorded.
+    pub async fn recording_stopped(&self, room: u32) -> std::result::Result<(), ServerError> {
+        self.request_sender
+            .send(ServiceRequest::Recording(room, RecordingState::Stopped))
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+
+    /// Returns the [`RecordingState`] of every recorded room.
+    pub fn recordings(&self) -> Recordings {
+        self.recordings.clone()
+    }
+
+    /// Subscribes to the [`ServerEvent`]s of the server service.
+    /// Only the events happening after the subscription are received, and the oldest events are skipped if the receiver lags behind.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ServerEvent> {
+        self.event_sender.subscribe()
+    }
+
+    /// Closes the session of every client with the [`CloseReason`], then shuts down the server service.
+    /// Unlike cancelling the [`CancellationToken`], this notifies the clients.
+    pub async fn shutdown(
+        &self,
+        close_reason: CloseReason,
+    ) -> std::result::Result<(), ServerError> {
+        self.request_sender
+            .send(ServiceRequest::Shutdown(close_reason))
+            .await
+            .map_err(|_| ServerError::ChannelClosed)
+    }
+}
+
+/// Creates the [`RetryToken`] challenging the new address of a session, which is only valid until the session has moved there (or the session is challenged again).
+/// Unlike the tokens of the [`RetryConfig`] it is random, as it is stored in the [`Peer`] until it is echoed.
+fn migration_challenge() -> RetryToken {
+    let issued_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    RetryToken::new(issued_at, *Uuid::new_v4().as_bytes())
+}
+
+/// The state of the server service, owned by the service task.
+struct ServerState<T> {
+    /// The transport of the server, limiting the replies to the addresses which haven't joined yet.
+    socket_handle: AmplificationGuard<T>,
+
+    /// The addresses the messages of the application are fanned out to.
+    client_list: ClientList,
+
+    /// The peers which have joined the session.
+    peers: PeerRegistry,
+
+    /// The policy of every room.
+    room_policies: RoomPolicies,
+
+    /// The recording state of every recorded room.
+    recordings: Recordings,
+
+    /// The banned authors.
+    bans: BanList,
+
+    /// The sender of the [`ServerEvent`]s.
+    event_sender: broadcast::Sender<ServerEvent>,
+
+    /// The sender of the messages forwarded to the application.
+    inbound_message_sender: Sender<(VoipHeader, Vec<u8>, SocketAddr)>,
+
+    /// The token cancelling the server service.
+    cancellation_token: CancellationToken,
+
+    /// The loudness under which the voice messages are discarded.
+    silence_threshold: Option<u8>,
+
+    /// The validation of the addresses of the unregistered senders.
+    retry: Option<RetryConfig>,
+
+    /// The resumption of the sessions.
+    resumption: Option<ResumptionConfig>,
+
+    /// How an author joining from another address is handled.
+    duplicate_login: DuplicateLoginPolicy,
+
+    /// The addresses whose datagrams are accepted.
+    source_filter: SourceFilter,
+
+    /// The caps of the media forwarded by the peers and the rooms.
+    bandwidth_limits: BandwidthLimits,
+
+    /// The outbound rate limits of the destinations.
+    egress_scheduler: Option<EgressScheduler>,
+
+    /// The floor of every room.
+    floor_control: Option<FloorControl>,
+
+    /// The music the clients on hold hear.
+    hold_music: Option<HoldMusic>,
+
+    /// The media shed under overload.
+    load_shedder: Option<LoadShedder>,
+
+    /// The counters and the log of the dropped messages.
+    drop_log: DropLog,
+
+    /// The link to the other nodes of the cluster.
+    #[cfg(feature = "cluster")]
+    cluster_link: Option<ClusterLink>,
+
+    /// The transcoder re-encoding the voice for the clients which cant receive it at its original bitrate.
+    #[cfg(feature = "transcode")]
+    transcoder: Option<Transcoder>,
+
+    /// The media forwarded in every room in the current window of the bandwidth limits.
+    room_meters: HashMap<u32, BandwidthMeter>,
+
+    /// The simulcast layers every author is sending.
+    active_layers: ActiveLayers,
+
+    /// The clients on hold.
+    held_clients: HashMap<SocketAddr, HoldPlayback>,
+
+    /// The time the next frame of the hold music is sent at.
+    next_hold_frame: Instant,
+
+    /// The time the earliest keepalive probe of the peers is answered at.
+    next_keepalive_probe: Option<Instant>,
+}
+
+impl<T: Transport> ServerState<T> {
+    ///
+    /// Handles a datagram received from the `socket_addr`.
+    ///
+    /// # Behavior
+    /// The message is counted in the statistics of the sender, and the control messages the server is responsible for are handled (see [`ServerState::handle_control_message`]).
+    /// The messages are forwarded to the application, unless they are dropped by the filters of the server (the bans, the holds, the room policies, the recording consents, the floors, the silence, the load shedding and the bandwidth limits).
+    /// Every dropped message is counted in the [`DropLog`].
+    ///
+    /// Returns whether the service should keep serving, which it shouldn't once the application has dropped the receiver of the messages.
+    ///
+    async fn handle_datagram(
+        &mut self,
+        datagram: &[u8],
+        socket_addr: SocketAddr,
+        ecn: Ecn,
+    ) -> bool {
+        //Discard the datagrams of the filtered addresses before touching them
+        if !self.source_filter.is_allowed(socket_addr) {
+            self.drop_log.record(DropReason::Filtered, socket_addr);
+
+            return true;
+        }
+
+        //Try deserializing the bytes
+        let (voip_header, voip_body) = match decode_message(datagram) {
+            Ok(message) => message,
+            Err(err) => {
+                self.drop_log.record_error(socket_addr, &err);
+
+                return true;
+            }
+        };
+
+        //Discard the messages of the banned authors, and close their sessions whenever they try to join
+        let ban_reason = self
+            .bans
+            .get(&voip_header.author())
+            .map(|ban| ban.reason.clone());
+
+        if let Some(ban_reason) = ban_reason {
+            if matches!(
+                voip_header.voip_message_type(),
+                VoipMessageType::Control(
+                    ControlMessage::Heartbeat
+                        | ControlMessage::RetryHeartbeat(_)
+                        | ControlMessage::Resume(_)
+                        | ControlMessage::ConnectionHeartbeat(_)
+                )
+            ) {
+                let close_reason = CloseReason::new(CloseCode::Banned, ban_reason);
+
+                send_control_message(
+                    &self.socket_handle,
+                    ControlMessage::Close(close_reason.clone()),
+                    socket_addr,
+                )
+                .await;
+
+                let _ = self.event_sender.send(ServerEvent::ConnectionRejected {
+                    remote_addr: socket_addr,
+                    author: voip_header.author(),
+                    close_reason,
+                });
+            }
+
+            self.drop_log.record(DropReason::Banned, socket_addr);
+
+            return true;
+        }
+
+        //Pass on the floors held for too long
+        if let Some(floor_control) = self.floor_control.as_mut() {
+            send_floor_notices(
+                &self.socket_handle,
+                &self.peers,
+                floor_control.expire(Instant::now()),
+            )
+            .await;
+        }
+
+        self.count_received(&voip_header, socket_addr, ecn);
+
+        //Handle the control messages the server is responsible for
+        if let VoipMessageType::Control(control_message) = voip_header.voip_message_type() {
+            let is_forwarded = self
+                .handle_control_message(
+                    control_message,
+                    voip_header.author(),
+                    voip_header.channel(),
+                    socket_addr,
+                )
+                .await;
+
+            //The probe may be answered before the ones already pending
+            if matches!(control_message, ControlMessage::KeepaliveProbe(..)) {
+                self.next_keepalive_probe = earliest_keepalive_probe(&self.peers);
+            }
+
+            if !is_forwarded {
+                return true;
+            }
+        }
+
+        //The padding only probes the bandwidth of the path, it is counted in the statistics but never forwarded
+        if voip_header.flags().contains(HeaderFlags::PADDING) {
+            return true;
+        }
+
+        if let Some(drop_reason) = self
+            .filter_media(&voip_header, datagram.len(), socket_addr)
+            .await
+        {
+            self.drop_log.record(drop_reason, socket_addr);
+
+            return true;
+        }
+
+        //Send the deserialized message through the channel, if the receiver was dropped the server was shut down
+        self.inbound_message_sender
+            .send((voip_header, voip_body, socket_addr))
+            .await
+            .is_ok()
+    }
+
+    /// Counts a message received from the `socket_addr` in the statistics of the sender, and tracks the room it is talking in.
+    fn count_received(&self, voip_header: &VoipHeader, socket_addr: SocketAddr, ecn: Ecn) {
+        let Some(mut peer) = self.peers.get_mut(&socket_addr) else {
+            return;
+        };
+
+        peer.last_packet = Instant::now();
+        peer.packets_received += 1;
+
+        if ecn == Ecn::Ce {
+            peer.packets_ce += 1;
+        }
+
+        //The loss is estimated from the default audio stream, as every stream has its own sequence numbers
+        if let (VoipMessageType::VoiceMessage(_), Some(sequence), None) = (
+            voip_header.voip_message_type(),
+            voip_header.sequence(),
+            voip_header.stream(),
+        ) {
+            //The aggregated frames have consecutive sequence numbers
+            for offset in 0..voip_header.frame_count() {
+                peer.loss.observe(sequence.wrapping_add(offset));
+            }
+        }
+
+        //Track the room the peer is talking in, the padding isn't sent to any room
+        if !matches!(voip_header.voip_message_type(), VoipMessageType::Control(_))
+            && !voip_header.flags().contains(HeaderFlags::PADDING)
+            && peer.room != voip_header.channel()
+        {
+            peer.room = voip_header.channel();
+
+            let _ = self.event_sender.send(ServerEvent::PeerMoved {
+                remote_addr: socket_addr,
+                author: peer.author,
+                room: peer.room,
+            });
+        }
+    }
+
+    ///
+    /// Applies the filters of the server to a message received from the `socket_addr`, which is `byte_count` bytes long.
+    ///
+    /// # Behavior
+    /// The media of the clients on hold, the media violating the policy of its room, the media of the clients which haven't consented to the recording of the room, the voice of the clients not holding the floor, the silent voice messages, the media shed under overload and the media exceeding the bandwidth caps are dropped.
+    ///
+    /// Returns the reason the message is dropped for, or `None` if it is forwarded.
+    ///
+    async fn filter_media(
+        &mut self,
+        voip_header: &VoipHeader,
+        byte_count: usize,
+        socket_addr: SocketAddr,
+    ) -> Option<DropReason> {
+        let is_control = matches!(voip_header.voip_message_type(), VoipMessageType::Control(_));
+
+        //Discard the media of the clients on hold
+        if self.held_clients.contains_key(&socket_addr) && !is_control {
+            return Some(DropReason::OnHold);
+        }
+
+        //Reject the media violating the policy of its room
+        if !admit_room_policy(
+            &self.socket_handle,
+            &self.peers,
+            &self.room_policies,
+            voip_header,
+            socket_addr,
+        )
+        .await
+        {
+            return Some(DropReason::PolicyViolation);
+        }
+
+        //Discard the media of the clients which haven't consented to the recording of the room
+        if self
+            .recordings
+            .get(&voip_header.channel())
+            .is_some_and(|recording_state| recording_state.requires_consent())
+            && !is_control
+            && !self
+                .peers
+                .get(&socket_addr)
+                .is_some_and(|peer| peer.has_consented_to_recording(voip_header.channel()))
+        {
+            return Some(DropReason::WithoutConsent);
+        }
+
+        //Discard the voice of the clients not holding the floor
+        if self
+            .floor_control
+            .as_ref()
+            .is_some_and(|floor_control| !floor_control.admits(voip_header))
+        {
+            return Some(DropReason::WithoutFloor);
+        }
+
+        //Discard the silent voice messages
+        if is_silent(voip_header, self.silence_threshold) {
+            return Some(DropReason::Silent);
+        }
+
+        //Drop the media shed under overload
+        if self
+            .load_shedder
+            .as_ref()
+            .is_some_and(|load_shedder| !load_shedder.admits(voip_header))
+        {
+            return Some(DropReason::Shed);
+        }
+
+        //Drop the media exceeding the bandwidth caps
+        if self.bandwidth_limits.is_enabled()
+            && !admit_bandwidth(
+                &self.socket_handle,
+                &self.peers,
+                &mut self.room_meters,
+                &self.bandwidth_limits,
+                voip_header,
+                byte_count as u64,
+                socket_addr,
+            )
+            .await
+        {
+            return Some(DropReason::RateLimited);
+        }
+
+        None
+    }
+
+    ///
+    /// Fans a message of the application out to every client on the [`ClientList`].
+    ///
+    /// # Behavior
+    /// The message is published to the other nodes of the cluster, and re-encoded for the clients which cant receive it at its original bitrate.
+    /// The clients on hold, the clients which have paused their video (if the message is a video message), and the clients which haven't selected the simulcast layer of the message are skipped.
+    /// The messages exceeding the outbound rate limits of a client are queued, and sent once its buckets have refilled.
+    ///
+    async fn fan_out(&mut self, outgoing_message: VoipPacket) {
+        //Decode the header once, it is inspected by every stage of the fan out
+        let outgoing_header = decode_header(outgoing_message.inner()).ok();
+
+        //Drop the media shed under overload before fanning it out
+        if self.load_shedder.as_ref().is_some_and(|load_shedder| {
+            load_shedder.is_shedding()
+                && outgoing_header
+                    .as_ref()
+                    .is_some_and(|voip_header| !load_shedder.admits(voip_header))
+        }) {
+            self.drop_log.record_outgoing(DropReason::Shed);
+
+            return;
+        }
+
+        //Publish the message to the other nodes of the cluster
+        #[cfg(feature = "cluster")]
+        if let Some(cluster_link) = &self.cluster_link {
+            cluster_link.publish(&outgoing_message);
+        }
+
+        //Re-encode the message for the clients which cant receive it at its original bitrate
+        #[cfg(feature = "transcode")]
+        let transcoded_messages = self
+            .transcoder
+            .as_mut()
+            .map(|transcoder| {
+                transcoder.transcode_for_clients(&outgoing_message, &self.client_list, &self.peers)
+            })
+            .unwrap_or_default();
+
+        //Track the simulcast layers, so that every receiver is only sent the layer it has selected
+        let simulcast_header = outgoing_header
+            .as_ref()
+            .filter(|voip_header| voip_header.layer().is_some());
+
+        if let Some(simulcast_header) = simulcast_header {
+            self.active_layers.observe(simulcast_header, Instant::now());
+        }
+
+        let pacing = self
+            .egress_scheduler
+            .is_some()
+            .then(|| Pacing::of(outgoing_header.as_ref()));
+
+        let is_video = is_video_message(outgoing_header.as_ref());
+
+        let remote_addrs: Vec<SocketAddr> = self
+            .client_list
+            .iter()
+            .map(|remote_addr| *remote_addr)
+            .collect();
+
+        //Iter over all the remote_addresses and echo back the VoipPacket to everyone.
+        for remote_addr in remote_addrs {
+            //The clients on hold only hear the hold music
+            if self.held_clients.contains_key(&remote_addr) {
+                continue;
+            }
+
+            //The clients which have paused their video aren't woken up by it
+            if is_video
+                && self
+                    .peers
+                    .get(&remote_addr)
+                    .is_some_and(|peer| peer.video_paused)
+            {
+                continue;
+            }
+
+            if let Some(simulcast_header) = simulcast_header {
+                let is_admitted = self.peers.get_mut(&remote_addr).is_none_or(|mut peer| {
+                    peer.layer_routing
+                        .admit(simulcast_header, &self.active_layers)
+                });
+
+                if !is_admitted {
+                    continue;
+                }
+            }
+
+            #[cfg(feature = "transcode")]
+            let outgoing_message = transcoded_messages
+                .get(&remote_addr)
+                .unwrap_or(&outgoing_message);
+
+            //Queue the messages exceeding the outbound rate limits, they are sent once the buckets have refilled
+            if let (Some(egress_scheduler), Some(pacing)) = (self.egress_scheduler.as_mut(), pacing)
+            {
+                if !egress_scheduler.admit(
+                    remote_addr,
+                    outgoing_message.inner(),
+                    pacing,
+                    Instant::now(),
+                ) {
+                    continue;
+                }
+            }
+
+            self.send_media(outgoing_message.inner(), remote_addr).await;
+        }
+    }
+
+    /// Relays a message received from the other nodes of the cluster to the local members of its `room`.
+    async fn relay_remote_message(&mut self, room: u32, remote_message: VoipPacket) {
+        let remote_header = decode_header(remote_message.inner()).ok();
+
+        let is_video = is_video_message(remote_header.as_ref());
+
+        let remote_addrs: Vec<SocketAddr> = self
+            .peers
+            .iter()
+            .filter(|peer| {
+                peer.room == room
+                    && !peer.on_hold
+                    && !(is_video && peer.video_paused)
+                    && self.client_list.contains(peer.key())
+            })
+            .map(|peer| *peer.key())
+            .collect();
+
+        let pacing = self
+            .egress_scheduler
+            .is_some()
+            .then(|| Pacing::of(remote_header.as_ref()));
+
+        for remote_addr in remote_addrs {
+            if let (Some(egress_scheduler), Some(pacing)) = (self.egress_scheduler.as_mut(), pacing)
+            {
+                if !egress_scheduler.admit(
+                    remote_addr,
+                    remote_message.inner(),
+                    pacing,
+                    Instant::now(),
+                ) {
+                    continue;
+                }
+            }
+
+            self.send_media(remote_message.inner(), remote_addr).await;
+        }
+    }
+
+    /// Sends the messages queued by the outbound rate limits whose buckets have refilled, and drops the ones which have gone stale in the queue.
+    async fn release_queued_messages(&mut self) {
+        let Some(egress_scheduler) = self.egress_scheduler.as_mut() else {
+            return;
+        };
+
+        let released = egress_scheduler.release(Instant::now());
+
+        for remote_addr in released.dropped {
+            if let Some(mut peer) = self.peers.get_mut(&remote_addr) {
+                peer.packets_rate_limited += 1;
+            }
+
+            self.drop_log.record(DropReason::Stale, remote_addr);
+        }
+
+        for (remote_addr, datagram) in released.datagrams {
+            self.send_media(&datagram, remote_addr).await;
+        }
+    }
+
+    /// Sends a `datagram` of the media to the `remote_addr`, counting it in the statistics of the peer (or in the [`DropLog`] if it couldn't be sent).
+    async fn send_media(&mut self, datagram: &[u8], remote_addr: SocketAddr) {
+        match self
+            .socket_handle
+            .send_datagram(datagram, remote_addr)
+            .await
+        {
+            Ok(_) => {
+                if let Some(mut peer) = self.peers.get_mut(&remote_addr) {
+                    peer.packets_sent += 1;
+                }
+            }
+            Err(err) => {
+                event!(
+                    Level::ERROR,
+                    "Failed to send message to {remote_addr}: {err}"
+                );
+
+                self.drop_log.record(DropReason::SendFailed, remote_addr);
+            }
+        }
+    }
+
+    /// Plays the next frame of the hold music to the clients on hold.
+    async fn play_hold_music(&mut self) {
+        let Some(hold_music) = self.hold_music.as_ref() else {
+            return;
+        };
+
+        self.next_hold_frame += hold_music.frame_duration;
+
+        for (remote_addr, hold_playback) in self.held_clients.iter_mut() {
+            let Some(voice_frame) = hold_playback.next_frame(hold_music, SERVER_AUTHOR) else {
+                continue;
+            };
+
+            let voip_packet = match voice_frame
+                .to_header()
+                .create_message_buffer(&voice_frame.to_body())
+            {
+                Ok(voip_packet) => voip_packet,
+                Err(err) => {
+                    event!(Level::ERROR, "Failed to encode the hold music: {err}");
+
+                    continue;
+                }
+            };
+
+            if let Err(err) = self
+                .socket_handle
+                .send_datagram(voip_packet.inner(), *remote_addr)
+                .await
+            {
+                event!(
+                    Level::ERROR,
+                    "Failed to send message to {remote_addr}: {err}"
+                );
+            }
+        }
+    }
+
+    /// Evaluates the load of the server, with the `outbound_queue_depth` messages waiting to be fanned out, and sheds the media accordingly.
+    fn evaluate_load(&mut self, outbound_queue_depth: usize) {
+        let Some(load_shedder) = self.load_shedder.as_mut() else {
+            return;
+        };
+
+        //The messages waiting for the application, and the ones waiting to be fanned out (or paced)
+        let queue_depth = self.inbound_message_sender.max_capacity()
+            - self.inbound_message_sender.capacity()
+            + outbound_queue_depth
+            + self
+                .egress_scheduler
+                .as_ref()
+                .map_or(0, EgressScheduler::queued);
+        let active_rooms: HashSet<u32> = self.peers.iter().map(|peer| peer.room).collect();
+
+        if let Some(load_change) = load_shedder.evaluate(Instant::now(), queue_depth, &active_rooms)
+        {
+            let _ = self.event_sender.send(load_change.into());
+        }
+    }
+
+    ///
+    /// Handles a request of the user.
+    ///
+    /// # Behavior
+    /// * [`ServiceRequest::Client`]: Notifies the client with a [`ControlMessage::Close`], removes it from the session and broadcasts [`ServerEvent::PeerClosed`].
+    /// * [`ServiceRequest::Hold`]: Places the client on hold (or retrieves it from hold).
+    /// * [`ServiceRequest::CreateRoom`] and [`ServiceRequest::DestroyRoom`]: Stores (or removes) the policy of the room, and advertises it to every peer.
+    /// * [`ServiceRequest::Recording`]: Stores the recording state of the room, and signals it to every peer.
+    /// * [`ServiceRequest::Handoff`]: Hands the sessions over without notifying the clients.
+    /// * [`ServiceRequest::Shutdown`]: Notifies every known client with a [`ControlMessage::Close`].
+    ///
+    /// Returns whether the service should keep serving, which it shouldn't once it was handed over or shut down.
+    ///
+    async fn handle_service_request(&mut self, service_request: ServiceRequest) -> bool {
+        match service_request {
+            ServiceRequest::Client(remote_addr, close_reason) => {
+                //Notify the client while it is still registered, so the amplification limit doesn't apply
+                send_control_message(
+                    &self.socket_handle,
+                    ControlMessage::Close(close_reason.clone()),
+                    remote_addr,
+                )
+                .await;
+
+                self.client_list.remove(&remote_addr);
+
+                let author = self.peers.remove(&remote_addr).map(|(_, peer)| peer.author);
+
+                if let Some(egress_scheduler) = self.egress_scheduler.as_mut() {
+                    egress_scheduler.remove(&remote_addr);
+                }
+
+                self.held_clients.remove(&remote_addr);
+
+                //Pass on the floors of the closed client
+                if let (Some(floor_control), Some(author)) = (self.floor_control.as_mut(), author) {
+                    send_floor_notices(
+                        &self.socket_handle,
+                        &self.peers,
+                        floor_control.remove(author, Instant::now()),
+                    )
+                    .await;
+                }
+
+                let _ = self.event_sender.send(ServerEvent::PeerClosed {
+                    remote_addr,
+                    author,
+                    close_reason,
+                });
+            }
+            ServiceRequest::Hold(remote_addr, on_hold) => {
+                let Some(mut peer) = self.peers.get_mut(&remote_addr) else {
+                    return true;
+                };
+
+                peer.on_hold = on_hold;
+
+                if !on_hold {
+                    self.held_clients.remove(&remote_addr);
+
+                    return true;
+                }
+
+                //The hold music starts right away if nobody was on hold
+                if self.held_clients.is_empty() {
+                    self.next_hold_frame = Instant::now();
+                }
+
+                self.held_clients.entry(remote_addr).or_default();
+            }
+            ServiceRequest::CreateRoom(room, room_policy) => {
+                self.room_policies.insert(room, room_policy.clone());
+
+                //Advertise the policy of the room to every peer
+                self.send_to_peers(
+                    VoipHeader::new(
+                        VoipMessageType::Control(ControlMessage::RoomPolicy(room_policy.clone())),
+                        SERVER_AUTHOR,
+                    )
+                    .with_channel(room),
+                )
+                .await;
+
+                let _ = self
+                    .event_sender
+                    .send(ServerEvent::RoomCreated { room, room_policy });
+            }
+            ServiceRequest::DestroyRoom(room) => {
+                if self.room_policies.remove(&room).is_none() {
+                    return true;
+                }
+
+                //Lift the policy of the room on every peer
+                self.send_to_peers(
+                    VoipHeader::new(
+                        VoipMessageType::Control(ControlMessage::RoomPolicy(RoomPolicy::default())),
+                        SERVER_AUTHOR,
+                    )
+                    .with_channel(room),
+                )
+                .await;
+
+                let _ = self.event_sender.send(ServerEvent::RoomDestroyed { room });
+            }
+            ServiceRequest::Recording(room, recording_state) => {
+                let was_recording = match recording_state {
+                    RecordingState::Stopped => self.recordings.remove(&room).is_some(),
+                    RecordingState::Recording { .. } => {
+                        self.recordings.insert(room, recording_state).is_some()
+                    }
+                };
+
+                if !was_recording && !recording_state.is_recording() {
+                    return true;
+                }
+
+                //The consents only apply to the recording they were given to
+                if !recording_state.is_recording() {
+                    for mut peer in self.peers.iter_mut() {
+                        peer.recording_consents.remove(&room);
+                    }
+                }
+
+                //Signal the recording state of the room to every peer
+                self.send_to_peers(
+                    VoipHeader::new(
+                        VoipMessageType::Control(ControlMessage::RecordingState(recording_state)),
+                        SERVER_AUTHOR,
+                    )
+                    .with_channel(room),
+                )
+                .await;
+
+                match recording_state {
+                    RecordingState::Stopped => {
+                        let _ = self
+                            .event_sender
+                            .send(ServerEvent::RecordingStopped { room });
+                    }
+                    RecordingState::Recording { .. } if !was_recording => {
+                        let _ = self
+                            .event_sender
+                            .send(ServerEvent::RecordingStarted { room });
+                    }
+                    RecordingState::Recording { .. } => (),
+                }
+            }
+            ServiceRequest::Handoff(handoff_sender) => {
+                //Hand the sessions over without notifying the clients, they keep sending to the port of the new server
+                let handoff = SessionHandoff {
+                    peers: self
+                        .peers
+                        .iter()
+                        .map(|peer| peer.to_handoff(*peer.key()))
+                        .collect(),
+                    reply_list: self
+                        .client_list
+                        .iter()
+                        .map(|remote_addr| *remote_addr)
+                        .collect(),
+                    room_policies: self
+                        .room_policies
+                        .iter()
+                        .map(|room_policy| (*room_policy.key(), room_policy.value().clone()))
+                        .collect(),
+                    recordings: self
+                        .recordings
+                        .iter()
+                        .map(|recording_state| (*recording_state.key(), *recording_state.value()))
+                        .collect(),
+                    bans: self
+                        .bans
+                        .iter()
+                        .map(|ban| (*ban.key(), ban.value().clone()))
+                        .collect(),
+                };
+
+                let _ = handoff_sender.send(handoff);
+
+                self.cancellation_token.cancel();
+
+                return false;
+            }
+            ServiceRequest::Shutdown(close_reason) => {
+                //Notify every known client, whether they are on the reply list or have only sent heartbeats
+                let remote_addrs: HashSet<SocketAddr> = self
+                    .client_list
+                    .iter()
+                    .map(|remote_addr| *remote_addr)
+                    .chain(self.peers.iter().map(|peer| *peer.key()))
+                    .collect();
+
+                for remote_addr in remote_addrs {
+                    send_control_message(
+                        &self.socket_handle,
+                        ControlMessage::Close(close_reason.clone()),
+                        remote_addr,
+                    )
+                    .await;
+                }
+
+                self.cancellation_token.cancel();
+
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Sends the `voip_header` to every peer.
+    async fn send_to_peers(&mut self, voip_header: VoipHeader) {
+        let remote_addrs: Vec<SocketAddr> = self.peers.iter().map(|peer| *peer.key()).collect();
+
+        for remote_addr in remote_addrs {
+            send_voip_header(&self.socket_handle, voip_header.clone(), remote_addr).await;
+        }
+    }
+
+    /// Sends the `voip_header` to every peer other than the sender at the `socket_addr`.
+    async fn send_to_other_peers(&mut self, voip_header: VoipHeader, socket_addr: SocketAddr) {
+        let remote_addrs: Vec<SocketAddr> = self
+            .peers
+            .iter()
+            .map(|peer| *peer.key())
+            .filter(|remote_addr| *remote_addr != socket_addr)
+            .collect();
+
+        for remote_addr in remote_addrs {
+            send_voip_header(&self.socket_handle, voip_header.clone(), remote_addr).await;
+        }
+    }
+
+    ///
+    /// Handles a control message received by the server service.
+    ///
+    /// # Behavior
+    /// * [`ControlMessage::Heartbeat`], [`ControlMessage::RetryHeartbeat`], [`ControlMessage::Resume`] and [`ControlMessage::ConnectionHeartbeat`]: Refreshes (or creates) the sender's entry in the [`PeerRegistry`], and echoes the heartbeat back to the sender.
+    ///   If the sender has just joined (or its session has moved to its address), it is sent the [`ConnectionId`] of its session.
+    ///   If the sender has just joined, the [`RoomPolicy`] of every room is advertised to it, and [`ServerEvent::PeerJoined`] is broadcast.
+    ///   The joining sender is also sent the retained control state of the session, so that it doesn't start with an inconsistent view until the next update: a [`ControlMessage::ParticipantJoined`] for every other peer, the [`RecordingState`] of every recorded room, and the presence and media states which aren't the default.
+    ///   If address validation is enabled, an unregistered sender is only registered if it has echoed a valid [`RetryToken`], otherwise it is answered with a [`ControlMessage::Retry`] (without allocating any state).
+    ///   The heartbeats presenting an invalid token (or ticket) are counted as [`DropReason::AuthenticationFailed`].
+    ///   An unregistered sender presenting the [`ConnectionId`] of a session (or a [`ResumptionTicket`] valid for its author) is sent a [`ControlMessage::Retry`] with a [`migration_challenge`], and the session only moves to its address once it has echoed the challenge, so a spoofed address can't have the messages of the session reflected at it.
+    ///   A [`ControlMessage::ResumptionTicket`] is issued to the peers which have joined or resumed their session (and again once half of the lifetime of their ticket has passed), if resumption is enabled.
+    ///   An author already in the session joining from another address is handled by the [`DuplicateLoginPolicy`].
+    /// * [`ControlMessage::ResumptionTicket`]: Ignored, as the tickets are issued by the server.
+    /// * [`ControlMessage::ConnectionId`]: Ignored, as the connection ids are issued by the server.
+    /// * [`ControlMessage::Retry`]: Ignored, as the server doesn't register to other servers.
+    /// * [`ControlMessage::QualityReport`]: Stores the report in the sender's entry of the [`PeerRegistry`].
+    /// * [`ControlMessage::MaxBitrate`]: Stores the limit in the sender's entry of the [`PeerRegistry`].
+    /// * [`ControlMessage::RoomPolicy`]: Ignored, as room policies are set by the server.
+    /// * [`ControlMessage::Goodbye`] and [`ControlMessage::Close`]: Removes the sender from the [`PeerRegistry`] and the [`ClientList`], sends [`ControlMessage::ParticipantLeft`] to the remaining clients, and broadcasts [`ServerEvent::PeerLeft`].
+    ///   The notices carry the author of the removed peer, and the messages of the unregistered senders are dropped without notifying anyone.
+    ///   If other devices of the author remain, they are sent a [`DeviceNotice::Left`] instead of the [`ControlMessage::ParticipantLeft`].
+    /// * [`ControlMessage::Device`]: Ignored, as the devices are signaled by the server.
+    /// * [`ControlMessage::PolicyViolation`]: Ignored, as the policy violations are signaled by the server.
+    /// * [`ControlMessage::Ping`]: Answers the sender with a [`ControlMessage::Pong`], without registering it.
+    /// * [`ControlMessage::Pong`]: Ignored, as the server doesn't send pings.
+    /// * [`ControlMessage::KeepaliveProbe`]: Stores the probe in the sender's entry of the [`PeerRegistry`] (replacing its pending probe), the probe is answered with a [`ControlMessage::Pong`] once its delay has passed.
+    /// * [`ControlMessage::SelectLayer`]: Stores the selection in the sender's entry of the [`PeerRegistry`], the forwarded layer is switched on the next keyframe.
+    /// * [`ControlMessage::PauseVideo`]: Stores the choice in the sender's entry of the [`PeerRegistry`], the video messages aren't fanned out to it while it is paused.
+    /// * [`ControlMessage::Tone`]: Forwarded to the application, which decides whom to relay it to.
+    /// * [`ControlMessage::FloorRequest`] and [`ControlMessage::FloorRelease`]: Requests or releases the floor of the `room`, if floor control is enabled.
+    /// * [`ControlMessage::Floor`]: Ignored, as the floor is arbitrated by the server.
+    /// * [`ControlMessage::Call`]: Routed to the recipient of the [`CallSignal`] only, or answered with a [`CallSignalKind::Hangup`] if the recipient isn't in the session.
+    ///   The invitations of the recipients whose presence is [`PresenceState::DoNotDisturb`] are answered with a [`CallSignalKind::DoNotDisturb`] on their behalf.
+    ///   The signals are routed with the registered author of the sender, and the signals of the unregistered senders are dropped.
+    /// * [`ControlMessage::Presence`]: Stores the state in the sender's entry of the [`PeerRegistry`], and sends it to every other peer with the registered author of the sender.
+    ///   The states of the unregistered senders are dropped.
+    /// * [`ControlMessage::MediaState`]: Stores the state of the `room` in the sender's entry of the [`PeerRegistry`], and sends it to every other peer with the registered author of the sender.
+    ///   The states of the unregistered senders are dropped.
+    /// * [`ControlMessage::RecordingState`]: Ignored, as the recordings are signaled by the server.
+    /// * [`ControlMessage::RecordingConsent`]: Stores the consent of the sender to the recording of the `room` in its entry of the [`PeerRegistry`], and broadcasts [`ServerEvent::RecordingConsentChanged`] if it has changed.
+    ///   The consents to the rooms which aren't recorded are ignored.
+    /// * [`ControlMessage::Codecs`]: Stores the codecs in the sender's entry of the [`PeerRegistry`], and sends the codecs every peer can decode to every peer (see [`send_session_codecs`]).
+    ///
+    /// Returns whether the message should be forwarded to the application.
+    /// Heartbeats, resumption tickets, connection ids, device notices, bitrate limits, room policies, policy violations, layer selections, video pauses, the floor control, the call signals, the presence and media states, the recordings, the codecs, the relay and the keepalive probes are handled entirely by the server, every other control message is forwarded.
+    ///
+    async fn handle_control_message(
+        &mut self,
+        control_message: &ControlMessage,
+        author: Uuid,
+        room: u32,
+        socket_addr: SocketAddr,
+    ) -> bool {
+        match control_message {
+            ControlMessage::Heartbeat
+            | ControlMessage::RetryHeartbeat(_)
+            | ControlMessage::Resume(_)
+            | ControlMessage::ConnectionHeartbeat(_) => {
+                self.handle_heartbeat(control_message, author, socket_addr)
+                    .await;
+
+                false
+            }
+            ControlMessage::QualityReport(quality_report) => {
+                if let Some(mut peer) = self.peers.get_mut(&socket_addr) {
+                    peer.quality_report = Some(*quality_report);
+                }
+
+                true
+            }
+            ControlMessage::MaxBitrate(max_bitrate) => {
+                if let Some(mut peer) = self.peers.get_mut(&socket_addr) {
+                    peer.max_bitrate = *max_bitrate;
+                }
+
+                false
+            }
+            //Room policies are set by the server only
+            ControlMessage::RoomPolicy(_) => false,
+            //Resumption tickets are issued by the server only
+            ControlMessage::ResumptionTicket(_) => false,
+            //Connection ids are issued by the server only
+            ControlMessage::ConnectionId(_) => false,
+            //Devices are signaled by the server only
+            ControlMessage::Device(_) => false,
+            //Policy violations are signaled by the server only
+            ControlMessage::PolicyViolation(_) => false,
+            ControlMessage::Goodbye | ControlMessage::Close(_) => {
+                self.handle_leave(socket_addr).await
+            }
+            ControlMessage::Ping(sequence) => {
+                send_control_message(
+                    &self.socket_handle,
+                    ControlMessage::Pong(*sequence),
+                    socket_addr,
+                )
+                .await;
+
+                false
+            }
+            ControlMessage::KeepaliveProbe(sequence, delay_ms) => {
+                //The probes of the unregistered addresses aren't answered, so they can't be used to reflect traffic
+                if let Some(mut peer) = self.peers.get_mut(&socket_addr) {
+                    let delay =
+                        Duration::from_millis((*delay_ms).min(MAX_KEEPALIVE_PROBE_DELAY_MS) as u64);
+
+                    peer.keepalive_probe = Some((Instant::now() + delay, *sequence));
+                }
+
+                false
+            }
+            ControlMessage::Pong(_) | ControlMessage::Retry(_) => false,
+            ControlMessage::PauseVideo(video_paused) => {
+                if let Some(mut peer) = self.peers.get_mut(&socket_addr) {
+                    peer.video_paused = *video_paused;
+                }
+
+                false
+            }
+            ControlMessage::SelectLayer(simulcast_author, layer_selection) => {
+                if let Some(mut peer) = self.peers.get_mut(&socket_addr) {
+                    peer.layer_routing
+                        .select(*simulcast_author, *layer_selection);
+                }
+
+                false
+            }
+            ControlMessage::FloorRequest => {
+                if let Some(floor_control) = self.floor_control.as_mut() {
+                    send_floor_notices(
+                        &self.socket_handle,
+                        &self.peers,
+                        floor_control.request(room, author, socket_addr, Instant::now()),
+                    )
+                    .await;
+                }
+
+                false
+            }
+            ControlMessage::FloorRelease => {
+                if let Some(floor_control) = self.floor_control.as_mut() {
+                    send_floor_notices(
+                        &self.socket_handle,
+                        &self.peers,
+                        floor_control.release(room, author, Instant::now()),
+                    )
+                    .await;
+                }
+
+                false
+            }
+            //The floor is arbitrated by the server only
+            ControlMessage::Floor(_) => false,
+            ControlMessage::Presence(presence) => {
+                self.handle_presence(*presence, socket_addr).await;
+
+                false
+            }
+            ControlMessage::MediaState(media_state) => {
+                self.handle_media_state(*media_state, room, socket_addr)
+                    .await;
+
+                false
+            }
+            //Recordings are signaled by the server only
+            ControlMessage::RecordingState(_) => false,
+            ControlMessage::RecordingConsent(consent) => {
+                self.handle_recording_consent(*consent, author, room, socket_addr);
+
+                false
+            }
+            ControlMessage::Codecs(codecs) => {
+                if let Some(mut peer) = self.peers.get_mut(&socket_addr) {
+                    peer.codecs = Some(codecs.clone());
+                }
+
+                send_session_codecs(&self.socket_handle, &self.peers).await;
+
+                false
+            }
+            ControlMessage::Call(call_signal) => {
+                self.handle_call(call_signal, socket_addr).await;
+
+                false
+            }
+            ControlMessage::ParticipantJoined(_)
+            | ControlMessage::ParticipantLeft(_)
+            | ControlMessage::Tone(_) => true,
+        }
+    }
+
+    ///
+    /// Handles a [`ControlMessage::Heartbeat`], [`ControlMessage::RetryHeartbeat`], [`ControlMessage::Resume`] or [`ControlMessage::ConnectionHeartbeat`] of the sender at the `socket_addr`.
+    ///
+    /// # Behavior
+    /// The session the sender has proven to own is moved to its address (see [`ServerState::challenge_migration`] and [`ServerState::complete_migration`]), the address of an unregistered sender is validated (see [`ServerState::validate_address`]), and the [`DuplicateLoginPolicy`] is applied (see [`ServerState::admit_duplicate_login`]).
+    /// The sender is then registered (or refreshed) and the heartbeat is echoed back to it.
+    /// The new and the moved sessions are sent their [`ConnectionId`], and a joining sender is sent the retained control state of the session (see [`ServerState::send_session_state`]).
+    ///
+    async fn handle_heartbeat(
+        &mut self,
+        control_message: &ControlMessage,
+        author: Uuid,
+        socket_addr: SocketAddr,
+    ) {
+        //A valid ticket proves the sender has joined as the author before, but not that it can receive messages at its address
+        let is_resuming = match control_message {
+            ControlMessage::Resume(ticket) => self
+                .resumption
+                .as_ref()
+                .is_some_and(|resumption| resumption.validate(ticket, author)),
+            _ => false,
+        };
+
+        let mut is_migrated = false;
+
+        if !self.peers.contains_key(&socket_addr) {
+            if self
+                .challenge_migration(control_message, author, socket_addr, is_resuming)
+                .await
+            {
+                return;
+            }
+
+            is_migrated = self.complete_migration(control_message, author, socket_addr);
+        }
+
+        if !self
+            .validate_address(control_message, author, socket_addr, is_resuming)
+            .await
+        {
+            return;
+        }
+
+        let Some(device) = self.admit_duplicate_login(author, socket_addr).await else {
+            return;
+        };
+
+        let is_joining = match self.peers.entry(socket_addr) {
+            Entry::Occupied(mut entry) => {
+                let peer = entry.get_mut();
+
+                peer.author = author;
+                peer.last_seen = Instant::now();
+
+                false
+            }
+            Entry::Vacant(entry) => {
+                let mut peer = Peer::new(author);
+
+                peer.device = device;
+
+                entry.insert(peer);
+
+                true
+            }
+        };
+
+        send_control_message(&self.socket_handle, ControlMessage::Heartbeat, socket_addr).await;
+
+        //Issue the connection id to the new sessions, and to the restarted clients whose session has moved
+        if is_joining || is_migrated {
+            let connection_id = self.peers.get(&socket_addr).map(|peer| peer.connection_id);
+
+            if let Some(connection_id) = connection_id {
+                send_control_message(
+                    &self.socket_handle,
+                    ControlMessage::ConnectionId(connection_id),
+                    socket_addr,
+                )
+                .await;
+            }
+        }
+
+        self.renew_resumption_ticket(
+            author,
+            socket_addr,
+            is_joining || is_resuming || is_migrated,
+        )
+        .await;
+
+        if is_joining {
+            self.send_session_state(author, device, socket_addr).await;
+
+            let _ = self.event_sender.send(ServerEvent::PeerJoined {
+                remote_addr: socket_addr,
+                author,
+            });
+        }
+    }
+
+    ///
+    /// Challenges the address of an unregistered sender, which has proven to own a session with the [`ConnectionId`] of the session (or a [`ResumptionTicket`] valid for its author).
+    ///
+    /// # Behavior
+    /// The sender is sent a [`ControlMessage::Retry`] with a [`migration_challenge`], which is stored in the session until the sender echoes it.
+    /// The sessions are only challenged if the [`DuplicateLoginPolicy`] is [`DuplicateLoginPolicy::Migrate`].
+    ///
+    /// Returns whether the sender was challenged.
+    ///
+    async fn challenge_migration(
+        &mut self,
+        control_message: &ControlMessage,
+        author: Uuid,
+        socket_addr: SocketAddr,
+        is_resuming: bool,
+    ) -> bool {
+        let owned_addr = match control_message {
+            _ if self.duplicate_login != DuplicateLoginPolicy::Migrate => None,
+            ControlMessage::ConnectionHeartbeat(connection_id) => self
+                .peers
+                .iter()
+                .find(|peer| peer.author == author && peer.connection_id == *connection_id)
+                .map(|peer| *peer.key()),
+            ControlMessage::Resume(_) if is_resuming => self
+                .peers
+                .iter()
+                .find(|peer| peer.author == author)
+                .map(|peer| *peer.key()),
+            _ => None,
+        };
+
+        let Some(owned_addr) = owned_addr else {
+            return false;
+        };
+
+        let challenge = migration_challenge();
+
+        if let Some(mut peer) = self.peers.get_mut(&owned_addr) {
+            peer.pending_migration = Some((socket_addr, challenge));
+        }
+
+        send_control_message(
+            &self.socket_handle,
+            ControlMessage::Retry(challenge),
+            socket_addr,
+        )
+        .await;
+
+        true
+    }
+
+    ///
+    /// Moves the session whose challenge the sender has echoed with a [`ControlMessage::RetryHeartbeat`] to the address of the sender.
+    ///
+    /// # Behavior
+    /// The session keeps its place on the [`ClientList`], and [`ServerEvent::PeerMigrated`] is broadcast.
+    ///
+    /// Returns whether the session has moved.
+    ///
+    fn complete_migration(
+        &mut self,
+        control_message: &ControlMessage,
+        author: Uuid,
+        socket_addr: SocketAddr,
+    ) -> bool {
+        let migrated_addr = match control_message {
+            ControlMessage::RetryHeartbeat(retry_token) => self
+                .peers
+                .iter()
+                .find(|peer| {
+                    peer.author == author
+                        && peer.pending_migration == Some((socket_addr, *retry_token))
+                })
+                .map(|peer| *peer.key()),
+            _ => None,
+        };
+
+        let Some((previous_addr, mut peer)) =
+            migrated_addr.and_then(|migrated_addr| self.peers.remove(&migrated_addr))
+        else {
+            return false;
+        };
+
+        peer.pending_migration = None;
+
+        self.peers.insert(socket_addr, peer);
+
+        if self.client_list.remove(&previous_addr).is_some() {
+            self.client_list.insert(socket_addr);
+        }
+
+        let _ = self.event_sender.send(ServerEvent::PeerMigrated {
+            previous_addr,
+            remote_addr: socket_addr,
+            author,
+        });
+
+        true
+    }
+
+    ///
+    /// Validates the address of the sender, if address validation is enabled.
+    ///
+    /// # Behavior
+    /// An unregistered sender which hasn't echoed a valid [`RetryToken`] is answered with a [`ControlMessage::Retry`], without allocating any state.
+    /// The heartbeats presenting an invalid token (or ticket) are counted as [`DropReason::AuthenticationFailed`], and [`ServerEvent::ConnectionRejected`] is broadcast.
+    ///
+    /// Returns whether the sender is registered, or may register.
+    ///
+    async fn validate_address(
+        &mut self,
+        control_message: &ControlMessage,
+        author: Uuid,
+        socket_addr: SocketAddr,
+        is_resuming: bool,
+    ) -> bool {
+        let Some(retry) = self.retry.as_ref() else {
+            return true;
+        };
+
+        let is_validated = match control_message {
+            ControlMessage::RetryHeartbeat(retry_token) => retry.validate(retry_token, socket_addr),
+            _ => false,
+        };
+
+        if is_validated || self.peers.contains_key(&socket_addr) {
+            return true;
+        }
+
+        //An echoed token (or a presented ticket) which isn't valid is either forged or has expired
+        let rejection = match control_message {
+            ControlMessage::RetryHeartbeat(_) => Some("Invalid retry token"),
+            ControlMessage::Resume(_) if !is_resuming => Some("Invalid resumption ticket"),
+            _ => None,
+        };
+
+        if let Some(rejection) = rejection {
+            self.drop_log
+                .record(DropReason::AuthenticationFailed, socket_addr);
+
+            let _ = self.event_sender.send(ServerEvent::ConnectionRejected {
+                remote_addr: socket_addr,
+                author,
+                close_reason: CloseReason::new(
+                    CloseCode::AuthenticationFailed,
+                    Some(String::from(rejection)),
+                ),
+            });
+        }
+
+        send_control_message(
+            &self.socket_handle,
+            ControlMessage::Retry(retry.issue(socket_addr)),
+            socket_addr,
+        )
+        .await;
+
+        false
+    }
+
+    ///
+    /// Applies the [`DuplicateLoginPolicy`] to an author already in the session, joining from the `socket_addr`.
+    ///
+    /// # Behavior
+    /// The author identifies the session, so a known author joining from a new address either takes the session over, is rejected with a [`CloseCode::DuplicateSession`], replaces the session or joins as another device.
+    /// A client restarted on a new port has lost the proof of its session, so with [`DuplicateLoginPolicy::Migrate`] it takes the session over once the session has been silent for [`STALE_SESSION_TIMEOUT`].
+    ///
+    /// Returns the device the sender joins as, or `None` if it was rejected.
+    ///
+    async fn admit_duplicate_login(
+        &mut self,
+        author: Uuid,
+        socket_addr: SocketAddr,
+    ) -> Option<u32> {
+        if self.peers.contains_key(&socket_addr) {
+            return Some(0);
+        }
+
+        let Some(previous_addr) = self
+            .peers
+            .iter()
+            .find(|peer| peer.author == author)
+            .map(|peer| *peer.key())
+        else {
+            return Some(0);
+        };
+
+        match self.duplicate_login {
+            //A client restarted on a new port has lost the proof of its session, so it takes the session over once the session has gone silent
+            DuplicateLoginPolicy::Migrate
+                if self
+                    .peers
+                    .get(&previous_addr)
+                    .is_some_and(|peer| peer.last_seen.elapsed() > STALE_SESSION_TIMEOUT) =>
+            {
+                self.peers.remove(&previous_addr);
+                self.client_list.remove(&previous_addr);
+
+                let _ = self.event_sender.send(ServerEvent::PeerClosed {
+                    remote_addr: previous_addr,
+                    author: Some(author),
+                    close_reason: CloseReason::new(CloseCode::Replaced, None),
+                });
+
+                Some(0)
+            }
+            //Anyone can send messages with the author, so without a proof of the session it doesn't move
+            DuplicateLoginPolicy::Migrate | DuplicateLoginPolicy::Reject => {
+                let close_reason = CloseReason::new(CloseCode::DuplicateSession, None);
+
+                send_control_message(
+                    &self.socket_handle,
+                    ControlMessage::Close(close_reason.clone()),
+                    socket_addr,
+                )
+                .await;
+
+                let _ = self.event_sender.send(ServerEvent::ConnectionRejected {
+                    remote_addr: socket_addr,
+                    author,
+                    close_reason,
+                });
+
+                None
+            }
+            DuplicateLoginPolicy::Replace => {
+                let close_reason = CloseReason::new(CloseCode::Replaced, None);
+
+                //Notify the replaced session while it is still registered, so the amplification limit doesn't apply
+                send_control_message(
+                    &self.socket_handle,
+                    ControlMessage::Close(close_reason.clone()),
+                    previous_addr,
+                )
+                .await;
+
+                self.peers.remove(&previous_addr);
+                self.client_list.remove(&previous_addr);
+
+                let _ = self.event_sender.send(ServerEvent::PeerClosed {
+                    remote_addr: previous_addr,
+                    author: Some(author),
+                    close_reason,
+                });
+
+                Some(0)
+            }
+            DuplicateLoginPolicy::MultiDevice => {
+                let devices: HashSet<u32> = self
+                    .peers
+                    .iter()
+                    .filter(|peer| peer.author == author)
+                    .map(|peer| peer.device)
+                    .collect();
+
+                Some(
+                    (0..)
+                        .find(|device| !devices.contains(device))
+                        .unwrap_or_default(),
+                )
+            }
+        }
+    }
+
+    /// Issues a [`ResumptionTicket`] to the sender if it is `is_due` (or its ticket is about to expire), if resumption is enabled.
+    async fn renew_resumption_ticket(
+        &mut self,
+        author: Uuid,
+        socket_addr: SocketAddr,
+        is_due: bool,
+    ) {
+        let Some(resumption) = self.resumption.as_ref() else {
+            return;
+        };
+
+        let now = Instant::now();
+
+        let is_ticket_due = is_due
+            || self.peers.get(&socket_addr).is_some_and(|peer| {
+                peer.resumption_issued_at.is_none_or(|issued_at| {
+                    now.duration_since(issued_at) >= resumption.lifetime / 2
+                })
+            });
+
+        if !is_ticket_due {
+            return;
+        }
+
+        if let Some(mut peer) = self.peers.get_mut(&socket_addr) {
+            peer.resumption_issued_at = Some(now);
+        }
+
+        send_control_message(
+            &self.socket_handle,
+            ControlMessage::ResumptionTicket(resumption.issue(author)),
+            socket_addr,
+        )
+        .await;
+    }
+
+    ///
+    /// Sends the retained control state of the session to the peer which has joined from the `socket_addr` as the `device` of the `author`.
+    ///
+    /// # Behavior
+    /// The joining peer is sent the [`RoomPolicy`] of every room, the [`RecordingState`] of every recorded room, a [`ControlMessage::ParticipantJoined`] for every other author, and the presence and media states which aren't the default.
+    /// The codecs every peer can decode are sent to every peer, and the devices of the author are signaled to each other if the [`DuplicateLoginPolicy`] is [`DuplicateLoginPolicy::MultiDevice`].
+    ///
+    async fn send_session_state(&mut self, author: Uuid, device: u32, socket_addr: SocketAddr) {
+        //Advertise the policy of every room to the joining peer
+        let room_policies: Vec<(u32, RoomPolicy)> = self
+            .room_policies
+            .iter()
+            .map(|room_policy| (*room_policy.key(), room_policy.value().clone()))
+            .collect();
+
+        for (room, room_policy) in room_policies {
+            send_voip_header(
+                &self.socket_handle,
+                VoipHeader::new(
+                    VoipMessageType::Control(ControlMessage::RoomPolicy(room_policy)),
+                    SERVER_AUTHOR,
+                )
+                .with_channel(room),
+                socket_addr,
+            )
+            .await;
+        }
+
+        //Signal the recorded rooms to the joining peer, so that it can consent before sending its media
+        let recording_states: Vec<(u32, RecordingState)> = self
+            .recordings
+            .iter()
+            .map(|recording| (*recording.key(), *recording.value()))
+            .collect();
+
+        for (room, recording_state) in recording_states {
+            send_voip_header(
+                &self.socket_handle,
+                VoipHeader::new(
+                    VoipMessageType::Control(ControlMessage::RecordingState(recording_state)),
+                    SERVER_AUTHOR,
+                )
+                .with_channel(room),
+                socket_addr,
+            )
+            .await;
+        }
+
+        //Send the roster of the session to the joining peer, the authors joined from several devices are only listed once
+        let roster: HashSet<Uuid> = self
+            .peers
+            .iter()
+            .filter(|peer| peer.author != author)
+            .map(|peer| peer.author)
+            .collect();
+
+        for peer_author in roster {
+            send_control_message(
+                &self.socket_handle,
+                ControlMessage::ParticipantJoined(peer_author),
+                socket_addr,
+            )
+            .await;
+        }
+
+        //Send the presence of the peers to the joining peer, the peers which haven't set any are available
+        let presences: Vec<(Uuid, PresenceState)> = self
+            .peers
+            .iter()
+            .filter(|peer| peer.presence != PresenceState::Available)
+            .map(|peer| (peer.author, peer.presence))
+            .collect();
+
+        for (peer_author, presence) in presences {
+            send_voip_header(
+                &self.socket_handle,
+                VoipHeader::new(
+                    VoipMessageType::Control(ControlMessage::Presence(presence)),
+                    peer_author,
+                ),
+                socket_addr,
+            )
+            .await;
+        }
+
+        //Send the retained media states of the peers to the joining peer
+        let media_states: Vec<(Uuid, u32, MediaState)> = self
+            .peers
+            .iter()
+            .flat_map(|peer| {
+                peer.media_states
+                    .iter()
+                    .map(|(room, media_state)| (peer.author, *room, *media_state))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        for (peer_author, room, media_state) in media_states {
+            send_voip_header(
+                &self.socket_handle,
+                VoipHeader::new(
+                    VoipMessageType::Control(ControlMessage::MediaState(media_state)),
+                    peer_author,
+                )
+                .with_channel(room),
+                socket_addr,
+            )
+            .await;
+        }
+
+        //The joining peer may not decode the codecs the others have agreed on
+        send_session_codecs(&self.socket_handle, &self.peers).await;
+
+        //Signal the devices of the author to each other
+        if self.duplicate_login == DuplicateLoginPolicy::MultiDevice {
+            send_voip_header(
+                &self.socket_handle,
+                VoipHeader::new(
+                    VoipMessageType::Control(ControlMessage::Device(DeviceNotice::Assigned(
+                        device,
+                    ))),
+                    author,
+                ),
+                socket_addr,
+            )
+            .await;
+
+            let other_devices: Vec<SocketAddr> = self
+                .peers
+                .iter()
+                .filter(|peer| peer.author == author && *peer.key() != socket_addr)
+                .map(|peer| *peer.key())
+                .collect();
+
+            for remote_addr in other_devices {
+                send_voip_header(
+                    &self.socket_handle,
+                    VoipHeader::new(
+                        VoipMessageType::Control(ControlMessage::Device(DeviceNotice::Joined(
+                            device,
+                        ))),
+                        author,
+                    ),
+                    remote_addr,
+                )
+                .await;
+            }
+        }
+    }
+
+    ///
+    /// Handles a [`ControlMessage::Goodbye`] (or a [`ControlMessage::Close`]) of the sender at the `socket_addr`.
+    ///
+    /// # Behavior
+    /// The sender is removed from the [`PeerRegistry`] and the [`ClientList`], and [`ServerEvent::PeerLeft`] is broadcast.
+    /// If other devices of its author remain, they are sent a [`DeviceNotice::Left`], otherwise the floors of the author are passed on and the remaining clients are sent a [`ControlMessage::ParticipantLeft`].
+    /// The messages of the unregistered senders are dropped without notifying anyone.
+    ///
+    /// Returns whether the message should be forwarded to the application, which it should if a registered peer has left.
+    ///
+    async fn handle_leave(&mut self, socket_addr: SocketAddr) -> bool {
+        self.client_list.remove(&socket_addr);
+
+        //Only a registered peer can leave, so a spoofed goodbye can't announce the departure of anyone
+        let Some((_, leaving_peer)) = self.peers.remove(&socket_addr) else {
+            return false;
+        };
+
+        let author = leaving_peer.author;
+
+        let _ = self.event_sender.send(ServerEvent::PeerLeft {
+            remote_addr: socket_addr,
+            author,
+        });
+
+        //The author stays in the session while any of its other devices does
+        let other_devices: Vec<SocketAddr> = self
+            .peers
+            .iter()
+            .filter(|peer| peer.author == author)
+            .map(|peer| *peer.key())
+            .collect();
+
+        if !other_devices.is_empty() {
+            for remote_addr in other_devices {
+                send_voip_header(
+                    &self.socket_handle,
+                    VoipHeader::new(
+                        VoipMessageType::Control(ControlMessage::Device(DeviceNotice::Left(
+                            leaving_peer.device,
+                        ))),
+                        author,
+                    ),
+                    remote_addr,
+                )
+                .await;
+            }
+
+            return true;
+        }
+
+        //Pass on the floors of the leaving peer
+        if let Some(floor_control) = self.floor_control.as_mut() {
+            send_floor_notices(
+                &self.socket_handle,
+                &self.peers,
+                floor_control.remove(author, Instant::now()),
+            )
+            .await;
+        }
+
+        //Notify the remaining clients
+        let remote_addrs: Vec<SocketAddr> = self
+            .client_list
+            .iter()
+            .map(|remote_addr| *remote_addr)
+            .collect();
+
+        for remote_addr in remote_addrs {
+            send_control_message(
+                &self.socket_handle,
+                ControlMessage::ParticipantLeft(author),
+                remote_addr,
+            )
+            .await;
+        }
+
+        //The remaining peers may have more codecs in common
+        send_session_codecs(&self.socket_handle, &self.peers).await;
+
+        true
+    }
+
+    /// Stores the `presence` of the sender at the `socket_addr`, and sends it to every other peer with the registered author of the sender.
+    async fn handle_presence(&mut self, presence: PresenceState, socket_addr: SocketAddr) {
+        //Only a registered peer has a presence, and it is always sent with its registered author
+        let Some(author) = self.peers.get_mut(&socket_addr).map(|mut peer| {
+            peer.presence = presence;
+
+            peer.author
+        }) else {
+            return;
+        };
+
+        self.send_to_other_peers(
+            VoipHeader::new(
+                VoipMessageType::Control(ControlMessage::Presence(presence)),
+                author,
+            ),
+            socket_addr,
+        )
+        .await;
+    }
+
+    /// Stores the `media_state` of the sender at the `socket_addr` in the `room`, and sends it to every other peer with the registered author of the sender.
+    async fn handle_media_state(
+        &mut self,
+        media_state: MediaState,
+        room: u32,
+        socket_addr: SocketAddr,
+    ) {
+        //Only a registered peer has media states, and they are always sent with its registered author
+        let Some(author) = self.peers.get_mut(&socket_addr).map(|mut peer| {
+            if media_state == MediaState::default() {
+                peer.media_states.remove(&room);
+            } else {
+                peer.media_states.insert(room, media_state);
+            }
+
+            peer.author
+        }) else {
+            return;
+        };
+
+        self.send_to_other_peers(
+            VoipHeader::new(
+                VoipMessageType::Control(ControlMessage::MediaState(media_state)),
+                author,
+            )
+            .with_channel(room),
+            socket_addr,
+        )
+        .await;
+    }
+
+    /// Stores the `consent` of the sender at the `socket_addr` to the recording of the `room`, and broadcasts [`ServerEvent::RecordingConsentChanged`] if it has changed.
+    fn handle_recording_consent(
+        &mut self,
+        consent: bool,
+        author: Uuid,
+        room: u32,
+        socket_addr: SocketAddr,
+    ) {
+        //The consents to the rooms which aren't recorded are ignored
+        if !self.recordings.contains_key(&room) {
+            return;
+        }
+
+        let is_changed = self.peers.get_mut(&socket_addr).is_some_and(|mut peer| {
+            if consent {
+                peer.recording_consents.insert(room)
+            } else {
+                peer.recording_consents.remove(&room)
+            }
+        });
+
+        if is_changed {
+            let _ = self
+                .event_sender
+                .send(ServerEvent::RecordingConsentChanged {
+                    remote_addr: socket_addr,
+                    author,
+                    room,
+                    consent,
+                });
+        }
+    }
+
+    ///
+    /// Routes the `call_signal` of the sender at the `socket_addr` to its recipient.
+    ///
+    /// # Behavior
+    /// The signal is sent with the registered author of the sender, and the signals of the unregistered senders are dropped.
+    /// The invitations of the recipients whose presence is [`PresenceState::DoNotDisturb`] are answered with a [`CallSignalKind::DoNotDisturb`] on their behalf, and the calls to the recipients which aren't in the session are answered with a [`CallSignalKind::Hangup`].
+    ///
+    async fn handle_call(&mut self, call_signal: &CallSignal, socket_addr: SocketAddr) {
+        //Only a registered peer can call, and its signals are always sent with its registered author
+        let Some(author) = self.peers.get(&socket_addr).map(|peer| peer.author) else {
+            return;
+        };
+
+        let recipient = self
+            .peers
+            .iter()
+            .find(|peer| peer.author == call_signal.recipient)
+            .map(|peer| (*peer.key(), peer.presence));
+
+        match recipient {
+            //Reject the invitations of the recipients who dont want to be disturbed on their behalf
+            Some((_, PresenceState::DoNotDisturb))
+                if call_signal.kind == CallSignalKind::Invite =>
+            {
+                send_voip_header(
+                    &self.socket_handle,
+                    VoipHeader::new(
+                        VoipMessageType::Control(ControlMessage::Call(CallSignal {
+                            call_id: call_signal.call_id,
+                            recipient: author,
+                            kind: CallSignalKind::DoNotDisturb,
+                        })),
+                        call_signal.recipient,
+                    ),
+                    socket_addr,
+                )
+                .await
+            }
+            Some((recipient_addr, _)) => {
+                send_voip_header(
+                    &self.socket_handle,
+                    VoipHeader::new(
+                        VoipMessageType::Control(ControlMessage::Call(*call_signal)),
+                        author,
+                    ),
+                    recipient_addr,
+                )
+                .await
+            }
+            //Hang up the calls to the recipients which aren't in the session
+            None if call_signal.kind != CallSignalKind::Hangup => {
+                send_control_message(
+                    &self.socket_handle,
+                    ControlMessage::Call(CallSignal {
+                        call_id: call_signal.call_id,
+                        recipient: author,
+                        kind: CallSignalKind::Hangup,
+                    }),
+                    socket_addr,
+                )
+                .await
+            }
+            None => (),
+        }
+    }
+}
+
+///
+/// Sends the audio codecs every peer of the session can decode to every peer, with a [`ControlMessage::Codecs`].
+///
+/// # Behavior
+/// Nothing is sent if no peer has advertised any codecs, as every peer decodes [`MediaCodec::Opus`] anyway.
+/// [`MediaCodec::Opus`] is always listed first, as every peer decodes it.
+///
+async fn send_session_codecs<T: Transport>(socket_handle: &T, peers: &PeerRegistry) {
+    if peers.iter().all(|peer| peer.codecs.is_none()) {
+        return;
+    }
+
+    let peer_codecs: Vec<Vec<MediaCodec>> = peers.iter().map(|peer| peer.codecs()).collect();
+
+    let session_codecs: Vec<MediaCodec> = peer_codecs
+        .first()
+        .into_iter()
+        .flatten()
+        .filter(|codec| peer_codecs.iter().all(|codecs| codecs.contains(codec)))
+        .copied()
+        .collect();
+
+    let remote_addrs: Vec<SocketAddr> = peers.iter().map(|peer| *peer.key()).collect();
+
+    for remote_addr in remote_addrs {
+        send_voip_header(
+            socket_handle,
+            VoipHeader::new(
+                VoipMessageType::Control(ControlMessage::Codecs(session_codecs.clone())),
+                SERVER_AUTHOR,
+            ),
+            remote_addr,
+        )
+        .await;
+    }
+}
+
+///
+/// Sends a [`QualityReport`] about every peer to the moderators.
+///
+/// # Behavior
+/// The reports are sent with the [`Uuid`] of the reported peer as their author, and describe the messages the server has received from the peer since the last report.
+/// The lost messages are estimated from the gaps in the sequence numbers of the voice messages, the jitter and the round trip time aren't measured by the server, so they are always `0`.
+///
+async fn send_stats_reports<T: Transport>(
+    socket_handle: &T,
+    peers: &PeerRegistry,
+    moderators: &HashSet<Uuid>,
+) {
+    let moderator_addrs: Vec<SocketAddr> = peers
+        .iter()
+        .filter(|peer| moderators.contains(&peer.author))
+        .map(|peer| *peer.key())
+        .collect();
+
+    let quality_reports: Vec<(Uuid, QualityReport)> = peers
+        .iter_mut()
+        .map(|mut peer| (peer.author, peer.take_report()))
+        .collect();
+
+    for (author, quality_report) in quality_reports {
+        for remote_addr in &moderator_addrs {
+            send_voip_header(
+                socket_handle,
+                VoipHeader::new(
+                    VoipMessageType::Control(ControlMessage::QualityReport(quality_report)),
+                    author,
+                ),
+                *remote_addr,
+            )
+            .await;
+        }
+    }
+}
+
+///
+/// Sends a [`QualityReport`] back to every peer, about the messages the server has received from it since the last feedback.
+///
+/// # Behavior
+/// The reports are sent with [`SERVER_AUTHOR`] as their author, which tells them apart from the reports of the other peers.
+/// The peers which haven't sent anything since the last feedback aren't sent a report.
+///
+async fn send_congestion_feedback<T: Transport>(socket_handle: &T, peers: &PeerRegistry) {
+    let quality_reports: Vec<(SocketAddr, QualityReport)> = peers
+        .iter_mut()
+        .map(|mut peer| (*peer.key(), peer.take_feedback()))
+        .filter(|(_, quality_report)| quality_report.packets_received > 0)
+        .collect();
+
+    for (remote_addr, quality_report) in quality_reports {
+        send_control_message(
+            socket_handle,
+            ControlMessage::QualityReport(quality_report),
+            remote_addr,
+        )
+        .await;
+    }
+}
+
+/// Returns the time the earliest pending [`ControlMessage::KeepaliveProbe`] of the peers is answered at.
+fn earliest_keepalive_probe(peers: &PeerRegistry) -> Option<Instant> {
+    peers
+        .iter()
+        .filter_map(|peer| peer.keepalive_probe.map(|(answer_at, _)| answer_at))
+        .min()
+}
+
+/// Answers the [`ControlMessage::KeepaliveProbe`]s of the peers whose delay has passed, with a [`ControlMessage::Pong`] carrying their sequence number.
+async fn send_keepalive_answers<T: Transport>(socket_handle: &T, peers: &PeerRegistry) {
+    let now = Instant::now();
+    let answers: Vec<(SocketAddr, u32)> = peers
+        .iter_mut()
+        .filter_map(|mut peer| {
+            let (_, sequence) = peer
+                .keepalive_probe
+                .filter(|(answer_at, _)| *answer_at <= now)?;
+
+            peer.keepalive_probe = None;
+
+            Some((*peer.key(), sequence))
+        })
+        .collect();
+
+    for (remote_addr, sequence) in answers {
+        send_control_message(socket_handle, ControlMessage::Pong(sequence), remote_addr).await;
+    }
+}
+
+/// Returns whether the message with the decoded `voip_header` is a video message, which isn't fanned out to the peers which have paused their video.
+fn is_video_message(voip_header: Option<&VoipHeader>) -> bool {
+    voip_header.is_some_and(|voip_header| {
+        matches!(
+            voip_header.voip_message_type(),
+            VoipMessageType::VideoMessage(_)
+        )
+    })
+}
+
+///
+/// Returns whether the media message of `bytes` from the `socket_addr` fits the [`BandwidthLimits`] of its sender and its room, counting it if it does.
+///
+/// # Behavior
+/// Only the voice and video messages of the registered peers are metered.
+/// The sender of a dropped message is sent a [`ControlMessage::MaxBitrate`] once per window, with its cap (or its share of the room's cap).
+///
+async fn admit_bandwidth<T: Transport>(
+    socket_handle: &T,
+    peers: &PeerRegistry,
+    room_meters: &mut HashMap<u32, BandwidthMeter>,
+    bandwidth_limits: &BandwidthLimits,
+    voip_header: &VoipHeader,
+    bytes: u64,
+    socket_addr: SocketAddr,
+) -> bool {
+    let is_voice = match voip_header.voip_message_type() {
+        VoipMessageType::VoiceMessage(_) => true,
+        VoipMessageType::VideoMessage(_) => false,
+        _ => return true,
+    };
+
+    let Some(mut peer) = peers.get_mut(&socket_addr) else {
+        return true;
+    };
+
+    let now = Instant::now();
+    let room = voip_header.channel();
+
+    //Forget the rooms which haven't forwarded anything for a whole window
+    if !room_meters.contains_key(&room) {
+        room_meters.retain(|_, room_meter| !room_meter.is_expired(now, bandwidth_limits));
+    }
+
+    let room_meter = room_meters
+        .entry(room)
+        .or_insert_with(|| BandwidthMeter::new(now));
+
+    let fits_client = peer.bandwidth.fits(
+        now,
+        bandwidth_limits,
+        bandwidth_limits.max_client_bitrate,
+        bytes,
+        is_voice,
+    );
+    let fits_room = room_meter.fits(
+        now,
+        bandwidth_limits,
+        bandwidth_limits.max_room_bitrate,
+        bytes,
+        is_voice,
+    );
+
+    if fits_client && fits_room {
+        peer.bandwidth.record(bytes, is_voice);
+        room_meter.record(bytes, is_voice);
+
+        return true;
+    }
+
+    let is_notified = peer.bandwidth.notify();
+
+    drop(peer);
+
+    if is_notified {
+        let max_bitrate = if fits_client {
+            let room_peers = peers.iter().filter(|peer| peer.room == room).count().max(1);
+
+            bandwidth_limits
+                .max_room_bitrate
+                .map(|max_room_bitrate| max_room_bitrate / room_peers as u32)
+        } else {
+            bandwidth_limits.max_client_bitrate
+        };
+
+        send_control_message(
+            socket_handle,
+            ControlMessage::MaxBitrate(max_bitrate),
+            socket_addr,
+        )
+        .await;
+    }
+
+    false
+}
+
+///
+/// Returns whether the media message of the [`VoipHeader`] from the `socket_addr` complies with the [`RoomPolicy`] of its room, counting it if it does.
+///
+/// # Behavior
+/// Only the media of the registered peers in the rooms with a policy is checked (see the [`policy`](super::policy) module).
+/// The sender of a rejected message is sent a [`ControlMessage::PolicyViolation`] once per window, on the channel of the room.
+///
+async fn admit_room_policy<T: Transport>(
+    socket_handle: &T,
+    peers: &PeerRegistry,
+    room_policies: &RoomPolicies,
+    voip_header: &VoipHeader,
+    socket_addr: SocketAddr,
+) -> bool {
+    let room = voip_header.channel();
+
+    let Some(room_policy) = room_policies.get(&room) else {
+        return true;
+    };
+
+    let Some(mut peer) = peers.get_mut(&socket_addr) else {
+        return true;
+    };
+
+    let media_state = peer.media_state(room);
+
+    let Err(violation) = peer
+        .policy
+        .check(Instant::now(), &room_policy, media_state, voip_header)
+    else {
+        return true;
+    };
+
+    let is_notified = peer.policy.notify();
+
+    drop(peer);
+    drop(room_policy);
+
+    if is_notified {
+        send_voip_header(
+            socket_handle,
+            VoipHeader::new(
+                VoipMessageType::Control(ControlMessage::PolicyViolation(violation)),
+                SERVER_AUTHOR,
+            )
+            .with_channel(room),
+            socket_addr,
+        )
+        .await;
+    }
+
+    false
+}
+
+/// Receives from the `receiver` if there is one, otherwise never completes.
+async fn recv_optional<T>(receiver: &mut Option<Receiver<T>>) -> Option<T> {
+    match receiver {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Returns whether the message is a voice message, whose audio level is at or below the silence threshold.
+fn is_silent(voip_header: &VoipHeader, silence_threshold: Option<u8>) -> bool {
+    match (
+        voip_header.voip_message_type(),
+        voip_header.audio_level(),
+        silence_threshold,
+    ) {
+        (VoipMessageType::VoiceMessage(_), Some(audio_level), Some(silence_threshold)) => {
+            audio_level >= silence_threshold
+        }
+        _ => false,
+    }
+}
+
+/// Signals the changes of the floors with [`ControlMessage::Floor`], the changes of the holders to every peer and the queue positions and denials to their requester.
+async fn send_floor_notices<T: Transport>(
+    socket_handle: &T,
+    peers: &PeerRegistry,
+    floor_notices: Vec<FloorNotice>,
+) {
+    for floor_notice in floor_notices {
+        let (remote_addrs, room, floor_state) = match floor_notice {
+            FloorNotice::Room(room, floor_state) => (
+                peers.iter().map(|peer| *peer.key()).collect(),
+                room,
+                floor_state,
+            ),
+            FloorNotice::Peer(remote_addr, room, floor_state) => {
+                (vec![remote_addr], room, floor_state)
+            }
+        };
+
+        for remote_addr in remote_addrs {
+            send_voip_header(
+                socket_handle,
+                VoipHeader::new(
+                    VoipMessageType::Control(ControlMessage::Floor(floor_state)),
+                    SERVER_AUTHOR,
+                )
+                .with_channel(room),
+                remote_addr,
+            )
+            .await;
+        }
+    }
+}
+
+/// Sends a control message created by the server to the `remote_addr`, logging any errors.
+pub(crate) async fn send_control_message<T: Transport>(
+    socket_handle: &T,
+    control_message: ControlMessage,
+    remote_addr: SocketAddr,
+) {
+    send_voip_header(
+        socket_handle,
+        VoipHeader::new(VoipMessageType::Control(control_message), SERVER_AUTHOR),
+        remote_addr,
+    )
+    .await;
+}
+
+/// Sends a bodyless message created by the server to the `remote_addr`, logging any errors.
+async fn send_voip_header<T: Transport>(
+    socket_handle: &T,
+    voip_header: VoipHeader,
+    remote_addr: SocketAddr,
+) {
+    let voip_packet = match voip_header.create_message_buffer(&[]) {
+        Ok(voip_packet) => voip_packet,
+        Err(err) => {
+            event!(Level::ERROR, "Failed to encode a control message: {err}");
+
+            return;
+        }
+    };
+
+    if let Err(err) = socket_handle
+        .send_datagram(voip_packet.inner(), remote_addr)
+        .await
+    {
+        event!(
+            Level::ERROR,
+            "Failed to send message to {remote_addr}: {err}"
+        );
     }
 }
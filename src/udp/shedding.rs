@@ -0,0 +1,238 @@
+//!
+//! Provides the load shedding of the [`Server`](super::server::Server), which keeps the most important media flowing when the server can't keep up with all of it.
+//!
+//! The load is evaluated periodically from the depth of the message queues of the server, and the lag of its service loop (which grows when the CPU is saturated).
+//! While the server is overloaded, the shedding escalates by one step every evaluation: first the video messages are dropped, then the media of the rooms with the lowest [priority](LoadSheddingConfig::room_priorities) is paused, one priority at a time.
+//! The rooms with the highest priority are never paused. The shedding steps back one step every evaluation once the load has dropped below half of the thresholds.
+//! The text and control messages are never dropped, and every change of the shedding is reported with a [`ServerEvent::Overloaded`](super::server::ServerEvent::Overloaded) or a [`ServerEvent::OverloadRelieved`](super::server::ServerEvent::OverloadRelieved).
+//!
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::packet::{VoipHeader, VoipMessageType};
+
+///
+/// Load shedding configuration type definition.
+///
+/// Describes when the [`Server`](super::server::Server) is considered overloaded, and which rooms are paused first.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadSheddingConfig {
+    /// The interval the load is evaluated at, the shedding changes by at most one step per evaluation.
+    pub interval: Duration,
+
+    /// The amount of messages waiting in the queues of the server (to be received by the application, or to be sent to the clients) from which it is overloaded.
+    /// The queue depth is ignored if this is [`None`].
+    pub max_queue_depth: Option<usize>,
+
+    /// The delay of the evaluations behind their schedule from which the server is overloaded, which grows when the service loop is starved of CPU.
+    /// The lag is ignored if this is [`None`].
+    pub max_loop_lag: Option<Duration>,
+
+    /// The priority of every channel (or room), the rooms with lower priorities are paused first.
+    /// The rooms without a priority have the lowest priority, `0`.
+    pub room_priorities: HashMap<u32, u8>,
+}
+
+impl Default for LoadSheddingConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            max_queue_depth: Some(192),
+            max_loop_lag: Some(Duration::from_millis(50)),
+            room_priorities: HashMap::new(),
+        }
+    }
+}
+
+///
+/// Shedding type definition.
+///
+/// Describes the media the [`Server`](super::server::Server) drops while it is overloaded.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Shedding {
+    /// Whether the video messages are dropped.
+    pub video_dropped: bool,
+
+    /// The channels of the rooms whose voice and video messages are dropped, in ascending order.
+    pub paused_rooms: Vec<u32>,
+}
+
+impl Shedding {
+    /// Returns whether no media is dropped.
+    pub fn is_empty(&self) -> bool {
+        !self.video_dropped && self.paused_rooms.is_empty()
+    }
+}
+
+/// A change of the shedding, which is reported with a [`ServerEvent`](super::server::ServerEvent).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LoadChange {
+    /// The server is overloaded, and sheds more (or different) media.
+    Overloaded {
+        /// The queue depth of the evaluation.
+        queue_depth: usize,
+        /// The loop lag of the evaluation.
+        loop_lag: Duration,
+        /// The media dropped from now on.
+        shedding: Shedding,
+    },
+
+    /// The load of the server has dropped, and it sheds less media.
+    Relieved(Shedding),
+}
+
+/// Evaluates the load of the server, and decides which media is dropped.
+#[derive(Debug, Clone)]
+pub(crate) struct LoadShedder {
+    /// The configuration of the shedding.
+    config: LoadSheddingConfig,
+
+    /// The step of the shedding, `0` drops nothing, `1` drops the video, and every further step pauses the rooms of one more priority.
+    level: usize,
+
+    /// The media dropped at the current step.
+    shedding: Shedding,
+
+    /// The time the next evaluation is scheduled at.
+    next_evaluation: Instant,
+}
+
+impl LoadShedder {
+    /// Creates a new [`LoadShedder`] instance, with its first evaluation scheduled one interval after `now`.
+    pub(crate) fn new(config: LoadSheddingConfig, now: Instant) -> Self {
+        let next_evaluation = now + config.interval;
+
+        Self {
+            config,
+            level: 0,
+            shedding: Shedding::default(),
+            next_evaluation,
+        }
+    }
+
+    /// Returns the time the next evaluation is scheduled at.
+    pub(crate) fn next_evaluation(&self) -> Instant {
+        self.next_evaluation
+    }
+
+    /// Returns whether any media is dropped.
+    pub(crate) fn is_shedding(&self) -> bool {
+        !self.shedding.is_empty()
+    }
+
+    /// Returns the priority of the `room`.
+    fn priority(&self, room: u32) -> u8 {
+        self.config
+            .room_priorities
+            .get(&room)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    ///
+    /// Evaluates the load at `now`, from the `queue_depth` of the server and the lag of the evaluation behind its schedule.
+    /// Returns the change of the shedding, if it has changed.
+    ///
+    /// # Behavior
+    /// The rooms are paused from the `active_rooms` (the rooms which have peers), so the rooms joined later are paused at the next evaluation.
+    ///
+    pub(crate) fn evaluate(
+        &mut self,
+        now: Instant,
+        queue_depth: usize,
+        active_rooms: &HashSet<u32>,
+    ) -> Option<LoadChange> {
+        let loop_lag = now.saturating_duration_since(self.next_evaluation);
+
+        self.next_evaluation = now + self.config.interval;
+
+        let is_overloaded = self
+            .config
+            .max_queue_depth
+            .is_some_and(|max_queue_depth| queue_depth >= max_queue_depth)
+            || self
+                .config
+                .max_loop_lag
+                .is_some_and(|max_loop_lag| loop_lag >= max_loop_lag);
+
+        let is_relieved = self
+            .config
+            .max_queue_depth
+            .is_none_or(|max_queue_depth| queue_depth <= max_queue_depth / 2)
+            && self
+                .config
+                .max_loop_lag
+                .is_none_or(|max_loop_lag| loop_lag <= max_loop_lag / 2);
+
+        let priorities: BTreeSet<u8> = active_rooms
+            .iter()
+            .map(|room| self.priority(*room))
+            .collect();
+
+        //The highest priority is never paused
+        let max_level = priorities.len().max(1);
+
+        let level = if is_overloaded {
+            self.level + 1
+        } else if is_relieved {
+            self.level.saturating_sub(1)
+        } else {
+            self.level
+        }
+        .min(max_level);
+
+        let paused_priorities: BTreeSet<u8> = priorities
+            .into_iter()
+            .take(level.saturating_sub(1))
+            .collect();
+
+        let mut paused_rooms: Vec<u32> = active_rooms
+            .iter()
+            .copied()
+            .filter(|room| paused_priorities.contains(&self.priority(*room)))
+            .collect();
+
+        paused_rooms.sort_unstable();
+
+        let shedding = Shedding {
+            video_dropped: level > 0,
+            paused_rooms,
+        };
+
+        let previous_level = std::mem::replace(&mut self.level, level);
+
+        if shedding == self.shedding {
+            return None;
+        }
+
+        self.shedding = shedding.clone();
+
+        if level < previous_level {
+            Some(LoadChange::Relieved(shedding))
+        } else {
+            Some(LoadChange::Overloaded {
+                queue_depth,
+                loop_lag,
+                shedding,
+            })
+        }
+    }
+
+    /// Returns whether the message of the `voip_header` is forwarded under the current shedding.
+    pub(crate) fn admits(&self, voip_header: &VoipHeader) -> bool {
+        let is_paused = || self.shedding.paused_rooms.contains(&voip_header.channel());
+
+        match voip_header.voip_message_type() {
+            VoipMessageType::VoiceMessage(_) => !is_paused(),
+            VoipMessageType::VideoMessage(_) => !self.shedding.video_dropped && !is_paused(),
+            _ => true,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//!
+//! Provides the simulcast layer selection of the [`Server`](super::server::Server).
+//!
+//! A simulcasting author sends its video in multiple layers (for example in multiple resolutions), and tags every video message with its [layer](crate::packet::VoipHeader::layer).
+//! Every receiver selects the layer it gets from each author with a [`ControlMessage::SelectLayer`](crate::packet::control::ControlMessage::SelectLayer), and the server only forwards that layer to it.
+//! The forwarded layer is only switched on a keyframe of the new layer, so the receivers never get a frame referencing frames they haven't received.
+//!
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::packet::{control::LayerSelection, VoipHeader};
+
+/// The time after which a layer an author has stopped sending isn't selected anymore.
+pub const LAYER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The layers every author is sending, with the time of their latest message.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ActiveLayers(HashMap<Uuid, BTreeMap<u8, Instant>>);
+
+impl ActiveLayers {
+    /// Marks the layer of the message active, and forgets the layers of the author which have timed out.
+    pub(crate) fn observe(&mut self, voip_header: &VoipHeader, now: Instant) {
+        let Some(layer) = voip_header.layer() else {
+            return;
+        };
+
+        //Forget the authors which have stopped simulcasting
+        if !self.0.contains_key(&voip_header.author()) {
+            self.0.retain(|_, layers| {
+                layers
+                    .values()
+                    .any(|last_seen| now.duration_since(*last_seen) < LAYER_TIMEOUT)
+            });
+        }
+
+        let layers = self.0.entry(voip_header.author()).or_default();
+
+        layers.insert(layer, now);
+        layers.retain(|_, last_seen| now.duration_since(*last_seen) < LAYER_TIMEOUT);
+    }
+
+    /// Returns the active layer of the `author` matching the [`LayerSelection`], [`None`] if the author isn't simulcasting.
+    fn resolve(&self, author: Uuid, layer_selection: LayerSelection) -> Option<u8> {
+        let layers = self.0.get(&author)?;
+
+        match layer_selection {
+            LayerSelection::Auto => layers.keys().next_back().copied(),
+            LayerSelection::Layer(layer) => layers
+                .range(..=layer)
+                .next_back()
+                .or_else(|| layers.iter().next())
+                .map(|(layer, _)| *layer),
+        }
+    }
+}
+
+/// The layers a receiver has selected, and the layers it is being forwarded.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LayerRouting {
+    /// The [`LayerSelection`] of every author, the authors without one are [`LayerSelection::Auto`].
+    selections: HashMap<Uuid, LayerSelection>,
+
+    /// The layer of every author which is currently forwarded.
+    forwarded: HashMap<Uuid, u8>,
+}
+
+impl LayerRouting {
+    /// Selects the layer of the `author`, which is switched to on its next keyframe.
+    pub(crate) fn select(&mut self, author: Uuid, layer_selection: LayerSelection) {
+        self.selections.insert(author, layer_selection);
+    }
+
+    /// Returns the [`LayerSelection`] of every author the receiver has selected a layer from.
+    pub(crate) fn selections(&self) -> &HashMap<Uuid, LayerSelection> {
+        &self.selections
+    }
+
+    ///
+    /// Returns whether the message should be forwarded to the receiver.
+    ///
+    /// # Behavior
+    /// The messages without a layer are always forwarded.
+    /// The forwarded layer is switched to the selected one on its first keyframe, until then the previous layer is forwarded.
+    ///
+    pub(crate) fn admit(&mut self, voip_header: &VoipHeader, active_layers: &ActiveLayers) -> bool {
+        let Some(layer) = voip_header.layer() else {
+            return true;
+        };
+
+        let author = voip_header.author();
+        let layer_selection = self.selections.get(&author).copied().unwrap_or_default();
+        let target_layer = active_layers
+            .resolve(author, layer_selection)
+            .unwrap_or(layer);
+
+        if layer == target_layer && voip_header.is_marker() {
+            self.forwarded.insert(author, layer);
+
+            return true;
+        }
+
+        self.forwarded.get(&author) == Some(&layer)
+    }
+}
@@ -0,0 +1,193 @@
+//!
+//! Provides [`rodio`] [`Source`]s, which play out the voice received by the [`Client`](super::client::Client).
+//!
+//! Desktop applications already using [`rodio`] can append the sources to their existing [`Sink`](rodio::Sink)s (or mix them into their own sources), instead of driving the playout themselves.
+//! The [`MixedSource`] plays out the voice of every author mixed together, while the [`StreamSource`] plays out the voice of a single author (for example to route it to a different device).
+//!
+
+use std::{sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+use rodio::Source;
+use uuid::Uuid;
+
+use super::playout::Playout;
+
+/// The duration of the samples the sources pull from the [`Playout`] at once.
+const PULL_DURATION: Duration = Duration::from_millis(10);
+
+/// The samples pulled from the [`Playout`], which are handed out one by one.
+#[derive(Debug)]
+struct PulledSamples {
+    /// The [`Playout`] the samples are pulled from.
+    playout: Arc<Mutex<Playout>>,
+
+    /// The sample rate of the samples.
+    sample_rate: u32,
+
+    /// The amount of interleaved channels of the samples.
+    channels: u16,
+
+    /// The pulled samples.
+    samples: Vec<f32>,
+
+    /// The index of the next sample handed out.
+    index: usize,
+}
+
+impl PulledSamples {
+    /// Creates a new [`PulledSamples`] instance.
+    fn new(playout: Arc<Mutex<Playout>>) -> Self {
+        let (sample_rate, channels) = {
+            let playout = playout.lock();
+
+            (playout.sample_rate(), playout.channels())
+        };
+
+        let sample_count = (sample_rate as f64 * PULL_DURATION.as_secs_f64()) as usize * channels;
+
+        Self {
+            playout,
+            sample_rate,
+            channels: channels as u16,
+            samples: vec![0.; sample_count.max(channels)],
+            index: sample_count.max(channels),
+        }
+    }
+
+    /// Hands out the next sample, pulling the next samples with `pull` when every sample was handed out.
+    fn next_sample(&mut self, pull: impl FnOnce(&mut Playout, &mut [f32])) -> f32 {
+        if self.index == self.samples.len() {
+            pull(&mut self.playout.lock(), &mut self.samples);
+
+            self.index = 0;
+        }
+
+        let sample = self.samples[self.index];
+
+        self.index += 1;
+
+        sample
+    }
+}
+
+///
+/// Mixed source type definition.
+///
+/// An endless [`Source`], playing out the voice of every author mixed together (see [`Playout::pull_mixed`]).
+///
+#[derive(Debug)]
+pub struct MixedSource {
+    /// The samples pulled from the [`Playout`].
+    pulled_samples: PulledSamples,
+}
+
+impl MixedSource {
+    /// Creates a new [`MixedSource`] instance, playing out the voice of the [`Playout`].
+    pub fn new(playout: Arc<Mutex<Playout>>) -> Self {
+        Self {
+            pulled_samples: PulledSamples::new(playout),
+        }
+    }
+}
+
+impl Iterator for MixedSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(
+            self.pulled_samples
+                .next_sample(|playout, samples| playout.pull_mixed(samples)),
+        )
+    }
+}
+
+impl Source for MixedSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.pulled_samples.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.pulled_samples.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+///
+/// Stream source type definition.
+///
+/// An endless [`Source`], playing out the voice of a single author (see [`Playout::pull_author`]).
+/// The voice of the author is left out of the mix while the source exists.
+///
+#[derive(Debug)]
+pub struct StreamSource {
+    /// The author whose voice is played out.
+    author: Uuid,
+
+    /// The samples pulled from the [`Playout`].
+    pulled_samples: PulledSamples,
+}
+
+impl StreamSource {
+    /// Creates a new [`StreamSource`] instance, playing out the voice of the `author` from the [`Playout`].
+    pub fn new(playout: Arc<Mutex<Playout>>, author: Uuid) -> Self {
+        playout.lock().detach_author(author);
+
+        Self {
+            author,
+            pulled_samples: PulledSamples::new(playout),
+        }
+    }
+
+    /// Returns the author whose voice is played out.
+    pub fn author(&self) -> Uuid {
+        self.author
+    }
+}
+
+impl Iterator for StreamSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let author = self.author;
+
+        Some(self.pulled_samples.next_sample(|playout, samples| {
+            playout.pull_author(author, samples);
+        }))
+    }
+}
+
+impl Source for StreamSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.pulled_samples.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.pulled_samples.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Drop for StreamSource {
+    fn drop(&mut self) {
+        //Put the author back into the mix
+        self.pulled_samples
+            .playout
+            .lock()
+            .attach_author(self.author);
+    }
+}
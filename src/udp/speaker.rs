@@ -0,0 +1,229 @@
+//!
+//! Provides the [`ActiveSpeakerDetector`], which computes the dominant speaker of every channel from the [audio level](crate::packet::VoipHeader::audio_level) of the voice messages.
+//!
+//! Video UIs can use the [`ActiveSpeakerChange`]s to switch the speaker view, without decoding any of the streams.
+//! The loudness of every speaker is smoothed, and a new speaker has to be louder than the current one by a margin to take over, so the dominant speaker doesn't flicker between participants talking over each other.
+//!
+
+use std::{collections::HashMap, time::Duration};
+
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::packet::AUDIO_LEVEL_SILENCE;
+
+/// The weight of the latest audio level in the smoothed loudness of a speaker.
+const LOUDNESS_SMOOTHING: f32 = 0.3;
+
+///
+/// Active speaker detection configuration type definition.
+///
+/// Contains the thresholds the [`ActiveSpeakerDetector`] uses.
+///
+#[derive(Debug, Clone)]
+pub struct ActiveSpeakerConfig {
+    /// The audio level (in -dBov) at or below which a voice message counts as speech.
+    pub activity_threshold: u8,
+
+    /// The amount (in dB) a speaker has to be louder than the current dominant speaker to take over.
+    pub switch_margin: f32,
+
+    /// The duration after which a speaker, who hasn't spoken, is no longer considered active.
+    pub speaker_timeout: Duration,
+}
+
+impl Default for ActiveSpeakerConfig {
+    fn default() -> Self {
+        Self {
+            activity_threshold: 60,
+            switch_margin: 6.,
+            speaker_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A change of the dominant speaker of a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveSpeakerChange {
+    /// The channel (or room) the dominant speaker has changed in.
+    pub channel: u32,
+
+    /// The new dominant speaker, or [`None`] if nobody is speaking.
+    pub speaker: Option<Uuid>,
+}
+
+/// The activity of a single speaker.
+#[derive(Debug, Clone, Copy)]
+struct SpeakerActivity {
+    /// The smoothed loudness of the speaker, in dB above silence.
+    loudness: f32,
+
+    /// The time the speaker has last spoken.
+    last_active: Option<Instant>,
+
+    /// The time the last voice message was received from the speaker.
+    last_heard: Instant,
+}
+
+/// The speakers of a single channel.
+#[derive(Debug, Clone, Default)]
+struct ChannelSpeakers {
+    /// The current dominant speaker of the channel.
+    dominant: Option<Uuid>,
+
+    /// The activity of every speaker heard in the channel.
+    speakers: HashMap<Uuid, SpeakerActivity>,
+}
+
+///
+/// Active speaker detector type definition.
+///
+/// Tracks the loudness of every speaker, and reports the changes of the dominant speaker of every channel.
+///
+#[derive(Debug, Clone)]
+pub struct ActiveSpeakerDetector {
+    /// The configuration of the detector.
+    config: ActiveSpeakerConfig,
+
+    /// The speakers of every channel.
+    channels: HashMap<u32, ChannelSpeakers>,
+}
+
+impl ActiveSpeakerDetector {
+    /// Creates a new [`ActiveSpeakerDetector`] instance.
+    pub fn new(config: ActiveSpeakerConfig) -> Self {
+        Self {
+            config,
+            channels: HashMap::new(),
+        }
+    }
+
+    /// Returns the current dominant speaker of the `channel`.
+    pub fn dominant_speaker(&self, channel: u32) -> Option<Uuid> {
+        self.channels
+            .get(&channel)
+            .and_then(|channel_speakers| channel_speakers.dominant)
+    }
+
+    /// Records a voice message of the `author` in the `channel` with the `audio_level`, received at `now`.
+    /// Returns the [`ActiveSpeakerChange`] if the dominant speaker of the channel has changed.
+    pub fn observe(
+        &mut self,
+        channel: u32,
+        author: Uuid,
+        audio_level: u8,
+        now: Instant,
+    ) -> Option<ActiveSpeakerChange> {
+        let loudness = (AUDIO_LEVEL_SILENCE - audio_level.min(AUDIO_LEVEL_SILENCE)) as f32;
+        let is_speaking = audio_level <= self.config.activity_threshold;
+
+        let channel_speakers = self.channels.entry(channel).or_default();
+
+        channel_speakers
+            .speakers
+            .entry(author)
+            .and_modify(|activity| {
+                activity.loudness += (loudness - activity.loudness) * LOUDNESS_SMOOTHING;
+                activity.last_heard = now;
+
+                if is_speaking {
+                    activity.last_active = Some(now);
+                }
+            })
+            .or_insert(SpeakerActivity {
+                loudness,
+                last_active: is_speaking.then_some(now),
+                last_heard: now,
+            });
+
+        self.select_dominant(channel, now)
+    }
+
+    /// Forgets the speakers which haven't been heard for the speaker timeout, and re-evaluates the dominant speaker of every channel.
+    /// Returns the [`ActiveSpeakerChange`]s of the channels whose dominant speaker has changed.
+    pub fn expire(&mut self, now: Instant) -> Vec<ActiveSpeakerChange> {
+        let speaker_timeout = self.config.speaker_timeout;
+
+        for channel_speakers in self.channels.values_mut() {
+            channel_speakers
+                .speakers
+                .retain(|_, activity| now.duration_since(activity.last_heard) < speaker_timeout);
+        }
+
+        let channels: Vec<u32> = self.channels.keys().copied().collect();
+
+        let changes = channels
+            .into_iter()
+            .filter_map(|channel| self.select_dominant(channel, now))
+            .collect();
+
+        //Forget the channels nobody speaks in
+        self.channels.retain(|_, channel_speakers| {
+            channel_speakers.dominant.is_some() || !channel_speakers.speakers.is_empty()
+        });
+
+        changes
+    }
+
+    /// Returns the time the dominant speaker of any channel times out at, which is when [`ActiveSpeakerDetector::expire`] should be called next.
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.channels
+            .values()
+            .filter_map(|channel_speakers| {
+                let activity = channel_speakers.speakers.get(&channel_speakers.dominant?)?;
+
+                Some(activity.last_active? + self.config.speaker_timeout)
+            })
+            .min()
+    }
+
+    /// Selects the dominant speaker of the `channel`, and returns the [`ActiveSpeakerChange`] if it has changed.
+    fn select_dominant(&mut self, channel: u32, now: Instant) -> Option<ActiveSpeakerChange> {
+        let speaker_timeout = self.config.speaker_timeout;
+        let switch_margin = self.config.switch_margin;
+
+        let channel_speakers = self.channels.get_mut(&channel)?;
+
+        let is_active = |activity: &SpeakerActivity| {
+            activity
+                .last_active
+                .is_some_and(|last_active| now.duration_since(last_active) < speaker_timeout)
+        };
+
+        let loudest = channel_speakers
+            .speakers
+            .iter()
+            .filter(|(_, activity)| is_active(activity))
+            .max_by(|(_, lhs), (_, rhs)| lhs.loudness.total_cmp(&rhs.loudness))
+            .map(|(author, activity)| (*author, activity.loudness));
+
+        let current = channel_speakers.dominant.and_then(|dominant| {
+            channel_speakers
+                .speakers
+                .get(&dominant)
+                .filter(|activity| is_active(activity))
+                .map(|activity| (dominant, activity.loudness))
+        });
+
+        //The current dominant speaker keeps the floor, unless the loudest speaker is louder by the margin
+        let dominant = match (current, loudest) {
+            (Some((current, current_loudness)), Some((_, loudest_loudness)))
+                if loudest_loudness <= current_loudness + switch_margin =>
+            {
+                Some(current)
+            }
+            (_, loudest) => loudest.map(|(author, _)| author),
+        };
+
+        if dominant == channel_speakers.dominant {
+            return None;
+        }
+
+        channel_speakers.dominant = dominant;
+
+        Some(ActiveSpeakerChange {
+            channel,
+            speaker: dominant,
+        })
+    }
+}
@@ -0,0 +1,268 @@
+//!
+//! Provides the persistence layer of the [`Server`](super::server::Server).
+//!
+//! The [`ServerStore`] keeps the bans, the room definitions and the invite tokens in a [`sled`] database, so a relay restart doesn't forget the moderation decisions.
+//! When a store is set in the [`ServerConfig`](super::server::ServerConfig), the server restores its bans and rooms from it, and saves every change made through the [`ServerHandle`](super::server::ServerHandle).
+//! The invite tokens are only stored, redeeming them is up to the application (for example while authenticating its users).
+//!
+
+use std::{collections::HashMap, path::Path, time::SystemTime};
+
+use uuid::Uuid;
+
+use super::server::Ban;
+use crate::packet::control::RoomPolicy;
+
+/// Custom store errors.
+#[derive(thiserror::Error, Debug)]
+pub enum StoreError {
+    /// This error is thrown when the database has failed to open, read or write.
+    #[error("Database error: {0}")]
+    Database(#[from] sled::Error),
+
+    /// This error is thrown when a stored value could not be serialized or deserialized.
+    #[error("Failed to (de)serialize a stored value: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+///
+/// Invite type definition.
+///
+/// Describes the room an invite token grants access to.
+///
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Invite {
+    /// The channel of the room the invite is for.
+    pub room: u32,
+
+    /// The time the invite expires at, it never expires if this is [`None`].
+    pub expires_at: Option<SystemTime>,
+
+    /// The amount of times the invite can still be redeemed, it can be redeemed any amount of times if this is [`None`].
+    pub remaining_uses: Option<u32>,
+}
+
+impl Invite {
+    /// Creates a new [`Invite`] instance for the `room`, which never expires and can be redeemed any amount of times.
+    pub fn new(room: u32) -> Self {
+        Self {
+            room,
+            expires_at: None,
+            remaining_uses: None,
+        }
+    }
+
+    /// Sets the time the invite expires at.
+    pub fn with_expires_at(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Sets the amount of times the invite can be redeemed.
+    pub fn with_remaining_uses(mut self, remaining_uses: u32) -> Self {
+        self.remaining_uses = Some(remaining_uses);
+        self
+    }
+
+    /// Returns whether the invite can't be redeemed anymore.
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining_uses == Some(0)
+            || self
+                .expires_at
+                .is_some_and(|expires_at| expires_at <= SystemTime::now())
+    }
+}
+
+///
+/// Server store type definition.
+///
+/// A cloneable handle to the database the [`Server`](super::server::Server) persists its state in.
+/// The values are stored as JSON, so the database can be inspected with any sled tooling.
+///
+#[derive(Debug, Clone)]
+pub struct ServerStore {
+    /// The database of the store, which owns the trees below.
+    db: sled::Db,
+
+    /// The bans by the [`Uuid`] of the banned author.
+    bans: sled::Tree,
+
+    /// The [`RoomPolicy`] of every room by its channel.
+    rooms: sled::Tree,
+
+    /// The invites by their tokens.
+    invites: sled::Tree,
+}
+
+impl ServerStore {
+    /// Opens (or creates) the store at the `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::from_db(sled::open(path)?)
+    }
+
+    /// Creates a store which is deleted once every handle to it has been dropped (for example for tests).
+    pub fn temporary() -> Result<Self, StoreError> {
+        Self::from_db(sled::Config::new().temporary(true).open()?)
+    }
+
+    /// Creates a store from an already opened database.
+    pub fn from_db(db: sled::Db) -> Result<Self, StoreError> {
+        Ok(Self {
+            bans: db.open_tree("bans")?,
+            rooms: db.open_tree("rooms")?,
+            invites: db.open_tree("invites")?,
+            db,
+        })
+    }
+
+    /// Saves the [`Ban`] of the `author`, replacing its previous ban.
+    pub fn save_ban(&self, author: Uuid, ban: &Ban) -> Result<(), StoreError> {
+        self.bans
+            .insert(author.as_bytes(), serde_json::to_vec(ban)?)?;
+
+        Ok(())
+    }
+
+    /// Removes the ban of the `author`, returning it if there was one.
+    pub fn remove_ban(&self, author: Uuid) -> Result<Option<Ban>, StoreError> {
+        self.bans
+            .remove(author.as_bytes())?
+            .map(|ban| serde_json::from_slice(&ban))
+            .transpose()
+            .map_err(StoreError::from)
+    }
+
+    /// Returns every stored [`Ban`] by the [`Uuid`] of the banned author.
+    pub fn bans(&self) -> Result<HashMap<Uuid, Ban>, StoreError> {
+        self.bans
+            .iter()
+            .map(|entry| {
+                let (author, ban) = entry?;
+
+                Ok((
+                    Uuid::from_slice(&author).unwrap_or_default(),
+                    serde_json::from_slice(&ban)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Saves the [`RoomPolicy`] of the `room`, replacing its previous policy.
+    pub fn save_room(&self, room: u32, room_policy: &RoomPolicy) -> Result<(), StoreError> {
+        self.rooms
+            .insert(room.to_be_bytes(), serde_json::to_vec(room_policy)?)?;
+
+        Ok(())
+    }
+
+    /// Removes the `room`, returning its [`RoomPolicy`] if it was stored.
+    pub fn remove_room(&self, room: u32) -> Result<Option<RoomPolicy>, StoreError> {
+        self.rooms
+            .remove(room.to_be_bytes())?
+            .map(|room_policy| serde_json::from_slice(&room_policy))
+            .transpose()
+            .map_err(StoreError::from)
+    }
+
+    /// Returns the [`RoomPolicy`] of every stored room by its channel.
+    pub fn rooms(&self) -> Result<HashMap<u32, RoomPolicy>, StoreError> {
+        self.rooms
+            .iter()
+            .map(|entry| {
+                let (room, room_policy) = entry?;
+
+                Ok((
+                    <[u8; 4]>::try_from(&room[..])
+                        .map(u32::from_be_bytes)
+                        .unwrap_or_default(),
+                    serde_json::from_slice(&room_policy)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Saves the [`Invite`] under the `token`, replacing the previous invite of the token.
+    pub fn save_invite(&self, token: &str, invite: &Invite) -> Result<(), StoreError> {
+        self.invites.insert(token, serde_json::to_vec(invite)?)?;
+
+        Ok(())
+    }
+
+    /// Removes the invite of the `token`, returning it if there was one.
+    pub fn remove_invite(&self, token: &str) -> Result<Option<Invite>, StoreError> {
+        self.invites
+            .remove(token)?
+            .map(|invite| serde_json::from_slice(&invite))
+            .transpose()
+            .map_err(StoreError::from)
+    }
+
+    ///
+    /// Redeems the invite of the `token`.
+    ///
+    /// # Behavior
+    /// Returns the [`Invite`] as it was before redeeming it, or [`None`] if there is no such invite or it is exhausted.
+    /// The remaining uses of the invite are decremented atomically, and the invite is removed once it is exhausted.
+    ///
+    /// # Error
+    /// Returns an error if the database could not be accessed, or the stored invite could not be deserialized.
+    ///
+    pub fn redeem_invite(&self, token: &str) -> Result<Option<Invite>, StoreError> {
+        loop {
+            let Some(stored_invite) = self.invites.get(token)? else {
+                return Ok(None);
+            };
+
+            let invite: Invite = serde_json::from_slice(&stored_invite)?;
+
+            //Remove the exhausted invites, whether or not another task has changed them since
+            if invite.is_exhausted() {
+                let _ =
+                    self.invites
+                        .compare_and_swap(token, Some(&stored_invite), None::<&[u8]>)?;
+
+                return Ok(None);
+            }
+
+            let redeemed_invite = match invite.remaining_uses {
+                Some(1) => None,
+                Some(remaining_uses) => Some(serde_json::to_vec(&Invite {
+                    remaining_uses: Some(remaining_uses - 1),
+                    ..invite.clone()
+                })?),
+                None => Some(stored_invite.to_vec()),
+            };
+
+            //Retry if another task has redeemed the invite in the meantime
+            if self
+                .invites
+                .compare_and_swap(token, Some(&stored_invite), redeemed_invite)?
+                .is_ok()
+            {
+                return Ok(Some(invite));
+            }
+        }
+    }
+
+    /// Returns every stored [`Invite`] by its token.
+    pub fn invites(&self) -> Result<HashMap<String, Invite>, StoreError> {
+        self.invites
+            .iter()
+            .map(|entry| {
+                let (token, invite) = entry?;
+
+                Ok((
+                    String::from_utf8_lossy(&token).into_owned(),
+                    serde_json::from_slice(&invite)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Writes every pending change to the disk, returning once it is durable.
+    pub async fn flush(&self) -> Result<(), StoreError> {
+        self.db.flush_async().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//!
+//! Provides the [`VoiceTap`], which exposes the decoded voice of the speakers received by the [`Client`](super::client::Client).
+//!
+//! Applications transcribing the session (for example feeding a speech-to-text engine for live captions) can tap the voice with [`Client::tap_voice`](super::client::Client::tap_voice), instead of decoding it a second time.
+//! The tap receives the same [`DecodedVoiceFrame`]s the [`Playout`](super::playout::Playout) does, right after they are decoded, so the frames aren't delayed by the jitter buffer.
+//! The voice is only tapped while [`VoiceConfig::decode_received`](super::voice::VoiceConfig::decode_received) is enabled.
+//! The end of a stream is tapped as an empty frame with [`DecodedVoiceFrame::end_of_stream`] set, so for example the recording of the stream can be finalized right away.
+//!
+
+use std::{sync::Arc, time::SystemTime};
+
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use super::voice::DecodedVoiceFrame;
+
+/// The amount of tapped frames buffered for every [`VoiceTap`], the oldest frames are skipped if a tap lags behind.
+pub const VOICE_TAP_CAPACITY: usize = 255;
+
+///
+/// Tapped voice frame type definition.
+///
+/// A [`DecodedVoiceFrame`] received through a [`VoiceTap`], with the time it was decoded at.
+///
+#[derive(Debug, Clone)]
+pub struct TappedVoiceFrame {
+    /// The wall clock time the frame was decoded at, which can be used to align the transcripts of the speakers.
+    pub decoded_at: SystemTime,
+
+    /// The decoded frame, which is shared between every tap.
+    pub frame: Arc<DecodedVoiceFrame>,
+}
+
+///
+/// Voice tap type definition.
+///
+/// Receives the decoded voice of every speaker, or of a single speaker (see [`VoiceTap::with_author`]).
+///
+#[derive(Debug)]
+pub struct VoiceTap {
+    /// The receiver of the tapped frames.
+    receiver: broadcast::Receiver<TappedVoiceFrame>,
+
+    /// The author whose frames are received, or [`None`] to receive every frame.
+    author: Option<Uuid>,
+}
+
+impl VoiceTap {
+    /// Creates a new [`VoiceTap`] instance, receiving every frame of the `receiver`.
+    pub(crate) fn new(receiver: broadcast::Receiver<TappedVoiceFrame>) -> Self {
+        Self {
+            receiver,
+            author: None,
+        }
+    }
+
+    /// Returns the tap only receiving the frames of the `author`.
+    pub fn with_author(mut self, author: Uuid) -> Self {
+        self.author = Some(author);
+
+        self
+    }
+
+    /// Receives the next tapped frame, waiting for one if there is none.
+    /// Returns [`None`] once the [`Client`](super::client::Client) has been dropped.
+    pub async fn recv(&mut self) -> Option<TappedVoiceFrame> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(tapped_frame) if self.is_tapped(&tapped_frame) => return Some(tapped_frame),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => Self::log_skipped(skipped),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Receives the next tapped frame, or returns [`None`] if there is none yet.
+    pub fn try_recv(&mut self) -> Option<TappedVoiceFrame> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(tapped_frame) if self.is_tapped(&tapped_frame) => return Some(tapped_frame),
+                Ok(_) => continue,
+                Err(TryRecvError::Lagged(skipped)) => Self::log_skipped(skipped),
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Returns whether the `tapped_frame` is received by this tap.
+    fn is_tapped(&self, tapped_frame: &TappedVoiceFrame) -> bool {
+        self.author
+            .is_none_or(|author| author == tapped_frame.frame.author)
+    }
+
+    /// Logs the frames skipped as the tap has lagged behind.
+    fn log_skipped(skipped: u64) {
+        event!(
+            Level::WARN,
+            "A voice tap has lagged behind, skipped {skipped} frames."
+        );
+    }
+}
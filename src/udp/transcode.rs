@@ -0,0 +1,284 @@
+//!
+//! Provides the [`Transcoder`], which re-encodes the Opus voice messages of a sender at lower bitrates for receivers with constrained bandwidth.
+//!
+//! Receivers signal the highest bitrate they can receive with [`ControlMessage::MaxBitrate`](crate::packet::control::ControlMessage::MaxBitrate).
+//! Instead of forcing every sender down to the lowest common denominator, the [`Server`](super::server::Server) re-encodes the messages exceeding a receiver's limit, so the unconstrained receivers still get the original stream.
+//!
+//! The re-encoded bitrates are quantized to the tiers of the [`TranscodeConfig`], so every tier is only encoded once per message, regardless of the amount of receivers.
+//!
+
+use std::{
+    collections::{hash_map::Entry, BTreeSet, HashMap},
+    net::SocketAddr,
+};
+
+use silence_core::opus::opus::{self, Application, Bitrate, Channels, Decoder, Encoder};
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use super::server::{ClientList, PeerRegistry};
+use crate::{
+    packet::{decode_message, HeaderFlags, MediaCodec, VoipMessageType, VoipPacket},
+    MTU_MAX_PACKET_SIZE,
+};
+
+/// The highest amount of samples (per channel) an Opus packet can contain, which is 120ms at 48kHz.
+const MAX_FRAME_SIZE: usize = 5760;
+
+/// Custom transcoding errors.
+#[derive(thiserror::Error, Debug)]
+pub enum TranscodeError {
+    /// This error is thrown when the Opus codec has failed to create a decoder or an encoder, or to process a packet.
+    #[error("Opus error: {0}")]
+    Opus(#[from] opus::Error),
+
+    /// This error is thrown when the re-encoded message could not be created.
+    #[error("Failed to encode the transcoded message: {0}")]
+    Encode(#[from] crate::packet::codec::CodecError),
+}
+
+///
+/// Transcoding configuration type definition.
+///
+/// Describes the decoded stream, and the bitrates the [`Transcoder`] re-encodes to.
+///
+#[derive(Debug, Clone)]
+pub struct TranscodeConfig {
+    /// The sample rate the messages are decoded and re-encoded at.
+    pub sample_rate: u32,
+
+    /// The channels the messages are decoded and re-encoded with.
+    pub channels: Channels,
+
+    /// The bitrates (in bits per second) the messages are re-encoded at.
+    /// A receiver gets the highest tier within its limit, or the lowest tier if none of them are.
+    pub bitrate_tiers: Vec<u32>,
+}
+
+impl Default for TranscodeConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48000,
+            channels: Channels::Stereo,
+            bitrate_tiers: vec![12_000, 24_000, 48_000],
+        }
+    }
+}
+
+///
+/// Transcoder type definition.
+///
+/// Holds a decoder for every sender, and an encoder for every sender and bitrate tier, as Opus streams are stateful.
+///
+#[derive(Debug)]
+pub struct Transcoder {
+    /// The configuration of the transcoder.
+    config: TranscodeConfig,
+
+    /// The decoder of every sender.
+    decoders: HashMap<Uuid, Decoder>,
+
+    /// The encoder of every sender and bitrate tier.
+    encoders: HashMap<(Uuid, u32), Encoder>,
+}
+
+impl Transcoder {
+    /// Creates a new [`Transcoder`] instance.
+    pub fn new(config: TranscodeConfig) -> Self {
+        Self {
+            config,
+            decoders: HashMap::new(),
+            encoders: HashMap::new(),
+        }
+    }
+
+    /// Returns the configuration of the transcoder.
+    pub fn config(&self) -> &TranscodeConfig {
+        &self.config
+    }
+
+    /// Returns the bitrate tier a receiver with the `max_bitrate` limit gets.
+    /// Returns [`None`] if there are no tiers configured.
+    pub fn tier_for(&self, max_bitrate: u32) -> Option<u32> {
+        let tiers = self.config.bitrate_tiers.iter().copied();
+
+        tiers
+            .clone()
+            .filter(|tier| *tier <= max_bitrate)
+            .max()
+            .or_else(|| tiers.min())
+    }
+
+    /// Returns the bitrate (in bits per second) the Opus `packet` was encoded at.
+    pub fn packet_bitrate(&self, packet: &[u8]) -> Result<u32, TranscodeError> {
+        let samples = opus::packet::get_nb_samples(packet, self.config.sample_rate)?;
+
+        if samples == 0 {
+            return Ok(0);
+        }
+
+        Ok((packet.len() as u64 * 8 * self.config.sample_rate as u64 / samples as u64) as u32)
+    }
+
+    /// Re-encodes the Opus `packet` of the `author` at every bitrate tier of `tiers`.
+    /// The packet is only decoded once, so that the decoder of the author stays in sync with the stream.
+    pub fn transcode(
+        &mut self,
+        author: Uuid,
+        packet: &[u8],
+        tiers: &BTreeSet<u32>,
+    ) -> Result<HashMap<u32, Vec<u8>>, TranscodeError> {
+        let channels = self.config.channels;
+        let sample_rate = self.config.sample_rate;
+
+        let decoder = match self.decoders.entry(author) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Decoder::new(sample_rate, channels)?),
+        };
+
+        let mut samples = vec![0f32; MAX_FRAME_SIZE * channels as usize];
+        let sample_count = decoder.decode_float(packet, &mut samples, false)?;
+
+        samples.truncate(sample_count * channels as usize);
+
+        let mut transcoded_packets = HashMap::new();
+
+        for tier in tiers {
+            let encoder = match self.encoders.entry((author, *tier)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let mut encoder = Encoder::new(sample_rate, channels, Application::Voip)?;
+
+                    encoder.set_bitrate(Bitrate::Bits(*tier as i32))?;
+
+                    entry.insert(encoder)
+                }
+            };
+
+            transcoded_packets.insert(
+                *tier,
+                encoder.encode_vec_float(&samples, MTU_MAX_PACKET_SIZE)?,
+            );
+        }
+
+        Ok(transcoded_packets)
+    }
+
+    /// Removes the decoder and the encoders of the `author` (for example when the author has left the session).
+    pub fn remove_author(&mut self, author: Uuid) {
+        self.decoders.remove(&author);
+        self.encoders
+            .retain(|(encoder_author, _), _| *encoder_author != author);
+    }
+
+    /// Re-encodes the `voip_packet` for every client of the [`ClientList`], whose limit in the [`PeerRegistry`] it exceeds.
+    /// Returns the re-encoded messages by the address of the client, the remaining clients should receive the original message.
+    ///
+    /// Only Opus voice messages are transcoded, if transcoding fails the original message is sent to everyone.
+    pub(crate) fn transcode_for_clients(
+        &mut self,
+        voip_packet: &VoipPacket,
+        client_list: &ClientList,
+        peers: &PeerRegistry,
+    ) -> HashMap<SocketAddr, VoipPacket> {
+        let Ok((voip_header, voip_body)) = decode_message(voip_packet.inner()) else {
+            return HashMap::new();
+        };
+
+        if !matches!(
+            voip_header.voip_message_type(),
+            VoipMessageType::VoiceMessage(_)
+        ) || voip_header.codec() != MediaCodec::Opus
+        {
+            return HashMap::new();
+        }
+
+        //The end of stream markers carry no audio, they are forwarded as they are
+        if voip_header.flags().contains(HeaderFlags::END_OF_STREAM) {
+            return HashMap::new();
+        }
+
+        //The aggregated frames are forwarded as they are, as their body isn't a single Opus packet
+        if voip_header.frame_lengths().is_some() {
+            return HashMap::new();
+        }
+
+        //Only the frame itself is re-encoded, the redundant copy of the previous frame is dropped
+        let (voip_body, _) = voip_header.split_redundancy(&voip_body);
+
+        let packet_bitrate = match self.packet_bitrate(voip_body) {
+            Ok(packet_bitrate) => packet_bitrate,
+            Err(err) => {
+                event!(Level::ERROR, "Failed to parse an Opus packet: {err}");
+
+                return HashMap::new();
+            }
+        };
+
+        //Find the tier of every client whose limit is exceeded
+        let client_tiers: Vec<(SocketAddr, u32)> = client_list
+            .iter()
+            .filter_map(|remote_addr| {
+                let max_bitrate = peers.get(remote_addr.key())?.max_bitrate()?;
+
+                if packet_bitrate <= max_bitrate {
+                    return None;
+                }
+
+                let tier = self.tier_for(max_bitrate)?;
+
+                //Dont re-encode into a higher bitrate than the original
+                (tier < packet_bitrate).then_some((*remote_addr.key(), tier))
+            })
+            .collect();
+
+        if client_tiers.is_empty() {
+            return HashMap::new();
+        }
+
+        let tiers: BTreeSet<u32> = client_tiers.iter().map(|(_, tier)| *tier).collect();
+
+        let transcoded_packets = match self.transcode(voip_header.author(), voip_body, &tiers) {
+            Ok(transcoded_packets) => transcoded_packets,
+            Err(err) => {
+                event!(Level::ERROR, "Failed to transcode a voice message: {err}");
+
+                return HashMap::new();
+            }
+        };
+
+        //Create the messages of every tier, preserving every header field of the original except the redundancy
+        let mut tier_messages = HashMap::new();
+
+        for (tier, transcoded_packet) in transcoded_packets {
+            let transcoded_header = voip_header
+                .clone()
+                .with_voip_message_type(VoipMessageType::VoiceMessage(
+                    transcoded_packet.len() as u64
+                ))
+                .with_redundancy(None);
+
+            let voip_packet = transcoded_header.create_message_buffer(&transcoded_packet);
+
+            match voip_packet {
+                Ok(voip_packet) => {
+                    tier_messages.insert(tier, voip_packet);
+                }
+                Err(err) => {
+                    event!(
+                        Level::ERROR,
+                        "Failed to encode a transcoded message: {}",
+                        TranscodeError::from(err)
+                    );
+                }
+            }
+        }
+
+        client_tiers
+            .into_iter()
+            .filter_map(|(remote_addr, tier)| {
+                Some((remote_addr, tier_messages.get(&tier)?.clone()))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,506 @@
+//!
+//! Provides the [`PacketCipher`] abstraction, and the [`CipherInterceptor`] which encrypts every datagram of a [`Transport`](super::Transport) with it.
+//!
+//! The nonce of every datagram is derived from its sequence number (like in QUIC and TLS 1.3), and from the random 96 bit session identifier of its sender, so the nonces never have to be stored.
+//! As the session identifier is as long as the nonce, the nonces of two sessions sealed with the same key only collide if their identifiers happen to differ by the XOR of their sequence numbers.
+//! The server looks up the ciphers of every peer by its address, so every client can seal with its own keys.
+//! AES-256-GCM ([`Aes256GcmCipher`]) and ChaCha20-Poly1305 ([`ChaCha20Poly1305Cipher`]) are provided, other implementations (for example FIPS validated modules or hardware crypto) can be plugged in by implementing [`PacketCipher`].
+//!
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+
+use aes_gcm::{
+    aead::{AeadInPlace, KeyInit},
+    Aes256Gcm,
+};
+use chacha20poly1305::ChaCha20Poly1305;
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use super::layer::Interceptor;
+
+/// The length of the nonces of the provided ciphers.
+pub const NONCE_SIZE: usize = 12;
+
+/// The length of the session identifier prepended to every datagram sealed by a [`CipherInterceptor`], which is as long as the nonces.
+pub const SESSION_SIZE: usize = NONCE_SIZE;
+
+/// The length of the sequence number prepended to every datagram sealed by a [`CipherInterceptor`].
+pub const SEQUENCE_SIZE: usize = 8;
+
+/// The length of the header (the session identifier and the sequence number) of every datagram sealed by a [`CipherInterceptor`].
+pub const HEADER_SIZE: usize = SESSION_SIZE + SEQUENCE_SIZE;
+
+/// The number of sequence numbers below the highest received one, which are still accepted once (in case the datagrams got reordered).
+pub const REPLAY_WINDOW_SIZE: u64 = 64;
+
+/// The maximum number of sessions a [`CipherInterceptor`] keeps a replay window of, the window of the session which has been silent for the longest is evicted once it is reached.
+pub const MAX_REPLAY_WINDOWS: usize = 4096;
+
+/// The random identifier of the session of a [`CipherInterceptor`].
+pub type SessionId = [u8; SESSION_SIZE];
+
+/// Custom cipher errors.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum CipherError {
+    /// This error is thrown when a datagram could not be encrypted.
+    #[error("Failed to seal the datagram.")]
+    Seal,
+
+    /// This error is thrown when a datagram could not be decrypted, because it was tampered with or sealed with another key.
+    #[error("Failed to open the datagram.")]
+    Open,
+}
+
+///
+/// Packet cipher definition.
+///
+/// An authenticated cipher which encrypts (seals) and decrypts (opens) the datagrams in place.
+/// The nonce of every datagram has to be derived from its `session` identifier and its `sequence` number, so a key must never seal two datagrams with the same pair of them.
+///
+pub trait PacketCipher: Send + Sync + 'static {
+    /// Encrypts the `buffer` in place and appends the authentication tag, authenticating the `associated_data` too.
+    fn seal(
+        &self,
+        session: &SessionId,
+        sequence: u64,
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CipherError>;
+
+    /// Verifies the authentication tag at the end of the `buffer` and the `associated_data`, then decrypts the `buffer` in place and removes the tag.
+    fn open(
+        &self,
+        session: &SessionId,
+        sequence: u64,
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CipherError>;
+}
+
+/// Derives the nonce of the datagram with the `session` identifier and the `sequence` number, by XOR-ing the session identifier and the sequence number (into the last bytes) into the `iv`.
+pub fn derive_nonce(iv: &[u8; NONCE_SIZE], session: &SessionId, sequence: u64) -> [u8; NONCE_SIZE] {
+    let mut nonce = *iv;
+
+    for (nonce_byte, session_byte) in nonce.iter_mut().zip(session) {
+        *nonce_byte ^= session_byte;
+    }
+
+    for (nonce_byte, sequence_byte) in nonce[NONCE_SIZE - SEQUENCE_SIZE..]
+        .iter_mut()
+        .zip(sequence.to_be_bytes())
+    {
+        *nonce_byte ^= sequence_byte;
+    }
+
+    nonce
+}
+
+/// Returns a random [`SessionId`], made up of the bytes of a version 4 [`Uuid`] which aren't fixed by its version and variant.
+fn random_session() -> SessionId {
+    let uuid = Uuid::new_v4();
+    let random_bytes = [&uuid.as_bytes()[..6], &uuid.as_bytes()[9..15]].concat();
+
+    random_bytes
+        .try_into()
+        .expect("The random bytes are as long as a session identifier")
+}
+
+///
+/// AES-256-GCM cipher type definition.
+///
+/// Uses the AES instructions of the CPU if they are available.
+///
+#[derive(Clone)]
+pub struct Aes256GcmCipher {
+    /// The keyed cipher.
+    cipher: Aes256Gcm,
+
+    /// The IV the nonces are derived from.
+    iv: [u8; NONCE_SIZE],
+}
+
+impl Aes256GcmCipher {
+    /// Creates a new [`Aes256GcmCipher`] instance with the 256 bit `key`, and the `iv` the nonces are derived from.
+    pub fn new(key: &[u8; 32], iv: [u8; NONCE_SIZE]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(key.into()),
+            iv,
+        }
+    }
+}
+
+impl std::fmt::Debug for Aes256GcmCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Aes256GcmCipher").finish_non_exhaustive()
+    }
+}
+
+impl PacketCipher for Aes256GcmCipher {
+    fn seal(
+        &self,
+        session: &SessionId,
+        sequence: u64,
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CipherError> {
+        self.cipher
+            .encrypt_in_place(
+                &derive_nonce(&self.iv, session, sequence).into(),
+                associated_data,
+                buffer,
+            )
+            .map_err(|_| CipherError::Seal)
+    }
+
+    fn open(
+        &self,
+        session: &SessionId,
+        sequence: u64,
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CipherError> {
+        self.cipher
+            .decrypt_in_place(
+                &derive_nonce(&self.iv, session, sequence).into(),
+                associated_data,
+                buffer,
+            )
+            .map_err(|_| CipherError::Open)
+    }
+}
+
+///
+/// ChaCha20-Poly1305 cipher type definition.
+///
+/// This is faster than AES-GCM on the platforms without AES instructions (for example most mobile and embedded CPUs).
+///
+#[derive(Clone)]
+pub struct ChaCha20Poly1305Cipher {
+    /// The keyed cipher.
+    cipher: ChaCha20Poly1305,
+
+    /// The IV the nonces are derived from.
+    iv: [u8; NONCE_SIZE],
+}
+
+impl ChaCha20Poly1305Cipher {
+    /// Creates a new [`ChaCha20Poly1305Cipher`] instance with the 256 bit `key`, and the `iv` the nonces are derived from.
+    pub fn new(key: &[u8; 32], iv: [u8; NONCE_SIZE]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(key.into()),
+            iv,
+        }
+    }
+}
+
+impl std::fmt::Debug for ChaCha20Poly1305Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChaCha20Poly1305Cipher")
+            .finish_non_exhaustive()
+    }
+}
+
+impl PacketCipher for ChaCha20Poly1305Cipher {
+    fn seal(
+        &self,
+        session: &SessionId,
+        sequence: u64,
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CipherError> {
+        self.cipher
+            .encrypt_in_place(
+                &derive_nonce(&self.iv, session, sequence).into(),
+                associated_data,
+                buffer,
+            )
+            .map_err(|_| CipherError::Seal)
+    }
+
+    fn open(
+        &self,
+        session: &SessionId,
+        sequence: u64,
+        associated_data: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CipherError> {
+        self.cipher
+            .decrypt_in_place(
+                &derive_nonce(&self.iv, session, sequence).into(),
+                associated_data,
+                buffer,
+            )
+            .map_err(|_| CipherError::Open)
+    }
+}
+
+///
+/// Peer ciphers type definition.
+///
+/// The pair of ciphers the datagrams exchanged with a peer are sealed and opened with.
+///
+#[derive(Debug, Clone)]
+pub struct PeerCiphers<C> {
+    /// The cipher the datagrams sent to the peer are sealed with.
+    pub seal_cipher: C,
+
+    /// The cipher the datagrams received from the peer are opened with.
+    pub open_cipher: C,
+}
+
+impl<C> PeerCiphers<C> {
+    /// Creates a new [`PeerCiphers`] instance, sealing the datagrams sent to the peer with the `seal_cipher` and opening the ones received from it with the `open_cipher`.
+    pub fn new(seal_cipher: C, open_cipher: C) -> Self {
+        Self {
+            seal_cipher,
+            open_cipher,
+        }
+    }
+}
+
+/// Looks up the ciphers of the peer at the address, returning [`None`] if there are no keys for it.
+pub type CipherResolver<C> = Arc<dyn Fn(SocketAddr) -> Option<Arc<PeerCiphers<C>>> + Send + Sync>;
+
+///
+/// Replay window type definition.
+///
+/// Tracks which of the last [`REPLAY_WINDOW_SIZE`] sequence numbers of a session have been received already.
+///
+#[derive(Debug, Clone, Copy)]
+struct ReplayWindow {
+    /// The highest sequence number received.
+    highest: u64,
+
+    /// The bit `n` is set if the sequence number `highest - n` has been received.
+    received: u64,
+
+    /// The time the last datagram of the session was received at.
+    last_received: Instant,
+}
+
+impl ReplayWindow {
+    /// Creates a new [`ReplayWindow`] instance, which has received the `sequence` number only.
+    fn new(sequence: u64) -> Self {
+        Self {
+            highest: sequence,
+            received: 1,
+            last_received: Instant::now(),
+        }
+    }
+
+    /// Marks the `sequence` number as received, returning `false` if it has been received already or it is too old to tell.
+    fn accept(&mut self, sequence: u64) -> bool {
+        self.last_received = Instant::now();
+
+        if sequence > self.highest {
+            let shift = sequence - self.highest;
+
+            self.received = if shift < REPLAY_WINDOW_SIZE {
+                (self.received << shift) | 1
+            } else {
+                1
+            };
+            self.highest = sequence;
+
+            return true;
+        }
+
+        let offset = self.highest - sequence;
+
+        if offset >= REPLAY_WINDOW_SIZE || self.received & (1 << offset) != 0 {
+            return false;
+        }
+
+        self.received |= 1 << offset;
+
+        true
+    }
+}
+
+///
+/// Cipher interceptor type definition.
+///
+/// Seals every outgoing datagram with the [`PacketCipher`] of its target, and opens every incoming datagram with the one of its source.
+/// The sealed datagrams are prefixed with the session identifier of the interceptor and their sequence number, which are authenticated as associated data.
+/// The datagrams which can't be opened, or which have been received already (replayed), are dropped.
+///
+/// The 96 bit session identifier is random for every interceptor, so the nonces don't repeat even if the same keys are used again (for example after a reconnect or a resumption).
+/// The replay windows of at most [`MAX_REPLAY_WINDOWS`] sessions are kept, and the windows of a peer can be evicted with [`CipherInterceptor::forget`] once it has left.
+/// Every sender still has to seal with its own key, so that a peer can't open or forge the datagrams of another one:
+/// the client seals with the key the server opens the datagrams of that client with, and the other way around.
+///
+pub struct CipherInterceptor<C> {
+    /// Looks up the ciphers of the peers.
+    resolver: CipherResolver<C>,
+
+    /// The random session identifier of the sealed datagrams.
+    session: SessionId,
+
+    /// The sequence number of the next sealed datagram, shared between the clones of the interceptor.
+    next_sequence: Arc<AtomicU64>,
+
+    /// The replay windows of the sessions of the peers, shared between the clones of the interceptor.
+    replay_windows: Arc<DashMap<(SocketAddr, SessionId), ReplayWindow>>,
+}
+
+impl<C: Send + Sync + 'static> CipherInterceptor<C> {
+    /// Creates a new [`CipherInterceptor`] instance, sealing the outgoing datagrams with the `seal_cipher` and opening the incoming ones with the `open_cipher`, whichever peer they are exchanged with.
+    /// This is meant for the clients, which only exchange datagrams with the server.
+    pub fn new(seal_cipher: C, open_cipher: C) -> Self {
+        let ciphers = Arc::new(PeerCiphers::new(seal_cipher, open_cipher));
+
+        Self::with_resolver(move |_| Some(ciphers.clone()))
+    }
+
+    /// Creates a new [`CipherInterceptor`] instance, which looks up the ciphers of every peer with the `resolver`.
+    /// This is meant for the servers, so that every client can have its own keys. The datagrams exchanged with the peers without keys are dropped.
+    pub fn with_resolver(
+        resolver: impl Fn(SocketAddr) -> Option<Arc<PeerCiphers<C>>> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            resolver: Arc::new(resolver),
+            session: random_session(),
+            next_sequence: Arc::new(AtomicU64::new(0)),
+            replay_windows: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Creates a new [`CipherInterceptor`] instance, which looks up the ciphers of every peer in the `ciphers` map by its address.
+    /// The map can be updated while the interceptor is in use, as the peers join and leave.
+    pub fn with_peer_ciphers(ciphers: Arc<DashMap<SocketAddr, Arc<PeerCiphers<C>>>>) -> Self {
+        Self::with_resolver(move |address| {
+            ciphers
+                .get(&address)
+                .map(|peer_ciphers| peer_ciphers.clone())
+        })
+    }
+
+    /// Returns the random session identifier of the datagrams sealed by the interceptor.
+    pub fn session(&self) -> SessionId {
+        self.session
+    }
+
+    /// Evicts the replay windows of every session of the peer at the `address`.
+    /// This should be called once the keys of the peer are removed (for example after it has left), as the windows are otherwise only evicted once there are [`MAX_REPLAY_WINDOWS`] of them.
+    pub fn forget(&self, address: SocketAddr) {
+        self.replay_windows
+            .retain(|(window_address, _), _| *window_address != address);
+    }
+
+    /// Evicts the replay window of the session which has been silent for the longest, if there are [`MAX_REPLAY_WINDOWS`] windows already.
+    fn evict_stalest_window(&self) {
+        if self.replay_windows.len() < MAX_REPLAY_WINDOWS {
+            return;
+        }
+
+        let stalest_session = self
+            .replay_windows
+            .iter()
+            .min_by_key(|window| window.last_received)
+            .map(|window| *window.key());
+
+        if let Some(stalest_session) = stalest_session {
+            self.replay_windows.remove(&stalest_session);
+        }
+    }
+}
+
+impl<C> Clone for CipherInterceptor<C> {
+    fn clone(&self) -> Self {
+        Self {
+            resolver: self.resolver.clone(),
+            session: self.session,
+            next_sequence: self.next_sequence.clone(),
+            replay_windows: self.replay_windows.clone(),
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for CipherInterceptor<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CipherInterceptor")
+            .field("session", &self.session)
+            .field("next_sequence", &self.next_sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: PacketCipher> Interceptor for CipherInterceptor<C> {
+    fn on_send(&self, mut datagram: Vec<u8>, target: SocketAddr) -> Option<Vec<u8>> {
+        let Some(ciphers) = (self.resolver)(target) else {
+            event!(
+                Level::WARN,
+                "Dropped a datagram to {target}: there are no keys for it."
+            );
+
+            return None;
+        };
+
+        let sequence = self.next_sequence.fetch_add(1, Ordering::Relaxed);
+        let header = [self.session.as_slice(), &sequence.to_be_bytes()].concat();
+
+        if let Err(err) = ciphers
+            .seal_cipher
+            .seal(&self.session, sequence, &header, &mut datagram)
+        {
+            event!(Level::ERROR, "Failed to seal a datagram to {target}: {err}");
+
+            return None;
+        }
+
+        Some([header, datagram].concat())
+    }
+
+    fn on_recv(&self, mut datagram: Vec<u8>, source: SocketAddr) -> Option<Vec<u8>> {
+        if datagram.len() < HEADER_SIZE {
+            return None;
+        }
+
+        let ciphers = (self.resolver)(source)?;
+        let mut sealed = datagram.split_off(HEADER_SIZE);
+        let session: SessionId = datagram[..SESSION_SIZE].try_into().ok()?;
+        let sequence = u64::from_be_bytes(datagram[SESSION_SIZE..].try_into().ok()?);
+
+        if let Err(err) = ciphers
+            .open_cipher
+            .open(&session, sequence, &datagram, &mut sealed)
+        {
+            event!(Level::DEBUG, "Dropped a datagram from {source}: {err}");
+
+            return None;
+        }
+
+        //The window is only updated once the datagram has been authenticated, so forged sequence numbers can't move it
+        //The map is capped before the entry is locked, as evicting iterates over every shard
+        if !self.replay_windows.contains_key(&(source, session)) {
+            self.evict_stalest_window();
+        }
+
+        let is_fresh = match self.replay_windows.entry((source, session)) {
+            Entry::Occupied(mut window) => window.get_mut().accept(sequence),
+            Entry::Vacant(window) => {
+                window.insert(ReplayWindow::new(sequence));
+
+                true
+            }
+        };
+
+        if !is_fresh {
+            event!(Level::DEBUG, "Dropped a replayed datagram from {source}.");
+
+            return None;
+        }
+
+        Some(sealed)
+    }
+}
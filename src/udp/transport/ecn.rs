@@ -0,0 +1,278 @@
+//!
+//! Provides the [`Ecn`] codepoints of the datagrams, and the socket options reading and writing them on the operating system's sockets.
+//!
+//! Explicit Congestion Notification lets the routers mark the datagrams of a congested queue with [`Ecn::Ce`] instead of dropping them.
+//! The receivers count the marked datagrams and report them back, so the senders can lower their bitrate before any datagram is lost.
+//!
+
+///
+/// ECN codepoint type definition.
+///
+/// The two low bits of the IPv4 TOS (or the IPv6 traffic class) field of a datagram, see [RFC 3168](https://www.rfc-editor.org/rfc/rfc3168).
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Ecn {
+    /// The sender doesn't support ECN, the routers drop the datagram instead of marking it.
+    #[default]
+    NotEct,
+
+    /// The sender supports ECN, with the ECT(1) codepoint (used by L4S senders).
+    Ect1,
+
+    /// The sender supports ECN, with the ECT(0) codepoint.
+    Ect0,
+
+    /// A router on the path has experienced congestion.
+    Ce,
+}
+
+impl Ecn {
+    /// Creates the [`Ecn`] codepoint from the two low bits of the TOS (or traffic class) byte.
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => Self::NotEct,
+            0b01 => Self::Ect1,
+            0b10 => Self::Ect0,
+            _ => Self::Ce,
+        }
+    }
+
+    /// Returns the bits of the codepoint in the TOS (or traffic class) byte.
+    pub fn to_bits(self) -> u8 {
+        match self {
+            Self::NotEct => 0b00,
+            Self::Ect1 => 0b01,
+            Self::Ect0 => 0b10,
+            Self::Ce => 0b11,
+        }
+    }
+
+    /// Returns whether the datagram was sent by an ECN capable sender (whether it may be marked with [`Ecn::Ce`]).
+    pub fn is_ect(self) -> bool {
+        self != Self::NotEct
+    }
+}
+
+/// The socket options and the `recvmsg` based receiving of the ECN codepoints on unix sockets.
+#[cfg(unix)]
+pub(super) mod sys {
+    use std::{
+        io,
+        mem::{self, MaybeUninit},
+        net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+        os::fd::{AsRawFd, RawFd},
+    };
+
+    use super::Ecn;
+
+    /// The size of the buffer the control messages are received into, which fits both the TOS and the traffic class messages.
+    const CONTROL_BUFFER_SIZE: usize = 64;
+
+    /// Sets an integer socket option on the `fd`.
+    pub(in crate::udp::transport) fn set_option(
+        fd: RawFd,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: libc::c_int,
+    ) -> io::Result<()> {
+        // SAFETY: The value is a valid `c_int` which outlives the call, and its size is passed along.
+        let result = unsafe {
+            libc::setsockopt(
+                fd,
+                level,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the value of an integer socket option of the `fd`.
+    fn get_option(fd: RawFd, level: libc::c_int, name: libc::c_int) -> io::Result<libc::c_int> {
+        let mut value: libc::c_int = 0;
+        let mut length = mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+        // SAFETY: The value and its length are valid for the call.
+        let result = unsafe {
+            libc::getsockopt(
+                fd,
+                level,
+                name,
+                &mut value as *mut libc::c_int as *mut libc::c_void,
+                &mut length,
+            )
+        };
+
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(value)
+    }
+
+    ///
+    /// Replaces the `mask` bits of the TOS (or traffic class) byte of the datagrams sent through the `fd` with the `bits`, keeping the rest of the byte.
+    ///
+    /// # Behavior
+    /// IPv6 sockets set both the IPv6 and the IPv4 options, so that the IPv4 mapped peers of dual-stack sockets are covered too.
+    /// The IPv4 options of a IPv6-only socket may be refused, which is ignored.
+    ///
+    pub(in crate::udp::transport) fn update_traffic_class(
+        fd: RawFd,
+        is_ipv6: bool,
+        mask: u8,
+        bits: u8,
+    ) -> io::Result<()> {
+        let update = |level, name| {
+            //The traffic class is reported as -1 while it is unset on some platforms
+            let current = get_option(fd, level, name)?.max(0) as u8;
+
+            set_option(
+                fd,
+                level,
+                name,
+                ((current & !mask) | (bits & mask)) as libc::c_int,
+            )
+        };
+
+        if is_ipv6 {
+            update(libc::IPPROTO_IPV6, libc::IPV6_TCLASS)?;
+
+            let _ = update(libc::IPPROTO_IP, libc::IP_TOS);
+        } else {
+            update(libc::IPPROTO_IP, libc::IP_TOS)?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Marks the datagrams sent through the `socket` with the `ecn` codepoint, and enables receiving the codepoints of the incoming datagrams.
+    ///
+    /// # Behavior
+    /// IPv6 sockets set both the IPv6 and the IPv4 options, so that the IPv4 mapped peers of dual-stack sockets are covered too.
+    /// The IPv4 options of a IPv6-only socket may be refused, which is ignored.
+    ///
+    pub(in crate::udp::transport) fn set_ecn(
+        socket: &impl AsRawFd,
+        is_ipv6: bool,
+        ecn: Ecn,
+    ) -> io::Result<()> {
+        let fd = socket.as_raw_fd();
+
+        //Keep the DSCP of the traffic class
+        update_traffic_class(fd, is_ipv6, 0b11, ecn.to_bits())?;
+
+        if is_ipv6 {
+            set_option(fd, libc::IPPROTO_IPV6, libc::IPV6_RECVTCLASS, 1)?;
+
+            let _ = set_option(fd, libc::IPPROTO_IP, libc::IP_RECVTOS, 1);
+        } else {
+            set_option(fd, libc::IPPROTO_IP, libc::IP_RECVTOS, 1)?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Receives a single datagram from the non-blocking `socket` with `recvmsg`, along with its [`Ecn`] codepoint.
+    ///
+    /// # Behavior
+    /// The codepoint is read from the TOS (or traffic class) control message, datagrams without one are reported as [`Ecn::NotEct`].
+    /// Returns [`io::ErrorKind::WouldBlock`] if there is nothing to receive.
+    ///
+    pub(in crate::udp::transport) fn recv_with_ecn(
+        socket: &impl AsRawFd,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, SocketAddr, Ecn)> {
+        let mut source = MaybeUninit::<libc::sockaddr_storage>::zeroed();
+        let mut control = [0u64; CONTROL_BUFFER_SIZE / mem::size_of::<u64>()];
+        let mut iovec = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+
+        // SAFETY: An all zero `msghdr` is valid, the pointers set below outlive the call.
+        let mut message: libc::msghdr = unsafe { mem::zeroed() };
+
+        message.msg_name = source.as_mut_ptr() as *mut libc::c_void;
+        message.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        message.msg_iov = &mut iovec;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        message.msg_controllen = CONTROL_BUFFER_SIZE as _;
+
+        // SAFETY: Every buffer of the `msghdr` is valid for its advertised length.
+        let byte_count = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut message, 0) };
+
+        if byte_count == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut ecn = Ecn::NotEct;
+
+        // SAFETY: The control messages were written by the kernel into the control buffer of the `msghdr`, which is still alive.
+        unsafe {
+            let mut control_message = libc::CMSG_FIRSTHDR(&message);
+
+            while !control_message.is_null() {
+                let (level, kind) = ((*control_message).cmsg_level, (*control_message).cmsg_type);
+                let data = libc::CMSG_DATA(control_message);
+
+                //The TOS is received as a single byte, the traffic class as an integer
+                if level == libc::IPPROTO_IP && (kind == libc::IP_TOS || kind == libc::IP_RECVTOS) {
+                    ecn = Ecn::from_bits(*data);
+                } else if level == libc::IPPROTO_IPV6 && kind == libc::IPV6_TCLASS {
+                    ecn = Ecn::from_bits((data as *const libc::c_int).read_unaligned() as u8);
+                }
+
+                control_message = libc::CMSG_NXTHDR(&message, control_message);
+            }
+        }
+
+        // SAFETY: The kernel has written a socket address of the family it reports into the storage.
+        let source = unsafe { socket_addr(source.assume_init_ref()) }?;
+
+        Ok((byte_count as usize, source, ecn))
+    }
+
+    /// Converts the socket address written by the kernel into a [`SocketAddr`].
+    ///
+    /// # Safety
+    /// The `storage` has to contain a valid socket address of the family it reports.
+    pub(in crate::udp::transport) unsafe fn socket_addr(
+        storage: &libc::sockaddr_storage,
+    ) -> io::Result<SocketAddr> {
+        match storage.ss_family as libc::c_int {
+            libc::AF_INET => {
+                let addr = &*(storage as *const libc::sockaddr_storage as *const libc::sockaddr_in);
+
+                Ok(SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                    u16::from_be(addr.sin_port),
+                )))
+            }
+            libc::AF_INET6 => {
+                let addr =
+                    &*(storage as *const libc::sockaddr_storage as *const libc::sockaddr_in6);
+
+                Ok(SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                )))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The datagram was received from an unsupported address family.",
+            )),
+        }
+    }
+}
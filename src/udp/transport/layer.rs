@@ -0,0 +1,215 @@
+//!
+//! Provides a [tower](https://crates.io/crates/tower) like layer API, for inserting interceptors into the client and server pipelines.
+//!
+//! A [`Layer`] wraps a [`Transport`] into another [`Transport`], so the layered transport can be passed to any constructor accepting a [`Transport`] without changing the services' I/O loops.
+//! The most common layer is the [`InterceptorLayer`], which runs an [`Interceptor`] on every datagram sent and received (for example to encrypt, log, mutate, drop or rate-limit them).
+//!
+//! Layers can be composed with [`Stack`] or by calling [`TransportExt::layer`] multiple times.
+//! The layer applied last is the outermost one: it sees the outgoing datagrams first, and the incoming datagrams last.
+//!
+
+use std::{future::Future, io, net::SocketAddr};
+
+use super::{ecn::Ecn, path::PathError, qos::Dscp, Transport};
+
+/// The size of the buffer an [`Intercepted`] transport receives datagrams into.
+/// This is the largest possible UDP datagram, so that interceptors which grow the datagrams (for example encryption) dont get truncated.
+const INTERCEPTED_BUFFER_SIZE: usize = u16::MAX as usize;
+
+///
+/// Layer definition.
+///
+/// Wraps a [`Transport`] into another [`Transport`] with additional behavior.
+///
+pub trait Layer<T: Transport> {
+    /// The [`Transport`] created by this layer.
+    type Transport: Transport;
+
+    /// Wraps the `inner` [`Transport`].
+    fn layer(&self, inner: T) -> Self::Transport;
+}
+
+///
+/// Interceptor definition.
+///
+/// An interceptor is run on every datagram which passes through an [`Intercepted`] transport.
+/// Both methods return the datagram which should be passed on, or [`None`] if the datagram should be dropped.
+///
+pub trait Interceptor: Send + Sync + 'static {
+    /// Called with every outgoing datagram, before it is sent to the `target` address.
+    fn on_send(&self, datagram: Vec<u8>, target: SocketAddr) -> Option<Vec<u8>> {
+        let _ = target;
+
+        Some(datagram)
+    }
+
+    /// Called with every incoming datagram, before it is handed to the service.
+    fn on_recv(&self, datagram: Vec<u8>, source: SocketAddr) -> Option<Vec<u8>> {
+        let _ = source;
+
+        Some(datagram)
+    }
+}
+
+/// A [`Layer`] which runs the [`Interceptor`] on every datagram of the wrapped [`Transport`].
+#[derive(Debug, Clone)]
+pub struct InterceptorLayer<I> {
+    /// The interceptor every created [`Intercepted`] transport runs.
+    interceptor: I,
+}
+
+impl<I> InterceptorLayer<I> {
+    /// Creates a new [`InterceptorLayer`] instance.
+    pub fn new(interceptor: I) -> Self {
+        Self { interceptor }
+    }
+}
+
+impl<T: Transport, I: Interceptor + Clone> Layer<T> for InterceptorLayer<I> {
+    type Transport = Intercepted<T, I>;
+
+    fn layer(&self, inner: T) -> Self::Transport {
+        Intercepted::new(inner, self.interceptor.clone())
+    }
+}
+
+///
+/// Intercepted transport type definition.
+///
+/// Runs the [`Interceptor`] on every datagram sent and received through the wrapped [`Transport`].
+///
+#[derive(Debug)]
+pub struct Intercepted<T, I> {
+    /// The wrapped [`Transport`].
+    inner: T,
+
+    /// The [`Interceptor`] run on every datagram.
+    interceptor: I,
+}
+
+impl<T, I> Intercepted<T, I> {
+    /// Creates a new [`Intercepted`] instance.
+    pub fn new(inner: T, interceptor: I) -> Self {
+        Self { inner, interceptor }
+    }
+
+    /// Returns a reference to the wrapped [`Transport`].
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a reference to the [`Interceptor`].
+    pub fn interceptor(&self) -> &I {
+        &self.interceptor
+    }
+}
+
+impl<T: Transport, I: Interceptor> Transport for Intercepted<T, I> {
+    async fn send_datagram(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        match self.interceptor.on_send(buf.to_vec(), target) {
+            Some(datagram) => {
+                self.inner.send_datagram(&datagram, target).await?;
+
+                Ok(buf.len())
+            }
+            //Dropped datagrams are reported as sent, like datagrams lost on the network
+            None => Ok(buf.len()),
+        }
+    }
+
+    async fn recv_datagram(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (byte_count, source, _) = self.recv_datagram_ecn(buf).await?;
+
+        Ok((byte_count, source))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    async fn recv_datagram_ecn(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Ecn)> {
+        let mut datagram_buf = vec![0; INTERCEPTED_BUFFER_SIZE];
+
+        //Keep receiving until the interceptor lets a datagram through
+        loop {
+            let (byte_count, source, ecn) = self.inner.recv_datagram_ecn(&mut datagram_buf).await?;
+
+            if let Some(datagram) = self
+                .interceptor
+                .on_recv(datagram_buf[..byte_count].to_vec(), source)
+            {
+                //Truncate the datagram like a real socket would
+                let byte_count = datagram.len().min(buf.len());
+
+                buf[..byte_count].copy_from_slice(&datagram[..byte_count]);
+
+                return Ok((byte_count, source, ecn));
+            }
+        }
+    }
+
+    fn set_ecn(&self, ecn: Ecn) -> io::Result<()> {
+        self.inner.set_ecn(ecn)
+    }
+
+    fn set_traffic_class(&self, dscp: Dscp) -> io::Result<()> {
+        self.inner.set_traffic_class(dscp)
+    }
+
+    fn set_flow_label(&self, flow_label: u32) -> io::Result<()> {
+        self.inner.set_flow_label(flow_label)
+    }
+
+    fn set_hop_limit(&self, hop_limit: u8) -> io::Result<()> {
+        self.inner.set_hop_limit(hop_limit)
+    }
+
+    fn enable_path_errors(&self) -> io::Result<()> {
+        self.inner.enable_path_errors()
+    }
+
+    fn recv_path_error(&self) -> impl Future<Output = io::Result<PathError>> + Send {
+        self.inner.recv_path_error()
+    }
+}
+
+/// Two [`Layer`]s composed into one.
+/// The `inner` layer is applied first, so the `outer` layer wraps its result.
+#[derive(Debug, Clone)]
+pub struct Stack<Inner, Outer> {
+    /// The layer applied first.
+    inner: Inner,
+
+    /// The layer applied last.
+    outer: Outer,
+}
+
+impl<Inner, Outer> Stack<Inner, Outer> {
+    /// Creates a new [`Stack`] instance.
+    pub fn new(inner: Inner, outer: Outer) -> Self {
+        Self { inner, outer }
+    }
+}
+
+impl<T, Inner, Outer> Layer<T> for Stack<Inner, Outer>
+where
+    T: Transport,
+    Inner: Layer<T>,
+    Outer: Layer<Inner::Transport>,
+{
+    type Transport = Outer::Transport;
+
+    fn layer(&self, inner: T) -> Self::Transport {
+        self.outer.layer(self.inner.layer(inner))
+    }
+}
+
+/// Extension methods for every [`Transport`].
+pub trait TransportExt: Transport + Sized {
+    /// Wraps this [`Transport`] with the [`Layer`].
+    fn layer<L: Layer<Self>>(self, layer: L) -> L::Transport {
+        layer.layer(self)
+    }
+}
+
+impl<T: Transport> TransportExt for T {}
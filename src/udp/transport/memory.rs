@@ -0,0 +1,378 @@
+//! An in-memory [`Transport`] implementation.
+//!
+//! Every [`MemorySocket`] bound to the same [`MemoryNetwork`] can exchange datagrams with the others, without touching the operating system's network stack.
+//! This makes it possible to run a [`Client`](crate::udp::client::Client) and a [`Server`](crate::udp::server::Server) in the same process deterministically.
+
+use std::{
+    io,
+    net::{Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use super::{
+    ecn::Ecn,
+    path::{PathError, PathErrorKind, DEFAULT_HOP_LIMIT},
+    qos::{self, Dscp},
+    Transport,
+};
+
+/// The first port handed out when binding to port `0`.
+const EPHEMERAL_PORT_START: u16 = 49152;
+
+/// A datagram travelling on a [`MemoryNetwork`], with the address it was sent from and its ECN codepoint.
+type Datagram = (Vec<u8>, SocketAddr, Ecn);
+
+/// A shared in-memory network.
+/// Cloning this creates a new handle to the same network.
+#[derive(Debug, Clone)]
+pub struct MemoryNetwork {
+    /// The sockets currently bound to the network.
+    sockets: Arc<DashMap<SocketAddr, UnboundedSender<Datagram>>>,
+
+    /// The addresses whose traffic (both inbound and outbound) is currently dropped.
+    blocked: Arc<DashSet<SocketAddr>>,
+
+    /// The addresses whose traffic (both inbound and outbound) is currently marked with [`Ecn::Ce`], if it is ECN capable.
+    congested: Arc<DashSet<SocketAddr>>,
+
+    /// The number of routers the datagrams pass on their way to (and from) the addresses.
+    hop_counts: Arc<DashMap<SocketAddr, u8>>,
+
+    /// The addresses behind a simulated NAT, with the time their binding expires after and the time they have last sent a datagram at.
+    nat_bindings: Arc<DashMap<SocketAddr, (Duration, Instant)>>,
+
+    /// The next port handed out when binding to port `0`.
+    next_port: Arc<AtomicU16>,
+}
+
+impl Default for MemoryNetwork {
+    fn default() -> Self {
+        Self {
+            sockets: Default::default(),
+            blocked: Default::default(),
+            congested: Default::default(),
+            hop_counts: Default::default(),
+            nat_bindings: Default::default(),
+            next_port: Arc::new(AtomicU16::new(EPHEMERAL_PORT_START)),
+        }
+    }
+}
+
+impl MemoryNetwork {
+    /// Creates a new, empty [`MemoryNetwork`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds a new [`MemorySocket`] to the address.
+    /// If the port of the address is `0` a free port is picked automaticly.
+    ///
+    /// # Error
+    /// Returns an error if the address is already in use.
+    pub fn bind(&self, mut addr: SocketAddr) -> io::Result<MemorySocket> {
+        if addr.port() == 0 {
+            addr.set_port(self.next_port.fetch_add(1, Ordering::Relaxed));
+        }
+
+        let (sender, receiver) = unbounded_channel();
+
+        match self.sockets.entry(addr) {
+            Entry::Occupied(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{addr} is already bound."),
+                ))
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(sender);
+            }
+        }
+
+        let (path_error_sender, path_error_receiver) = unbounded_channel();
+
+        Ok(MemorySocket {
+            local_addr: addr,
+            network: self.clone(),
+            receiver: Mutex::new(receiver),
+            ecn: AtomicU8::new(Ecn::NotEct.to_bits()),
+            dscp: AtomicU8::new(Dscp::DEFAULT.value()),
+            flow_label: AtomicU32::new(0),
+            hop_limit: AtomicU8::new(DEFAULT_HOP_LIMIT),
+            path_errors: AtomicBool::new(false),
+            path_error_sender,
+            path_error_receiver: Mutex::new(path_error_receiver),
+        })
+    }
+
+    /// Binds a new [`MemorySocket`] to a free port of the local IPV6 address.
+    pub fn bind_any(&self) -> io::Result<MemorySocket> {
+        self.bind(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 0))
+    }
+
+    /// Sets whether the traffic of the address should be dropped.
+    /// This can be used to simulate a peer disappearing from the network.
+    pub fn set_blocked(&self, addr: SocketAddr, blocked: bool) {
+        if blocked {
+            self.blocked.insert(addr);
+        } else {
+            self.blocked.remove(&addr);
+        }
+    }
+
+    /// Sets whether the traffic of the address should be marked with [`Ecn::Ce`], like a router with a congested queue would mark it.
+    /// Only the datagrams of the sockets which have enabled ECN are marked, the others are delivered unchanged.
+    pub fn set_congested(&self, addr: SocketAddr, congested: bool) {
+        if congested {
+            self.congested.insert(addr);
+        } else {
+            self.congested.remove(&addr);
+        }
+    }
+
+    /// Sets the number of routers the traffic of the address passes, the datagrams sent with a lower (or equal) hop limit expire on the way.
+    /// The addresses are directly reachable (without any routers) by default.
+    pub fn set_hop_count(&self, addr: SocketAddr, hop_count: u8) {
+        if hop_count == 0 {
+            self.hop_counts.remove(&addr);
+        } else {
+            self.hop_counts.insert(addr, hop_count);
+        }
+    }
+
+    /// Places the address behind a simulated NAT, which forgets its binding once the address hasn't sent anything for the `timeout`.
+    /// The datagrams sent to the address without a binding are silently dropped, until it sends a datagram again. The NAT is removed if the `timeout` is [`None`].
+    pub fn set_nat_timeout(&self, addr: SocketAddr, timeout: Option<Duration>) {
+        match timeout {
+            Some(timeout) => {
+                self.nat_bindings.insert(addr, (timeout, Instant::now()));
+            }
+            None => {
+                self.nat_bindings.remove(&addr);
+            }
+        }
+    }
+
+    ///
+    /// Delivers a datagram sent with the `hop_limit` to the socket bound to `target`.
+    ///
+    /// # Behavior
+    /// Datagrams sent to blocked addresses, or to the addresses whose NAT binding has expired are silently dropped, like they would be on a real network.
+    /// Returns the [`PathError`] the network reports if the datagram has expired on the way, or it was sent to an unbound address.
+    ///
+    fn deliver(
+        &self,
+        buf: &[u8],
+        source: SocketAddr,
+        target: SocketAddr,
+        mut ecn: Ecn,
+        hop_limit: u8,
+    ) -> Option<PathError> {
+        if self.blocked.contains(&source) || self.blocked.contains(&target) {
+            return None;
+        }
+
+        //The sent datagram creates (or refreshes) the binding of its source
+        if let Some(mut nat_binding) = self.nat_bindings.get_mut(&source) {
+            nat_binding.1 = Instant::now();
+        }
+
+        if self
+            .nat_bindings
+            .get(&target)
+            .is_some_and(|nat_binding| nat_binding.1.elapsed() >= nat_binding.0)
+        {
+            return None;
+        }
+
+        let hop_count = [source, target]
+            .iter()
+            .filter_map(|addr| self.hop_counts.get(addr).map(|hop_count| *hop_count))
+            .fold(0_u8, u8::saturating_add);
+
+        if hop_count != 0 && hop_limit <= hop_count {
+            return Some(PathError {
+                destination: target,
+                reporter: None,
+                kind: PathErrorKind::HopLimitExceeded,
+            });
+        }
+
+        if ecn.is_ect() && (self.congested.contains(&source) || self.congested.contains(&target)) {
+            ecn = Ecn::Ce;
+        }
+
+        let Some(sender) = self.sockets.get(&target) else {
+            return Some(PathError {
+                destination: target,
+                reporter: Some(target.ip()),
+                kind: PathErrorKind::PortUnreachable,
+            });
+        };
+
+        //The receiving socket might be shutting down, which we dont care about
+        let _ = sender.send((buf.to_vec(), source, ecn));
+
+        None
+    }
+}
+
+/// A socket bound to a [`MemoryNetwork`].
+/// The socket is unbound from the network when dropped.
+#[derive(Debug)]
+pub struct MemorySocket {
+    /// The address this socket is bound to.
+    local_addr: SocketAddr,
+
+    /// The network this socket is bound to.
+    network: MemoryNetwork,
+
+    /// The receiver of the incoming datagrams.
+    receiver: Mutex<UnboundedReceiver<Datagram>>,
+
+    /// The bits of the [`Ecn`] codepoint the sent datagrams are marked with.
+    ecn: AtomicU8,
+
+    /// The value of the [`Dscp`] the sent datagrams are marked with.
+    dscp: AtomicU8,
+
+    /// The flow label the sent datagrams are labeled with, `0` if it wasn't set.
+    flow_label: AtomicU32,
+
+    /// The hop limit the sent datagrams are sent with.
+    hop_limit: AtomicU8,
+
+    /// Whether the [`PathError`]s of the sent datagrams are reported.
+    path_errors: AtomicBool,
+
+    /// The sender of the reported [`PathError`]s.
+    path_error_sender: UnboundedSender<PathError>,
+
+    /// The receiver of the reported [`PathError`]s.
+    path_error_receiver: Mutex<UnboundedReceiver<PathError>>,
+}
+
+impl MemorySocket {
+    /// Returns the address this socket is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the [`Dscp`] set with [`Transport::set_traffic_class`].
+    pub fn dscp(&self) -> Dscp {
+        Dscp::new(self.dscp.load(Ordering::Relaxed)).unwrap_or_default()
+    }
+
+    /// Returns the flow label set with [`Transport::set_flow_label`], `0` if it wasn't set.
+    pub fn flow_label(&self) -> u32 {
+        self.flow_label.load(Ordering::Relaxed)
+    }
+
+    /// Returns the hop limit set with [`Transport::set_hop_limit`], [`DEFAULT_HOP_LIMIT`] if it wasn't set.
+    pub fn hop_limit(&self) -> u8 {
+        self.hop_limit.load(Ordering::Relaxed)
+    }
+}
+
+impl Transport for MemorySocket {
+    async fn send_datagram(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        let ecn = Ecn::from_bits(self.ecn.load(Ordering::Relaxed));
+
+        let hop_limit = self.hop_limit.load(Ordering::Relaxed);
+
+        if let Some(path_error) = self
+            .network
+            .deliver(buf, self.local_addr, target, ecn, hop_limit)
+        {
+            if self.path_errors.load(Ordering::Relaxed) {
+                let _ = self.path_error_sender.send(path_error);
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    async fn recv_datagram(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (byte_count, source, _) = self.recv_datagram_ecn(buf).await?;
+
+        Ok((byte_count, source))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    async fn recv_datagram_ecn(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Ecn)> {
+        let (datagram, source, ecn) =
+            self.receiver.lock().await.recv().await.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotConnected, "The socket was unbound.")
+            })?;
+
+        //Truncate the datagram like a real socket would
+        let byte_count = datagram.len().min(buf.len());
+
+        buf[..byte_count].copy_from_slice(&datagram[..byte_count]);
+
+        Ok((byte_count, source, ecn))
+    }
+
+    fn set_ecn(&self, ecn: Ecn) -> io::Result<()> {
+        self.ecn.store(ecn.to_bits(), Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn set_traffic_class(&self, dscp: Dscp) -> io::Result<()> {
+        self.dscp.store(dscp.value(), Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn set_flow_label(&self, flow_label: u32) -> io::Result<()> {
+        qos::check_flow_label(flow_label)?;
+
+        self.flow_label.store(flow_label, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn set_hop_limit(&self, hop_limit: u8) -> io::Result<()> {
+        self.hop_limit.store(hop_limit, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn enable_path_errors(&self) -> io::Result<()> {
+        self.path_errors.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    async fn recv_path_error(&self) -> io::Result<PathError> {
+        //The socket owns the sender, so the channel is never closed
+        self.path_error_receiver
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "The socket was unbound."))
+    }
+}
+
+impl Drop for MemorySocket {
+    fn drop(&mut self) {
+        self.network.sockets.remove(&self.local_addr);
+    }
+}
@@ -0,0 +1,294 @@
+//! Provides the [`Transport`] abstraction the client and server services are built upon.
+//!
+//! The services only ever send and receive whole datagrams, so any type which can do that can be used to carry [`VoipPacket`](crate::packet::VoipPacket)s.
+//! Transports can be wrapped with the [`layer`] API, to intercept the datagrams passing through them.
+
+use std::{future::Future, io, net::SocketAddr, sync::Arc};
+
+use tokio::net::UdpSocket;
+
+use ecn::Ecn;
+use path::PathError;
+use qos::Dscp;
+
+#[cfg(feature = "crypto")]
+pub mod cipher;
+pub mod ecn;
+pub mod layer;
+pub mod memory;
+pub mod padding;
+pub mod path;
+pub mod qos;
+#[cfg(unix)]
+pub mod unix;
+
+/// Datagram transport definition.
+///
+/// The [`Client`](super::client::Client) and the [`Server`](super::server::Server) services send and receive every message through a type implementing this trait.
+pub trait Transport: Send + Sync + 'static {
+    /// Sends a single datagram to the `target` address.
+    /// Returns the amount of bytes sent.
+    fn send_datagram(
+        &self,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> impl Future<Output = io::Result<usize>> + Send;
+
+    /// Receives a single datagram into `buf`.
+    /// Returns the amount of bytes read and the address the datagram was sent from.
+    fn recv_datagram(
+        &self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = io::Result<(usize, SocketAddr)>> + Send;
+
+    /// Returns the local address this transport is bound to.
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Receives a single datagram into `buf` like [`Transport::recv_datagram`], along with the [`Ecn`] codepoint it has arrived with.
+    /// Transports which can't read the codepoints report every datagram as [`Ecn::NotEct`].
+    fn recv_datagram_ecn(
+        &self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = io::Result<(usize, SocketAddr, Ecn)>> + Send {
+        async move {
+            let (byte_count, source) = self.recv_datagram(buf).await?;
+
+            Ok((byte_count, source, Ecn::NotEct))
+        }
+    }
+
+    /// Marks the datagrams sent from now on with the `ecn` codepoint, and starts reading the codepoints of the received datagrams.
+    /// Returns an error of the [`io::ErrorKind::Unsupported`] kind if the transport (or the operating system) doesn't allow it.
+    fn set_ecn(&self, ecn: Ecn) -> io::Result<()> {
+        let _ = ecn;
+
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "The transport doesn't support ECN.",
+        ))
+    }
+
+    /// Marks the datagrams sent from now on with the `dscp`, keeping their [`Ecn`] codepoint.
+    /// Returns an error of the [`io::ErrorKind::Unsupported`] kind if the transport (or the operating system) doesn't allow it.
+    fn set_traffic_class(&self, dscp: Dscp) -> io::Result<()> {
+        let _ = dscp;
+
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "The transport doesn't support traffic classes.",
+        ))
+    }
+
+    ///
+    /// Sends the datagrams to the connected IPv6 peer with the `flow_label` from now on, `0` lets the operating system choose the label.
+    ///
+    /// # Behavior
+    /// Linux only sends the labels leased by the application, which are leased by this call.
+    /// Linux reserves the labels from `0x80000` for its automatic labels by default (see the `flowlabel_state_ranges` option of the kernel), so the labels below it should be used.
+    /// The label is cleared when the socket is connected again.
+    ///
+    /// # Error
+    /// Returns an error of the [`io::ErrorKind::InvalidInput`] kind if the label doesn't fit into 20 bits (see [`qos::MAX_FLOW_LABEL`]).
+    /// Returns an error of the [`io::ErrorKind::Unsupported`] kind if the transport (or the operating system) doesn't allow it, or it isn't connected to an IPv6 peer.
+    ///
+    fn set_flow_label(&self, flow_label: u32) -> io::Result<()> {
+        let _ = flow_label;
+
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "The transport doesn't support flow labels.",
+        ))
+    }
+
+    /// Sends the datagrams from now on with the `hop_limit` (the TTL of the IPv4 datagrams), the routers drop them after forwarding them that many times.
+    /// Returns an error of the [`io::ErrorKind::Unsupported`] kind if the transport (or the operating system) doesn't allow it.
+    fn set_hop_limit(&self, hop_limit: u8) -> io::Result<()> {
+        let _ = hop_limit;
+
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "The transport doesn't support hop limits.",
+        ))
+    }
+
+    ///
+    /// Starts collecting the errors the network reports about the sent datagrams, which are received with [`Transport::recv_path_error`].
+    ///
+    /// # Error
+    /// Returns an error of the [`io::ErrorKind::Unsupported`] kind if the transport (or the operating system) doesn't report them, see [`path`] for the platforms which do.
+    ///
+    fn enable_path_errors(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "The transport doesn't report path errors.",
+        ))
+    }
+
+    /// Receives a single [`PathError`] reported about a sent datagram, once they were enabled with [`Transport::enable_path_errors`].
+    /// Transports which don't report them never return.
+    fn recv_path_error(&self) -> impl Future<Output = io::Result<PathError>> + Send {
+        std::future::pending()
+    }
+}
+
+/// Shared transports can be used by the services, while the application keeps a handle to them.
+impl<T: Transport> Transport for Arc<T> {
+    fn send_datagram(
+        &self,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> impl Future<Output = io::Result<usize>> + Send {
+        T::send_datagram(self, buf, target)
+    }
+
+    fn recv_datagram(
+        &self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = io::Result<(usize, SocketAddr)>> + Send {
+        T::recv_datagram(self, buf)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        T::local_addr(self)
+    }
+
+    fn recv_datagram_ecn(
+        &self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = io::Result<(usize, SocketAddr, Ecn)>> + Send {
+        T::recv_datagram_ecn(self, buf)
+    }
+
+    fn set_ecn(&self, ecn: Ecn) -> io::Result<()> {
+        T::set_ecn(self, ecn)
+    }
+
+    fn set_traffic_class(&self, dscp: Dscp) -> io::Result<()> {
+        T::set_traffic_class(self, dscp)
+    }
+
+    fn set_flow_label(&self, flow_label: u32) -> io::Result<()> {
+        T::set_flow_label(self, flow_label)
+    }
+
+    fn set_hop_limit(&self, hop_limit: u8) -> io::Result<()> {
+        T::set_hop_limit(self, hop_limit)
+    }
+
+    fn enable_path_errors(&self) -> io::Result<()> {
+        T::enable_path_errors(self)
+    }
+
+    fn recv_path_error(&self) -> impl Future<Output = io::Result<PathError>> + Send {
+        T::recv_path_error(self)
+    }
+}
+
+impl Transport for UdpSocket {
+    async fn send_datagram(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        //Connected sockets may refuse `send_to` on some platforms, so use `send` if we are sending to the connected peer
+        match self.peer_addr() {
+            Ok(peer_addr) if peer_addr == target => self.send(buf).await,
+            _ => self.send_to(buf, target).await,
+        }
+    }
+
+    async fn recv_datagram(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.recv_from(buf).await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.local_addr()
+    }
+
+    #[cfg(unix)]
+    async fn recv_datagram_ecn(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Ecn)> {
+        self.async_io(tokio::io::Interest::READABLE, || {
+            ecn::sys::recv_with_ecn(self, buf)
+        })
+        .await
+    }
+
+    #[cfg(unix)]
+    fn set_ecn(&self, ecn: Ecn) -> io::Result<()> {
+        ecn::sys::set_ecn(self, self.local_addr()?.is_ipv6(), ecn)
+    }
+
+    #[cfg(unix)]
+    fn set_traffic_class(&self, dscp: Dscp) -> io::Result<()> {
+        qos::sys::set_dscp(self, self.local_addr()?.is_ipv6(), dscp)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_flow_label(&self, flow_label: u32) -> io::Result<()> {
+        qos::check_flow_label(flow_label)?;
+
+        //Only the IPv6 datagrams have a flow label, the IPv4 mapped peers of dual-stack sockets are sent IPv4 datagrams
+        match self.peer_addr()? {
+            SocketAddr::V6(peer_addr) if peer_addr.ip().to_ipv4_mapped().is_none() => {
+                qos::sys::set_flow_label(self, peer_addr, flow_label)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "The socket isn't connected to an IPv6 peer.",
+            )),
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_hop_limit(&self, hop_limit: u8) -> io::Result<()> {
+        path::sys::set_hop_limit(self, self.local_addr()?.is_ipv6(), hop_limit)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn enable_path_errors(&self) -> io::Result<()> {
+        path::sys::enable_path_errors(self, self.local_addr()?.is_ipv6())
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn recv_path_error(&self) -> io::Result<PathError> {
+        //The queued errors are signaled as an error condition of the socket, instead of its readability
+        self.async_io(tokio::io::Interest::ERROR, || {
+            path::sys::recv_path_error(self)
+        })
+        .await
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl Transport for async_std::net::UdpSocket {
+    async fn send_datagram(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        //Connected sockets may refuse `send_to` on some platforms, so use `send` if we are sending to the connected peer
+        match self.peer_addr() {
+            Ok(peer_addr) if peer_addr == target => self.send(buf).await,
+            _ => self.send_to(buf, target).await,
+        }
+    }
+
+    async fn recv_datagram(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.recv_from(buf).await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.local_addr()
+    }
+}
+
+#[cfg(feature = "smol")]
+impl Transport for smol::net::UdpSocket {
+    async fn send_datagram(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        //Connected sockets may refuse `send_to` on some platforms, so use `send` if we are sending to the connected peer
+        match self.peer_addr() {
+            Ok(peer_addr) if peer_addr == target => self.send(buf).await,
+            _ => self.send_to(buf, target).await,
+        }
+    }
+
+    async fn recv_datagram(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.recv_from(buf).await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.local_addr()
+    }
+}
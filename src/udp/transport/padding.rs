@@ -0,0 +1,78 @@
+//!
+//! Provides the [`PaddingInterceptor`], which pads every datagram of a [`Transport`](super::Transport) to a multiple of a block size.
+//!
+//! The size of the encrypted voice still tells when someone is speaking (and even what, with variable bitrate codecs), so privacy focused deployments pad it away.
+//! Padding every datagram to the same block before it is encrypted hides the sizes, and the cover traffic of [`ClientConfig::traffic_shaping`](crate::udp::client::ClientConfig::traffic_shaping) hides the pauses of the voice.
+//!
+//! Both ends of a session have to use the same interceptor, as the padding is removed by the receiving one.
+//! The padding layer has to be applied after the [`CipherInterceptor`](super::cipher::CipherInterceptor), so that the datagrams are padded before they are encrypted.
+//!
+
+use std::net::SocketAddr;
+
+use tracing::{event, Level};
+
+use super::layer::Interceptor;
+
+/// The length of the trailer appended to every padded datagram, which stores the length of the padding.
+pub const PADDING_TRAILER_SIZE: usize = 2;
+
+///
+/// Padding interceptor type definition.
+///
+/// Pads every outgoing datagram to a multiple of the block size, and removes the padding of every incoming datagram.
+/// The padding is appended to the datagram, followed by its length (including the trailer) as a big endian [`u16`].
+/// The incoming datagrams without a valid padding are dropped.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaddingInterceptor {
+    /// The size (in bytes) every datagram is padded to a multiple of.
+    block_size: usize,
+}
+
+impl PaddingInterceptor {
+    /// Creates a new [`PaddingInterceptor`] instance, padding the datagrams to a multiple of the `block_size` bytes.
+    /// A `block_size` at least as large as the largest datagram pads every datagram to the same size, the block size is capped at [`u16::MAX`].
+    pub fn new(block_size: usize) -> Self {
+        Self {
+            block_size: block_size.clamp(PADDING_TRAILER_SIZE, u16::MAX as usize),
+        }
+    }
+
+    /// Returns the size (in bytes) every datagram is padded to a multiple of.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+}
+
+impl Interceptor for PaddingInterceptor {
+    fn on_send(&self, mut datagram: Vec<u8>, _target: SocketAddr) -> Option<Vec<u8>> {
+        let padded_length =
+            (datagram.len() + PADDING_TRAILER_SIZE).next_multiple_of(self.block_size);
+        let padding_length = padded_length - datagram.len();
+
+        datagram.resize(padded_length - PADDING_TRAILER_SIZE, 0);
+        datagram.extend_from_slice(&(padding_length as u16).to_be_bytes());
+
+        Some(datagram)
+    }
+
+    fn on_recv(&self, mut datagram: Vec<u8>, source: SocketAddr) -> Option<Vec<u8>> {
+        let trailer_start = datagram.len().checked_sub(PADDING_TRAILER_SIZE)?;
+        let padding_length =
+            u16::from_be_bytes([datagram[trailer_start], datagram[trailer_start + 1]]) as usize;
+
+        if !(PADDING_TRAILER_SIZE..=datagram.len()).contains(&padding_length) {
+            event!(
+                Level::DEBUG,
+                "Dropped a datagram from {source} with an invalid padding."
+            );
+
+            return None;
+        }
+
+        datagram.truncate(datagram.len() - padding_length);
+
+        Some(datagram)
+    }
+}
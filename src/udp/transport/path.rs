@@ -0,0 +1,327 @@
+//!
+//! Provides the hop limit of the datagrams, and the [`PathError`]s the network reports about their path.
+//!
+//! The routers (and the hosts) which can't deliver a datagram report it back with an ICMP error, for example when the destination is unreachable or the hop limit of the datagram has expired on the way.
+//! Without reading these errors the datagrams of an unreachable peer are silently blackholed. Transports which can read them (see [`Transport::enable_path_errors`](super::Transport::enable_path_errors)) report them as [`PathError`]s instead.
+//! The operating systems only pass on a subset of the errors: Linux reports every ICMP error of the sent datagrams, while the other systems only report the unreachable ports of connected sockets as [`io::ErrorKind::ConnectionRefused`] errors.
+//!
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+};
+
+/// The hop limit the operating systems send the datagrams with by default.
+pub const DEFAULT_HOP_LIMIT: u8 = 64;
+
+///
+/// Path error type definition.
+///
+/// An error the network has reported about a datagram sent through the transport.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+pub struct PathError {
+    /// The address the datagram was sent to.
+    pub destination: SocketAddr,
+
+    /// The address of the router (or host) which has reported the error, if the operating system passes it on.
+    pub reporter: Option<IpAddr>,
+
+    /// The kind of the error.
+    pub kind: PathErrorKind,
+}
+
+/// The kinds of the [`PathError`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathErrorKind {
+    /// No route leads to the network of the destination.
+    NetworkUnreachable,
+
+    /// The destination host can't be reached.
+    HostUnreachable,
+
+    /// Nothing listens on the port of the destination, it has likely stopped.
+    PortUnreachable,
+
+    /// A router (or a firewall) has refused forwarding the datagram.
+    Prohibited,
+
+    /// The hop limit (or TTL) of the datagram has expired before it has reached the destination.
+    HopLimitExceeded,
+
+    /// The datagram was too large for a link of the path.
+    PacketTooBig {
+        /// The largest datagram (in bytes) the link can carry, `0` if it wasn't reported.
+        mtu: u32,
+    },
+
+    /// An error without a dedicated kind, with its ICMP type and code.
+    Other {
+        /// The type of the ICMP (or ICMPv6) message.
+        icmp_type: u8,
+        /// The code of the ICMP (or ICMPv6) message.
+        code: u8,
+    },
+}
+
+impl PathErrorKind {
+    /// Creates the [`PathErrorKind`] of an ICMP message of the `icmp_type` and `code`, reporting a datagram which couldn't be delivered.
+    pub fn from_icmp(icmp_type: u8, code: u8, mtu: u32) -> Self {
+        match (icmp_type, code) {
+            (3, 0) => Self::NetworkUnreachable,
+            (3, 1) => Self::HostUnreachable,
+            (3, 3) => Self::PortUnreachable,
+            (3, 4) => Self::PacketTooBig { mtu },
+            (3, 9 | 10 | 13) => Self::Prohibited,
+            (11, _) => Self::HopLimitExceeded,
+            _ => Self::Other { icmp_type, code },
+        }
+    }
+
+    /// Creates the [`PathErrorKind`] of an ICMPv6 message of the `icmp_type` and `code`, reporting a datagram which couldn't be delivered.
+    pub fn from_icmpv6(icmp_type: u8, code: u8, mtu: u32) -> Self {
+        match (icmp_type, code) {
+            (1, 0) => Self::NetworkUnreachable,
+            (1, 1) => Self::Prohibited,
+            (1, 3) => Self::HostUnreachable,
+            (1, 4) => Self::PortUnreachable,
+            (2, _) => Self::PacketTooBig { mtu },
+            (3, 0) => Self::HopLimitExceeded,
+            _ => Self::Other { icmp_type, code },
+        }
+    }
+}
+
+/// Returns whether the `err` returned by a receive is an ICMP error, which the transports reporting [`PathError`]s report again from their error queues.
+pub(crate) fn is_path_error(err: &io::Error) -> bool {
+    if err.kind() == io::ErrorKind::ConnectionRefused {
+        return true;
+    }
+
+    #[cfg(unix)]
+    if let Some(errno) = err.raw_os_error() {
+        return matches!(
+            errno,
+            libc::EHOSTUNREACH | libc::ENETUNREACH | libc::EMSGSIZE | libc::EACCES
+        );
+    }
+
+    false
+}
+
+/// The socket options setting the hop limit, and the reading of the ICMP errors from the error queue on unix sockets.
+#[cfg(unix)]
+pub(super) mod sys {
+    use std::{io, os::fd::AsRawFd};
+
+    use super::super::ecn;
+
+    ///
+    /// Sends the datagrams of the `socket` with the `hop_limit`.
+    ///
+    /// # Behavior
+    /// IPv6 sockets set both the IPv6 and the IPv4 options, so that the IPv4 mapped peers of dual-stack sockets are covered too.
+    /// The IPv4 options of a IPv6-only socket may be refused, which is ignored.
+    ///
+    pub(in crate::udp::transport) fn set_hop_limit(
+        socket: &impl AsRawFd,
+        is_ipv6: bool,
+        hop_limit: u8,
+    ) -> io::Result<()> {
+        let fd = socket.as_raw_fd();
+        let hop_limit = hop_limit as libc::c_int;
+
+        if is_ipv6 {
+            ecn::sys::set_option(fd, libc::IPPROTO_IPV6, libc::IPV6_UNICAST_HOPS, hop_limit)?;
+
+            let _ = ecn::sys::set_option(fd, libc::IPPROTO_IP, libc::IP_TTL, hop_limit);
+        } else {
+            ecn::sys::set_option(fd, libc::IPPROTO_IP, libc::IP_TTL, hop_limit)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(in crate::udp::transport) use linux::{enable_path_errors, recv_path_error};
+
+    /// The error queue of the Linux sockets.
+    #[cfg(target_os = "linux")]
+    mod linux {
+        use std::{
+            io,
+            mem::{self, MaybeUninit},
+            net::{IpAddr, Ipv4Addr, Ipv6Addr},
+            os::fd::AsRawFd,
+        };
+
+        use super::super::{
+            super::ecn::{self, sys::socket_addr},
+            PathError, PathErrorKind,
+        };
+
+        /// The errors reported by a local check of the kernel (for example the MTU of the interface).
+        const SO_EE_ORIGIN_LOCAL: u8 = 1;
+
+        /// The errors reported by an ICMP message.
+        const SO_EE_ORIGIN_ICMP: u8 = 2;
+
+        /// The errors reported by an ICMPv6 message.
+        const SO_EE_ORIGIN_ICMP6: u8 = 3;
+
+        /// The size of the buffer the control messages are received into, which fits the extended error and the address of its reporter.
+        const CONTROL_BUFFER_SIZE: usize = 128;
+
+        /// The `sock_extended_err` of the error queue, followed by the address of the reporter.
+        #[repr(C)]
+        struct ExtendedError {
+            errno: u32,
+            origin: u8,
+            icmp_type: u8,
+            code: u8,
+            padding: u8,
+            info: u32,
+            data: u32,
+        }
+
+        ///
+        /// Queues the ICMP errors of the datagrams sent through the `socket` on its error queue.
+        ///
+        /// # Behavior
+        /// IPv6 sockets set both the IPv6 and the IPv4 options, so that the IPv4 mapped peers of dual-stack sockets are covered too.
+        ///
+        pub(in crate::udp::transport) fn enable_path_errors(
+            socket: &impl AsRawFd,
+            is_ipv6: bool,
+        ) -> io::Result<()> {
+            let fd = socket.as_raw_fd();
+
+            if is_ipv6 {
+                ecn::sys::set_option(fd, libc::IPPROTO_IPV6, libc::IPV6_RECVERR, 1)?;
+
+                let _ = ecn::sys::set_option(fd, libc::IPPROTO_IP, libc::IP_RECVERR, 1);
+            } else {
+                ecn::sys::set_option(fd, libc::IPPROTO_IP, libc::IP_RECVERR, 1)?;
+            }
+
+            Ok(())
+        }
+
+        ///
+        /// Receives a single [`PathError`] from the error queue of the non-blocking `socket`.
+        ///
+        /// # Behavior
+        /// Returns [`io::ErrorKind::WouldBlock`] if the error queue is empty.
+        ///
+        pub(in crate::udp::transport) fn recv_path_error(
+            socket: &impl AsRawFd,
+        ) -> io::Result<PathError> {
+            let mut destination = MaybeUninit::<libc::sockaddr_storage>::zeroed();
+            let mut control = [0u64; CONTROL_BUFFER_SIZE / mem::size_of::<u64>()];
+
+            //Only the error is read, the returned part of the datagram is truncated
+            let mut payload = [0u8; 1];
+            let mut iovec = libc::iovec {
+                iov_base: payload.as_mut_ptr() as *mut libc::c_void,
+                iov_len: payload.len(),
+            };
+
+            // SAFETY: An all zero `msghdr` is valid, the pointers set below outlive the call.
+            let mut message: libc::msghdr = unsafe { mem::zeroed() };
+
+            message.msg_name = destination.as_mut_ptr() as *mut libc::c_void;
+            message.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            message.msg_iov = &mut iovec;
+            message.msg_iovlen = 1;
+            message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            message.msg_controllen = CONTROL_BUFFER_SIZE as _;
+
+            // SAFETY: Every buffer of the `msghdr` is valid for its advertised length.
+            let result =
+                unsafe { libc::recvmsg(socket.as_raw_fd(), &mut message, libc::MSG_ERRQUEUE) };
+
+            if result == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // SAFETY: The kernel has written the address the erroneous datagram was sent to into the storage.
+            let destination = unsafe { socket_addr(destination.assume_init_ref()) }?;
+
+            // SAFETY: The control messages were written by the kernel into the control buffer of the `msghdr`, which is still alive.
+            unsafe {
+                let mut control_message = libc::CMSG_FIRSTHDR(&message);
+
+                while !control_message.is_null() {
+                    let (level, message_type) =
+                        ((*control_message).cmsg_level, (*control_message).cmsg_type);
+
+                    if (level == libc::IPPROTO_IP && message_type == libc::IP_RECVERR)
+                        || (level == libc::IPPROTO_IPV6 && message_type == libc::IPV6_RECVERR)
+                    {
+                        let data = libc::CMSG_DATA(control_message);
+                        let extended_error = (data as *const ExtendedError).read_unaligned();
+                        let reporter = reporter(data.add(mem::size_of::<ExtendedError>()));
+
+                        let kind = match extended_error.origin {
+                            SO_EE_ORIGIN_ICMP => PathErrorKind::from_icmp(
+                                extended_error.icmp_type,
+                                extended_error.code,
+                                extended_error.info,
+                            ),
+                            SO_EE_ORIGIN_ICMP6 => PathErrorKind::from_icmpv6(
+                                extended_error.icmp_type,
+                                extended_error.code,
+                                extended_error.info,
+                            ),
+                            SO_EE_ORIGIN_LOCAL if extended_error.errno == libc::EMSGSIZE as u32 => {
+                                PathErrorKind::PacketTooBig {
+                                    mtu: extended_error.info,
+                                }
+                            }
+                            _ => PathErrorKind::Other {
+                                icmp_type: extended_error.icmp_type,
+                                code: extended_error.code,
+                            },
+                        };
+
+                        return Ok(PathError {
+                            destination,
+                            reporter,
+                            kind,
+                        });
+                    }
+
+                    control_message = libc::CMSG_NXTHDR(&message, control_message);
+                }
+            }
+
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The error queue has returned a message without an extended error.",
+            ))
+        }
+
+        /// Reads the address of the reporter following the extended error.
+        ///
+        /// # Safety
+        /// The `data` has to point to the address of the reporter of an extended error written by the kernel, which is `AF_UNSPEC` if there is none.
+        unsafe fn reporter(data: *const u8) -> Option<IpAddr> {
+            let family = (data as *const libc::sockaddr).read_unaligned().sa_family as libc::c_int;
+
+            match family {
+                libc::AF_INET => {
+                    let addr = (data as *const libc::sockaddr_in).read_unaligned();
+
+                    Some(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)).into())
+                }
+                libc::AF_INET6 => {
+                    let addr = (data as *const libc::sockaddr_in6).read_unaligned();
+
+                    Some(Ipv6Addr::from(addr.sin6_addr.s6_addr).into())
+                }
+                _ => None,
+            }
+        }
+    }
+}
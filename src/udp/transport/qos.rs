@@ -0,0 +1,208 @@
+//!
+//! Provides the quality of service markings of the datagrams: the [`Dscp`] of their traffic class, and the IPv6 flow label of their flow.
+//!
+//! The networks honoring the markings (like the ISPs and the enterprise QoS policies) queue the datagrams marked with [`Dscp::EF`] ahead of the bulk traffic.
+//! The routers balancing the load over multiple paths keep the datagrams of the same flow label on the same path, so the voice isn't reordered or delayed by a change of the path.
+//! The DSCP shares the traffic class (or TOS) byte with the [`Ecn`](super::ecn::Ecn) codepoint, setting either of them keeps the other one.
+//!
+
+/// The largest IPv6 flow label, the label is the 20 low bits of the flow information.
+pub const MAX_FLOW_LABEL: u32 = 0xF_FFFF;
+
+///
+/// DSCP type definition.
+///
+/// The Differentiated Services Code Point, the six high bits of the IPv4 TOS (or the IPv6 traffic class) field of a datagram, see [RFC 2474](https://www.rfc-editor.org/rfc/rfc2474).
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Dscp(u8);
+
+impl Dscp {
+    /// The default, best effort forwarding.
+    pub const DEFAULT: Self = Self(0);
+
+    /// The assured forwarding class of the interactive video, see [RFC 4594](https://www.rfc-editor.org/rfc/rfc4594).
+    pub const AF41: Self = Self(34);
+
+    /// The expedited forwarding of the voice traffic, see [RFC 3246](https://www.rfc-editor.org/rfc/rfc3246).
+    pub const EF: Self = Self(46);
+
+    /// Creates a new [`Dscp`] instance from its `value`.
+    /// Returns [`None`] if the value doesn't fit into six bits.
+    pub fn new(value: u8) -> Option<Self> {
+        (value < 64).then_some(Self(value))
+    }
+
+    /// Returns the value of the code point.
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    /// Creates the [`Dscp`] from the six high bits of the TOS (or traffic class) byte.
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits >> 2)
+    }
+
+    /// Returns the bits of the code point in the TOS (or traffic class) byte.
+    pub fn to_bits(self) -> u8 {
+        self.0 << 2
+    }
+}
+
+///
+/// Quality of service configuration type definition.
+///
+/// Describes the markings the [`Client`](crate::udp::client::Client) sends its datagrams with.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QosConfig {
+    /// The [`Dscp`] the datagrams are marked with, the traffic class is left unchanged if this is [`None`].
+    pub dscp: Option<Dscp>,
+
+    /// The IPv6 flow label the datagrams are sent with (see [`Transport::set_flow_label`](super::Transport::set_flow_label)), the label is chosen by the operating system if this is [`None`].
+    pub flow_label: Option<u32>,
+}
+
+impl Default for QosConfig {
+    fn default() -> Self {
+        Self {
+            dscp: Some(Dscp::EF),
+            flow_label: None,
+        }
+    }
+}
+
+/// Returns an error of the [`std::io::ErrorKind::InvalidInput`] kind if the `flow_label` doesn't fit into 20 bits.
+pub(super) fn check_flow_label(flow_label: u32) -> std::io::Result<()> {
+    if flow_label > MAX_FLOW_LABEL {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "The flow label doesn't fit into 20 bits.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// The socket options setting the DSCP and the flow label on unix sockets.
+#[cfg(unix)]
+pub(super) mod sys {
+    use std::{io, os::fd::AsRawFd};
+
+    use super::{super::ecn, Dscp};
+
+    /// Marks the datagrams sent through the `socket` with the `dscp`, keeping their ECN codepoint.
+    pub(in crate::udp::transport) fn set_dscp(
+        socket: &impl AsRawFd,
+        is_ipv6: bool,
+        dscp: Dscp,
+    ) -> io::Result<()> {
+        ecn::sys::update_traffic_class(socket.as_raw_fd(), is_ipv6, !0b11, dscp.to_bits())
+    }
+
+    /// The socket option leasing the flow labels, which the kernel only sends the leased labels with.
+    #[cfg(target_os = "linux")]
+    const IPV6_FLOWLABEL_MGR: libc::c_int = 32;
+
+    /// The socket option enabling the flow labels set by the application.
+    #[cfg(target_os = "linux")]
+    const IPV6_FLOWINFO_SEND: libc::c_int = 33;
+
+    /// The lease action of the [`FlowLabelRequest`].
+    #[cfg(target_os = "linux")]
+    const IPV6_FL_A_GET: u8 = 0;
+
+    /// The label can be shared by the sockets of the process, so the socket can lease it again.
+    #[cfg(target_os = "linux")]
+    const IPV6_FL_S_PROCESS: u8 = 2;
+
+    /// The label is created if it isn't leased yet.
+    #[cfg(target_os = "linux")]
+    const IPV6_FL_F_CREATE: u16 = 1;
+
+    /// The `in6_flowlabel_req` of the `IPV6_FLOWLABEL_MGR` option.
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    struct FlowLabelRequest {
+        destination: libc::in6_addr,
+        label: u32,
+        action: u8,
+        share: u8,
+        flags: u16,
+        expires: u16,
+        linger: u16,
+        padding: u32,
+    }
+
+    ///
+    /// Sends the datagrams of the connected `socket` with the `flow_label`, by leasing the label and connecting the socket to the `peer_addr` again with it.
+    ///
+    /// # Behavior
+    /// The label `0` lets the kernel choose the label of the flow again.
+    ///
+    #[cfg(target_os = "linux")]
+    pub(in crate::udp::transport) fn set_flow_label(
+        socket: &impl AsRawFd,
+        peer_addr: std::net::SocketAddrV6,
+        flow_label: u32,
+    ) -> io::Result<()> {
+        let fd = socket.as_raw_fd();
+
+        if flow_label != 0 {
+            let request = FlowLabelRequest {
+                destination: libc::in6_addr {
+                    s6_addr: peer_addr.ip().octets(),
+                },
+                label: flow_label.to_be(),
+                action: IPV6_FL_A_GET,
+                share: IPV6_FL_S_PROCESS,
+                flags: IPV6_FL_F_CREATE,
+                expires: 0,
+                linger: 0,
+                padding: 0,
+            };
+
+            // SAFETY: The request is a valid `in6_flowlabel_req` which outlives the call, and its size is passed along.
+            let result = unsafe {
+                libc::setsockopt(
+                    fd,
+                    libc::IPPROTO_IPV6,
+                    IPV6_FLOWLABEL_MGR,
+                    &request as *const FlowLabelRequest as *const libc::c_void,
+                    std::mem::size_of::<FlowLabelRequest>() as libc::socklen_t,
+                )
+            };
+
+            if result == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        ecn::sys::set_option(fd, libc::IPPROTO_IPV6, IPV6_FLOWINFO_SEND, 1)?;
+
+        // SAFETY: An all zero `sockaddr_in6` is valid.
+        let mut sockaddr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+
+        sockaddr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+        sockaddr.sin6_port = peer_addr.port().to_be();
+        sockaddr.sin6_addr.s6_addr = peer_addr.ip().octets();
+        //The kernel reads the flow information in network byte order
+        sockaddr.sin6_flowinfo = flow_label.to_be();
+        sockaddr.sin6_scope_id = peer_addr.scope_id();
+
+        // SAFETY: The address is a valid `sockaddr_in6` of the advertised length.
+        let result = unsafe {
+            libc::connect(
+                fd,
+                &sockaddr as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+            )
+        };
+
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
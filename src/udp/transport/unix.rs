@@ -0,0 +1,126 @@
+//!
+//! Provides a [`Transport`] over Unix datagram sockets, so co-located processes (for example a capture daemon and a network daemon) can exchange [`VoipPacket`](crate::packet::VoipPacket)s with the same API used over the network.
+//!
+//! The services identify peers by [`SocketAddr`], so every peer path is mapped to a synthetic loopback address.
+//! Register the path of a peer with [`UnixTransport::register_peer`] to get the address it can be reached at.
+//! Peers which send a datagram are registered automaticly, so a server doesn't have to know its clients in advance.
+//!
+//! ***Only peers bound to a path can be replied to, as unnamed sockets have no address to send to.***
+//!
+
+use std::{
+    io,
+    net::{Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU16, Ordering},
+};
+
+use dashmap::DashMap;
+use tokio::net::UnixDatagram;
+
+use super::Transport;
+
+/// The port of the synthetic address every unnamed peer is mapped to.
+/// Datagrams can't be sent to this address.
+const UNNAMED_PEER_PORT: u16 = 0;
+
+///
+/// Unix datagram transport type definition.
+///
+/// Wraps a [`UnixDatagram`] bound to a path, and maps the paths of its peers to synthetic [`SocketAddr`]s.
+///
+#[derive(Debug)]
+pub struct UnixTransport {
+    /// The underlying socket.
+    socket: UnixDatagram,
+
+    /// The synthetic address of this transport.
+    local_addr: SocketAddr,
+
+    /// The path of every registered peer, by its synthetic address.
+    peer_paths: DashMap<SocketAddr, PathBuf>,
+
+    /// The synthetic address of every registered peer, by its path.
+    peer_addrs: DashMap<PathBuf, SocketAddr>,
+
+    /// The port of the next synthetic address.
+    next_port: AtomicU16,
+}
+
+impl UnixTransport {
+    /// Creates a new [`UnixTransport`] instance, bound to the `path`.
+    /// The socket file must not exist yet.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            socket: UnixDatagram::bind(path)?,
+            local_addr: synthetic_addr(1),
+            peer_paths: DashMap::new(),
+            peer_addrs: DashMap::new(),
+            next_port: AtomicU16::new(2),
+        })
+    }
+
+    /// Registers the peer bound to the `path`.
+    /// Returns the synthetic address the peer can be reached at, which is the same for every registration of the same path.
+    pub fn register_peer(&self, path: impl AsRef<Path>) -> SocketAddr {
+        let path = path.as_ref();
+
+        if let Some(peer_addr) = self.peer_addrs.get(path) {
+            return *peer_addr;
+        }
+
+        *self
+            .peer_addrs
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                let peer_addr = synthetic_addr(self.next_port.fetch_add(1, Ordering::Relaxed));
+
+                self.peer_paths.insert(peer_addr, path.to_path_buf());
+
+                peer_addr
+            })
+    }
+
+    /// Returns the path of the peer registered at the synthetic `peer_addr`.
+    pub fn peer_path(&self, peer_addr: SocketAddr) -> Option<PathBuf> {
+        self.peer_paths.get(&peer_addr).map(|path| path.clone())
+    }
+
+    /// Returns the underlying [`UnixDatagram`].
+    pub fn socket(&self) -> &UnixDatagram {
+        &self.socket
+    }
+}
+
+impl Transport for UnixTransport {
+    async fn send_datagram(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        let path = self.peer_path(target).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No peer is registered at {target}."),
+            )
+        })?;
+
+        self.socket.send_to(buf, path).await
+    }
+
+    async fn recv_datagram(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (byte_count, source) = self.socket.recv_from(buf).await?;
+
+        let source = match source.as_pathname() {
+            Some(path) => self.register_peer(path),
+            None => synthetic_addr(UNNAMED_PEER_PORT),
+        };
+
+        Ok((byte_count, source))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Creates the synthetic address with the `port`.
+fn synthetic_addr(port: u16) -> SocketAddr {
+    SocketAddr::new(Ipv6Addr::LOCALHOST.into(), port)
+}
@@ -0,0 +1,218 @@
+//!
+//! Provides the data usage of the session of a [`Client`](super::client::Client), so that the applications can show the users on metered connections how much data a call has used.
+//!
+//! The client service counts every datagram it sends and receives through its [`Transport`], including the heartbeats, the retransmissions and the messages of the other addresses which are ignored.
+//! The bytes are counted at the UDP payload, the IP and UDP headers (28 bytes on IPv4, 48 bytes on IPv6) are added by the operating system on top of every datagram.
+//!
+
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use super::transport::{ecn::Ecn, path::PathError, qos::Dscp, Transport};
+
+///
+/// Data usage type definition.
+///
+/// A snapshot of the data a session has used, as returned by [`Client::data_usage`](super::client::Client::data_usage).
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DataUsage {
+    /// The bytes sent in the session.
+    pub bytes_sent: u64,
+
+    /// The bytes received in the session.
+    pub bytes_received: u64,
+
+    /// The amount of datagrams sent in the session.
+    pub datagrams_sent: u64,
+
+    /// The amount of datagrams received in the session.
+    pub datagrams_received: u64,
+
+    /// The time the session has lasted for, until now or until it was closed.
+    pub duration: Duration,
+}
+
+impl DataUsage {
+    /// Returns the bytes sent and received in the session.
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes_sent + self.bytes_received
+    }
+
+    /// Returns the average bitrate (in bits per second) the session has sent at, `0` if it hasn't lasted for any time yet.
+    pub fn average_send_bitrate(&self) -> u32 {
+        average_bitrate(self.bytes_sent, self.duration)
+    }
+
+    /// Returns the average bitrate (in bits per second) the session has received at, `0` if it hasn't lasted for any time yet.
+    pub fn average_receive_bitrate(&self) -> u32 {
+        average_bitrate(self.bytes_received, self.duration)
+    }
+}
+
+/// Returns the average bitrate (in bits per second) of the `bytes` transferred during the `duration`.
+fn average_bitrate(bytes: u64, duration: Duration) -> u32 {
+    if duration.is_zero() {
+        return 0;
+    }
+
+    (bytes as f64 * 8.0 / duration.as_secs_f64()).min(u32::MAX as f64) as u32
+}
+
+///
+/// Data usage meter type definition.
+///
+/// Counts the data of a session, shared between the [`Client`](super::client::Client) and its client service.
+///
+#[derive(Debug)]
+pub(crate) struct UsageMeter {
+    /// The bytes sent in the session.
+    bytes_sent: AtomicU64,
+
+    /// The bytes received in the session.
+    bytes_received: AtomicU64,
+
+    /// The amount of datagrams sent in the session.
+    datagrams_sent: AtomicU64,
+
+    /// The amount of datagrams received in the session.
+    datagrams_received: AtomicU64,
+
+    /// The time the session was started at.
+    started_at: Instant,
+
+    /// The time the session has ended at, once the client service has shut down.
+    ended_at: Mutex<Option<Instant>>,
+}
+
+impl UsageMeter {
+    /// Creates a new [`UsageMeter`] instance, for a session starting now.
+    pub(crate) fn new() -> Self {
+        Self {
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            datagrams_sent: AtomicU64::new(0),
+            datagrams_received: AtomicU64::new(0),
+            started_at: Instant::now(),
+            ended_at: Mutex::new(None),
+        }
+    }
+
+    /// Stops the clock of the session, the datagrams are still counted afterwards.
+    pub(crate) fn end(&self) {
+        self.ended_at.lock().get_or_insert_with(Instant::now);
+    }
+
+    /// Returns the [`DataUsage`] of the session so far.
+    pub(crate) fn snapshot(&self) -> DataUsage {
+        let ended_at = self.ended_at.lock().unwrap_or_else(Instant::now);
+
+        DataUsage {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            datagrams_sent: self.datagrams_sent.load(Ordering::Relaxed),
+            datagrams_received: self.datagrams_received.load(Ordering::Relaxed),
+            duration: ended_at.saturating_duration_since(self.started_at),
+        }
+    }
+
+    /// Counts a datagram of `byte_count` bytes sent in the session.
+    fn on_sent(&self, byte_count: usize) {
+        self.bytes_sent
+            .fetch_add(byte_count as u64, Ordering::Relaxed);
+        self.datagrams_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a datagram of `byte_count` bytes received in the session.
+    fn on_received(&self, byte_count: usize) {
+        self.bytes_received
+            .fetch_add(byte_count as u64, Ordering::Relaxed);
+        self.datagrams_received.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+///
+/// Metered transport type definition.
+///
+/// Wraps the [`Transport`] of the client, counting every datagram sent and received through it on the [`UsageMeter`].
+///
+#[derive(Debug)]
+pub(crate) struct Metered<T> {
+    /// The wrapped transport.
+    inner: T,
+
+    /// The meter the datagrams are counted on.
+    meter: Arc<UsageMeter>,
+}
+
+impl<T> Metered<T> {
+    /// Creates a new [`Metered`] instance.
+    pub(crate) fn new(inner: T, meter: Arc<UsageMeter>) -> Self {
+        Self { inner, meter }
+    }
+}
+
+impl<T: Transport> Transport for Metered<T> {
+    async fn send_datagram(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        let byte_count = self.inner.send_datagram(buf, target).await?;
+
+        self.meter.on_sent(byte_count);
+
+        Ok(byte_count)
+    }
+
+    async fn recv_datagram(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (byte_count, source) = self.inner.recv_datagram(buf).await?;
+
+        self.meter.on_received(byte_count);
+
+        Ok((byte_count, source))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    async fn recv_datagram_ecn(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Ecn)> {
+        let (byte_count, source, ecn) = self.inner.recv_datagram_ecn(buf).await?;
+
+        self.meter.on_received(byte_count);
+
+        Ok((byte_count, source, ecn))
+    }
+
+    fn set_ecn(&self, ecn: Ecn) -> io::Result<()> {
+        self.inner.set_ecn(ecn)
+    }
+
+    fn set_traffic_class(&self, dscp: Dscp) -> io::Result<()> {
+        self.inner.set_traffic_class(dscp)
+    }
+
+    fn set_flow_label(&self, flow_label: u32) -> io::Result<()> {
+        self.inner.set_flow_label(flow_label)
+    }
+
+    fn set_hop_limit(&self, hop_limit: u8) -> io::Result<()> {
+        self.inner.set_hop_limit(hop_limit)
+    }
+
+    fn enable_path_errors(&self) -> io::Result<()> {
+        self.inner.enable_path_errors()
+    }
+
+    fn recv_path_error(&self) -> impl Future<Output = io::Result<PathError>> + Send {
+        self.inner.recv_path_error()
+    }
+}
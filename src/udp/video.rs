@@ -0,0 +1,386 @@
+//!
+//! Provides the video decoding of the [`Client`](super::client::Client).
+//!
+//! GUI clients usually only want to draw the remote video, so the client service can decode the reassembled [`VideoFrame`]s into RGBA images, which can be uploaded to textures directly (for example with egui or wgpu).
+//! The decoders are provided by the user per [`MediaCodec`] (see [`Client::register_video_decoder`](super::client::Client::register_video_decoder)), and a separate decoder is created for every remote author, as video streams are usually stateful.
+//!
+//! The sent video can be encoded by the client as well, with a [`VideoEncoder`] provided by the user (see [`Client::set_video_encoder`](super::client::Client::set_video_encoder)).
+//! The client decides which frames refresh the picture according to the [`VideoEncoderConfig`]: either periodic keyframes, or a rolling intra refresh which spreads the refresh over several frames.
+//! Large keyframes cause bursts which delay the frames behind them on constrained links, while the intra refreshed frames keep the size of the frames even.
+//!
+
+use std::{collections::HashMap, fmt::Debug, sync::Arc, time::Duration};
+
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use super::{decoder::DecoderLimits, event::MediaError};
+use crate::packet::{frame::VideoFrame, MediaCodec};
+
+///
+/// RGBA image type definition.
+///
+/// A decoded image, with 4 bytes (red, green, blue and alpha) per pixel, row by row.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    /// The width of the image in pixels.
+    pub width: u32,
+
+    /// The height of the image in pixels.
+    pub height: u32,
+
+    /// The pixels of the image, its length is `width * height * 4`.
+    pub rgba: Vec<u8>,
+}
+
+///
+/// Video decoder trait definition.
+///
+/// Decodes the [`VideoFrame`]s of a single author into [`RgbaImage`]s.
+///
+pub trait VideoDecoder: Send {
+    /// Decodes the [`VideoFrame`] into an [`RgbaImage`].
+    /// Returns [`None`] if the frame doesn't produce an image (for example while waiting for a keyframe).
+    /// The frames encoded in slices may miss the slices which were lost (see [`VideoFrame::slices`]), the decoders supporting error concealment can still render the rest of the frame.
+    fn decode(&mut self, video_frame: &VideoFrame) -> Result<Option<RgbaImage>, MediaError>;
+}
+
+///
+/// Frame refresh type definition.
+///
+/// Describes the part of a frame a [`VideoEncoder`] has to encode without referencing the previous frames.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameRefresh {
+    /// The frame may reference the previous frames.
+    Predicted,
+
+    /// The whole frame is encoded without referencing the previous frames, so the receivers can start decoding at it.
+    Keyframe,
+
+    /// A single stripe of the frame is encoded without referencing the previous frames, the picture is refreshed once every stripe was.
+    IntraRefresh {
+        /// The stripe refreshed by the frame, from `0` to `stripes - 1`.
+        stripe: u32,
+        /// The amount of stripes the frame is split into, which is the amount of frames a whole refresh spans.
+        stripes: u32,
+    },
+}
+
+///
+/// Intra refresh configuration type definition.
+///
+/// Describes the rolling refresh which replaces the periodic keyframes of a [`VideoEncoderConfig`].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntraRefreshConfig {
+    /// The amount of frames a whole refresh is spread over, each refreshing a stripe of the picture.
+    /// This is capped at the [`VideoEncoderConfig::keyframe_interval`].
+    pub stripes: u32,
+}
+
+impl Default for IntraRefreshConfig {
+    fn default() -> Self {
+        Self { stripes: 10 }
+    }
+}
+
+///
+/// Video encoder configuration type definition.
+///
+/// Describes how often the picture of the sent video is refreshed, so the receivers recover from the lost frames.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoEncoderConfig {
+    /// The amount of frames between the refreshes of the picture (the length of a GOP).
+    /// Only the first frame (and the frames requested with [`Client::request_keyframe`](super::client::Client::request_keyframe)) is a keyframe if this is [`None`].
+    pub keyframe_interval: Option<u32>,
+
+    /// The configuration of the rolling intra refresh, which refreshes the picture instead of the periodic keyframes.
+    /// The requested keyframes are still sent as keyframes. The periodic keyframes are sent if this is [`None`].
+    pub intra_refresh: Option<IntraRefreshConfig>,
+}
+
+impl Default for VideoEncoderConfig {
+    fn default() -> Self {
+        Self {
+            keyframe_interval: Some(120),
+            intra_refresh: None,
+        }
+    }
+}
+
+///
+/// Video encoder trait definition.
+///
+/// Encodes the [`RgbaImage`]s of the sent video.
+///
+pub trait VideoEncoder: Send {
+    /// Encodes the [`RgbaImage`], refreshing the part of the frame described by the [`FrameRefresh`].
+    fn encode(&mut self, image: &RgbaImage, refresh: FrameRefresh) -> Result<Vec<u8>, MediaError>;
+
+    /// Encodes the [`RgbaImage`] like [`VideoEncoder::encode`], into slices (or tiles) which can be decoded independently of each other.
+    /// The encoders supporting slices should override this, so a lost packet only corrupts a part of the frame. By default the frame is a single slice.
+    fn encode_slices(
+        &mut self,
+        image: &RgbaImage,
+        refresh: FrameRefresh,
+    ) -> Result<Vec<Vec<u8>>, MediaError> {
+        Ok(vec![self.encode(image, refresh)?])
+    }
+}
+
+///
+/// Refresh scheduler type definition.
+///
+/// Decides the [`FrameRefresh`] of every sent frame according to a [`VideoEncoderConfig`].
+///
+#[derive(Debug, Clone)]
+pub struct RefreshScheduler {
+    /// The configuration of the refreshes.
+    config: VideoEncoderConfig,
+
+    /// The amount of frames scheduled since the last keyframe.
+    frames_since_keyframe: u64,
+
+    /// Whether the next frame is a keyframe, which is set for the first frame.
+    keyframe_requested: bool,
+}
+
+impl RefreshScheduler {
+    /// Creates a new [`RefreshScheduler`] instance, whose first frame is a keyframe.
+    pub fn new(config: VideoEncoderConfig) -> Self {
+        Self {
+            config,
+            frames_since_keyframe: 0,
+            keyframe_requested: true,
+        }
+    }
+
+    /// Returns the [`VideoEncoderConfig`] of the scheduler.
+    pub fn config(&self) -> &VideoEncoderConfig {
+        &self.config
+    }
+
+    /// Makes the next frame a keyframe, the periodic refreshes are counted from it.
+    pub fn request_keyframe(&mut self) {
+        self.keyframe_requested = true;
+    }
+
+    /// Returns the [`FrameRefresh`] of the next frame.
+    pub fn next_refresh(&mut self) -> FrameRefresh {
+        if std::mem::take(&mut self.keyframe_requested) {
+            self.frames_since_keyframe = 1;
+
+            return FrameRefresh::Keyframe;
+        }
+
+        let position = self.frames_since_keyframe;
+
+        self.frames_since_keyframe += 1;
+
+        let Some(keyframe_interval) = self
+            .config
+            .keyframe_interval
+            .filter(|interval| *interval > 0)
+        else {
+            return FrameRefresh::Predicted;
+        };
+
+        let position = (position % keyframe_interval as u64) as u32;
+
+        match &self.config.intra_refresh {
+            Some(intra_refresh) => {
+                let stripes = intra_refresh.stripes.clamp(1, keyframe_interval);
+
+                if position < stripes {
+                    FrameRefresh::IntraRefresh {
+                        stripe: position,
+                        stripes,
+                    }
+                } else {
+                    FrameRefresh::Predicted
+                }
+            }
+            None if position == 0 => FrameRefresh::Keyframe,
+            None => FrameRefresh::Predicted,
+        }
+    }
+}
+
+/// The video stream a [`Client`](super::client::Client) encodes and sends.
+pub(crate) struct VideoEncoderState {
+    /// The codec the stream is encoded with.
+    pub(crate) codec: MediaCodec,
+
+    /// The encoder of the stream.
+    pub(crate) encoder: Box<dyn VideoEncoder>,
+
+    /// The scheduler of the refreshes of the stream.
+    pub(crate) scheduler: RefreshScheduler,
+}
+
+impl Debug for VideoEncoderState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VideoEncoderState")
+            .field("codec", &self.codec)
+            .field("scheduler", &self.scheduler)
+            .finish()
+    }
+}
+
+/// Creates a new [`VideoDecoder`] for every remote author.
+pub type VideoDecoderFactory = Arc<dyn Fn() -> Box<dyn VideoDecoder> + Send + Sync>;
+
+/// A decoded video frame of a remote author.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedVideoFrame {
+    /// The author of the video frame.
+    pub author: Uuid,
+
+    /// The sequence number of the video frame, if the sender has set one.
+    pub sequence: Option<u32>,
+
+    /// The media time of the video frame since the start of the author's stream, if the sender has set one.
+    pub timestamp: Option<Duration>,
+
+    /// The decoded image of the video frame.
+    pub image: RgbaImage,
+}
+
+///
+/// Video decoder registry type definition.
+///
+/// Holds the [`VideoDecoderFactory`] of every registered [`MediaCodec`], and the [`VideoDecoder`] of every remote author.
+///
+#[derive(Default)]
+pub struct VideoDecoders {
+    /// The factory of the decoders of every registered codec.
+    factories: HashMap<MediaCodec, VideoDecoderFactory>,
+
+    /// The decoder of every author, the codec it was created for, and the time it was last used at.
+    decoders: HashMap<Uuid, (MediaCodec, Box<dyn VideoDecoder>, Instant)>,
+
+    /// The limits deciding when the decoders are torn down, and which new authors are decoded.
+    limits: DecoderLimits,
+}
+
+impl Debug for VideoDecoders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VideoDecoders")
+            .field("codecs", &self.factories.keys().collect::<Vec<_>>())
+            .field("authors", &self.decoders.keys().collect::<Vec<_>>())
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+impl VideoDecoders {
+    /// Creates a new [`VideoDecoders`] instance, without any registered codecs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the [`DecoderLimits`] of the decoders.
+    pub fn with_limits(mut self, limits: DecoderLimits) -> Self {
+        self.limits = limits;
+
+        self
+    }
+
+    /// Returns the authors who are currently decoded.
+    pub fn authors(&self) -> Vec<Uuid> {
+        self.decoders.keys().copied().collect()
+    }
+
+    /// Registers the `factory` of the decoders of the `codec`, replacing the previously registered one.
+    /// The decoders already created for the codec are dropped.
+    pub fn register(&mut self, codec: MediaCodec, factory: VideoDecoderFactory) {
+        self.factories.insert(codec, factory);
+        self.decoders
+            .retain(|_, (decoder_codec, _, _)| *decoder_codec != codec);
+    }
+
+    /// Returns whether a decoder is registered for the `codec`.
+    pub fn is_registered(&self, codec: MediaCodec) -> bool {
+        self.factories.contains_key(&codec)
+    }
+
+    ///
+    /// Decodes the [`VideoFrame`] with the decoder of its author.
+    ///
+    /// # Behavior
+    /// Returns [`None`] if no decoder is registered for the codec of the frame, the author is new and isn't admitted by the [`DecoderLimits`], or the decoder hasn't produced an image.
+    /// The decoder of the author is created the first time the author is seen, and it is recreated if the author switches codecs.
+    ///
+    /// # Error
+    /// Returns the error of the [`VideoDecoder`], or [`MediaError::InvalidImageSize`] if the size of the image doesn't match its resolution.
+    ///
+    pub fn decode(
+        &mut self,
+        video_frame: &VideoFrame,
+    ) -> Result<Option<DecodedVideoFrame>, MediaError> {
+        let Some(factory) = self.factories.get(&video_frame.codec) else {
+            return Ok(None);
+        };
+
+        if !self.decoders.contains_key(&video_frame.author)
+            && !self.limits.admits(video_frame.author, self.decoders.len())
+        {
+            return Ok(None);
+        }
+
+        let (codec, decoder, last_used) = self
+            .decoders
+            .entry(video_frame.author)
+            .or_insert_with(|| (video_frame.codec, factory(), Instant::now()));
+
+        *last_used = Instant::now();
+
+        if *codec != video_frame.codec {
+            *codec = video_frame.codec;
+            *decoder = factory();
+        }
+
+        let Some(image) = decoder.decode(video_frame)? else {
+            return Ok(None);
+        };
+
+        if image.rgba.len() != image.width as usize * image.height as usize * 4 {
+            return Err(MediaError::InvalidImageSize {
+                size: image.rgba.len(),
+                width: image.width,
+                height: image.height,
+            });
+        }
+
+        Ok(Some(DecodedVideoFrame {
+            author: video_frame.author,
+            sequence: video_frame.sequence,
+            timestamp: video_frame.timestamp,
+            image,
+        }))
+    }
+
+    /// Removes the decoder of the `author` (for example when the author has left the session).
+    pub fn remove_author(&mut self, author: Uuid) {
+        self.decoders.remove(&author);
+    }
+
+    /// Removes the decoders which haven't been used for the [`DecoderLimits::idle_timeout`] at `now`, returning the authors who aren't decoded anymore.
+    pub fn remove_idle(&mut self, now: Instant) -> Vec<Uuid> {
+        let idle_authors: Vec<Uuid> = self
+            .decoders
+            .iter()
+            .filter(|(_, (_, _, last_used))| self.limits.is_idle(*last_used, now))
+            .map(|(author, _)| *author)
+            .collect();
+
+        for author in &idle_authors {
+            self.decoders.remove(author);
+        }
+
+        idle_authors
+    }
+}
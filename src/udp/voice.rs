@@ -0,0 +1,743 @@
+//!
+//! Provides the voice codec state of the [`Client`](super::client::Client).
+//!
+//! Opus streams are stateful, so the encoder of the sent voice, and the decoder of every remote author, have to live as long as the session.
+//! The [`Client`](super::client::Client) owns this state, so the users only deal with samples (see [`Client::send_samples`](super::client::Client::send_samples) and [`Client::recv_frames`](super::client::Client::recv_frames)).
+//!
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::Debug,
+    sync::Arc,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use silence_core::opus::opus::{Application, Bitrate, Channels};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use super::{
+    audio_codec::{AudioCodecs, AudioDecoder, AudioEncoder},
+    decoder::{DecoderLimits, SpeakerSelector, SpeakerStats},
+    event::MediaError,
+};
+use crate::packet::{frame::VoiceFrame, HeaderFlags, MediaCodec, Position, VoipHeader};
+
+/// The frame durations (in milliseconds) the Opus encoder supports.
+pub const SUPPORTED_FRAME_DURATIONS_MS: [u32; 5] = [5, 10, 20, 40, 60];
+
+/// The longest duration (in milliseconds) of the frames aggregated into a single voice message, which is the longest Opus packet.
+pub const MAX_AGGREGATED_DURATION_MS: u32 = 120;
+
+/// Custom voice codec errors.
+#[derive(thiserror::Error, Debug)]
+pub enum VoiceError {
+    /// This error is thrown when the configured frame duration isn't one of [`SUPPORTED_FRAME_DURATIONS_MS`].
+    #[error("Unsupported frame duration: {0}ms")]
+    UnsupportedFrameDuration(u32),
+
+    /// This error is thrown when no frames, or frames longer than [`MAX_AGGREGATED_DURATION_MS`] together, are aggregated into a voice message.
+    #[error("Unsupported frame aggregation: {frames_per_packet} frames of {frame_duration_ms}ms")]
+    UnsupportedAggregation {
+        /// The configured amount of frames per voice message.
+        frames_per_packet: u32,
+        /// The configured duration of a single frame.
+        frame_duration_ms: u32,
+    },
+}
+
+/// The audio profile of a [`VoiceConfig`], which decides how the sent audio is treated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AudioProfile {
+    /// Speech, the silent frames are dropped if the [`RoomPolicy`](crate::packet::control::RoomPolicy) of the channel mandates discontinuous transmission.
+    #[default]
+    Voice,
+
+    /// Music, every frame is sent even if discontinuous transmission is mandated, so the quiet passages and the fades aren't cut off.
+    Music,
+}
+
+///
+/// Voice codec configuration type definition.
+///
+/// Describes the samples the [`Client`](super::client::Client) sends and receives, and how they are encoded.
+/// The default configuration is tuned for speech, [`VoiceConfig::music`] is tuned for music streaming.
+///
+#[derive(Debug, Clone)]
+pub struct VoiceConfig {
+    /// The sample rate of the sent and the decoded samples.
+    pub sample_rate: u32,
+
+    /// The channels of the sent and the decoded samples, the samples are interleaved.
+    pub channels: Channels,
+
+    /// The [`Application`] the encoder is optimized for.
+    pub application: Application,
+
+    /// The bitrate of the encoder, this is capped by the [`RoomPolicy`](crate::packet::control::RoomPolicy) of the channel.
+    pub bitrate: Bitrate,
+
+    /// The duration (in milliseconds) of a single encoded frame, this has to be one of [`SUPPORTED_FRAME_DURATIONS_MS`].
+    pub frame_duration_ms: u32,
+
+    /// The amount of frames aggregated into a single voice message, which saves the overhead of their headers on low bitrate links at the cost of latency.
+    /// The frames together can't be longer than [`MAX_AGGREGATED_DURATION_MS`].
+    pub frames_per_packet: u32,
+
+    /// Whether the client service decodes the received Opus voice messages, so that they can be read with [`Client::recv_frames`](super::client::Client::recv_frames).
+    pub decode_received: bool,
+
+    /// The longest gap in the received voice of an author, which is filled with the packet loss concealment of the decoder.
+    /// Longer gaps are only concealed up to this duration, as the author has most likely stopped sending. Concealment is disabled if this is [`Duration::ZERO`].
+    pub max_concealed_duration: Duration,
+
+    /// The bitrate (in bits per second) of the redundant copy of the previous frame, which is attached to every sent voice message (like RFC 2198).
+    /// A single lost message is recovered from the next one without any retransmission latency, for roughly 20-40% more bandwidth at a quarter of the primary bitrate.
+    /// Redundancy is disabled if this is [`None`].
+    pub redundancy_bitrate: Option<i32>,
+
+    /// The [`AudioProfile`] of the sent audio.
+    pub profile: AudioProfile,
+
+    /// The codec the audio is encoded with while every peer of the session can decode it, [`MediaCodec::Opus`] is used otherwise.
+    /// The codec has to be registered in the [`AudioCodecs`] of the [`ClientConfig`](super::client::ClientConfig).
+    pub codec: MediaCodec,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48000,
+            channels: Channels::Stereo,
+            application: Application::Voip,
+            bitrate: Bitrate::Auto,
+            frame_duration_ms: 20,
+            frames_per_packet: 1,
+            decode_received: true,
+            max_concealed_duration: Duration::from_millis(120),
+            redundancy_bitrate: None,
+            profile: AudioProfile::Voice,
+            codec: MediaCodec::Opus,
+        }
+    }
+}
+
+impl VoiceConfig {
+    /// Creates a new [`VoiceConfig`] instance for music streaming: full-band 48kHz stereo, encoded for general audio at 128kbps with the [`AudioProfile::Music`] profile.
+    /// Longer frames (see [`VoiceConfig::frame_duration_ms`]) can be set for better quality at the same bitrate, if the added latency is acceptable.
+    pub fn music() -> Self {
+        Self {
+            sample_rate: 48000,
+            channels: Channels::Stereo,
+            application: Application::Audio,
+            bitrate: Bitrate::Bits(128_000),
+            profile: AudioProfile::Music,
+            ..Default::default()
+        }
+    }
+
+    /// Checks that the configuration can be used by the Opus encoder.
+    pub fn validate(&self) -> Result<(), VoiceError> {
+        if !SUPPORTED_FRAME_DURATIONS_MS.contains(&self.frame_duration_ms) {
+            return Err(VoiceError::UnsupportedFrameDuration(self.frame_duration_ms));
+        }
+
+        if self.frames_per_packet == 0
+            || self
+                .frames_per_packet
+                .saturating_mul(self.frame_duration_ms)
+                > MAX_AGGREGATED_DURATION_MS
+        {
+            return Err(VoiceError::UnsupportedAggregation {
+                frames_per_packet: self.frames_per_packet,
+                frame_duration_ms: self.frame_duration_ms,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the amount of interleaved samples a single encoded frame contains.
+    pub fn samples_per_frame(&self) -> usize {
+        (self.sample_rate * self.frame_duration_ms / 1000) as usize * self.channels as usize
+    }
+
+    /// Returns the duration of the interleaved `sample_count` samples.
+    pub fn duration_of(&self, sample_count: usize) -> Duration {
+        Duration::from_secs_f64(
+            sample_count as f64 / (self.sample_rate * self.channels as u32) as f64,
+        )
+    }
+}
+
+/// A decoded voice frame of a remote author.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedVoiceFrame {
+    /// The author of the voice frame.
+    pub author: Uuid,
+
+    /// The audio stream of the author the voice frame belongs to, `0` being the default stream.
+    pub stream: u8,
+
+    /// The device of the author the voice frame was sent from, `0` being the author's first device.
+    pub device: u32,
+
+    /// The sequence number of the voice frame, if the sender has set one.
+    pub sequence: Option<u32>,
+
+    /// The media time of the voice frame since the start of the author's stream, if the sender has set one.
+    pub timestamp: Option<Duration>,
+
+    /// The duration of the decoded samples.
+    pub duration: Duration,
+
+    /// The [`Position`] of the author, if the author has sent one.
+    pub position: Option<Position>,
+
+    /// The decoded interleaved samples of the voice frame.
+    pub samples: Vec<f32>,
+
+    /// Whether the samples were generated by the packet loss concealment of the decoder, as the voice frame was lost.
+    pub concealed: bool,
+
+    /// Whether the author has ended the stream with this frame, which carries no samples.
+    pub end_of_stream: bool,
+}
+
+/// The state of the voice stream a [`Client`](super::client::Client) sends.
+#[derive(Default)]
+pub(crate) struct VoiceEncoderState {
+    /// The encoder of the stream and the codec it encodes, it is created when the first samples are sent (or the codec changes).
+    pub(crate) encoder: Option<(MediaCodec, Box<dyn AudioEncoder>)>,
+
+    /// The sequence number of the next sent frame.
+    pub(crate) sequence: u32,
+
+    /// The capture time of the first samples of the stream, the timestamps of the frames are measured from it.
+    pub(crate) stream_start: Option<Instant>,
+
+    /// The samples which didn't fill a whole frame yet, they are carried over to the next call.
+    pub(crate) pending_samples: Vec<f32>,
+
+    /// The capture time of the first pending sample.
+    pub(crate) pending_capture: Option<Instant>,
+
+    /// The encoder of the redundant copies, it is created with the encoder of the stream if redundancy is enabled.
+    pub(crate) redundancy_encoder: Option<Box<dyn AudioEncoder>>,
+
+    /// The redundant copy of the last sent frame, which is attached to the next frame.
+    pub(crate) redundant_payload: Option<Vec<u8>>,
+
+    /// The encoded frames which are waiting to be aggregated into a single voice message.
+    pub(crate) aggregated_frames: Vec<VoiceFrame>,
+}
+
+impl Debug for VoiceEncoderState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VoiceEncoderState")
+            .field("codec", &self.encoder.as_ref().map(|(codec, _)| codec))
+            .field("sequence", &self.sequence)
+            .field("stream_start", &self.stream_start)
+            .field("pending_samples", &self.pending_samples.len())
+            .field("aggregated_frames", &self.aggregated_frames.len())
+            .finish()
+    }
+}
+
+/// An additional audio stream a [`Client`](super::client::Client) sends, encoded with its own [`VoiceConfig`].
+#[derive(Debug)]
+pub(crate) struct AudioStream {
+    /// The configuration of the codec of the stream.
+    pub(crate) config: VoiceConfig,
+
+    /// The encoder, and the sequence and timing state of the stream.
+    pub(crate) encoder: VoiceEncoderState,
+}
+
+/// The decoding state of the voice stream of a remote author.
+struct AuthorDecoder {
+    /// The codec the stream is decoded with.
+    codec: MediaCodec,
+
+    /// The decoder of the stream.
+    decoder: Box<dyn AudioDecoder>,
+
+    /// The highest sequence number received from the author.
+    highest_sequence: Option<u32>,
+
+    /// The amount of samples (per channel) of the last decoded frame, the lost frames are concealed with this duration.
+    last_frame_size: usize,
+
+    /// The time the decoder was last used at.
+    last_used: Instant,
+}
+
+///
+/// Voice decoder registry type definition.
+///
+/// Holds a decoder for every audio stream of every device of every remote author, as every stream is a separate stateful stream.
+/// The decoders are created with the [`AudioCodecs`] registered for the codec of the stream.
+///
+pub struct VoiceDecoders {
+    /// The sample rate the messages are decoded at.
+    sample_rate: u32,
+
+    /// The channels the messages are decoded with.
+    channels: Channels,
+
+    /// The longest gap which is concealed.
+    max_concealed_duration: Duration,
+
+    /// The decoder of every audio stream of every author, by the author, its device and the stream.
+    decoders: HashMap<(Uuid, u32, u8), AuthorDecoder>,
+
+    /// The limits deciding when the decoders are torn down, and which new authors are decoded.
+    limits: DecoderLimits,
+
+    /// The selection of the loudest speakers, if [`DecoderLimits::max_speakers`] is set.
+    speakers: SpeakerSelector,
+
+    /// The codecs the decoders are created with.
+    codecs: AudioCodecs,
+}
+
+impl Debug for VoiceDecoders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VoiceDecoders")
+            .field("sample_rate", &self.sample_rate)
+            .field("channels", &self.channels)
+            .field("authors", &self.authors())
+            .field("limits", &self.limits)
+            .field("codecs", &self.codecs)
+            .finish()
+    }
+}
+
+impl VoiceDecoders {
+    /// Creates a new [`VoiceDecoders`] instance, decoding the messages with the sample rate and the channels of the [`VoiceConfig`].
+    pub fn new(config: &VoiceConfig) -> Self {
+        Self {
+            sample_rate: config.sample_rate,
+            channels: config.channels,
+            max_concealed_duration: config.max_concealed_duration,
+            decoders: HashMap::new(),
+            limits: DecoderLimits::default(),
+            speakers: SpeakerSelector::default(),
+            codecs: AudioCodecs::default(),
+        }
+    }
+
+    /// Sets the [`AudioCodecs`] the decoders are created with, only [`Opus`](super::audio_codec::Opus) is decoded by default.
+    pub fn with_codecs(mut self, codecs: AudioCodecs) -> Self {
+        self.codecs = codecs;
+
+        self
+    }
+
+    /// Returns whether the messages encoded with the `codec` can be decoded.
+    pub fn is_registered(&self, codec: MediaCodec) -> bool {
+        self.codecs.is_registered(codec)
+    }
+
+    /// Records the [`SpeakerStats`] of every speaker heard into the shared `speaker_stats`.
+    pub fn with_speaker_stats(
+        mut self,
+        speaker_stats: Arc<Mutex<HashMap<Uuid, SpeakerStats>>>,
+    ) -> Self {
+        self.speakers = SpeakerSelector::new(speaker_stats);
+
+        self
+    }
+
+    /// Sets the [`DecoderLimits`] of the decoders.
+    pub fn with_limits(mut self, limits: DecoderLimits) -> Self {
+        self.limits = limits;
+
+        self
+    }
+
+    /// Returns the authors who are currently decoded.
+    pub fn authors(&self) -> Vec<Uuid> {
+        let mut authors: Vec<Uuid> = self.decoders.keys().map(|(author, ..)| *author).collect();
+
+        authors.sort();
+        authors.dedup();
+
+        authors
+    }
+
+    /// Returns whether any stream of the `author` is currently decoded.
+    fn is_decoded(&self, author: Uuid) -> bool {
+        self.decoders
+            .keys()
+            .any(|(stream_author, ..)| *stream_author == author)
+    }
+
+    /// Returns the decoder of the `stream` of the `author`'s `device`, creating it the first time the stream is heard.
+    /// The decoder is recreated if the author switches to another `codec`, which keeps the sequence numbers of the stream.
+    fn author_decoder(
+        &mut self,
+        author: Uuid,
+        device: u32,
+        stream: u8,
+        codec: MediaCodec,
+    ) -> Result<&mut AuthorDecoder, MediaError> {
+        let (sample_rate, channels) = (self.sample_rate, self.channels);
+        let create_decoder = || {
+            self.codecs
+                .get(codec)
+                .ok_or(MediaError::UnregisteredCodec(codec))?
+                .decoder(sample_rate, channels)
+        };
+
+        let author_decoder = match self.decoders.entry((author, device, stream)) {
+            Entry::Occupied(entry) => {
+                let author_decoder = entry.into_mut();
+
+                if author_decoder.codec != codec {
+                    author_decoder.decoder = create_decoder()?;
+                    author_decoder.codec = codec;
+                    author_decoder.last_frame_size = 0;
+                }
+
+                author_decoder
+            }
+            Entry::Vacant(entry) => entry.insert(AuthorDecoder {
+                codec,
+                decoder: create_decoder()?,
+                highest_sequence: None,
+                last_frame_size: 0,
+                last_used: Instant::now(),
+            }),
+        };
+
+        author_decoder.last_used = Instant::now();
+
+        Ok(author_decoder)
+    }
+
+    /// Decodes the Opus `packet` of the `author`'s default stream into interleaved samples.
+    /// The decoder of the author is created the first time the author is heard.
+    pub fn decode(&mut self, author: Uuid, packet: &[u8]) -> Result<Vec<f32>, MediaError> {
+        self.decode_stream(author, 0, packet)
+    }
+
+    /// Decodes the Opus `packet` of the `author`'s `stream` into interleaved samples, like [`VoiceDecoders::decode`].
+    pub fn decode_stream(
+        &mut self,
+        author: Uuid,
+        stream: u8,
+        packet: &[u8],
+    ) -> Result<Vec<f32>, MediaError> {
+        self.decode_with(author, 0, stream, MediaCodec::Opus, packet)
+    }
+
+    /// Decodes the `packet` of the `stream` of the `author`'s `device` encoded with the `codec` into interleaved samples.
+    fn decode_with(
+        &mut self,
+        author: Uuid,
+        device: u32,
+        stream: u8,
+        codec: MediaCodec,
+        packet: &[u8],
+    ) -> Result<Vec<f32>, MediaError> {
+        let channels = self.channels as usize;
+        let author_decoder = self.author_decoder(author, device, stream, codec)?;
+
+        let samples = author_decoder.decoder.decode(packet)?;
+
+        author_decoder.last_frame_size = samples.len() / channels;
+
+        Ok(samples)
+    }
+
+    ///
+    /// Conceals the frames of the `author`'s default stream lost before the frame with the `sequence` number, and records the sequence number.
+    ///
+    /// # Behavior
+    /// Returns the sequence numbers and the interleaved samples of the concealed frames, in order.
+    /// The decoder is invoked in packet loss concealment mode for every missing sequence number, with the duration of the last decoded frame.
+    /// Nothing is concealed for the first frame of the author, for frames arriving out of order, or if concealment is disabled in the [`VoiceConfig`].
+    /// Gaps longer than [`VoiceConfig::max_concealed_duration`] are only concealed up to that duration.
+    /// This has to be called before the frame is [decoded](VoiceDecoders::decode), so that the concealed samples precede it.
+    ///
+    pub fn conceal(
+        &mut self,
+        author: Uuid,
+        sequence: u32,
+    ) -> Result<Vec<(u32, Vec<f32>)>, MediaError> {
+        self.conceal_until(author, 0, 0, MediaCodec::Opus, sequence, false)
+    }
+
+    /// Conceals the lost frames of the `stream` of the `author`'s `device` encoded with the `codec` like [`VoiceDecoders::conceal`], except for the frame right before the `sequence` number if it is `recoverable` from a redundant copy.
+    fn conceal_until(
+        &mut self,
+        author: Uuid,
+        device: u32,
+        stream: u8,
+        codec: MediaCodec,
+        sequence: u32,
+        recoverable: bool,
+    ) -> Result<Vec<(u32, Vec<f32>)>, MediaError> {
+        let channels = self.channels as usize;
+        let sample_rate = self.sample_rate;
+        let max_concealed_duration = self.max_concealed_duration;
+        let author_decoder = self.author_decoder(author, device, stream, codec)?;
+
+        let Some(highest_sequence) = author_decoder.highest_sequence else {
+            author_decoder.highest_sequence = Some(sequence);
+
+            return Ok(vec![]);
+        };
+
+        //The sequence numbers wrap around, so compare them by their distance
+        let distance = sequence.wrapping_sub(highest_sequence) as i32;
+
+        if distance <= 0 {
+            return Ok(vec![]);
+        }
+
+        author_decoder.highest_sequence = Some(sequence);
+
+        let frame_size = author_decoder.last_frame_size;
+
+        if frame_size == 0 {
+            return Ok(vec![]);
+        }
+
+        let max_concealed_frames =
+            (max_concealed_duration.as_secs_f64() * sample_rate as f64) as usize / frame_size;
+        let lost_frames = distance as usize - 1;
+        let concealed_frames = lost_frames
+            .saturating_sub(recoverable as usize)
+            .min(max_concealed_frames);
+
+        (1..=concealed_frames as u32)
+            .map(|offset| {
+                let samples = author_decoder.decoder.conceal(frame_size * channels)?;
+
+                Ok((highest_sequence.wrapping_add(offset), samples))
+            })
+            .collect()
+    }
+
+    ///
+    /// Decodes the voice message of the [`VoipHeader`] into [`DecodedVoiceFrame`]s, with the decoder of its codec.
+    ///
+    /// # Behavior
+    /// Every audio stream of every device of the author is decoded by its own decoder, which is recreated if the author switches codecs.
+    /// If the message has a sequence number, the frames lost before it are [concealed](VoiceDecoders::conceal) first, and returned before the decoded frame.
+    /// If the frame right before the message was lost, and the message carries a redundant copy of it, the copy is decoded instead of concealing the frame.
+    /// The concealed and the recovered frames are timestamped backwards from the timestamp of the message.
+    /// The frames aggregated into the message are returned one by one, with consecutive sequence numbers and timestamps.
+    /// Nothing is decoded for a new author who isn't admitted by the [`DecoderLimits`], or a speaker who isn't among the loudest [`DecoderLimits::max_speakers`] speakers.
+    /// The decoders of a speaker are removed once a louder speaker replaces them.
+    /// If the message is marked with [`HeaderFlags::END_OF_STREAM`], the decoder of the stream is removed, and a single empty frame ending the stream is returned (if the stream was decoded).
+    ///
+    pub fn decode_message(
+        &mut self,
+        voip_header: &VoipHeader,
+        voip_body: &[u8],
+    ) -> Result<Vec<DecodedVoiceFrame>, MediaError> {
+        let author = voip_header.author();
+        let stream = voip_header.stream().unwrap_or_default();
+        let device = voip_header.device().unwrap_or_default();
+        let codec = voip_header.codec();
+
+        if voip_header.flags().contains(HeaderFlags::END_OF_STREAM) {
+            return Ok(self
+                .decoders
+                .remove(&(author, device, stream))
+                .map(|_| DecodedVoiceFrame {
+                    author,
+                    stream,
+                    device,
+                    sequence: voip_header.sequence(),
+                    timestamp: voip_header.timestamp().map(Duration::from_micros),
+                    duration: Duration::ZERO,
+                    position: voip_header.position(),
+                    samples: vec![],
+                    concealed: false,
+                    end_of_stream: true,
+                })
+                .into_iter()
+                .collect());
+        }
+
+        let (is_selected, replaced_speaker) = self.speakers.observe(
+            author,
+            voip_header.audio_level(),
+            Instant::now(),
+            self.limits.max_speakers,
+        );
+
+        if let Some(replaced_speaker) = replaced_speaker {
+            self.remove_decoders(replaced_speaker);
+        }
+
+        if !is_selected
+            || (!self.is_decoded(author) && !self.limits.admits(author, self.authors().len()))
+        {
+            return Ok(vec![]);
+        }
+
+        let sequence = voip_header.sequence();
+        let timestamp = voip_header.timestamp().map(Duration::from_micros);
+        let (voip_body, redundant_body) = voip_header.split_redundancy(voip_body);
+
+        //The previous frame can only be recovered if it was lost, and the message carries a copy of it
+        let highest_sequence = self
+            .decoders
+            .get(&(author, device, stream))
+            .and_then(|author_decoder| author_decoder.highest_sequence);
+
+        let recovered_sequence = match (sequence, highest_sequence, redundant_body) {
+            (Some(sequence), Some(highest_sequence), Some(_))
+                if sequence.wrapping_sub(highest_sequence) as i32 >= 2 =>
+            {
+                Some(sequence.wrapping_sub(1))
+            }
+            _ => None,
+        };
+
+        let concealed_frames = match sequence {
+            Some(sequence) => self.conceal_until(
+                author,
+                device,
+                stream,
+                codec,
+                sequence,
+                recovered_sequence.is_some(),
+            )?,
+            None => vec![],
+        };
+
+        let recovered_samples = match (recovered_sequence, redundant_body) {
+            (Some(_), Some(redundant_body)) => {
+                Some(self.decode_with(author, device, stream, codec, redundant_body)?)
+            }
+            _ => None,
+        };
+
+        let mut decoded_frames: Vec<DecodedVoiceFrame> = concealed_frames
+            .into_iter()
+            .map(|(concealed_sequence, samples)| {
+                let concealed_duration = self.duration_of(samples.len());
+                let frames_before = sequence
+                    .unwrap_or_default()
+                    .wrapping_sub(concealed_sequence);
+
+                DecodedVoiceFrame {
+                    author,
+                    stream,
+                    device,
+                    sequence: Some(concealed_sequence),
+                    timestamp: timestamp.and_then(|timestamp| {
+                        timestamp.checked_sub(concealed_duration * frames_before)
+                    }),
+                    duration: concealed_duration,
+                    position: voip_header.position(),
+                    samples,
+                    concealed: true,
+                    end_of_stream: false,
+                }
+            })
+            .collect();
+
+        if let (Some(recovered_sequence), Some(recovered_samples)) =
+            (recovered_sequence, recovered_samples)
+        {
+            let recovered_duration = self.duration_of(recovered_samples.len());
+
+            decoded_frames.push(DecodedVoiceFrame {
+                author,
+                stream,
+                device,
+                sequence: Some(recovered_sequence),
+                timestamp: timestamp
+                    .and_then(|timestamp| timestamp.checked_sub(recovered_duration)),
+                duration: recovered_duration,
+                position: voip_header.position(),
+                samples: recovered_samples,
+                concealed: false,
+                end_of_stream: false,
+            });
+        }
+
+        let frame_bodies = voip_header.split_frames(voip_body);
+        let frame_count = frame_bodies.len() as u32;
+        let mut frame_offset = Duration::ZERO;
+
+        for (index, frame_body) in frame_bodies.into_iter().enumerate() {
+            let samples = self.decode_with(author, device, stream, codec, frame_body)?;
+            let duration = self.duration_of(samples.len());
+
+            decoded_frames.push(DecodedVoiceFrame {
+                author,
+                stream,
+                device,
+                sequence: sequence.map(|sequence| sequence.wrapping_add(index as u32)),
+                timestamp: timestamp.map(|timestamp| timestamp + frame_offset),
+                duration,
+                position: voip_header.position(),
+                samples,
+                concealed: false,
+                end_of_stream: false,
+            });
+
+            frame_offset += duration;
+        }
+
+        //Record the sequence number of the last aggregated frame, so it isn't concealed with the next message
+        if let (Some(sequence), Some(author_decoder)) =
+            (sequence, self.decoders.get_mut(&(author, device, stream)))
+        {
+            let last_sequence = sequence.wrapping_add(frame_count - 1);
+
+            if author_decoder
+                .highest_sequence
+                .is_none_or(|highest_sequence| {
+                    last_sequence.wrapping_sub(highest_sequence) as i32 > 0
+                })
+            {
+                author_decoder.highest_sequence = Some(last_sequence);
+            }
+        }
+
+        Ok(decoded_frames)
+    }
+
+    /// Returns the duration of the interleaved `sample_count` samples.
+    fn duration_of(&self, sample_count: usize) -> Duration {
+        Duration::from_secs_f64(
+            sample_count as f64 / (self.sample_rate * self.channels as u32) as f64,
+        )
+    }
+
+    /// Removes the decoders of every stream of the `author` (for example when the author has left the session).
+    pub fn remove_author(&mut self, author: Uuid) {
+        self.remove_decoders(author);
+        self.speakers.remove(author);
+    }
+
+    /// Removes the decoders of every stream of the `author`.
+    fn remove_decoders(&mut self, author: Uuid) {
+        self.decoders
+            .retain(|(stream_author, ..), _| *stream_author != author);
+    }
+
+    /// Removes the decoders which haven't been used for the [`DecoderLimits::idle_timeout`] at `now`, returning the authors who aren't decoded anymore.
+    pub fn remove_idle(&mut self, now: Instant) -> Vec<Uuid> {
+        let authors = self.authors();
+        let limits = &self.limits;
+
+        self.decoders
+            .retain(|_, author_decoder| !limits.is_idle(author_decoder.last_used, now));
+
+        authors
+            .into_iter()
+            .filter(|author| !self.is_decoded(*author))
+            .collect()
+    }
+}